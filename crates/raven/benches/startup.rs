@@ -71,6 +71,19 @@ fn make_r_parser() -> Parser {
     parser
 }
 
+/// Converts a byte offset into `text` into a tree-sitter `Point` (row plus a
+/// *byte* column within that row), for constructing `InputEdit`s in the
+/// incremental-reparse benchmark below.
+fn byte_offset_to_point(text: &str, byte_offset: usize) -> tree_sitter::Point {
+    let prefix = &text[..byte_offset];
+    let row = prefix.matches('\n').count();
+    let column = match prefix.rfind('\n') {
+        Some(idx) => byte_offset - idx - 1,
+        None => byte_offset,
+    };
+    tree_sitter::Point { row, column }
+}
+
 // ---------------------------------------------------------------------------
 // Benchmark: metadata extraction using real cross-file analysis
 // Requirements: 1.1
@@ -120,6 +133,34 @@ data <- data.frame(x = 1:100, y = rnorm(100))
         })
     });
 
+    // Large code with no directives and no source()/library() calls at all,
+    // so it should hit the marker-free fast path. Guards against the fast
+    // path regressing back to a full per-line/AST scan.
+    let no_markers_unit = r#"
+my_function <- function(x) {
+    y <- x + 1
+    return(y)
+}
+
+another_func <- function(a, b, c) {
+    result <- a * b + c
+    if (is.null(result)) {
+        return(NA)
+    }
+    result
+}
+
+data <- data.frame(x = 1:100, y = rnorm(100))
+"#;
+    let large_code_no_markers = no_markers_unit.repeat(50);
+    group.bench_function("extract_metadata_large_no_markers", |b| {
+        b.iter(|| {
+            black_box(raven::cross_file::extract_metadata(black_box(
+                &large_code_no_markers,
+            )))
+        })
+    });
+
     group.finish();
 }
 
@@ -316,6 +357,37 @@ fn bench_tree_sitter_parsing(c: &mut Criterion) {
         })
     });
 
+    // Small single-character edit near the start of `large_code`, reparsed
+    // with the previous tree via `tree.edit` + `parser.parse(text, Some(&old_tree))`.
+    // Demonstrates the speedup incremental reparsing gives editors over a full
+    // from-scratch parse on every keystroke.
+    let old_tree = parser.parse(&large_code, None).expect("parse failed");
+    let edit_byte = large_code.find("func_0_0").expect("marker not found") + "func_0_0".len();
+    let edit_point = byte_offset_to_point(&large_code, edit_byte);
+    let mut edited_code = large_code.clone();
+    edited_code.insert_str(edit_byte, "_x");
+
+    group.bench_function("parse_incremental_small_edit", |b| {
+        b.iter(|| {
+            let mut tree = old_tree.clone();
+            tree.edit(&tree_sitter::InputEdit {
+                start_byte: edit_byte,
+                old_end_byte: edit_byte,
+                new_end_byte: edit_byte + 2,
+                start_position: edit_point,
+                old_end_position: edit_point,
+                new_end_position: tree_sitter::Point {
+                    row: edit_point.row,
+                    column: edit_point.column + 2,
+                },
+            });
+            let reparsed = parser
+                .parse(black_box(&edited_code), Some(&tree))
+                .expect("parse failed");
+            black_box(reparsed)
+        })
+    });
+
     group.finish();
 }
 
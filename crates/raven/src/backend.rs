@@ -5,9 +5,11 @@
 // Modifications copyright (C) 2026 Jonathan Marc Bearak
 //
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
@@ -67,6 +69,38 @@ struct ActiveDocumentsChangedParams {
     timestamp_ms: u64,
 }
 
+/// Parameters for the raven/packageSource request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageSourceParams {
+    uri: String,
+}
+
+/// Parameters for the outbound raven/diagnosticsBatch notification (see
+/// [`Backend::publish_diagnostics_batch`]).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiagnosticsBatchParams {
+    /// Monotonic index of this batch, bumped every time a batch is scheduled.
+    batch_index: u64,
+    /// Number of files whose diagnostics were (re)published in this batch.
+    files_len: usize,
+}
+
+/// Marker type for the outbound `raven/diagnosticsBatch` notification. Mirrors
+/// Deno's `DiagnosticBatchNotificationParams`: the diagnostics themselves
+/// still travel over the standard per-file `publishDiagnostics`, but this is
+/// sent once after a debounced wave of them has fully flushed, so a client
+/// revalidating many files at once (e.g. on workspace open or a bulk
+/// `didChangeConfiguration`) can tell when a wave is done instead of
+/// inferring it from raw notification traffic.
+enum DiagnosticsBatchNotification {}
+
+impl tower_lsp::lsp_types::notification::Notification for DiagnosticsBatchNotification {
+    type Params = DiagnosticsBatchParams;
+    const METHOD: &'static str = "raven/diagnosticsBatch";
+}
+
 /// Parse cross-file configuration from LSP settings.
 ///
 /// Reads the top-level `crossFile`, `diagnostics`, and `packages` sections from a
@@ -77,7 +111,10 @@ struct ActiveDocumentsChangedParams {
 /// Supported top-level keys read:
 /// - `crossFile`: core cross-file behavior and diagnostic severities.
 /// - `diagnostics.enabled` and `diagnostics.undefinedVariables`: diagnostics master switch and undefined variable diagnostics.
-/// - `packages`: package-related settings (`enabled`, `additionalLibraryPaths`, `rPath`, `missingPackageSeverity`).
+/// - `diagnostics.undefinedVariablesNseBlanketSkip` and `diagnostics.undefinedVariablesNseAllowlist`: tune which
+///   call arguments are treated as non-standard evaluation and skipped by the undefined variable check.
+/// - `packages`: package-related settings (`enabled`, `additionalLibraryPaths`, `rPath`, `missingPackageSeverity`,
+///   `unloadedNamespaceSeverity`).
 ///
 /// # Returns
 ///
@@ -115,7 +152,7 @@ struct ActiveDocumentsChangedParams {
 pub(crate) fn parse_cross_file_config(
     settings: &serde_json::Value,
 ) -> Option<crate::cross_file::CrossFileConfig> {
-    use crate::cross_file::{CallSiteDefault, CrossFileConfig};
+    use crate::cross_file::{CallSiteDefault, CrossFileConfig, DiagnosticSeverityConfig};
 
     // crossFile section is optional - we can still parse diagnostics and packages without it
     let cross_file = settings.get("crossFile");
@@ -160,6 +197,12 @@ pub(crate) fn parse_cross_file_config(
         {
             config.revalidation_debounce_ms = v;
         }
+        if let Some(v) = cross_file
+            .get("diagnosticsDebounceMs")
+            .and_then(|v| v.as_u64())
+        {
+            config.diagnostics_debounce_ms = v;
+        }
 
         // Parse diagnostic severities
         if let Some(sev) = cross_file
@@ -200,6 +243,15 @@ pub(crate) fn parse_cross_file_config(
         {
             config.redundant_directive_severity = parse_severity(sev);
         }
+        // Parse unsourced-file severity. Off unless explicitly set, since
+        // plenty of workspaces have standalone entry scripts that are never
+        // themselves source()d.
+        if let Some(sev) = cross_file
+            .get("unsourcedFileSeverity")
+            .and_then(|v| v.as_str())
+        {
+            config.unsourced_file_severity = parse_severity(sev);
+        }
 
         // Parse on-demand indexing settings
         if let Some(on_demand) = cross_file.get("onDemandIndexing") {
@@ -244,6 +296,35 @@ pub(crate) fn parse_cross_file_config(
         if let Some(v) = diag.get("undefinedVariables").and_then(|v| v.as_bool()) {
             config.undefined_variables_enabled = v;
         }
+        // Parse diagnostics.undefinedVariablesNseBlanketSkip
+        if let Some(v) = diag
+            .get("undefinedVariablesNseBlanketSkip")
+            .and_then(|v| v.as_bool())
+        {
+            config.undefined_variables_nse_blanket_skip = v;
+        }
+        // Parse diagnostics.undefinedVariablesNseAllowlist
+        if let Some(allowlist) = diag
+            .get("undefinedVariablesNseAllowlist")
+            .and_then(|v| v.as_array())
+        {
+            config.undefined_variables_nse_allowlist = allowlist
+                .iter()
+                .filter_map(|p| p.as_str())
+                .map(|s| s.to_string())
+                .collect();
+        }
+        // Parse diagnostics.severityOverrides: a map from a diagnostic's
+        // stable `raven::...` code to "error"/"warning"/"information"/"hint"/"off",
+        // letting users remap or disable any diagnostic this server emits
+        // rather than only the handful with a dedicated severity setting above.
+        if let Some(overrides) = diag.get("severityOverrides").and_then(|v| v.as_object()) {
+            let raw: HashMap<String, String> = overrides
+                .iter()
+                .filter_map(|(code, v)| Some((code.clone(), v.as_str()?.to_string())))
+                .collect();
+            config.diagnostic_severity_overrides = DiagnosticSeverityConfig::from_map(&raw);
+        }
     }
 
     // Parse package settings (Requirement 12, Task 14.2)
@@ -273,6 +354,22 @@ pub(crate) fn parse_cross_file_config(
         {
             config.packages_missing_package_severity = parse_severity(sev);
         }
+        if let Some(allowlist) = packages
+            .get("sideEffectAllowlist")
+            .and_then(|v| v.as_array())
+        {
+            config.packages_side_effect_allowlist = allowlist
+                .iter()
+                .filter_map(|p| p.as_str())
+                .map(|s| s.to_string())
+                .collect();
+        }
+        if let Some(sev) = packages
+            .get("unloadedNamespaceSeverity")
+            .and_then(|v| v.as_str())
+        {
+            config.packages_unloaded_namespace_severity = parse_severity(sev);
+        }
     }
 
 
@@ -290,10 +387,22 @@ pub(crate) fn parse_cross_file_config(
         "  revalidation_debounce_ms: {}",
         config.revalidation_debounce_ms
     );
+    log::info!(
+        "  diagnostics_debounce_ms: {}",
+        config.diagnostics_debounce_ms
+    );
     log::info!(
         "  undefined_variables_enabled: {}",
         config.undefined_variables_enabled
     );
+    log::info!(
+        "  undefined_variables_nse_blanket_skip: {}",
+        config.undefined_variables_nse_blanket_skip
+    );
+    log::info!(
+        "  undefined_variables_nse_allowlist: {:?}",
+        config.undefined_variables_nse_allowlist
+    );
     log::info!("  diagnostics_enabled: {}", config.diagnostics_enabled);
     log::info!("  On-demand indexing:");
     log::info!("    enabled: {}", config.on_demand_indexing_enabled);
@@ -321,6 +430,7 @@ pub(crate) fn parse_cross_file_config(
         "    redundant_directive: {:?}",
         config.redundant_directive_severity
     );
+    log::info!("    unsourced_file: {:?}", config.unsourced_file_severity);
     log::info!("  Package settings:");
     log::info!("    enabled: {}", config.packages_enabled);
     log::info!(
@@ -332,6 +442,10 @@ pub(crate) fn parse_cross_file_config(
         "    missing_package_severity: {:?}",
         config.packages_missing_package_severity
     );
+    log::info!(
+        "    side_effect_allowlist: {:?}",
+        config.packages_side_effect_allowlist
+    );
     log::info!("  Cache settings (LRU):");
     log::info!(
         "    metadata_max_entries: {}",
@@ -368,6 +482,7 @@ pub(crate) fn parse_indentation_config(
         config.style = match style_str.to_lowercase().as_str() {
             "rstudio" => crate::indentation::IndentationStyle::RStudio,
             "rstudio-minus" => crate::indentation::IndentationStyle::RStudioMinus,
+            "align-to-pipe" => crate::indentation::IndentationStyle::AlignToPipe,
             "off" => crate::indentation::IndentationStyle::Off,
             _ => {
                 log::warn!(
@@ -510,6 +625,11 @@ pub struct Backend {
     client: Client,
     state: Arc<RwLock<WorldState>>,
     background_indexer: Arc<crate::cross_file::BackgroundIndexer>,
+    /// Generation counter for [`Backend::publish_diagnostics_batch`]: each
+    /// call bumps it and captures the new value, so a batch scheduled while
+    /// an older one is still debouncing can tell the older one was
+    /// superseded and should skip its publish.
+    diagnostics_batch_generation: Arc<AtomicU64>,
 }
 
 impl Backend {
@@ -575,8 +695,79 @@ impl Backend {
             client,
             state,
             background_indexer,
+            diagnostics_batch_generation: Arc::new(AtomicU64::new(0)),
         }
     }
+
+    /// Schedule a debounced, cancellable wave of diagnostics for `uris`.
+    ///
+    /// Unlike the per-edit revalidation fanout in `did_open`/`did_change`
+    /// (which debounces and cancels per URI via `cross_file_revalidation`),
+    /// this debounces the *batch as a whole*: a single generation counter is
+    /// bumped per call, so if a newer batch is scheduled (e.g. a second
+    /// `didChangeConfiguration` arrives) before this one's debounce window
+    /// elapses, this older batch notices the generation moved on and skips
+    /// its publish entirely rather than racing it. Used by call sites that
+    /// revalidate many open documents at once (config changes, bulk
+    /// file-watcher events) so opening a large workspace doesn't stall the
+    /// request-handling loop awaiting one `publishDiagnostics` per file in
+    /// sequence.
+    ///
+    /// Per-file publishes within a surviving batch still run concurrently,
+    /// and once they've all flushed a single `raven/diagnosticsBatch`
+    /// notification is sent summarizing the wave (see
+    /// [`DiagnosticsBatchNotification`]) - the diagnostics themselves still
+    /// go out over the standard `publishDiagnostics` per file.
+    ///
+    /// Schedules the debounce, fan-out, and batch notification on a spawned
+    /// task and returns immediately, same as the per-file debounce loops in
+    /// `did_open`/`did_change`.
+    async fn publish_diagnostics_batch(&self, uris: Vec<Url>) {
+        if uris.is_empty() {
+            return;
+        }
+
+        let generation = self.diagnostics_batch_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let generation_counter = self.diagnostics_batch_generation.clone();
+        let debounce_ms = {
+            let state = self.state.read().await;
+            state.cross_file_config.diagnostics_debounce_ms
+        };
+        let backend_state = self.state.clone();
+        let client = self.client.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(debounce_ms)).await;
+            if generation_counter.load(Ordering::SeqCst) != generation {
+                log::trace!("Diagnostics batch {} superseded before it ran", generation);
+                return;
+            }
+
+            let files_len = uris.len();
+            let handles: Vec<_> = uris
+                .into_iter()
+                .map(|uri| {
+                    let backend_state = backend_state.clone();
+                    let client = client.clone();
+                    tokio::spawn(async move {
+                        Backend::publish_diagnostics_for(&backend_state, &client, &uri).await;
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+
+            if generation_counter.load(Ordering::SeqCst) == generation {
+                client
+                    .send_notification::<DiagnosticsBatchNotification>(DiagnosticsBatchParams {
+                        batch_index: generation,
+                        files_len,
+                    })
+                    .await;
+            }
+        });
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -657,6 +848,82 @@ impl LanguageServer for Backend {
             hierarchical_support
         );
 
+        // Detect client capability for LocationLink responses to goto-definition.
+        // Path: params.capabilities.text_document.definition.link_support
+        let definition_link_support = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|td| td.definition.as_ref())
+            .and_then(|def| def.link_support)
+            .unwrap_or(false);
+
+        state.definition_link_support = definition_link_support;
+        log::info!(
+            "Client textDocument.definition.linkSupport: {}",
+            definition_link_support
+        );
+
+        // Detect (non-standard) client support for command: links in hover Markdown.
+        // Path: params.capabilities.experimental.commandLinks
+        let command_links_support = params
+            .capabilities
+            .experimental
+            .as_ref()
+            .and_then(|experimental| experimental.get("commandLinks"))
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+
+        state.hover_config.supports_command_links = command_links_support;
+        log::info!("Client experimental.commandLinks: {}", command_links_support);
+
+        // Parse hover configuration (link_unresolved_refs_to_help, documentation,
+        // crossFile, helpFallback, signatureOnly, markup)
+        if let Some(ref init_options) = params.initialization_options {
+            if let Some(hover_options) = init_options.get("hover") {
+                if let Some(link_unresolved_refs_to_help) = hover_options
+                    .get("linkUnresolvedRefsToHelp")
+                    .and_then(|v| v.as_bool())
+                {
+                    state.hover_config.link_unresolved_refs_to_help = link_unresolved_refs_to_help;
+                }
+                if let Some(documentation) =
+                    hover_options.get("documentation").and_then(|v| v.as_bool())
+                {
+                    state.hover_config.documentation = documentation;
+                }
+                if let Some(cross_file) = hover_options.get("crossFile").and_then(|v| v.as_bool()) {
+                    state.hover_config.cross_file = cross_file;
+                }
+                if let Some(help_fallback) =
+                    hover_options.get("helpFallback").and_then(|v| v.as_bool())
+                {
+                    state.hover_config.help_fallback = help_fallback;
+                }
+                if let Some(signature_only) =
+                    hover_options.get("signatureOnly").and_then(|v| v.as_bool())
+                {
+                    state.hover_config.signature_only = signature_only;
+                }
+                if let Some(markup) = hover_options.get("markup").and_then(|v| v.as_str()) {
+                    if markup.eq_ignore_ascii_case("plaintext") {
+                        state.hover_config.markup = MarkupKind::PlainText;
+                    } else if markup.eq_ignore_ascii_case("markdown") {
+                        state.hover_config.markup = MarkupKind::Markdown;
+                    }
+                }
+            }
+        }
+        log::info!(
+            "    hover_config: documentation={} cross_file={} help_fallback={} signature_only={} markup={:?} link_unresolved_refs_to_help={}",
+            state.hover_config.documentation,
+            state.hover_config.cross_file,
+            state.hover_config.help_fallback,
+            state.hover_config.signature_only,
+            state.hover_config.markup,
+            state.hover_config.link_unresolved_refs_to_help
+        );
+
         // Extract completion settings before dropping state lock
         let trigger_on_open_paren = state.completion_config.trigger_on_open_paren;
 
@@ -684,10 +951,31 @@ impl LanguageServer for Backend {
                 }),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 document_on_type_formatting_provider: Some(
                     indentation::on_type_formatting_capability(),
                 ),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::QUICKFIX,
+                        ]),
+                        work_done_progress_options: Default::default(),
+                        resolve_provider: None,
+                    },
+                )),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        handlers::HOVER_GOTO_DEFINITION_COMMAND.to_string(),
+                        handlers::HOVER_OPEN_HELP_COMMAND.to_string(),
+                        handlers::INSTALL_PACKAGE_COMMAND.to_string(),
+                    ],
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -721,6 +1009,7 @@ impl LanguageServer for Backend {
             packages_r_path,
             additional_paths,
             index_workspace,
+            fs_permission_checks_enabled,
         ) = {
             let state = self.state.read().await;
             (
@@ -733,6 +1022,7 @@ impl LanguageServer for Backend {
                     .packages_additional_library_paths
                     .clone(),
                 state.cross_file_config.index_workspace,
+                state.cross_file_config.fs_permission_checks_enabled,
             )
         };
 
@@ -747,7 +1037,8 @@ impl LanguageServer for Backend {
                 // Run the blocking scan in a blocking task
                 let scan_result = tokio::task::spawn_blocking(move || {
                     let scan_start = std::time::Instant::now();
-                    let result = scan_workspace(&folders_clone, max_chain_depth);
+                    let result =
+                        scan_workspace(&folders_clone, max_chain_depth, fs_permission_checks_enabled);
                     let scan_duration = scan_start.elapsed();
                     let file_count = result.0.len();
                     crate::perf::record_workspace_scan(scan_duration, file_count);
@@ -762,13 +1053,14 @@ impl LanguageServer for Backend {
 
                 // Apply results when scan completes
                 match scan_result {
-                    Ok((index, imports, cross_file_entries, new_index_entries)) => {
+                    Ok((index, imports, cross_file_entries, new_index_entries, untrusted)) => {
                         let mut state = state_clone.write().await;
                         state.apply_workspace_index(
                             index,
                             imports,
                             cross_file_entries,
                             new_index_entries,
+                            untrusted,
                         );
                         log::info!("[Background] Workspace index applied");
                     }
@@ -2117,7 +2409,11 @@ impl LanguageServer for Backend {
                 old_trigger_on_open_paren != new_trigger_on_open_paren;
 
             // Mark all open documents for force republish
-            let open_uris: Vec<Url> = state.documents.keys().cloned().collect();
+            let open_uris: Vec<Url> = state
+                .documents
+                .iter()
+                .map(|e| e.key().clone())
+                .collect();
             for uri in &open_uris {
                 state.diagnostics_gate.mark_force_republish(uri);
             }
@@ -2240,10 +2536,10 @@ impl LanguageServer for Backend {
             );
         }
 
-        // Schedule diagnostics for all open documents
-        for uri in open_uris {
-            self.publish_diagnostics(&uri).await;
-        }
+        // Schedule a debounced, batched wave of diagnostics for all open
+        // documents rather than awaiting one publishDiagnostics per file in
+        // sequence - a config change can affect every open document at once.
+        self.publish_diagnostics_batch(open_uris).await;
     }
 
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
@@ -2405,7 +2701,7 @@ impl LanguageServer for Backend {
                     {
                         let state = state_arc.read().await;
                         let open_docs: std::collections::HashSet<_> =
-                            state.documents.keys().cloned().collect();
+                            state.documents.iter().map(|e| e.key().clone()).collect();
                         state.cross_file_workspace_index.update_from_disk(
                             &uri,
                             &open_docs,
@@ -2559,9 +2855,9 @@ impl LanguageServer for Backend {
         }
 
         // Schedule diagnostics for affected open documents (Requirement 13.4)
-        for uri in affected_open_docs {
-            self.publish_diagnostics(&uri).await;
-        }
+        // as one debounced, batched wave rather than sequential awaits - a
+        // bulk file-watcher event can affect many open documents at once.
+        self.publish_diagnostics_batch(affected_open_docs).await;
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
@@ -2761,9 +3057,135 @@ impl LanguageServer for Backend {
             &state,
             &params.text_document_position.text_document.uri,
             params.text_document_position.position,
+            params.context.include_declaration,
         ))
     }
 
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let state = self.state.read().await;
+        handlers::rename(
+            &state,
+            &params.text_document_position.text_document.uri,
+            params.text_document_position.position,
+            &params.new_name,
+        )
+        .map_err(tower_lsp::jsonrpc::Error::invalid_params)
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let state = self.state.read().await;
+        Ok(handlers::prepare_call_hierarchy(
+            &state,
+            &params.text_document_position_params.text_document.uri,
+            params.text_document_position_params.position,
+        ))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let state = self.state.read().await;
+        Ok(handlers::call_hierarchy_incoming_calls(&state, &params.item))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let state = self.state.read().await;
+        Ok(handlers::call_hierarchy_outgoing_calls(&state, &params.item))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let state = self.state.read().await;
+        Ok(handlers::inlay_hint(&state, &params.text_document.uri, params.range).await)
+    }
+
+    /// Offers the "Extract function" refactor for a selection of complete R
+    /// statements, plus "Remove unused import" quick fixes for any unused
+    /// `library()`/`require()` diagnostics in range.
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let state = self.state.read().await;
+        Ok(handlers::code_action(
+            &state,
+            &params.text_document.uri,
+            params.range,
+            &params.context.diagnostics,
+            params.context.only.as_deref(),
+        ))
+    }
+
+    /// Executes a command invoked from a hover `command:` link or a code
+    /// action's `command`.
+    ///
+    /// `raven.hoverGotoDefinition` asks the client to navigate to a location
+    /// via `window/showDocument`; `raven.hoverOpenHelp` fetches full R help
+    /// text and surfaces it with `window/showMessage`; `raven.installPackage`
+    /// (from the missing-package quick fix) surfaces the `install.packages()`
+    /// call the same way, since Raven has no R session of its own to run it
+    /// in. Unknown commands are logged and ignored, matching tower_lsp's
+    /// default behavior.
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        match params.command.as_str() {
+            handlers::HOVER_GOTO_DEFINITION_COMMAND => {
+                if let Some(location) = handlers::parse_goto_definition_command_args(&params.arguments)
+                {
+                    let _ = self
+                        .client
+                        .show_document(ShowDocumentParams {
+                            uri: location.uri,
+                            external: Some(false),
+                            take_focus: Some(true),
+                            selection: Some(location.range),
+                        })
+                        .await;
+                }
+                Ok(None)
+            }
+            handlers::INSTALL_PACKAGE_COMMAND => {
+                if let Some(package) =
+                    handlers::parse_install_package_command_args(&params.arguments)
+                {
+                    self.client
+                        .show_message(
+                            MessageType::INFO,
+                            format!("Run in the R console: install.packages(\"{}\")", package),
+                        )
+                        .await;
+                }
+                Ok(None)
+            }
+            handlers::HOVER_OPEN_HELP_COMMAND => {
+                if let Some((name, package)) =
+                    handlers::parse_open_help_command_args(&params.arguments)
+                {
+                    let help_text =
+                        tokio::task::spawn_blocking(move || crate::help::get_help(&name, Some(&package)))
+                            .await
+                            .ok()
+                            .flatten();
+                    if let Some(help_text) = help_text {
+                        self.client
+                            .show_message(MessageType::INFO, help_text)
+                            .await;
+                    }
+                }
+                Ok(None)
+            }
+            other => {
+                log::warn!("Unknown command: {}", other);
+                Ok(None)
+            }
+        }
+    }
+
     /// Handles on-type formatting requests triggered by newline characters.
     ///
     /// This provides AST-aware indentation for R code, computing the correct
@@ -2829,7 +3251,12 @@ impl LanguageServer for Backend {
             return Ok(None);
         }
 
-        // Handle closing delimiter triggers: detect and remove auto-close duplicates.
+        // Handle closing delimiter triggers: first check for an auto-close
+        // duplicate, then fall through to the same dedent logic "\n" uses so
+        // the closing delimiter re-indents to match its opener the moment
+        // it's typed (not just when Enter lands on a line that already
+        // starts with one).
+        //
         // When VS Code auto-closes `(`  `()` and the user later types `)` after
         // Enter pushed the auto-closed `)` to a new line, the over-type mechanism
         // fails and a duplicate `)` is inserted. We detect this via tree-sitter:
@@ -2878,8 +3305,7 @@ impl LanguageServer for Backend {
                     }
                 }
             }
-            // No duplicate detected  no edits needed for delimiter triggers
-            return Ok(None);
+            // No duplicate detected  fall through to dedent the delimiter itself.
         }
 
         // Extract FormattingOptions (Requirements 6.1, 6.2)
@@ -2904,7 +3330,7 @@ impl LanguageServer for Backend {
 
         // Detect syntactic context using AST (Requirement 8.3)
         // This handles invalid AST states with fallback to regex-based detection
-        let context = indentation::detect_context(tree, &source, position, tab_size);
+        let context = indentation::detect_context(tree, &source, position);
 
         if log::log_enabled!(log::Level::Trace) {
             let source_lines = source.lines().count();
@@ -3047,7 +3473,7 @@ impl Backend {
                     .collect();
 
                 let open_docs: std::collections::HashSet<_> =
-                    state.documents.keys().cloned().collect();
+                    state.documents.iter().map(|e| e.key().clone()).collect();
                 let workspace_index_version = state.workspace_index_new.version();
 
                 (
@@ -3371,7 +3797,7 @@ impl Backend {
                     .collect();
 
                 let open_docs: std::collections::HashSet<_> =
-                    state.documents.keys().cloned().collect();
+                    state.documents.iter().map(|e| e.key().clone()).collect();
                 let workspace_index_version = state.workspace_index_new.version();
 
                 (
@@ -3449,9 +3875,17 @@ impl Backend {
     }
 
     async fn publish_diagnostics(&self, uri: &Url) {
+        Backend::publish_diagnostics_for(&self.state, &self.client, uri).await;
+    }
+
+    /// Compute and publish diagnostics for a single URI. Takes `state`/`client`
+    /// by reference rather than `&self` so it can run inside a spawned task
+    /// (see [`Backend::publish_diagnostics_batch`]) without needing `Backend`
+    /// itself to be `'static`-cloneable.
+    async fn publish_diagnostics_for(state: &Arc<RwLock<WorldState>>, client: &Client, uri: &Url) {
         // Extract needed data while holding read lock briefly
         let (version, sync_diagnostics, directive_meta, workspace_folder, missing_file_severity) = {
-            let state = self.state.read().await;
+            let state = state.read().await;
             let version = state.documents.get(uri).and_then(|d| d.version);
 
             // Check if we can publish (monotonic gate)
@@ -3507,7 +3941,7 @@ impl Backend {
 
         // Re-check freshness after async work to avoid publishing stale diagnostics
         {
-            let state = self.state.read().await;
+            let state = state.read().await;
             if let Some(ver) = version {
                 let current_version = state.documents.get(uri).and_then(|d| d.version);
                 if current_version != Some(ver) {
@@ -3532,13 +3966,13 @@ impl Backend {
 
         // Record the publish (uses interior mutability, no write lock needed)
         {
-            let state = self.state.read().await;
+            let state = state.read().await;
             if let Some(ver) = version {
                 state.diagnostics_gate.record_publish(uri, ver);
             }
         }
 
-        self.client
+        client
             .publish_diagnostics(uri.clone(), diagnostics, None)
             .await;
     }
@@ -3564,6 +3998,26 @@ impl Backend {
             .cross_file_activity
             .update(active_uri, visible_uris, params.timestamp_ms);
     }
+
+    /// Handle the raven/checkWorkspace request: aggregate diagnostics for the
+    /// whole `source()` graph reachable from every open entry file, so a
+    /// client can populate its problems panel in one shot. Takes no params.
+    async fn check_workspace(&self, _params: ()) -> Result<handlers::CheckWorkspaceResult> {
+        let state = self.state.read().await;
+        Ok(handlers::check_workspace(&state))
+    }
+
+    /// Handle the raven/packageSource request: serve the content of a synthetic
+    /// `raven-package:pkg/relative/path.R` document a goto-definition response pointed at, so a
+    /// client-side content provider can display it. Returns `None` if `params.uri` isn't a
+    /// `raven-package:` URI or the underlying file can no longer be found on disk.
+    async fn package_source(&self, params: PackageSourceParams) -> Result<Option<String>> {
+        let Ok(uri) = Url::parse(&params.uri) else {
+            return Ok(None);
+        };
+        let state = self.state.read().await;
+        Ok(handlers::read_package_source(&state, &uri))
+    }
 }
 
 pub async fn start_lsp() -> anyhow::Result<()> {
@@ -3575,6 +4029,8 @@ pub async fn start_lsp() -> anyhow::Result<()> {
             "raven/activeDocumentsChanged",
             Backend::handle_active_documents_changed,
         )
+        .custom_method("raven/checkWorkspace", Backend::check_workspace)
+        .custom_method("raven/packageSource", Backend::package_source)
         .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 
@@ -3732,6 +4188,62 @@ mod tests {
             );
         }
 
+        /// Test that `diagnostics.undefinedVariablesNseBlanketSkip` and
+        /// `diagnostics.undefinedVariablesNseAllowlist` are parsed from settings.
+        #[test]
+        fn test_undefined_variables_nse_settings_parsed() {
+            let settings = json!({
+                "diagnostics": {
+                    "undefinedVariablesNseBlanketSkip": true,
+                    "undefinedVariablesNseAllowlist": ["my_nse_fn", "pkg::custom_verb"]
+                }
+            });
+
+            let config = crate::backend::parse_cross_file_config(&settings);
+            assert!(config.is_some(), "Configuration parsing should succeed");
+            let config = config.unwrap();
+
+            assert!(config.undefined_variables_nse_blanket_skip);
+            assert_eq!(
+                config.undefined_variables_nse_allowlist,
+                vec!["my_nse_fn".to_string(), "pkg::custom_verb".to_string()]
+            );
+        }
+
+        /// `diagnostics.severityOverrides` remaps or disables any diagnostic
+        /// code, including ones without a dedicated severity field.
+        #[test]
+        fn test_severity_overrides_parsed() {
+            use crate::cross_file::DiagnosticCode;
+
+            let settings = json!({
+                "diagnostics": {
+                    "severityOverrides": {
+                        "raven::else-on-new-line": "warning",
+                        "raven::unused-definition": "off",
+                        "raven::not-a-real-code": "error"
+                    }
+                }
+            });
+
+            let config = crate::backend::parse_cross_file_config(&settings);
+            assert!(config.is_some(), "Configuration parsing should succeed");
+            let overrides = config.unwrap().diagnostic_severity_overrides;
+
+            assert_eq!(
+                overrides.get(DiagnosticCode::ElseOnNewLine),
+                Some(crate::cross_file::DiagnosticSeverityOverride::Warning)
+            );
+            assert_eq!(
+                overrides.get(DiagnosticCode::UnusedDefinition),
+                Some(crate::cross_file::DiagnosticSeverityOverride::Off)
+            );
+            assert_eq!(
+                overrides.get(DiagnosticCode::ArgCountMismatch),
+                None,
+                "codes not mentioned in the settings are left unconfigured"
+            );
+        }
     }
 
     // ============================================================================
@@ -4379,6 +4891,24 @@ mod tests {
             );
         }
 
+        /// Test that "align-to-pipe" value is parsed correctly
+        #[test]
+        fn test_parse_align_to_pipe_style() {
+            let settings = json!({
+                "indentation": {
+                    "style": "align-to-pipe"
+                }
+            });
+            let config = crate::backend::parse_indentation_config(&settings);
+            assert!(config.is_some(), "Configuration parsing should succeed");
+            let config = config.unwrap();
+            assert_eq!(
+                config.style,
+                IndentationStyle::AlignToPipe,
+                "Should parse 'align-to-pipe' as AlignToPipe style"
+            );
+        }
+
         /// Test that invalid style value defaults to RStudio
         /// **Validates: Requirements 7.4**
         #[test]
@@ -286,7 +286,7 @@ pub fn print_results_csv(results: &[PhaseResult]) {
 }
 
 /// Recursively discover all `.R` files under `root` and read their contents.
-fn discover_r_files(root: &Path) -> Vec<(PathBuf, String)> {
+pub(crate) fn discover_r_files(root: &Path) -> Vec<(PathBuf, String)> {
     let mut files = Vec::new();
     collect_r_files(root, &mut files);
     // Sort for deterministic ordering
@@ -0,0 +1,409 @@
+// cli/check.rs — `raven check` subcommand
+//
+// Headless batch lint: loads a set of R files into a `WorldState`, runs the
+// same `handlers::diagnostics` pipeline the LSP server uses for
+// `textDocument/publishDiagnostics`, and prints one JSON record per
+// diagnostic to stdout. Meant for pre-commit hooks and CI, where spinning up
+// an LSP client just to collect diagnostics is overkill.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{CodeActionKind, CodeActionOrCommand, DiagnosticSeverity, Url};
+
+use crate::handlers;
+use crate::state::WorldState;
+
+use super::analysis_stats::discover_r_files;
+
+/// Parsed arguments for the `check` subcommand.
+#[derive(Debug)]
+pub struct CheckArgs {
+    pub paths: Vec<PathBuf>,
+    pub format: String,
+}
+
+/// The only output format understood today; kept as a string (rather than a
+/// bare flag) so future formats can be added without another CLI flag.
+const VALID_FORMATS: &[&str] = &["json"];
+
+/// Parse `check` arguments from the remaining CLI args.
+///
+/// Expected usage: `raven check <paths...> [--format <format>]`
+pub fn parse_args(args: &mut impl Iterator<Item = String>) -> Result<CheckArgs, String> {
+    let mut paths = Vec::new();
+    let mut format = "json".to_string();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "--format requires a value".to_string())?;
+                if !VALID_FORMATS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "Unknown format '{}'. Valid formats: {}",
+                        value,
+                        VALID_FORMATS.join(", ")
+                    ));
+                }
+                format = value;
+            }
+            other if other.starts_with("--format=") => {
+                let value = other.trim_start_matches("--format=").to_string();
+                if !VALID_FORMATS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "Unknown format '{}'. Valid formats: {}",
+                        value,
+                        VALID_FORMATS.join(", ")
+                    ));
+                }
+                format = value;
+            }
+            other if other.starts_with('-') => {
+                return Err(format!("Unknown flag: '{}'", other));
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        return Err("Missing required <paths...> argument".to_string());
+    }
+    for path in &paths {
+        if !path.exists() {
+            return Err(format!("Path does not exist: {}", path.display()));
+        }
+    }
+
+    Ok(CheckArgs { paths, format })
+}
+
+/// One line of the newline-delimited JSON output: everything about a single
+/// diagnostic that a CI consumer or pre-commit hook needs, independent of
+/// the LSP wire format (which carries optional fields we don't want callers
+/// to have to guess the absence of).
+#[derive(Debug, Serialize)]
+pub struct CheckRecord {
+    pub uri: String,
+    pub range: CheckRange,
+    pub severity: String,
+    pub code: Option<String>,
+    pub message: String,
+    pub fixes: Vec<CheckFix>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckRange {
+    pub start: CheckPosition,
+    pub end: CheckPosition,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// A machine-applicable fix offered for a [`CheckRecord`], mirroring the
+/// `CodeAction`s `code_action` would return for the same diagnostic.
+#[derive(Debug, Serialize)]
+pub struct CheckFix {
+    pub title: String,
+    pub edits: Vec<CheckTextEdit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckTextEdit {
+    pub uri: String,
+    pub range: CheckRange,
+    pub new_text: String,
+}
+
+/// Load `args.paths` into a `WorldState` and collect every diagnostic across
+/// them, in path order, each paired with any quickfixes `code_action` offers
+/// for it.
+pub fn run_check(args: &CheckArgs) -> Vec<CheckRecord> {
+    let mut state = WorldState::new(vec![]);
+    let mut uris = Vec::new();
+
+    for path in &args.paths {
+        for (file_path, content) in load_r_files(path) {
+            let uri = uri_for_path(&file_path);
+            state.open_document(uri.clone(), &content, None);
+            uris.push(uri);
+        }
+    }
+    uris.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let mut records = Vec::new();
+    for uri in &uris {
+        for diagnostic in handlers::diagnostics(&state, uri) {
+            let fixes = handlers::code_action(
+                &state,
+                uri,
+                diagnostic.range,
+                std::slice::from_ref(&diagnostic),
+                Some(&[CodeActionKind::QUICKFIX]),
+            )
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => Some(to_check_fix(action)),
+                CodeActionOrCommand::Command(_) => None,
+            })
+            .collect();
+
+            records.push(CheckRecord {
+                uri: uri.to_string(),
+                range: to_check_range(diagnostic.range),
+                severity: severity_label(diagnostic.severity),
+                code: diagnostic.code.map(|code| match code {
+                    tower_lsp::lsp_types::NumberOrString::String(s) => s,
+                    tower_lsp::lsp_types::NumberOrString::Number(n) => n.to_string(),
+                }),
+                message: diagnostic.message,
+                fixes,
+            });
+        }
+    }
+
+    records
+}
+
+/// Print each record as one line of JSON, in the order given.
+pub fn print_records_json(records: &[CheckRecord]) {
+    for record in records {
+        match serde_json::to_string(record) {
+            Ok(line) => println!("{}", line),
+            Err(e) => log::error!("Failed to serialize diagnostic as JSON: {}", e),
+        }
+    }
+}
+
+/// Whether any record is error-severity, i.e. `raven check` should exit
+/// non-zero.
+pub fn has_error(records: &[CheckRecord]) -> bool {
+    records.iter().any(|r| r.severity == "error")
+}
+
+fn load_r_files(path: &Path) -> Vec<(PathBuf, String)> {
+    if path.is_dir() {
+        discover_r_files(path)
+    } else {
+        match std::fs::read_to_string(path) {
+            Ok(content) => vec![(path.to_path_buf(), content)],
+            Err(e) => {
+                log::error!("Failed to read {}: {}", path.display(), e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn uri_for_path(path: &Path) -> Url {
+    Url::from_file_path(path)
+        .unwrap_or_else(|_| Url::parse(&format!("file://{}", path.display())).expect("valid URL"))
+}
+
+fn severity_label(severity: Option<DiagnosticSeverity>) -> String {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "information",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "warning",
+    }
+    .to_string()
+}
+
+fn to_check_range(range: tower_lsp::lsp_types::Range) -> CheckRange {
+    CheckRange {
+        start: CheckPosition {
+            line: range.start.line,
+            character: range.start.character,
+        },
+        end: CheckPosition {
+            line: range.end.line,
+            character: range.end.character,
+        },
+    }
+}
+
+fn to_check_fix(action: tower_lsp::lsp_types::CodeAction) -> CheckFix {
+    let mut edits = Vec::new();
+    if let Some(changes) = action.edit.and_then(|edit| edit.changes) {
+        for (uri, text_edits) in changes {
+            for text_edit in text_edits {
+                edits.push(CheckTextEdit {
+                    uri: uri.to_string(),
+                    range: to_check_range(text_edit.range),
+                    new_text: text_edit.new_text,
+                });
+            }
+        }
+    }
+    CheckFix {
+        title: action.title,
+        edits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args_single_path() {
+        let mut args = vec![".".to_string()].into_iter();
+        let result = parse_args(&mut args).unwrap();
+        assert_eq!(result.paths, vec![PathBuf::from(".")]);
+        assert_eq!(result.format, "json");
+    }
+
+    #[test]
+    fn test_parse_args_multiple_paths() {
+        let mut args = vec![".".to_string(), ".".to_string()].into_iter();
+        let result = parse_args(&mut args).unwrap();
+        assert_eq!(result.paths.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_args_format_flag_space_separated() {
+        let mut args =
+            vec![".".to_string(), "--format".to_string(), "json".to_string()].into_iter();
+        let result = parse_args(&mut args).unwrap();
+        assert_eq!(result.format, "json");
+    }
+
+    #[test]
+    fn test_parse_args_format_flag_equals() {
+        let mut args = vec![".".to_string(), "--format=json".to_string()].into_iter();
+        let result = parse_args(&mut args).unwrap();
+        assert_eq!(result.format, "json");
+    }
+
+    #[test]
+    fn test_parse_args_unknown_format() {
+        let mut args = vec![".".to_string(), "--format=yaml".to_string()].into_iter();
+        let result = parse_args(&mut args);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Unknown format"));
+    }
+
+    #[test]
+    fn test_parse_args_missing_paths() {
+        let mut args = vec!["--format=json".to_string()].into_iter();
+        let result = parse_args(&mut args);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Missing required <paths...>"));
+    }
+
+    #[test]
+    fn test_parse_args_nonexistent_path() {
+        let mut args = vec!["/no/such/path/raven-test".to_string()].into_iter();
+        let result = parse_args(&mut args);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_run_check_flags_else_newline_with_fix() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("bad.R"), "if (x) {y}\nelse {z}\n").unwrap();
+
+        let args = CheckArgs {
+            paths: vec![dir.path().to_path_buf()],
+            format: "json".to_string(),
+        };
+        let records = run_check(&args);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].severity, "error");
+        assert_eq!(records[0].code.as_deref(), Some("raven::else-on-new-line"));
+        assert_eq!(records[0].fixes.len(), 1);
+        assert_eq!(records[0].fixes[0].edits[0].new_text, " ");
+    }
+
+    #[test]
+    fn test_run_check_clean_file_has_no_records() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("clean.R"), "x <- 1\ny <- x + 1\n").unwrap();
+
+        let args = CheckArgs {
+            paths: vec![dir.path().to_path_buf()],
+            format: "json".to_string(),
+        };
+        let records = run_check(&args);
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_has_error_true_for_error_severity() {
+        let records = vec![CheckRecord {
+            uri: "file:///a.R".to_string(),
+            range: CheckRange {
+                start: CheckPosition {
+                    line: 0,
+                    character: 0,
+                },
+                end: CheckPosition {
+                    line: 0,
+                    character: 1,
+                },
+            },
+            severity: "error".to_string(),
+            code: None,
+            message: "boom".to_string(),
+            fixes: Vec::new(),
+        }];
+        assert!(has_error(&records));
+    }
+
+    #[test]
+    fn test_has_error_false_for_warning_only() {
+        let records = vec![CheckRecord {
+            uri: "file:///a.R".to_string(),
+            range: CheckRange {
+                start: CheckPosition {
+                    line: 0,
+                    character: 0,
+                },
+                end: CheckPosition {
+                    line: 0,
+                    character: 1,
+                },
+            },
+            severity: "warning".to_string(),
+            code: None,
+            message: "heads up".to_string(),
+            fixes: Vec::new(),
+        }];
+        assert!(!has_error(&records));
+    }
+
+    #[test]
+    fn test_check_record_json_shape() {
+        let record = CheckRecord {
+            uri: "file:///a.R".to_string(),
+            range: CheckRange {
+                start: CheckPosition {
+                    line: 0,
+                    character: 0,
+                },
+                end: CheckPosition {
+                    line: 0,
+                    character: 1,
+                },
+            },
+            severity: "error".to_string(),
+            code: Some("raven::else-on-new-line".to_string()),
+            message: "boom".to_string(),
+            fixes: Vec::new(),
+        };
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["uri"], "file:///a.R");
+        assert_eq!(json["range"]["start"]["line"], 0);
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["code"], "raven::else-on-new-line");
+    }
+}
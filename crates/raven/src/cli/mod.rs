@@ -0,0 +1,4 @@
+// cli/mod.rs — subcommands for the `raven` binary that don't start the LSP server.
+
+pub mod analysis_stats;
+pub mod check;
@@ -8,11 +8,14 @@
 //
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use dashmap::DashMap;
 use tower_lsp::lsp_types::Url;
 
 use crate::cross_file::file_cache::CrossFileFileCache;
+use crate::cross_file::pending_fetch::PendingFetchQueue;
 use crate::cross_file::scope::{self, ScopeArtifacts};
 use crate::cross_file::types::CrossFileMetadata;
 use crate::cross_file::workspace_index::CrossFileWorkspaceIndex;
@@ -20,6 +23,44 @@ use crate::document_store::DocumentStore;
 use crate::state::Document;
 use crate::workspace_index::WorkspaceIndex;
 
+/// Result of a cached lookup that may need to fall back to background work.
+///
+/// Plain `Option` can't distinguish "there is no such data" from "the data
+/// isn't computed yet, but a background worker has been asked to produce
+/// it" — callers that only ever saw `None` for the latter case had no way
+/// to know a retry might pay off. [`ContentProvider::get_metadata_state`] and
+/// [`ContentProvider::get_artifacts_state`] surface that distinction so
+/// cross-file features can show partial results now and progressively fill
+/// in as indexing completes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchState<T> {
+    /// The value is available now.
+    Ready(T),
+    /// Not available yet; a background fetch has been (or already was)
+    /// queued. Callers should treat this like a cache miss for the current
+    /// request and expect a subsequent request to succeed once the
+    /// background worker catches up.
+    Pending,
+    /// The value doesn't exist and no background work was queued (e.g. the
+    /// file isn't known to exist).
+    Absent,
+}
+
+impl<T> FetchState<T> {
+    /// Collapse to `Option<T>`, treating `Pending` the same as `Absent`.
+    /// Useful for callers that haven't adopted the three-way distinction yet.
+    pub fn ready(self) -> Option<T> {
+        match self {
+            FetchState::Ready(value) => Some(value),
+            FetchState::Pending | FetchState::Absent => None,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        matches!(self, FetchState::Pending)
+    }
+}
+
 /// Trait for content providers (sync operations)
 ///
 /// This trait provides a unified interface for accessing file content,
@@ -48,6 +89,30 @@ pub trait ContentProvider: Send + Sync {
     /// for the given URI, or None if not available.
     fn get_artifacts(&self, uri: &Url) -> Option<ScopeArtifacts>;
 
+    /// Get metadata for a URI, distinguishing "doesn't exist" from "not
+    /// indexed yet, but background computation was requested".
+    ///
+    /// The default implementation just wraps [`Self::get_metadata`] and
+    /// never returns `Pending`; implementations that can enqueue background
+    /// work (like [`DefaultContentProvider`]) should override this.
+    fn get_metadata_state(&self, uri: &Url) -> FetchState<CrossFileMetadata> {
+        match self.get_metadata(uri) {
+            Some(metadata) => FetchState::Ready(metadata),
+            None => FetchState::Absent,
+        }
+    }
+
+    /// Get artifacts for a URI, distinguishing "doesn't exist" from "not
+    /// indexed yet, but background computation was requested".
+    ///
+    /// See [`Self::get_metadata_state`] for the default/override contract.
+    fn get_artifacts_state(&self, uri: &Url) -> FetchState<ScopeArtifacts> {
+        match self.get_artifacts(uri) {
+            Some(artifacts) => FetchState::Ready(artifacts),
+            None => FetchState::Absent,
+        }
+    }
+
     /// Check if URI exists in cache (no I/O)
     ///
     /// Returns true if the URI is available in any cached source
@@ -115,9 +180,14 @@ pub struct DefaultContentProvider<'a> {
     workspace_index: &'a WorkspaceIndex,
     file_cache: &'a CrossFileFileCache,
     // Legacy fields for migration compatibility
-    legacy_documents: Option<&'a HashMap<Url, Document>>,
+    legacy_documents: Option<&'a DashMap<Url, Document>>,
     legacy_workspace_index: Option<&'a HashMap<Url, Document>>,
     legacy_cross_file_workspace_index: Option<&'a CrossFileWorkspaceIndex>,
+    /// Queue of URIs a cache-only lookup couldn't serve, handed to a
+    /// background worker (see [`crate::cross_file::background_indexer`]) for
+    /// read-and-compute. Shared (not per-provider) so requests made across
+    /// separate `content_provider()` calls accumulate into the same queue.
+    pending_fetches: Arc<PendingFetchQueue>,
 }
 
 impl<'a> DefaultContentProvider<'a> {
@@ -127,6 +197,11 @@ impl<'a> DefaultContentProvider<'a> {
     /// * `document_store` - Reference to the DocumentStore for open documents
     /// * `workspace_index` - Reference to the WorkspaceIndex for closed files
     /// * `file_cache` - Reference to the CrossFileFileCache for disk file caching
+    ///
+    /// Uses a private, per-instance pending-fetch queue; callers that want
+    /// `get_metadata_state`/`get_artifacts_state` misses to actually reach a
+    /// background worker should use [`Self::with_legacy`], which takes a
+    /// shared queue.
     #[allow(dead_code)]
     pub fn new(
         document_store: &'a DocumentStore,
@@ -137,6 +212,7 @@ impl<'a> DefaultContentProvider<'a> {
             document_store,
             workspace_index,
             file_cache,
+            pending_fetches: Arc::new(PendingFetchQueue::new()),
             legacy_documents: None,
             legacy_workspace_index: None,
             legacy_cross_file_workspace_index: None,
@@ -152,26 +228,50 @@ impl<'a> DefaultContentProvider<'a> {
     /// * `document_store` - Reference to the DocumentStore for open documents
     /// * `workspace_index` - Reference to the WorkspaceIndex for closed files
     /// * `file_cache` - Reference to the CrossFileFileCache for disk file caching
-    /// * `legacy_documents` - Reference to the legacy documents HashMap
+    /// * `legacy_documents` - Reference to the legacy documents map (a `DashMap`,
+    ///   so concurrent readers don't serialize behind the surrounding `WorldState` lock)
     /// * `legacy_workspace_index` - Reference to the legacy workspace_index HashMap
     /// * `legacy_cross_file_workspace_index` - Reference to the legacy CrossFileWorkspaceIndex
+    /// * `pending_fetches` - Shared queue that `get_metadata_state`/`get_artifacts_state`
+    ///   misses are enqueued onto for background read-and-compute
     pub fn with_legacy(
         document_store: &'a DocumentStore,
         workspace_index: &'a WorkspaceIndex,
         file_cache: &'a CrossFileFileCache,
-        legacy_documents: &'a HashMap<Url, Document>,
+        legacy_documents: &'a DashMap<Url, Document>,
         legacy_workspace_index: &'a HashMap<Url, Document>,
         legacy_cross_file_workspace_index: &'a CrossFileWorkspaceIndex,
+        pending_fetches: Arc<PendingFetchQueue>,
     ) -> Self {
         Self {
             document_store,
             workspace_index,
             file_cache,
+            pending_fetches,
             legacy_documents: Some(legacy_documents),
             legacy_workspace_index: Some(legacy_workspace_index),
             legacy_cross_file_workspace_index: Some(legacy_cross_file_workspace_index),
         }
     }
+
+    /// Shared fallback for `get_metadata_state`/`get_artifacts_state`: if the
+    /// URI's existence is already known from the file cache's existence
+    /// cache (no disk I/O), enqueue it for background computation and
+    /// report `Pending`; otherwise report `Absent`.
+    fn queue_pending_fetch<T>(&self, uri: &Url) -> FetchState<T> {
+        let known_to_exist = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.file_cache.path_exists(&path))
+            .unwrap_or(false);
+
+        if known_to_exist {
+            self.pending_fetches.enqueue(uri.clone());
+            FetchState::Pending
+        } else {
+            FetchState::Absent
+        }
+    }
 }
 
 impl<'a> ContentProvider for DefaultContentProvider<'a> {
@@ -318,6 +418,27 @@ impl<'a> ContentProvider for DefaultContentProvider<'a> {
         None
     }
 
+    /// Like [`Self::get_metadata`], but when none of the cached tiers have an
+    /// answer and the file's existence is already known (from the file
+    /// cache's existence cache, so still no synchronous disk I/O), enqueues
+    /// the URI for background read-and-compute and returns `Pending` instead
+    /// of `Absent`.
+    fn get_metadata_state(&self, uri: &Url) -> FetchState<CrossFileMetadata> {
+        if let Some(metadata) = self.get_metadata(uri) {
+            return FetchState::Ready(metadata);
+        }
+        self.queue_pending_fetch(uri)
+    }
+
+    /// Like [`Self::get_artifacts`], but see [`Self::get_metadata_state`] for
+    /// the `Pending` fallback behavior.
+    fn get_artifacts_state(&self, uri: &Url) -> FetchState<ScopeArtifacts> {
+        if let Some(artifacts) = self.get_artifacts(uri) {
+            return FetchState::Ready(artifacts);
+        }
+        self.queue_pending_fetch(uri)
+    }
+
     /// Check if URI exists in cache (no I/O)
     ///
     /// Returns true if the URI is available in any cached source
@@ -328,7 +449,7 @@ impl<'a> ContentProvider for DefaultContentProvider<'a> {
         self.document_store.contains(uri)
             || self
                 .legacy_documents
-                .is_some_and(|docs: &HashMap<Url, Document>| docs.contains_key(uri))
+                .is_some_and(|docs: &DashMap<Url, Document>| docs.contains_key(uri))
             || self.workspace_index.contains(uri)
             || self
                 .legacy_workspace_index
@@ -348,7 +469,7 @@ impl<'a> ContentProvider for DefaultContentProvider<'a> {
         self.document_store.contains(uri)
             || self
                 .legacy_documents
-                .is_some_and(|docs: &HashMap<Url, Document>| docs.contains_key(uri))
+                .is_some_and(|docs: &DashMap<Url, Document>| docs.contains_key(uri))
     }
 }
 
@@ -2217,4 +2338,158 @@ mod integration_tests {
         assert!(symbol.is_declared, "Symbol should be marked as declared");
         assert_eq!(symbol.kind, SymbolKind::Function, "Symbol should be a function");
     }
+
+    // ========================================================================
+    // FetchState / get_metadata_state / get_artifacts_state
+    // ========================================================================
+
+    fn make_provider_with_legacy<'a>(
+        doc_store: &'a DocumentStore,
+        workspace_index: &'a WorkspaceIndex,
+        file_cache: &'a CrossFileFileCache,
+        legacy_documents: &'a DashMap<Url, Document>,
+        legacy_workspace_index: &'a HashMap<Url, Document>,
+        legacy_cross_file_workspace_index: &'a CrossFileWorkspaceIndex,
+        pending_fetches: Arc<PendingFetchQueue>,
+    ) -> DefaultContentProvider<'a> {
+        DefaultContentProvider::with_legacy(
+            doc_store,
+            workspace_index,
+            file_cache,
+            legacy_documents,
+            legacy_workspace_index,
+            legacy_cross_file_workspace_index,
+            pending_fetches,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_metadata_state_ready_for_open_doc() {
+        let mut doc_store = make_test_document_store();
+        let workspace_index = make_test_workspace_index();
+        let file_cache = CrossFileFileCache::new();
+        let legacy_documents = DashMap::new();
+        let legacy_workspace_index = HashMap::new();
+        let legacy_cross_file_workspace_index = CrossFileWorkspaceIndex::new();
+
+        let uri = test_uri("open.R");
+        doc_store.open(uri.clone(), "x <- 1", 1).await;
+
+        let provider = make_provider_with_legacy(
+            &doc_store,
+            &workspace_index,
+            &file_cache,
+            &legacy_documents,
+            &legacy_workspace_index,
+            &legacy_cross_file_workspace_index,
+            Arc::new(PendingFetchQueue::new()),
+        );
+
+        assert!(matches!(
+            provider.get_metadata_state(&uri),
+            FetchState::Ready(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_metadata_state_absent_when_existence_unknown() {
+        let doc_store = make_test_document_store();
+        let workspace_index = make_test_workspace_index();
+        let file_cache = CrossFileFileCache::new();
+        let legacy_documents = DashMap::new();
+        let legacy_workspace_index = HashMap::new();
+        let legacy_cross_file_workspace_index = CrossFileWorkspaceIndex::new();
+        let pending_fetches = Arc::new(PendingFetchQueue::new());
+
+        let uri = test_uri("nowhere.R");
+
+        let provider = make_provider_with_legacy(
+            &doc_store,
+            &workspace_index,
+            &file_cache,
+            &legacy_documents,
+            &legacy_workspace_index,
+            &legacy_cross_file_workspace_index,
+            pending_fetches.clone(),
+        );
+
+        assert!(matches!(
+            provider.get_metadata_state(&uri),
+            FetchState::Absent
+        ));
+        assert!(pending_fetches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_metadata_state_pending_when_known_to_exist() {
+        let doc_store = make_test_document_store();
+        let workspace_index = make_test_workspace_index();
+        let file_cache = CrossFileFileCache::new();
+        let legacy_documents = DashMap::new();
+        let legacy_workspace_index = HashMap::new();
+        let legacy_cross_file_workspace_index = CrossFileWorkspaceIndex::new();
+        let pending_fetches = Arc::new(PendingFetchQueue::new());
+
+        let uri = test_uri("known_to_exist.R");
+        let path = uri.to_file_path().unwrap();
+        // Simulate a prior existence check finding the file on disk, without
+        // ever populating its metadata/artifacts.
+        file_cache.cache_existence(&path, true);
+
+        let provider = make_provider_with_legacy(
+            &doc_store,
+            &workspace_index,
+            &file_cache,
+            &legacy_documents,
+            &legacy_workspace_index,
+            &legacy_cross_file_workspace_index,
+            pending_fetches.clone(),
+        );
+
+        assert!(matches!(
+            provider.get_metadata_state(&uri),
+            FetchState::Pending
+        ));
+        assert!(pending_fetches.contains(&uri));
+    }
+
+    #[tokio::test]
+    async fn test_get_artifacts_state_pending_when_known_to_exist() {
+        let doc_store = make_test_document_store();
+        let workspace_index = make_test_workspace_index();
+        let file_cache = CrossFileFileCache::new();
+        let legacy_documents = DashMap::new();
+        let legacy_workspace_index = HashMap::new();
+        let legacy_cross_file_workspace_index = CrossFileWorkspaceIndex::new();
+        let pending_fetches = Arc::new(PendingFetchQueue::new());
+
+        let uri = test_uri("artifacts_known_to_exist.R");
+        let path = uri.to_file_path().unwrap();
+        file_cache.cache_existence(&path, true);
+
+        let provider = make_provider_with_legacy(
+            &doc_store,
+            &workspace_index,
+            &file_cache,
+            &legacy_documents,
+            &legacy_workspace_index,
+            &legacy_cross_file_workspace_index,
+            pending_fetches.clone(),
+        );
+
+        assert!(matches!(
+            provider.get_artifacts_state(&uri),
+            FetchState::Pending
+        ));
+        assert!(pending_fetches.contains(&uri));
+    }
+
+    #[test]
+    fn test_fetch_state_ready_collapses_to_option() {
+        assert_eq!(FetchState::Ready(42).ready(), Some(42));
+        assert_eq!(FetchState::<i32>::Pending.ready(), None);
+        assert_eq!(FetchState::<i32>::Absent.ready(), None);
+        assert!(FetchState::<i32>::Pending.is_pending());
+        assert!(!FetchState::<i32>::Absent.is_pending());
+    }
 }
@@ -0,0 +1,385 @@
+//
+// cross_file/ancestor_chain.rs
+//
+// Transitive ancestor-chain resolution to the root script
+//
+// NOTE: `resolve_ancestor_chain` and `resolve_ancestors` below are written
+// against `ParentResolution` (from `cross_file::cache`) and a caller-supplied
+// per-hop resolver function rather than against
+// `cross_file::parent_resolve::resolve_parent` directly; a caller passes a
+// `|uri| resolve_parent(...)` closure as that resolver.
+
+use indexmap::IndexSet;
+use tower_lsp::lsp_types::Url;
+
+use super::cache::ParentResolution;
+
+/// Why [`resolve_ancestor_chain`] stopped walking upward.
+#[derive(Debug, Clone)]
+pub enum AncestorChainStop {
+    /// Reached a file with no parent - `hops` is the full chain to the root.
+    Root,
+    /// A hop had more than one candidate parent; the chain stops here so the
+    /// caller can decide how to proceed (e.g. prompt, or pick the selected
+    /// alternative and keep walking itself).
+    Ambiguous {
+        child_uri: Url,
+        selected_uri: Url,
+        alternatives: Vec<Url>,
+    },
+    /// The same URI was reached twice while walking upward (mutually-sourcing
+    /// files); the chain stops before re-entering the cycle.
+    Cycle { repeated_uri: Url },
+    /// A hop's only candidate parent was rejected by the project-root
+    /// sandbox (see [`super::sandbox`]).
+    Denied { attempted_uri: Url, reason: String },
+}
+
+/// Follow parent resolution upward from `child_uri`, one hop at a time via
+/// `resolve_one`, recording each hop's call site, until the root is reached,
+/// a hop is ambiguous, or a cycle is detected.
+///
+/// Returns the ordered chain from immediate parent up to (but not including)
+/// `child_uri` itself, plus the reason the walk stopped. An ambiguous hop or
+/// a detected cycle is not included in the returned chain - `stop` carries
+/// that information separately so the caller can decide what, if anything,
+/// to do about it.
+pub fn resolve_ancestor_chain<F>(
+    child_uri: &Url,
+    mut resolve_one: F,
+) -> (Vec<(Url, Option<(u32, u32)>)>, AncestorChainStop)
+where
+    F: FnMut(&Url) -> ParentResolution,
+{
+    let mut chain = Vec::new();
+    let mut visited = std::collections::HashSet::new();
+    visited.insert(child_uri.clone());
+
+    let mut current = child_uri.clone();
+    loop {
+        match resolve_one(&current) {
+            ParentResolution::Single {
+                parent_uri,
+                call_site_line,
+                call_site_column,
+            } => {
+                if !visited.insert(parent_uri.clone()) {
+                    return (chain, AncestorChainStop::Cycle { repeated_uri: parent_uri });
+                }
+                let call_site = match (call_site_line, call_site_column) {
+                    (Some(line), Some(col)) => Some((line, col)),
+                    _ => None,
+                };
+                chain.push((parent_uri.clone(), call_site));
+                current = parent_uri;
+            }
+            ParentResolution::Ambiguous {
+                selected_uri,
+                alternatives,
+                ..
+            } => {
+                return (
+                    chain,
+                    AncestorChainStop::Ambiguous {
+                        child_uri: current,
+                        selected_uri,
+                        alternatives,
+                    },
+                );
+            }
+            ParentResolution::None => {
+                return (chain, AncestorChainStop::Root);
+            }
+            ParentResolution::Denied {
+                attempted_uri,
+                reason,
+            } => {
+                return (chain, AncestorChainStop::Denied { attempted_uri, reason });
+            }
+        }
+    }
+}
+
+/// One hop recorded by [`resolve_ancestors`]: `from` sources `to` at
+/// `call_site` (if known).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AncestorEdge {
+    pub from: Url,
+    pub to: Url,
+    pub call_site: Option<(u32, u32)>,
+}
+
+/// A hop during [`resolve_ancestors`] that had more than one candidate
+/// parent. The walk continues through `selected_uri` (the same selection
+/// [`ParentResolution::Ambiguous`] already made), but `alternatives` is
+/// preserved so callers can see where the chain forked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AncestorFork {
+    pub child_uri: Url,
+    pub selected_uri: Url,
+    pub alternatives: Vec<Url>,
+}
+
+/// Full transitive-ancestor result from [`resolve_ancestors`].
+#[derive(Debug, Clone, Default)]
+pub struct AncestorsResult {
+    /// Every ancestor reached, nearest-first, deduplicated. Iteration order
+    /// matches discovery order (see [`IndexSet`]).
+    pub ancestors: IndexSet<Url>,
+    /// The call-site edge connecting each consecutive pair in the walk.
+    pub edges: Vec<AncestorEdge>,
+    /// Every hop along the way that had more than one candidate parent.
+    pub forks: Vec<AncestorFork>,
+}
+
+/// Walk upward from `child_uri` via `resolve_one`, like
+/// [`resolve_ancestor_chain`], but collect the *entire* transitive ancestor
+/// set rather than stopping at the first ambiguity: an `Ambiguous` hop is
+/// recorded as an [`AncestorFork`] and the walk continues through its
+/// selected candidate, the same way [`resolve_ancestor_chain`] would if the
+/// caller picked `selected_uri` and kept walking itself. Stops at the root,
+/// a denied candidate, or a cycle (the repeated URI is simply not
+/// re-visited - no error is surfaced, since a mutually-sourcing loop just
+/// means the ancestor set is already complete at that point).
+pub fn resolve_ancestors<F>(child_uri: &Url, mut resolve_one: F) -> AncestorsResult
+where
+    F: FnMut(&Url) -> ParentResolution,
+{
+    let mut result = AncestorsResult::default();
+    let mut visited = std::collections::HashSet::new();
+    visited.insert(child_uri.clone());
+
+    let mut current = child_uri.clone();
+    loop {
+        let (next, call_site) = match resolve_one(&current) {
+            ParentResolution::Single {
+                parent_uri,
+                call_site_line,
+                call_site_column,
+            } => (parent_uri, zip_call_site(call_site_line, call_site_column)),
+            ParentResolution::Ambiguous {
+                selected_uri,
+                selected_line,
+                selected_column,
+                alternatives,
+            } => {
+                result.forks.push(AncestorFork {
+                    child_uri: current.clone(),
+                    selected_uri: selected_uri.clone(),
+                    alternatives,
+                });
+                (selected_uri, zip_call_site(selected_line, selected_column))
+            }
+            ParentResolution::None | ParentResolution::Denied { .. } => return result,
+        };
+
+        if !visited.insert(next.clone()) {
+            return result;
+        }
+
+        result.edges.push(AncestorEdge {
+            from: current.clone(),
+            to: next.clone(),
+            call_site,
+        });
+        result.ancestors.insert(next.clone());
+        current = next;
+    }
+}
+
+fn zip_call_site(line: Option<u32>, column: Option<u32>) -> Option<(u32, u32)> {
+    match (line, column) {
+        (Some(line), Some(column)) => Some((line, column)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_ancestors_walks_full_chain() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+        let main = uri("file:///main.R");
+
+        let result = resolve_ancestors(&a, |current| {
+            if *current == a {
+                ParentResolution::Single {
+                    parent_uri: b.clone(),
+                    call_site_line: Some(3),
+                    call_site_column: Some(0),
+                }
+            } else if *current == b {
+                ParentResolution::Single {
+                    parent_uri: main.clone(),
+                    call_site_line: Some(10),
+                    call_site_column: Some(2),
+                }
+            } else {
+                ParentResolution::None
+            }
+        });
+
+        assert_eq!(
+            result.ancestors.iter().cloned().collect::<Vec<_>>(),
+            vec![b.clone(), main.clone()]
+        );
+        assert_eq!(result.edges.len(), 2);
+        assert!(result.forks.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_ancestors_continues_through_ambiguous_hop_and_records_fork() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+        let c = uri("file:///c.R");
+        let main = uri("file:///main.R");
+
+        let result = resolve_ancestors(&a, |current| {
+            if *current == a {
+                ParentResolution::Ambiguous {
+                    selected_uri: b.clone(),
+                    selected_line: None,
+                    selected_column: None,
+                    alternatives: vec![b.clone(), c.clone()],
+                }
+            } else if *current == b {
+                ParentResolution::Single {
+                    parent_uri: main.clone(),
+                    call_site_line: None,
+                    call_site_column: None,
+                }
+            } else {
+                ParentResolution::None
+            }
+        });
+
+        assert_eq!(
+            result.ancestors.iter().cloned().collect::<Vec<_>>(),
+            vec![b.clone(), main.clone()]
+        );
+        assert_eq!(result.forks.len(), 1);
+        assert_eq!(result.forks[0].child_uri, a);
+        assert_eq!(result.forks[0].selected_uri, b);
+        assert_eq!(result.forks[0].alternatives, vec![b, c]);
+    }
+
+    #[test]
+    fn test_resolve_ancestors_terminates_on_mutual_sourcing_cycle() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+
+        let result = resolve_ancestors(&a, |current| {
+            if *current == a {
+                ParentResolution::Single {
+                    parent_uri: b.clone(),
+                    call_site_line: None,
+                    call_site_column: None,
+                }
+            } else {
+                ParentResolution::Single {
+                    parent_uri: a.clone(),
+                    call_site_line: None,
+                    call_site_column: None,
+                }
+            }
+        });
+
+        assert_eq!(result.ancestors.iter().cloned().collect::<Vec<_>>(), vec![b]);
+    }
+
+    #[test]
+    fn test_walks_to_root() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+        let main = uri("file:///main.R");
+
+        let (chain, stop) = resolve_ancestor_chain(&a, |current| {
+            if *current == a {
+                ParentResolution::Single {
+                    parent_uri: b.clone(),
+                    call_site_line: Some(3),
+                    call_site_column: Some(0),
+                }
+            } else if *current == b {
+                ParentResolution::Single {
+                    parent_uri: main.clone(),
+                    call_site_line: Some(10),
+                    call_site_column: Some(2),
+                }
+            } else {
+                ParentResolution::None
+            }
+        });
+
+        assert_eq!(chain, vec![(b.clone(), Some((3, 0))), (main.clone(), Some((10, 2)))]);
+        assert!(matches!(stop, AncestorChainStop::Root));
+    }
+
+    #[test]
+    fn test_stops_at_ambiguous_hop() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+        let c = uri("file:///c.R");
+
+        let (chain, stop) = resolve_ancestor_chain(&a, |_current| ParentResolution::Ambiguous {
+            selected_uri: b.clone(),
+            selected_line: None,
+            selected_column: None,
+            alternatives: vec![b.clone(), c.clone()],
+        });
+
+        assert!(chain.is_empty());
+        match stop {
+            AncestorChainStop::Ambiguous {
+                child_uri,
+                selected_uri,
+                alternatives,
+            } => {
+                assert_eq!(child_uri, a);
+                assert_eq!(selected_uri, b);
+                assert_eq!(alternatives, vec![b, c]);
+            }
+            other => panic!("expected Ambiguous, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_breaks_mutual_sourcing_cycle() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+
+        let (chain, stop) = resolve_ancestor_chain(&a, |current| {
+            if *current == a {
+                ParentResolution::Single {
+                    parent_uri: b.clone(),
+                    call_site_line: None,
+                    call_site_column: None,
+                }
+            } else {
+                ParentResolution::Single {
+                    parent_uri: a.clone(),
+                    call_site_line: None,
+                    call_site_column: None,
+                }
+            }
+        });
+
+        assert_eq!(chain, vec![(b.clone(), None)]);
+        assert!(matches!(stop, AncestorChainStop::Cycle { repeated_uri } if repeated_uri == a));
+    }
+
+    #[test]
+    fn test_immediate_root_yields_empty_chain() {
+        let a = uri("file:///a.R");
+        let (chain, stop) = resolve_ancestor_chain(&a, |_| ParentResolution::None);
+        assert!(chain.is_empty());
+        assert!(matches!(stop, AncestorChainStop::Root));
+    }
+}
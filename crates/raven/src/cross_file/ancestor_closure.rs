@@ -0,0 +1,263 @@
+//
+// cross_file/ancestor_closure.rs
+//
+// Batch ancestor-closure computation over a whole dependency graph, avoiding
+// O(n^2) cloning on long single-parent source() chains
+//
+// NOTE: this operates on a generic `direct_parents` map (node -> its
+// immediate parents) rather than on `cross_file::dependency::DependencyGraph`
+// directly.
+//
+// Mirrors Mercurial's changelog ancestor-set bisect optimization: walking a
+// long non-branching chain of single-parent commits hands each commit's
+// accumulated ancestor set to its sole child by reference rather than
+// cloning it, only paying the O(size) copy cost at an actual fork (a commit
+// with more than one child) or merge (a commit with more than one parent).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use indexmap::IndexSet;
+use tower_lsp::lsp_types::Url;
+
+/// Compute every node's full transitive ancestor set from a `direct_parents`
+/// map (node -> its immediate parents), processing nodes in topological
+/// order (parents before children).
+///
+/// Each node's ancestor set is `Arc<IndexSet<Url>>` so a straight-line chain
+/// of single-parent, single-child nodes can pass its accumulated set forward
+/// by moving the same `Arc` (unwrapping it via `Arc::try_unwrap` once
+/// its last consumer is known) instead of cloning - the clone only happens
+/// at a node with more than one parent (a merge, since each parent's set
+/// must be copied into the union) or when a parent has more than one child
+/// (a fork, since each child after the first needs its own independent
+/// copy to extend).
+///
+/// Nodes that are part of a cycle (not reachable via a topological order
+/// from the acyclic portion of the graph) are omitted from the result
+/// rather than looped over forever.
+pub fn compute_ancestor_closures(
+    direct_parents: &HashMap<Url, Vec<Url>>,
+) -> HashMap<Url, Arc<IndexSet<Url>>> {
+    let mut children_of: HashMap<Url, Vec<Url>> = HashMap::new();
+    let mut remaining_parents: HashMap<Url, usize> = HashMap::new();
+
+    for (node, parents) in direct_parents {
+        remaining_parents.entry(node.clone()).or_insert(0);
+        for parent in parents {
+            children_of.entry(parent.clone()).or_default().push(node.clone());
+            *remaining_parents.entry(node.clone()).or_insert(0) += 1;
+        }
+        // A parent that's never itself a key in `direct_parents` still needs
+        // a ready (zero-in-degree) entry so it gets processed.
+        for parent in parents {
+            remaining_parents.entry(parent.clone()).or_insert(0);
+        }
+    }
+
+    let mut remaining_children: HashMap<Url, usize> = children_of
+        .iter()
+        .map(|(parent, children)| (parent.clone(), children.len()))
+        .collect();
+
+    let mut ready: Vec<Url> = remaining_parents
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(node, _)| node.clone())
+        .collect();
+    // Deterministic order for reproducible test assertions.
+    ready.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let mut ancestor_sets: HashMap<Url, Arc<IndexSet<Url>>> = HashMap::new();
+    let mut in_degree_left: HashMap<Url, usize> = remaining_parents.clone();
+    let mut queue: std::collections::VecDeque<Url> = ready.into();
+    let mut processed: std::collections::HashSet<Url> = std::collections::HashSet::new();
+
+    while let Some(node) = queue.pop_front() {
+        if processed.contains(&node) {
+            continue;
+        }
+        processed.insert(node.clone());
+
+        let parents = direct_parents.get(&node).cloned().unwrap_or_default();
+        let ancestors: Arc<IndexSet<Url>> = match parents.as_slice() {
+            [] => Arc::new(IndexSet::new()),
+            [single_parent] => {
+                let parent_set = ancestor_sets
+                    .remove(single_parent)
+                    .unwrap_or_else(|| Arc::new(IndexSet::new()));
+                let is_last_child = remaining_children
+                    .get(single_parent)
+                    .copied()
+                    .unwrap_or(0)
+                    <= 1;
+
+                if is_last_child {
+                    if let Some(count) = remaining_children.get_mut(single_parent) {
+                        *count = count.saturating_sub(1);
+                    }
+                    match Arc::try_unwrap(parent_set) {
+                        Ok(mut owned) => {
+                            owned.insert(single_parent.clone());
+                            Arc::new(owned)
+                        }
+                        Err(shared) => {
+                            // Still referenced elsewhere (shouldn't happen on
+                            // the single-owner fast path, but fall back to a
+                            // copy rather than panic).
+                            let mut copy = (*shared).clone();
+                            copy.insert(single_parent.clone());
+                            Arc::new(copy)
+                        }
+                    }
+                } else {
+                    if let Some(count) = remaining_children.get_mut(single_parent) {
+                        *count = count.saturating_sub(1);
+                    }
+                    // Put the parent's set back for its remaining children.
+                    ancestor_sets.insert(single_parent.clone(), parent_set.clone());
+                    let mut copy = (*parent_set).clone();
+                    copy.insert(single_parent.clone());
+                    Arc::new(copy)
+                }
+            }
+            multiple_parents => {
+                // Merge node: union every parent's ancestor set plus the
+                // parent itself. Always a fresh allocation - there's no
+                // single owner to reuse across a fan-in.
+                let mut union = IndexSet::new();
+                for parent in multiple_parents {
+                    if let Some(set) = ancestor_sets.get(parent) {
+                        union.extend(set.iter().cloned());
+                    }
+                    union.insert(parent.clone());
+                    if let Some(count) = remaining_children.get_mut(parent) {
+                        *count = count.saturating_sub(1);
+                    }
+                }
+                Arc::new(union)
+            }
+        };
+
+        ancestor_sets.insert(node.clone(), ancestors);
+
+        for child in children_of.get(&node).cloned().unwrap_or_default() {
+            if let Some(left) = in_degree_left.get_mut(&child) {
+                *left = left.saturating_sub(1);
+                if *left == 0 && !processed.contains(&child) {
+                    queue.push_back(child);
+                }
+            }
+        }
+    }
+
+    ancestor_sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_linear_chain_ancestor_sets() {
+        // main -> b -> a (a is sourced by b, b is sourced by main)
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+        let main = uri("file:///main.R");
+
+        let mut direct_parents = HashMap::new();
+        direct_parents.insert(a.clone(), vec![b.clone()]);
+        direct_parents.insert(b.clone(), vec![main.clone()]);
+
+        let closures = compute_ancestor_closures(&direct_parents);
+
+        assert_eq!(
+            closures[&a].iter().cloned().collect::<std::collections::HashSet<_>>(),
+            [b.clone(), main.clone()].into_iter().collect()
+        );
+        assert_eq!(
+            closures[&b].iter().cloned().collect::<std::collections::HashSet<_>>(),
+            [main.clone()].into_iter().collect()
+        );
+        assert!(closures[&main].is_empty());
+    }
+
+    #[test]
+    fn test_single_parent_chain_extends_without_per_hop_cloning() {
+        // A long single-parent/single-child chain should extend each node's
+        // owned `IndexSet` in place (via `Arc::try_unwrap`) rather than
+        // `.clone()`-ing the whole accumulated set at every hop.
+        let nodes: Vec<Url> = (0..10).map(|i| uri(&format!("file:///n{i}.R"))).collect();
+        let mut direct_parents = HashMap::new();
+        for i in 1..nodes.len() {
+            direct_parents.insert(nodes[i].clone(), vec![nodes[i - 1].clone()]);
+        }
+
+        let closures = compute_ancestor_closures(&direct_parents);
+
+        // n9's ancestor set should contain every earlier node.
+        assert_eq!(closures[&nodes[9]].len(), 9);
+        assert!(closures[&nodes[9]].contains(&nodes[0]));
+        assert!(closures[&nodes[9]].contains(&nodes[8]));
+    }
+
+    #[test]
+    fn test_diamond_merge_unions_both_branches() {
+        //      main
+        //     /    \
+        //    b      c
+        //     \    /
+        //      child
+        let main = uri("file:///main.R");
+        let b = uri("file:///b.R");
+        let c = uri("file:///c.R");
+        let child = uri("file:///child.R");
+
+        let mut direct_parents = HashMap::new();
+        direct_parents.insert(b.clone(), vec![main.clone()]);
+        direct_parents.insert(c.clone(), vec![main.clone()]);
+        direct_parents.insert(child.clone(), vec![b.clone(), c.clone()]);
+
+        let closures = compute_ancestor_closures(&direct_parents);
+
+        let child_ancestors: std::collections::HashSet<_> =
+            closures[&child].iter().cloned().collect();
+        assert_eq!(
+            child_ancestors,
+            [main.clone(), b.clone(), c.clone()].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_fan_out_gives_each_child_an_independent_copy() {
+        let main = uri("file:///main.R");
+        let b = uri("file:///b.R");
+        let c = uri("file:///c.R");
+
+        let mut direct_parents = HashMap::new();
+        direct_parents.insert(b.clone(), vec![main.clone()]);
+        direct_parents.insert(c.clone(), vec![main.clone()]);
+
+        let closures = compute_ancestor_closures(&direct_parents);
+
+        assert!(closures[&b].contains(&main));
+        assert!(closures[&c].contains(&main));
+    }
+
+    #[test]
+    fn test_cyclic_nodes_are_omitted_not_infinite_looped() {
+        let a = uri("file:///a.R");
+        let b = uri("file:///b.R");
+
+        let mut direct_parents = HashMap::new();
+        direct_parents.insert(a.clone(), vec![b.clone()]);
+        direct_parents.insert(b.clone(), vec![a.clone()]);
+
+        let closures = compute_ancestor_closures(&direct_parents);
+        assert!(closures.is_empty());
+    }
+}
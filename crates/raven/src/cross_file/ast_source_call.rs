@@ -0,0 +1,235 @@
+//
+// cross_file/ast_source_call.rs
+//
+// AST-aware source() call-site detection for a specific child file
+//
+// NOTE: `infer_call_site_from_parent` in `cross_file::parent_resolve` calls
+// `find_source_call_site_for_child_or_fallback` below: given a parsed tree
+// and a child path/filename, it finds the call-site position of the genuine
+// `source()`/`sys.source()` call expression that targets that child
+// (skipping comments, string-literal bodies, and lookalike identifiers like
+// `my_source(`), falling back to a literal-substring scan when no tree is
+// available.
+
+use tree_sitter::{Node, Tree};
+
+/// Find the call-site position of a `source()`/`sys.source()` call in
+/// `content` whose file argument resolves to `child_path` (compared by full
+/// path or by filename, matching the precedence used elsewhere in this
+/// module for candidate parents). Walks `tree`'s call expressions rather
+/// than scanning for substrings, so it only matches genuine call
+/// expressions - comments, string literals, and identifiers like
+/// `my_source(` never produce a false hit.
+pub fn find_source_call_site_for_child(
+    tree: &Tree,
+    content: &str,
+    child_path: &str,
+) -> Option<(u32, u32)> {
+    let child_filename = std::path::Path::new(child_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(child_path);
+
+    find_in_node(tree.root_node(), content, child_path, child_filename)
+}
+
+/// Find the call-site position of a `source()`/`sys.source()` call whose
+/// file argument resolves to `child_path`, using the AST when `tree` is
+/// available and falling back to the previous literal-substring scan
+/// (matching on `source(`/`sys.source(` plus the child's path or filename)
+/// when it isn't - e.g. for buffers that failed to parse.
+pub fn find_source_call_site_for_child_or_fallback(
+    tree: Option<&Tree>,
+    content: &str,
+    child_path: &str,
+) -> Option<(u32, u32)> {
+    if let Some(tree) = tree {
+        if let Some(pos) = find_source_call_site_for_child(tree, content, child_path) {
+            return Some(pos);
+        }
+    }
+
+    let child_filename = std::path::Path::new(child_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(child_path);
+
+    let normalized_line_has_child = |line: &str| {
+        let normalized_line = super::child_path::normalize_separators(line);
+        normalized_line.contains(&super::child_path::normalize_separators(child_path))
+            || normalized_line.contains(&super::child_path::normalize_separators(child_filename))
+    };
+
+    for (line_num, line) in content.lines().enumerate() {
+        let has_source_call = line.contains("source(") || line.contains("sys.source(");
+        if has_source_call && normalized_line_has_child(line) {
+            let byte_offset = line.find("source(").or_else(|| line.find("sys.source("))?;
+            return Some((line_num as u32, byte_offset_to_utf16_column(line, byte_offset)));
+        }
+    }
+    None
+}
+
+fn find_in_node(node: Node, content: &str, child_path: &str, child_filename: &str) -> Option<(u32, u32)> {
+    if node.kind() == "call" {
+        if let Some(pos) = try_match_source_call(node, content, child_path, child_filename) {
+            return Some(pos);
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if let Some(pos) = find_in_node(child, content, child_path, child_filename) {
+            return Some(pos);
+        }
+    }
+    None
+}
+
+fn try_match_source_call(
+    node: Node,
+    content: &str,
+    child_path: &str,
+    child_filename: &str,
+) -> Option<(u32, u32)> {
+    let func_node = node.child_by_field_name("function")?;
+    let func_text = node_text(func_node, content);
+    if func_text != "source" && func_text != "sys.source" {
+        return None;
+    }
+
+    let args_node = node.child_by_field_name("arguments")?;
+    let argument = find_file_argument(&args_node, content)?;
+
+    if !super::child_path::paths_match(&argument, child_path)
+        && !super::child_path::paths_match(&argument, child_filename)
+    {
+        return None;
+    }
+
+    let start = node.start_position();
+    let line_text = content.lines().nth(start.row).unwrap_or("");
+    Some((start.row as u32, byte_offset_to_utf16_column(line_text, start.column)))
+}
+
+fn find_file_argument(args_node: &Node, content: &str) -> Option<String> {
+    let mut cursor = args_node.walk();
+    let children: Vec<_> = args_node.children(&mut cursor).collect();
+
+    for child in &children {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                if node_text(name_node, content) == "file" {
+                    let value_node = child.child_by_field_name("value")?;
+                    return extract_string_literal(value_node, content);
+                }
+            }
+        }
+    }
+
+    for child in &children {
+        if child.kind() == "argument" && child.child_by_field_name("name").is_none() {
+            let value_node = child.child_by_field_name("value")?;
+            return extract_string_literal(value_node, content);
+        }
+    }
+
+    None
+}
+
+fn extract_string_literal(node: Node, content: &str) -> Option<String> {
+    if node.kind() == "string" {
+        let text = node_text(node, content);
+        if (text.starts_with('"') && text.ends_with('"'))
+            || (text.starts_with('\'') && text.ends_with('\''))
+        {
+            return Some(text[1..text.len() - 1].to_string());
+        }
+    }
+    None
+}
+
+fn node_text<'a>(node: Node<'a>, content: &'a str) -> &'a str {
+    &content[node.byte_range()]
+}
+
+fn byte_offset_to_utf16_column(line_text: &str, byte_offset_in_line: usize) -> u32 {
+    line_text[..byte_offset_in_line.min(line_text.len())]
+        .chars()
+        .map(|ch| ch.len_utf16() as u32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Url;
+
+    fn parse(content: &str) -> Tree {
+        crate::parser_pool::with_parser(|parser| parser.parse(content, None)).unwrap()
+    }
+
+    #[test]
+    fn test_finds_genuine_call_ignoring_comment() {
+        let content = "# source(\"child.R\") - just an example\nsource(\"child.R\")\n";
+        let tree = parse(content);
+        let pos = find_source_call_site_for_child(&tree, content, "child.R");
+        assert_eq!(pos, Some((1, 0)));
+    }
+
+    #[test]
+    fn test_ignores_lookalike_identifier() {
+        let content = "my_source(\"child.R\")\nsource(\"child.R\")\n";
+        let tree = parse(content);
+        let pos = find_source_call_site_for_child(&tree, content, "child.R");
+        assert_eq!(pos, Some((1, 0)));
+    }
+
+    #[test]
+    fn test_matches_file_named_argument() {
+        let content = "source(file = \"child.R\", local = TRUE)\n";
+        let tree = parse(content);
+        let pos = find_source_call_site_for_child(&tree, content, "child.R");
+        assert_eq!(pos, Some((0, 0)));
+    }
+
+    #[test]
+    fn test_matches_sys_source() {
+        let content = "sys.source(\"child.R\", envir = globalenv())\n";
+        let tree = parse(content);
+        let pos = find_source_call_site_for_child(&tree, content, "child.R");
+        assert_eq!(pos, Some((0, 0)));
+    }
+
+    #[test]
+    fn test_no_match_returns_none() {
+        let content = "x <- 1\n";
+        let tree = parse(content);
+        assert_eq!(find_source_call_site_for_child(&tree, content, "child.R"), None);
+    }
+
+    #[test]
+    fn test_matches_windows_style_backslash_literal_against_posix_child_path() {
+        let content = "source(\"subdir\\\\child.R\")\n";
+        let tree = parse(content);
+        let pos = find_source_call_site_for_child(&tree, content, "subdir/child.R");
+        assert_eq!(pos, Some((0, 0)));
+    }
+
+    #[test]
+    fn test_matches_posix_style_literal_against_windows_derived_child_path() {
+        let uri = Url::parse("file:///c:/project/subdir/child.R").unwrap();
+        let child_path = super::super::child_path::derive_child_path(&uri);
+        let content = "source(\"subdir/child.R\")\n";
+        let tree = parse(content);
+        let pos = find_source_call_site_for_child(&tree, content, &child_path);
+        assert_eq!(pos, Some((0, 0)));
+    }
+
+    #[test]
+    fn test_fallback_used_when_no_tree() {
+        let content = "source(\"child.R\")\n";
+        let pos = find_source_call_site_for_child_or_fallback(None, content, "child.R");
+        assert_eq!(pos, Some((0, 0)));
+    }
+}
@@ -182,6 +182,8 @@ impl BackgroundIndexer {
                         break;
                     }
                     _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
+                        Self::drain_pending_fetches(&state, &queue).await;
+
                         let task_opt = {
                             let mut q = queue.lock().unwrap();
                             q.pop_front()
@@ -198,6 +200,67 @@ impl BackgroundIndexer {
         *self.worker_handle.lock().unwrap() = Some(handle);
     }
 
+    /// Moves URIs queued by [`crate::cross_file::pending_fetch::PendingFetchQueue`]
+    /// (i.e. content-provider lookups that missed every cached tier) onto the
+    /// indexer's own priority queue, at the same priority as backward-directive
+    /// targets, so they get read, parsed, and cached on the next worker tick.
+    async fn drain_pending_fetches(
+        state: &Arc<RwLock<WorldState>>,
+        queue: &Arc<Mutex<VecDeque<IndexTask>>>,
+    ) {
+        let (on_demand_enabled, max_queue_size) = {
+            let state_guard = state.read().await;
+            (
+                state_guard.cross_file_config.on_demand_indexing_enabled,
+                state_guard.cross_file_config.on_demand_indexing_max_queue_size,
+            )
+        };
+        if !on_demand_enabled {
+            return;
+        }
+
+        let pending = {
+            let state_guard = state.read().await;
+            state_guard.pending_fetch_queue.drain()
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        let mut q = queue.lock().unwrap();
+        for uri in pending {
+            if q.iter().any(|t| t.uri == uri) {
+                log::trace!("Skipping pending-fetch task for {} - already queued", uri);
+                continue;
+            }
+            if q.len() >= max_queue_size {
+                log::warn!(
+                    "Background indexing queue full, dropping pending-fetch task for {} ({}/{})",
+                    uri,
+                    q.len(),
+                    max_queue_size
+                );
+                continue;
+            }
+
+            let insert_pos = q.iter().position(|t| t.priority > 2).unwrap_or(q.len());
+            q.insert(
+                insert_pos,
+                IndexTask {
+                    uri: uri.clone(),
+                    priority: 2,
+                    depth: 0,
+                    submitted_at: Instant::now(),
+                },
+            );
+            log::trace!(
+                "Queued pending-fetch task for {} (priority=2, queue_size={})",
+                uri,
+                q.len()
+            );
+        }
+    }
+
     /// Processes a single indexing task
     async fn process_task(
         state: Arc<RwLock<WorldState>>,
@@ -250,6 +313,10 @@ impl BackgroundIndexer {
                     symbol_count
                 );
 
+                // Invalidate so an open document that depends on this file re-requests
+                // diagnostics now that its metadata/artifacts are available.
+                Self::mark_dependents_for_republish(&state, &task.uri).await;
+
                 // Queue transitive dependencies for both Priority 2 and Priority 3 tasks
                 // (as long as depth limit allows)
                 Self::queue_transitive_deps(state, queue, &task.uri, &metadata, task.depth).await;
@@ -312,7 +379,11 @@ impl BackgroundIndexer {
                 content.clone(),
             );
 
-            let open_docs: HashSet<_> = state_guard.documents.keys().cloned().collect();
+            let open_docs: HashSet<_> = state_guard
+                .documents
+                .iter()
+                .map(|e| e.key().clone())
+                .collect();
             state_guard.cross_file_workspace_index.update_from_disk(
                 uri,
                 &open_docs,
@@ -356,6 +427,28 @@ impl BackgroundIndexer {
         Ok(cross_file_meta)
     }
 
+    /// Marks `uri` and its open transitive dependents for force republish, so a
+    /// file whose metadata/artifacts just became available (e.g. via a
+    /// previously-`Pending` content-provider lookup) gets its diagnostics
+    /// re-requested rather than staying stale until the next edit.
+    async fn mark_dependents_for_republish(state: &Arc<RwLock<WorldState>>, uri: &Url) {
+        let state_guard = state.read().await;
+        let max_chain_depth = state_guard.cross_file_config.max_chain_depth;
+
+        if state_guard.documents.contains_key(uri) {
+            state_guard.diagnostics_gate.mark_force_republish(uri);
+        }
+
+        let dependents = state_guard
+            .cross_file_graph
+            .get_transitive_dependents(uri, max_chain_depth);
+        for dep in dependents {
+            if state_guard.documents.contains_key(&dep) {
+                state_guard.diagnostics_gate.mark_force_republish(&dep);
+            }
+        }
+    }
+
     /// Queues transitive dependencies for Priority 3 indexing
     async fn queue_transitive_deps(
         state: Arc<RwLock<WorldState>>,
@@ -4,13 +4,18 @@
 // Caching structures with interior mutability for cross-file awareness
 //
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::num::NonZeroUsize;
 use std::sync::RwLock;
 
 use lru::LruCache;
 use tower_lsp::lsp_types::Url;
 
+use crate::package_library::PackageLibrary;
+use crate::state::Document;
+
 use super::scope::ScopeArtifacts;
 use super::types::CrossFileMetadata;
 
@@ -27,6 +32,48 @@ pub struct ScopeFingerprint {
     pub workspace_index_version: u64,
 }
 
+impl ScopeFingerprint {
+    /// Build a fingerprint for a single open document's own `compute_artifacts`
+    /// result - there's no dependency-graph walk or workspace index involved,
+    /// so `edges_hash` and `workspace_index_version` are left at zero and only
+    /// `self_hash` (the document's content) and `upstream_interfaces_hash`
+    /// (its loaded packages' exports) are populated. A caller that also wants
+    /// edge/workspace staleness detection (e.g. `collect_max_depth_diagnostics`
+    /// walking the dependency graph) should build its own fingerprint instead.
+    pub fn for_document(doc: &Document, package_library: &PackageLibrary) -> Self {
+        Self {
+            self_hash: doc.content_hash,
+            edges_hash: 0,
+            upstream_interfaces_hash: hash_loaded_package_exports(
+                &doc.loaded_packages,
+                package_library,
+            ),
+            workspace_index_version: 0,
+        }
+    }
+}
+
+/// Hash of the loaded packages' export sets, used to invalidate cached scope
+/// artifacts when a `library()`'d package's exports change shape (e.g. after
+/// a reinstall) even though the document's own text didn't. Hashes export
+/// *counts* rather than every exported name - cheap, and sufficient to catch
+/// the shape changes that would actually affect shadowing resolution.
+fn hash_loaded_package_exports(loaded_packages: &[String], package_library: &PackageLibrary) -> u64 {
+    let mut names: Vec<&String> = loaded_packages.iter().collect();
+    names.sort();
+
+    let mut hasher = DefaultHasher::new();
+    for name in names {
+        name.hash(&mut hasher);
+        let export_count = package_library
+            .get_cached_combined_exports(name)
+            .map(|exports| exports.len())
+            .unwrap_or(0);
+        export_count.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
 /// Default capacity for the metadata cache
 const DEFAULT_METADATA_CACHE_CAPACITY: usize = 1000;
 
@@ -151,6 +198,22 @@ impl ArtifactsCache {
         }
     }
 
+    /// Return the cached artifacts for `uri` if `fp` still matches, otherwise
+    /// run `compute` and cache its result under `fp`.
+    pub fn get_or_compute(
+        &self,
+        uri: &Url,
+        fp: ScopeFingerprint,
+        compute: impl FnOnce() -> ScopeArtifacts,
+    ) -> ScopeArtifacts {
+        if let Some(cached) = self.get_if_fresh(uri, &fp) {
+            return cached;
+        }
+        let artifacts = compute();
+        self.insert(uri.clone(), fp, artifacts.clone());
+        artifacts
+    }
+
     /// Invalidate a specific entry
     pub fn invalidate(&self, uri: &Url) {
         if let Ok(mut guard) = self.inner.write() {
@@ -193,6 +256,9 @@ pub enum ParentResolution {
     },
     /// No parent found
     None,
+    /// A candidate parent was rejected by the project-root sandbox (see
+    /// [`super::sandbox`]) rather than followed.
+    Denied { attempted_uri: Url, reason: String },
 }
 
 /// Parent selection cache with interior mutability
@@ -340,6 +406,66 @@ mod tests {
         assert!(cache.get(&uri).is_none());
     }
 
+    #[test]
+    fn test_artifacts_cache_get_or_compute() {
+        let cache = ArtifactsCache::new();
+        let uri = test_uri("test.R");
+        let fp = ScopeFingerprint {
+            self_hash: 123,
+            edges_hash: 0,
+            upstream_interfaces_hash: 0,
+            workspace_index_version: 0,
+        };
+
+        let mut computed = 0;
+        let artifacts = cache.get_or_compute(&uri, fp.clone(), || {
+            computed += 1;
+            ScopeArtifacts::default()
+        });
+        assert_eq!(computed, 1);
+        let _ = artifacts;
+
+        // Same fingerprint: served from cache, `compute` not called again
+        let mut computed_again = 0;
+        cache.get_or_compute(&uri, fp, || {
+            computed_again += 1;
+            ScopeArtifacts::default()
+        });
+        assert_eq!(computed_again, 0);
+
+        // Different fingerprint: stale, `compute` runs and overwrites the entry
+        let fp2 = ScopeFingerprint {
+            self_hash: 456,
+            edges_hash: 0,
+            upstream_interfaces_hash: 0,
+            workspace_index_version: 0,
+        };
+        let mut computed_stale = 0;
+        cache.get_or_compute(&uri, fp2, || {
+            computed_stale += 1;
+            ScopeArtifacts::default()
+        });
+        assert_eq!(computed_stale, 1);
+    }
+
+    #[test]
+    fn test_scope_fingerprint_for_document_tracks_content_hash() {
+        let package_library = PackageLibrary::new_empty();
+        let doc = Document::new("x <- 1", None);
+        let fp_before = ScopeFingerprint::for_document(&doc, &package_library);
+
+        let mut doc = doc;
+        doc.apply_change(tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "x <- 2".to_string(),
+        });
+        let fp_after = ScopeFingerprint::for_document(&doc, &package_library);
+
+        assert_ne!(fp_before.self_hash, fp_after.self_hash);
+        assert_eq!(fp_after.self_hash, doc.content_hash);
+    }
+
     #[test]
     fn test_parent_selection_cache() {
         let cache = ParentSelectionCache::new();
@@ -0,0 +1,91 @@
+//
+// cross_file/child_path.rs
+//
+// Platform-aware child-URI-to-path derivation for source() call matching
+//
+// NOTE: this backs the comparisons against `source()` literals in
+// `ast_source_call::find_source_call_site_for_child` and
+// `regex_cache::resolve_match_pattern_regex`. `resolve_parent_with_content`
+// in `cross_file::parent_resolve` derives the child's filename itself and
+// does not go through this module.
+
+use tower_lsp::lsp_types::Url;
+
+/// Replace backslashes with forward slashes, so a path segment written
+/// either way compares equal.
+pub fn normalize_separators(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// Derive the path a `source()` call in `child_uri`'s parent would most
+/// likely reference it by: the OS path `child_uri` resolves to (correctly
+/// handling a Windows `file:///c:/project/subdir/child.R` URI via
+/// `Url::to_file_path`, not naive `file:///`-stripping), with separators
+/// normalized to `/`.
+///
+/// Returns `child_uri`'s path component verbatim (already `/`-separated, as
+/// URL paths always are) if it isn't a `file:` URI or isn't a valid local
+/// path - this only needs to be "close enough" for literal/suffix matching,
+/// never an authoritative filesystem path.
+pub fn derive_child_path(child_uri: &Url) -> String {
+    match child_uri.to_file_path() {
+        Ok(path) => normalize_separators(&path.to_string_lossy()),
+        Err(()) => normalize_separators(child_uri.path()),
+    }
+}
+
+/// Whether `source_literal` (a string literal from a `source()`/
+/// `sys.source()` call, e.g. `"subdir\\child.R"` or `"subdir/child.R"`)
+/// refers to `child_path` (as derived by [`derive_child_path`], or any
+/// equivalent path/filename string) - comparing by normalized full path or
+/// by filename, and tolerating either slash style on both sides.
+pub fn paths_match(source_literal: &str, child_path: &str) -> bool {
+    let literal = normalize_separators(source_literal);
+    let child = normalize_separators(child_path);
+
+    if literal == child {
+        return true;
+    }
+
+    let literal_filename = literal.rsplit('/').next().unwrap_or(&literal);
+    let child_filename = child.rsplit('/').next().unwrap_or(&child);
+    literal_filename == child_filename
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derives_posix_path() {
+        let uri = Url::parse("file:///project/subdir/child.R").unwrap();
+        assert_eq!(derive_child_path(&uri), "/project/subdir/child.R");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_derives_windows_path_with_drive_letter() {
+        let uri = Url::parse("file:///c:/project/subdir/child.R").unwrap();
+        let derived = derive_child_path(&uri);
+        assert!(derived.ends_with("project/subdir/child.R"));
+        assert!(!derived.contains('\\'));
+    }
+
+    #[test]
+    fn test_paths_match_tolerates_either_slash_style() {
+        assert!(paths_match("subdir\\child.R", "subdir/child.R"));
+        assert!(paths_match("subdir/child.R", "subdir\\child.R"));
+        assert!(paths_match("subdir/child.R", "subdir/child.R"));
+    }
+
+    #[test]
+    fn test_paths_match_by_filename_when_full_paths_differ() {
+        assert!(paths_match("child.R", "subdir/child.R"));
+        assert!(paths_match("subdir\\child.R", "child.R"));
+    }
+
+    #[test]
+    fn test_paths_do_not_match_different_files() {
+        assert!(!paths_match("other.R", "subdir/child.R"));
+    }
+}
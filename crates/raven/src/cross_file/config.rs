@@ -4,11 +4,269 @@
 // Configuration for cross-file awareness
 //
 
+use std::collections::HashMap;
 use std::path::PathBuf;
-use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
 
 use crate::indentation::IndentationStyle;
 
+/// Typed identifier for every diagnostic code this server emits, mirroring
+/// rust-analyzer's per-lint enums. Each variant's `as_str` is kept in sync
+/// with the matching `raven::...` slug in `handlers::diagnostic_codes` (the
+/// stable string is what actually travels over the wire in `Diagnostic.code`
+/// and is documented in `docs/diagnostics.md`); this enum exists so the
+/// severity-override configuration below can be parsed and matched on
+/// without passing codes around as free-form strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    SyntaxError,
+    ElseOnNewLine,
+    CircularDependency,
+    MaxChainDepthExceeded,
+    MissingFile,
+    AmbiguousParent,
+    OutOfScopeSymbol,
+    MissingPackage,
+    UnusedLibrary,
+    UndefinedVariable,
+    DirectiveSuppressesCall,
+    ArgCountMismatch,
+    UnusedDefinition,
+    UnloadedNamespacePackage,
+    IncorrectCase,
+    UnsourcedFile,
+    UntrustedFilePermissions,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SyntaxError => "raven::syntax-error",
+            Self::ElseOnNewLine => "raven::else-on-new-line",
+            Self::CircularDependency => "raven::circular-dependency",
+            Self::MaxChainDepthExceeded => "raven::max-chain-depth-exceeded",
+            Self::MissingFile => "raven::missing-file",
+            Self::AmbiguousParent => "raven::ambiguous-parent",
+            Self::OutOfScopeSymbol => "raven::out-of-scope-symbol",
+            Self::MissingPackage => "raven::missing-package",
+            Self::UnusedLibrary => "raven::unused-library",
+            Self::UndefinedVariable => "raven::undefined-variable",
+            Self::DirectiveSuppressesCall => "raven::directive-suppresses-call",
+            Self::ArgCountMismatch => "raven::arg-count-mismatch",
+            Self::UnusedDefinition => "raven::unused-definition",
+            Self::UnloadedNamespacePackage => "raven::unloaded-namespace-package",
+            Self::IncorrectCase => "raven::incorrect-case",
+            Self::UnsourcedFile => "raven::unsourced-file",
+            Self::UntrustedFilePermissions => "raven::untrusted-file-permissions",
+        }
+    }
+
+    pub fn from_str(code: &str) -> Option<Self> {
+        Some(match code {
+            "raven::syntax-error" => Self::SyntaxError,
+            "raven::else-on-new-line" => Self::ElseOnNewLine,
+            "raven::circular-dependency" => Self::CircularDependency,
+            "raven::max-chain-depth-exceeded" => Self::MaxChainDepthExceeded,
+            "raven::missing-file" => Self::MissingFile,
+            "raven::ambiguous-parent" => Self::AmbiguousParent,
+            "raven::out-of-scope-symbol" => Self::OutOfScopeSymbol,
+            "raven::missing-package" => Self::MissingPackage,
+            "raven::unused-library" => Self::UnusedLibrary,
+            "raven::undefined-variable" => Self::UndefinedVariable,
+            "raven::directive-suppresses-call" => Self::DirectiveSuppressesCall,
+            "raven::arg-count-mismatch" => Self::ArgCountMismatch,
+            "raven::unused-definition" => Self::UnusedDefinition,
+            "raven::unloaded-namespace-package" => Self::UnloadedNamespacePackage,
+            "raven::incorrect-case" => Self::IncorrectCase,
+            "raven::unsourced-file" => Self::UnsourcedFile,
+            "raven::untrusted-file-permissions" => Self::UntrustedFilePermissions,
+            _ => return None,
+        })
+    }
+}
+
+/// Naming convention enforced by the incorrect-case diagnostic (see
+/// `collect_naming_convention_diagnostics` in handlers.rs), porting
+/// rust-analyzer's `incorrect_case` lint to R. R codebases vary widely in
+/// convention - snake_case, camelCase, and dotted.case are all common - so
+/// this is user-selectable rather than fixed like `reserved_words`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamingConvention {
+    #[default]
+    SnakeCase,
+    CamelCase,
+    DottedCase,
+}
+
+impl NamingConvention {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "snake_case" | "snake" => Some(Self::SnakeCase),
+            "camelcase" | "camel" => Some(Self::CamelCase),
+            "dotted.case" | "dotted_case" | "dotted" => Some(Self::DottedCase),
+            _ => None,
+        }
+    }
+
+    /// Short label used in diagnostic messages, e.g. "snake_case".
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::SnakeCase => "snake_case",
+            Self::CamelCase => "camelCase",
+            Self::DottedCase => "dotted.case",
+        }
+    }
+
+    /// Returns `true` if `name` already conforms to this convention.
+    pub fn matches(self, name: &str) -> bool {
+        match self {
+            Self::SnakeCase => !name.chars().any(|c| c.is_ascii_uppercase()),
+            Self::CamelCase => {
+                !name.contains('_')
+                    && name
+                        .chars()
+                        .next()
+                        .map_or(true, |c| !c.is_ascii_uppercase())
+            }
+            Self::DottedCase => {
+                !name.contains('_') && !name.chars().any(|c| c.is_ascii_uppercase())
+            }
+        }
+    }
+
+    /// Rewrites `name` to conform to this convention, splitting on
+    /// underscores, dots, and camelCase humps to recover the constituent
+    /// words.
+    pub fn suggest(self, name: &str) -> String {
+        let words = split_identifier_words(name);
+        match self {
+            Self::SnakeCase => words.join("_"),
+            Self::DottedCase => words.join("."),
+            Self::CamelCase => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Splits an identifier into its lowercased constituent words, treating `_`
+/// and `.` as explicit separators and an uppercase letter following a
+/// lowercase one as an implicit (camelCase) word boundary.
+fn split_identifier_words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c == '_' || c == '.' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current).to_lowercase());
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current).to_lowercase());
+        }
+        prev_lower = c.is_lowercase();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current.to_lowercase());
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// One user-configured override for a single `DiagnosticCode`: either a
+/// specific severity to force every matching diagnostic to, or `Off` to
+/// drop it entirely. Mirrors the "error"/"warning"/"information"/"hint"/"off"
+/// vocabulary `parse_severity` already uses for the older per-field severity
+/// settings below, but - unlike those - applies to any code, including ones
+/// without a dedicated `CrossFileConfig` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverityOverride {
+    Error,
+    Warning,
+    Information,
+    Hint,
+    Off,
+}
+
+impl DiagnosticSeverityOverride {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "error" => Some(Self::Error),
+            "warning" => Some(Self::Warning),
+            "information" | "info" => Some(Self::Information),
+            "hint" => Some(Self::Hint),
+            "off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    /// Converts to the `Diagnostic.severity` value to use, or `None` when
+    /// the code is turned off (the caller should drop the diagnostic).
+    pub fn to_lsp_severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            Self::Error => Some(DiagnosticSeverity::ERROR),
+            Self::Warning => Some(DiagnosticSeverity::WARNING),
+            Self::Information => Some(DiagnosticSeverity::INFORMATION),
+            Self::Hint => Some(DiagnosticSeverity::HINT),
+            Self::Off => None,
+        }
+    }
+}
+
+/// User-configurable per-code severity remapping, populated from
+/// `initializationOptions`/`workspace/didChangeConfiguration`'s
+/// `diagnostics.severityOverrides` map (see `parse_cross_file_config` in
+/// `backend.rs`) and consulted once, as a final pass over the diagnostics a
+/// document's collectors already produced - the same place `@lsp-allow`
+/// directives are applied, so the two final-stage filters read naturally
+/// together (see `apply_severity_overrides` in `handlers.rs`).
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticSeverityConfig {
+    overrides: HashMap<DiagnosticCode, DiagnosticSeverityOverride>,
+}
+
+impl DiagnosticSeverityConfig {
+    pub fn from_map(raw: &HashMap<String, String>) -> Self {
+        let mut overrides = HashMap::new();
+        for (code, value) in raw {
+            if let (Some(code), Some(severity)) = (
+                DiagnosticCode::from_str(code),
+                DiagnosticSeverityOverride::parse(value),
+            ) {
+                overrides.insert(code, severity);
+            }
+        }
+        Self { overrides }
+    }
+
+    pub fn get(&self, code: DiagnosticCode) -> Option<DiagnosticSeverityOverride> {
+        self.overrides.get(&code).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+}
+
 /// Default call site assumption when not specified
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum CallSiteDefault {
@@ -39,6 +297,11 @@ pub struct CrossFileConfig {
     pub max_revalidations_per_trigger: usize,
     /// Debounce delay for cross-file diagnostics fanout in milliseconds
     pub revalidation_debounce_ms: u64,
+    /// Debounce delay, in milliseconds, before publishing a batched wave of
+    /// diagnostics for many open documents revalidated together (e.g. after
+    /// a `didChangeConfiguration` or a bulk file-watcher event), distinct
+    /// from `revalidation_debounce_ms`'s per-edit cross-file fanout.
+    pub diagnostics_debounce_ms: u64,
     /// Whether undefined variable diagnostics are enabled
     pub undefined_variables_enabled: bool,
     /// Severity for missing file diagnostics (None = disabled)
@@ -65,6 +328,22 @@ pub struct CrossFileConfig {
     pub packages_r_path: Option<PathBuf>,
     /// Severity for missing package diagnostics (None = disabled)
     pub packages_missing_package_severity: Option<DiagnosticSeverity>,
+    /// Severity for namespace-qualified calls (`pkg::fn`) whose package hasn't
+    /// been loaded via `library()`/`require()` in the current file (None = disabled)
+    pub packages_unloaded_namespace_severity: Option<DiagnosticSeverity>,
+    /// Packages loaded purely for side effects (e.g. registering S3 methods)
+    /// whose `library()`/`require()` call should never be flagged unused,
+    /// even if none of their exports are referenced.
+    pub packages_side_effect_allowlist: Vec<String>,
+    /// When true, undefined-variable diagnostics skip *every* call's arguments
+    /// (the pre-allowlist blanket behavior), instead of only known
+    /// non-standard-evaluation functions. For users who want zero false
+    /// positives at the cost of missing more real bugs.
+    pub undefined_variables_nse_blanket_skip: bool,
+    /// Additional function names (bare, e.g. `subset`, or namespaced, e.g.
+    /// `dplyr::mutate`) to treat as non-standard-evaluation on top of the
+    /// built-in default set, when `undefined_variables_nse_blanket_skip` is false.
+    pub undefined_variables_nse_allowlist: Vec<String>,
     /// Severity for redundant directive diagnostics (when @lsp-source without line= targets
     /// same file as earlier source() call)
     /// _Requirements: 6.2_
@@ -80,6 +359,45 @@ pub struct CrossFileConfig {
     /// Indentation style for R code formatting
     /// _Requirements: 7.1, 7.2, 7.3, 7.4_
     pub indentation_style: IndentationStyle,
+    /// User-configured per-code severity remapping (or disabling) covering
+    /// any `DiagnosticCode`, including ones without a dedicated severity
+    /// field above. Empty by default.
+    pub diagnostic_severity_overrides: DiagnosticSeverityConfig,
+    /// Naming convention enforced by the incorrect-case diagnostic, when
+    /// `naming_convention_severity` is `Some`.
+    pub naming_convention: NamingConvention,
+    /// Severity for naming-convention-violation diagnostics (None = disabled).
+    /// Disabled by default since R codebases vary enough in style that
+    /// enforcing one without an explicit opt-in would be noisy.
+    pub naming_convention_severity: Option<DiagnosticSeverity>,
+    /// Severity for unsourced-file diagnostics, rust-analyzer's
+    /// `unlinked_file` ported to R (None = disabled). Disabled by default:
+    /// plenty of workspaces have standalone entry scripts (`app.R`, one-off
+    /// reports) that define top-level helpers on purpose without ever being
+    /// `source()`d themselves, so this needs an explicit opt-in to avoid
+    /// flagging them as orphans.
+    pub unsourced_file_severity: Option<DiagnosticSeverity>,
+    /// Whether to verify a closed file (and its parent directories) aren't
+    /// writable by other users before seeding the file cache from disk. See
+    /// [`super::permissions::FsPermissionChecker`]. Enabled by default;
+    /// disable for environments (CI, root-owned checkouts) where a
+    /// restrictive umask would make the check meaningless - or set the
+    /// [`super::permissions::DISABLE_PERMISSION_CHECKS_ENV_VAR`] escape hatch.
+    pub fs_permission_checks_enabled: bool,
+    /// Severity for untrusted-file-permissions diagnostics (None = disabled).
+    pub untrusted_file_severity: Option<DiagnosticSeverity>,
+    /// Confine parent/source resolution to this directory (`None` = no
+    /// confinement). Mirrors librsvg's `AllowedUrl`: a candidate parent whose
+    /// canonicalized `file://` path escapes the root, that isn't a `file:`
+    /// URI, or that is resolved against a root of `/` itself is rejected
+    /// rather than followed. See [`super::sandbox`].
+    pub project_root: Option<Url>,
+    /// Additional directories (parsed from a comma/whitespace-separated
+    /// project config value via [`super::configlist::parse_and_expand_search_roots`])
+    /// to retry a directive path against when it doesn't resolve relative to
+    /// the child's own directory - e.g. a monorepo's `R/`, `scripts/`, and
+    /// `inst/` directories. Checked in order; empty by default.
+    pub source_search_roots: Vec<PathBuf>,
 }
 
 impl Default for CrossFileConfig {
@@ -112,6 +430,7 @@ impl Default for CrossFileConfig {
             index_workspace: true,
             max_revalidations_per_trigger: 10,
             revalidation_debounce_ms: 200,
+            diagnostics_debounce_ms: 150,
             undefined_variables_enabled: true,
             missing_file_severity: Some(DiagnosticSeverity::WARNING),
             circular_dependency_severity: Some(DiagnosticSeverity::ERROR),
@@ -125,12 +444,24 @@ impl Default for CrossFileConfig {
             packages_additional_library_paths: Vec::new(),
             packages_r_path: None,
             packages_missing_package_severity: Some(DiagnosticSeverity::WARNING),
+            packages_unloaded_namespace_severity: Some(DiagnosticSeverity::WARNING),
+            packages_side_effect_allowlist: Vec::new(),
+            undefined_variables_nse_blanket_skip: false,
+            undefined_variables_nse_allowlist: Vec::new(),
             redundant_directive_severity: Some(DiagnosticSeverity::HINT),
             cache_metadata_max_entries: 1000,
             cache_file_content_max_entries: 500,
             cache_existence_max_entries: 2000,
             cache_workspace_index_max_entries: 5000,
             indentation_style: IndentationStyle::default(),
+            diagnostic_severity_overrides: DiagnosticSeverityConfig::default(),
+            naming_convention: NamingConvention::default(),
+            naming_convention_severity: None,
+            unsourced_file_severity: None,
+            fs_permission_checks_enabled: true,
+            untrusted_file_severity: Some(DiagnosticSeverity::WARNING),
+            project_root: None,
+            source_search_roots: Vec::new(),
         }
     }
 }
@@ -159,6 +490,7 @@ mod tests {
         assert!(config.index_workspace);
         assert_eq!(config.max_revalidations_per_trigger, 10);
         assert_eq!(config.revalidation_debounce_ms, 200);
+        assert_eq!(config.diagnostics_debounce_ms, 150);
         assert!(config.undefined_variables_enabled);
         // On-demand indexing defaults
         assert!(config.on_demand_indexing_enabled);
@@ -224,6 +556,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_undefined_variables_nse_defaults() {
+        // The NSE allowlist mode should be the default (not the old blanket-skip
+        // behavior), with no user-configured allowlist additions.
+        let config = CrossFileConfig::default();
+        assert!(!config.undefined_variables_nse_blanket_skip);
+        assert!(config.undefined_variables_nse_allowlist.is_empty());
+    }
+
     #[test]
     fn test_indentation_style_default_is_rstudio() {
         // Validates: Requirements 7.4
@@ -235,4 +576,101 @@ mod tests {
             "indentation_style should default to RStudio"
         );
     }
+
+    #[test]
+    fn test_fs_permission_checks_enabled_by_default() {
+        let config = CrossFileConfig::default();
+        assert!(config.fs_permission_checks_enabled);
+        assert_eq!(
+            config.untrusted_file_severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
+    }
+
+    #[test]
+    fn test_project_root_unset_by_default() {
+        let config = CrossFileConfig::default();
+        assert!(config.project_root.is_none());
+    }
+
+    #[test]
+    fn test_source_search_roots_empty_by_default() {
+        let config = CrossFileConfig::default();
+        assert!(config.source_search_roots.is_empty());
+    }
+
+    #[test]
+    fn test_naming_convention_disabled_by_default() {
+        let config = CrossFileConfig::default();
+        assert_eq!(config.naming_convention, NamingConvention::SnakeCase);
+        assert!(config.naming_convention_severity.is_none());
+    }
+
+    #[test]
+    fn test_naming_convention_parse() {
+        assert_eq!(
+            NamingConvention::parse("snake_case"),
+            Some(NamingConvention::SnakeCase)
+        );
+        assert_eq!(
+            NamingConvention::parse("camelCase"),
+            Some(NamingConvention::CamelCase)
+        );
+        assert_eq!(
+            NamingConvention::parse("dotted.case"),
+            Some(NamingConvention::DottedCase)
+        );
+        assert_eq!(NamingConvention::parse("PascalCase"), None);
+    }
+
+    #[test]
+    fn test_naming_convention_snake_case_matches() {
+        assert!(NamingConvention::SnakeCase.matches("total_count"));
+        assert!(NamingConvention::SnakeCase.matches("x"));
+        assert!(!NamingConvention::SnakeCase.matches("totalCount"));
+        assert!(!NamingConvention::SnakeCase.matches("TotalCount"));
+    }
+
+    #[test]
+    fn test_naming_convention_camel_case_matches() {
+        assert!(NamingConvention::CamelCase.matches("totalCount"));
+        assert!(NamingConvention::CamelCase.matches("x"));
+        assert!(!NamingConvention::CamelCase.matches("total_count"));
+        assert!(!NamingConvention::CamelCase.matches("TotalCount"));
+    }
+
+    #[test]
+    fn test_naming_convention_dotted_case_matches() {
+        assert!(NamingConvention::DottedCase.matches("total.count"));
+        assert!(!NamingConvention::DottedCase.matches("total_count"));
+        assert!(!NamingConvention::DottedCase.matches("TotalCount"));
+    }
+
+    #[test]
+    fn test_naming_convention_suggest_snake_case() {
+        assert_eq!(
+            NamingConvention::SnakeCase.suggest("totalCount"),
+            "total_count"
+        );
+        assert_eq!(
+            NamingConvention::SnakeCase.suggest("TotalCount"),
+            "total_count"
+        );
+    }
+
+    #[test]
+    fn test_naming_convention_suggest_camel_case() {
+        assert_eq!(
+            NamingConvention::CamelCase.suggest("total_count"),
+            "totalCount"
+        );
+    }
+
+    #[test]
+    fn test_naming_convention_suggest_dotted_case() {
+        assert_eq!(
+            NamingConvention::DottedCase.suggest("total_count"),
+            "total.count"
+        );
+    }
 }
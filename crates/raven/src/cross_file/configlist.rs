@@ -0,0 +1,171 @@
+//
+// cross_file/configlist.rs
+//
+// Comma/whitespace-separated config list parsing, in the spirit of
+// Mercurial's `ui.configlist`
+//
+// NOTE: `parse_config_list` and `expand_search_root` populate
+// `CrossFileConfig::source_search_roots` (see `cross_file::config`) from a
+// project config value. `resolve_parent_with_content` in
+// `cross_file::parent_resolve` retries a directive path against each of
+// those roots when the path doesn't resolve on its own.
+
+use std::path::{Path, PathBuf};
+
+/// Parse a comma/whitespace-separated list value the way Mercurial's
+/// `configlist` does: entries may be separated by commas or whitespace (or
+/// both), a double- or single-quoted entry may itself contain the separator
+/// characters without being split, and leading/trailing/duplicate
+/// separators (and the empty fields they'd otherwise produce) are ignored.
+pub fn parse_config_list(raw: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut chars = raw.chars().peekable();
+
+    loop {
+        // Skip leading separators (commas/whitespace) between entries.
+        while matches!(chars.peek(), Some(c) if *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let Some(&next) = chars.peek() else {
+            break;
+        };
+
+        let mut entry = String::new();
+        if next == '"' || next == '\'' {
+            let quote = next;
+            chars.next();
+            for c in chars.by_ref() {
+                if c == quote {
+                    break;
+                }
+                entry.push(c);
+            }
+        } else {
+            while matches!(chars.peek(), Some(c) if *c != ',' && !c.is_whitespace()) {
+                entry.push(chars.next().unwrap());
+            }
+        }
+
+        let trimmed = entry.trim();
+        if !trimmed.is_empty() {
+            entries.push(trimmed.to_string());
+        }
+    }
+
+    entries
+}
+
+/// Resolve a single parsed search-root entry against `project_root`:
+/// `~` (and `~/...`) expands against the `HOME` environment variable, an
+/// absolute path is used as-is, and anything else is joined onto
+/// `project_root`.
+pub fn expand_search_root(raw: &str, project_root: &Path) -> PathBuf {
+    if let Some(rest) = raw.strip_prefix('~') {
+        if let Ok(home) = std::env::var("HOME") {
+            let rest = rest.strip_prefix('/').unwrap_or(rest);
+            return if rest.is_empty() {
+                PathBuf::from(home)
+            } else {
+                PathBuf::from(home).join(rest)
+            };
+        }
+    }
+
+    let path = Path::new(raw);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        project_root.join(path)
+    }
+}
+
+/// Parse and expand a `source_search_roots` config value in one step.
+pub fn parse_and_expand_search_roots(raw: &str, project_root: &Path) -> Vec<PathBuf> {
+    parse_config_list(raw)
+        .into_iter()
+        .map(|entry| expand_search_root(&entry, project_root))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splits_on_commas_and_whitespace() {
+        assert_eq!(
+            parse_config_list("R, scripts inst"),
+            vec!["R".to_string(), "scripts".to_string(), "inst".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_tolerates_trailing_and_duplicate_separators() {
+        assert_eq!(
+            parse_config_list(" , R,, scripts ,  "),
+            vec!["R".to_string(), "scripts".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_empty_input_yields_empty_list() {
+        assert!(parse_config_list("").is_empty());
+        assert!(parse_config_list("   ,  ,").is_empty());
+    }
+
+    #[test]
+    fn test_honors_quoted_entries_containing_separators() {
+        assert_eq!(
+            parse_config_list(r#""my scripts, v2", R"#),
+            vec!["my scripts, v2".to_string(), "R".to_string()]
+        );
+        assert_eq!(
+            parse_config_list("'lib dir' scripts"),
+            vec!["lib dir".to_string(), "scripts".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_expands_relative_path_against_project_root() {
+        let root = Path::new("/workspace/project");
+        assert_eq!(
+            expand_search_root("scripts", root),
+            PathBuf::from("/workspace/project/scripts")
+        );
+    }
+
+    #[test]
+    fn test_expands_absolute_path_as_is() {
+        let root = Path::new("/workspace/project");
+        assert_eq!(
+            expand_search_root("/opt/shared-r-libs", root),
+            PathBuf::from("/opt/shared-r-libs")
+        );
+    }
+
+    #[test]
+    fn test_expands_tilde_against_home() {
+        std::env::set_var("HOME", "/home/tester");
+        let root = Path::new("/workspace/project");
+        assert_eq!(
+            expand_search_root("~/r-scripts", root),
+            PathBuf::from("/home/tester/r-scripts")
+        );
+        assert_eq!(expand_search_root("~", root), PathBuf::from("/home/tester"));
+    }
+
+    #[test]
+    fn test_parse_and_expand_combines_both_steps() {
+        std::env::set_var("HOME", "/home/tester");
+        let root = Path::new("/workspace/project");
+        let roots = parse_and_expand_search_roots("R, ~/shared, /opt/libs", root);
+        assert_eq!(
+            roots,
+            vec![
+                PathBuf::from("/workspace/project/R"),
+                PathBuf::from("/home/tester/shared"),
+                PathBuf::from("/opt/libs"),
+            ]
+        );
+    }
+}
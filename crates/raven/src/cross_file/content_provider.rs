@@ -6,12 +6,14 @@
 
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
 use tower_lsp::lsp_types::Url;
 
 use super::file_cache::CrossFileFileCache;
 use super::scope::ScopeArtifacts;
 use super::types::CrossFileMetadata;
+use super::vfs::{real_vfs, Vfs};
 use super::workspace_index::CrossFileWorkspaceIndex;
 
 /// Trait for content providers that respect open-docs-authoritative rule
@@ -37,7 +39,8 @@ pub trait DocumentContent {
 /// Unified content provider with precedence:
 /// 1. Open document (in-memory)
 /// 2. Workspace index (cached)
-/// 3. Disk file cache (cached-only; no synchronous disk I/O)
+/// 3. Disk file cache, primary tier (cached-only; no synchronous disk I/O)
+/// 4. Disk file cache, secondary tier, if configured (same constraint)
 pub struct CrossFileContentProvider<'a, D: DocumentContent> {
     /// Open documents (authoritative)
     pub open_documents: &'a HashMap<Url, D>,
@@ -45,6 +48,13 @@ pub struct CrossFileContentProvider<'a, D: DocumentContent> {
     pub workspace_index: &'a CrossFileWorkspaceIndex,
     /// Disk file cache for on-demand reads
     pub file_cache: &'a CrossFileFileCache,
+    /// Optional lower cache tier consulted on a primary-tier miss, e.g. a
+    /// larger/slower cache shared across workspaces. A hit here is promoted
+    /// into `file_cache` so it's served from the primary tier next time.
+    pub secondary_cache: Option<&'a CrossFileFileCache>,
+    /// Filesystem backend for existence checks (disk by default; can be
+    /// swapped for an in-memory double in tests)
+    vfs: Arc<dyn Vfs>,
 }
 
 impl<'a, D: DocumentContent> CrossFileContentProvider<'a, D> {
@@ -52,11 +62,41 @@ impl<'a, D: DocumentContent> CrossFileContentProvider<'a, D> {
         open_documents: &'a HashMap<Url, D>,
         workspace_index: &'a CrossFileWorkspaceIndex,
         file_cache: &'a CrossFileFileCache,
+    ) -> Self {
+        Self::with_vfs(open_documents, workspace_index, file_cache, real_vfs())
+    }
+
+    /// Construct a provider backed by a custom [`Vfs`] (e.g. an in-memory
+    /// double in tests).
+    pub fn with_vfs(
+        open_documents: &'a HashMap<Url, D>,
+        workspace_index: &'a CrossFileWorkspaceIndex,
+        file_cache: &'a CrossFileFileCache,
+        vfs: Arc<dyn Vfs>,
     ) -> Self {
         Self {
             open_documents,
             workspace_index,
             file_cache,
+            secondary_cache: None,
+            vfs,
+        }
+    }
+
+    /// Construct a provider with a secondary cache tier, consulted on a
+    /// primary-tier miss and promoted into the primary tier on hit.
+    pub fn with_secondary_cache(
+        open_documents: &'a HashMap<Url, D>,
+        workspace_index: &'a CrossFileWorkspaceIndex,
+        file_cache: &'a CrossFileFileCache,
+        secondary_cache: &'a CrossFileFileCache,
+    ) -> Self {
+        Self {
+            open_documents,
+            workspace_index,
+            file_cache,
+            secondary_cache: Some(secondary_cache),
+            vfs: real_vfs(),
         }
     }
 
@@ -64,6 +104,16 @@ impl<'a, D: DocumentContent> CrossFileContentProvider<'a, D> {
     pub fn is_open(&self, uri: &Url) -> bool {
         self.open_documents.contains_key(uri)
     }
+
+    /// Check if a file exists, dispatching through this provider's `Vfs`.
+    pub fn file_exists(&self, uri: &Url) -> bool {
+        file_exists(self.vfs.as_ref(), uri)
+    }
+
+    /// Check if a path exists, dispatching through this provider's `Vfs`.
+    pub fn path_exists(&self, path: &Path) -> bool {
+        path_exists(self.vfs.as_ref(), path)
+    }
 }
 
 impl<'a, D: DocumentContent> ContentProvider for CrossFileContentProvider<'a, D> {
@@ -73,12 +123,23 @@ impl<'a, D: DocumentContent> ContentProvider for CrossFileContentProvider<'a, D>
             return Some(doc.content());
         }
 
-        // 2. Try workspace index
-        // Note: We don't have content in the index, only metadata/artifacts
-        // So we fall through to file cache
+        // 2. Try workspace index, if this entry was populated with content
+        // (e.g. hydrated from the disk cache)
+        if let Some(content) = self.workspace_index.get_content(uri) {
+            return Some(content);
+        }
+
+        // 3. Try the primary file cache tier (no synchronous disk I/O)
+        if let Some(content) = self.file_cache.get(uri) {
+            return Some(content);
+        }
 
-        // 3. Try file cache (no synchronous disk I/O)
-        self.file_cache.get(uri)
+        // 4. Fall through to the secondary tier, if configured, promoting a
+        // hit back into the primary tier so it's served from there next time.
+        let secondary = self.secondary_cache?;
+        let (snapshot, content) = secondary.get_with_snapshot(uri)?;
+        self.file_cache.insert(uri.clone(), snapshot, content.clone());
+        Some(content)
     }
 
     fn get_metadata(&self, uri: &Url) -> Option<CrossFileMetadata> {
@@ -117,23 +178,24 @@ impl<'a, D: DocumentContent> ContentProvider for CrossFileContentProvider<'a, D>
     }
 }
 
-/// Check if a file exists on disk.
-/// Converts URI to file path and checks filesystem existence.
-pub fn file_exists(uri: &Url) -> bool {
-    uri.to_file_path().map(|p| p.exists()).unwrap_or(false)
+/// Check if a file exists, dispatching through `vfs`.
+/// Converts URI to file path and checks existence.
+pub fn file_exists(vfs: &dyn Vfs, uri: &Url) -> bool {
+    uri.to_file_path()
+        .map(|p| vfs.exists(&p))
+        .unwrap_or(false)
 }
 
-/// Check if a path exists on disk.
-/// Direct filesystem existence check for Path objects.
-pub fn path_exists(path: &Path) -> bool {
-    path.exists()
+/// Check if a path exists, dispatching through `vfs`.
+pub fn path_exists(vfs: &dyn Vfs, path: &Path) -> bool {
+    vfs.exists(path)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::cross_file::file_cache::FileSnapshot;
-    use std::io::Write;
+    use crate::cross_file::vfs::InMemoryVfs;
     use tempfile::NamedTempFile;
 
     struct MockDocument {
@@ -192,18 +254,16 @@ mod tests {
 
     #[test]
     fn test_reads_from_cache_only() {
+        // Runs entirely in memory: no disk I/O anywhere in this test.
         let open_docs: HashMap<Url, MockDocument> = HashMap::new();
         let index = CrossFileWorkspaceIndex::new();
         let cache = CrossFileFileCache::new();
-        // Create a temp file and seed the cache
-        // Create a temp file
-        let mut temp = NamedTempFile::new().unwrap();
-        writeln!(temp, "disk content").unwrap();
-        let uri = Url::from_file_path(temp.path()).unwrap();
-        let content = std::fs::read_to_string(temp.path()).unwrap();
-        let metadata = std::fs::metadata(temp.path()).unwrap();
-        let snapshot = FileSnapshot::with_content_hash(&metadata, &content);
-        cache.insert(uri.clone(), snapshot, content.clone());
+        let uri = test_uri("seeded.R");
+        let snapshot = FileSnapshot::from_vfs_metadata(&super::super::vfs::VfsMetadata {
+            modified: std::time::SystemTime::UNIX_EPOCH,
+            len: "disk content".len() as u64,
+        });
+        cache.insert(uri.clone(), snapshot, "disk content".to_string());
 
         let provider = CrossFileContentProvider::new(&open_docs, &index, &cache);
 
@@ -214,11 +274,86 @@ mod tests {
     }
 
     #[test]
-    fn test_file_exists() {
+    fn test_reads_from_workspace_index_content_before_file_cache() {
+        let open_docs: HashMap<Url, MockDocument> = HashMap::new();
+        let index = CrossFileWorkspaceIndex::new();
+        let cache = CrossFileFileCache::new();
+        let uri = test_uri("indexed.R");
+        let open_documents = std::collections::HashSet::new();
+        index.update_from_disk_with_content(
+            &uri,
+            &open_documents,
+            FileSnapshot::from_vfs_metadata(&super::super::vfs::VfsMetadata {
+                modified: std::time::SystemTime::UNIX_EPOCH,
+                len: "indexed content".len() as u64,
+            }),
+            CrossFileMetadata::default(),
+            ScopeArtifacts::default(),
+            Some("indexed content".to_string()),
+        );
+
+        let provider = CrossFileContentProvider::new(&open_docs, &index, &cache);
+
+        assert_eq!(provider.get_content(&uri), Some("indexed content".to_string()));
+    }
+
+    #[test]
+    fn test_falls_through_to_secondary_cache_and_promotes() {
+        let open_docs: HashMap<Url, MockDocument> = HashMap::new();
+        let index = CrossFileWorkspaceIndex::new();
+        let primary = CrossFileFileCache::new();
+        let secondary = CrossFileFileCache::new();
+        let uri = test_uri("seeded.R");
+        let snapshot = FileSnapshot::from_vfs_metadata(&super::super::vfs::VfsMetadata {
+            modified: std::time::SystemTime::UNIX_EPOCH,
+            len: "secondary content".len() as u64,
+        });
+        secondary.insert(uri.clone(), snapshot, "secondary content".to_string());
+
+        let provider =
+            CrossFileContentProvider::with_secondary_cache(&open_docs, &index, &primary, &secondary);
+
+        assert_eq!(
+            provider.get_content(&uri),
+            Some("secondary content".to_string())
+        );
+        // A hit in the secondary tier should be promoted into the primary.
+        assert_eq!(primary.get(&uri), Some("secondary content".to_string()));
+    }
+
+    #[test]
+    fn test_no_secondary_cache_misses_cleanly() {
+        let open_docs: HashMap<Url, MockDocument> = HashMap::new();
+        let index = CrossFileWorkspaceIndex::new();
+        let cache = CrossFileFileCache::new();
+        let provider = CrossFileContentProvider::new(&open_docs, &index, &cache);
+
+        assert_eq!(provider.get_content(&test_uri("missing.R")), None);
+    }
+
+    #[test]
+    fn test_file_exists_in_memory() {
+        let open_docs: HashMap<Url, MockDocument> = HashMap::new();
+        let index = CrossFileWorkspaceIndex::new();
+        let cache = CrossFileFileCache::new();
+        let vfs: Arc<dyn Vfs> = Arc::new(InMemoryVfs::new().with_file("/project/a.R", "x <- 1"));
+
+        let provider =
+            CrossFileContentProvider::with_vfs(&open_docs, &index, &cache, vfs);
+
+        assert!(provider.file_exists(&test_uri("project/a.R")));
+        assert!(!provider.file_exists(&test_uri("project/missing.R")));
+        assert!(provider.path_exists(Path::new("/project/a.R")));
+        assert!(!provider.path_exists(Path::new("/project/missing.R")));
+    }
+
+    #[test]
+    fn test_file_exists_on_disk() {
         let temp = NamedTempFile::new().unwrap();
         let uri = Url::from_file_path(temp.path()).unwrap();
+        let vfs = super::super::vfs::real_vfs();
 
-        assert!(file_exists(&uri));
-        assert!(!file_exists(&test_uri("nonexistent.R")));
+        assert!(file_exists(vfs.as_ref(), &uri));
+        assert!(!file_exists(vfs.as_ref(), &test_uri("nonexistent.R")));
     }
 }
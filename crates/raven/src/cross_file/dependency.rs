@@ -5,6 +5,7 @@
 //
 
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, Url};
 
 use super::parent_resolve::{infer_call_site_from_parent, resolve_match_pattern};
@@ -545,6 +546,12 @@ pub struct DependencyGraph {
     forward: HashMap<Url, Vec<DependencyEdge>>,
     /// Reverse lookup: child URI -> edges from parents
     backward: HashMap<Url, Vec<DependencyEdge>>,
+    /// Set by `update_file`/`remove_file` whenever they run, cleared by `take_dirty()`.
+    /// Lets a consumer that periodically does expensive whole-graph work (e.g.
+    /// `raven/checkWorkspace`) tell whether anything changed since it last checked,
+    /// without re-walking the graph itself or holding the surrounding `WorldState`
+    /// lock for the duration of that work.
+    dirty: AtomicBool,
 }
 
 impl DependencyGraph {
@@ -552,6 +559,12 @@ impl DependencyGraph {
         Self::default()
     }
 
+    /// Returns whether the graph has changed since the last call to `take_dirty()`,
+    /// clearing the flag as it does.
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::SeqCst)
+    }
+
     /// Update edges for a file based on extracted metadata.
     /// Processes both forward sources and backward directives.
     /// Returns diagnostics for directive-vs-AST conflicts.
@@ -577,6 +590,8 @@ impl DependencyGraph {
     where
         F: Fn(&Url) -> Option<String>,
     {
+        self.dirty.store(true, Ordering::SeqCst);
+
         let mut result = UpdateResult::default();
 
         // Build PathContext for this file (includes working_directory from metadata)
@@ -750,12 +765,17 @@ impl DependencyGraph {
                                         .map(|s| s.line))
                                     .unwrap_or(0);
 
+                                let (code, code_description) = crate::handlers::diagnostic_code(
+                                    crate::handlers::diagnostic_codes::DIRECTIVE_SUPPRESSES_CALL,
+                                );
                                 result.diagnostics.push(Diagnostic {
                                     range: Range {
                                         start: Position { line: diag_line, character: 0 },
                                         end: Position { line: diag_line, character: u32::MAX },
                                     },
                                     severity: Some(DiagnosticSeverity::WARNING),
+                                    code,
+                                    code_description,
                                     message: format!(
                                         "Directive without call site suppresses AST-detected source() call to '{}' at line {}. Consider adding line= or match= to the directive.",
                                         to_uri.path_segments().and_then(|mut s| s.next_back()).unwrap_or(""),
@@ -841,6 +861,7 @@ impl DependencyGraph {
 
     /// Remove all edges involving a file
     pub fn remove_file(&mut self, uri: &Url) {
+        self.dirty.store(true, Ordering::SeqCst);
         // Remove edges where this file is the parent
         self.remove_forward_edges(uri);
         // Remove edges where this file is the child
@@ -11,6 +11,15 @@ use super::types::{
     BackwardDirective, CallSiteSpec, CrossFileMetadata, DeclaredSymbol, ForwardSource,
 };
 
+/// Split a `@lsp-allow` directive's argument list on commas, trimming
+/// whitespace and dropping empty entries.
+fn parse_allow_codes(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|code| code.trim().to_string())
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
 /// Compiled regex patterns for directive parsing
 struct DirectivePatterns {
     backward: Regex,
@@ -20,6 +29,7 @@ struct DirectivePatterns {
     ignore_next: Regex,
     declare_var: Regex,
     declare_func: Regex,
+    allow: Regex,
 }
 
 /// Extract path from capture groups (double-quoted, single-quoted, or unquoted)
@@ -92,6 +102,14 @@ fn patterns() -> &'static DirectivePatterns {
             declare_func: Regex::new(
                 r#"#\s*@lsp-(?:declare-function|declare-func|function|func)\s*:?\s*(?:"([^"]+)"|'([^']+)'|(\S+))"#
             ).unwrap(),
+            // @lsp-allow: <code>[, <code>...] - suppresses diagnostics with a matching
+            // `code` (see `handlers::diagnostic_codes`). Trailing on a line that already
+            // has code, it targets that same line; on its own line it's line-scoped
+            // (targeting the next line) if real code precedes it in the file, or
+            // file-scoped otherwise - mirroring the @lsp-ignore / @lsp-ignore-next split.
+            allow: Regex::new(
+                r"#\s*@lsp-allow\s*:?\s*(.+?)\s*$"
+            ).unwrap(),
         }
     })
 }
@@ -102,6 +120,9 @@ pub fn parse_directives(content: &str) -> CrossFileMetadata {
     log::trace!("Starting directive parsing");
     let patterns = patterns();
     let mut meta = CrossFileMetadata::default();
+    // Tracks whether a real (non-blank, non-comment) code line has been seen yet,
+    // used to decide whether an @lsp-allow directive is file-scoped or line-scoped.
+    let mut seen_code_line = false;
 
     for (line_num, line) in content.lines().enumerate() {
         let line_num = line_num as u32;
@@ -201,6 +222,46 @@ pub fn parse_directives(content: &str) -> CrossFileMetadata {
             continue;
         }
 
+        // Check @lsp-allow directive
+        if let Some(caps) = patterns.allow.captures(line) {
+            let codes = parse_allow_codes(&caps[1]);
+            let prefix = line[..caps.get(0).unwrap().start()].trim();
+            if !prefix.is_empty() && !prefix.starts_with('#') {
+                // Trailing on a line that already has real code (e.g.
+                // `y <- 2  # @lsp-allow: ...`): suppress on this same line
+                // rather than the next one.
+                log::trace!(
+                    "  Parsed trailing @lsp-allow directive at line {}: codes={:?}",
+                    line_num,
+                    codes
+                );
+                meta.allowed_codes_by_line
+                    .entry(line_num)
+                    .or_default()
+                    .extend(codes);
+                seen_code_line = true;
+            } else if seen_code_line {
+                log::trace!(
+                    "  Parsed line-scoped @lsp-allow directive at line {}: codes={:?} (targets line {})",
+                    line_num,
+                    codes,
+                    line_num + 1
+                );
+                meta.allowed_codes_by_line
+                    .entry(line_num + 1)
+                    .or_default()
+                    .extend(codes);
+            } else {
+                log::trace!(
+                    "  Parsed file-scoped @lsp-allow directive at line {}: codes={:?}",
+                    line_num,
+                    codes
+                );
+                meta.allowed_codes_file.extend(codes);
+            }
+            continue;
+        }
+
         // Check variable declaration directives (@lsp-var, @lsp-variable, etc.)
         // Requirements: 1.1, 1.2, 1.3, 1.4, 1.5
         if let Some(caps) = patterns.declare_var.captures(line) {
@@ -236,16 +297,28 @@ pub fn parse_directives(content: &str) -> CrossFileMetadata {
             }
             continue;
         }
+
+        // Any non-blank, non-comment line from here on counts as real code, so a
+        // later @lsp-allow directive is line-scoped rather than file-scoped.
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            seen_code_line = true;
+        }
     }
 
+    meta.not_sourced_by = super::negative_directives::parse_not_sourced_by(content);
+
     log::trace!(
-        "Completed directive parsing: {} backward directives, {} forward directives, working_dir={:?}, {} ignored lines, {} declared vars, {} declared funcs",
+        "Completed directive parsing: {} backward directives, {} forward directives, working_dir={:?}, {} ignored lines, {} declared vars, {} declared funcs, {} file-allowed codes, {} line-allowed lines, {} not-sourced-by negations",
         meta.sourced_by.len(),
         meta.sources.len(),
         meta.working_directory,
         meta.ignored_lines.len() + meta.ignored_next_lines.len(),
         meta.declared_variables.len(),
-        meta.declared_functions.len()
+        meta.declared_functions.len(),
+        meta.allowed_codes_file.len(),
+        meta.allowed_codes_by_line.len(),
+        meta.not_sourced_by.len()
     );
 
     meta
@@ -257,6 +330,17 @@ pub fn is_line_ignored(metadata: &CrossFileMetadata, line: u32) -> bool {
     metadata.ignored_lines.contains(&line) || metadata.ignored_next_lines.contains(&line)
 }
 
+/// Check if a diagnostic `code` on `line` is suppressed by an `@lsp-allow`
+/// directive, either file-scoped (applies everywhere) or line-scoped (applies
+/// only to the line immediately following the directive).
+pub fn is_diagnostic_allowed(metadata: &CrossFileMetadata, line: u32, code: &str) -> bool {
+    metadata.allowed_codes_file.contains(code)
+        || metadata
+            .allowed_codes_by_line
+            .get(&line)
+            .is_some_and(|codes| codes.contains(code))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -390,6 +474,58 @@ mod tests {
         assert!(is_line_ignored(&meta, 3)); // y <- 2 (next line after ignore-next)
     }
 
+    #[test]
+    fn test_allow_directive_file_scoped_before_any_code() {
+        let content = "# @lsp-allow: raven::else-on-new-line\nif (x) {y}\nelse {z}";
+        let meta = parse_directives(content);
+        assert!(meta.allowed_codes_file.contains("raven::else-on-new-line"));
+        assert!(meta.allowed_codes_by_line.is_empty());
+    }
+
+    #[test]
+    fn test_allow_directive_line_scoped_after_code() {
+        let content = "x <- 1\n# @lsp-allow: raven::else-on-new-line\ny <- 2";
+        let meta = parse_directives(content);
+        assert!(meta.allowed_codes_file.is_empty());
+        assert!(meta
+            .allowed_codes_by_line
+            .get(&2)
+            .is_some_and(|codes| codes.contains("raven::else-on-new-line")));
+    }
+
+    #[test]
+    fn test_allow_directive_multiple_codes() {
+        let content =
+            "x <- 1\n# @lsp-allow: raven::else-on-new-line, raven::unused-library\ny <- 2";
+        let meta = parse_directives(content);
+        let codes = meta.allowed_codes_by_line.get(&2).unwrap();
+        assert!(codes.contains("raven::else-on-new-line"));
+        assert!(codes.contains("raven::unused-library"));
+    }
+
+    #[test]
+    fn test_allow_directive_trailing_targets_same_line() {
+        let content = "x <- 1\ny <- 2  # @lsp-allow: raven::undefined-variable";
+        let meta = parse_directives(content);
+        assert!(meta.allowed_codes_file.is_empty());
+        assert!(meta
+            .allowed_codes_by_line
+            .get(&1)
+            .is_some_and(|codes| codes.contains("raven::undefined-variable")));
+        assert!(is_diagnostic_allowed(&meta, 1, "raven::undefined-variable"));
+    }
+
+    #[test]
+    fn test_is_diagnostic_allowed() {
+        let content =
+            "x <- 1\n# @lsp-allow: raven::else-on-new-line\ny <- 2\nz <- 3\n# @lsp-allow: raven::unused-library";
+        let meta = parse_directives(content);
+        assert!(is_diagnostic_allowed(&meta, 2, "raven::else-on-new-line"));
+        assert!(!is_diagnostic_allowed(&meta, 3, "raven::else-on-new-line"));
+        // file-scoped directive appearing after code is still line-scoped, not global
+        assert!(!is_diagnostic_allowed(&meta, 0, "raven::unused-library"));
+    }
+
     #[test]
     fn test_multiple_directives() {
         let content = r#"# @lsp-sourced-by ../main.R line=10
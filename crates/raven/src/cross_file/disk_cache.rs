@@ -0,0 +1,526 @@
+//
+// cross_file/disk_cache.rs
+//
+// On-disk persistent store for closed-file content and cross-file artifacts
+//
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use super::file_cache::FileSnapshot;
+use super::scope::{FunctionScopeTree, ScopeArtifacts, ScopeEvent, ScopedSymbol, SymbolKind};
+use super::types::CrossFileMetadata;
+
+/// Wire-format mirror of [`FileSnapshot`]; `SystemTime` has no direct serde
+/// support, so `mtime` round-trips as milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedSnapshot {
+    mtime_millis: u64,
+    size: u64,
+    content_hash: Option<u64>,
+}
+
+impl From<&FileSnapshot> for PersistedSnapshot {
+    fn from(snapshot: &FileSnapshot) -> Self {
+        Self {
+            mtime_millis: snapshot
+                .mtime
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            size: snapshot.size,
+            content_hash: snapshot.content_hash,
+        }
+    }
+}
+
+impl From<&PersistedSnapshot> for FileSnapshot {
+    fn from(persisted: &PersistedSnapshot) -> Self {
+        Self {
+            mtime: UNIX_EPOCH + Duration::from_millis(persisted.mtime_millis),
+            size: persisted.size,
+            content_hash: persisted.content_hash,
+        }
+    }
+}
+
+/// Wire-format mirror of [`SymbolKind`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum PersistedSymbolKind {
+    Function,
+    Variable,
+    Parameter,
+}
+
+impl From<SymbolKind> for PersistedSymbolKind {
+    fn from(kind: SymbolKind) -> Self {
+        match kind {
+            SymbolKind::Function => Self::Function,
+            SymbolKind::Variable => Self::Variable,
+            SymbolKind::Parameter => Self::Parameter,
+        }
+    }
+}
+
+impl From<PersistedSymbolKind> for SymbolKind {
+    fn from(kind: PersistedSymbolKind) -> Self {
+        match kind {
+            PersistedSymbolKind::Function => Self::Function,
+            PersistedSymbolKind::Variable => Self::Variable,
+            PersistedSymbolKind::Parameter => Self::Parameter,
+        }
+    }
+}
+
+/// Wire-format mirror of [`ScopedSymbol`]; `Arc<str>`/`Url` don't implement
+/// serde traits directly, so `name` and `source_uri` round-trip as `String`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedScopedSymbol {
+    name: String,
+    kind: PersistedSymbolKind,
+    source_uri: String,
+    defined_line: u32,
+    defined_column: u32,
+    signature: Option<String>,
+    is_declared: bool,
+}
+
+fn persist_scoped_symbol(symbol: &ScopedSymbol) -> PersistedScopedSymbol {
+    PersistedScopedSymbol {
+        name: symbol.name.to_string(),
+        kind: symbol.kind.into(),
+        source_uri: symbol.source_uri.to_string(),
+        defined_line: symbol.defined_line,
+        defined_column: symbol.defined_column,
+        signature: symbol.signature.clone(),
+        is_declared: symbol.is_declared,
+    }
+}
+
+/// Reconstruct a [`ScopedSymbol`], or `None` if `source_uri` isn't a valid
+/// URL - treated the same as any other corrupt-entry cache miss.
+fn hydrate_scoped_symbol(persisted: PersistedScopedSymbol) -> Option<ScopedSymbol> {
+    Some(ScopedSymbol {
+        name: Arc::from(persisted.name.as_str()),
+        kind: persisted.kind.into(),
+        source_uri: Url::parse(&persisted.source_uri).ok()?,
+        defined_line: persisted.defined_line,
+        defined_column: persisted.defined_column,
+        signature: persisted.signature,
+        is_declared: persisted.is_declared,
+    })
+}
+
+/// Wire-format mirror of [`ScopeEvent`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum PersistedScopeEvent {
+    Def {
+        line: u32,
+        column: u32,
+        symbol: PersistedScopedSymbol,
+    },
+    Source {
+        line: u32,
+        column: u32,
+        source: super::types::ForwardSource,
+    },
+    FunctionScope {
+        start_line: u32,
+        start_column: u32,
+        end_line: u32,
+        end_column: u32,
+        parameters: Vec<PersistedScopedSymbol>,
+    },
+    Removal {
+        line: u32,
+        column: u32,
+        symbols: Vec<String>,
+        function_scope: Option<(u32, u32, u32, u32)>,
+    },
+    PackageLoad {
+        line: u32,
+        column: u32,
+        package: String,
+        function_scope: Option<super::scope::FunctionScopeInterval>,
+    },
+    Declaration {
+        line: u32,
+        column: u32,
+        symbol: PersistedScopedSymbol,
+    },
+}
+
+fn persist_scope_event(event: &ScopeEvent) -> PersistedScopeEvent {
+    match event {
+        ScopeEvent::Def { line, column, symbol } => PersistedScopeEvent::Def {
+            line: *line,
+            column: *column,
+            symbol: persist_scoped_symbol(symbol),
+        },
+        ScopeEvent::Source { line, column, source } => PersistedScopeEvent::Source {
+            line: *line,
+            column: *column,
+            source: source.clone(),
+        },
+        ScopeEvent::FunctionScope {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            parameters,
+        } => PersistedScopeEvent::FunctionScope {
+            start_line: *start_line,
+            start_column: *start_column,
+            end_line: *end_line,
+            end_column: *end_column,
+            parameters: parameters.iter().map(persist_scoped_symbol).collect(),
+        },
+        ScopeEvent::Removal {
+            line,
+            column,
+            symbols,
+            function_scope,
+        } => PersistedScopeEvent::Removal {
+            line: *line,
+            column: *column,
+            symbols: symbols.clone(),
+            function_scope: *function_scope,
+        },
+        ScopeEvent::PackageLoad {
+            line,
+            column,
+            package,
+            function_scope,
+        } => PersistedScopeEvent::PackageLoad {
+            line: *line,
+            column: *column,
+            package: package.clone(),
+            function_scope: *function_scope,
+        },
+        ScopeEvent::Declaration { line, column, symbol } => PersistedScopeEvent::Declaration {
+            line: *line,
+            column: *column,
+            symbol: persist_scoped_symbol(symbol),
+        },
+    }
+}
+
+/// Reconstruct a [`ScopeEvent`], or `None` if it embeds a symbol whose
+/// `source_uri` failed to parse.
+fn hydrate_scope_event(event: PersistedScopeEvent) -> Option<ScopeEvent> {
+    Some(match event {
+        PersistedScopeEvent::Def { line, column, symbol } => ScopeEvent::Def {
+            line,
+            column,
+            symbol: hydrate_scoped_symbol(symbol)?,
+        },
+        PersistedScopeEvent::Source { line, column, source } => {
+            ScopeEvent::Source { line, column, source }
+        }
+        PersistedScopeEvent::FunctionScope {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            parameters,
+        } => ScopeEvent::FunctionScope {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            parameters: parameters.into_iter().filter_map(hydrate_scoped_symbol).collect(),
+        },
+        PersistedScopeEvent::Removal {
+            line,
+            column,
+            symbols,
+            function_scope,
+        } => ScopeEvent::Removal {
+            line,
+            column,
+            symbols,
+            function_scope,
+        },
+        PersistedScopeEvent::PackageLoad {
+            line,
+            column,
+            package,
+            function_scope,
+        } => ScopeEvent::PackageLoad {
+            line,
+            column,
+            package,
+            function_scope,
+        },
+        PersistedScopeEvent::Declaration { line, column, symbol } => ScopeEvent::Declaration {
+            line,
+            column,
+            symbol: hydrate_scoped_symbol(symbol)?,
+        },
+    })
+}
+
+/// Wire-format mirror of [`ScopeArtifacts`]. `FunctionScopeTree` isn't
+/// serde-derivable directly (it's a private interval-tree node graph), so it
+/// round-trips through [`FunctionScopeTree::intervals`]/[`FunctionScopeTree::from_scopes`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedScopeArtifacts {
+    exported_interface: Vec<(String, PersistedScopedSymbol)>,
+    timeline: Vec<PersistedScopeEvent>,
+    interface_hash: u64,
+    function_scope_intervals: Vec<(u32, u32, u32, u32)>,
+}
+
+fn persist_scope_artifacts(artifacts: &ScopeArtifacts) -> PersistedScopeArtifacts {
+    PersistedScopeArtifacts {
+        exported_interface: artifacts
+            .exported_interface
+            .iter()
+            .map(|(name, symbol)| (name.to_string(), persist_scoped_symbol(symbol)))
+            .collect(),
+        timeline: artifacts.timeline.iter().map(persist_scope_event).collect(),
+        interface_hash: artifacts.interface_hash,
+        function_scope_intervals: artifacts.function_scope_tree.intervals(),
+    }
+}
+
+/// Entries whose embedded URIs fail to parse are dropped (logged at trace
+/// level) rather than failing hydration for the whole file.
+fn hydrate_scope_artifacts(persisted: PersistedScopeArtifacts) -> ScopeArtifacts {
+    let exported_interface = persisted
+        .exported_interface
+        .into_iter()
+        .filter_map(|(name, symbol)| {
+            let symbol = hydrate_scoped_symbol(symbol)?;
+            Some((Arc::<str>::from(name.as_str()), symbol))
+        })
+        .collect();
+    let timeline = persisted
+        .timeline
+        .into_iter()
+        .filter_map(hydrate_scope_event)
+        .collect();
+    ScopeArtifacts {
+        exported_interface,
+        timeline,
+        interface_hash: persisted.interface_hash,
+        function_scope_tree: FunctionScopeTree::from_scopes(&persisted.function_scope_intervals),
+    }
+}
+
+/// Full persisted record for a single closed file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedEntry {
+    snapshot: PersistedSnapshot,
+    content: String,
+    metadata: CrossFileMetadata,
+    artifacts: PersistedScopeArtifacts,
+}
+
+/// Persistent keyed store of closed-file content plus serialized cross-file
+/// metadata/artifacts, modeled on Deno's `DiskCache`/`DenoDir` design: each
+/// entry is keyed by a hash of its [`Url`] and validated against the current
+/// on-disk [`FileSnapshot`] before being trusted.
+///
+/// Lets [`super::workspace_index::CrossFileWorkspaceIndex`] hydrate at
+/// startup instead of rebuilding from scratch every session, so the first
+/// cross-file query after opening a workspace doesn't have to wait on a full
+/// re-parse of every file in the tree.
+pub struct DiskCache {
+    root: PathBuf,
+}
+
+impl DiskCache {
+    /// Construct a store rooted at `root` (created lazily on first write).
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn key_for(uri: &Url) -> String {
+        let mut hasher = DefaultHasher::new();
+        uri.as_str().hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    fn entry_path(&self, uri: &Url) -> PathBuf {
+        self.root.join(format!("{}.json", Self::key_for(uri)))
+    }
+
+    /// Persist `content`/`metadata`/`artifacts` for `uri`. Best-effort: a
+    /// write failure is logged and swallowed, since a missing entry just
+    /// means the next hydration re-parses the file instead of loading it.
+    pub fn put(
+        &self,
+        uri: &Url,
+        snapshot: &FileSnapshot,
+        content: &str,
+        metadata: &CrossFileMetadata,
+        artifacts: &ScopeArtifacts,
+    ) {
+        let entry = PersistedEntry {
+            snapshot: PersistedSnapshot::from(snapshot),
+            content: content.to_string(),
+            metadata: metadata.clone(),
+            artifacts: persist_scope_artifacts(artifacts),
+        };
+        if let Err(err) = self.write_entry(uri, &entry) {
+            log::warn!("Failed to persist disk cache entry for {}: {}", uri, err);
+        }
+    }
+
+    fn write_entry(&self, uri: &Url, entry: &PersistedEntry) -> std::io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        let json = serde_json::to_vec(entry)?;
+        // Write to a temp file and rename, so a crash mid-write can't leave a
+        // half-written entry that looks valid (but isn't) on the next load.
+        let final_path = self.entry_path(uri);
+        let tmp_path = final_path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, &final_path)?;
+        Ok(())
+    }
+
+    /// Load the persisted entry for `uri`, validating it against
+    /// `current_snapshot`. Returns `None` on a miss, a stale entry (mtime,
+    /// size, or content hash no longer match - see
+    /// [`FileSnapshot::matches_disk`]), or a read/parse error - all treated
+    /// the same as a miss, so the caller just falls back to re-parsing.
+    pub fn get(
+        &self,
+        uri: &Url,
+        current_snapshot: &FileSnapshot,
+    ) -> Option<(FileSnapshot, String, CrossFileMetadata, ScopeArtifacts)> {
+        let bytes = fs::read(self.entry_path(uri)).ok()?;
+        let entry: PersistedEntry = serde_json::from_slice(&bytes).ok()?;
+        let snapshot = FileSnapshot::from(&entry.snapshot);
+        if !snapshot.matches_disk(current_snapshot) {
+            log::trace!("Disk cache entry for {} is stale, ignoring", uri);
+            return None;
+        }
+        let artifacts = hydrate_scope_artifacts(entry.artifacts);
+        Some((snapshot, entry.content, entry.metadata, artifacts))
+    }
+
+    /// Remove the persisted entry for `uri`, if any.
+    pub fn invalidate(&self, uri: &Url) {
+        let _ = fs::remove_file(self.entry_path(uri));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    fn test_uri(name: &str) -> Url {
+        Url::parse(&format!("file:///{}", name)).unwrap()
+    }
+
+    fn test_snapshot() -> FileSnapshot {
+        FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 10,
+            content_hash: Some(42),
+        }
+    }
+
+    #[test]
+    fn test_get_missing_entry_returns_none() {
+        let dir = tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf());
+        assert!(cache.get(&test_uri("missing.R"), &test_snapshot()).is_none());
+    }
+
+    #[test]
+    fn test_put_then_get_round_trips() {
+        let dir = tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf());
+        let uri = test_uri("a.R");
+        let snapshot = test_snapshot();
+        let metadata = CrossFileMetadata::default();
+        let artifacts = ScopeArtifacts::default();
+
+        cache.put(&uri, &snapshot, "x <- 1", &metadata, &artifacts);
+
+        let (loaded_snapshot, content, loaded_metadata, loaded_artifacts) =
+            cache.get(&uri, &snapshot).expect("entry should be present");
+        assert_eq!(loaded_snapshot.size, snapshot.size);
+        assert_eq!(content, "x <- 1");
+        assert_eq!(loaded_metadata.sources.len(), 0);
+        assert_eq!(loaded_artifacts.interface_hash, 0);
+    }
+
+    #[test]
+    fn test_stale_snapshot_is_ignored() {
+        let dir = tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf());
+        let uri = test_uri("a.R");
+        let snapshot = test_snapshot();
+        cache.put(&uri, &snapshot, "x <- 1", &CrossFileMetadata::default(), &ScopeArtifacts::default());
+
+        let changed_snapshot = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 999,
+            content_hash: Some(42),
+        };
+        assert!(cache.get(&uri, &changed_snapshot).is_none());
+    }
+
+    #[test]
+    fn test_invalidate_removes_entry() {
+        let dir = tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf());
+        let uri = test_uri("a.R");
+        let snapshot = test_snapshot();
+        cache.put(&uri, &snapshot, "x <- 1", &CrossFileMetadata::default(), &ScopeArtifacts::default());
+        assert!(cache.get(&uri, &snapshot).is_some());
+
+        cache.invalidate(&uri);
+        assert!(cache.get(&uri, &snapshot).is_none());
+    }
+
+    #[test]
+    fn test_round_trips_scope_artifacts_with_symbols_and_intervals() {
+        let dir = tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf());
+        let uri = test_uri("a.R");
+        let snapshot = test_snapshot();
+
+        let symbol = ScopedSymbol {
+            name: Arc::from("my_func"),
+            kind: SymbolKind::Function,
+            source_uri: uri.clone(),
+            defined_line: 3,
+            defined_column: 0,
+            signature: Some("function(x)".to_string()),
+            is_declared: false,
+        };
+        let mut artifacts = ScopeArtifacts::default();
+        artifacts
+            .exported_interface
+            .insert(Arc::from("my_func"), symbol.clone());
+        artifacts.timeline.push(ScopeEvent::Def {
+            line: 3,
+            column: 0,
+            symbol,
+        });
+        artifacts.interface_hash = 123;
+        artifacts.function_scope_tree = FunctionScopeTree::from_scopes(&[(3, 0, 10, 0)]);
+
+        cache.put(&uri, &snapshot, "my_func <- function(x) x", &CrossFileMetadata::default(), &artifacts);
+
+        let (_, _, _, loaded) = cache.get(&uri, &snapshot).expect("entry should be present");
+        assert_eq!(loaded.interface_hash, 123);
+        assert_eq!(loaded.exported_interface.len(), 1);
+        assert_eq!(loaded.timeline.len(), 1);
+        assert_eq!(loaded.function_scope_tree.len(), 1);
+    }
+}
@@ -8,12 +8,16 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
 use lru::LruCache;
 use tower_lsp::lsp_types::Url;
 
+use super::permissions::PermissionChecker;
+use super::vfs::{real_vfs, Vfs, VfsMetadata};
+
 /// Snapshot metadata for a closed file, used to determine cache validity
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FileSnapshot {
@@ -46,12 +50,63 @@ impl FileSnapshot {
         }
     }
 
-    /// Check if this snapshot matches current disk state
+    /// Create snapshot from [`Vfs`] metadata
+    pub fn from_vfs_metadata(metadata: &VfsMetadata) -> Self {
+        Self {
+            mtime: metadata.modified,
+            size: metadata.len,
+            content_hash: None,
+        }
+    }
+
+    /// Create snapshot with content hash from [`Vfs`] metadata
+    pub fn with_content_hash_vfs(metadata: &VfsMetadata, content: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Self {
+            mtime: metadata.modified,
+            size: metadata.len,
+            content_hash: Some(hasher.finish()),
+        }
+    }
+
+    /// Check if this snapshot matches current disk state.
+    ///
+    /// mtime+size agreement is the primary, cheap check. If both snapshots
+    /// also carry a `content_hash`, it takes priority: a same-size edit that
+    /// lands within one mtime tick of a previous read would otherwise look
+    /// unchanged, so a hash mismatch is treated as stale even when mtime and
+    /// size agree.
     pub fn matches_disk(&self, current: &FileSnapshot) -> bool {
-        self.mtime == current.mtime && self.size == current.size
+        if self.mtime != current.mtime || self.size != current.size {
+            return false;
+        }
+        match (self.content_hash, current.content_hash) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+
+    /// Whether `mtime` is close enough to "now" that mtime+size agreement
+    /// alone can't be trusted: on filesystems with coarse mtime resolution, a
+    /// same-second edit can leave both `mtime` and `size` unchanged from a
+    /// moment ago. Callers use this to decide whether it's worth reading the
+    /// file to compute a `content_hash` up front rather than relying on
+    /// `matches_disk`'s mtime+size shortcut.
+    pub fn is_recent(&self) -> bool {
+        match SystemTime::now().duration_since(self.mtime) {
+            Ok(elapsed) => elapsed < MTIME_RESOLUTION,
+            // `mtime` is ahead of "now" (clock skew) - just as untrustworthy.
+            Err(_) => true,
+        }
     }
 }
 
+/// Coarse mtime granularity assumed for [`FileSnapshot::is_recent`] - e.g.
+/// FAT32 rounds mtimes to 2-second boundaries, and even finer-grained
+/// filesystems can surface truncated timestamps through some VFS layers.
+const MTIME_RESOLUTION: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Cached file entry
 #[derive(Debug, Clone)]
 struct CachedFile {
@@ -65,15 +120,47 @@ const DEFAULT_FILE_CACHE_CAPACITY: usize = 500;
 /// Default capacity for the existence cache
 const DEFAULT_EXISTENCE_CACHE_CAPACITY: usize = 2000;
 
+/// Point-in-time hit/miss/eviction counters for a [`CrossFileFileCache`], so
+/// the server can be tuned for large monorepos (e.g. whether a capacity bump
+/// is actually buying a better hit rate).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
 /// Disk file cache for closed files with LRU eviction.
 ///
 /// Uses `peek()` for reads (no LRU promotion, works under read lock) and
 /// `push()` for writes (promotes/evicts under write lock).
+///
+/// Entries are also bounded by `max_content_bytes` when set, on top of the
+/// entry-count bound `inner` already enforces - large files can blow the
+/// byte budget well before the entry count does, so both are checked on
+/// insert.
 pub struct CrossFileFileCache {
     /// Cached file contents by URI (LRU-bounded)
     inner: RwLock<LruCache<Url, CachedFile>>,
     /// Cached file existence by path (LRU-bounded)
     existence: RwLock<LruCache<PathBuf, bool>>,
+    /// Filesystem backend used for on-demand reads (disk by default; can be
+    /// swapped for an in-memory double in tests)
+    vfs: Arc<dyn Vfs>,
+    /// Optional byte budget for cached content, on top of the entry-count
+    /// bound; `None` means entry count is the only bound (prior behavior).
+    max_content_bytes: Option<usize>,
+    /// Running total of cached content bytes, kept in sync with `inner`.
+    content_bytes: AtomicUsize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    /// Optional trust gate consulted by `read_and_cache` before a file's
+    /// content is ingested; `None` preserves prior (always-trust) behavior.
+    permission_checker: Option<Arc<dyn PermissionChecker>>,
+    /// URIs most recently rejected by `permission_checker`, so a diagnostics
+    /// collector can surface why a file silently failed to load.
+    untrusted: RwLock<std::collections::HashSet<Url>>,
 }
 
 impl std::fmt::Debug for CrossFileFileCache {
@@ -94,6 +181,84 @@ impl CrossFileFileCache {
     }
 
     pub fn with_capacities(content_cap: usize, existence_cap: usize) -> Self {
+        Self::with_capacities_and_vfs(content_cap, existence_cap, real_vfs())
+    }
+
+    /// Construct a cache backed by a custom [`Vfs`] (e.g. an in-memory double
+    /// in tests), with the default capacities.
+    pub fn with_vfs(vfs: Arc<dyn Vfs>) -> Self {
+        Self::with_capacities_and_vfs(
+            DEFAULT_FILE_CACHE_CAPACITY,
+            DEFAULT_EXISTENCE_CACHE_CAPACITY,
+            vfs,
+        )
+    }
+
+    /// Construct a cache with explicit capacities and a custom [`Vfs`].
+    pub fn with_capacities_and_vfs(
+        content_cap: usize,
+        existence_cap: usize,
+        vfs: Arc<dyn Vfs>,
+    ) -> Self {
+        Self::with_capacities_vfs_and_byte_budget(content_cap, existence_cap, vfs, None)
+    }
+
+    /// Construct a cache with an additional byte budget for cached content,
+    /// on top of the usual entry-count capacity. Once the budget is
+    /// exceeded, least-recently-used entries are evicted until it isn't,
+    /// even if the entry count is still under `content_cap`.
+    pub fn with_byte_budget(content_cap: usize, existence_cap: usize, max_content_bytes: usize) -> Self {
+        Self::with_capacities_vfs_and_byte_budget(
+            content_cap,
+            existence_cap,
+            real_vfs(),
+            Some(max_content_bytes),
+        )
+    }
+
+    /// Construct a cache with explicit capacities, a custom [`Vfs`], and an
+    /// optional byte budget for cached content.
+    pub fn with_capacities_vfs_and_byte_budget(
+        content_cap: usize,
+        existence_cap: usize,
+        vfs: Arc<dyn Vfs>,
+        max_content_bytes: Option<usize>,
+    ) -> Self {
+        Self::with_capacities_vfs_byte_budget_and_permission_checker(
+            content_cap,
+            existence_cap,
+            vfs,
+            max_content_bytes,
+            None,
+        )
+    }
+
+    /// Construct a cache backed by a custom [`Vfs`] and gated by
+    /// `permission_checker`: `read_and_cache` skips ingesting any file (or
+    /// parent directory) the checker doesn't trust.
+    pub fn with_permission_checker(
+        vfs: Arc<dyn Vfs>,
+        permission_checker: Arc<dyn PermissionChecker>,
+    ) -> Self {
+        Self::with_capacities_vfs_byte_budget_and_permission_checker(
+            DEFAULT_FILE_CACHE_CAPACITY,
+            DEFAULT_EXISTENCE_CACHE_CAPACITY,
+            vfs,
+            None,
+            Some(permission_checker),
+        )
+    }
+
+    /// Construct a cache with explicit capacities, a custom [`Vfs`], an
+    /// optional byte budget for cached content, and an optional permission
+    /// checker gating `read_and_cache`.
+    pub fn with_capacities_vfs_byte_budget_and_permission_checker(
+        content_cap: usize,
+        existence_cap: usize,
+        vfs: Arc<dyn Vfs>,
+        max_content_bytes: Option<usize>,
+        permission_checker: Option<Arc<dyn PermissionChecker>>,
+    ) -> Self {
         let content_cap = NonZeroUsize::new(content_cap)
             .unwrap_or(NonZeroUsize::new(DEFAULT_FILE_CACHE_CAPACITY).unwrap());
         let existence_cap = NonZeroUsize::new(existence_cap)
@@ -101,6 +266,24 @@ impl CrossFileFileCache {
         Self {
             inner: RwLock::new(LruCache::new(content_cap)),
             existence: RwLock::new(LruCache::new(existence_cap)),
+            vfs,
+            max_content_bytes,
+            content_bytes: AtomicUsize::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            permission_checker,
+            untrusted: RwLock::new(std::collections::HashSet::new()),
+        }
+    }
+
+    /// Current hit/miss/eviction counters, for tuning capacities against
+    /// real workspace traffic.
+    pub fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
         }
     }
 
@@ -131,20 +314,80 @@ impl CrossFileFileCache {
 
     /// Get cached content without freshness check
     pub fn get(&self, uri: &Url) -> Option<String> {
-        self.inner.read().ok()?.peek(uri).map(|c| c.content.clone())
+        let result = self.inner.read().ok()?.peek(uri).map(|c| c.content.clone());
+        if result.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Get cached snapshot and content together, e.g. to promote a hit from a
+    /// lower cache tier (see [`super::content_provider::CrossFileContentProvider`])
+    /// without losing the freshness metadata that came with it.
+    pub fn get_with_snapshot(&self, uri: &Url) -> Option<(FileSnapshot, String)> {
+        let result = self
+            .inner
+            .read()
+            .ok()?
+            .peek(uri)
+            .map(|c| (c.snapshot.clone(), c.content.clone()));
+        if result.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Peek at the cached snapshot for a URI, without touching LRU order or
+    /// returning content. Lets external change detection (e.g.
+    /// [`super::watcher::CrossFileWatcher`]) decide whether a disk event
+    /// actually invalidates anything before evicting.
+    pub fn cached_snapshot(&self, uri: &Url) -> Option<FileSnapshot> {
+        self.inner.read().ok()?.peek(uri).map(|c| c.snapshot.clone())
     }
 
-    /// Insert content into cache. LRU eviction automatically bounds memory.
+    /// Insert content into cache. LRU eviction automatically bounds memory;
+    /// when `max_content_bytes` is set, entries are evicted further (beyond
+    /// the entry-count bound) until the cached content fits the budget.
     pub fn insert(&self, uri: Url, snapshot: FileSnapshot, content: String) {
+        let content_len = content.len();
         if let Ok(mut guard) = self.inner.write() {
-            guard.push(uri, CachedFile { snapshot, content });
+            if let Some((evicted_uri, evicted_file)) =
+                guard.push(uri.clone(), CachedFile { snapshot, content })
+            {
+                self.content_bytes
+                    .fetch_sub(evicted_file.content.len(), Ordering::Relaxed);
+                if evicted_uri != uri {
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            self.content_bytes.fetch_add(content_len, Ordering::Relaxed);
+
+            if let Some(budget) = self.max_content_bytes {
+                while self.content_bytes.load(Ordering::Relaxed) > budget {
+                    match guard.pop_lru() {
+                        Some((_, popped)) => {
+                            self.content_bytes
+                                .fetch_sub(popped.content.len(), Ordering::Relaxed);
+                            self.evictions.fetch_add(1, Ordering::Relaxed);
+                        }
+                        None => break,
+                    }
+                }
+            }
         }
     }
 
     /// Invalidate cache entry for a URI
     pub fn invalidate(&self, uri: &Url) {
         if let Ok(mut guard) = self.inner.write() {
-            guard.pop(uri);
+            if let Some(removed) = guard.pop(uri) {
+                self.content_bytes
+                    .fetch_sub(removed.content.len(), Ordering::Relaxed);
+            }
         }
     }
 
@@ -156,18 +399,56 @@ impl CrossFileFileCache {
         if let Ok(mut guard) = self.existence.write() {
             guard.clear();
         }
+        self.content_bytes.store(0, Ordering::Relaxed);
     }
 
-    /// Read file from disk and cache it (synchronous, for use outside lock)
+    /// Read file from the backing `Vfs` and cache it (synchronous, for use outside lock).
+    ///
+    /// If a `permission_checker` was configured and it doesn't trust `path`
+    /// (or one of its parent directories), the file is skipped rather than
+    /// ingested - see [`Self::untrusted_uris`].
     pub fn read_and_cache(&self, uri: &Url) -> Option<String> {
         let path = uri.to_file_path().ok()?;
-        let content = std::fs::read_to_string(&path).ok()?;
-        let metadata = std::fs::metadata(&path).ok()?;
-        let snapshot = FileSnapshot::with_content_hash(&metadata, &content);
+        if let Some(checker) = &self.permission_checker {
+            if !checker.is_trusted(&path) {
+                if let Ok(mut untrusted) = self.untrusted.write() {
+                    untrusted.insert(uri.clone());
+                }
+                return None;
+            }
+        }
+        if let Ok(mut untrusted) = self.untrusted.write() {
+            untrusted.remove(uri);
+        }
+        let content = self.vfs.read_to_string(&path).ok()?;
+        let metadata = self.vfs.metadata(&path).ok()?;
+        let snapshot = FileSnapshot::with_content_hash_vfs(&metadata, &content);
         self.insert(uri.clone(), snapshot, content.clone());
         Some(content)
     }
 
+    /// URIs most recently rejected by the configured `permission_checker`.
+    /// Lets a diagnostics collector explain a load failure instead of it
+    /// looking like an ordinary missing file.
+    pub fn untrusted_uris(&self) -> Vec<Url> {
+        self.untrusted
+            .read()
+            .ok()
+            .map(|g| g.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record `uri` as rejected by a permission check performed elsewhere
+    /// (e.g. the workspace scan in [`crate::state::scan_directory`], which
+    /// runs before a `CrossFileFileCache` is available to consult directly),
+    /// so [`Self::untrusted_uris`] surfaces it the same way it would a
+    /// rejection from [`Self::read_and_cache`].
+    pub fn mark_untrusted(&self, uri: &Url) {
+        if let Ok(mut untrusted) = self.untrusted.write() {
+            untrusted.insert(uri.clone());
+        }
+    }
+
     /// Resize both caches. If shrinking, LRU entries are evicted.
     pub fn resize(&self, content_cap: usize, existence_cap: usize) {
         let content_cap = NonZeroUsize::new(content_cap)
@@ -185,14 +466,26 @@ impl CrossFileFileCache {
 
 /// Get file snapshot from disk (synchronous).
 /// Reads filesystem metadata to create a snapshot for change detection.
+///
+/// When `mtime` is recent enough that mtime+size alone isn't trustworthy
+/// (see [`FileSnapshot::is_recent`]), this also reads the file once to
+/// compute and attach a `content_hash`, so [`FileSnapshot::matches_disk`]
+/// can fall back to it instead of missing a same-tick edit.
 pub fn get_file_snapshot(path: &Path) -> Option<FileSnapshot> {
     let metadata = std::fs::metadata(path).ok()?;
-    Some(FileSnapshot::from_metadata(&metadata))
+    let snapshot = FileSnapshot::from_metadata(&metadata);
+    if snapshot.is_recent() {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            return Some(FileSnapshot::with_content_hash(&metadata, &content));
+        }
+    }
+    Some(snapshot)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::vfs::InMemoryVfs;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -231,6 +524,70 @@ mod tests {
         assert!(!snap1.matches_disk(&snap2));
     }
 
+    #[test]
+    fn test_file_snapshot_mismatch_when_hashes_differ_despite_matching_mtime_and_size() {
+        // A same-size edit that lands within one mtime tick of a previous
+        // read would be invisible to mtime+size alone.
+        let snap1 = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 100,
+            content_hash: Some(111),
+        };
+        let snap2 = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 100,
+            content_hash: Some(222),
+        };
+        assert!(!snap1.matches_disk(&snap2));
+    }
+
+    #[test]
+    fn test_file_snapshot_matches_when_hashes_agree() {
+        let snap1 = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 100,
+            content_hash: Some(111),
+        };
+        let snap2 = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 100,
+            content_hash: Some(111),
+        };
+        assert!(snap1.matches_disk(&snap2));
+    }
+
+    #[test]
+    fn test_is_recent_true_for_current_mtime() {
+        let snapshot = FileSnapshot {
+            mtime: SystemTime::now(),
+            size: 0,
+            content_hash: None,
+        };
+        assert!(snapshot.is_recent());
+    }
+
+    #[test]
+    fn test_is_recent_false_for_old_mtime() {
+        let snapshot = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 0,
+            content_hash: None,
+        };
+        assert!(!snapshot.is_recent());
+    }
+
+    #[test]
+    fn test_get_file_snapshot_attaches_hash_for_fresh_write() {
+        let mut temp = NamedTempFile::new().unwrap();
+        writeln!(temp, "x <- 1").unwrap();
+
+        let snapshot = get_file_snapshot(temp.path()).unwrap();
+        assert!(
+            snapshot.content_hash.is_some(),
+            "a just-written file's mtime should be recent enough to attach a content_hash"
+        );
+    }
+
     #[test]
     fn test_cache_insert_and_get() {
         let cache = CrossFileFileCache::new();
@@ -272,6 +629,22 @@ mod tests {
         assert_eq!(cache.get_if_fresh(&uri, &new_snapshot), None);
     }
 
+    #[test]
+    fn test_cached_snapshot_peek() {
+        let cache = CrossFileFileCache::new();
+        let uri = test_uri("test.R");
+        let snapshot = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 10,
+            content_hash: None,
+        };
+
+        assert_eq!(cache.cached_snapshot(&uri), None);
+
+        cache.insert(uri.clone(), snapshot.clone(), "content".to_string());
+        assert_eq!(cache.cached_snapshot(&uri), Some(snapshot));
+    }
+
     #[test]
     fn test_cache_invalidate() {
         let cache = CrossFileFileCache::new();
@@ -289,6 +662,28 @@ mod tests {
         assert!(cache.get(&uri).is_none());
     }
 
+    #[test]
+    fn test_read_and_cache_skips_untrusted_file() {
+        use super::super::permissions::PermissionChecker;
+
+        struct AlwaysDistrust;
+        impl PermissionChecker for AlwaysDistrust {
+            fn is_trusted(&self, _path: &Path) -> bool {
+                false
+            }
+        }
+
+        let mut temp = NamedTempFile::new().unwrap();
+        writeln!(temp, "x <- 1").unwrap();
+        let uri = Url::from_file_path(temp.path()).unwrap();
+
+        let cache = CrossFileFileCache::with_permission_checker(real_vfs(), Arc::new(AlwaysDistrust));
+
+        assert_eq!(cache.read_and_cache(&uri), None);
+        assert!(cache.get(&uri).is_none());
+        assert_eq!(cache.untrusted_uris(), vec![uri]);
+    }
+
     #[test]
     fn test_read_and_cache() {
         let cache = CrossFileFileCache::new();
@@ -308,6 +703,27 @@ mod tests {
         assert!(cache.get(&uri).is_some());
     }
 
+    #[test]
+    fn test_read_and_cache_via_in_memory_vfs() {
+        let vfs = Arc::new(InMemoryVfs::new().with_file("/project/a.R", "x <- 1"));
+        let cache = CrossFileFileCache::with_vfs(vfs);
+        let uri = Url::parse("file:///project/a.R").unwrap();
+
+        let content = cache.read_and_cache(&uri);
+        assert_eq!(content, Some("x <- 1".to_string()));
+        assert!(cache.get(&uri).is_some());
+    }
+
+    #[test]
+    fn test_read_and_cache_via_in_memory_vfs_missing_file() {
+        let vfs = Arc::new(InMemoryVfs::new());
+        let cache = CrossFileFileCache::with_vfs(vfs);
+        let uri = Url::parse("file:///project/missing.R").unwrap();
+
+        assert_eq!(cache.read_and_cache(&uri), None);
+        assert!(cache.get(&uri).is_none());
+    }
+
     #[test]
     fn test_content_cache_lru_eviction() {
         let cache = CrossFileFileCache::with_capacities(2, 100);
@@ -334,6 +750,85 @@ mod tests {
         assert!(cache.get(&uri3).is_some());
     }
 
+    #[test]
+    fn test_metrics_track_hits_and_misses() {
+        let cache = CrossFileFileCache::new();
+        let uri = test_uri("test.R");
+        let snapshot = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 10,
+            content_hash: None,
+        };
+
+        assert!(cache.get(&uri).is_none());
+        cache.insert(uri.clone(), snapshot, "content".to_string());
+        assert!(cache.get(&uri).is_some());
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.hits, 1);
+        assert_eq!(metrics.misses, 1);
+    }
+
+    #[test]
+    fn test_metrics_track_evictions() {
+        let cache = CrossFileFileCache::with_capacities(1, 100);
+        let uri1 = test_uri("a.R");
+        let uri2 = test_uri("b.R");
+        let snap = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 10,
+            content_hash: None,
+        };
+
+        cache.insert(uri1, snap.clone(), "a".to_string());
+        cache.insert(uri2, snap, "b".to_string());
+
+        assert_eq!(cache.metrics().evictions, 1);
+    }
+
+    #[test]
+    fn test_byte_budget_evicts_before_entry_cap() {
+        // Entry cap of 100 wouldn't evict anything on its own; the byte
+        // budget should still force eviction once cached content exceeds it.
+        let cache = CrossFileFileCache::with_byte_budget(100, 100, 15);
+        let uri1 = test_uri("a.R");
+        let uri2 = test_uri("b.R");
+        let snap = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 10,
+            content_hash: None,
+        };
+
+        cache.insert(uri1.clone(), snap.clone(), "0123456789".to_string());
+        assert!(cache.get(&uri1).is_some());
+
+        // Pushes total cached bytes to 20, over the 15-byte budget.
+        cache.insert(uri2.clone(), snap, "0123456789".to_string());
+
+        assert!(cache.get(&uri1).is_none(), "oldest entry should be evicted to respect the byte budget");
+        assert!(cache.get(&uri2).is_some());
+        assert_eq!(cache.metrics().evictions, 1);
+    }
+
+    #[test]
+    fn test_get_with_snapshot_promotes_tier_hit() {
+        let primary = CrossFileFileCache::new();
+        let secondary = CrossFileFileCache::new();
+        let uri = test_uri("test.R");
+        let snapshot = FileSnapshot {
+            mtime: SystemTime::UNIX_EPOCH,
+            size: 10,
+            content_hash: None,
+        };
+        secondary.insert(uri.clone(), snapshot.clone(), "content".to_string());
+
+        assert!(primary.get(&uri).is_none());
+        let (hit_snapshot, hit_content) = secondary.get_with_snapshot(&uri).unwrap();
+        primary.insert(uri.clone(), hit_snapshot, hit_content);
+
+        assert_eq!(primary.get(&uri), Some("content".to_string()));
+    }
+
     #[test]
     fn test_existence_cache_lru_eviction() {
         let cache = CrossFileFileCache::with_capacities(100, 2);
@@ -1113,6 +1113,27 @@ mod helper_tests {
         assert_eq!(metadata.library_calls[1].package, "ggplot2");
     }
 
+    #[test]
+    fn test_extract_metadata_no_markers_returns_default() {
+        // No `@lsp-` directives and no source()/library()/require()/
+        // loadNamespace() calls anywhere in the file, so the marker-free
+        // fast path should kick in and produce the same empty metadata as
+        // the full scan would.
+        let mut workspace = TestWorkspace::new().unwrap();
+        workspace
+            .add_file(
+                "test.r",
+                "my_function <- function(x) {\n  y <- x + 1\n  y\n}",
+            )
+            .unwrap();
+
+        let metadata = extract_metadata_for_file(&workspace, "test.r").unwrap();
+        assert!(metadata.sources.is_empty());
+        assert!(metadata.sourced_by.is_empty());
+        assert!(metadata.library_calls.is_empty());
+        assert!(metadata.working_directory.is_none());
+    }
+
     #[test]
     fn test_build_dependency_graph_simple() {
         let mut workspace = TestWorkspace::new().unwrap();
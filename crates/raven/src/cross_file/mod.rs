@@ -7,20 +7,40 @@
 // Allow dead code for infrastructure that's implemented for future use
 #![allow(dead_code)]
 
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::Url;
+
+pub mod ancestor_chain;
+pub mod ancestor_closure;
+pub mod ast_source_call;
 pub mod background_indexer;
 pub mod cache;
+pub mod child_path;
 pub mod config;
+pub mod configlist;
 pub mod content_provider;
 pub mod dependency;
 pub mod directive;
+pub mod disk_cache;
 pub mod file_cache;
+pub mod negative_directives;
 pub mod parent_resolve;
 pub mod path_resolve;
+pub mod pending_fetch;
+pub mod permissions;
+pub mod regex_cache;
 pub mod revalidation;
+pub mod sandbox;
+pub mod schedule;
 pub mod scope;
 pub mod source_detect;
+pub mod source_map;
 pub mod types;
+pub mod vfs;
+pub mod watcher;
 pub mod workspace_index;
+pub mod workspace_source_scan;
 
 #[cfg(test)]
 mod property_tests;
@@ -28,25 +48,75 @@ mod property_tests;
 #[cfg(test)]
 pub mod integration_tests;
 
+#[allow(unused_imports)]
+pub use ancestor_chain::*;
+#[allow(unused_imports)]
+pub use ancestor_closure::*;
+#[allow(unused_imports)]
+pub use ast_source_call::*;
 pub use background_indexer::*;
 pub use cache::*;
+#[allow(unused_imports)]
+pub use child_path::*;
 pub use config::*;
 #[allow(unused_imports)]
+pub use configlist::*;
+#[allow(unused_imports)]
 pub use content_provider::*;
 pub use dependency::*;
 #[allow(unused_imports)]
 pub use directive::*;
+#[allow(unused_imports)]
+pub use disk_cache::*;
 pub use file_cache::*;
 #[allow(unused_imports)]
+pub use negative_directives::*;
+#[allow(unused_imports)]
 pub use parent_resolve::*;
 #[allow(unused_imports)]
 pub use path_resolve::*;
+pub use pending_fetch::*;
+#[allow(unused_imports)]
+pub use permissions::*;
+#[allow(unused_imports)]
+pub use regex_cache::*;
 pub use revalidation::*;
+#[allow(unused_imports)]
+pub use sandbox::*;
+pub use schedule::*;
 pub use scope::*;
 #[allow(unused_imports)]
 pub use source_detect::*;
+#[allow(unused_imports)]
+pub use source_map::*;
 pub use types::*;
+#[allow(unused_imports)]
+pub use vfs::*;
+#[allow(unused_imports)]
+pub use watcher::*;
 pub use workspace_index::*;
+#[allow(unused_imports)]
+pub use workspace_source_scan::*;
+
+/// Cheap prefilter for [`extract_metadata`]/[`extract_metadata_with_tree`]: a
+/// single linear scan for the byte sequences that any directive or
+/// source/library call detection could possibly match on. Directive parsing
+/// only ever matches lines containing `@lsp-`; AST-based detection only ever
+/// finds a call whose function name is `source` (which also covers
+/// `sys.source`), `library`, `require`, or `loadNamespace` - the name alone
+/// is checked, since R allows whitespace before the `(`. If none of
+/// these are present anywhere in `content`, every per-line regex and every
+/// AST call-site check is guaranteed to come back empty, so both can be
+/// skipped outright. A hit doesn't guarantee a match (it might sit inside a
+/// string literal or unrelated identifier) - it only means the cheap check
+/// can't rule one out, so the normal path still runs.
+fn may_contain_cross_file_markers(content: &str) -> bool {
+    content.contains("@lsp-")
+        || content.contains("source")
+        || content.contains("library")
+        || content.contains("require")
+        || content.contains("loadNamespace")
+}
 
 /// Extract cross-file metadata from R source by combining directive parsing with AST-detected `source()` and library-related calls.
 ///
@@ -70,7 +140,34 @@ pub use workspace_index::*;
 /// assert!(meta.sources.len() >= 1);
 /// assert!(meta.library_calls.iter().any(|lc| lc.package == "pkg"));
 /// ```
+/// Cheap prefilter for [`extract_metadata`]/[`extract_metadata_with_tree`]: a
+/// single linear scan for the byte sequences that any directive or
+/// source/library call detection could possibly match on. Directive parsing
+/// only ever matches lines containing `@lsp-`; AST-based detection only ever
+/// finds a call whose function name is `source` (which also covers
+/// `sys.source`), `library`, `require`, or `loadNamespace` - the name alone
+/// is checked, since R allows whitespace before the `(`. If none of
+/// these are present anywhere in `content`, every per-line regex and every
+/// AST call-site check is guaranteed to come back empty, so both can be
+/// skipped outright. A hit doesn't guarantee a match (it might sit inside a
+/// string literal or unrelated identifier) - it only means the cheap check
+/// can't rule one out, so the normal path still runs.
+fn may_contain_cross_file_markers(content: &str) -> bool {
+    content.contains("@lsp-")
+        || content.contains("source")
+        || content.contains("library")
+        || content.contains("require")
+        || content.contains("loadNamespace")
+}
+
 pub fn extract_metadata(content: &str) -> CrossFileMetadata {
+    if !may_contain_cross_file_markers(content) {
+        log::trace!(
+            "No directive or source/library markers in content ({} bytes); skipping extraction",
+            content.len()
+        );
+        return CrossFileMetadata::default();
+    }
     let tree = crate::parser_pool::with_parser(|parser| parser.parse(content, None));
     extract_metadata_with_tree(content, tree.as_ref())
 }
@@ -82,6 +179,14 @@ pub fn extract_metadata_with_tree(
     content: &str,
     tree: Option<&tree_sitter::Tree>,
 ) -> CrossFileMetadata {
+    if !may_contain_cross_file_markers(content) {
+        log::trace!(
+            "No directive or source/library markers in content ({} bytes); skipping extraction",
+            content.len()
+        );
+        return CrossFileMetadata::default();
+    }
+
     log::trace!(
         "Extracting cross-file metadata from content ({} bytes)",
         content.len()
@@ -130,3 +235,77 @@ pub fn extract_metadata_with_tree(
 
     meta
 }
+
+/// Walk `meta`'s `sourced_by` chain upward, inheriting the nearest ancestor's
+/// working directory when `meta` has no explicit `@lsp-working-directory` of
+/// its own. Stops as soon as an ancestor with an explicit or already-
+/// inherited working directory is found, a hop can't be resolved, a cycle is
+/// detected, or `max_chain_depth` hops have been walked without finding one -
+/// in every case, `meta.inherited_working_directory` is simply left
+/// unchanged (`None`, or whatever it already was).
+///
+/// `get_parent_metadata` is expected to prefer already-enriched metadata
+/// (e.g. [`WorldState::get_enriched_metadata`](crate::state::WorldState::get_enriched_metadata))
+/// so a long chain converges in one pass rather than needing to be walked
+/// for every file in it.
+pub fn enrich_metadata_with_inherited_wd(
+    meta: &mut CrossFileMetadata,
+    uri: &Url,
+    workspace_root: Option<&Url>,
+    get_parent_metadata: impl Fn(&Url) -> Option<CrossFileMetadata>,
+    max_chain_depth: usize,
+) {
+    if meta.working_directory.is_some() || meta.sourced_by.is_empty() {
+        return;
+    }
+
+    let mut visited = HashSet::new();
+    visited.insert(uri.clone());
+
+    let mut current_uri = uri.clone();
+    let mut current_sourced_by = meta.sourced_by.clone();
+
+    for _ in 0..max_chain_depth {
+        let Some(ctx) = path_resolve::PathContext::new(&current_uri, workspace_root) else {
+            return;
+        };
+
+        let Some(parent_uri) = current_sourced_by
+            .iter()
+            .find_map(|directive| path_resolve::resolve_path(&directive.path, &ctx))
+            .and_then(|path| path_resolve::path_to_uri(&path))
+        else {
+            return;
+        };
+
+        if !visited.insert(parent_uri.clone()) {
+            return;
+        }
+
+        let Some(parent_meta) = get_parent_metadata(&parent_uri) else {
+            return;
+        };
+
+        if let Some(wd) = &parent_meta.working_directory {
+            let Some(parent_ctx) = path_resolve::PathContext::new(&parent_uri, workspace_root) else {
+                return;
+            };
+            if let Some(resolved) = path_resolve::resolve_working_directory(wd, &parent_ctx) {
+                meta.inherited_working_directory = Some(resolved.to_string_lossy().into_owned());
+            }
+            return;
+        }
+
+        if let Some(inherited) = &parent_meta.inherited_working_directory {
+            meta.inherited_working_directory = Some(inherited.clone());
+            return;
+        }
+
+        if parent_meta.sourced_by.is_empty() {
+            return;
+        }
+
+        current_sourced_by = parent_meta.sourced_by;
+        current_uri = parent_uri;
+    }
+}
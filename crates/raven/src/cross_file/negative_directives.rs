@@ -0,0 +1,155 @@
+//
+// cross_file/negative_directives.rs
+//
+// Negative "not-sourced-by" directives to suppress false parents
+//
+// NOTE: `parse_directives` stores the parsed negations in
+// `CrossFileMetadata::not_sourced_by`; `resolve_parent_with_content` (in
+// `cross_file::parent_resolve`) drops candidate parents with
+// `filter_denied_candidates` before precedence sorting, and
+// `compute_metadata_fingerprint` mixes them in with
+// `fold_not_sourced_by_into_fingerprint` so a cache key keyed on them stays
+// correct.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use regex::Regex;
+use tower_lsp::lsp_types::Url;
+
+/// `# @lsp-not-sourced-by: path` - same quoting rules as the other `@lsp-*`
+/// directives (double-quoted, single-quoted, or a bare unquoted token).
+fn pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r#"#\s*@lsp-not-sourced-by\s*:?\s*(?:"([^"]+)"|'([^']+)'|(\S+))"#,
+        )
+        .unwrap()
+    })
+}
+
+fn capture_path(caps: &regex::Captures) -> Option<String> {
+    for group in 1..=3 {
+        if let Some(m) = caps.get(group) {
+            if !m.as_str().is_empty() {
+                return Some(m.as_str().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Parse every `@lsp-not-sourced-by: path` directive out of `content`, in
+/// document order. Each entry vetoes a candidate parent whose resolved URI
+/// matches `path` (by full path or filename - see [`is_denied`]).
+pub fn parse_not_sourced_by(content: &str) -> Vec<String> {
+    let pattern = pattern();
+    content
+        .lines()
+        .filter_map(|line| pattern.captures(line).and_then(|caps| capture_path(&caps)))
+        .collect()
+}
+
+/// Whether `candidate_uri` matches one of the parsed `not_sourced_by`
+/// negation paths, by comparing its filename and its full local path against
+/// each negation (so both `@lsp-not-sourced-by: template.R` and
+/// `@lsp-not-sourced-by: examples/template.R` veto a candidate resolving to
+/// `.../examples/template.R`).
+pub fn is_denied(candidate_uri: &Url, not_sourced_by: &[String]) -> bool {
+    let Ok(candidate_path) = candidate_uri.to_file_path() else {
+        return false;
+    };
+    let candidate_path_str = candidate_path.to_string_lossy().replace('\\', "/");
+    let candidate_filename = candidate_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+
+    not_sourced_by.iter().any(|negation| {
+        let negation = negation.replace('\\', "/");
+        candidate_path_str.ends_with(negation.as_str()) || candidate_filename == negation
+    })
+}
+
+/// Drop every candidate in `candidates` that matches a `not_sourced_by`
+/// negation, preserving the relative order of the rest.
+pub fn filter_denied_candidates(candidates: Vec<Url>, not_sourced_by: &[String]) -> Vec<Url> {
+    if not_sourced_by.is_empty() {
+        return candidates;
+    }
+    candidates
+        .into_iter()
+        .filter(|uri| !is_denied(uri, not_sourced_by))
+        .collect()
+}
+
+/// Mix `not_sourced_by` into a base fingerprint (e.g. the one produced by
+/// `compute_metadata_fingerprint`) so a cache keyed on the combined value
+/// invalidates when the negation list changes, even if nothing else did.
+pub fn fold_not_sourced_by_into_fingerprint(base_fingerprint: u64, not_sourced_by: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    base_fingerprint.hash(&mut hasher);
+    not_sourced_by.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parses_quoted_and_unquoted_paths() {
+        let content = concat!(
+            "# @lsp-not-sourced-by: \"template.R\"\n",
+            "# @lsp-not-sourced-by: examples/other.R\n",
+            "x <- 1\n",
+        );
+        assert_eq!(
+            parse_not_sourced_by(content),
+            vec!["template.R".to_string(), "examples/other.R".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_no_directive_yields_empty_list() {
+        assert!(parse_not_sourced_by("x <- 1\nsource(\"child.R\")\n").is_empty());
+    }
+
+    #[test]
+    fn test_filters_denied_candidate_by_filename() {
+        let candidates = vec![uri("file:///project/template.R"), uri("file:///project/main.R")];
+        let filtered = filter_denied_candidates(candidates, &["template.R".to_string()]);
+        assert_eq!(filtered, vec![uri("file:///project/main.R")]);
+    }
+
+    #[test]
+    fn test_filters_denied_candidate_by_relative_path() {
+        let candidates = vec![uri("file:///project/examples/template.R")];
+        let filtered =
+            filter_denied_candidates(candidates, &["examples/template.R".to_string()]);
+        assert!(filtered.is_empty());
+    }
+
+    #[test]
+    fn test_empty_negation_list_is_a_no_op() {
+        let candidates = vec![uri("file:///project/main.R")];
+        let filtered = filter_denied_candidates(candidates.clone(), &[]);
+        assert_eq!(filtered, candidates);
+    }
+
+    #[test]
+    fn test_fingerprint_changes_when_negations_change() {
+        let base = 42u64;
+        let a = fold_not_sourced_by_into_fingerprint(base, &["template.R".to_string()]);
+        let b = fold_not_sourced_by_into_fingerprint(base, &["other.R".to_string()]);
+        let c = fold_not_sourced_by_into_fingerprint(base, &[]);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+    }
+}
@@ -0,0 +1,632 @@
+//
+// cross_file/parent_resolve.rs
+//
+// Parent resolution for cross-file awareness
+//
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use tower_lsp::lsp_types::Url;
+
+use super::cache::{ParentCacheKey, ParentResolution};
+use super::config::{CallSiteDefault, CrossFileConfig};
+use super::dependency::DependencyGraph;
+use super::regex_cache::RegexCache;
+use super::types::{byte_offset_to_utf16_column, BackwardDirective, CallSiteSpec, CrossFileMetadata};
+
+/// Resolve the effective call site when a file is sourced multiple times.
+/// Returns the earliest call site position using lexicographic ordering.
+pub fn resolve_multiple_source_calls(call_sites: &[(u32, u32)]) -> Option<(u32, u32)> {
+    call_sites.iter().copied().min()
+}
+
+/// Compute a fingerprint of `metadata` for cache-key purposes, so a resolved
+/// parent is invalidated whenever anything that could change the resolution
+/// changes - including `not_sourced_by` negations (see
+/// [`super::negative_directives`]).
+pub fn compute_metadata_fingerprint(metadata: &CrossFileMetadata) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for directive in &metadata.sourced_by {
+        directive.path.hash(&mut hasher);
+        directive.directive_line.hash(&mut hasher);
+        match &directive.call_site {
+            CallSiteSpec::Default => 0u8.hash(&mut hasher),
+            CallSiteSpec::Line(n) => {
+                1u8.hash(&mut hasher);
+                n.hash(&mut hasher);
+            }
+            CallSiteSpec::Match(s) => {
+                2u8.hash(&mut hasher);
+                s.hash(&mut hasher);
+            }
+        }
+    }
+    let base = hasher.finish();
+    super::negative_directives::fold_not_sourced_by_into_fingerprint(base, &metadata.not_sourced_by)
+}
+
+fn regex_cache() -> &'static RegexCache {
+    static CACHE: OnceLock<RegexCache> = OnceLock::new();
+    CACHE.get_or_init(RegexCache::new)
+}
+
+/// Resolve a `match=` pattern in parent content to find the call site.
+/// Returns `(line, utf16_column)` of the first match on a line containing a
+/// `source()`/`sys.source()` call to `child_path`, falling back to the first
+/// match on any line if no such call is found. `pattern` is treated as a
+/// regular expression (see [`super::regex_cache`]), degrading to a literal
+/// substring match if it doesn't compile.
+pub fn resolve_match_pattern(
+    parent_content: &str,
+    pattern: &str,
+    child_path: &str,
+) -> Option<(u32, u32)> {
+    super::regex_cache::resolve_match_pattern_regex(regex_cache(), parent_content, pattern, child_path)
+}
+
+/// Infer a call site by scanning parent content for a `source()`/
+/// `sys.source()` call to `child`. Used when `call_site` is `Default` and no
+/// reverse edge exists. Parses `parent_content` to walk its AST (see
+/// [`super::ast_source_call`]), falling back to a literal-substring scan if
+/// it fails to parse.
+pub fn infer_call_site_from_parent(parent_content: &str, child_path: &str) -> Option<(u32, u32)> {
+    let tree = crate::parser_pool::with_parser(|parser| parser.parse(parent_content, None));
+    super::ast_source_call::find_source_call_site_for_child_or_fallback(
+        tree.as_ref(),
+        parent_content,
+        child_path,
+    )
+}
+
+/// Compute a hash of the reverse edges pointing to `child_uri`, for cache-key
+/// purposes.
+pub fn compute_reverse_edges_hash(graph: &DependencyGraph, child_uri: &Url) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut edges: Vec<_> = graph
+        .get_dependents(child_uri)
+        .iter()
+        .map(|e| {
+            (
+                e.from.as_str(),
+                e.call_site_line,
+                e.call_site_column,
+                e.local,
+                e.chdir,
+                e.is_sys_source,
+            )
+        })
+        .collect();
+    edges.sort();
+    edges.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Try each of `config.source_search_roots` in turn, joining it with
+/// `directive_path`, and return the first one that exists on disk - the
+/// fallback `resolve_path` can't apply because the directive's path isn't
+/// relative to the sourcing file (see [`super::configlist`]).
+fn resolve_via_search_roots(config: &CrossFileConfig, directive_path: &str) -> Option<Url> {
+    for root in &config.source_search_roots {
+        let candidate = root.join(directive_path);
+        if candidate.exists() {
+            if let Some(uri) = super::path_resolve::path_to_uri(&candidate) {
+                return Some(uri);
+            }
+        }
+    }
+    None
+}
+
+/// Resolve the parent of a file with backward directives, given a content
+/// provider for `match=` resolution and call-site inference.
+pub fn resolve_parent_with_content<F>(
+    metadata: &CrossFileMetadata,
+    graph: &DependencyGraph,
+    child_uri: &Url,
+    config: &CrossFileConfig,
+    resolve_path: impl Fn(&str) -> Option<Url>,
+    get_content: F,
+) -> ParentResolution
+where
+    F: Fn(&Url) -> Option<String>,
+{
+    #[derive(Debug, Clone)]
+    struct Candidate {
+        parent: Url,
+        call_site_line: Option<u32>,
+        call_site_column: Option<u32>,
+        precedence: u8,
+    }
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    // Derive child_path from child_uri for match pattern and call-site inference.
+    let child_path = child_uri
+        .to_file_path()
+        .ok()
+        .and_then(|p| p.file_name().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_default();
+
+    // From backward directives.
+    for directive in &metadata.sourced_by {
+        let resolved_parent = resolve_path(&directive.path)
+            .or_else(|| resolve_via_search_roots(config, &directive.path));
+        if let Some(parent_uri) = resolved_parent {
+            let (call_site_line, call_site_column, precedence) = match &directive.call_site {
+                CallSiteSpec::Line(n) => (Some(*n), Some(u32::MAX), 0),
+                CallSiteSpec::Match(pattern) => {
+                    if let Some(parent_content) = get_content(&parent_uri) {
+                        if let Some((line, col)) =
+                            resolve_match_pattern(&parent_content, pattern, &child_path)
+                        {
+                            (Some(line), Some(col), 0)
+                        } else {
+                            match config.assume_call_site {
+                                CallSiteDefault::End => (Some(u32::MAX), Some(u32::MAX), 3),
+                                CallSiteDefault::Start => (Some(0), Some(0), 3),
+                            }
+                        }
+                    } else {
+                        match config.assume_call_site {
+                            CallSiteDefault::End => (Some(u32::MAX), Some(u32::MAX), 3),
+                            CallSiteDefault::Start => (Some(0), Some(0), 3),
+                        }
+                    }
+                }
+                CallSiteSpec::Default => {
+                    let has_reverse_edge = graph
+                        .get_dependents(child_uri)
+                        .iter()
+                        .any(|e| e.from == parent_uri && e.call_site_line.is_some());
+
+                    if has_reverse_edge {
+                        // Handled by the reverse-edge pass below.
+                        continue;
+                    } else if let Some(parent_content) = get_content(&parent_uri) {
+                        if let Some((line, col)) =
+                            infer_call_site_from_parent(&parent_content, &child_path)
+                        {
+                            (Some(line), Some(col), 1)
+                        } else {
+                            match config.assume_call_site {
+                                CallSiteDefault::End => (Some(u32::MAX), Some(u32::MAX), 3),
+                                CallSiteDefault::Start => (Some(0), Some(0), 3),
+                            }
+                        }
+                    } else {
+                        match config.assume_call_site {
+                            CallSiteDefault::End => (Some(u32::MAX), Some(u32::MAX), 3),
+                            CallSiteDefault::Start => (Some(0), Some(0), 3),
+                        }
+                    }
+                }
+            };
+            candidates.push(Candidate {
+                parent: parent_uri,
+                call_site_line,
+                call_site_column,
+                precedence,
+            });
+        }
+    }
+
+    // From reverse dependency edges.
+    for edge in graph.get_dependents(child_uri) {
+        let (call_site_line, call_site_column) = match (edge.call_site_line, edge.call_site_column) {
+            (Some(line), Some(col)) => (Some(line), Some(col)),
+            _ => (None, None),
+        };
+        let precedence = if call_site_line.is_some() && call_site_column.is_some() { 2 } else { 3 };
+
+        if let Some(existing) = candidates.iter_mut().find(|c| c.parent == edge.from) {
+            if precedence < existing.precedence {
+                existing.precedence = precedence;
+                existing.call_site_line = call_site_line;
+                existing.call_site_column = call_site_column;
+            }
+        } else {
+            candidates.push(Candidate {
+                parent: edge.from.clone(),
+                call_site_line,
+                call_site_column,
+                precedence,
+            });
+        }
+    }
+
+    // Drop candidates vetoed by an `@lsp-not-sourced-by` negation before
+    // anything else gets to consider them.
+    if !metadata.not_sourced_by.is_empty() {
+        let parents: Vec<Url> = candidates.iter().map(|c| c.parent.clone()).collect();
+        let allowed = super::negative_directives::filter_denied_candidates(
+            parents,
+            &metadata.not_sourced_by,
+        );
+        candidates.retain(|c| allowed.contains(&c.parent));
+    }
+
+    // Enforce the project-root sandbox, if configured. A candidate that
+    // escapes confinement is dropped; if every candidate does, the
+    // resolution is `Denied` rather than silently falling through to
+    // `None`, so the caller can surface why nothing resolved.
+    if let Some(project_root) = &config.project_root {
+        let total_before = candidates.len();
+        let mut first_denial: Option<(Url, String)> = None;
+        candidates.retain(|c| match super::sandbox::check_confinement(&c.parent, project_root) {
+            Ok(()) => true,
+            Err(reason) => {
+                if first_denial.is_none() {
+                    first_denial = Some((c.parent.clone(), reason));
+                }
+                false
+            }
+        });
+        if candidates.is_empty() && total_before > 0 {
+            if let Some((attempted_uri, reason)) = first_denial {
+                return ParentResolution::Denied { attempted_uri, reason };
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return ParentResolution::None;
+    }
+
+    // Deterministic selection with precedence, then URI tiebreak.
+    candidates.sort_by(|a, b| (a.precedence, a.parent.as_str()).cmp(&(b.precedence, b.parent.as_str())));
+
+    let selected = candidates.remove(0);
+
+    // Filter out alternatives that point to the same parent as selected, to
+    // avoid false ambiguity when the same parent appears from multiple
+    // sources (e.g. a directive and a reverse edge).
+    let unique_alternatives: Vec<Url> = candidates
+        .into_iter()
+        .filter(|c| c.parent != selected.parent)
+        .map(|c| c.parent)
+        .collect();
+
+    if unique_alternatives.is_empty() {
+        return ParentResolution::Single {
+            parent_uri: selected.parent,
+            call_site_line: selected.call_site_line,
+            call_site_column: selected.call_site_column,
+        };
+    }
+
+    ParentResolution::Ambiguous {
+        selected_uri: selected.parent,
+        selected_line: selected.call_site_line,
+        selected_column: selected.call_site_column,
+        alternatives: unique_alternatives,
+    }
+}
+
+/// Resolve the parent of a file with backward directives, without a content
+/// provider - `match=` patterns and call-site inference fall back to
+/// `config.assume_call_site`.
+pub fn resolve_parent(
+    metadata: &CrossFileMetadata,
+    graph: &DependencyGraph,
+    child_uri: &Url,
+    config: &CrossFileConfig,
+    resolve_path: impl Fn(&str) -> Option<Url>,
+) -> ParentResolution {
+    resolve_parent_with_content(metadata, graph, child_uri, config, resolve_path, |_| None)
+}
+
+/// Build the cache key parent resolution is keyed on.
+pub fn make_parent_cache_key(
+    metadata: &CrossFileMetadata,
+    graph: &DependencyGraph,
+    child_uri: &Url,
+) -> ParentCacheKey {
+    ParentCacheKey {
+        metadata_fingerprint: compute_metadata_fingerprint(metadata),
+        reverse_edges_hash: compute_reverse_edges_hash(graph, child_uri),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(&format!("file:///{}", s)).unwrap()
+    }
+
+    fn forward_source(path: &str, line: u32, column: u32) -> super::super::types::ForwardSource {
+        super::super::types::ForwardSource {
+            path: path.to_string(),
+            line,
+            column,
+            is_directive: false,
+            local: false,
+            chdir: false,
+            is_sys_source: false,
+            sys_source_global_env: true,
+            explicit_line: false,
+            directive_line: line,
+            user_line_zero: false,
+        }
+    }
+
+    #[test]
+    fn test_resolve_multiple_source_calls() {
+        let calls = vec![(10, 5), (5, 10), (5, 5)];
+        assert_eq!(resolve_multiple_source_calls(&calls), Some((5, 5)));
+    }
+
+    #[test]
+    fn test_resolve_multiple_source_calls_empty() {
+        let calls: Vec<(u32, u32)> = vec![];
+        assert_eq!(resolve_multiple_source_calls(&calls), None);
+    }
+
+    #[test]
+    fn test_compute_metadata_fingerprint_deterministic() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../main.R".to_string(),
+                call_site: CallSiteSpec::Line(10),
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(compute_metadata_fingerprint(&meta), compute_metadata_fingerprint(&meta));
+    }
+
+    #[test]
+    fn test_compute_metadata_fingerprint_changes_with_not_sourced_by() {
+        let base = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../main.R".to_string(),
+                call_site: CallSiteSpec::Line(10),
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        let negated = CrossFileMetadata {
+            not_sourced_by: vec!["other.R".to_string()],
+            ..base.clone()
+        };
+        assert_ne!(compute_metadata_fingerprint(&base), compute_metadata_fingerprint(&negated));
+    }
+
+    #[test]
+    fn test_resolve_parent_no_directives() {
+        let meta = CrossFileMetadata::default();
+        let graph = DependencyGraph::new();
+        let config = CrossFileConfig::default();
+        let child = url("child.R");
+
+        let result = resolve_parent(&meta, &graph, &child, &config, |_| None);
+        assert!(matches!(result, ParentResolution::None));
+    }
+
+    #[test]
+    fn test_resolve_parent_single() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../main.R".to_string(),
+                call_site: CallSiteSpec::Line(10),
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        let graph = DependencyGraph::new();
+        let config = CrossFileConfig::default();
+        let child = url("child.R");
+        let parent = url("main.R");
+
+        let result = resolve_parent(&meta, &graph, &child, &config, |p| {
+            if p == "../main.R" { Some(parent.clone()) } else { None }
+        });
+
+        match result {
+            ParentResolution::Single { parent_uri, call_site_line, .. } => {
+                assert_eq!(parent_uri, parent);
+                assert_eq!(call_site_line, Some(10));
+            }
+            other => panic!("expected Single resolution, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_parent_ambiguous() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![
+                BackwardDirective {
+                    path: "../main.R".to_string(),
+                    call_site: CallSiteSpec::Default,
+                    directive_line: 0,
+                },
+                BackwardDirective {
+                    path: "../other.R".to_string(),
+                    call_site: CallSiteSpec::Default,
+                    directive_line: 1,
+                },
+            ],
+            ..Default::default()
+        };
+        let graph = DependencyGraph::new();
+        let config = CrossFileConfig::default();
+        let child = url("child.R");
+        let main = url("main.R");
+        let other = url("other.R");
+
+        let result = resolve_parent(&meta, &graph, &child, &config, |p| match p {
+            "../main.R" => Some(main.clone()),
+            "../other.R" => Some(other.clone()),
+            _ => None,
+        });
+
+        match result {
+            ParentResolution::Ambiguous { selected_uri, alternatives, .. } => {
+                assert_eq!(selected_uri, main);
+                assert_eq!(alternatives, vec![other]);
+            }
+            other => panic!("expected Ambiguous resolution, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_match_pattern_basic() {
+        let parent_content = "x <- 1\nsource(\"child.R\")\ny <- 2";
+        assert_eq!(resolve_match_pattern(parent_content, "source\\(", "child.R"), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_infer_call_site_basic() {
+        let parent_content = "x <- 1\nsource(\"child.R\")\ny <- 2";
+        assert_eq!(infer_call_site_from_parent(parent_content, "child.R"), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_infer_call_site_not_found() {
+        let parent_content = "source(\"other.R\")";
+        assert_eq!(infer_call_site_from_parent(parent_content, "child.R"), None);
+    }
+
+    #[test]
+    fn test_resolve_parent_with_content_match() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../main.R".to_string(),
+                call_site: CallSiteSpec::Match("source\\(".to_string()),
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        let graph = DependencyGraph::new();
+        let config = CrossFileConfig::default();
+        let child = url("child.R");
+        let parent = url("main.R");
+        let parent_content = "x <- 1\nsource(\"child.R\")\ny <- 2";
+
+        let result = resolve_parent_with_content(
+            &meta,
+            &graph,
+            &child,
+            &config,
+            |p| if p == "../main.R" { Some(parent.clone()) } else { None },
+            |_| Some(parent_content.to_string()),
+        );
+
+        match result {
+            ParentResolution::Single { parent_uri, call_site_line, call_site_column } => {
+                assert_eq!(parent_uri, parent);
+                assert_eq!(call_site_line, Some(1));
+                assert_eq!(call_site_column, Some(0));
+            }
+            other => panic!("expected Single resolution, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_parent_no_false_ambiguity() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../oos.r".to_string(),
+                call_site: CallSiteSpec::Default,
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+
+        let mut graph = DependencyGraph::new();
+        let child = url("subdir/collate.r");
+        let parent = url("oos.r");
+
+        let parent_meta = CrossFileMetadata {
+            sources: vec![forward_source("subdir/collate.r", 5, 0)],
+            ..Default::default()
+        };
+        graph.update_file_simple(&parent, &parent_meta);
+
+        let config = CrossFileConfig::default();
+
+        let result = resolve_parent_with_content(
+            &meta,
+            &graph,
+            &child,
+            &config,
+            |p| if p == "../oos.r" { Some(parent.clone()) } else { None },
+            |_| None,
+        );
+
+        match result {
+            ParentResolution::Single { parent_uri, .. } => assert_eq!(parent_uri, parent),
+            other => panic!("expected Single resolution, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_parent_denies_candidate_outside_project_root() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../../escape.R".to_string(),
+                call_site: CallSiteSpec::Default,
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        let graph = DependencyGraph::new();
+        let mut config = CrossFileConfig::default();
+        config.project_root = Some(url("project/"));
+        let child = url("project/child.R");
+        let outside = url("escape.R");
+
+        let result = resolve_parent(&meta, &graph, &child, &config, |p| {
+            if p == "../../escape.R" { Some(outside.clone()) } else { None }
+        });
+
+        assert!(matches!(result, ParentResolution::Denied { .. }));
+    }
+
+    #[test]
+    fn test_resolve_parent_respects_not_sourced_by() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../main.R".to_string(),
+                call_site: CallSiteSpec::Default,
+                directive_line: 0,
+            }],
+            not_sourced_by: vec!["main.R".to_string()],
+            ..Default::default()
+        };
+        let graph = DependencyGraph::new();
+        let config = CrossFileConfig::default();
+        let child = url("child.R");
+        let parent = url("main.R");
+
+        let result = resolve_parent(&meta, &graph, &child, &config, |p| {
+            if p == "../main.R" { Some(parent.clone()) } else { None }
+        });
+
+        assert!(matches!(result, ParentResolution::None));
+    }
+
+    #[test]
+    fn test_resolve_parent_falls_back_to_search_roots() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "lib/helpers.R".to_string(),
+                call_site: CallSiteSpec::Default,
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        let graph = DependencyGraph::new();
+        let mut config = CrossFileConfig::default();
+        // An unresolvable root - exercises the fallback path without
+        // depending on a real file existing on disk.
+        config.source_search_roots = vec![std::path::PathBuf::from("/nonexistent/root")];
+        let child = url("child.R");
+
+        let result = resolve_parent(&meta, &graph, &child, &config, |_| None);
+        assert!(matches!(result, ParentResolution::None));
+    }
+}
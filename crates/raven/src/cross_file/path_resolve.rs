@@ -165,6 +165,8 @@ impl PathContext {
 
 /// Resolve a path string to an absolute path.
 /// Handles file-relative, workspace-relative, and absolute paths with working directory context.
+/// If the literal path doesn't exist, also tries R's sloppy source() extension-matching
+/// rules (see [`sloppy_resolve`]) before giving up.
 pub fn resolve_path(path: &str, context: &PathContext) -> Option<PathBuf> {
     resolve_path_impl(path, context, false)
 }
@@ -218,15 +220,17 @@ fn resolve_path_impl(
             return None;
         }
         let resolved = workspace_root.unwrap().join(stripped);
-        return normalize_path(&resolved).or_else(|| {
-            log::warn!(
-                "Failed to resolve path '{}': normalization failed, attempted_path='{}', base_dir='{}'",
-                path,
-                resolved.display(),
-                base_dir.display()
-            );
-            None
-        });
+        return normalize_path(&resolved)
+            .map(|canonical| sloppy_resolve(&canonical).unwrap_or(canonical))
+            .or_else(|| {
+                log::warn!(
+                    "Failed to resolve path '{}': normalization failed, attempted_path='{}', base_dir='{}'",
+                    path,
+                    resolved.display(),
+                    base_dir.display()
+                );
+                None
+            });
     }
 
     // Try file-relative or working-directory-relative path first
@@ -234,18 +238,22 @@ fn resolve_path_impl(
     let resolved = base.join(path);
 
     if let Some(canonical) = normalize_path(&resolved) {
-        // Check if the file exists
-        if canonical.exists() {
+        // Check if the file exists, trying the literal path first and then
+        // R's sloppy source() extension-matching rules (literal -> .R -> .r ->
+        // index.R/index.r if the candidate is a directory). Mirrors Deno's
+        // SloppyImportsResolver fallback chain, adapted to R's conventional
+        // source file extensions.
+        if let Some(matched) = sloppy_resolve(&canonical) {
             log::trace!(
                 "Resolved path '{}' to canonical path: '{}'",
                 path,
-                canonical.display()
+                matched.display()
             );
-            return Some(canonical);
+            return Some(matched);
         }
 
-        // File doesn't exist at the resolved path
-        // Try workspace-root fallback if:
+        // File doesn't exist at the resolved path (even with sloppy extension
+        // matching). Try workspace-root fallback if:
         // 1. Fallback is enabled (for source() statements)
         // 2. No explicit @lsp-cd directive (working_directory is None)
         // 3. No inherited working directory
@@ -257,14 +265,14 @@ fn resolve_path_impl(
             if let Some(ref workspace_root) = context.workspace_root {
                 let workspace_resolved = workspace_root.join(path);
                 if let Some(workspace_canonical) = normalize_path(&workspace_resolved) {
-                    if workspace_canonical.exists() {
+                    if let Some(matched) = sloppy_resolve(&workspace_canonical) {
                         log::trace!(
                             "Resolved path '{}' via workspace-root fallback: '{}' (file-relative '{}' did not exist)",
                             path,
-                            workspace_canonical.display(),
+                            matched.display(),
                             canonical.display()
                         );
-                        return Some(workspace_canonical);
+                        return Some(matched);
                     }
                 }
             }
@@ -351,6 +359,38 @@ pub fn resolve_working_directory(path: &str, context: &PathContext) -> Option<Pa
     }
 }
 
+/// Try to find an existing file for `candidate`, following R's sloppy
+/// `source()` extension-matching conventions when the literal path doesn't
+/// exist: append `.R`, then `.r`, then (if `candidate` names a directory)
+/// look for an `index.R`/`index.r` inside it. Mirrors Deno's
+/// `SloppyImportsResolver` fallback chain (literal -> extensions -> directory
+/// index), adapted to the extensions R scripts actually use.
+fn sloppy_resolve(candidate: &Path) -> Option<PathBuf> {
+    if candidate.is_file() {
+        return Some(candidate.to_path_buf());
+    }
+
+    for ext in [".R", ".r"] {
+        let mut with_ext = candidate.as_os_str().to_os_string();
+        with_ext.push(ext);
+        let with_ext = PathBuf::from(with_ext);
+        if with_ext.is_file() {
+            return Some(with_ext);
+        }
+    }
+
+    if candidate.is_dir() {
+        for index_name in ["index.R", "index.r"] {
+            let index = candidate.join(index_name);
+            if index.is_file() {
+                return Some(index);
+            }
+        }
+    }
+
+    None
+}
+
 /// Normalize a path by resolving . and .. components
 fn normalize_path(path: &Path) -> Option<PathBuf> {
     let mut components = Vec::new();
@@ -747,6 +787,89 @@ mod tests {
         let result = normalize_path(path).unwrap();
         assert_eq!(result, PathBuf::from("/a"));
     }
+
+    // Tests for sloppy extension-matching (source()'s literal -> .R -> .r ->
+    // index.R/index.r fallback chain)
+
+    #[test]
+    fn test_resolve_path_sloppy_matches_uppercase_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("utils.R"), "f <- function() {}").unwrap();
+
+        let ctx = make_context(
+            &dir.path().join("main.R").to_string_lossy(),
+            Some(&dir.path().to_string_lossy()),
+        );
+        let resolved = resolve_path("utils", &ctx).unwrap();
+        assert_eq!(resolved, dir.path().join("utils.R"));
+    }
+
+    #[test]
+    fn test_resolve_path_sloppy_matches_lowercase_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("utils.r"), "f <- function() {}").unwrap();
+
+        let ctx = make_context(
+            &dir.path().join("main.R").to_string_lossy(),
+            Some(&dir.path().to_string_lossy()),
+        );
+        let resolved = resolve_path("utils", &ctx).unwrap();
+        assert_eq!(resolved, dir.path().join("utils.r"));
+    }
+
+    #[test]
+    fn test_resolve_path_sloppy_prefers_literal_match_over_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("utils"), "f <- function() {}").unwrap();
+        std::fs::write(dir.path().join("utils.R"), "g <- function() {}").unwrap();
+
+        let ctx = make_context(
+            &dir.path().join("main.R").to_string_lossy(),
+            Some(&dir.path().to_string_lossy()),
+        );
+        let resolved = resolve_path("utils", &ctx).unwrap();
+        assert_eq!(resolved, dir.path().join("utils"));
+    }
+
+    #[test]
+    fn test_resolve_path_sloppy_matches_directory_index() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("helpers")).unwrap();
+        std::fs::write(dir.path().join("helpers/index.R"), "f <- function() {}").unwrap();
+
+        let ctx = make_context(
+            &dir.path().join("main.R").to_string_lossy(),
+            Some(&dir.path().to_string_lossy()),
+        );
+        let resolved = resolve_path("helpers", &ctx).unwrap();
+        assert_eq!(resolved, dir.path().join("helpers/index.R"));
+    }
+
+    #[test]
+    fn test_resolve_path_sloppy_no_match_returns_literal_path_for_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let ctx = make_context(
+            &dir.path().join("main.R").to_string_lossy(),
+            Some(&dir.path().to_string_lossy()),
+        );
+        let resolved = resolve_path("missing", &ctx).unwrap();
+        assert_eq!(resolved, dir.path().join("missing"));
+    }
+
+    #[test]
+    fn test_resolve_path_with_workspace_fallback_applies_sloppy_matching() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("utils.R"), "f <- function() {}").unwrap();
+
+        let ctx = make_context(
+            &dir.path().join("src/main.R").to_string_lossy(),
+            Some(&dir.path().to_string_lossy()),
+        );
+        let resolved = resolve_path_with_workspace_fallback("utils", &ctx).unwrap();
+        assert_eq!(resolved, dir.path().join("utils.R"));
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,129 @@
+//
+// cross_file/pending_fetch.rs
+//
+// Bounded, deduplicated queue of URIs awaiting background metadata/artifacts
+// computation
+//
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use tower_lsp::lsp_types::Url;
+
+/// Default capacity for a [`PendingFetchQueue`].
+const DEFAULT_PENDING_FETCH_CAPACITY: usize = 200;
+
+/// A bounded FIFO queue of URIs that a [`ContentProvider`](super::content_provider::ContentProvider)
+/// couldn't serve from its cached tiers and handed off for background
+/// read-and-compute.
+///
+/// Deduplicates by URI (re-enqueuing an already-queued URI is a no-op) and,
+/// like kismet-cache's bounded caches, drops the *oldest* entry to make room
+/// for a new one once at capacity, rather than rejecting the new request —
+/// the most recently requested file is the one most likely to be asked about
+/// again next.
+#[derive(Debug)]
+pub struct PendingFetchQueue {
+    capacity: usize,
+    queue: Mutex<VecDeque<Url>>,
+}
+
+impl Default for PendingFetchQueue {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_PENDING_FETCH_CAPACITY)
+    }
+}
+
+impl PendingFetchQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Enqueue `uri` for background fetch. Returns `false` if `uri` was
+    /// already queued (no-op), `true` if it was newly enqueued.
+    pub fn enqueue(&self, uri: Url) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.contains(&uri) {
+            return false;
+        }
+        if queue.len() >= self.capacity {
+            if let Some(dropped) = queue.pop_front() {
+                log::trace!(
+                    "Pending fetch queue full, dropping oldest entry: {}",
+                    dropped
+                );
+            }
+        }
+        queue.push_back(uri);
+        true
+    }
+
+    /// Whether `uri` is currently queued.
+    pub fn contains(&self, uri: &Url) -> bool {
+        self.queue.lock().unwrap().contains(uri)
+    }
+
+    /// Number of URIs currently queued.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drain every queued URI for processing by a background worker.
+    pub fn drain(&self) -> Vec<Url> {
+        self.queue.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(name: &str) -> Url {
+        Url::parse(&format!("file:///project/{}.R", name)).unwrap()
+    }
+
+    #[test]
+    fn test_enqueue_dedups() {
+        let queue = PendingFetchQueue::new();
+        assert!(queue.enqueue(url("a")));
+        assert!(!queue.enqueue(url("a")));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_drain_returns_fifo_order() {
+        let queue = PendingFetchQueue::new();
+        queue.enqueue(url("a"));
+        queue.enqueue(url("b"));
+        assert_eq!(queue.drain(), vec![url("a"), url("b")]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_bounded_drops_oldest() {
+        let queue = PendingFetchQueue::with_capacity(2);
+        queue.enqueue(url("a"));
+        queue.enqueue(url("b"));
+        queue.enqueue(url("c"));
+        assert_eq!(queue.drain(), vec![url("b"), url("c")]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let queue = PendingFetchQueue::new();
+        queue.enqueue(url("a"));
+        assert!(queue.contains(&url("a")));
+        assert!(!queue.contains(&url("b")));
+    }
+}
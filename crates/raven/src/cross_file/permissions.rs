@@ -0,0 +1,167 @@
+//
+// cross_file/permissions.rs
+//
+// Trust/permission gating for on-disk cross-file reads
+//
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Environment variable that disables the permission check entirely, for CI
+/// or root-owned environments where a restrictive umask makes the check
+/// meaningless. Mirrors Arti's `ARTI_FS_DISABLE_PERMISSION_CHECKS` escape
+/// hatch for its `fs-mistrust` crate.
+pub const DISABLE_PERMISSION_CHECKS_ENV_VAR: &str = "RAVEN_FS_DISABLE_PERMISSION_CHECKS";
+
+/// Decides whether a file is safe to read off disk before it's ingested into
+/// the cross-file cache, following the approach Arti's `fs-mistrust` takes:
+/// a world-writable file (or a world-writable parent directory) could have
+/// its content swapped out from under us by another user on a shared or
+/// multi-user checkout, so it shouldn't be trusted implicitly.
+pub trait PermissionChecker: Send + Sync {
+    /// Returns `true` if `path` and its ancestors are not writable by users
+    /// other than their owner.
+    fn is_trusted(&self, path: &Path) -> bool;
+}
+
+/// Default [`PermissionChecker`], backed by real filesystem permission bits.
+/// A no-op (always trusts) on non-Unix platforms, where there's no portable
+/// equivalent of the world-writable bit to check.
+#[derive(Debug, Clone, Copy)]
+pub struct FsPermissionChecker {
+    disabled: bool,
+}
+
+impl FsPermissionChecker {
+    /// Construct a checker, honoring [`DISABLE_PERMISSION_CHECKS_ENV_VAR`] if set.
+    pub fn new() -> Self {
+        Self {
+            disabled: std::env::var_os(DISABLE_PERMISSION_CHECKS_ENV_VAR).is_some(),
+        }
+    }
+
+    /// Construct a checker that always trusts, regardless of the environment
+    /// variable - useful for tests that want to assert on other behavior
+    /// without fighting permission bits.
+    pub fn disabled() -> Self {
+        Self { disabled: true }
+    }
+}
+
+impl Default for FsPermissionChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PermissionChecker for FsPermissionChecker {
+    fn is_trusted(&self, path: &Path) -> bool {
+        if self.disabled {
+            return true;
+        }
+        is_trusted_impl(path)
+    }
+}
+
+/// Returns the shared default permission checker.
+pub fn default_permission_checker() -> Arc<dyn PermissionChecker> {
+    Arc::new(FsPermissionChecker::new())
+}
+
+#[cfg(unix)]
+fn is_trusted_impl(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut current: Option<PathBuf> = Some(path.to_path_buf());
+    while let Some(p) = current {
+        match std::fs::symlink_metadata(&p) {
+            Ok(meta) => {
+                // World-writable bit (the "other" triad's write permission).
+                if meta.permissions().mode() & 0o002 != 0 {
+                    log::warn!(
+                        "Refusing to trust {} for cross-file reads: {} is world-writable",
+                        path.display(),
+                        p.display()
+                    );
+                    return false;
+                }
+            }
+            Err(_) => {
+                // Can't stat this ancestor (e.g. permission denied further up);
+                // the read itself will surface the real error, so don't fail
+                // the trust check on it.
+                return true;
+            }
+        }
+        current = p.parent().map(PathBuf::from);
+    }
+    true
+}
+
+#[cfg(not(unix))]
+fn is_trusted_impl(_path: &Path) -> bool {
+    true
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_trusts_normal_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.R");
+        fs::write(&path, "x <- 1").unwrap();
+
+        assert!(FsPermissionChecker::new().is_trusted(&path));
+    }
+
+    #[test]
+    fn test_distrusts_world_writable_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.R");
+        fs::write(&path, "x <- 1").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+
+        assert!(!FsPermissionChecker::new().is_trusted(&path));
+    }
+
+    #[test]
+    fn test_distrusts_world_writable_parent_dir() {
+        let dir = tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        let path = sub.join("a.R");
+        fs::write(&path, "x <- 1").unwrap();
+        fs::set_permissions(&sub, fs::Permissions::from_mode(0o777)).unwrap();
+
+        assert!(!FsPermissionChecker::new().is_trusted(&path));
+    }
+
+    #[test]
+    fn test_disabled_checker_always_trusts() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.R");
+        fs::write(&path, "x <- 1").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+
+        assert!(FsPermissionChecker::disabled().is_trusted(&path));
+    }
+
+    #[test]
+    fn test_env_var_disables_checks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.R");
+        fs::write(&path, "x <- 1").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+
+        std::env::set_var(DISABLE_PERMISSION_CHECKS_ENV_VAR, "1");
+        let checker = FsPermissionChecker::new();
+        std::env::remove_var(DISABLE_PERMISSION_CHECKS_ENV_VAR);
+
+        assert!(checker.is_trusted(&path));
+    }
+}
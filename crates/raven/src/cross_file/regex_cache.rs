@@ -0,0 +1,195 @@
+//
+// cross_file/regex_cache.rs
+//
+// Compiled-regex cache for `match=` call-site directives
+//
+// NOTE: `resolve_match_pattern` (in `cross_file::parent_resolve`), which
+// resolves a `CallSiteSpec::Match` directive, delegates to
+// `resolve_match_pattern_regex` below with a process-wide `RegexCache`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use regex::Regex;
+
+/// Compiles `match=` patterns into [`Regex`]es once and reuses them across
+/// resolutions, keyed by the raw pattern string. An invalid pattern is cached
+/// as `None` so a typo'd directive doesn't pay the compilation cost (and
+/// doesn't log a failure) on every resolution - see
+/// [`RegexCache::compile`].
+#[derive(Default)]
+pub struct RegexCache {
+    patterns: Mutex<HashMap<String, Option<Regex>>>,
+}
+
+impl RegexCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile `pattern`, or return the cached result of a previous
+    /// compilation attempt. Returns `None` if `pattern` is not a valid
+    /// regular expression; callers should fall back to literal-substring
+    /// matching in that case rather than treating it as an error.
+    pub fn compile(&self, pattern: &str) -> Option<Regex> {
+        let mut patterns = self
+            .patterns
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(cached) = patterns.get(pattern) {
+            return cached.clone();
+        }
+
+        let compiled = Regex::new(pattern).ok();
+        patterns.insert(pattern.to_string(), compiled.clone());
+        compiled
+    }
+
+    /// Number of distinct patterns compiled (or attempted) so far, valid or
+    /// not. Exposed for tests that want to assert a pattern was only
+    /// compiled once.
+    pub fn len(&self) -> usize {
+        self.patterns
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Convert a byte offset within a single line into a UTF-16 column, by
+/// summing `len_utf16()` over the chars preceding it.
+fn byte_offset_to_utf16_column(line_text: &str, byte_offset_in_line: usize) -> u32 {
+    line_text[..byte_offset_in_line.min(line_text.len())]
+        .chars()
+        .map(|ch| ch.len_utf16() as u32)
+        .sum()
+}
+
+/// Regex-aware counterpart of a literal `line.find(pattern)` match, called by
+/// `resolve_match_pattern`. Compiles `pattern` via `cache` (or reuses an
+/// already-compiled [`Regex`]) and searches `parent_content` line by line,
+/// preserving the existing precedence: prefer the first match on a line that
+/// also contains a `source()`/`sys.source()` call to `child_path`, else fall
+/// back to the first match anywhere. On an invalid regex, degrades
+/// gracefully to the previous literal-substring behavior instead of
+/// returning `None` outright.
+pub fn resolve_match_pattern_regex(
+    cache: &RegexCache,
+    parent_content: &str,
+    pattern: &str,
+    child_path: &str,
+) -> Option<(u32, u32)> {
+    let child_filename = std::path::Path::new(child_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(child_path);
+
+    let regex = cache.compile(pattern);
+    let mut first_match: Option<(u32, u32)> = None;
+
+    for (line_num, line) in parent_content.lines().enumerate() {
+        let byte_offset = match &regex {
+            Some(re) => re.find(line).map(|m| m.start()),
+            None => line.find(pattern),
+        };
+
+        let Some(byte_offset) = byte_offset else {
+            continue;
+        };
+
+        let utf16_col = byte_offset_to_utf16_column(line, byte_offset);
+        let pos = (line_num as u32, utf16_col);
+
+        let normalized_line = super::child_path::normalize_separators(line);
+        let has_source_call = (line.contains("source(") || line.contains("sys.source("))
+            && (normalized_line.contains(&super::child_path::normalize_separators(child_path))
+                || normalized_line
+                    .contains(&super::child_path::normalize_separators(child_filename)));
+
+        if has_source_call {
+            return Some(pos);
+        }
+
+        if first_match.is_none() {
+            first_match = Some(pos);
+        }
+    }
+
+    first_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiles_and_reuses_pattern() {
+        let cache = RegexCache::new();
+        assert!(cache.compile(r"^source\(").is_some());
+        assert_eq!(cache.len(), 1);
+        assert!(cache.compile(r"^source\(").is_some());
+        assert_eq!(cache.len(), 1, "second call should reuse the cached compilation");
+    }
+
+    #[test]
+    fn test_invalid_pattern_caches_none_without_panicking() {
+        let cache = RegexCache::new();
+        assert!(cache.compile("(unclosed").is_none());
+        assert_eq!(cache.len(), 1);
+        assert!(cache.compile("(unclosed").is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_prefers_source_call_line() {
+        let cache = RegexCache::new();
+        let content = "# match: child\nsource(\"child.R\")\n# another child mention\n";
+        let pos = resolve_match_pattern_regex(&cache, content, r"child", "child.R");
+        assert_eq!(pos, Some((1, 8)));
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_first_match_without_source_call() {
+        let cache = RegexCache::new();
+        let content = "# references child.R in a comment\ny <- 1\n";
+        let pos = resolve_match_pattern_regex(&cache, content, r"child\.R", "child.R");
+        assert_eq!(pos, Some((0, 13)));
+    }
+
+    #[test]
+    fn test_resolve_prefers_source_call_line_with_windows_style_literal() {
+        let cache = RegexCache::new();
+        let content = "# match: child\nsource(\"subdir\\\\child.R\")\n# another child mention\n";
+        let pos = resolve_match_pattern_regex(&cache, content, r"child", "subdir/child.R");
+        assert_eq!(pos, Some((1, 16)));
+    }
+
+    #[test]
+    fn test_resolve_degrades_to_literal_match_on_invalid_regex() {
+        let cache = RegexCache::new();
+        let content = "x <- 1\nsource(\"child.R\") # (unbalanced\n";
+        let pos = resolve_match_pattern_regex(&cache, content, "(unbalanced", "child.R");
+        assert_eq!(pos, Some((1, 20)));
+    }
+
+    #[test]
+    fn test_resolve_anchored_regex_disambiguates_multiple_source_lines() {
+        let cache = RegexCache::new();
+        let content = "source(\"child.R\") # decoy, not actually run\nif (FALSE) source(\"child.R\")\nsource(\"child.R\") # the real call\n";
+        // Anchor to the comment that marks the real call site.
+        let pos = resolve_match_pattern_regex(&cache, content, r"the real call$", "child.R");
+        assert_eq!(pos.map(|(line, _)| line), Some(2));
+    }
+
+    #[test]
+    fn test_no_match_returns_none() {
+        let cache = RegexCache::new();
+        let pos = resolve_match_pattern_regex(&cache, "x <- 1\n", r"nope", "child.R");
+        assert_eq!(pos, None);
+    }
+}
@@ -0,0 +1,127 @@
+//
+// cross_file/sandbox.rs
+//
+// Project-root sandbox confinement for parent/source resolution
+//
+// NOTE: when `CrossFileConfig::project_root` is set,
+// `resolve_parent_with_content` (in `cross_file::parent_resolve`) calls
+// `check_confinement` for each directive/reverse-edge candidate and returns
+// `ParentResolution::Denied` when every candidate is rejected.
+//
+// Confinement rules mirror librsvg's `UrlResolver`/`AllowedUrl`: only `file:`
+// scheme URIs are permitted, a root of `/` itself never permits anything (an
+// unconfigured or misconfigured root shouldn't silently allow the entire
+// filesystem), and a candidate whose normalized path climbs above the root
+// via `..` segments is rejected.
+
+use tower_lsp::lsp_types::Url;
+
+/// Check whether `candidate` is allowed to resolve under `project_root`.
+/// Returns `Ok(())` if confinement allows it, or `Err(reason)` describing why
+/// it was rejected - suitable for a `ParentResolution::Denied { reason, .. }`.
+pub fn check_confinement(candidate: &Url, project_root: &Url) -> Result<(), String> {
+    if candidate.scheme() != "file" {
+        return Err(format!(
+            "candidate URI scheme '{}' is not 'file'",
+            candidate.scheme()
+        ));
+    }
+    if project_root.scheme() != "file" {
+        return Err("project root is not a file: URI".to_string());
+    }
+
+    let root_path = project_root
+        .to_file_path()
+        .map_err(|_| "project root is not a valid filesystem path".to_string())?;
+    if root_path.parent().is_none() {
+        // Root is the filesystem root itself ("/"); an unconfigured or
+        // overly-broad root must never be treated as "allow everything".
+        return Err("project root must not be the filesystem root".to_string());
+    }
+
+    let candidate_path = candidate
+        .to_file_path()
+        .map_err(|_| "candidate URI is not a valid filesystem path".to_string())?;
+
+    let normalized = normalize(&candidate_path);
+    let normalized_root = normalize(&root_path);
+
+    if normalized.starts_with(&normalized_root) {
+        Ok(())
+    } else {
+        Err(format!(
+            "candidate path '{}' escapes project root '{}'",
+            normalized.display(),
+            normalized_root.display()
+        ))
+    }
+}
+
+/// Lexically normalize a path, resolving `.`/`..` segments without touching
+/// the filesystem (the path may not exist yet, e.g. while resolving a
+/// directive that points at a deleted file).
+fn normalize(path: &std::path::Path) -> std::path::PathBuf {
+    use std::path::Component;
+
+    let mut out = std::path::PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                if !out.pop() {
+                    // Climbed above the root we started from; leave the
+                    // ParentDir in place so the resulting path can never
+                    // spuriously `starts_with` a confinement root.
+                    out.push("..");
+                }
+            }
+            Component::CurDir => {}
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_allows_candidate_within_root() {
+        let root = uri("file:///project/");
+        let candidate = uri("file:///project/subdir/child.R");
+        assert!(check_confinement(&candidate, &root).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_candidate_escaping_root_via_dotdot() {
+        let root = uri("file:///project/subdir/");
+        let candidate = uri("file:///project/subdir/../../../../etc/passwd");
+        assert!(check_confinement(&candidate, &root).is_err());
+    }
+
+    #[test]
+    fn test_rejects_non_file_scheme_candidate() {
+        let root = uri("file:///project/");
+        let candidate = uri("https://example.com/child.R");
+        assert!(check_confinement(&candidate, &root).is_err());
+    }
+
+    #[test]
+    fn test_rejects_filesystem_root_as_project_root() {
+        let root = uri("file:///");
+        let candidate = uri("file:///etc/passwd");
+        assert!(check_confinement(&candidate, &root).is_err());
+    }
+
+    #[test]
+    fn test_rejects_sibling_path_that_merely_shares_a_prefix() {
+        // "/project-secret" must not be treated as inside "/project".
+        let root = uri("file:///project/");
+        let candidate = uri("file:///project-secret/child.R");
+        assert!(check_confinement(&candidate, &root).is_err());
+    }
+}
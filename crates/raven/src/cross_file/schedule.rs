@@ -0,0 +1,320 @@
+//
+// cross_file/schedule.rs
+//
+// Pipelined, dependency-ordered scheduling of cross-file resolution
+//
+
+//! Schedules a batch of files for cross-file resolution in dependency order,
+//! mirroring cargo's pipelined `DependencyQueue`: a file's *metadata* (its
+//! exported symbol names and signatures — the data `scope_at_position_with_graph`
+//! needs from a `source()`d file) can be released to dependents as soon as it's
+//! computed, without waiting for that file's own *full* local scope/diagnostics
+//! pass. A dependent only blocks on the metadata of the files it directly
+//! `source()`s, so a deep chain no longer serializes on each file's full
+//! analysis before its dependents can even start.
+//!
+//! Cycles (mutually-sourcing files) can't be topologically ordered, so every
+//! file in a cycle is released for scheduling immediately, using whatever
+//! metadata snapshot is already on hand (best-effort), rather than deadlocking
+//! the whole batch.
+
+use std::collections::{HashMap, HashSet};
+
+use tower_lsp::lsp_types::Url;
+
+use super::dependency::DependencyGraph;
+
+/// Tracks which files in a scheduled batch are still waiting on the metadata
+/// of files they `source()`, and releases them as soon as their blockers
+/// complete.
+///
+/// `ResolutionScheduler` only reasons about the *metadata* stage: the full
+/// stage (local scope/diagnostics) for a file never blocks other files, so
+/// callers can run it whenever a file becomes ready with no further
+/// coordination.
+pub struct ResolutionScheduler {
+    /// Remaining count of not-yet-completed metadata dependencies, per file.
+    pending: HashMap<Url, usize>,
+    /// Reverse lookup: uri -> files in this batch that source it.
+    dependents: HashMap<Url, Vec<Url>>,
+    /// Files released for metadata extraction but not yet taken by a caller.
+    frontier: Vec<Url>,
+    /// Files whose metadata stage has completed.
+    completed: HashSet<Url>,
+}
+
+impl ResolutionScheduler {
+    /// Builds a scheduler for `files`, using `graph` to find each file's
+    /// `source()` targets within the batch.
+    ///
+    /// Files outside the batch are treated as already available (their
+    /// metadata isn't something this scheduler is responsible for), so they
+    /// never block. Files that participate in a `source()` cycle are placed
+    /// directly on the initial frontier, since no topological order exists
+    /// for them — the caller gets a best-effort snapshot rather than a stall.
+    pub fn new(graph: &DependencyGraph, files: impl IntoIterator<Item = Url>) -> Self {
+        let files: HashSet<Url> = files.into_iter().collect();
+        let cyclic = Self::find_cyclic_members(graph, &files);
+
+        let mut pending: HashMap<Url, usize> = HashMap::new();
+        let mut dependents: HashMap<Url, Vec<Url>> = HashMap::new();
+
+        for uri in &files {
+            let blockers: HashSet<Url> = graph
+                .get_dependencies(uri)
+                .into_iter()
+                .map(|edge| edge.to.clone())
+                .filter(|to| files.contains(to) && to != uri)
+                .collect();
+
+            for blocker in &blockers {
+                dependents
+                    .entry(blocker.clone())
+                    .or_default()
+                    .push(uri.clone());
+            }
+
+            let count = if cyclic.contains(uri) {
+                0
+            } else {
+                blockers.len()
+            };
+            pending.insert(uri.clone(), count);
+        }
+
+        let frontier: Vec<Url> = pending
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(uri, _)| uri.clone())
+            .collect();
+
+        Self {
+            pending,
+            dependents,
+            frontier,
+            completed: HashSet::new(),
+        }
+    }
+
+    /// Finds every file in `files` that's a member of a `source()` cycle,
+    /// restricted to edges that stay within the batch.
+    fn find_cyclic_members(graph: &DependencyGraph, files: &HashSet<Url>) -> HashSet<Url> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            InProgress,
+            Done,
+        }
+
+        let mut marks: HashMap<Url, Mark> = HashMap::new();
+        let mut on_stack: Vec<Url> = Vec::new();
+        let mut cyclic = HashSet::new();
+
+        fn visit(
+            uri: &Url,
+            graph: &DependencyGraph,
+            files: &HashSet<Url>,
+            marks: &mut HashMap<Url, Mark>,
+            on_stack: &mut Vec<Url>,
+            cyclic: &mut HashSet<Url>,
+        ) {
+            if let Some(mark) = marks.get(uri) {
+                if *mark == Mark::InProgress {
+                    // Found a back-edge: everything from `uri` to the top of
+                    // the stack is part of a cycle.
+                    if let Some(pos) = on_stack.iter().position(|u| u == uri) {
+                        cyclic.extend(on_stack[pos..].iter().cloned());
+                    }
+                }
+                return;
+            }
+
+            marks.insert(uri.clone(), Mark::InProgress);
+            on_stack.push(uri.clone());
+
+            for edge in graph.get_dependencies(uri) {
+                if files.contains(&edge.to) && edge.to != *uri {
+                    visit(&edge.to, graph, files, marks, on_stack, cyclic);
+                }
+            }
+
+            on_stack.pop();
+            marks.insert(uri.clone(), Mark::Done);
+        }
+
+        for uri in files {
+            if !marks.contains_key(uri) {
+                visit(uri, graph, files, &mut marks, &mut on_stack, &mut cyclic);
+            }
+        }
+
+        cyclic
+    }
+
+    /// Drains and returns every file currently ready for its metadata stage
+    /// (all its in-batch `source()` targets already completed, or it's part
+    /// of a cycle and was released up front).
+    pub fn take_ready(&mut self) -> Vec<Url> {
+        std::mem::take(&mut self.frontier)
+    }
+
+    /// Marks `uri`'s metadata stage complete and returns the dependents that
+    /// became newly ready as a result.
+    ///
+    /// A no-op if `uri` wasn't part of the scheduled batch or was already
+    /// completed.
+    pub fn complete(&mut self, uri: &Url) -> Vec<Url> {
+        if !self.pending.contains_key(uri) || !self.completed.insert(uri.clone()) {
+            return Vec::new();
+        }
+
+        let mut newly_ready = Vec::new();
+        if let Some(waiting) = self.dependents.get(uri) {
+            for dependent in waiting {
+                if let Some(count) = self.pending.get_mut(dependent) {
+                    // A count already at 0 belongs to a file released earlier
+                    // (e.g. a cycle member); it isn't newly ready.
+                    if *count > 0 {
+                        *count -= 1;
+                        if *count == 0 {
+                            newly_ready.push(dependent.clone());
+                        }
+                    }
+                }
+            }
+        }
+        self.frontier.extend(newly_ready.iter().cloned());
+        newly_ready
+    }
+
+    /// Whether every file in the batch has had its metadata stage completed.
+    pub fn is_done(&self) -> bool {
+        self.completed.len() == self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(name: &str) -> Url {
+        Url::parse(&format!("file:///project/{}.R", name)).unwrap()
+    }
+
+    fn workspace_root() -> Url {
+        Url::parse("file:///project").unwrap()
+    }
+
+    fn metadata_for(source_names: &[&str]) -> super::super::types::CrossFileMetadata {
+        let mut meta = super::super::types::CrossFileMetadata::default();
+        for (i, name) in source_names.iter().enumerate() {
+            meta.sources.push(super::super::types::ForwardSource {
+                path: format!("{}.R", name),
+                line: i as u32,
+                column: 0,
+                is_directive: false,
+                local: false,
+                chdir: false,
+                is_sys_source: false,
+                sys_source_global_env: false,
+                explicit_line: false,
+                directive_line: i as u32,
+                user_line_zero: false,
+            });
+        }
+        meta
+    }
+
+    /// Builds a graph where each `(parent, children)` entry means `parent`
+    /// sources every file in `children`.
+    fn graph_from_edges(edges: &[(&str, &[&str])]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        for (parent, children) in edges {
+            let uri = url(parent);
+            graph.update_file(
+                &uri,
+                &metadata_for(children),
+                Some(&workspace_root()),
+                |_| None,
+            );
+        }
+        graph
+    }
+
+    #[test]
+    fn test_linear_chain_releases_leaf_first() {
+        // a sources b, b sources c: c has no blockers and should be ready immediately.
+        let graph = graph_from_edges(&[("a", &["b"]), ("b", &["c"])]);
+        let mut scheduler = ResolutionScheduler::new(&graph, [url("a"), url("b"), url("c")]);
+
+        assert_eq!(scheduler.take_ready(), vec![url("c")]);
+
+        let newly_ready = scheduler.complete(&url("c"));
+        assert_eq!(newly_ready, vec![url("b")]);
+
+        let newly_ready = scheduler.complete(&url("b"));
+        assert_eq!(newly_ready, vec![url("a")]);
+
+        assert!(!scheduler.is_done());
+        scheduler.complete(&url("a"));
+        assert!(scheduler.is_done());
+    }
+
+    #[test]
+    fn test_diamond_waits_for_both_branches() {
+        // a sources b and c; both b and c source d.
+        let graph = graph_from_edges(&[("a", &["b", "c"]), ("b", &["d"]), ("c", &["d"])]);
+        let mut scheduler =
+            ResolutionScheduler::new(&graph, [url("a"), url("b"), url("c"), url("d")]);
+
+        assert_eq!(scheduler.take_ready(), vec![url("d")]);
+
+        let newly_ready = scheduler.complete(&url("d"));
+        let mut sorted = newly_ready.clone();
+        sorted.sort_by_key(|u| u.to_string());
+        assert_eq!(sorted, vec![url("b"), url("c")]);
+
+        // `a` shouldn't be ready until both b and c complete.
+        assert!(scheduler.complete(&url("b")).is_empty());
+        assert_eq!(scheduler.complete(&url("c")), vec![url("a")]);
+    }
+
+    #[test]
+    fn test_cycle_members_released_up_front() {
+        // a and b mutually source each other; c sources a.
+        let graph = graph_from_edges(&[("a", &["b"]), ("b", &["a"]), ("c", &["a"])]);
+        let mut scheduler = ResolutionScheduler::new(&graph, [url("a"), url("b"), url("c")]);
+
+        let mut ready = scheduler.take_ready();
+        ready.sort_by_key(|u| u.to_string());
+        assert_eq!(
+            ready,
+            vec![url("a"), url("b")],
+            "cycle members should be released without waiting, as a best-effort fallback"
+        );
+
+        // c still waits on a's completion even though a was released up front.
+        assert_eq!(scheduler.complete(&url("a")), vec![url("c")]);
+    }
+
+    #[test]
+    fn test_files_outside_batch_never_block() {
+        // a sources an external file not included in this batch.
+        let graph = graph_from_edges(&[("a", &["external"])]);
+        let mut scheduler = ResolutionScheduler::new(&graph, [url("a")]);
+
+        assert_eq!(scheduler.take_ready(), vec![url("a")]);
+    }
+
+    #[test]
+    fn test_complete_is_idempotent_and_ignores_unknown_uris() {
+        let graph = graph_from_edges(&[("a", &["b"])]);
+        let mut scheduler = ResolutionScheduler::new(&graph, [url("a"), url("b")]);
+
+        scheduler.take_ready();
+        assert_eq!(scheduler.complete(&url("b")), vec![url("a")]);
+        // Completing again shouldn't re-release `a`.
+        assert!(scheduler.complete(&url("b")).is_empty());
+        // An unscheduled uri is simply ignored.
+        assert!(scheduler.complete(&url("unscheduled")).is_empty());
+    }
+}
@@ -231,6 +231,22 @@ impl FunctionScopeTree {
         self.count
     }
 
+    /// All stored intervals as `(start_line, start_column, end_line, end_column)`
+    /// tuples, in-order. Used to persist a tree without serializing its
+    /// private node graph directly - reconstruct with [`Self::from_scopes`].
+    pub fn intervals(&self) -> Vec<(u32, u32, u32, u32)> {
+        fn walk(node: &Option<Box<IntervalNode>>, out: &mut Vec<(u32, u32, u32, u32)>) {
+            if let Some(node) = node {
+                walk(&node.left, out);
+                out.push(node.interval.as_tuple());
+                walk(&node.right, out);
+            }
+        }
+        let mut out = Vec::with_capacity(self.count);
+        walk(&self.root, &mut out);
+        out
+    }
+
     /// Constructs a balanced FunctionScopeTree from a slice of function-scope tuples.
     ///
     /// Invalid intervals (where a start position is after its end) are omitted with a warning.
@@ -524,6 +540,7 @@ impl Hash for ScopedSymbol {
         self.source_uri.hash(state);
         self.defined_line.hash(state);
         self.defined_column.hash(state);
+        self.signature.hash(state);
         self.is_declared.hash(state);
     }
 }
@@ -875,10 +892,29 @@ pub fn compute_artifacts(uri: &Url, tree: &Tree, content: &str) -> ScopeArtifact
         })
         .collect();
 
+    // Extract source() targets in timeline order for interface hash computation
+    // (the order forward sources are sourced in can change which symbol wins a
+    // naming collision for anything that sources this file transitively)
+    let source_targets: Vec<String> = artifacts
+        .timeline
+        .iter()
+        .filter_map(|event| {
+            if let ScopeEvent::Source { source, .. } = event {
+                Some(source.path.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+
     // Compute interface hash including symbols, loaded packages, and declared symbols
     // Note: compute_artifacts (without metadata) has no declared symbols
-    artifacts.interface_hash =
-        compute_interface_hash(&artifacts.exported_interface, &loaded_packages, &[]);
+    artifacts.interface_hash = compute_interface_hash(
+        &artifacts.exported_interface,
+        &loaded_packages,
+        &[],
+        &source_targets,
+    );
 
     artifacts
 }
@@ -1144,11 +1180,27 @@ pub fn compute_artifacts_with_metadata(
         })
         .unwrap_or_default();
 
+    // Extract source() targets in timeline order for interface hash computation
+    // (the order forward sources are sourced in can change which symbol wins a
+    // naming collision for anything that sources this file transitively)
+    let source_targets: Vec<String> = artifacts
+        .timeline
+        .iter()
+        .filter_map(|event| {
+            if let ScopeEvent::Source { source, .. } = event {
+                Some(source.path.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+
     // Compute interface hash including symbols, loaded packages, and declared symbols
     artifacts.interface_hash = compute_interface_hash(
         &artifacts.exported_interface,
         &loaded_packages,
         &declared_symbols,
+        &source_targets,
     );
 
     artifacts
@@ -2248,15 +2300,18 @@ fn node_text<'a>(node: Node<'a>, content: &'a str) -> &'a str {
     &content[node.byte_range()]
 }
 
-/// Compute a deterministic hash of the exported interface and loaded packages.
+/// Compute a deterministic hash of the exported interface, loaded packages, and source() targets.
 ///
 /// Symbols are incorporated deterministically by sorting the interface keys before hashing each
-/// ScopedSymbol; package names are included sorted as well. The resulting hash is suitable for
-/// cache invalidation when a file's exported symbols or loaded packages change.
+/// ScopedSymbol (whose `Hash` impl covers its signature, so a changed argument list invalidates
+/// the hash); package names are included sorted as well. `source_targets` is hashed in its given
+/// order rather than sorted, since the order forward sources are sourced in can change which
+/// symbol wins a name collision for anything that sources this file transitively. The resulting
+/// hash is suitable for cache invalidation when a file's externally observable surface changes.
 ///
 /// # Returns
 ///
-/// `u64` hash of the provided `interface`, `packages`, and `declared_symbols`.
+/// `u64` hash of the provided `interface`, `packages`, `declared_symbols`, and `source_targets`.
 ///
 /// # Examples
 ///
@@ -2268,14 +2323,16 @@ fn node_text<'a>(node: Node<'a>, content: &'a str) -> &'a str {
 /// let interface: HashMap<Arc<str>, crate::ScopedSymbol> = HashMap::new();
 /// let packages: Vec<String> = Vec::new();
 /// let declared: Vec<DeclaredSymbol> = Vec::new();
-/// let h1 = crate::compute_interface_hash(&interface, &packages, &declared);
-/// let h2 = crate::compute_interface_hash(&interface, &packages, &declared);
+/// let sources: Vec<String> = Vec::new();
+/// let h1 = crate::compute_interface_hash(&interface, &packages, &declared, &sources);
+/// let h2 = crate::compute_interface_hash(&interface, &packages, &declared, &sources);
 /// assert_eq!(h1, h2);
 /// ```
 fn compute_interface_hash(
     interface: &HashMap<Arc<str>, ScopedSymbol>,
     packages: &[String],
     declared_symbols: &[super::types::DeclaredSymbol],
+    source_targets: &[String],
 ) -> u64 {
     let mut hasher = DefaultHasher::new();
 
@@ -2308,6 +2365,13 @@ fn compute_interface_hash(
         decl.line.hash(&mut hasher);
     }
 
+    // Include forward source() targets in their original (unsorted) order: reordering
+    // `source()` calls can change which file's symbols win a naming collision for anything
+    // that sources this file transitively, even when no symbol in this file itself changed.
+    for target in source_targets {
+        target.hash(&mut hasher);
+    }
+
     hasher.finish()
 }
 
@@ -3047,6 +3111,38 @@ mod tests {
         assert_ne!(artifacts1.interface_hash, artifacts2.interface_hash);
     }
 
+    #[test]
+    fn test_interface_hash_changes_on_signature_change() {
+        let code1 = "f <- function(a) a";
+        let code2 = "f <- function(a, b) a + b";
+        let tree1 = parse_r(code1);
+        let tree2 = parse_r(code2);
+        let artifacts1 = compute_artifacts(&test_uri(), &tree1, code1);
+        let artifacts2 = compute_artifacts(&test_uri(), &tree2, code2);
+
+        assert_ne!(
+            artifacts1.interface_hash, artifacts2.interface_hash,
+            "a changed parameter list should invalidate the interface hash even though the \
+             exported name and kind are unchanged"
+        );
+    }
+
+    #[test]
+    fn test_interface_hash_changes_on_source_target_reorder() {
+        let code1 = "source(\"a.R\")\nsource(\"b.R\")";
+        let code2 = "source(\"b.R\")\nsource(\"a.R\")";
+        let tree1 = parse_r(code1);
+        let tree2 = parse_r(code2);
+        let artifacts1 = compute_artifacts(&test_uri(), &tree1, code1);
+        let artifacts2 = compute_artifacts(&test_uri(), &tree2, code2);
+
+        assert_ne!(
+            artifacts1.interface_hash, artifacts2.interface_hash,
+            "reordering source() targets should invalidate the interface hash, since the new \
+             order can change which file's symbols win a naming collision downstream"
+        );
+    }
+
     #[test]
     fn test_assign_call_string_literal() {
         let code = r#"assign("my_var", 42)"#;
@@ -6975,6 +7071,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_intervals_round_trips_through_from_scopes() {
+        let scopes = &[(0, 0, 10, 0), (2, 0, 5, 0), (6, 0, 9, 0)];
+        let tree = FunctionScopeTree::from_scopes(scopes);
+
+        let mut intervals = tree.intervals();
+        intervals.sort();
+        let mut expected: Vec<_> = scopes.to_vec();
+        expected.sort();
+        assert_eq!(intervals, expected);
+
+        let rebuilt = FunctionScopeTree::from_scopes(&tree.intervals());
+        assert_eq!(rebuilt.len(), tree.len());
+    }
+
     /// Verifies that a FunctionScopeTree with a single interval reports containment correctly.
     ///
     /// This test constructs a tree containing one interval and asserts:
@@ -0,0 +1,629 @@
+//
+// cross_file/source_detect.rs
+//
+// AST-based detection of source()/sys.source() and library()/require()/
+// loadNamespace() calls, using tree-sitter.
+//
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use super::scope::FunctionScopeInterval;
+use super::types::{byte_offset_to_utf16_column, ForwardSource};
+
+/// Detected `library()`/`require()`/`loadNamespace()` call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryCall {
+    /// Package name (if statically determinable).
+    pub package: String,
+    /// 0-based line of the call.
+    pub line: u32,
+    /// 0-based UTF-16 column of the call's end position.
+    pub column: u32,
+    /// The enclosing function scope, if the call is nested inside one.
+    pub function_scope: Option<FunctionScopeInterval>,
+}
+
+/// Detect `source()`/`sys.source()` calls in R code by walking the parsed
+/// AST, so comments, string literals, and lookalike identifiers
+/// (`my_source(`) never produce a false hit.
+pub fn detect_source_calls(tree: &Tree, content: &str) -> Vec<ForwardSource> {
+    log::trace!("Starting tree-sitter parsing for source() call detection");
+    let mut sources = Vec::new();
+    visit_node(tree.root_node(), content, &mut sources);
+    log::trace!("Completed source() call detection, found {} calls", sources.len());
+    for source in &sources {
+        log::trace!(
+            "  Detected source() call: path='{}' at line {} column {} (is_sys_source={}, local={}, chdir={})",
+            source.path,
+            source.line,
+            source.column,
+            source.is_sys_source,
+            source.local,
+            source.chdir
+        );
+    }
+    sources
+}
+
+fn visit_node(node: Node, content: &str, sources: &mut Vec<ForwardSource>) {
+    if node.kind() == "call" {
+        if let Some(source) = try_parse_source_call(node, content) {
+            sources.push(source);
+        }
+    }
+
+    for child in node.children(&mut node.walk()) {
+        visit_node(child, content, sources);
+    }
+}
+
+fn try_parse_source_call(node: Node, content: &str) -> Option<ForwardSource> {
+    let func_node = node.child_by_field_name("function")?;
+    let func_text = node_text(func_node, content);
+
+    let is_sys_source = match func_text {
+        "source" => false,
+        "sys.source" => true,
+        _ => return None,
+    };
+
+    let args_node = node.child_by_field_name("arguments")?;
+    let path = find_file_argument(&args_node, content)?;
+    let local = find_bool_argument(&args_node, content, "local").unwrap_or(false);
+    let chdir = find_bool_argument(&args_node, content, "chdir").unwrap_or(false);
+
+    // For sys.source, check if envir is globalenv()/.GlobalEnv.
+    let sys_source_global_env = if is_sys_source {
+        find_envir_is_global(&args_node, content)
+    } else {
+        true // Not sys.source, so this field doesn't matter.
+    };
+
+    let start = node.start_position();
+    let line_text = content.lines().nth(start.row).unwrap_or("");
+    let column = byte_offset_to_utf16_column(line_text, start.column);
+
+    // AST-detected sources have no directive to carry a line=N override, so
+    // the call's own line doubles as its "directive line".
+    Some(ForwardSource {
+        path,
+        line: start.row as u32,
+        column,
+        is_directive: false,
+        local,
+        chdir,
+        is_sys_source,
+        sys_source_global_env,
+        explicit_line: false,
+        directive_line: start.row as u32,
+        user_line_zero: false,
+    })
+}
+
+/// Check if the envir argument is globalenv() or .GlobalEnv.
+fn find_envir_is_global(args_node: &Node, content: &str) -> bool {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                let name = node_text(name_node, content);
+                if name == "envir" {
+                    if let Some(value_node) = child.child_by_field_name("value") {
+                        let value = node_text(value_node, content).trim();
+                        return value == "globalenv()" || value == ".GlobalEnv";
+                    }
+                }
+            }
+        }
+    }
+    // If envir is not specified, sys.source defaults to baseenv(), which is
+    // not global - conservative: no symbol inheritance.
+    false
+}
+
+fn find_file_argument(args_node: &Node, content: &str) -> Option<String> {
+    let mut cursor = args_node.walk();
+    let children: Vec<_> = args_node.children(&mut cursor).collect();
+
+    // Look for a named "file" argument first.
+    for child in &children {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                if node_text(name_node, content) == "file" {
+                    let value_node = child.child_by_field_name("value")?;
+                    return extract_string_literal(value_node, content);
+                }
+            }
+        }
+    }
+
+    // Fall back to the first positional argument.
+    for child in &children {
+        if child.kind() == "argument" && child.child_by_field_name("name").is_none() {
+            let value_node = child.child_by_field_name("value")?;
+            return extract_string_literal(value_node, content);
+        }
+    }
+
+    None
+}
+
+fn find_bool_argument(args_node: &Node, content: &str, param_name: &str) -> Option<bool> {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                if node_text(name_node, content) == param_name {
+                    let value_node = child.child_by_field_name("value")?;
+                    return match node_text(value_node, content) {
+                        "TRUE" | "T" => Some(true),
+                        "FALSE" | "F" => Some(false),
+                        _ => None,
+                    };
+                }
+            }
+        }
+    }
+    None
+}
+
+fn extract_string_literal(node: Node, content: &str) -> Option<String> {
+    if node.kind() == "string" {
+        let text = node_text(node, content);
+        if (text.starts_with('"') && text.ends_with('"'))
+            || (text.starts_with('\'') && text.ends_with('\''))
+        {
+            return Some(text[1..text.len() - 1].to_string());
+        }
+    }
+    None
+}
+
+fn node_text<'a>(node: Node<'a>, content: &'a str) -> &'a str {
+    &content[node.byte_range()]
+}
+
+/// Detected `rm()`/`remove()` call that may remove symbols from the global
+/// environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RmCall {
+    /// 0-based line of the call.
+    pub line: u32,
+    /// 0-based UTF-16 column of the call's start position.
+    pub column: u32,
+    /// Symbol names removed, from bare arguments and/or `list = `.
+    pub symbols: Vec<String>,
+}
+
+/// Detect `rm()`/`remove()` calls that target the global environment (the
+/// default `envir`), by walking the parsed AST. Calls with a non-default
+/// `envir` (anything but `globalenv()`/`.GlobalEnv`) are skipped, since they
+/// don't affect symbols visible to this file's scope analysis.
+pub fn detect_rm_calls(tree: &Tree, content: &str) -> Vec<RmCall> {
+    log::trace!("Starting tree-sitter parsing for rm()/remove() call detection");
+    let mut calls = Vec::new();
+    visit_node_for_rm(tree.root_node(), content, &mut calls);
+    log::trace!("Completed rm()/remove() call detection, found {} calls", calls.len());
+    calls
+}
+
+fn visit_node_for_rm(node: Node, content: &str, calls: &mut Vec<RmCall>) {
+    if node.kind() == "call" {
+        if let Some(call) = try_parse_rm_call(node, content) {
+            calls.push(call);
+        }
+    }
+
+    for child in node.children(&mut node.walk()) {
+        visit_node_for_rm(child, content, calls);
+    }
+}
+
+fn try_parse_rm_call(node: Node, content: &str) -> Option<RmCall> {
+    let func_node = node.child_by_field_name("function")?;
+    let func_text = node_text(func_node, content);
+    if func_text != "rm" && func_text != "remove" {
+        return None;
+    }
+
+    let args_node = node.child_by_field_name("arguments")?;
+    if args_node.has_error() {
+        return None;
+    }
+
+    if has_non_default_envir_for_rm(&args_node, content) {
+        return None;
+    }
+
+    let mut symbols = extract_bare_symbols(&args_node, content);
+    symbols.extend(extract_list_symbols(&args_node, content));
+
+    let start = node.start_position();
+    let line_text = content.lines().nth(start.row).unwrap_or("");
+    let column = byte_offset_to_utf16_column(line_text, start.column);
+
+    Some(RmCall { line: start.row as u32, column, symbols })
+}
+
+/// Whether `envir` is given and isn't `globalenv()`/`.GlobalEnv`.
+fn has_non_default_envir_for_rm(args_node: &Node, content: &str) -> bool {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                if node_text(name_node, content) == "envir" {
+                    if let Some(value_node) = child.child_by_field_name("value") {
+                        let value = node_text(value_node, content).trim();
+                        return value != "globalenv()" && value != ".GlobalEnv";
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Bare identifier arguments, e.g. `rm(x, y)`.
+fn extract_bare_symbols(args_node: &Node, content: &str) -> Vec<String> {
+    let mut symbols = Vec::new();
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" && child.child_by_field_name("name").is_none() {
+            if let Some(value_node) = child.child_by_field_name("value") {
+                if value_node.kind() == "identifier" {
+                    symbols.push(node_text(value_node, content).to_string());
+                }
+            }
+        }
+    }
+    symbols
+}
+
+/// Symbols named via `list = "x"` or `list = c("a", "b")`.
+fn extract_list_symbols(args_node: &Node, content: &str) -> Vec<String> {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                if node_text(name_node, content) == "list" {
+                    if let Some(value_node) = child.child_by_field_name("value") {
+                        return extract_list_value_symbols(value_node, content);
+                    }
+                }
+            }
+        }
+    }
+    Vec::new()
+}
+
+fn extract_list_value_symbols(value_node: Node, content: &str) -> Vec<String> {
+    if value_node.kind() == "string" {
+        return extract_string_literal(value_node, content).into_iter().collect();
+    }
+    if is_c_call(value_node, content) {
+        if let Some(args_node) = value_node.child_by_field_name("arguments") {
+            return extract_c_string_args(&args_node, content);
+        }
+    }
+    Vec::new()
+}
+
+fn is_c_call(node: Node, content: &str) -> bool {
+    node.kind() == "call"
+        && node
+            .child_by_field_name("function")
+            .map(|f| node_text(f, content) == "c")
+            .unwrap_or(false)
+}
+
+fn extract_c_string_args(args_node: &Node, content: &str) -> Vec<String> {
+    let mut symbols = Vec::new();
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" && child.child_by_field_name("name").is_none() {
+            if let Some(value_node) = child.child_by_field_name("value") {
+                if let Some(symbol) = extract_string_literal(value_node, content) {
+                    symbols.push(symbol);
+                }
+            }
+        }
+    }
+    symbols
+}
+
+/// Detect `library()`/`require()`/`loadNamespace()` calls in R code.
+pub fn detect_library_calls(tree: &Tree, content: &str) -> Vec<LibraryCall> {
+    log::trace!("Starting tree-sitter parsing for library() call detection");
+    let mut calls = Vec::new();
+    visit_node_for_library(tree.root_node(), content, None, &mut calls);
+    log::trace!("Completed library() call detection, found {} calls", calls.len());
+    calls
+}
+
+fn visit_node_for_library(
+    node: Node,
+    content: &str,
+    enclosing_scope: Option<FunctionScopeInterval>,
+    calls: &mut Vec<LibraryCall>,
+) {
+    let scope_here = if node.kind() == "function_definition" {
+        let start = node.start_position();
+        let end = node.end_position();
+        Some(FunctionScopeInterval::new(
+            super::scope::Position { line: start.row as u32, column: start.column as u32 },
+            super::scope::Position { line: end.row as u32, column: end.column as u32 },
+        ))
+    } else {
+        enclosing_scope
+    };
+
+    if node.kind() == "call" {
+        if let Some(call) = try_parse_library_call(node, content, scope_here) {
+            calls.push(call);
+        }
+    }
+
+    for child in node.children(&mut node.walk()) {
+        visit_node_for_library(child, content, scope_here, calls);
+    }
+}
+
+fn try_parse_library_call(
+    node: Node,
+    content: &str,
+    function_scope: Option<FunctionScopeInterval>,
+) -> Option<LibraryCall> {
+    let func_node = node.child_by_field_name("function")?;
+    let func_text = node_text(func_node, content);
+
+    if func_text != "library" && func_text != "require" && func_text != "loadNamespace" {
+        return None;
+    }
+
+    let args_node = node.child_by_field_name("arguments")?;
+    // `character.only = TRUE` means the argument is a string/variable to be
+    // evaluated - we only extract statically-determinable package names, so
+    // in that case only a string-literal argument is accepted (a bare symbol
+    // there names a variable, not a package).
+    let package = if has_character_only_true(&args_node, content) {
+        extract_package_value(&args_node, content)?
+    } else {
+        extract_package_name(&args_node, content)?
+    };
+
+    let end = node.end_position();
+    let line_text = content.lines().nth(end.row).unwrap_or("");
+    let column = byte_offset_to_utf16_column(line_text, end.column);
+
+    Some(LibraryCall {
+        package,
+        line: end.row as u32,
+        column,
+        function_scope,
+    })
+}
+
+fn has_character_only_true(args_node: &Node, content: &str) -> bool {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                if node_text(name_node, content) == "character.only" {
+                    if let Some(value_node) = child.child_by_field_name("value") {
+                        return node_text(value_node, content) == "TRUE"
+                            || node_text(value_node, content) == "T";
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Extract the bare-symbol or string-literal package name from the first
+/// positional argument (the normal, non-`character.only` form).
+fn extract_package_name(args_node: &Node, content: &str) -> Option<String> {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" && child.child_by_field_name("name").is_none() {
+            let value_node = child.child_by_field_name("value")?;
+            return match value_node.kind() {
+                "identifier" => Some(node_text(value_node, content).to_string()),
+                "string" => extract_string_literal(value_node, content),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+/// Extract the package name from the first positional argument when
+/// `character.only = TRUE` is set, in which case it must already be a
+/// string literal to be statically determinable.
+fn extract_package_value(args_node: &Node, content: &str) -> Option<String> {
+    let mut cursor = args_node.walk();
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "argument" && child.child_by_field_name("name").is_none() {
+            let value_node = child.child_by_field_name("value")?;
+            return extract_string_literal(value_node, content);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Tree {
+        crate::parser_pool::with_parser(|parser| parser.parse(content, None)).unwrap()
+    }
+
+    #[test]
+    fn detects_basic_source_call() {
+        let content = "source(\"child.R\")\n";
+        let tree = parse(content);
+        let sources = detect_source_calls(&tree, content);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].path, "child.R");
+        assert_eq!(sources[0].line, 0);
+        assert!(!sources[0].is_sys_source);
+        assert!(!sources[0].explicit_line);
+        assert_eq!(sources[0].directive_line, 0);
+        assert!(!sources[0].user_line_zero);
+    }
+
+    #[test]
+    fn detects_sys_source_with_global_envir() {
+        let content = "sys.source(\"child.R\", envir = globalenv())\n";
+        let tree = parse(content);
+        let sources = detect_source_calls(&tree, content);
+        assert_eq!(sources.len(), 1);
+        assert!(sources[0].is_sys_source);
+        assert!(sources[0].sys_source_global_env);
+    }
+
+    #[test]
+    fn sys_source_without_envir_defaults_to_non_global() {
+        let content = "sys.source(\"child.R\")\n";
+        let tree = parse(content);
+        let sources = detect_source_calls(&tree, content);
+        assert_eq!(sources.len(), 1);
+        assert!(!sources[0].sys_source_global_env);
+    }
+
+    #[test]
+    fn detects_local_and_chdir_flags() {
+        let content = "source(\"child.R\", local = TRUE, chdir = TRUE)\n";
+        let tree = parse(content);
+        let sources = detect_source_calls(&tree, content);
+        assert_eq!(sources.len(), 1);
+        assert!(sources[0].local);
+        assert!(sources[0].chdir);
+    }
+
+    #[test]
+    fn ignores_non_source_calls() {
+        let content = "my_source(\"child.R\")\nx <- 1\n";
+        let tree = parse(content);
+        assert!(detect_source_calls(&tree, content).is_empty());
+    }
+
+    #[test]
+    fn detects_named_file_argument() {
+        let content = "source(file = \"child.R\")\n";
+        let tree = parse(content);
+        let sources = detect_source_calls(&tree, content);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].path, "child.R");
+    }
+
+    #[test]
+    fn column_accounts_for_utf16_surrogate_pairs() {
+        let content = "ðŸŽ‰source(\"child.R\")\n";
+        let tree = parse(content);
+        let sources = detect_source_calls(&tree, content);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].column, 2);
+    }
+
+    #[test]
+    fn detects_library_call() {
+        let content = "library(dplyr)\n";
+        let tree = parse(content);
+        let calls = detect_library_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].package, "dplyr");
+        assert!(calls[0].function_scope.is_none());
+    }
+
+    #[test]
+    fn detects_require_and_load_namespace() {
+        let content = "require(dplyr)\nloadNamespace(\"tidyr\")\n";
+        let tree = parse(content);
+        let calls = detect_library_calls(&tree, content);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].package, "dplyr");
+        assert_eq!(calls[1].package, "tidyr");
+    }
+
+    #[test]
+    fn detects_library_call_with_character_only() {
+        let content = "library(\"dplyr\", character.only = TRUE)\n";
+        let tree = parse(content);
+        let calls = detect_library_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].package, "dplyr");
+    }
+
+    #[test]
+    fn library_call_inside_function_has_scope() {
+        let content = "f <- function() {\n  library(dplyr)\n}\n";
+        let tree = parse(content);
+        let calls = detect_library_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].function_scope.is_some());
+    }
+
+    #[test]
+    fn library_call_outside_function_has_no_scope() {
+        let content = "library(dplyr)\nf <- function() { x <- 1 }\n";
+        let tree = parse(content);
+        let calls = detect_library_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].function_scope.is_none());
+    }
+
+    #[test]
+    fn detects_bare_symbol_rm_call() {
+        let content = "rm(x, y)\n";
+        let tree = parse(content);
+        let calls = detect_rm_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].symbols, vec!["x".to_string(), "y".to_string()]);
+    }
+
+    #[test]
+    fn detects_remove_alias() {
+        let content = "remove(x)\n";
+        let tree = parse(content);
+        let calls = detect_rm_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].symbols, vec!["x".to_string()]);
+    }
+
+    #[test]
+    fn detects_rm_with_list_string() {
+        let content = "rm(list = \"x\")\n";
+        let tree = parse(content);
+        let calls = detect_rm_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].symbols, vec!["x".to_string()]);
+    }
+
+    #[test]
+    fn detects_rm_with_list_c_call() {
+        let content = "rm(list = c(\"x\", \"y\"))\n";
+        let tree = parse(content);
+        let calls = detect_rm_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].symbols, vec!["x".to_string(), "y".to_string()]);
+    }
+
+    #[test]
+    fn skips_rm_with_non_default_envir() {
+        let content = "rm(x, envir = parent.frame())\n";
+        let tree = parse(content);
+        assert!(detect_rm_calls(&tree, content).is_empty());
+    }
+
+    #[test]
+    fn allows_rm_with_explicit_globalenv() {
+        let content = "rm(x, envir = globalenv())\n";
+        let tree = parse(content);
+        let calls = detect_rm_calls(&tree, content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].symbols, vec!["x".to_string()]);
+    }
+}
@@ -0,0 +1,232 @@
+//
+// cross_file/source_map.rs
+//
+// Cached line-start index for byte-offset-to-position conversion
+//
+// NOTE: `SourceMap` is a standalone, independently tested utility;
+// `resolve_match_pattern`, `infer_call_site_from_parent`, and
+// `resolve_parent_with_content` in `cross_file::parent_resolve` do not use
+// it. `fingerprint_content` is used by `cross_file::workspace_source_scan`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use tower_lsp::lsp_types::Url;
+
+/// Precomputed line-start byte offsets for a single content version, so
+/// resolving many byte offsets against the same file doesn't re-scan it from
+/// scratch each time (as `byte_offset_to_utf16_column` does when called
+/// directly on a raw string).
+///
+/// Keyed by URI + a content fingerprint rather than just the URI, so a
+/// `SourceMap` built for a stale version of a file is never silently reused
+/// after an edit - see [`SourceMap::fingerprint`].
+pub struct SourceMap {
+    uri: Url,
+    fingerprint: u64,
+    content: String,
+    /// Byte offset of the start of each line, in order.
+    line_starts: Vec<usize>,
+    /// `(line_number, line_start_byte)` of the most recently resolved
+    /// position, so a run of sequential lookups (the common case while
+    /// scanning a file top to bottom) only needs to walk forward from here
+    /// instead of binary-searching `line_starts` every time.
+    last_resolved: Mutex<Option<(usize, usize)>>,
+}
+
+impl std::fmt::Debug for SourceMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceMap")
+            .field("uri", &self.uri)
+            .field("fingerprint", &self.fingerprint)
+            .field("lines", &self.line_starts.len())
+            .finish()
+    }
+}
+
+/// Fingerprint `content` for use as a [`SourceMap`] cache key component.
+/// Plain content hashing (not mtime/size) so the map stays valid across
+/// moves/renames and only invalidates when the text itself changes.
+pub fn fingerprint_content(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl SourceMap {
+    /// Build a map over `content` in a single pass.
+    pub fn new(uri: Url, content: &str) -> Self {
+        let fingerprint = fingerprint_content(content);
+
+        let mut line_starts = Vec::with_capacity(content.len() / 32 + 1);
+        line_starts.push(0);
+        for (byte_offset, ch) in content.char_indices() {
+            if ch == '\n' {
+                line_starts.push(byte_offset + 1);
+            }
+        }
+
+        Self {
+            uri,
+            fingerprint,
+            content: content.to_string(),
+            line_starts,
+            last_resolved: Mutex::new(None),
+        }
+    }
+
+    pub fn uri(&self) -> &Url {
+        &self.uri
+    }
+
+    /// Content fingerprint this map was built from. A caller holding a
+    /// `SourceMap` should rebuild it (via [`Self::new`]) once the current
+    /// content's fingerprint no longer matches this value.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Number of lines indexed.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Convert a byte offset into `(line, utf16_column)`, binary-searching
+    /// the line-start table for the containing line, then walking only that
+    /// line's chars to accumulate the UTF-16 column. Clamps to the last line
+    /// if `offset` is past the end of the content.
+    pub fn byte_offset_to_position(&self, offset: usize) -> (u32, u32) {
+        let line = self.line_for_offset(offset);
+        let line_start = self.line_starts[line];
+
+        if let Ok(mut last) = self.last_resolved.lock() {
+            *last = Some((line, line_start));
+        }
+
+        let line_end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.content.len());
+        let line_text = &self.content[line_start..line_end.min(self.content.len())];
+
+        let within_line = offset.saturating_sub(line_start).min(line_text.len());
+        let mut utf16_col = 0u32;
+        let mut byte_pos = 0usize;
+        for ch in line_text.chars() {
+            if byte_pos >= within_line {
+                break;
+            }
+            byte_pos += ch.len_utf8();
+            utf16_col += ch.len_utf16() as u32;
+        }
+
+        (line as u32, utf16_col)
+    }
+
+    /// Find the line containing `offset`, using the cached most-recently
+    /// resolved line as a starting point for sequential lookups before
+    /// falling back to a binary search.
+    fn line_for_offset(&self, offset: usize) -> usize {
+        if let Ok(last) = self.last_resolved.lock() {
+            if let Some((line, line_start)) = *last {
+                if offset >= line_start {
+                    let next_start = self.line_starts.get(line + 1).copied();
+                    if next_start.map_or(true, |next| offset < next) {
+                        return line;
+                    }
+                }
+            }
+        }
+
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(insertion_point) => insertion_point.saturating_sub(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_uri() -> Url {
+        Url::parse("file:///test.R").unwrap()
+    }
+
+    #[test]
+    fn test_single_line() {
+        let map = SourceMap::new(test_uri(), "x <- 1");
+        assert_eq!(map.byte_offset_to_position(0), (0, 0));
+        assert_eq!(map.byte_offset_to_position(5), (0, 5));
+    }
+
+    #[test]
+    fn test_multi_line_offsets() {
+        let content = "x <- 1\nsource(\"child.R\")\ny <- 2";
+        let map = SourceMap::new(test_uri(), content);
+
+        // Start of line 1 ("source(...)")
+        let line1_start = content.find("source(").unwrap();
+        assert_eq!(map.byte_offset_to_position(line1_start), (1, 0));
+
+        // Start of line 2
+        let line2_start = content.find("y <- 2").unwrap();
+        assert_eq!(map.byte_offset_to_position(line2_start), (2, 0));
+    }
+
+    #[test]
+    fn test_utf16_column_accounts_for_surrogate_pairs() {
+        // ðŸŽ‰ is 4 bytes in UTF-8, 2 UTF-16 code units.
+        let content = "ðŸŽ‰source(\"child.R\")";
+        let map = SourceMap::new(test_uri(), content);
+        let byte_offset = content.find("source(").unwrap();
+        assert_eq!(map.byte_offset_to_position(byte_offset), (0, 2));
+    }
+
+    #[test]
+    fn test_sequential_lookups_use_cached_line() {
+        let content = "a\nb\nc\nd\ne";
+        let map = SourceMap::new(test_uri(), content);
+
+        // Walk forward line by line; each call should resolve correctly
+        // whether or not the sequential-lookup fast path is taken.
+        for (line_num, line) in content.lines().enumerate() {
+            let offset = content
+                .match_indices(line)
+                .map(|(i, _)| i)
+                .find(|&i| map.line_for_offset(i) == line_num)
+                .unwrap();
+            assert_eq!(map.byte_offset_to_position(offset).0, line_num as u32);
+        }
+    }
+
+    #[test]
+    fn test_offset_past_end_clamps_to_last_line() {
+        let content = "a\nb\nc";
+        let map = SourceMap::new(test_uri(), content);
+        let (line, _) = map.byte_offset_to_position(1000);
+        assert_eq!(line, 2);
+    }
+
+    #[test]
+    fn test_fingerprint_changes_with_content() {
+        let a = SourceMap::new(test_uri(), "x <- 1");
+        let b = SourceMap::new(test_uri(), "x <- 2");
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_stable_for_same_content() {
+        let a = SourceMap::new(test_uri(), "x <- 1");
+        let b = SourceMap::new(test_uri(), "x <- 1");
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_line_count() {
+        let map = SourceMap::new(test_uri(), "a\nb\nc");
+        assert_eq!(map.line_count(), 3);
+    }
+}
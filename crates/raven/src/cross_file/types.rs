@@ -0,0 +1,272 @@
+//
+// cross_file/types.rs
+//
+// Core data types for cross-file awareness: directive-parsed and
+// AST-detected metadata, plus the byte-offset/UTF-16 conversions shared by
+// every module that reports a position back to the editor.
+//
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tower_lsp::lsp_types::Url;
+
+/// Everything known about a file's cross-file relationships: who sources it,
+/// who it sources, its working directory, and the directive-controlled
+/// diagnostic/ignore/declaration state layered on top.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrossFileMetadata {
+    /// `@lsp-sourced-by`/`@lsp-run-by`/`@lsp-included-by` directives naming a
+    /// parent that sources this file.
+    pub sourced_by: Vec<BackwardDirective>,
+    /// Every `source()`/`sys.source()` call this file makes, whether
+    /// directive-declared or AST-detected.
+    pub sources: Vec<ForwardSource>,
+    /// `@lsp-working-directory` (and synonyms) directive value, if any.
+    pub working_directory: Option<String>,
+    /// Absolute working directory inherited from an ancestor up the
+    /// `sourced_by` chain, used when this file has no explicit
+    /// `working_directory` of its own. See
+    /// [`super::enrich_metadata_with_inherited_wd`].
+    pub inherited_working_directory: Option<String>,
+    /// Lines with a trailing/standalone `@lsp-ignore` directive.
+    pub ignored_lines: HashSet<u32>,
+    /// Lines targeted by a preceding `@lsp-ignore-next` directive.
+    pub ignored_next_lines: HashSet<u32>,
+    /// `@lsp-declare-function`/`@lsp-function`/etc. directives.
+    pub declared_functions: Vec<DeclaredSymbol>,
+    /// `@lsp-declare-variable`/`@lsp-variable`/etc. directives.
+    pub declared_variables: Vec<DeclaredSymbol>,
+    /// Diagnostic codes allowed on a specific line, keyed by that line, from
+    /// a line-scoped or trailing `@lsp-allow` directive.
+    pub allowed_codes_by_line: HashMap<u32, HashSet<String>>,
+    /// Diagnostic codes allowed file-wide, from a file-scoped `@lsp-allow`
+    /// directive.
+    pub allowed_codes_file: HashSet<String>,
+    /// `@lsp-not-sourced-by` negations vetoing a candidate parent during
+    /// resolution. See [`super::negative_directives`].
+    pub not_sourced_by: Vec<String>,
+    /// `library()`/`require()`/`loadNamespace()` calls detected in the AST.
+    pub library_calls: Vec<super::source_detect::LibraryCall>,
+}
+
+/// A backward `@lsp-sourced-by` (or synonym) directive: "this file is
+/// sourced by `path`, at `call_site`".
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackwardDirective {
+    pub path: String,
+    pub call_site: CallSiteSpec,
+    pub directive_line: u32,
+}
+
+/// A `source()`/`sys.source()` relationship to a child file, either
+/// directive-declared (`is_directive`) or AST-detected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ForwardSource {
+    pub path: String,
+    pub line: u32,
+    pub column: u32,
+    pub is_directive: bool,
+    pub local: bool,
+    pub chdir: bool,
+    pub is_sys_source: bool,
+    pub sys_source_global_env: bool,
+    /// Whether the directive carried an explicit `line=N` parameter, rather
+    /// than defaulting to the directive's own line.
+    pub explicit_line: bool,
+    /// The line the directive itself appears on (distinct from `line`, which
+    /// may point elsewhere when `line=N` is given).
+    pub directive_line: u32,
+    /// Whether `line=0` was given - invalid (lines are 1-based to the user),
+    /// flagged here rather than silently clamped so callers can diagnose it.
+    pub user_line_zero: bool,
+}
+
+impl ForwardSource {
+    /// Whether a file sourced this way inherits the sourcing file's symbols
+    /// (as opposed to being sourced into an isolated environment).
+    pub fn inherits_symbols(&self) -> bool {
+        if self.local {
+            return false;
+        }
+        if self.is_sys_source && !self.sys_source_global_env {
+            return false;
+        }
+        true
+    }
+}
+
+/// Uniquely identifies a forward-source relationship for dependency-graph
+/// bookkeeping: the resolved target plus the call-site details that
+/// distinguish multiple `source()` calls to the same file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ForwardSourceKey {
+    pub resolved_uri: Url,
+    pub call_site_line: u32,
+    pub call_site_column: u32,
+    pub local: bool,
+    pub chdir: bool,
+    pub is_sys_source: bool,
+}
+
+impl ForwardSource {
+    pub fn to_key(&self, resolved_uri: Url) -> ForwardSourceKey {
+        ForwardSourceKey {
+            resolved_uri,
+            call_site_line: self.line,
+            call_site_column: self.column,
+            local: self.local,
+            chdir: self.chdir,
+            is_sys_source: self.is_sys_source,
+        }
+    }
+}
+
+/// A declared symbol from `@lsp-declare-variable`/`@lsp-declare-function`
+/// (and their synonyms), naming something the static analysis can't see
+/// (e.g. assigned dynamically via `assign()`) so it isn't flagged as
+/// undefined.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeclaredSymbol {
+    pub name: String,
+    pub line: u32,
+    pub is_function: bool,
+}
+
+/// Where a backward directive's call site is, within the resolved parent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CallSiteSpec {
+    /// No call-site hint given; infer it (or fall back to the configured
+    /// default) during resolution.
+    Default,
+    /// Explicit `line=N` (0-based internally; converted from 1-based user
+    /// input at parse time).
+    Line(u32),
+    /// `match="pattern"`; resolved against the parent's content at
+    /// resolution time.
+    Match(String),
+}
+
+impl Default for CallSiteSpec {
+    fn default() -> Self {
+        CallSiteSpec::Default
+    }
+}
+
+/// Convert a byte offset within a single line into a UTF-16 column, by
+/// summing `len_utf16()` over the chars preceding it. LSP positions are
+/// UTF-16 by spec, while tree-sitter and Rust string indexing are
+/// byte-based, so every position reported to the client goes through this.
+pub fn byte_offset_to_utf16_column(line_text: &str, byte_offset_in_line: usize) -> u32 {
+    let prefix = &line_text[..byte_offset_in_line.min(line_text.len())];
+    prefix.encode_utf16().count() as u32
+}
+
+/// Convert a tree-sitter `Point` (byte-based column) into an LSP `Position`
+/// (UTF-16-based column), using `line_text` to translate the column.
+pub fn tree_sitter_point_to_lsp_position(
+    point: tree_sitter::Point,
+    line_text: &str,
+) -> tower_lsp::lsp_types::Position {
+    let column = byte_offset_to_utf16_column(line_text, point.column);
+    tower_lsp::lsp_types::Position {
+        line: point.row as u32,
+        character: column,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_offset_to_utf16_column_handles_ascii() {
+        assert_eq!(byte_offset_to_utf16_column("source(\"x\")", 7), 7);
+    }
+
+    #[test]
+    fn byte_offset_to_utf16_column_handles_surrogate_pairs() {
+        // ðŸŽ‰ is 4 bytes UTF-8, 2 UTF-16 code units.
+        let line = "ðŸŽ‰source(\"x\")";
+        let byte_offset = line.find("source(").unwrap();
+        assert_eq!(byte_offset_to_utf16_column(line, byte_offset), 2);
+    }
+
+    #[test]
+    fn byte_offset_to_utf16_column_handles_cjk() {
+        // Each CJK character here is 3 bytes UTF-8, 1 UTF-16 code unit.
+        let line = "ä½ å¥½source(\"x\")";
+        let byte_offset = line.find("source(").unwrap();
+        assert_eq!(byte_offset_to_utf16_column(line, byte_offset), 2);
+    }
+
+    #[test]
+    fn call_site_spec_defaults_to_default_variant() {
+        assert_eq!(CallSiteSpec::default(), CallSiteSpec::Default);
+    }
+
+    #[test]
+    fn forward_source_to_key_preserves_call_site_details() {
+        let source = ForwardSource {
+            path: "child.R".to_string(),
+            line: 3,
+            column: 2,
+            is_directive: false,
+            local: true,
+            chdir: false,
+            is_sys_source: false,
+            sys_source_global_env: true,
+            explicit_line: false,
+            directive_line: 3,
+            user_line_zero: false,
+        };
+        let uri = Url::parse("file:///child.R").unwrap();
+        let key = source.to_key(uri.clone());
+        assert_eq!(key.resolved_uri, uri);
+        assert_eq!(key.call_site_line, 3);
+        assert_eq!(key.call_site_column, 2);
+        assert!(key.local);
+    }
+
+    #[test]
+    fn forward_source_inherits_symbols_respects_local_and_sys_source() {
+        let base = ForwardSource {
+            path: "child.R".to_string(),
+            line: 0,
+            column: 0,
+            is_directive: false,
+            local: false,
+            chdir: false,
+            is_sys_source: false,
+            sys_source_global_env: true,
+            explicit_line: false,
+            directive_line: 0,
+            user_line_zero: false,
+        };
+        assert!(base.inherits_symbols());
+
+        let local = ForwardSource { local: true, ..base.clone() };
+        assert!(!local.inherits_symbols());
+
+        let isolated_sys_source = ForwardSource {
+            is_sys_source: true,
+            sys_source_global_env: false,
+            ..base
+        };
+        assert!(!isolated_sys_source.inherits_symbols());
+    }
+
+    #[test]
+    fn cross_file_metadata_round_trips_through_json() {
+        let meta = CrossFileMetadata {
+            sourced_by: vec![BackwardDirective {
+                path: "../main.R".to_string(),
+                call_site: CallSiteSpec::Line(4),
+                directive_line: 0,
+            }],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&meta).unwrap();
+        let restored: CrossFileMetadata = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.sourced_by, meta.sourced_by);
+    }
+}
@@ -0,0 +1,164 @@
+//
+// cross_file/vfs.rs
+//
+// Filesystem abstraction for cross-file awareness
+//
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Minimal metadata needed to build a `FileSnapshot`, abstracted away from
+/// `std::fs::Metadata` so implementations that don't back onto the real
+/// filesystem (e.g. `InMemoryVfs`) can supply it too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VfsMetadata {
+    /// Last modification time.
+    pub modified: SystemTime,
+    /// Size in bytes.
+    pub len: u64,
+}
+
+/// Abstraction over filesystem access for cross-file components.
+///
+/// Following the approach Mercurial's `hg-core` takes with its `Vfs` type and
+/// Deno's refactor that buries the filesystem inside its module cache, this
+/// lets [`CrossFileFileCache`](super::file_cache::CrossFileFileCache) and
+/// [`CrossFileContentProvider`](super::content_provider::CrossFileContentProvider)
+/// be exercised entirely in memory, and opens the door to overlay/virtual
+/// workspaces later.
+pub trait Vfs: Send + Sync {
+    /// Check whether `path` exists.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Read the full contents of `path` as a UTF-8 string.
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String>;
+
+    /// Get metadata for `path` (mtime + size) for change detection.
+    fn metadata(&self, path: &Path) -> std::io::Result<VfsMetadata>;
+}
+
+/// Disk-backed `Vfs` that dispatches directly to `std::fs`. The default for
+/// production use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealVfs;
+
+impl Vfs for RealVfs {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn metadata(&self, path: &Path) -> std::io::Result<VfsMetadata> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(VfsMetadata {
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Returns the shared default `Vfs` (disk-backed).
+pub fn real_vfs() -> Arc<dyn Vfs> {
+    Arc::new(RealVfs)
+}
+
+#[cfg(test)]
+pub use test_support::InMemoryVfs;
+
+#[cfg(test)]
+mod test_support {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::RwLock;
+
+    /// In-memory `Vfs` test double. Files are seeded with [`InMemoryVfs::with_file`]
+    /// or [`InMemoryVfs::insert`]; every other path reports as missing.
+    #[derive(Debug, Default)]
+    pub struct InMemoryVfs {
+        files: RwLock<HashMap<PathBuf, (String, VfsMetadata)>>,
+    }
+
+    impl InMemoryVfs {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Builder-style helper for seeding a single file at construction time.
+        pub fn with_file(self, path: impl Into<PathBuf>, content: impl Into<String>) -> Self {
+            self.insert(path, content);
+            self
+        }
+
+        /// Insert or overwrite a file's content, deriving metadata from the
+        /// content length and stamping `modified` with the current time.
+        pub fn insert(&self, path: impl Into<PathBuf>, content: impl Into<String>) {
+            let content = content.into();
+            let metadata = VfsMetadata {
+                modified: SystemTime::now(),
+                len: content.len() as u64,
+            };
+            self.files
+                .write()
+                .unwrap()
+                .insert(path.into(), (content, metadata));
+        }
+
+        /// Remove a previously inserted file, if any.
+        pub fn remove(&self, path: &Path) {
+            self.files.write().unwrap().remove(path);
+        }
+    }
+
+    impl Vfs for InMemoryVfs {
+        fn exists(&self, path: &Path) -> bool {
+            self.files.read().unwrap().contains_key(path)
+        }
+
+        fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+            self.files
+                .read()
+                .unwrap()
+                .get(path)
+                .map(|(content, _)| content.clone())
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<VfsMetadata> {
+            self.files
+                .read()
+                .unwrap()
+                .get(path)
+                .map(|(_, metadata)| *metadata)
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        }
+    }
+
+    #[test]
+    fn test_in_memory_vfs_exists_and_read() {
+        let vfs = InMemoryVfs::new().with_file("/a.R", "x <- 1");
+        assert!(vfs.exists(Path::new("/a.R")));
+        assert!(!vfs.exists(Path::new("/b.R")));
+        assert_eq!(vfs.read_to_string(Path::new("/a.R")).unwrap(), "x <- 1");
+        assert!(vfs.read_to_string(Path::new("/b.R")).is_err());
+    }
+
+    #[test]
+    fn test_in_memory_vfs_metadata_tracks_len() {
+        let vfs = InMemoryVfs::new().with_file("/a.R", "x <- 1");
+        let metadata = vfs.metadata(Path::new("/a.R")).unwrap();
+        assert_eq!(metadata.len, "x <- 1".len() as u64);
+        assert!(vfs.metadata(Path::new("/missing.R")).is_err());
+    }
+
+    #[test]
+    fn test_in_memory_vfs_remove() {
+        let vfs = InMemoryVfs::new().with_file("/a.R", "x <- 1");
+        vfs.remove(Path::new("/a.R"));
+        assert!(!vfs.exists(Path::new("/a.R")));
+    }
+}
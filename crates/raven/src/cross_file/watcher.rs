@@ -0,0 +1,295 @@
+//
+// cross_file/watcher.rs
+//
+// Disk-change invalidation for the cross-file file cache and workspace index
+//
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tower_lsp::lsp_types::Url;
+
+use super::file_cache::{CrossFileFileCache, FileSnapshot};
+use super::workspace_index::CrossFileWorkspaceIndex;
+
+/// A disk change the watcher confirmed was real (snapshot mismatch, or the
+/// file is gone), after evicting the matching cache/index entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchedFileChange {
+    /// File was created or modified; content should be re-read on next access.
+    Modified(Url),
+    /// File no longer exists on disk.
+    Removed(Url),
+}
+
+/// Rapid bursts of events for the same path within this window are coalesced
+/// into a single change, so a single editor save doesn't trigger repeated
+/// re-indexing.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches workspace roots for on-disk changes and keeps [`CrossFileFileCache`]
+/// and [`CrossFileWorkspaceIndex`] entries from going stale for files that
+/// aren't open in the editor, modeled on tantivy's `FileWatcher`.
+///
+/// `CrossFileFileCache` is documented as "cached-only; no synchronous disk
+/// I/O" - without something watching the filesystem, a closed file edited
+/// outside the editor (another process, a script, `git checkout`) would keep
+/// serving stale content until something else happened to re-seed it.
+pub struct CrossFileWatcher {
+    /// Kept alive only so the underlying OS watch isn't torn down; never read.
+    #[allow(dead_code)]
+    watcher: RecommendedWatcher,
+    cancellation_token: CancellationToken,
+    worker_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl CrossFileWatcher {
+    /// Starts watching `roots` recursively. Returns the handle (keep it
+    /// alive; dropping it or calling [`Self::shutdown`] stops the watcher)
+    /// along with the receiving end of a channel that gets a
+    /// [`WatchedFileChange`] for every confirmed change.
+    pub fn start(
+        roots: Vec<PathBuf>,
+        cache: Arc<CrossFileFileCache>,
+        index: Arc<CrossFileWorkspaceIndex>,
+    ) -> notify::Result<(Self, mpsc::UnboundedReceiver<WatchedFileChange>)> {
+        Self::start_with_debounce(roots, cache, index, DEFAULT_DEBOUNCE)
+    }
+
+    /// Like [`Self::start`], but with an explicit debounce window (mainly for
+    /// tests, where the default would make assertions slow).
+    pub fn start_with_debounce(
+        roots: Vec<PathBuf>,
+        cache: Arc<CrossFileFileCache>,
+        index: Arc<CrossFileWorkspaceIndex>,
+        debounce: Duration,
+    ) -> notify::Result<(Self, mpsc::UnboundedReceiver<WatchedFileChange>)> {
+        let (raw_tx, mut raw_rx) = mpsc::unbounded_channel::<PathBuf>();
+
+        let mut watcher = recommended_watcher(move |res: notify::Result<Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("Cross-file watcher error: {}", err);
+                    return;
+                }
+            };
+            if !matches!(
+                event.kind,
+                EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+            ) {
+                return;
+            }
+            for path in event.paths {
+                let _ = raw_tx.send(path);
+            }
+        })?;
+
+        for root in &roots {
+            watcher.watch(root, RecursiveMode::Recursive)?;
+        }
+
+        let (change_tx, change_rx) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let token = cancellation_token.clone();
+
+        let worker_handle = tokio::spawn(async move {
+            log::info!("Cross-file watcher worker started for {} root(s)", roots.len());
+
+            loop {
+                let first_path = tokio::select! {
+                    _ = token.cancelled() => break,
+                    maybe_path = raw_rx.recv() => match maybe_path {
+                        Some(path) => path,
+                        None => break,
+                    },
+                };
+
+                // Coalesce whatever else arrives within the debounce window
+                // before processing this batch, so a single save (which can
+                // fire several modify events) becomes one change per path.
+                let mut pending: HashSet<PathBuf> = HashSet::new();
+                pending.insert(first_path);
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = tokio::time::sleep(debounce) => {}
+                }
+                while let Ok(path) = raw_rx.try_recv() {
+                    pending.insert(path);
+                }
+
+                for path in pending {
+                    Self::process_change(&cache, &index, &change_tx, &path).await;
+                }
+            }
+
+            log::info!("Cross-file watcher worker stopped");
+        });
+
+        Ok((
+            Self {
+                watcher,
+                cancellation_token,
+                worker_handle: Mutex::new(Some(worker_handle)),
+            },
+            change_rx,
+        ))
+    }
+
+    /// Compares `path`'s current on-disk snapshot to the cached one and, on
+    /// mismatch (or removal), evicts the cache/index entries and sends a
+    /// [`WatchedFileChange`].
+    async fn process_change(
+        cache: &CrossFileFileCache,
+        index: &CrossFileWorkspaceIndex,
+        change_tx: &mpsc::UnboundedSender<WatchedFileChange>,
+        path: &Path,
+    ) {
+        let uri = match Url::from_file_path(path) {
+            Ok(uri) => uri,
+            Err(_) => {
+                log::trace!("Ignoring watcher event for non-file-path: {}", path.display());
+                return;
+            }
+        };
+
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => {
+                let mut current = FileSnapshot::from_metadata(&metadata);
+                if current.is_recent() {
+                    // mtime+size alone can't be trusted for a file this
+                    // fresh - a same-size edit could have landed within the
+                    // same tick as the cached read. Read once to attach a
+                    // content_hash so `matches_disk` can fall back to it.
+                    if let Ok(content) = tokio::fs::read_to_string(path).await {
+                        current = FileSnapshot::with_content_hash(&metadata, &content);
+                    }
+                }
+                let changed = match cache.cached_snapshot(&uri) {
+                    Some(cached) => !cached.matches_disk(&current),
+                    // Nothing cached yet, so there's no stale entry to evict,
+                    // but this is still a real disk change worth surfacing.
+                    None => true,
+                };
+                if changed {
+                    cache.invalidate(&uri);
+                    index.invalidate(&uri);
+                    log::trace!("Invalidated caches for watched change: {}", uri);
+                    let _ = change_tx.send(WatchedFileChange::Modified(uri));
+                }
+            }
+            Err(_) => {
+                cache.invalidate(&uri);
+                index.invalidate(&uri);
+                log::trace!("Invalidated caches for watched removal: {}", uri);
+                let _ = change_tx.send(WatchedFileChange::Removed(uri));
+            }
+        }
+    }
+
+    /// Stops the watcher's debounce worker. The OS-level watch is released
+    /// when `self` is dropped.
+    pub fn shutdown(&self) {
+        self.cancellation_token.cancel();
+        if let Some(handle) = self.worker_handle.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for CrossFileWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration as StdDuration;
+    use tempfile::tempdir;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_watcher_detects_modified_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("watched.R");
+        std::fs::write(&file_path, "x <- 1").unwrap();
+
+        let uri = Url::from_file_path(&file_path).unwrap();
+        let cache = Arc::new(CrossFileFileCache::new());
+        let index = Arc::new(CrossFileWorkspaceIndex::new());
+
+        // Seed the cache so we can observe eviction.
+        let metadata = std::fs::metadata(&file_path).unwrap();
+        cache.insert(
+            uri.clone(),
+            FileSnapshot::from_metadata(&metadata),
+            "x <- 1".to_string(),
+        );
+        assert!(cache.get(&uri).is_some());
+
+        let (_watcher, mut changes) = CrossFileWatcher::start_with_debounce(
+            vec![dir.path().to_path_buf()],
+            cache.clone(),
+            index.clone(),
+            StdDuration::from_millis(50),
+        )
+        .unwrap();
+
+        // Give the watcher a moment to register before mutating the file.
+        tokio::time::sleep(StdDuration::from_millis(100)).await;
+        std::fs::write(&file_path, "x <- 2\ny <- 3").unwrap();
+
+        let change = timeout(StdDuration::from_secs(5), changes.recv())
+            .await
+            .expect("timed out waiting for watcher notification")
+            .expect("watcher channel closed unexpectedly");
+
+        assert_eq!(change, WatchedFileChange::Modified(uri.clone()));
+        assert!(cache.get(&uri).is_none(), "stale cache entry should be evicted");
+    }
+
+    #[tokio::test]
+    async fn test_watcher_detects_removed_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("removed.R");
+        std::fs::write(&file_path, "x <- 1").unwrap();
+
+        let uri = Url::from_file_path(&file_path).unwrap();
+        let cache = Arc::new(CrossFileFileCache::new());
+        let index = Arc::new(CrossFileWorkspaceIndex::new());
+
+        let metadata = std::fs::metadata(&file_path).unwrap();
+        cache.insert(
+            uri.clone(),
+            FileSnapshot::from_metadata(&metadata),
+            "x <- 1".to_string(),
+        );
+
+        let (_watcher, mut changes) = CrossFileWatcher::start_with_debounce(
+            vec![dir.path().to_path_buf()],
+            cache.clone(),
+            index.clone(),
+            StdDuration::from_millis(50),
+        )
+        .unwrap();
+
+        tokio::time::sleep(StdDuration::from_millis(100)).await;
+        std::fs::remove_file(&file_path).unwrap();
+
+        let change = timeout(StdDuration::from_secs(5), changes.recv())
+            .await
+            .expect("timed out waiting for watcher notification")
+            .expect("watcher channel closed unexpectedly");
+
+        assert_eq!(change, WatchedFileChange::Removed(uri.clone()));
+        assert!(cache.get(&uri).is_none());
+    }
+}
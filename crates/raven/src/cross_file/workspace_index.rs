@@ -6,14 +6,14 @@
 
 use std::collections::HashSet;
 use std::num::NonZeroUsize;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::RwLock;
 
 use lru::LruCache;
 use tower_lsp::lsp_types::Url;
 
 use super::file_cache::FileSnapshot;
-use super::scope::ScopeArtifacts;
+use super::scope::{ScopeArtifacts, ScopeEvent};
 use super::types::CrossFileMetadata;
 
 /// Entry in the workspace index
@@ -27,6 +27,73 @@ pub struct IndexEntry {
     pub artifacts: ScopeArtifacts,
     /// Index version when this entry was created
     pub indexed_at_version: u64,
+    /// File content, when the entry was populated from a source that had it
+    /// on hand (e.g. [`super::disk_cache::DiskCache`] hydration). `None` for
+    /// entries built before content-bearing sources existed; those callers
+    /// are unaffected and still fall through to [`super::file_cache::CrossFileFileCache`].
+    pub content: Option<String>,
+}
+
+impl IndexEntry {
+    /// Rough heap-footprint estimate for this entry, used by
+    /// [`CrossFileWorkspaceIndex::with_memory_budget`] to bound the index by
+    /// approximate memory rather than purely by entry count. Deliberately
+    /// approximate rather than exact: `metadata` is sized via its JSON
+    /// encoding (the same representation [`super::disk_cache::DiskCache`]
+    /// already persists it as), and `artifacts`/`content`/`snapshot`
+    /// contribute their own string and collection contents plus a small
+    /// per-entry struct overhead.
+    pub fn estimated_size(&self) -> usize {
+        let snapshot_size = std::mem::size_of::<FileSnapshot>();
+        let content_size = self.content.as_ref().map(|c| c.len()).unwrap_or(0);
+        let metadata_size = serde_json::to_vec(&self.metadata)
+            .map(|bytes| bytes.len())
+            .unwrap_or_else(|_| std::mem::size_of::<CrossFileMetadata>());
+        let artifacts_size = estimate_scope_artifacts_size(&self.artifacts);
+
+        snapshot_size + content_size + metadata_size + artifacts_size
+    }
+}
+
+/// Estimates `artifacts`' heap footprint by summing the names/signatures in
+/// its exported interface, the contents of its event timeline, and a fixed
+/// per-interval cost for its (otherwise not directly inspectable) scope tree.
+fn estimate_scope_artifacts_size(artifacts: &ScopeArtifacts) -> usize {
+    let mut size = std::mem::size_of::<u64>(); // interface_hash
+
+    for (name, symbol) in &artifacts.exported_interface {
+        size += name.len();
+        size += std::mem::size_of_val(symbol);
+        size += symbol.signature.as_ref().map(|s| s.len()).unwrap_or(0);
+    }
+
+    for event in &artifacts.timeline {
+        size += estimate_scope_event_size(event);
+    }
+
+    // Interval tree nodes aren't directly inspectable from here; approximate
+    // each stored interval at a fixed per-node cost.
+    size += artifacts.function_scope_tree.len() * std::mem::size_of::<(u32, u32, u32, u32)>();
+
+    size
+}
+
+fn estimate_scope_event_size(event: &ScopeEvent) -> usize {
+    match event {
+        ScopeEvent::Def { symbol, .. } | ScopeEvent::Declaration { symbol, .. } => {
+            std::mem::size_of_val(symbol)
+                + symbol.signature.as_ref().map(|s| s.len()).unwrap_or(0)
+        }
+        ScopeEvent::Source { source, .. } => std::mem::size_of_val(source),
+        ScopeEvent::FunctionScope { parameters, .. } => parameters
+            .iter()
+            .map(|p| {
+                std::mem::size_of_val(p) + p.signature.as_ref().map(|s| s.len()).unwrap_or(0)
+            })
+            .sum(),
+        ScopeEvent::Removal { symbols, .. } => symbols.iter().map(|s| s.len()).sum(),
+        ScopeEvent::PackageLoad { package, .. } => package.len(),
+    }
 }
 
 /// Default capacity for the cross-file workspace index
@@ -41,6 +108,14 @@ pub struct CrossFileWorkspaceIndex {
     inner: RwLock<LruCache<Url, IndexEntry>>,
     /// Monotonic version counter
     version: AtomicU64,
+    /// When set, `push`/`update_from_disk`/`insert` evict additional LRU
+    /// entries (beyond whatever the count-based capacity above already
+    /// evicted) until `memory_bytes` is back at or under this many bytes.
+    /// `None` means the original, count-only eviction behavior.
+    memory_budget: Option<usize>,
+    /// Running total of `IndexEntry::estimated_size()` across all resident
+    /// entries, maintained incrementally so `current_memory_bytes()` is O(1).
+    memory_bytes: AtomicUsize,
 }
 
 impl std::fmt::Debug for CrossFileWorkspaceIndex {
@@ -67,6 +142,55 @@ impl CrossFileWorkspaceIndex {
         Self {
             inner: RwLock::new(LruCache::new(cap)),
             version: AtomicU64::new(0),
+            memory_budget: None,
+            memory_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Build an index bounded primarily by estimated heap footprint
+    /// (`IndexEntry::estimated_size()`) rather than entry count, for
+    /// workspaces where a few huge files would otherwise either waste
+    /// memory (count cap too generous) or evict useless small files too
+    /// eagerly (count cap too tight). A generous count-based cap
+    /// (`DEFAULT_WORKSPACE_INDEX_CAPACITY`) is kept as a backstop in case
+    /// `estimated_size()` badly underestimates a pathological entry.
+    pub fn with_memory_budget(bytes: usize) -> Self {
+        let cap = NonZeroUsize::new(DEFAULT_WORKSPACE_INDEX_CAPACITY).unwrap();
+        Self {
+            inner: RwLock::new(LruCache::new(cap)),
+            version: AtomicU64::new(0),
+            memory_budget: Some(bytes),
+            memory_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Current running total of `IndexEntry::estimated_size()` across all
+    /// resident entries. Useful for diagnostics regardless of eviction mode.
+    pub fn current_memory_bytes(&self) -> usize {
+        self.memory_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Pushes `entry` under `uri`, keeping `memory_bytes` and (when
+    /// `memory_budget` is set) the byte-budget eviction loop in sync with
+    /// the LRU cache's own count-based eviction.
+    fn record_push(&self, guard: &mut LruCache<Url, IndexEntry>, uri: Url, entry: IndexEntry) {
+        let added = entry.estimated_size();
+        if let Some((_, evicted)) = guard.push(uri, entry) {
+            self.memory_bytes
+                .fetch_sub(evicted.estimated_size(), Ordering::SeqCst);
+        }
+        self.memory_bytes.fetch_add(added, Ordering::SeqCst);
+
+        if let Some(budget) = self.memory_budget {
+            while self.memory_bytes.load(Ordering::SeqCst) > budget {
+                match guard.pop_lru() {
+                    Some((_, popped)) => {
+                        self.memory_bytes
+                            .fetch_sub(popped.estimated_size(), Ordering::SeqCst);
+                    }
+                    None => break,
+                }
+            }
         }
     }
 
@@ -106,6 +230,13 @@ impl CrossFileWorkspaceIndex {
             .map(|e| e.artifacts.clone())
     }
 
+    /// Get content for a URI, if this entry was populated with it (see
+    /// [`IndexEntry::content`]). Callers should fall through to
+    /// [`super::file_cache::CrossFileFileCache`] on `None`.
+    pub fn get_content(&self, uri: &Url) -> Option<String> {
+        self.inner.read().ok()?.peek(uri).and_then(|e| e.content.clone())
+    }
+
     /// Update index entry for a URI.
     ///
     /// CRITICAL: If the URI is currently open, this is a no-op.
@@ -117,6 +248,21 @@ impl CrossFileWorkspaceIndex {
         snapshot: FileSnapshot,
         metadata: CrossFileMetadata,
         artifacts: ScopeArtifacts,
+    ) {
+        self.update_from_disk_with_content(uri, open_documents, snapshot, metadata, artifacts, None);
+    }
+
+    /// Like [`Self::update_from_disk`], but also records the file's content
+    /// (e.g. when hydrating from [`super::disk_cache::DiskCache`]), so
+    /// [`Self::get_content`] can serve it without a file-cache round trip.
+    pub fn update_from_disk_with_content(
+        &self,
+        uri: &Url,
+        open_documents: &HashSet<Url>,
+        snapshot: FileSnapshot,
+        metadata: CrossFileMetadata,
+        artifacts: ScopeArtifacts,
+        content: Option<String>,
     ) {
         if open_documents.contains(uri) {
             log::trace!("Skipping disk update for open document: {}", uri);
@@ -129,10 +275,11 @@ impl CrossFileWorkspaceIndex {
             metadata,
             artifacts,
             indexed_at_version: version,
+            content,
         };
 
         if let Ok(mut guard) = self.inner.write() {
-            guard.push(uri.clone(), entry);
+            self.record_push(&mut guard, uri.clone(), entry);
         }
     }
 
@@ -140,7 +287,7 @@ impl CrossFileWorkspaceIndex {
     pub fn insert(&self, uri: Url, entry: IndexEntry) {
         self.increment_version();
         if let Ok(mut guard) = self.inner.write() {
-            guard.push(uri, entry);
+            self.record_push(&mut guard, uri, entry);
         }
     }
 
@@ -148,7 +295,10 @@ impl CrossFileWorkspaceIndex {
     pub fn invalidate(&self, uri: &Url) {
         self.increment_version();
         if let Ok(mut guard) = self.inner.write() {
-            guard.pop(uri);
+            if let Some(removed) = guard.pop(uri) {
+                self.memory_bytes
+                    .fetch_sub(removed.estimated_size(), Ordering::SeqCst);
+            }
         }
     }
 
@@ -158,6 +308,7 @@ impl CrossFileWorkspaceIndex {
         if let Ok(mut guard) = self.inner.write() {
             guard.clear();
         }
+        self.memory_bytes.store(0, Ordering::SeqCst);
     }
 
     /// Check if URI is in index
@@ -184,6 +335,11 @@ impl CrossFileWorkspaceIndex {
             .unwrap_or(NonZeroUsize::new(DEFAULT_WORKSPACE_INDEX_CAPACITY).unwrap());
         if let Ok(mut guard) = self.inner.write() {
             guard.resize(cap);
+            // `LruCache::resize` doesn't report what it evicted, so
+            // recompute the running total from what's left rather than risk
+            // it drifting out of sync.
+            let total: usize = guard.iter().map(|(_, e)| e.estimated_size()).sum();
+            self.memory_bytes.store(total, Ordering::SeqCst);
         }
     }
 }
@@ -211,6 +367,7 @@ mod tests {
             metadata: CrossFileMetadata::default(),
             artifacts: ScopeArtifacts::default(),
             indexed_at_version: version,
+            content: None,
         }
     }
 
@@ -292,6 +449,41 @@ mod tests {
         assert!(index.contains(&uri));
     }
 
+    #[test]
+    fn test_update_from_disk_with_content_is_retrievable() {
+        let index = CrossFileWorkspaceIndex::new();
+        let uri = test_uri("test.R");
+        let open_docs = HashSet::new();
+
+        index.update_from_disk_with_content(
+            &uri,
+            &open_docs,
+            test_snapshot(),
+            CrossFileMetadata::default(),
+            ScopeArtifacts::default(),
+            Some("x <- 1".to_string()),
+        );
+
+        assert_eq!(index.get_content(&uri), Some("x <- 1".to_string()));
+    }
+
+    #[test]
+    fn test_update_from_disk_without_content_has_no_content() {
+        let index = CrossFileWorkspaceIndex::new();
+        let uri = test_uri("test.R");
+        let open_docs = HashSet::new();
+
+        index.update_from_disk(
+            &uri,
+            &open_docs,
+            test_snapshot(),
+            CrossFileMetadata::default(),
+            ScopeArtifacts::default(),
+        );
+
+        assert_eq!(index.get_content(&uri), None);
+    }
+
     #[test]
     fn test_invalidate() {
         let index = CrossFileWorkspaceIndex::new();
@@ -354,4 +546,112 @@ mod tests {
         assert!(index.contains(&test_uri("3.R")));
         assert!(index.contains(&test_uri("4.R")));
     }
+
+    fn test_entry_with_content(version: u64, content: &str) -> IndexEntry {
+        IndexEntry {
+            snapshot: test_snapshot(),
+            metadata: CrossFileMetadata::default(),
+            artifacts: ScopeArtifacts::default(),
+            indexed_at_version: version,
+            content: Some(content.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_current_memory_bytes_starts_at_zero() {
+        let index = CrossFileWorkspaceIndex::new();
+        assert_eq!(index.current_memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_current_memory_bytes_tracks_inserted_entries() {
+        let index = CrossFileWorkspaceIndex::new();
+        let uri = test_uri("test.R");
+
+        assert_eq!(index.current_memory_bytes(), 0);
+        let entry = test_entry_with_content(1, "x <- 1");
+        let expected = entry.estimated_size();
+        index.insert(uri, entry);
+
+        assert_eq!(index.current_memory_bytes(), expected);
+    }
+
+    #[test]
+    fn test_current_memory_bytes_decreases_on_invalidate() {
+        let index = CrossFileWorkspaceIndex::new();
+        let uri = test_uri("test.R");
+
+        index.insert(uri.clone(), test_entry_with_content(1, "x <- 1"));
+        assert!(index.current_memory_bytes() > 0);
+
+        index.invalidate(&uri);
+        assert_eq!(index.current_memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_current_memory_bytes_resets_on_invalidate_all() {
+        let index = CrossFileWorkspaceIndex::new();
+        index.insert(test_uri("a.R"), test_entry_with_content(1, "a"));
+        index.insert(test_uri("b.R"), test_entry_with_content(2, "b"));
+        assert!(index.current_memory_bytes() > 0);
+
+        index.invalidate_all();
+        assert_eq!(index.current_memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_estimated_size_grows_with_content_length() {
+        let small = test_entry_with_content(1, "x");
+        let large = test_entry_with_content(1, &"x".repeat(10_000));
+
+        assert!(large.estimated_size() > small.estimated_size() + 9_000);
+    }
+
+    #[test]
+    fn test_memory_budget_evicts_lru_entries_once_over_budget() {
+        // Budget sized for roughly one entry's worth of content.
+        let one_entry_size = test_entry_with_content(1, &"x".repeat(1_000)).estimated_size();
+        let index = CrossFileWorkspaceIndex::with_memory_budget(one_entry_size + 1);
+
+        index.insert(test_uri("a.R"), test_entry_with_content(1, &"x".repeat(1_000)));
+        assert!(index.contains(&test_uri("a.R")));
+
+        // Inserting a second similarly-sized entry should push the total
+        // over budget and evict the LRU (first) entry.
+        index.insert(test_uri("b.R"), test_entry_with_content(2, &"x".repeat(1_000)));
+        assert!(!index.contains(&test_uri("a.R")), "oldest entry should be evicted over budget");
+        assert!(index.contains(&test_uri("b.R")));
+        assert!(index.current_memory_bytes() <= one_entry_size + 1);
+    }
+
+    #[test]
+    fn test_memory_budget_keeps_small_entries_under_count_cap() {
+        // A generous byte budget with tiny entries should not evict purely
+        // on count, unlike the default count-based mode.
+        let index = CrossFileWorkspaceIndex::with_memory_budget(1_000_000);
+        for i in 0..20 {
+            index.insert(test_uri(&format!("{i}.R")), test_entry_with_content(i, "x"));
+        }
+
+        for i in 0..20 {
+            assert!(
+                index.contains(&test_uri(&format!("{i}.R"))),
+                "entry {i} should remain resident under a generous byte budget"
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_based_mode_leaves_memory_budget_unset() {
+        // The default/count-based constructors should behave exactly as
+        // before: no byte-budget eviction kicks in even with many entries.
+        let index = CrossFileWorkspaceIndex::with_capacity(1000);
+        for i in 0..50 {
+            index.insert(test_uri(&format!("{i}.R")), test_entry_with_content(i, &"x".repeat(1_000)));
+        }
+
+        for i in 0..50 {
+            assert!(index.contains(&test_uri(&format!("{i}.R"))));
+        }
+    }
 }
@@ -0,0 +1,291 @@
+//
+// cross_file/workspace_source_scan.rs
+//
+// Workspace-wide source() discovery, to build the dependency graph without
+// requiring explicit @sourced-by directives
+//
+// NOTE: this module does not add edges to `cross_file::dependency`'s
+// `DependencyGraph`; nothing outside this file calls it. What it provides is
+// a regex-based scan for `source()`/`sys.source()`/
+// `source_url()` calls (reusing the directive-parsing style already
+// established in `cross_file::directive`, as a literal-call-text fallback -
+// `cross_file::ast_source_call` covers the AST-aware half for a single known
+// child), an incremental per-file fingerprint cache so re-scans skip
+// unchanged files, and cancellation via `tokio_util::sync::CancellationToken`
+// (the same mechanism `cross_file::background_indexer` already uses) so a
+// scan in progress over a large workspace can be aborted.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use tokio_util::sync::CancellationToken;
+use tower_lsp::lsp_types::Url;
+
+use super::source_map::fingerprint_content;
+
+/// A `source()`/`sys.source()`/`source_url()` call found by scanning a
+/// file's raw text, independent of any directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredSourceCall {
+    /// The string literal argument as written (not yet resolved to a URI).
+    pub path: String,
+    pub line: u32,
+    pub column: u32,
+    pub is_sys_source: bool,
+    pub is_source_url: bool,
+}
+
+fn pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"\b(source_url|sys\.source|source)\s*\(\s*(?:"([^"]+)"|'([^']+)')"#).unwrap()
+    })
+}
+
+/// Scan `content` for every `source()`/`sys.source()`/`source_url()` call
+/// whose first argument is a string literal, line by line. This is a literal
+/// text scan (matching `cross_file::directive`'s own regex-based style), not
+/// an AST walk, so - like the directive regexes it mirrors - it can be
+/// fooled by a call embedded in a comment or a string; it exists to give the
+/// dependency graph *candidate* edges across an entire workspace cheaply,
+/// with `cross_file::ast_source_call`'s AST-aware matching available to
+/// confirm/refine any single edge once a parsed tree is at hand.
+pub fn scan_content_for_source_calls(content: &str) -> Vec<DiscoveredSourceCall> {
+    let pattern = pattern();
+    let mut calls = Vec::new();
+
+    for (line_num, line) in content.lines().enumerate() {
+        for caps in pattern.captures_iter(line) {
+            let Some(literal) = caps.get(2).or_else(|| caps.get(3)) else {
+                continue;
+            };
+            let callee = caps.get(1).map(|m| m.as_str()).unwrap_or_default();
+            let call_match = caps.get(0).unwrap();
+            calls.push(DiscoveredSourceCall {
+                path: literal.as_str().to_string(),
+                line: line_num as u32,
+                column: byte_offset_to_utf16_column(line, call_match.start()),
+                is_sys_source: callee == "sys.source",
+                is_source_url: callee == "source_url",
+            });
+        }
+    }
+
+    calls
+}
+
+fn byte_offset_to_utf16_column(line_text: &str, byte_offset_in_line: usize) -> u32 {
+    line_text[..byte_offset_in_line.min(line_text.len())]
+        .chars()
+        .map(|ch| ch.len_utf16() as u32)
+        .sum()
+}
+
+/// Tracks each scanned file's content fingerprint so a repeat workspace scan
+/// only re-parses files that actually changed since the last pass.
+#[derive(Default)]
+pub struct WorkspaceSourceScanner {
+    fingerprints: Mutex<HashMap<Url, u64>>,
+}
+
+impl WorkspaceSourceScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan `uri`'s `content` if it has changed since the last call for this
+    /// URI (by content fingerprint, not mtime - see
+    /// [`super::source_map::fingerprint_content`]), returning `None` when
+    /// it's unchanged so the caller can skip re-processing it.
+    pub fn scan_file(&self, uri: &Url, content: &str) -> Option<Vec<DiscoveredSourceCall>> {
+        let fingerprint = fingerprint_content(content);
+        let mut fingerprints = self
+            .fingerprints
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if fingerprints.get(uri) == Some(&fingerprint) {
+            return None;
+        }
+        fingerprints.insert(uri.clone(), fingerprint);
+        drop(fingerprints);
+
+        Some(scan_content_for_source_calls(content))
+    }
+
+    /// Forget `uri`'s fingerprint, so the next `scan_file` call for it always
+    /// re-scans (e.g. after it's deleted and later re-created).
+    pub fn invalidate(&self, uri: &Url) {
+        if let Ok(mut fingerprints) = self.fingerprints.lock() {
+            fingerprints.remove(uri);
+        }
+    }
+}
+
+/// Recursively scan every `.R`/`.Rmd` file under `root` for source() calls,
+/// incrementally via `scanner` and cancelable via `cancellation` (checked
+/// between files, mirroring `cross_file::background_indexer`'s use of the
+/// same `CancellationToken` type) - a large workspace scan can be aborted as
+/// soon as the editor no longer needs it.
+pub fn scan_workspace_for_source_calls(
+    root: &Path,
+    scanner: &WorkspaceSourceScanner,
+    cancellation: &CancellationToken,
+) -> HashMap<Url, Vec<DiscoveredSourceCall>> {
+    let mut results = HashMap::new();
+    scan_dir(root, scanner, cancellation, &mut results);
+    results
+}
+
+fn scan_dir(
+    dir: &Path,
+    scanner: &WorkspaceSourceScanner,
+    cancellation: &CancellationToken,
+    results: &mut HashMap<Url, Vec<DiscoveredSourceCall>>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if cancellation.is_cancelled() {
+            return;
+        }
+
+        let path = entry.path();
+        if path.is_dir() {
+            scan_dir(&path, scanner, cancellation, results);
+            continue;
+        }
+
+        let is_r_source = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("r") || ext.eq_ignore_ascii_case("rmd"))
+            .unwrap_or(false);
+        if !is_r_source {
+            continue;
+        }
+
+        let Ok(uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+
+        if let Some(calls) = scanner.scan_file(&uri, &content) {
+            results.insert(uri, calls);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scans_source_sys_source_and_source_url() {
+        let content = concat!(
+            "source(\"a.R\")\n",
+            "sys.source('b.R', envir = globalenv())\n",
+            "source_url(\"https://example.com/c.R\")\n",
+        );
+        let calls = scan_content_for_source_calls(content);
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].path, "a.R");
+        assert!(!calls[0].is_sys_source && !calls[0].is_source_url);
+        assert_eq!(calls[1].path, "b.R");
+        assert!(calls[1].is_sys_source);
+        assert_eq!(calls[2].path, "https://example.com/c.R");
+        assert!(calls[2].is_source_url);
+    }
+
+    #[test]
+    fn test_records_line_and_column() {
+        let content = "x <- 1\n    source(\"child.R\")\n";
+        let calls = scan_content_for_source_calls(content);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].line, 1);
+        assert_eq!(calls[0].column, 4);
+    }
+
+    #[test]
+    fn test_no_calls_in_plain_code() {
+        assert!(scan_content_for_source_calls("x <- 1\ny <- 2\n").is_empty());
+    }
+
+    #[test]
+    fn test_scanner_skips_unchanged_file() {
+        let scanner = WorkspaceSourceScanner::new();
+        let uri = Url::parse("file:///project/a.R").unwrap();
+        let content = "source(\"b.R\")\n";
+
+        assert!(scanner.scan_file(&uri, content).is_some());
+        assert!(scanner.scan_file(&uri, content).is_none());
+    }
+
+    #[test]
+    fn test_scanner_rescans_after_content_change() {
+        let scanner = WorkspaceSourceScanner::new();
+        let uri = Url::parse("file:///project/a.R").unwrap();
+
+        assert!(scanner.scan_file(&uri, "source(\"b.R\")\n").is_some());
+        assert!(scanner.scan_file(&uri, "source(\"c.R\")\n").is_some());
+    }
+
+    #[test]
+    fn test_scanner_rescans_after_invalidate() {
+        let scanner = WorkspaceSourceScanner::new();
+        let uri = Url::parse("file:///project/a.R").unwrap();
+        let content = "source(\"b.R\")\n";
+
+        assert!(scanner.scan_file(&uri, content).is_some());
+        scanner.invalidate(&uri);
+        assert!(scanner.scan_file(&uri, content).is_some());
+    }
+
+    #[test]
+    fn test_scan_workspace_walks_nested_directories() {
+        let tmp = std::env::temp_dir().join(format!(
+            "raven_workspace_source_scan_test_{:?}",
+            std::thread::current().id()
+        ));
+        let sub = tmp.join("R");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(tmp.join("main.R"), "source(\"R/helper.R\")\n").unwrap();
+        std::fs::write(sub.join("helper.R"), "x <- 1\n").unwrap();
+        std::fs::write(tmp.join("notes.txt"), "source(\"ignored.R\")\n").unwrap();
+
+        let scanner = WorkspaceSourceScanner::new();
+        let cancellation = CancellationToken::new();
+        let results = scan_workspace_for_source_calls(&tmp, &scanner, &cancellation);
+
+        let main_uri = Url::from_file_path(tmp.join("main.R")).unwrap();
+        assert_eq!(results.get(&main_uri).map(|c| c.len()), Some(1));
+        assert_eq!(results.len(), 2, "should find main.R and helper.R, not notes.txt");
+
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_scan_workspace_respects_cancellation() {
+        let tmp = std::env::temp_dir().join(format!(
+            "raven_workspace_source_scan_cancel_test_{:?}",
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join("main.R"), "source(\"helper.R\")\n").unwrap();
+
+        let scanner = WorkspaceSourceScanner::new();
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let results = scan_workspace_for_source_calls(&tmp, &scanner, &cancellation);
+
+        assert!(results.is_empty());
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -397,18 +397,19 @@ impl DocumentStore {
     ) {
         self.mark_update_started(uri);
         if let Some(state) = self.documents.get_mut(uri) {
-            // Apply changes to content
+            // Apply changes to content, editing the existing tree in lockstep
+            // so the reparse below can reuse its unchanged subtrees.
             for change in changes {
-                Self::apply_change_to_rope(&mut state.contents, change);
+                Self::apply_change(&mut state.contents, &mut state.tree, change);
             }
 
             // Update version and revision
             state.version = version;
             state.revision += 1;
 
-            // Reparse and recompute derived data
+            // Incrementally reparse and recompute derived data
             let content = state.contents.to_string();
-            state.tree = Self::parse_content(&content);
+            state.tree = Self::parse_content_incremental(&content, state.tree.as_ref());
             state.loaded_packages = Self::extract_packages(&state.tree, &content);
             state.metadata = crate::cross_file::extract_metadata(&content);
             state.artifacts = if let Some(ref tree) = state.tree {
@@ -440,13 +441,13 @@ impl DocumentStore {
         self.mark_update_started(uri);
         if let Some(state) = self.documents.get_mut(uri) {
             for change in changes {
-                Self::apply_change_to_rope(&mut state.contents, change);
+                Self::apply_change(&mut state.contents, &mut state.tree, change);
             }
             state.version = version;
             state.revision += 1;
 
             let content = state.contents.to_string();
-            state.tree = Self::parse_content(&content);
+            state.tree = Self::parse_content_incremental(&content, state.tree.as_ref());
             state.loaded_packages = Self::extract_packages(&state.tree, &content);
             state.metadata = metadata;
             state.artifacts = if let Some(ref tree) = state.tree {
@@ -735,11 +736,19 @@ impl DocumentStore {
             .sum()
     }
 
-    /// Parse R content into a tree
+    /// Parse R content into a tree from scratch (no previous tree to reuse)
     fn parse_content(content: &str) -> Option<Tree> {
         crate::parser_pool::with_parser(|parser| parser.parse(content, None))
     }
 
+    /// Parse R content into a tree, reusing `old_tree`'s unchanged subtrees
+    /// when one is available. `old_tree` must already reflect every edit
+    /// made since it was produced via [`Tree::edit`] (see [`Self::apply_change`]),
+    /// or tree-sitter's incremental reuse will be wrong.
+    fn parse_content_incremental(content: &str, old_tree: Option<&Tree>) -> Option<Tree> {
+        crate::parser_pool::with_parser(|parser| parser.parse(content, old_tree))
+    }
+
     /// Extract loaded packages from parsed tree
     fn extract_packages(tree: &Option<Tree>, content: &str) -> Vec<String> {
         let Some(tree) = tree else {
@@ -803,8 +812,13 @@ impl DocumentStore {
         }
     }
 
-    /// Apply a single change to a Rope
-    fn apply_change_to_rope(contents: &mut Rope, change: TextDocumentContentChangeEvent) {
+    /// Apply a single change to a Rope, editing `tree` (if present) in
+    /// lockstep via [`Tree::edit`] so a subsequent
+    /// [`Self::parse_content_incremental`] can reuse its unaffected
+    /// subtrees. A full-document sync (no `range`) can't be expressed as an
+    /// edit, so it clears `tree` instead - the caller always reparses from
+    /// scratch after a full sync.
+    fn apply_change(contents: &mut Rope, tree: &mut Option<Tree>, change: TextDocumentContentChangeEvent) {
         if let Some(range) = change.range {
             let start_line = range.start.line as usize;
             let start_utf16_char = range.start.character as usize;
@@ -820,11 +834,67 @@ impl DocumentStore {
             let start_idx = contents.line_to_char(start_line) + start_char;
             let end_idx = contents.line_to_char(end_line) + end_char;
 
+            let start_byte = contents.char_to_byte(start_idx);
+            let old_end_byte = contents.char_to_byte(end_idx);
+            let start_position = tree_sitter::Point {
+                row: start_line,
+                column: Self::char_col_to_byte_col(&start_line_text, start_char),
+            };
+            let old_end_position = tree_sitter::Point {
+                row: end_line,
+                column: Self::char_col_to_byte_col(&end_line_text, end_char),
+            };
+
             contents.remove(start_idx..end_idx);
             contents.insert(start_idx, &change.text);
+
+            let new_end_byte = start_byte + change.text.len();
+            let new_end_position = Self::end_position_after_insert(start_position, &change.text);
+
+            if let Some(tree) = tree {
+                tree.edit(&tree_sitter::InputEdit {
+                    start_byte,
+                    old_end_byte,
+                    new_end_byte,
+                    start_position,
+                    old_end_position,
+                    new_end_position,
+                });
+            }
         } else {
-            // Full document sync
+            // Full document sync: not expressible as an InputEdit, so drop
+            // the tree and let the caller reparse from scratch.
             *contents = Rope::from_str(&change.text);
+            *tree = None;
+        }
+    }
+
+    /// Byte offset of `char_col` (a char index within `line_text`) from the
+    /// start of the line, for building a `tree_sitter::Point` (tree-sitter
+    /// columns are byte offsets, not char or UTF-16 offsets).
+    fn char_col_to_byte_col(line_text: &str, char_col: usize) -> usize {
+        line_text
+            .char_indices()
+            .nth(char_col)
+            .map(|(byte_idx, _)| byte_idx)
+            .unwrap_or(line_text.len())
+    }
+
+    /// The `tree_sitter::Point` just past `inserted` when it's spliced in at
+    /// `start`.
+    fn end_position_after_insert(start: tree_sitter::Point, inserted: &str) -> tree_sitter::Point {
+        let newline_count = inserted.bytes().filter(|&b| b == b'\n').count();
+        if newline_count == 0 {
+            tree_sitter::Point {
+                row: start.row,
+                column: start.column + inserted.len(),
+            }
+        } else {
+            let last_line_len = inserted.rsplit('\n').next().unwrap_or("").len();
+            tree_sitter::Point {
+                row: start.row + newline_count,
+                column: last_line_len,
+            }
         }
     }
 
@@ -6,15 +6,22 @@
 //
 
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
 
+use serde::Serialize;
 use tower_lsp::lsp_types::*;
 use tree_sitter::Node;
 use tree_sitter::Point;
 
 use crate::content_provider::ContentProvider;
 use crate::cross_file::dependency::compute_inherited_working_directory;
-use crate::cross_file::{scope, ScopedSymbol};
-use crate::state::WorldState;
+use crate::cross_file::dependency::DependencyEdge;
+use crate::cross_file::{
+    scope, DiagnosticCode, DiagnosticSeverityOverride, ScopeFingerprint, ScopedSymbol,
+};
+use crate::state::{HoverConfig, WorldState};
+use crate::string_utils::levenshtein_distance;
 
 use crate::builtins;
 
@@ -73,6 +80,29 @@ fn get_cross_file_scope(
     uri: &Url,
     line: u32,
     column: u32,
+) -> scope::ScopeAtPosition {
+    get_cross_file_scope_with_max_depth(
+        state,
+        uri,
+        line,
+        column,
+        state.cross_file_config.max_chain_depth,
+    )
+}
+
+/// Same as `get_cross_file_scope`, but with the traversal depth supplied by
+/// the caller instead of always reading `cross_file_config.max_chain_depth`.
+/// `hover` uses this with `max_depth = 1` when `HoverConfig::cross_file` is
+/// `false`, which processes the current file's own artifacts but stops
+/// before following any backward/forward `source()` edge — restricting
+/// resolution to the current file's local scope without duplicating
+/// `scope_at_position_with_graph`'s traversal logic.
+fn get_cross_file_scope_with_max_depth(
+    state: &WorldState,
+    uri: &Url,
+    line: u32,
+    column: u32,
+    max_depth: usize,
 ) -> scope::ScopeAtPosition {
     // Use ContentProvider for unified access
     let content_provider = state.content_provider();
@@ -87,8 +117,6 @@ fn get_cross_file_scope(
         content_provider.get_metadata(target_uri)
     };
 
-    let max_depth = state.cross_file_config.max_chain_depth;
-
     // Get base_exports from package_library if ready, otherwise empty set.
     // This ensures base R functions (stop, sprintf, exists, etc.) are available
     // in cross-file scope resolution for hover, completions, and go-to-definition.
@@ -119,9 +147,11 @@ fn get_cross_file_scope(
 pub fn folding_range(state: &WorldState, uri: &Url) -> Option<Vec<FoldingRange>> {
     let doc = state.get_document(uri)?;
     let tree = doc.tree.as_ref()?;
+    let text = doc.contents.to_string();
     let mut ranges = Vec::new();
 
     collect_folding_ranges(tree.root_node(), &mut ranges);
+    collect_comment_folding_ranges(tree.root_node(), &text, &mut ranges);
 
     Some(ranges)
 }
@@ -146,6 +176,24 @@ fn collect_folding_ranges(node: Node, ranges: &mut Vec<FoldingRange>) {
         });
     }
 
+    // Multi-line call argument lists fold as their own region, independent of
+    // whatever statement the call appears in (e.g. a long `data.frame(...)` call
+    // inside a single-line assignment).
+    if kind == "call" {
+        if let Some(arguments) = node.child_by_field_name("arguments") {
+            if arguments.start_position().row != arguments.end_position().row {
+                ranges.push(FoldingRange {
+                    start_line: arguments.start_position().row as u32,
+                    start_character: Some(arguments.start_position().column as u32),
+                    end_line: arguments.end_position().row as u32,
+                    end_character: Some(arguments.end_position().column as u32),
+                    kind: Some(FoldingRangeKind::Region),
+                    collapsed_text: None,
+                });
+            }
+        }
+    }
+
     // Recurse into children
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
@@ -153,6 +201,60 @@ fn collect_folding_ranges(node: Node, ranges: &mut Vec<FoldingRange>) {
     }
 }
 
+/// Coalesces runs of consecutive `comment` nodes into foldable regions.
+///
+/// Roxygen (`#'`) blocks are kept separate from plain `#` comment runs even
+/// when adjacent, since they document different things (and editors usually
+/// want to fold/unfold them independently).
+fn collect_comment_folding_ranges(root: Node, text: &str, ranges: &mut Vec<FoldingRange>) {
+    let mut comments = Vec::new();
+    collect_comment_nodes(root, &mut comments);
+    comments.sort_by_key(|node| node.start_position().row);
+
+    let mut run_start = 0;
+    for i in 1..=comments.len() {
+        let run_continues = i < comments.len()
+            && comments[i].start_position().row == comments[i - 1].end_position().row + 1
+            && is_roxygen_comment(comments[i], text) == is_roxygen_comment(comments[i - 1], text);
+
+        if !run_continues {
+            push_comment_fold(&comments[run_start..i], ranges);
+            run_start = i;
+        }
+    }
+}
+
+fn collect_comment_nodes<'a>(node: Node<'a>, comments: &mut Vec<Node<'a>>) {
+    if node.kind() == "comment" {
+        comments.push(node);
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comment_nodes(child, comments);
+    }
+}
+
+fn is_roxygen_comment(node: Node, text: &str) -> bool {
+    node_text(node, text).starts_with("#'")
+}
+
+fn push_comment_fold(run: &[Node], ranges: &mut Vec<FoldingRange>) {
+    // A single comment line has nothing left to collapse once folded.
+    if run.len() < 2 {
+        return;
+    }
+    let first = run[0];
+    let last = run[run.len() - 1];
+    ranges.push(FoldingRange {
+        start_line: first.start_position().row as u32,
+        start_character: Some(first.start_position().column as u32),
+        end_line: last.end_position().row as u32,
+        end_character: Some(last.end_position().column as u32),
+        kind: Some(FoldingRangeKind::Comment),
+        collapsed_text: None,
+    });
+}
+
 // ============================================================================
 // Selection Range
 // ============================================================================
@@ -176,8 +278,18 @@ pub fn selection_range(
     Some(results)
 }
 
+// Starts from the innermost *named* node rather than whatever leaf token sits
+// under the cursor, so landing exactly on a comma or a quote still begins the
+// chain at a meaningful syntactic unit. From there the plain ancestor walk
+// already produces the desired shape for free: tree-sitter-r gives comma
+// lists their own `arguments`/`parameters` node (so one element expands to
+// the whole list before the enclosing `call`/`function_definition` adds its
+// parentheses), and string/comment contents have no named children to
+// descend into (so they expand whole before their enclosing expression).
 fn build_selection_range(root: Node, point: Point) -> Option<SelectionRange> {
-    let mut node = root.descendant_for_point_range(point, point)?;
+    let mut node = root
+        .named_descendant_for_point_range(point, point)
+        .or_else(|| root.descendant_for_point_range(point, point))?;
     let mut ranges: Vec<Range> = Vec::new();
 
     loop {
@@ -293,6 +405,120 @@ fn collect_symbols(node: Node, text: &str, symbols: &mut Vec<SymbolInformation>)
 // Diagnostics
 // ============================================================================
 
+/// Stable, machine-readable identifiers for every diagnostic Raven emits,
+/// mirroring rust-analyzer's `DiagnosticCode` convention. Editors can use
+/// these to group, filter, or let users suppress a specific diagnostic kind,
+/// instead of matching on `message` substrings (which break if wording changes).
+pub(crate) mod diagnostic_codes {
+    pub const SYNTAX_ERROR: &str = "raven::syntax-error";
+    pub const ELSE_ON_NEW_LINE: &str = "raven::else-on-new-line";
+    pub const CIRCULAR_DEPENDENCY: &str = "raven::circular-dependency";
+    pub const MAX_CHAIN_DEPTH_EXCEEDED: &str = "raven::max-chain-depth-exceeded";
+    pub const MISSING_FILE: &str = "raven::missing-file";
+    pub const AMBIGUOUS_PARENT: &str = "raven::ambiguous-parent";
+    pub const OUT_OF_SCOPE_SYMBOL: &str = "raven::out-of-scope-symbol";
+    pub const MISSING_PACKAGE: &str = "raven::missing-package";
+    pub const UNUSED_LIBRARY: &str = "raven::unused-library";
+    pub const UNDEFINED_VARIABLE: &str = "raven::undefined-variable";
+    pub const DIRECTIVE_SUPPRESSES_CALL: &str = "raven::directive-suppresses-call";
+    pub const ARG_COUNT_MISMATCH: &str = "raven::arg-count-mismatch";
+    pub const UNUSED_DEFINITION: &str = "raven::unused-definition";
+    pub const UNLOADED_NAMESPACE_PACKAGE: &str = "raven::unloaded-namespace-package";
+    pub const INCORRECT_CASE: &str = "raven::incorrect-case";
+    pub const UNSOURCED_FILE: &str = "raven::unsourced-file";
+    pub const UNTRACKED_SOURCE_TARGET: &str = "raven::untracked-source-target";
+    pub const UNTRUSTED_FILE_PERMISSIONS: &str = "raven::untrusted-file-permissions";
+}
+
+/// Builds the `(code, code_description)` pair for a diagnostic from one of the
+/// stable slugs in `diagnostic_codes`, pointing `code_description` at the
+/// matching section of the in-repo diagnostics reference so clients that
+/// render it (e.g. "learn more" links) have somewhere to send the user.
+pub(crate) fn diagnostic_code(code: &str) -> (Option<NumberOrString>, Option<CodeDescription>) {
+    let anchor = code.trim_start_matches("raven::");
+    let href = format!("https://github.com/jbearak/raven/blob/main/docs/diagnostics.md#{anchor}");
+    (
+        Some(NumberOrString::String(code.to_string())),
+        Url::parse(&href).ok().map(|href| CodeDescription { href }),
+    )
+}
+
+/// Builds a single `DiagnosticRelatedInformation` entry pointing at a secondary
+/// span in `uri`, e.g. the declaration a symbol shadows or (for the orphaned-else
+/// diagnostic) the closing brace the `else` should have stayed on the same line
+/// as. Shared so cross-file and completion-precedence diagnostics can attach the
+/// same kind of secondary span without re-deriving the `Location` plumbing.
+pub(crate) fn related_information_entry(
+    uri: Url,
+    range: Range,
+    message: impl Into<String>,
+) -> DiagnosticRelatedInformation {
+    DiagnosticRelatedInformation {
+        location: Location { uri, range },
+        message: message.into(),
+    }
+}
+
+/// Drops diagnostics suppressed by an `@lsp-allow: <code>[, <code>...]` directive,
+/// matching each diagnostic's `code` (see `diagnostic_codes`) and the line its
+/// range starts on against the file- and line-scoped allow-lists parsed from the
+/// document. Diagnostics without a `code` (there shouldn't be any) pass through
+/// unfiltered, since there's nothing to match against.
+fn filter_allowed_diagnostics(
+    diagnostics: Vec<Diagnostic>,
+    directive_meta: &crate::cross_file::CrossFileMetadata,
+) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            let Some(NumberOrString::String(code)) = &diagnostic.code else {
+                return true;
+            };
+            !crate::cross_file::directive::is_diagnostic_allowed(
+                directive_meta,
+                diagnostic.range.start.line,
+                code,
+            )
+        })
+        .collect()
+}
+
+/// Applies the user-configured `diagnostics.severityOverrides` map (see
+/// `DiagnosticSeverityConfig` in `cross_file::config`) to the diagnostics a
+/// document's collectors already produced: remaps each diagnostic's
+/// severity to whatever the matching `DiagnosticCode` is configured to, or
+/// drops it entirely when configured `off`. Runs as a final pass, same as
+/// `filter_allowed_diagnostics`, so collectors stay free of severity
+/// bookkeeping and every code - including ones without a dedicated
+/// `CrossFileConfig` field - is remappable.
+fn apply_severity_overrides(
+    diagnostics: Vec<Diagnostic>,
+    overrides: &crate::cross_file::DiagnosticSeverityConfig,
+) -> Vec<Diagnostic> {
+    if overrides.is_empty() {
+        return diagnostics;
+    }
+
+    diagnostics
+        .into_iter()
+        .filter_map(|mut diagnostic| {
+            let Some(NumberOrString::String(code)) = &diagnostic.code else {
+                return Some(diagnostic);
+            };
+            let Some(code) = DiagnosticCode::from_str(code) else {
+                return Some(diagnostic);
+            };
+            match overrides.get(code) {
+                None => Some(diagnostic),
+                Some(severity_override) => {
+                    diagnostic.severity = severity_override.to_lsp_severity();
+                    diagnostic.severity.is_some().then_some(diagnostic)
+                }
+            }
+        })
+        .collect()
+}
+
 /// Compute diagnostics for the document at the given URI.
 ///
 /// Performs a full set of checks for the specified open document and returns collected diagnostics.
@@ -369,12 +595,21 @@ pub fn diagnostics(state: &WorldState, uri: &Url) -> Vec<Diagnostic> {
         }
     }
 
-    // Collect syntax errors (not suppressed by @lsp-ignore)
-    collect_syntax_errors(tree.root_node(), &mut diagnostics);
-
-    // Collect else-on-newline errors
+    // Collect syntax errors and else-on-newline errors in a single shared
+    // traversal (see `run_handlers`) instead of walking the tree once per
+    // check.
     // _Requirements: 4.1_
-    collect_else_newline_errors(tree.root_node(), &text, &mut diagnostics);
+    let else_newline_handler = ElseNewlineHandler { uri };
+    run_handlers(
+        tree.root_node(),
+        &text,
+        &[
+            &SyntaxErrorHandler as &dyn DiagnosticHandler,
+            &else_newline_handler as &dyn DiagnosticHandler,
+        ],
+        &state.cross_file_config.diagnostic_severity_overrides,
+        &mut diagnostics,
+    );
 
     // Check for circular dependencies
     if let Some(cycle_edge) = state.cross_file_graph.detect_cycle(uri) {
@@ -385,12 +620,15 @@ pub fn diagnostics(state: &WorldState, uri: &Url) -> Vec<Diagnostic> {
             .path_segments()
             .and_then(|mut s| s.next_back().map(|s| s.to_string()))
             .unwrap_or_default();
+        let (code, code_description) = diagnostic_code(diagnostic_codes::CIRCULAR_DEPENDENCY);
         diagnostics.push(Diagnostic {
             range: Range {
                 start: Position::new(line, col),
                 end: Position::new(line, col + 1),
             },
             severity: Some(state.cross_file_config.circular_dependency_severity),
+            code,
+            code_description,
             message: format!(
                 "Circular dependency detected: sourcing '{}' creates a cycle",
                 target
@@ -405,6 +643,9 @@ pub fn diagnostics(state: &WorldState, uri: &Url) -> Vec<Diagnostic> {
     // Check for missing files in source() calls and directives (Requirement 10.2)
     collect_missing_file_diagnostics(state, uri, &directive_meta, &mut diagnostics);
 
+    // Check for files skipped by the permission checker rather than loaded
+    collect_untrusted_file_diagnostics(state, uri, &directive_meta, &mut diagnostics);
+
     // Check for ambiguous parents (Requirement 5.10 / 10.6)
     collect_ambiguous_parent_diagnostics(state, uri, &directive_meta, &mut diagnostics);
 
@@ -421,6 +662,25 @@ pub fn diagnostics(state: &WorldState, uri: &Url) -> Vec<Diagnostic> {
     // Check for missing packages in library() calls (Requirement 15.1)
     collect_missing_package_diagnostics(state, &directive_meta, &mut diagnostics);
 
+    // Check for namespace-qualified calls (pkg::fn) whose package isn't loaded
+    collect_unloaded_namespace_package_diagnostics(
+        state,
+        uri,
+        tree.root_node(),
+        &text,
+        &directive_meta,
+        &mut diagnostics,
+    );
+
+    // Check for library()/require() calls whose package is never used
+    collect_unused_library_diagnostics(
+        state,
+        tree.root_node(),
+        &text,
+        &directive_meta,
+        &mut diagnostics,
+    );
+
     // Collect undefined variable errors if enabled in config
     if state.cross_file_config.undefined_variables_enabled {
         collect_undefined_variables_position_aware(
@@ -436,7 +696,48 @@ pub fn diagnostics(state: &WorldState, uri: &Url) -> Vec<Diagnostic> {
         );
     }
 
-    diagnostics
+    // Check calls to locally-defined functions for mismatched argument counts
+    collect_arg_count_diagnostics(
+        state,
+        uri,
+        tree.root_node(),
+        &text,
+        &directive_meta,
+        &mut diagnostics,
+    );
+
+    // Check for assignments that are never read back
+    collect_unused_definition_diagnostics(
+        state,
+        uri,
+        tree.root_node(),
+        &text,
+        &directive_meta,
+        &mut diagnostics,
+    );
+
+    // Check assignment targets against the configured naming convention
+    collect_naming_convention_diagnostics(
+        state,
+        tree.root_node(),
+        &text,
+        &directive_meta,
+        &mut diagnostics,
+    );
+
+    // Check whether this file is reached by any source() chain at all
+    collect_unsourced_file_diagnostics(state, uri, tree.root_node(), &text, &mut diagnostics);
+
+    // Drop diagnostics suppressed by @lsp-allow directives (Requirement: diagnostic
+    // code subsystem). Runs last so it sees the final code/range of every diagnostic.
+    let diagnostics = filter_allowed_diagnostics(diagnostics, &directive_meta);
+
+    // Apply user-configured per-code severity remapping/disabling. Runs after
+    // @lsp-allow so an overridden severity still reflects in what that directive sees.
+    apply_severity_overrides(
+        diagnostics,
+        &state.cross_file_config.diagnostic_severity_overrides,
+    )
 }
 
 /// Async version of diagnostics that uses batched existence checks for missing files
@@ -526,6 +827,7 @@ async fn collect_missing_file_diagnostics_standalone(
     missing_file_severity: DiagnosticSeverity,
 ) -> Vec<Diagnostic> {
     let mut diagnostics = Vec::new();
+    let (code, code_description) = diagnostic_code(diagnostic_codes::MISSING_FILE);
     let workspace_root = workspace_folders.and_then(|w| w.to_file_path().ok());
 
     // Forward sources use @lsp-cd for path resolution
@@ -556,6 +858,8 @@ async fn collect_missing_file_diagnostics_standalone(
                             ),
                         },
                         severity: Some(missing_file_severity),
+                        code: code.clone(),
+                        code_description: code_description.clone(),
                         message: format!("Path is outside workspace: '{}'", source.path),
                         ..Default::default()
                     });
@@ -576,6 +880,8 @@ async fn collect_missing_file_diagnostics_standalone(
                     ),
                 },
                 severity: Some(missing_file_severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
                 message: format!("Cannot resolve path: '{}'", source.path),
                 ..Default::default()
             });
@@ -595,6 +901,8 @@ async fn collect_missing_file_diagnostics_standalone(
                             end: Position::new(directive.directive_line, u32::MAX),
                         },
                         severity: Some(missing_file_severity),
+                        code: code.clone(),
+                        code_description: code_description.clone(),
                         message: format!("Path is outside workspace: '{}'", directive.path),
                         ..Default::default()
                     });
@@ -615,6 +923,8 @@ async fn collect_missing_file_diagnostics_standalone(
                     end: Position::new(directive.directive_line, u32::MAX),
                 },
                 severity: Some(missing_file_severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
                 message: format!("Cannot resolve parent path: '{}'", directive.path),
                 ..Default::default()
             });
@@ -652,6 +962,8 @@ async fn collect_missing_file_diagnostics_standalone(
                         end: Position::new(line, u32::MAX),
                     },
                     severity: Some(missing_file_severity),
+                    code: code.clone(),
+                    code_description: code_description.clone(),
                     message: format!("Parent file not found: '{}'", path_str),
                     ..Default::default()
                 });
@@ -665,6 +977,8 @@ async fn collect_missing_file_diagnostics_standalone(
                         ),
                     },
                     severity: Some(missing_file_severity),
+                    code: code.clone(),
+                    code_description: code_description.clone(),
                     message: format!("File not found: '{}'", path_str),
                     ..Default::default()
                 });
@@ -694,6 +1008,7 @@ fn collect_missing_file_diagnostics(
     diagnostics: &mut Vec<Diagnostic>,
 ) {
     let content_provider = state.content_provider();
+    let (code, code_description) = diagnostic_code(diagnostic_codes::MISSING_FILE);
 
     // Forward sources use @lsp-cd for path resolution
     let forward_ctx = crate::cross_file::path_resolve::PathContext::from_metadata(
@@ -726,6 +1041,8 @@ fn collect_missing_file_diagnostics(
                         ),
                     },
                     severity: Some(state.cross_file_config.missing_file_severity),
+                    code: code.clone(),
+                    code_description: code_description.clone(),
                     message: format!("File not found: '{}'", source.path),
                     ..Default::default()
                 });
@@ -743,6 +1060,8 @@ fn collect_missing_file_diagnostics(
                     ),
                 },
                 severity: Some(state.cross_file_config.missing_file_severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
                 message: format!("Cannot resolve path: '{}'", source.path),
                 ..Default::default()
             });
@@ -764,6 +1083,8 @@ fn collect_missing_file_diagnostics(
                         end: Position::new(directive.directive_line, u32::MAX),
                     },
                     severity: Some(state.cross_file_config.missing_file_severity),
+                    code: code.clone(),
+                    code_description: code_description.clone(),
                     message: format!("Parent file not found: '{}'", directive.path),
                     ..Default::default()
                 });
@@ -775,6 +1096,8 @@ fn collect_missing_file_diagnostics(
                     end: Position::new(directive.directive_line, u32::MAX),
                 },
                 severity: Some(state.cross_file_config.missing_file_severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
                 message: format!("Cannot resolve parent path: '{}'", directive.path),
                 ..Default::default()
             });
@@ -782,6 +1105,99 @@ fn collect_missing_file_diagnostics(
     }
 }
 
+/// Collect diagnostics for files skipped by the workspace's `permission_checker`
+/// rather than ingested (Requirement 10.2's "skip, don't pretend" follow-up).
+///
+/// A file that fails the trust check never makes it into the cross-file cache,
+/// so it would otherwise look identical to an ordinary missing file - or
+/// simply vanish if something else (another source call, the workspace index)
+/// already resolved it. This cross-references `meta.sources`/`meta.sourced_by`
+/// against [`crate::cross_file::CrossFileFileCache::untrusted_uris`] so the
+/// call site gets a diagnostic explaining *why* the target was skipped.
+///
+/// Emits nothing if `untrusted_file_severity` is `None` (permission checking
+/// disabled, or the maintainer opted out of surfacing it as a diagnostic).
+fn collect_untrusted_file_diagnostics(
+    state: &WorldState,
+    uri: &Url,
+    meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some(severity) = state.cross_file_config.untrusted_file_severity else {
+        return;
+    };
+
+    let untrusted: std::collections::HashSet<Url> =
+        state.cross_file_file_cache.untrusted_uris().into_iter().collect();
+    if untrusted.is_empty() {
+        return;
+    }
+
+    let (code, code_description) = diagnostic_code(diagnostic_codes::UNTRUSTED_FILE_PERMISSIONS);
+
+    // Forward sources use @lsp-cd for path resolution
+    let forward_ctx = crate::cross_file::path_resolve::PathContext::from_metadata(
+        uri,
+        meta,
+        state.workspace_folders.first(),
+    );
+    // Backward directives IGNORE @lsp-cd - always resolve relative to file's directory
+    let backward_ctx =
+        crate::cross_file::path_resolve::PathContext::new(uri, state.workspace_folders.first());
+
+    for source in &meta.sources {
+        let resolved = forward_ctx.as_ref().and_then(|ctx| {
+            let path = crate::cross_file::path_resolve::resolve_path(&source.path, ctx)?;
+            crate::cross_file::path_resolve::path_to_uri(&path)
+        });
+        if resolved.is_some_and(|target_uri| untrusted.contains(&target_uri)) {
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position::new(source.line, source.column),
+                    end: Position::new(
+                        source.line,
+                        source
+                            .column
+                            .saturating_add(source.path.len() as u32)
+                            .saturating_add(10),
+                    ),
+                },
+                severity: Some(severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
+                message: format!(
+                    "File not trusted and was not loaded: '{}'",
+                    source.path
+                ),
+                ..Default::default()
+            });
+        }
+    }
+
+    for directive in &meta.sourced_by {
+        let resolved = backward_ctx.as_ref().and_then(|ctx| {
+            let path = crate::cross_file::path_resolve::resolve_path(&directive.path, ctx)?;
+            crate::cross_file::path_resolve::path_to_uri(&path)
+        });
+        if resolved.is_some_and(|target_uri| untrusted.contains(&target_uri)) {
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position::new(directive.directive_line, 0),
+                    end: Position::new(directive.directive_line, u32::MAX),
+                },
+                severity: Some(severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
+                message: format!(
+                    "Parent file not trusted and was not loaded: '{}'",
+                    directive.path
+                ),
+                ..Default::default()
+            });
+        }
+    }
+}
+
 /// Async version of missing file diagnostics that checks disk existence
 ///
 /// This version uses `AsyncContentProvider::check_existence_batch` to perform
@@ -801,6 +1217,7 @@ pub async fn collect_missing_file_diagnostics_async(
     missing_file_severity: DiagnosticSeverity,
 ) -> Vec<Diagnostic> {
     let mut diagnostics = Vec::new();
+    let (code, code_description) = diagnostic_code(diagnostic_codes::MISSING_FILE);
 
     // Forward sources use @lsp-cd for path resolution
     let forward_ctx =
@@ -837,6 +1254,8 @@ pub async fn collect_missing_file_diagnostics_async(
                     ),
                 },
                 severity: Some(missing_file_severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
                 message: format!("Cannot resolve path: '{}'", source.path),
                 ..Default::default()
             });
@@ -863,6 +1282,8 @@ pub async fn collect_missing_file_diagnostics_async(
                     end: Position::new(directive.directive_line, u32::MAX),
                 },
                 severity: Some(missing_file_severity),
+                code: code.clone(),
+                code_description: code_description.clone(),
                 message: format!("Cannot resolve parent path: '{}'", directive.path),
                 ..Default::default()
             });
@@ -890,6 +1311,8 @@ pub async fn collect_missing_file_diagnostics_async(
                         end: Position::new(line, u32::MAX),
                     },
                     severity: Some(missing_file_severity),
+                    code: code.clone(),
+                    code_description: code_description.clone(),
                     message: format!("Parent file not found: '{}'", path),
                     ..Default::default()
                 });
@@ -903,6 +1326,8 @@ pub async fn collect_missing_file_diagnostics_async(
                         ),
                     },
                     severity: Some(missing_file_severity),
+                    code: code.clone(),
+                    code_description: code_description.clone(),
                     message: format!("File not found: '{}'", path),
                     ..Default::default()
                 });
@@ -913,26 +1338,42 @@ pub async fn collect_missing_file_diagnostics_async(
     diagnostics
 }
 
+/// Look up the exported-scope artifacts for `target_uri`, preferring open
+/// documents, then the cross-file workspace index, then the legacy workspace
+/// index (same priority every `get_artifacts` closure in this file uses).
+///
+/// An open document's own `compute_artifacts` result is cached by content +
+/// loaded-package-exports fingerprint (see [`ScopeFingerprint::for_document`]),
+/// so walking the same unedited file's scope repeatedly - e.g. once per hop
+/// while checking chain depth, or once per `source()` target while checking
+/// out-of-scope usages - reparses and rescoped it at most once per edit
+/// rather than once per lookup.
+fn get_chain_artifacts(state: &WorldState, target_uri: &Url) -> Option<scope::ScopeArtifacts> {
+    if let Some(doc) = state.documents.get(target_uri) {
+        if let Some(tree) = &doc.tree {
+            let fp = ScopeFingerprint::for_document(&doc, &state.package_library);
+            return Some(state.cross_file_cache.get_or_compute(target_uri, fp, || {
+                scope::compute_artifacts(target_uri, tree, &doc.text())
+            }));
+        }
+    }
+    if let Some(artifacts) = state.cross_file_workspace_index.get_artifacts(target_uri) {
+        return Some(artifacts);
+    }
+    if let Some(doc) = state.workspace_index.get(target_uri) {
+        if let Some(tree) = &doc.tree {
+            return Some(scope::compute_artifacts(target_uri, tree, &doc.text()));
+        }
+    }
+    None
+}
+
 /// Collect diagnostics for max chain depth exceeded (Requirement 5.8)
 fn collect_max_depth_diagnostics(state: &WorldState, uri: &Url, diagnostics: &mut Vec<Diagnostic>) {
     use crate::cross_file::scope;
 
-    let get_artifacts = |target_uri: &Url| -> Option<scope::ScopeArtifacts> {
-        if let Some(doc) = state.documents.get(target_uri) {
-            if let Some(tree) = &doc.tree {
-                return Some(scope::compute_artifacts(target_uri, tree, &doc.text()));
-            }
-        }
-        if let Some(artifacts) = state.cross_file_workspace_index.get_artifacts(target_uri) {
-            return Some(artifacts);
-        }
-        if let Some(doc) = state.workspace_index.get(target_uri) {
-            if let Some(tree) = &doc.tree {
-                return Some(scope::compute_artifacts(target_uri, tree, &doc.text()));
-            }
-        }
-        None
-    };
+    let get_artifacts =
+        |target_uri: &Url| -> Option<scope::ScopeArtifacts> { get_chain_artifacts(state, target_uri) };
 
     let get_metadata = |target_uri: &Url| -> Option<crate::cross_file::CrossFileMetadata> {
         if let Some(doc) = state.documents.get(target_uri) {
@@ -963,12 +1404,16 @@ fn collect_max_depth_diagnostics(state: &WorldState, uri: &Url, diagnostics: &mu
     // Emit diagnostics for depth exceeded, filtering to only those in this file
     for (exceeded_uri, line, col) in &scope.depth_exceeded {
         if exceeded_uri == uri {
+            let (code, code_description) =
+                diagnostic_code(diagnostic_codes::MAX_CHAIN_DEPTH_EXCEEDED);
             diagnostics.push(Diagnostic {
                 range: Range {
                     start: Position::new(*line, *col),
                     end: Position::new(*line, col.saturating_add(1)),
                 },
                 severity: Some(state.cross_file_config.max_chain_depth_severity),
+                code,
+                code_description,
                 message: format!(
                     "Maximum chain depth ({}) exceeded; some symbols may not be resolved",
                     max_depth
@@ -1061,12 +1506,15 @@ fn collect_ambiguous_parent_diagnostics(
             .and_then(|mut s| s.next_back().map(|s| s.to_string()))
             .unwrap_or_else(|| selected_uri.to_string());
 
+        let (code, code_description) = diagnostic_code(diagnostic_codes::AMBIGUOUS_PARENT);
         diagnostics.push(Diagnostic {
             range: Range {
                 start: Position::new(directive_line, 0),
                 end: Position::new(directive_line, u32::MAX),
             },
             severity: Some(state.cross_file_config.ambiguous_parent_severity),
+            code,
+            code_description,
             message: format!(
                 "Ambiguous parent: using '{}' but also found: {}. Consider adding line= or match= to disambiguate.",
                 selected_name,
@@ -1077,6 +1525,11 @@ fn collect_ambiguous_parent_diagnostics(
     }
 }
 
+/// Substring every missing-package diagnostic message contains, used by
+/// `code_action` to recognize which incoming `context.diagnostics` entries
+/// its "Install package" and "Replace with ..." quick fixes apply to.
+const MISSING_PACKAGE_DIAGNOSTIC_MARKER: &str = "is not installed";
+
 /// Emit diagnostics for `library()` calls that reference packages not present in the package library.
 ///
 /// Scans the cross-file metadata for `library()` calls and, for each call that is not ignored
@@ -1118,6 +1571,12 @@ fn collect_missing_package_diagnostics(
             // Calculate approximate start column (library( is 8 chars, package name varies)
             // We'll highlight from column 0 to the end column for simplicity
             let end_col = lib_call.column;
+            let (code, code_description) = diagnostic_code(diagnostic_codes::MISSING_PACKAGE);
+
+            let mut message = format!("Package '{}' is not installed", lib_call.package);
+            if let Some(suggestion) = suggest_similar_package(state, &lib_call.package) {
+                message.push_str(&format!(". {}", suggestion));
+            }
 
             diagnostics.push(Diagnostic {
                 range: Range {
@@ -1125,51 +1584,354 @@ fn collect_missing_package_diagnostics(
                     end: Position::new(lib_call.line, end_col),
                 },
                 severity: Some(state.cross_file_config.packages_missing_package_severity),
-                message: format!("Package '{}' is not installed", lib_call.package),
+                code,
+                code_description,
+                message,
                 ..Default::default()
             });
         }
     }
 }
 
-/// Emit diagnostics for symbols defined in sourced files that are referenced
-/// earlier in the current document than the corresponding `source()` call.
-///
-/// This function:
-/// - Scans `directive_meta.sources` and collects source paths declared in the file.
-/// - Collects identifier usages (UTF-16 columns) in `node`.
-/// - For each sourced file, resolves its URI and obtains its exported symbols (preferring open documents, then cross-file index, then legacy index).
-/// - Emits a diagnostic for every usage of an exported symbol that occurs before the `source()` call (skipping lines marked ignored by directives).
-///
-/// The produced diagnostics are appended to `diagnostics` and use the configured
-/// `out_of_scope_severity` from `state.cross_file_config`.
-///
-/// # Parameters
-///
-/// - `state`: Workspace state and indexes used to resolve artifacts and configuration.
-/// - `uri`: URI of the current document being analyzed (used to resolve relative source paths).
-/// - `node`: Root AST node of the current document.
-/// - `text`: Full source text of the current document.
-/// - `directive_meta`: Cross-file directive metadata (contains `@lsp-source` / `source()` locations).
-/// - `diagnostics`: Mutable vector to receive emitted diagnostics.
+/// Builds a "Did you mean ...?" hint for an unresolved `library()` package
+/// name, or `None` when no installed package is a plausible typo match.
 ///
-/// # Examples
-///
-/// ```no_run
-/// // Collect diagnostics into `diags` for a parsed document:
-/// let mut diags = Vec::new();
-/// collect_out_of_scope_diagnostics(&state, &uri, root_node, &text, &directive_meta, &mut diags);
-/// // `diags` now contains diagnostics for symbols used before their `source()` calls.
-/// ```
-fn collect_out_of_scope_diagnostics(
+/// Ranks every installed, non-base package by case-insensitive Levenshtein
+/// distance to `name`, keeps candidates within `max(2, name.len() / 3)`, and
+/// reports the closest one to three (ties broken alphabetically). A
+/// candidate that's identical to `name` once lowercased is excluded here,
+/// since that's a case-only mismatch rather than a typo.
+fn suggest_similar_package(state: &WorldState, name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    let lower_name = name.to_lowercase();
+    let threshold = (name.len() / 3).max(2);
+
+    let mut candidates: Vec<(usize, String)> = state
+        .package_library
+        .installed_package_names()
+        .into_iter()
+        .filter(|candidate| !state.package_library.is_base_package(candidate))
+        .filter_map(|candidate| {
+            let lower_candidate = candidate.to_lowercase();
+            if lower_candidate == lower_name {
+                return None;
+            }
+            let distance = levenshtein_distance(&lower_name, &lower_candidate);
+            (distance <= threshold).then_some((distance, candidate))
+        })
+        .collect();
+
+    if candidates.is_empty() {
+        return None;
+    }
+    candidates.sort_by(|(a_dist, a_name), (b_dist, b_name)| {
+        a_dist.cmp(b_dist).then_with(|| a_name.cmp(b_name))
+    });
+    candidates.truncate(3);
+
+    let suggestions: Vec<String> = candidates
+        .into_iter()
+        .map(|(_, candidate)| format!("'{}'", candidate))
+        .collect();
+    Some(format!("Did you mean {}?", suggestions.join(", ")))
+}
+
+/// Pulls the closest candidate back out of a [`suggest_similar_package`]
+/// hint embedded in a missing-package diagnostic message, for the "Replace
+/// with '...'" quick fix. Returns `None` when the message carries no
+/// suggestion (the package wasn't even a plausible typo).
+fn first_missing_package_suggestion(message: &str) -> Option<&str> {
+    let after = message.split_once("Did you mean '")?.1;
+    after.split(['\'']).next()
+}
+
+/// Substring every unloaded-namespace-package diagnostic message contains,
+/// used by `code_action` to recognize which incoming `context.diagnostics`
+/// entries its "Add library() call" quick fix applies to, without
+/// re-deriving the check itself.
+const UNLOADED_NAMESPACE_PACKAGE_DIAGNOSTIC_MARKER: &str = "has not been loaded with library()";
+
+/// Emit a warning for every namespace-qualified call (`pkg::fn`) whose
+/// package is neither a base package nor loaded (directly or inherited from
+/// a parent file) at that position, analogous to rust-analyzer's
+/// `unresolved_import`. Since `pkg::fn` works in R regardless of whether
+/// `library(pkg)` was ever called, this is a readability nudge rather than a
+/// correctness check, so it stays a warning and is easy to suppress via
+/// `@lsp-allow` or `diagnostics.severityOverrides`.
+fn collect_unloaded_namespace_package_diagnostics(
     state: &WorldState,
     uri: &Url,
-    node: Node,
+    root: Node,
     text: &str,
-    directive_meta: &crate::cross_file::CrossFileMetadata,
+    meta: &crate::cross_file::CrossFileMetadata,
     diagnostics: &mut Vec<Diagnostic>,
 ) {
-    use crate::cross_file::types::byte_offset_to_utf16_column;
+    let Some(severity) = state.cross_file_config.packages_unloaded_namespace_severity else {
+        return;
+    };
+
+    let mut calls = Vec::new();
+    collect_namespace_calls(root, text, &mut calls);
+
+    for (package_node, package) in calls {
+        let line = package_node.start_position().row as u32;
+        if crate::cross_file::directive::is_line_ignored(meta, line) {
+            continue;
+        }
+        if state.package_library.is_base_package(&package) {
+            continue;
+        }
+
+        let scope = get_cross_file_scope(
+            state,
+            uri,
+            line,
+            package_node.start_position().column as u32,
+        );
+        if scope.loaded_packages.contains(&package) || scope.inherited_packages.contains(&package) {
+            continue;
+        }
+
+        // Installed-but-unconfirmed packages still get the nudge; a package
+        // the library knows doesn't exist is already reported by
+        // `collect_missing_package_diagnostics` for its own `library()` call,
+        // so skip here to avoid a duplicate warning for the same typo.
+        if state.cross_file_config.packages_enabled
+            && state.package_library_ready
+            && !state.package_library.package_exists(&package)
+        {
+            continue;
+        }
+
+        let (code, code_description) =
+            diagnostic_code(diagnostic_codes::UNLOADED_NAMESPACE_PACKAGE);
+        diagnostics.push(Diagnostic {
+            range: Range {
+                start: Position::new(line, package_node.start_position().column as u32),
+                end: Position::new(line, package_node.end_position().column as u32),
+            },
+            severity: Some(severity),
+            code,
+            code_description,
+            message: format!(
+                "Package '{}' {}",
+                package, UNLOADED_NAMESPACE_PACKAGE_DIAGNOSTIC_MARKER
+            ),
+            ..Default::default()
+        });
+    }
+}
+
+/// Collects the package-name node of every namespace-qualified call
+/// (`pkg::fn(...)`) under `node`.
+fn collect_namespace_calls<'a>(node: Node<'a>, text: &str, out: &mut Vec<(Node<'a>, String)>) {
+    if node.kind() == "call" {
+        if let Some(function_node) = node.child_by_field_name("function") {
+            if function_node.kind() == "namespace_operator" {
+                let mut cursor = function_node.walk();
+                let children: Vec<_> = function_node.children(&mut cursor).collect();
+                if let Some(package_node) = children.first() {
+                    out.push((*package_node, node_text(*package_node, text).to_string()));
+                }
+            }
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_namespace_calls(child, text, out);
+    }
+}
+
+/// Inserts a `library(pkg)` call at the top of the file, offered as the quick
+/// fix for [`UNLOADED_NAMESPACE_PACKAGE_DIAGNOSTIC_MARKER`] diagnostics.
+fn insert_library_call_edit(uri: &Url, package: &str) -> WorkspaceEdit {
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: Range {
+                start: Position::new(0, 0),
+                end: Position::new(0, 0),
+            },
+            new_text: format!("library({})\n", package),
+        }],
+    );
+    WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    }
+}
+
+/// Inserts `source("relative/path.R")` at the top of the file, offered as
+/// the quick fix for [`UNDEFINED_VARIABLE_DIAGNOSTIC_MARKER`] diagnostics
+/// when the missing name is defined at the top level of another indexed
+/// file. Mirrors `insert_library_call_edit`.
+fn insert_source_call_edit(uri: &Url, relative_path: &str) -> WorkspaceEdit {
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: Range {
+                start: Position::new(0, 0),
+                end: Position::new(0, 0),
+            },
+            new_text: format!("source(\"{}\")\n", relative_path),
+        }],
+    );
+    WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    }
+}
+
+/// Substring every unused-import diagnostic message contains, used by
+/// `code_action` to recognize which incoming `context.diagnostics` entries
+/// its "Remove unused import" quick fix applies to, without re-deriving the
+/// check itself.
+const UNUSED_LIBRARY_DIAGNOSTIC_MARKER: &str = "is imported but never used";
+
+/// Emit a hint for every `library()`/`require()` call whose package exports
+/// don't actually show up anywhere in the collected usage set, so the
+/// "Remove unused import" quick fix (see `code_action`) can offer to delete
+/// it — mirroring rust-analyzer's `remove_unused_imports` assist.
+///
+/// A usage only counts against the package if it doesn't resolve to a local
+/// binding first: a local definition of the same name shadows the package
+/// export (the same "local over package" precedence hover and goto-definition
+/// already use), so it can't be what's keeping the import alive.
+///
+/// Suppressed via `packages.sideEffectAllowlist` (for packages loaded purely
+/// for side effects, e.g. registering S3 methods) and via a trailing
+/// `# nolint` comment on the call, in addition to the usual `@lsp-ignore`.
+fn collect_unused_library_diagnostics(
+    state: &WorldState,
+    root: Node,
+    text: &str,
+    meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if !state.cross_file_config.packages_enabled || meta.library_calls.is_empty() {
+        return;
+    }
+
+    let mut usages = Vec::new();
+    collect_usages_with_context(root, text, &UsageContext::default(), &mut usages);
+    let scopes = LocalScopeTree::build(root, text);
+
+    for lib_call in &meta.library_calls {
+        if crate::cross_file::directive::is_line_ignored(meta, lib_call.line) {
+            continue;
+        }
+        if state
+            .cross_file_config
+            .packages_side_effect_allowlist
+            .iter()
+            .any(|pkg| pkg == &lib_call.package)
+        {
+            continue;
+        }
+        if line_has_nolint_comment(text, lib_call.line) {
+            continue;
+        }
+
+        // Without cached exports we can't tell whether the package is used,
+        // so stay quiet rather than risk a false positive.
+        let Some(exports) = state
+            .package_library
+            .get_cached_combined_exports(&lib_call.package)
+        else {
+            continue;
+        };
+
+        let is_used = usages.iter().any(|(name, node)| {
+            exports.contains(name) && scopes.resolve_at(node.start_position(), name).is_none()
+        });
+
+        if !is_used {
+            let (code, code_description) = diagnostic_code(diagnostic_codes::UNUSED_LIBRARY);
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position::new(lib_call.line, 0),
+                    end: Position::new(lib_call.line, lib_call.column),
+                },
+                severity: Some(DiagnosticSeverity::HINT),
+                code,
+                code_description,
+                message: format!(
+                    "Package '{}' {}",
+                    lib_call.package, UNUSED_LIBRARY_DIAGNOSTIC_MARKER
+                ),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Returns true if `line`'s trailing comment is a `# nolint`-style directive
+/// (case-insensitive, surrounding whitespace ignored) — the convention this
+/// diagnostic honors for suppressing a single call without disabling
+/// diagnostics for the whole line via `@lsp-ignore`.
+fn line_has_nolint_comment(text: &str, line: u32) -> bool {
+    let Some(line_text) = text.lines().nth(line as usize) else {
+        return false;
+    };
+    let Some(comment_start) = line_text.find('#') else {
+        return false;
+    };
+    line_text[comment_start + 1..]
+        .trim()
+        .eq_ignore_ascii_case("nolint")
+}
+
+/// Substring every out-of-scope-symbol diagnostic message contains, used by
+/// `code_action` to offer a "Qualify as `pkg::name`" quick fix when the
+/// flagged symbol also happens to be an export of a package already loaded
+/// in scope, reusing [`qualify_call_edits`] rather than re-deriving it.
+const OUT_OF_SCOPE_SYMBOL_DIAGNOSTIC_MARKER: &str = "used before source() call at line";
+
+/// Emit diagnostics for symbols defined in sourced files that are referenced
+/// earlier in the current document than the corresponding `source()` call.
+///
+/// This function:
+/// - Scans `directive_meta.sources` and collects source paths declared in the file.
+/// - Collects identifier usages (UTF-16 columns) in `node`.
+/// - For each sourced file, resolves its URI and obtains its exported symbols (preferring open documents, then cross-file index, then legacy index).
+/// - Emits a diagnostic for every usage of an exported symbol that occurs before the `source()` call (skipping lines marked ignored by directives).
+///
+/// The produced diagnostics are appended to `diagnostics` and use the configured
+/// `out_of_scope_severity` from `state.cross_file_config`.
+///
+/// # Parameters
+///
+/// - `state`: Workspace state and indexes used to resolve artifacts and configuration.
+/// - `uri`: URI of the current document being analyzed (used to resolve relative source paths).
+/// - `node`: Root AST node of the current document.
+/// - `text`: Full source text of the current document.
+/// - `directive_meta`: Cross-file directive metadata (contains `@lsp-source` / `source()` locations).
+/// - `diagnostics`: Mutable vector to receive emitted diagnostics.
+///
+/// # Examples
+///
+/// ```no_run
+/// // Collect diagnostics into `diags` for a parsed document:
+/// let mut diags = Vec::new();
+/// collect_out_of_scope_diagnostics(&state, &uri, root_node, &text, &directive_meta, &mut diags);
+/// // `diags` now contains diagnostics for symbols used before their `source()` calls.
+/// ```
+fn collect_out_of_scope_diagnostics(
+    state: &WorldState,
+    uri: &Url,
+    node: Node,
+    text: &str,
+    directive_meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    use crate::cross_file::types::byte_offset_to_utf16_column;
 
     // Get all source() calls and @lsp-source directives in this file
     let source_calls: Vec<_> = directive_meta.sources.iter().collect();
@@ -1201,32 +1963,10 @@ fn collect_out_of_scope_diagnostics(
         };
 
         // Get symbols from the sourced file
-        let source_symbols: std::collections::HashSet<String> = {
-            let get_artifacts = |target_uri: &Url| -> Option<scope::ScopeArtifacts> {
-                // Try open documents first (authoritative)
-                if let Some(doc) = state.documents.get(target_uri) {
-                    if let Some(tree) = &doc.tree {
-                        return Some(scope::compute_artifacts(target_uri, tree, &doc.text()));
-                    }
-                }
-                // Try cross-file workspace index (preferred for closed files)
-                if let Some(artifacts) = state.cross_file_workspace_index.get_artifacts(target_uri)
-                {
-                    return Some(artifacts);
-                }
-                // Fallback to legacy workspace index
-                if let Some(doc) = state.workspace_index.get(target_uri) {
-                    if let Some(tree) = &doc.tree {
-                        return Some(scope::compute_artifacts(target_uri, tree, &doc.text()));
-                    }
-                }
-                None
-            };
-
-            get_artifacts(&source_uri)
+        let source_symbols: std::collections::HashSet<String> =
+            get_chain_artifacts(state, &source_uri)
                 .map(|a| a.exported_interface.keys().cloned().collect())
-                .unwrap_or_default()
-        };
+                .unwrap_or_default();
 
         // Check for usages of these symbols before the source() call
         for (name, usage_line, usage_col, usage_node) in &usages {
@@ -1257,12 +1997,16 @@ fn collect_out_of_scope_diagnostics(
                 let end_col =
                     byte_offset_to_utf16_column(end_line_text, usage_node.end_position().column);
 
+                let (code, code_description) =
+                    diagnostic_code(diagnostic_codes::OUT_OF_SCOPE_SYMBOL);
                 diagnostics.push(Diagnostic {
                     range: Range {
                         start: Position::new(usage_node.start_position().row as u32, start_col),
                         end: Position::new(usage_node.end_position().row as u32, end_col),
                     },
                     severity: Some(state.cross_file_config.out_of_scope_severity),
+                    code,
+                    code_description,
                     message: format!(
                         "Symbol '{}' used before source() call at line {}",
                         name,
@@ -1326,15 +2070,91 @@ fn collect_identifier_usages_utf16<'a>(
     }
 }
 
-fn collect_syntax_errors(node: Node, diagnostics: &mut Vec<Diagnostic>) {
-    if node.is_error() || node.is_missing() {
+/// Shared, read-only data every [`DiagnosticHandler`] sees for the node it's
+/// currently visiting. Today that's just the source text, but it's a named
+/// struct rather than a loose `&str` parameter so a handler that needs more
+/// (e.g. `WorldState` access) can grow into it without changing every
+/// existing handler's signature.
+///
+/// Most of this file's other checks (missing packages, unused libraries,
+/// undefined variables, ...) still run as ad-hoc free functions rather than
+/// `DiagnosticHandler`s, because they need that `WorldState`/cross-file
+/// metadata this context doesn't carry yet, and/or iterate a pre-collected
+/// list (e.g. `meta.library_calls`) rather than every node in the tree.
+/// Migrating them is future work; this registry only covers checks that are
+/// pure functions of a single node and the source text.
+struct HandlerContext<'a> {
+    text: &'a str,
+}
+
+/// A single diagnostic check driven by the shared traversal in
+/// [`run_handlers`], mirroring rust-analyzer's move to one handler per lint
+/// behind a common sink. Each handler owns a stable [`DiagnosticCode`] (so
+/// the severity-config layer can enable/disable it centrally) and inspects
+/// one node at a time rather than walking the whole file on its own.
+trait DiagnosticHandler {
+    /// The code this handler reports under.
+    fn code(&self) -> DiagnosticCode;
+
+    /// Inspect a single node, pushing any diagnostics it finds onto `out`.
+    /// Handlers never recurse themselves; `run_handlers` owns the traversal.
+    fn visit(&self, node: Node, ctx: &HandlerContext, out: &mut Vec<Diagnostic>);
+}
+
+/// Walk `root` once, offering every node to each handler in `handlers`, so N
+/// independent checks don't each re-walk the whole tree. Handlers whose code
+/// is configured `off` in `overrides` are skipped entirely rather than only
+/// having their output filtered afterward.
+fn run_handlers(
+    root: Node,
+    text: &str,
+    handlers: &[&dyn DiagnosticHandler],
+    overrides: &crate::cross_file::DiagnosticSeverityConfig,
+    out: &mut Vec<Diagnostic>,
+) {
+    let active: Vec<&dyn DiagnosticHandler> = handlers
+        .iter()
+        .copied()
+        .filter(|handler| overrides.get(handler.code()) != Some(DiagnosticSeverityOverride::Off))
+        .collect();
+    walk_handlers(root, &HandlerContext { text }, &active, out);
+}
+
+fn walk_handlers(
+    node: Node,
+    ctx: &HandlerContext,
+    handlers: &[&dyn DiagnosticHandler],
+    out: &mut Vec<Diagnostic>,
+) {
+    for handler in handlers {
+        handler.visit(node, ctx, out);
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        walk_handlers(child, ctx, handlers, out);
+    }
+}
+
+/// Flags nodes tree-sitter-r could not parse.
+struct SyntaxErrorHandler;
+
+impl DiagnosticHandler for SyntaxErrorHandler {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::SyntaxError
+    }
+
+    fn visit(&self, node: Node, _ctx: &HandlerContext, out: &mut Vec<Diagnostic>) {
+        if !node.is_error() && !node.is_missing() {
+            return;
+        }
         let message = if node.is_missing() {
             format!("Missing {}", node.kind())
         } else {
             "Syntax error".to_string()
         };
 
-        diagnostics.push(Diagnostic {
+        let (code, code_description) = diagnostic_code(diagnostic_codes::SYNTAX_ERROR);
+        out.push(Diagnostic {
             range: Range {
                 start: Position::new(
                     node.start_position().row as u32,
@@ -1346,19 +2166,28 @@ fn collect_syntax_errors(node: Node, diagnostics: &mut Vec<Diagnostic>) {
                 ),
             },
             severity: Some(DiagnosticSeverity::ERROR),
+            code,
+            code_description,
             message,
             ..Default::default()
         });
     }
+}
 
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        collect_syntax_errors(child, diagnostics);
-    }
+/// Runs [`SyntaxErrorHandler`] through the shared traversal; kept as a free
+/// function since a handful of call sites still want to check syntax errors
+/// in isolation.
+fn collect_syntax_errors(node: Node, diagnostics: &mut Vec<Diagnostic>) {
+    walk_handlers(
+        node,
+        &HandlerContext { text: "" },
+        &[&SyntaxErrorHandler as &dyn DiagnosticHandler],
+        diagnostics,
+    );
 }
 
-/// Detect and report diagnostics for `else` keywords that appear on a new line
-/// after the closing brace of an `if` block.
+/// Detects `else` keywords that appear on a new line after the closing brace
+/// of an `if` block.
 ///
 /// In R, `else` must appear on the same line as the closing `}` of the `if` block.
 /// When `else` is on a new line, R treats the `if` as complete and `else` becomes
@@ -1368,16 +2197,11 @@ fn collect_syntax_errors(node: Node, diagnostics: &mut Vec<Diagnostic>) {
 ///
 /// When `else` appears on a new line after an `if` block, tree-sitter-r parses it
 /// as an `identifier` node (not an `"else"` keyword node) that is a sibling of the
-/// `if_statement` in the parent node. This function detects this pattern by:
+/// `if_statement` in the parent node. This handler detects that pattern by:
 /// 1. Finding `identifier` nodes with text "else"
 /// 2. Checking if the preceding sibling is an `if_statement`
 /// 3. Comparing line numbers to determine if `else` is on a new line
 ///
-/// # Arguments
-/// * `node` - The root AST node to traverse
-/// * `text` - The source text for extracting node content
-/// * `diagnostics` - Vector to append diagnostics to
-///
 /// # Examples
 ///
 /// Invalid (emits diagnostic):
@@ -1392,95 +2216,144 @@ fn collect_syntax_errors(node: Node, diagnostics: &mut Vec<Diagnostic>) {
 /// ```
 ///
 /// **Validates: Requirements 1.1, 1.2, 1.3, 4.2**
-fn collect_else_newline_errors(node: Node, text: &str, diagnostics: &mut Vec<Diagnostic>) {
-    // Case 1: Check if this node is an identifier with text "else"
-    // When else is on a new line at the top level, tree-sitter parses it as an identifier
-    if node.kind() == "identifier" {
-        let node_text_str = node_text(node, text);
-        if node_text_str == "else" {
-            // Skip if this node is already marked as an error by tree-sitter
-            // to avoid duplicate diagnostics (Requirement 4.2)
-            if node.is_error() {
-                // Already handled by collect_syntax_errors
-            } else if let Some(parent) = node.parent() {
-                if parent.is_error() {
-                    // Parent is error, skip to avoid duplicate
-                } else {
-                    // Check if there's a preceding if_statement (skipping over comments)
-                    // This indicates an orphaned else on a new line
-                    // Validates: Requirement 5.3 - comments between `}` and `else` should not
-                    // prevent detection when else is on a new line
-                    let mut prev = node.prev_sibling();
-                    while let Some(sibling) = prev {
-                        if sibling.kind() == "comment" {
-                            // Skip comments and continue looking
-                            prev = sibling.prev_sibling();
-                        } else if sibling.kind() == "if_statement" {
-                            // Found the preceding if_statement
-                            let brace_line = find_closing_brace_line(&sibling, text);
-                            let else_start_line = node.start_position().row;
-
-                            if let Some(brace_line) = brace_line {
-                                // If else is on a different line than the closing brace, emit diagnostic
-                                if else_start_line > brace_line {
-                                    emit_else_newline_diagnostic(node, diagnostics);
+struct ElseNewlineHandler<'u> {
+    uri: &'u Url,
+}
+
+impl<'u> DiagnosticHandler for ElseNewlineHandler<'u> {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::ElseOnNewLine
+    }
+
+    fn visit(&self, node: Node, ctx: &HandlerContext, out: &mut Vec<Diagnostic>) {
+        let text = ctx.text;
+        // Case 1: Check if this node is an identifier with text "else"
+        // When else is on a new line at the top level, tree-sitter parses it as an identifier
+        if node.kind() == "identifier" {
+            let node_text_str = node_text(node, text);
+            if node_text_str == "else" {
+                // Skip if this node is already marked as an error by tree-sitter
+                // to avoid duplicate diagnostics (Requirement 4.2)
+                if node.is_error() {
+                    // Already handled by SyntaxErrorHandler
+                } else if let Some(parent) = node.parent() {
+                    if parent.is_error() {
+                        // Parent is error, skip to avoid duplicate
+                    } else {
+                        // Check if there's a preceding if_statement (skipping over comments)
+                        // This indicates an orphaned else on a new line
+                        // Validates: Requirement 5.3 - comments between `}` and `else` should not
+                        // prevent detection when else is on a new line
+                        let mut prev = node.prev_sibling();
+                        while let Some(sibling) = prev {
+                            if sibling.kind() == "comment" {
+                                // Skip comments and continue looking
+                                prev = sibling.prev_sibling();
+                            } else if sibling.kind() == "if_statement" {
+                                // Found the preceding if_statement
+                                let brace_range = find_closing_brace_range(&sibling, text);
+                                let else_start_line = node.start_position().row;
+
+                                if let Some(brace_range) = brace_range {
+                                    // If else is on a different line than the closing brace, emit diagnostic
+                                    if else_start_line > brace_range.end.line as usize {
+                                        emit_else_newline_diagnostic(
+                                            node,
+                                            self.uri,
+                                            Some(brace_range),
+                                            out,
+                                        );
+                                    }
+                                } else {
+                                    // Fallback: use the end line of the if_statement
+                                    let if_end_line = sibling.end_position().row;
+                                    if else_start_line > if_end_line {
+                                        emit_else_newline_diagnostic(node, self.uri, None, out);
+                                    }
                                 }
+                                break;
                             } else {
-                                // Fallback: use the end line of the if_statement
-                                let if_end_line = sibling.end_position().row;
-                                if else_start_line > if_end_line {
-                                    emit_else_newline_diagnostic(node, diagnostics);
-                                }
+                                // Found something other than comment or if_statement, stop looking
+                                break;
                             }
-                            break;
-                        } else {
-                            // Found something other than comment or if_statement, stop looking
-                            break;
                         }
                     }
                 }
             }
         }
-    }
 
-    // Case 2: Check if this is an if_statement with an else clause
-    // When else is on a new line inside a braced expression (nested), tree-sitter still parses
-    // it as part of the if_statement with an "else" keyword node
-    // Validates: Requirement 2.5 - nested if-else detection
-    if node.kind() == "if_statement" {
-        // Look for the "else" keyword child and the consequence (braced_expression)
-        let mut cursor = node.walk();
-        let mut consequence_end_line: Option<usize> = None;
-        let mut else_node: Option<Node> = None;
+        // Case 2: Check if this is an if_statement with an else clause
+        // When else is on a new line inside a braced expression (nested), tree-sitter still parses
+        // it as part of the if_statement with an "else" keyword node
+        // Validates: Requirement 2.5 - nested if-else detection
+        if node.kind() == "if_statement" {
+            // Look for the "else" keyword child and the consequence (braced_expression)
+            let mut cursor = node.walk();
+            let mut consequence: Option<Node> = None;
+            let mut else_node: Option<Node> = None;
 
-        for child in node.children(&mut cursor) {
-            if child.kind() == "braced_expression" && else_node.is_none() {
-                // This is the consequence (the first braced_expression before else)
-                consequence_end_line = Some(child.end_position().row);
-            } else if child.kind() == "else" {
-                else_node = Some(child);
-                // Don't break - we want to capture the consequence before the else
+            for child in node.children(&mut cursor) {
+                if child.kind() == "braced_expression" && else_node.is_none() {
+                    // This is the consequence (the first braced_expression before else)
+                    consequence = Some(child);
+                } else if child.kind() == "else" {
+                    else_node = Some(child);
+                    // Don't break - we want to capture the consequence before the else
+                }
             }
-        }
 
-        // If we found both a consequence and an else, check line positions
-        if let (Some(brace_line), Some(else_kw)) = (consequence_end_line, else_node) {
-            let else_start_line = else_kw.start_position().row;
-            if else_start_line > brace_line {
-                emit_else_newline_diagnostic(else_kw, diagnostics);
+            // If we found both a consequence and an else, check line positions
+            if let (Some(consequence), Some(else_kw)) = (consequence, else_node) {
+                let brace_range = closing_brace_range(&consequence);
+                let else_start_line = else_kw.start_position().row;
+                if else_start_line > brace_range.end.line as usize {
+                    emit_else_newline_diagnostic(else_kw, self.uri, Some(brace_range), out);
+                }
             }
         }
     }
+}
 
-    // Recurse into children
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        collect_else_newline_errors(child, text, diagnostics);
-    }
+/// Runs [`ElseNewlineHandler`] through the shared traversal; kept as a free
+/// function since many existing call sites check this in isolation.
+fn collect_else_newline_errors(
+    node: Node,
+    text: &str,
+    uri: &Url,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let handler = ElseNewlineHandler { uri };
+    walk_handlers(
+        node,
+        &HandlerContext { text },
+        &[&handler as &dyn DiagnosticHandler],
+        diagnostics,
+    );
 }
 
-/// Emit a diagnostic for an orphaned else keyword
-fn emit_else_newline_diagnostic(node: Node, diagnostics: &mut Vec<Diagnostic>) {
+/// Substring every orphaned-else diagnostic message contains, used by
+/// `code_action` to recognize which incoming `context.diagnostics` entries
+/// its "Move 'else' onto the same line" quick fix applies to, without
+/// re-deriving the check itself.
+const ELSE_NEWLINE_DIAGNOSTIC_MARKER: &str = "must appear on the same line as the closing '}'";
+
+/// Emit a diagnostic for an orphaned else keyword, attaching `brace_range` (the
+/// preceding `if` block's closing `}`, when known) as related information so
+/// clients can point the user at both ends of the problem.
+fn emit_else_newline_diagnostic(
+    node: Node,
+    uri: &Url,
+    brace_range: Option<Range>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let (code, code_description) = diagnostic_code(diagnostic_codes::ELSE_ON_NEW_LINE);
+    let related_information = brace_range.map(|range| {
+        vec![related_information_entry(
+            uri.clone(),
+            range,
+            "block closed here; move 'else' to this line",
+        )]
+    });
     diagnostics.push(Diagnostic {
         range: Range {
             start: Position::new(
@@ -1493,41 +2366,81 @@ fn emit_else_newline_diagnostic(node: Node, diagnostics: &mut Vec<Diagnostic>) {
             ),
         },
         severity: Some(DiagnosticSeverity::ERROR),
-        message: "In R, 'else' must appear on the same line as the closing '}' of the if block"
-            .to_string(),
+        code,
+        code_description,
+        message: format!("In R, 'else' {ELSE_NEWLINE_DIAGNOSTIC_MARKER} of the if block"),
+        related_information,
         ..Default::default()
     });
 }
 
-/// Helper function to find the line number of the closing brace in a node.
-/// Returns the line number of the last "}" in the node, or None if not found.
-fn find_closing_brace_line(node: &Node, text: &str) -> Option<usize> {
-    // For if_statement, we need to find the consequence (the braced_expression after the condition)
-    // The consequence is the last braced_expression child that is NOT the alternative
+/// Returns the `Range` of the closing `}` character of a node's brace block, or
+/// `None` if no brace block is found.
+///
+/// For an `if_statement`, the brace block is the first `braced_expression`
+/// child (the consequence), not the alternative (which would follow the
+/// `else` keyword). Falls back to treating `node` itself as the brace block
+/// when its own text ends with `}` (e.g. when `node` already *is* the
+/// `braced_expression`).
+fn find_closing_brace_range(node: &Node, text: &str) -> Option<Range> {
     let mut cursor = node.walk();
-    let mut last_brace_line = None;
 
     for child in node.children(&mut cursor) {
-        // Look for braced_expression which contains the closing brace
+        // Look for braced_expression which contains the closing brace.
+        // Don't keep looking - we want the FIRST braced_expression (the consequence),
+        // not the alternative (which would be after the else keyword).
         if child.kind() == "braced_expression" {
-            // The end position of braced_expression is where the "}" is
-            last_brace_line = Some(child.end_position().row);
-            // Don't break - we want the FIRST braced_expression (the consequence),
-            // not the alternative (which would be after the else keyword)
-            // But since we're looking at if_statement without else, there's only one
-            break;
+            return Some(closing_brace_range(&child));
         }
     }
 
     // If we didn't find a braced_expression, check if the node's text ends with "}"
-    if last_brace_line.is_none() {
-        let node_text_str = node_text(*node, text);
-        if node_text_str.trim_end().ends_with('}') {
-            return Some(node.end_position().row);
-        }
+    let node_text_str = node_text(*node, text);
+    if node_text_str.trim_end().ends_with('}') {
+        return Some(closing_brace_range(node));
+    }
+
+    None
+}
+
+/// Returns the `Range` of the last character of `node`, which callers must have
+/// already confirmed is a `}`.
+fn closing_brace_range(node: &Node) -> Range {
+    let end = node.end_position();
+    if end.column == 0 {
+        // The "}" is the only character on a line that otherwise starts at
+        // column 0 (e.g. tree-sitter reports the end position on the
+        // following line); there's no preceding column to point at, so fall
+        // back to a zero-width range at the reported end position.
+        let position = Position::new(end.row as u32, 0);
+        return Range {
+            start: position,
+            end: position,
+        };
+    }
+    Range {
+        start: Position::new(end.row as u32, (end.column - 1) as u32),
+        end: Position::new(end.row as u32, end.column as u32),
     }
+}
 
-    last_brace_line
+/// Builds the `UsageContext` that governs NSE argument-skipping for undefined-variable
+/// diagnostics, merging the built-in default allowlist with the user-configured
+/// additions in `state.cross_file_config` and threading through the blanket-skip mode.
+fn usage_context_for(state: &WorldState) -> UsageContext {
+    let mut nse_allowlist = default_nse_allowlist();
+    nse_allowlist.extend(
+        state
+            .cross_file_config
+            .undefined_variables_nse_allowlist
+            .iter()
+            .cloned(),
+    );
+    UsageContext {
+        nse_allowlist: Arc::new(nse_allowlist),
+        blanket_nse_skip: state.cross_file_config.undefined_variables_nse_blanket_skip,
+        ..UsageContext::default()
+    }
 }
 
 /// Report undefined variable usages in a document using position-aware cross-file scope.
@@ -1564,7 +2477,7 @@ pub(crate) fn collect_undefined_variables_position_aware(
     let mut used: Vec<(String, Node)> = Vec::new();
 
     // Second pass: collect all usages with NSE-aware context
-    collect_usages_with_context(node, text, &UsageContext::default(), &mut used);
+    collect_usages_with_context(node, text, &usage_context_for(state), &mut used);
 
     // Report undefined variables with position-aware cross-file scope
     for (name, usage_node) in used {
@@ -1604,18 +2517,18 @@ pub(crate) fn collect_undefined_variables_position_aware(
             continue;
         }
 
+        // Build position-aware package list: inherited packages + locally loaded packages
+        // Requirements 5.1, 5.2: Inherited packages from parent files
+        // Requirements 8.1, 8.3: Locally loaded packages before this position
+        let position_aware_packages: Vec<String> = scope
+            .inherited_packages
+            .iter()
+            .chain(scope.loaded_packages.iter())
+            .cloned()
+            .collect();
+
         // Check package exports only if packages feature is enabled and library is ready
         if state.cross_file_config.packages_enabled && state.package_library_ready {
-            // Build position-aware package list: inherited packages + locally loaded packages
-            // Requirements 5.1, 5.2: Inherited packages from parent files
-            // Requirements 8.1, 8.3: Locally loaded packages before this position
-            let position_aware_packages: Vec<String> = scope
-                .inherited_packages
-                .iter()
-                .chain(scope.loaded_packages.iter())
-                .cloned()
-                .collect();
-
             // Check if symbol is exported by any package loaded at this position
             if is_package_export(&name, &position_aware_packages, package_library) {
                 continue;
@@ -1636,24 +2549,103 @@ pub(crate) fn collect_undefined_variables_position_aware(
             byte_offset_to_utf16_column(start_line_text, usage_node.start_position().column);
         let end_col = byte_offset_to_utf16_column(end_line_text, usage_node.end_position().column);
 
+        // Suggest the closest known identifier, from the same candidate
+        // pool completion() aggregates (in-scope symbols plus, when
+        // enabled, package exports), for a "Did you mean ...?" hint.
+        let package_export_names: Vec<String> =
+            if state.cross_file_config.packages_enabled && state.package_library_ready {
+                package_library
+                    .get_exports_for_completions(&position_aware_packages)
+                    .into_keys()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        let candidates = scope
+            .symbols
+            .keys()
+            .map(|k| k.as_ref())
+            .chain(package_export_names.iter().map(|s| s.as_str()));
+        let message = match suggest_similar_identifier(&name, candidates) {
+            Some(suggestion) => format!(
+                "Undefined variable: {}. Did you mean `{}`?",
+                name, suggestion
+            ),
+            None => format!("Undefined variable: {}", name),
+        };
+
+        let (code, code_description) = diagnostic_code(diagnostic_codes::UNDEFINED_VARIABLE);
         diagnostics.push(Diagnostic {
             range: Range {
                 start: Position::new(usage_node.start_position().row as u32, start_col),
                 end: Position::new(usage_node.end_position().row as u32, end_col),
             },
             severity: Some(DiagnosticSeverity::WARNING),
-            message: format!("Undefined variable: {}", name),
+            code,
+            code_description,
+            message,
             ..Default::default()
         });
     }
 }
 
-/// Emit diagnostics for identifiers that are used but not defined, built-in, imported, exported by a loaded package, or available from cross-file symbols.
-///
-/// This function performs a two-pass analysis on the provided syntax `node`:
-/// it collects all defined identifiers, then collects usages (respecting NSE/context rules),
-/// and pushes a `Diagnostic` with severity `Warning` for each usage that is not found in any of:
-/// - the local definitions in the current tree,
+/// Finds the closest known identifier to `name` among `candidates`, to
+/// suggest as a "Did you mean ...?" hint for an undefined-variable
+/// diagnostic. Tries a case-insensitive exact match first (catches plain
+/// capitalization typos), then falls back to the candidate with the
+/// smallest Levenshtein distance, accepted only if that distance is within
+/// `max(name.len(), candidate.len()) / 3` (at least 1) -- close enough to
+/// plausibly be a typo rather than an unrelated identifier. Returns `None`
+/// when nothing is close enough.
+fn suggest_similar_identifier<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let mut case_insensitive_match = None;
+    let mut best: Option<(&str, usize)> = None;
+
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        if case_insensitive_match.is_none() && candidate.eq_ignore_ascii_case(name) {
+            case_insensitive_match = Some(candidate);
+            continue;
+        }
+        let distance = levenshtein_distance(name, candidate);
+        if best.map_or(true, |(_, best_distance)| distance < best_distance) {
+            best = Some((candidate, distance));
+        }
+    }
+
+    if let Some(exact) = case_insensitive_match {
+        return Some(exact);
+    }
+
+    let (candidate, distance) = best?;
+    let threshold = (name.len().max(candidate.len()) / 3).max(1);
+    (distance <= threshold).then_some(candidate)
+}
+
+/// Splits an undefined-variable diagnostic message's post-marker remainder
+/// (everything after `"Undefined variable: "`) into the bare identifier and
+/// its `Did you mean \`...\`?` suggestion, if one was attached.
+fn split_did_you_mean_suggestion(rest: &str) -> (&str, Option<&str>) {
+    let Some((name, after)) = rest.split_once(". Did you mean `") else {
+        return (rest, None);
+    };
+    match after.strip_suffix("`?") {
+        Some(suggestion) => (name, Some(suggestion)),
+        None => (rest, None),
+    }
+}
+
+/// Emit diagnostics for identifiers that are used but not defined, built-in, imported, exported by a loaded package, or available from cross-file symbols.
+///
+/// This function performs a two-pass analysis on the provided syntax `node`:
+/// it collects all defined identifiers, then collects usages (respecting NSE/context rules),
+/// and pushes a `Diagnostic` with severity `Warning` for each usage that is not found in any of:
+/// - the local definitions in the current tree,
 /// - the set of builtins,
 /// - symbols exported by any loaded package (via `package_library` and `loaded_packages`),
 /// - names imported into the workspace (`workspace_imports`),
@@ -1702,6 +2694,7 @@ fn collect_undefined_variables(
             && !workspace_imports.contains(&name)
             && !cross_file_symbols.contains_key(&name)
         {
+            let (code, code_description) = diagnostic_code(diagnostic_codes::UNDEFINED_VARIABLE);
             diagnostics.push(Diagnostic {
                 range: Range {
                     start: Position::new(
@@ -1714,6 +2707,8 @@ fn collect_undefined_variables(
                     ),
                 },
                 severity: Some(DiagnosticSeverity::WARNING),
+                code,
+                code_description,
                 message: format!("Undefined variable: {}", name),
                 ..Default::default()
             });
@@ -1768,12 +2763,76 @@ fn collect_parameters(node: Node, text: &str, defined: &mut std::collections::Ha
 }
 
 /// Context for tracking NSE-related state during AST traversal
-#[derive(Clone, Default)]
+#[derive(Clone)]
 struct UsageContext {
     /// True when inside a formula expression (~ operator)
     in_formula: bool,
     /// True when inside the arguments of a call-like node (call, subset, subset2)
     in_call_like_arguments: bool,
+    /// Function names (bare, e.g. `subset`, or namespaced, e.g. `dplyr::mutate`)
+    /// whose arguments should be treated as non-standard-evaluation and skipped.
+    /// `subset`/`subset2` bracket indexing is always skipped and doesn't consult
+    /// this list; it only gates plain `call` nodes.
+    nse_allowlist: Arc<std::collections::HashSet<String>>,
+    /// When true, every call's arguments are skipped regardless of `nse_allowlist`
+    /// (the old blanket behavior, for users who want zero false positives).
+    blanket_nse_skip: bool,
+}
+
+impl Default for UsageContext {
+    fn default() -> Self {
+        Self {
+            in_formula: false,
+            in_call_like_arguments: false,
+            nse_allowlist: Arc::new(default_nse_allowlist()),
+            blanket_nse_skip: false,
+        }
+    }
+}
+
+/// The built-in set of known non-standard-evaluation functions whose arguments
+/// are skipped by default, before any user-configured additions are merged in.
+fn default_nse_allowlist() -> std::collections::HashSet<String> {
+    [
+        "subset",
+        "with",
+        "within",
+        "transform",
+        "dplyr::filter",
+        "dplyr::mutate",
+        "dplyr::summarise",
+        "dplyr::summarize",
+        "dplyr::arrange",
+        "dplyr::select",
+        "dplyr::group_by",
+        "aes",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// Returns the name a call's `function` node is invoked as, for allowlist lookups:
+/// a bare identifier (`subset`) or a namespaced call (`dplyr::mutate`). `None` for
+/// anything else (e.g. calling through a variable holding a function).
+fn call_function_name(function_node: Node, text: &str) -> Option<String> {
+    match function_node.kind() {
+        "identifier" => Some(node_text(function_node, text).to_string()),
+        "namespace_operator" => {
+            let mut cursor = function_node.walk();
+            let children: Vec<_> = function_node.children(&mut cursor).collect();
+            if children.len() >= 3 {
+                Some(format!(
+                    "{}::{}",
+                    node_text(children[0], text),
+                    node_text(children[2], text)
+                ))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
 }
 
 /// Legacy version of collect_usages without NSE context tracking.
@@ -1814,56 +2873,106 @@ fn collect_usages<'a>(node: Node<'a>, text: &str, used: &mut Vec<(String, Node<'
     }
 }
 
-/// Context-aware version of collect_usages that tracks NSE-related state during AST traversal.
-/// This function skips undefined variable checks in contexts where R uses non-standard evaluation.
-fn collect_usages_with_context<'a>(
-    node: Node<'a>,
-    text: &str,
-    context: &UsageContext,
-    used: &mut Vec<(String, Node<'a>)>,
-) {
-    if node.kind() == "identifier" {
-        // Skip if we're in a formula or call-like arguments context
-        if context.in_formula || context.in_call_like_arguments {
-            return;
-        }
+/// The role a single `identifier` occurrence plays, shared by every consumer
+/// that needs to tell a genuine reference to a binding from a syntactically
+/// identical but semantically unrelated name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdentifierRole {
+    /// LHS of an assignment (`<-`, `=`, `<<-`) — this occurrence defines the
+    /// binding rather than reading it.
+    Definition,
+    /// A real read of the binding: counts toward "is this defined?" checks
+    /// and is safe to rename.
+    Usage,
+    /// Same name, different meaning — a named argument, an NSE call argument,
+    /// a formula term, or the RHS of `$`/`@`. Never a usage of the binding,
+    /// so diagnostics must not flag it and rename must not touch it.
+    Skipped,
+}
 
-        // Skip if this is the LHS of an assignment
-        if let Some(parent) = node.parent() {
-            if parent.kind() == "binary_operator" {
-                let mut cursor = parent.walk();
-                let children: Vec<_> = parent.children(&mut cursor).collect();
-                if children.len() >= 2 && children[0].id() == node.id() {
-                    let op = children[1];
-                    let op_text = node_text(op, text);
-                    if matches!(op_text, "<-" | "=" | "<<-") {
-                        return; // Skip LHS of assignment
-                    }
+/// Classifies a single `identifier` node per `IdentifierRole`, using the same
+/// NSE-exemption rules `collect_usages_with_context` has always applied.
+/// Factored out so diagnostics (which only care about `Usage`) and rename
+/// (which also needs `Definition`, to rename every assignment, and must
+/// refuse `Skipped` positions) can't drift apart.
+fn classify_identifier(node: Node, text: &str, context: &UsageContext) -> IdentifierRole {
+    if context.in_formula || context.in_call_like_arguments {
+        return IdentifierRole::Skipped;
+    }
+
+    if let Some(parent) = node.parent() {
+        if parent.kind() == "binary_operator" {
+            let mut cursor = parent.walk();
+            let children: Vec<_> = parent.children(&mut cursor).collect();
+            if children.len() >= 2 && children[0].id() == node.id() {
+                let op = children[1];
+                let op_text = node_text(op, text);
+                if matches!(op_text, "<-" | "=" | "<<-") {
+                    return IdentifierRole::Definition;
                 }
             }
+        }
 
-            // Skip if this is a named argument (e.g., n = 1 in readLines(..., n = 1))
-            if parent.kind() == "argument" {
-                if let Some(name_node) = parent.child_by_field_name("name") {
-                    if name_node.id() == node.id() {
-                        return; // Skip argument names
-                    }
+        // Named argument (e.g., n = 1 in readLines(..., n = 1))
+        if parent.kind() == "argument" {
+            if let Some(name_node) = parent.child_by_field_name("name") {
+                if name_node.id() == node.id() {
+                    return IdentifierRole::Skipped;
                 }
             }
+        }
 
-            // Skip if this is the RHS of an extract operator ($ or @)
-            // e.g., df$column or obj@slot - we don't want to check if column/slot is defined
-            // The LHS (df, obj) should still be checked for undefined variables
-            if parent.kind() == "extract_operator" {
-                if let Some(rhs_node) = parent.child_by_field_name("rhs") {
-                    if rhs_node.id() == node.id() {
-                        return; // Skip RHS of extract operator
-                    }
+        // RHS of an extract operator ($ or @), e.g. df$column or obj@slot -
+        // the LHS (df, obj) is still a usage, but column/slot isn't a
+        // variable reference at all.
+        if parent.kind() == "extract_operator" {
+            if let Some(rhs_node) = parent.child_by_field_name("rhs") {
+                if rhs_node.id() == node.id() {
+                    return IdentifierRole::Skipped;
                 }
             }
         }
+    }
 
-        used.push((node_text(node, text).to_string(), node));
+    IdentifierRole::Usage
+}
+
+/// Context-aware version of collect_usages that tracks NSE-related state during AST traversal.
+/// This function skips undefined variable checks in contexts where R uses non-standard evaluation.
+///
+/// Thin wrapper over `collect_identifiers_with_roles` that keeps the original
+/// "usages only" signature every existing call site (diagnostics, hover,
+/// goto-definition, ...) already depends on.
+fn collect_usages_with_context<'a>(
+    node: Node<'a>,
+    text: &str,
+    context: &UsageContext,
+    used: &mut Vec<(String, Node<'a>)>,
+) {
+    let mut identifiers = Vec::new();
+    collect_identifiers_with_roles(node, text, context, &mut identifiers);
+    used.extend(
+        identifiers
+            .into_iter()
+            .filter(|(_, _, role)| *role == IdentifierRole::Usage)
+            .map(|(name, node, _)| (name, node)),
+    );
+}
+
+/// Walks `node`, classifying every `identifier` it finds via `classify_identifier`
+/// and recording `(name, node, role)` for each — the shared traversal consumed by
+/// both `collect_usages_with_context` (diagnostics, which only want `Usage`) and
+/// rename (which also needs `Definition`, to rename every assignment, while still
+/// refusing to touch `Skipped` positions).
+fn collect_identifiers_with_roles<'a>(
+    node: Node<'a>,
+    text: &str,
+    context: &UsageContext,
+    out: &mut Vec<(String, Node<'a>, IdentifierRole)>,
+) {
+    if node.kind() == "identifier" {
+        let role = classify_identifier(node, text, context);
+        out.push((node_text(node, text).to_string(), node, role));
     }
 
     // Check if we're entering a formula expression (~ operator)
@@ -1914,21 +3023,36 @@ fn collect_usages_with_context<'a>(
         if let Some(function_node) = node.child_by_field_name("function") {
             // The function field should NOT have in_call_like_arguments set
             // We still want to check if the function name is defined
-            collect_usages_with_context(function_node, text, &base_context, used);
+            collect_identifiers_with_roles(function_node, text, &base_context, out);
         }
         if let Some(arguments_node) = node.child_by_field_name("arguments") {
-            // The arguments field SHOULD have in_call_like_arguments set
-            let args_context = UsageContext {
-                in_call_like_arguments: true,
-                ..base_context.clone()
+            // `subset`/`subset2` (bracket indexing) are always treated as NSE.
+            // Plain `call` nodes only skip their arguments when the function
+            // being called is blanket-allowed or on the NSE allowlist -
+            // ordinary calls like `mean(typo_var)` get their arguments checked.
+            let treat_as_nse = match node.kind() {
+                "subset" | "subset2" => true,
+                _ => node.child_by_field_name("function").is_some_and(|f| {
+                    base_context.blanket_nse_skip
+                        || call_function_name(f, text)
+                            .is_some_and(|name| base_context.nse_allowlist.contains(&name))
+                }),
+            };
+            let args_context = if treat_as_nse {
+                UsageContext {
+                    in_call_like_arguments: true,
+                    ..base_context.clone()
+                }
+            } else {
+                base_context.clone()
             };
-            collect_usages_with_context(arguments_node, text, &args_context, used);
+            collect_identifiers_with_roles(arguments_node, text, &args_context, out);
         }
     } else {
         // For non-call-like nodes, recurse normally
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
-            collect_usages_with_context(child, text, &base_context, used);
+            collect_identifiers_with_roles(child, text, &base_context, out);
         }
     }
 }
@@ -2026,8 +3150,13 @@ pub fn completion(state: &WorldState, uri: &Url, position: Position) -> Option<C
         let metadata = match file_path_context {
             crate::file_path_intellisense::FilePathContext::SourceCall { .. } => {
                 // Use get_enriched_metadata to get metadata with inherited_working_directory
-                // from parent files, not just the current file's directives
-                state.get_enriched_metadata(uri).unwrap_or_default()
+                // from parent files, not just the current file's directives.
+                // `doc` above is already the guard for `uri`, so pass it through
+                // rather than calling get_enriched_metadata, which would re-lock
+                // `documents` for the same URI and deadlock against it.
+                state
+                    .get_enriched_metadata_with_document(uri, Some(&*doc))
+                    .unwrap_or_default()
             }
             _ => Default::default(),
         };
@@ -2058,6 +3187,25 @@ pub fn completion(state: &WorldState, uri: &Url, position: Position) -> Option<C
     let point = Point::new(position.line as usize, position.character as usize);
     let node = tree.root_node().descendant_for_point_range(point, point)?;
 
+    // Completing the RHS of `$`/`[[` names a column/element, not a global
+    // symbol -- exactly the position `classify_identifier` marks `Skipped`
+    // for diagnostics/rename. Branch into a dedicated extract-completion mode
+    // instead of polluting the list with unrelated keywords/package exports.
+    let line_text = text.lines().nth(position.line as usize).unwrap_or("");
+    let cursor_byte = utf16_column_to_byte_offset(line_text, position.character);
+    if let Some(var_name) = detect_extract_completion_context(node, &text, line_text, cursor_byte) {
+        let items = infer_data_frame_columns(tree.root_node(), &text, &var_name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name,
+                kind: Some(CompletionItemKind::FIELD),
+                ..Default::default()
+            })
+            .collect();
+        return Some(CompletionResponse::Array(items));
+    }
+
     let mut items = Vec::new();
     let mut seen_names = std::collections::HashSet::new();
 
@@ -2067,34 +3215,9 @@ pub fn completion(state: &WorldState, uri: &Url, position: Position) -> Option<C
         return Some(CompletionResponse::Array(items));
     }
 
-    // Add R keywords
-    let keywords = [
-        "if",
-        "else",
-        "repeat",
-        "while",
-        "function",
-        "for",
-        "in",
-        "next",
-        "break",
-        "TRUE",
-        "FALSE",
-        "NULL",
-        "Inf",
-        "NaN",
-        "NA",
-        "NA_integer_",
-        "NA_real_",
-        "NA_complex_",
-        "NA_character_",
-        "library",
-        "require",
-        "return",
-        "print",
-    ];
-
-    for kw in keywords {
+    // Add R reserved-word keywords, filtered to ones that are syntactically
+    // valid at the cursor (Requirement: context-sensitive keyword completion).
+    for kw in context_sensitive_keywords(node, line_text, cursor_byte) {
         items.push(CompletionItem {
             label: kw.to_string(),
             kind: Some(CompletionItemKind::KEYWORD),
@@ -2103,8 +3226,19 @@ pub fn completion(state: &WorldState, uri: &Url, position: Position) -> Option<C
         seen_names.insert(kw.to_string());
     }
 
+    // These are ordinary functions, not reserved words, so they're always
+    // offered wherever a call/value is expected rather than context-filtered.
+    for builtin in ["library", "require", "return", "print"] {
+        items.push(CompletionItem {
+            label: builtin.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            ..Default::default()
+        });
+        seen_names.insert(builtin.to_string());
+    }
+
     // Add symbols from current document (local definitions take precedence)
-    collect_document_completions(tree.root_node(), &text, &mut items, &mut seen_names);
+    collect_document_completions(tree.root_node(), &text, uri, &mut items, &mut seen_names);
 
     // Get scope at cursor position for package exports
     // Requirements 9.1, 9.2: Add package exports to completions with package attribution
@@ -2128,17 +3262,28 @@ pub fn completion(state: &WorldState, uri: &Url, position: Position) -> Option<C
             .get_exports_for_completions(&all_packages);
         for (export_name, package_names) in package_exports {
             if seen_names.contains(&export_name) {
+                // A local definition shadows this export. Record which
+                // package(s) it shadows so `completion_item_resolve` can
+                // surface that without re-walking the scope.
+                if let Some(local_item) = items.iter_mut().find(|item| item.label == export_name) {
+                    attach_shadowed_packages(local_item, &package_names);
+                }
                 continue; // Local definitions take precedence
             }
             seen_names.insert(export_name.clone());
 
             // Requirement 9.3: Show all packages that export this symbol
-            for package_name in package_names {
+            for package_name in &package_names {
                 // Requirement 9.2: Include package name in detail field (e.g., "{dplyr}")
                 items.push(CompletionItem {
                     label: export_name.clone(),
                     kind: Some(CompletionItemKind::FUNCTION), // Most package exports are functions
                     detail: Some(format!("{{{}}}", package_name)),
+                    data: Some(serde_json::json!({
+                        "kind": "package_export",
+                        "package": package_name,
+                        "all_packages": package_names,
+                    })),
                     ..Default::default()
                 });
             }
@@ -2185,6 +3330,137 @@ pub fn completion(state: &WorldState, uri: &Url, position: Position) -> Option<C
     Some(CompletionResponse::Array(items))
 }
 
+/// Detects "cursor is completing the RHS of `$`/`[[`", returning the LHS
+/// variable name when so. This is the positive counterpart of the NSE-skip
+/// rule `classify_identifier` already applies to that same position for
+/// diagnostics/rename: there it means "don't treat this as a usage", here it
+/// means "offer column names instead of global symbols".
+///
+/// Tries the AST first (`df$col` / `df[["col"` already parsed), then falls
+/// back to a plain text scan for the not-yet-typed case (`df$` / `df[["`
+/// with nothing after the trigger character yet), since tree-sitter's error
+/// recovery for an incomplete extract varies with what follows it.
+fn detect_extract_completion_context(
+    node: Node,
+    text: &str,
+    line_text: &str,
+    cursor_byte: usize,
+) -> Option<String> {
+    let mut current = Some(node);
+    while let Some(n) = current {
+        if n.kind() == "extract_operator" {
+            let operator = n
+                .child_by_field_name("operator")
+                .map(|op| node_text(op, text))
+                .unwrap_or("");
+            if operator == "$" {
+                let lhs = n.child_by_field_name("lhs")?;
+                return (lhs.kind() == "identifier").then(|| node_text(lhs, text).to_string());
+            }
+            return None;
+        }
+        if n.kind() == "subset2" {
+            let function_node = n.child_by_field_name("function")?;
+            return (function_node.kind() == "identifier")
+                .then(|| node_text(function_node, text).to_string());
+        }
+        current = n.parent();
+    }
+
+    let before_cursor = &line_text[..cursor_byte.min(line_text.len())];
+    if let Some(prefix) = before_cursor.strip_suffix('$') {
+        return identifier_at_end_of(prefix).map(String::from);
+    }
+    if let Some(prefix) = before_cursor
+        .strip_suffix("[[\"")
+        .or_else(|| before_cursor.strip_suffix("[['"))
+        .or_else(|| before_cursor.strip_suffix("[["))
+    {
+        return identifier_at_end_of(prefix).map(String::from);
+    }
+
+    None
+}
+
+/// Returns the trailing R identifier in `s`, if `s` ends with one.
+fn identifier_at_end_of(s: &str) -> Option<&str> {
+    let start = s
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '.' || *c == '_')
+        .last()
+        .map(|(i, _)| i)?;
+    let ident = &s[start..];
+    let first = ident.chars().next()?;
+    (first.is_alphabetic() || first == '.').then_some(ident)
+}
+
+/// Infers the column/element names of `var_name` from its most recent
+/// `data.frame(...)`/`tibble(...)` assignment anywhere in `root` (later
+/// assignments shadow earlier ones, matching `collect_document_completions`'
+/// "last definition wins" treatment of redefinitions). Returns `None` when
+/// `var_name` was never assigned such a call, or was assigned from something
+/// whose columns can't be known statically (e.g. `read_csv`/`read.csv`) —
+/// callers should show no completions rather than guess.
+fn infer_data_frame_columns(root: Node, text: &str, var_name: &str) -> Option<Vec<String>> {
+    let mut found = None;
+    collect_data_frame_columns(root, text, var_name, &mut found);
+    found
+}
+
+fn collect_data_frame_columns(
+    node: Node,
+    text: &str,
+    var_name: &str,
+    found: &mut Option<Vec<String>>,
+) {
+    if node.kind() == "binary_operator" {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+        if children.len() >= 3 {
+            let (lhs, op, rhs) = (children[0], children[1], children[2]);
+            if matches!(node_text(op, text), "<-" | "=" | "<<-")
+                && lhs.kind() == "identifier"
+                && node_text(lhs, text) == var_name
+            {
+                if let Some(columns) = data_frame_call_columns(rhs, text) {
+                    *found = Some(columns);
+                }
+            }
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_data_frame_columns(child, text, var_name, found);
+    }
+}
+
+/// Extracts named-argument columns from a `data.frame(...)`/`tibble(...)`
+/// call. Any other call (notably `read_csv`/`read.csv`, whose columns depend
+/// on file contents this server doesn't read) isn't recognized here.
+fn data_frame_call_columns(node: Node, text: &str) -> Option<Vec<String>> {
+    if node.kind() != "call" {
+        return None;
+    }
+    let function_node = node.child_by_field_name("function")?;
+    if !matches!(node_text(function_node, text), "data.frame" | "tibble") {
+        return None;
+    }
+
+    let arguments_node = node.child_by_field_name("arguments")?;
+    let mut columns = Vec::new();
+    let mut cursor = arguments_node.walk();
+    for arg in arguments_node.children(&mut cursor) {
+        if arg.kind() == "argument" {
+            if let Some(name_node) = arg.child_by_field_name("name") {
+                columns.push(node_text(name_node, text).to_string());
+            }
+        }
+    }
+    (!columns.is_empty()).then_some(columns)
+}
+
 fn find_namespace_context<'a>(node: &Node<'a>, text: &'a str) -> Option<&'a str> {
     // Walk up to find namespace_operator
     let mut current = *node;
@@ -2200,9 +3476,124 @@ fn find_namespace_context<'a>(node: &Node<'a>, text: &'a str) -> Option<&'a str>
     }
 }
 
+/// Classifies which of `RESERVED_WORDS` are syntactically valid completions
+/// at `node`'s position, analogous to rust-analyzer's context-sensitive
+/// keyword completion: `else` only follows a complete `if` block without its
+/// own `else` yet, `in` only completes an unfinished `for (var ` header,
+/// and `break`/`next` only apply inside a loop body. Every other reserved
+/// word (values like `TRUE`/`NA`/`NULL`, and statement keywords like `if`,
+/// `for`, `function`) is offered wherever an expression is expected.
+fn context_sensitive_keywords(
+    node: Node,
+    line_text: &str,
+    cursor_byte: usize,
+) -> Vec<&'static str> {
+    // Typing inside a string or comment is never a keyword position.
+    if matches!(node.kind(), "string" | "comment") {
+        return Vec::new();
+    }
+
+    let mut keywords: Vec<&'static str> = crate::reserved_words::RESERVED_WORDS
+        .iter()
+        .copied()
+        .filter(|kw| !matches!(*kw, "else" | "in" | "break" | "next"))
+        .collect();
+
+    if preceding_if_without_else(node) {
+        keywords.push("else");
+    }
+    if is_incomplete_for_header(line_text, cursor_byte) {
+        keywords.push("in");
+    }
+    if is_inside_loop_body(node) {
+        keywords.push("break");
+        keywords.push("next");
+    }
+
+    keywords
+}
+
+/// True when the nearest non-comment sibling before `node` (or, failing
+/// that, before `node`'s parent) is a complete `if_statement` that doesn't
+/// already have an `else` clause -- mirroring `ElseNewlineHandler`'s sibling
+/// walk, but for the position *before* `else` has been typed.
+fn preceding_if_without_else(node: Node) -> bool {
+    for candidate in [Some(node), node.parent()].into_iter().flatten() {
+        let mut prev = candidate.prev_sibling();
+        while let Some(sibling) = prev {
+            if sibling.kind() == "comment" {
+                prev = sibling.prev_sibling();
+                continue;
+            }
+            if sibling.kind() == "if_statement" {
+                let mut cursor = sibling.walk();
+                let has_else = sibling.children(&mut cursor).any(|c| c.kind() == "else");
+                return !has_else;
+            }
+            break;
+        }
+    }
+    false
+}
+
+/// True when the text before the cursor looks like an unfinished `for (`
+/// header that already has its loop variable but no `in` yet, e.g.
+/// `for (x `. Resolved from raw text rather than the parse tree since
+/// tree-sitter typically recovers an incomplete header as an error node.
+fn is_incomplete_for_header(line_text: &str, cursor_byte: usize) -> bool {
+    let before = &line_text[..cursor_byte.min(line_text.len())];
+    let Some(for_idx) = before.rfind("for") else {
+        return false;
+    };
+    let at_word_boundary = before[..for_idx]
+        .chars()
+        .last()
+        .map_or(true, |c| !(c.is_alphanumeric() || c == '.' || c == '_'));
+    if !at_word_boundary {
+        return false;
+    }
+
+    let Some(paren_idx) = before[for_idx + 3..].find('(') else {
+        return false;
+    };
+    let header = &before[for_idx + 3 + paren_idx + 1..];
+    if header.contains(')') {
+        return false;
+    }
+
+    let mut has_identifier = false;
+    let mut has_in = false;
+    for tok in header.split(|c: char| !(c.is_alphanumeric() || c == '.' || c == '_')) {
+        if tok == "in" {
+            has_in = true;
+        } else if !tok.is_empty() {
+            has_identifier = true;
+        }
+    }
+    has_identifier && !has_in
+}
+
+/// True when any ancestor of `node` is a loop (`for`/`while`/`repeat`)
+/// without an intervening `function_definition` -- `break`/`next` only
+/// apply to the loop lexically enclosing them, not one enclosing a nested
+/// function definition.
+fn is_inside_loop_body(node: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(n) = current {
+        match n.kind() {
+            "for_statement" | "while_statement" | "repeat_statement" => return true,
+            "function_definition" => return false,
+            _ => {}
+        }
+        current = n.parent();
+    }
+    false
+}
+
 fn collect_document_completions(
     node: Node,
     text: &str,
+    uri: &Url,
     items: &mut Vec<CompletionItem>,
     seen: &mut std::collections::HashSet<String>,
 ) {
@@ -2229,6 +3620,14 @@ fn collect_document_completions(
                     items.push(CompletionItem {
                         label: name,
                         kind: Some(kind),
+                        // Records the declaring line so `completion_item_resolve`
+                        // can show the definition's source text without
+                        // re-walking the document tree.
+                        data: Some(serde_json::json!({
+                            "kind": "local",
+                            "uri": uri.as_str(),
+                            "line": lhs.start_position().row,
+                        })),
                         ..Default::default()
                     });
                 }
@@ -2238,8 +3637,93 @@ fn collect_document_completions(
 
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
-        collect_document_completions(child, text, items, seen);
+        collect_document_completions(child, text, uri, items, seen);
+    }
+}
+
+/// Records on `item.data` the package(s) whose export a local definition
+/// shadows, so `completion_item_resolve` can surface a "shadows export from"
+/// note without re-walking the scope that produced it.
+fn attach_shadowed_packages(item: &mut CompletionItem, shadowed_packages: &[String]) {
+    let mut data = item.data.clone().unwrap_or_else(|| serde_json::json!({}));
+    if let Some(obj) = data.as_object_mut() {
+        obj.insert("shadows".to_string(), serde_json::json!(shadowed_packages));
+    }
+    item.data = Some(data);
+}
+
+/// Fills in `documentation` for a `CompletionItem` the client asked to
+/// resolve, using the provenance `completion()` recorded on `item.data` so
+/// this can run without re-walking `WorldState`. Handles:
+/// - a local definition that shadows one or more package exports,
+/// - a symbol exported by multiple loaded packages (lists all of them),
+/// - a plain local definition (shows the declaring line's source text).
+pub fn completion_item_resolve(
+    mut item: CompletionItem,
+    _help_cache: &crate::help::HelpCache,
+    document_contents: &std::collections::HashMap<Url, String>,
+) -> CompletionItem {
+    let Some(data) = item.data.clone() else {
+        return item;
+    };
+
+    let mut doc_lines: Vec<String> = Vec::new();
+
+    match data.get("kind").and_then(|v| v.as_str()) {
+        Some("local") => {
+            if let Some(shadowed) = data.get("shadows").and_then(|v| v.as_array()) {
+                let packages: Vec<&str> = shadowed.iter().filter_map(|v| v.as_str()).collect();
+                if !packages.is_empty() {
+                    doc_lines.push(format!(
+                        "Shadows export from {}",
+                        packages
+                            .iter()
+                            .map(|p| format!("{{{}}}", p))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+
+            if let (Some(uri_str), Some(line)) = (
+                data.get("uri").and_then(|v| v.as_str()),
+                data.get("line").and_then(|v| v.as_u64()),
+            ) {
+                if let Some(declaring_line) = Url::parse(uri_str)
+                    .ok()
+                    .and_then(|uri| document_contents.get(&uri))
+                    .and_then(|text| text.lines().nth(line as usize))
+                {
+                    doc_lines.push(format!("```r\n{}\n```", declaring_line.trim()));
+                }
+            }
+        }
+        Some("package_export") => {
+            if let Some(all_packages) = data.get("all_packages").and_then(|v| v.as_array()) {
+                let packages: Vec<&str> = all_packages.iter().filter_map(|v| v.as_str()).collect();
+                if packages.len() > 1 {
+                    doc_lines.push(format!(
+                        "Exported by {}",
+                        packages
+                            .iter()
+                            .map(|p| format!("{{{}}}", p))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if !doc_lines.is_empty() {
+        item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: doc_lines.join("\n\n"),
+        }));
     }
+
+    item
 }
 
 // ============================================================================
@@ -2252,6 +3736,10 @@ pub struct DefinitionInfo {
     pub line: u32,
     #[allow(dead_code)]
     pub column: u32,
+    /// Contiguous run of `#'` roxygen comment lines immediately preceding the
+    /// definition, with the `#'` prefix (and one following space) stripped.
+    /// `None` when there is no roxygen block directly above the definition.
+    pub docs: Option<String>,
 }
 
 pub fn extract_definition_statement(
@@ -2339,6 +3827,8 @@ fn extract_statement_from_tree(
         scope::SymbolKind::Parameter => find_function_statement(node, content),
     }?;
 
+    let def_start_row = statement_node.node.start_position().row;
+    let docs = collect_roxygen_docs(content, def_start_row);
     let statement = extract_statement_text(statement_node, content);
 
     Some(DefinitionInfo {
@@ -2346,9 +3836,37 @@ fn extract_statement_from_tree(
         source_uri: symbol.source_uri.clone(),
         line: symbol.defined_line,
         column: symbol.defined_column,
+        docs,
     })
 }
 
+/// Scans upward from `start_row` collecting the contiguous run of `#'`
+/// roxygen comment lines immediately preceding a definition, stopping at the
+/// first non-roxygen line (or the top of the file). Returns the lines in
+/// source order with the `#'` prefix stripped, or `None` if there is no
+/// roxygen block directly above.
+fn collect_roxygen_docs(content: &str, start_row: usize) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut roxygen_lines = Vec::new();
+    let mut row = start_row;
+
+    while row > 0 {
+        row -= 1;
+        let trimmed = lines.get(row).copied().unwrap_or("").trim_start();
+        match trimmed.strip_prefix("#'") {
+            Some(rest) => roxygen_lines.push(rest.strip_prefix(' ').unwrap_or(rest).to_string()),
+            None => break,
+        }
+    }
+
+    if roxygen_lines.is_empty() {
+        return None;
+    }
+
+    roxygen_lines.reverse();
+    Some(roxygen_lines.join("\n"))
+}
+
 /// Result of finding a statement node - includes whether to extract header only
 struct StatementMatch<'a> {
     node: tree_sitter::Node<'a>,
@@ -2639,33 +4157,420 @@ fn extract_function_header(node: tree_sitter::Node, content: &str) -> String {
 // Hover
 // ============================================================================
 
-/// Provide hover information for the symbol at a given text document position.
-///
-/// Tries, in order:
-/// 1. Cross-file symbol resolution (including local definitions), returning an extracted definition or signature with source attribution.
-/// 2. Package exports discovered from the combined package scope, returning a signature and package attribution.
-/// 3. Cached R help text or a one-time lookup of R help for builtins and other symbols.
-///
-/// The produced hover content is Markdown (code block for signatures/definitions and optional attribution) and the hover range corresponds to the identifier node under the cursor.
-///
-/// # Examples
-///
-/// ```no_run
-/// # use lsp_types::Position;
-/// # use url::Url;
-/// # use crate::state::WorldState;
-/// // Assuming `state` is available and `uri` refers to an open R document:
-/// let pos = Position::new(10, 4);
-/// let _ = hover(&state, &uri, pos);
-/// ```
-///
-/// Returns `Some(Hover)` when information (definition, signature, package attribution, or help text) is available for the identifier at `position`, `None` when no useful hover content can be produced.
-pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<Hover> {
-    let doc = state.get_document(uri)?;
-    let tree = doc.tree.as_ref()?;
-    let text = doc.text();
+/// Command id for the hover "Go to definition" action.
+pub const HOVER_GOTO_DEFINITION_COMMAND: &str = "raven.hoverGotoDefinition";
+/// Command id for the hover "Open help" action.
+pub const HOVER_OPEN_HELP_COMMAND: &str = "raven.hoverOpenHelp";
 
-    let line_text = text.lines().nth(position.line as usize).unwrap_or("");
+/// Target location parsed back out of a `raven.hoverGotoDefinition` command invocation.
+pub struct GotoDefinitionCommandArgs {
+    pub uri: Url,
+    pub range: Range,
+}
+
+/// Builds a Markdown `command:` link, e.g. `[Go to definition](command:raven.hoverGotoDefinition?%7B...%7D)`.
+///
+/// `command:` links are not part of the LSP spec; they're a convention some
+/// clients (e.g. VS Code) support for invoking `workspace/executeCommand`
+/// from within rendered Markdown. Callers must only emit these when the
+/// client has advertised support (see `HoverConfig::supports_command_links`).
+fn command_markdown_link(title: &str, command: &str, args: &serde_json::Value) -> String {
+    let encoded_args: String =
+        url::form_urlencoded::byte_serialize(args.to_string().as_bytes()).collect();
+    format!("[{}](command:{}?{})", title, command, encoded_args)
+}
+
+/// Appends a "Go to definition" command link for a symbol resolved to a
+/// navigable (non-`package:`) location, when the client supports command links.
+fn append_goto_definition_action(value: &mut String, state: &WorldState, uri: &Url, line: u32) {
+    if !state.hover_config.supports_command_links {
+        return;
+    }
+    let args = serde_json::json!([{
+        "uri": uri.as_str(),
+        "line": line,
+        "character": 0,
+    }]);
+    value.push_str("\n\n");
+    value.push_str(&command_markdown_link(
+        "Go to definition",
+        HOVER_GOTO_DEFINITION_COMMAND,
+        &args,
+    ));
+}
+
+/// Appends an "Open help" command link for a symbol resolved to a package
+/// export, when the client supports command links.
+fn append_open_help_action(value: &mut String, state: &WorldState, name: &str, package: &str) {
+    if !state.hover_config.supports_command_links {
+        return;
+    }
+    let args = serde_json::json!([{
+        "name": name,
+        "package": package,
+    }]);
+    value.push_str("\n\n");
+    value.push_str(&command_markdown_link(
+        "Open help",
+        HOVER_OPEN_HELP_COMMAND,
+        &args,
+    ));
+}
+
+/// Parses the arguments of a `raven.hoverGotoDefinition` command invocation
+/// back into a navigable location.
+pub fn parse_goto_definition_command_args(
+    arguments: &[serde_json::Value],
+) -> Option<GotoDefinitionCommandArgs> {
+    let arg = arguments.first()?;
+    let uri = Url::parse(arg.get("uri")?.as_str()?).ok()?;
+    let line = arg.get("line")?.as_u64()? as u32;
+    let character = arg.get("character").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    let position = Position::new(line, character);
+    Some(GotoDefinitionCommandArgs {
+        uri,
+        range: Range {
+            start: position,
+            end: position,
+        },
+    })
+}
+
+/// Parses the arguments of a `raven.hoverOpenHelp` command invocation back
+/// into a `(topic, package)` pair.
+pub fn parse_open_help_command_args(arguments: &[serde_json::Value]) -> Option<(String, String)> {
+    let arg = arguments.first()?;
+    let name = arg.get("name")?.as_str()?.to_string();
+    let package = arg.get("package")?.as_str()?.to_string();
+    Some((name, package))
+}
+
+/// Borrows rust-analyzer's `HoverResult { results, exact }`: a hover can
+/// surface more than one candidate definition (e.g. a local definition
+/// shadowing a sourced one, or two sourced files both defining the same
+/// name). `exact` is true only when exactly one candidate survived
+/// `collect_definition_candidates`'s scope/position filtering; `hover`
+/// renders each entry in `results` as its own fenced block, annotated with
+/// its origin, when `exact` is false.
+struct HoverResult {
+    results: Vec<String>,
+    exact: bool,
+}
+
+/// Collects every in-scope definition of `name` reachable from `uri`,
+/// ordered by scope precedence (local > nearest `source()` > transitive):
+/// `uri`'s own top-level definition first (if any), then the symbol scope
+/// resolution already picked via `get_cross_file_symbols`, then any other
+/// file reachable through `source()` edges that also defines `name`,
+/// visited nearest-first and deduplicated by source file.
+fn collect_definition_candidates(
+    state: &WorldState,
+    uri: &Url,
+    name: &str,
+    winner: &ScopedSymbol,
+) -> Vec<ScopedSymbol> {
+    let content_provider = state.content_provider();
+    let mut candidates = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    seen.insert(uri.clone());
+
+    if let Some(local_artifacts) = content_provider.get_artifacts(uri) {
+        if let Some(local_symbol) = local_artifacts.exported_interface.get(name) {
+            candidates.push(local_symbol.clone());
+        }
+    }
+
+    if seen.insert(winner.source_uri.clone()) {
+        candidates.push(winner.clone());
+    }
+
+    let max_depth = state.cross_file_config.max_chain_depth;
+    let mut queue: std::collections::VecDeque<(Url, usize)> = state
+        .cross_file_graph
+        .get_dependencies(uri)
+        .into_iter()
+        .map(|edge| (edge.to.clone(), 1))
+        .collect();
+
+    while let Some((candidate_uri, depth)) = queue.pop_front() {
+        if depth > max_depth || !seen.insert(candidate_uri.clone()) {
+            continue;
+        }
+        if let Some(artifacts) = content_provider.get_artifacts(&candidate_uri) {
+            if let Some(symbol) = artifacts.exported_interface.get(name) {
+                candidates.push(symbol.clone());
+            }
+        }
+        for edge in state.cross_file_graph.get_dependencies(&candidate_uri) {
+            queue.push_back((edge.to.clone(), depth + 1));
+        }
+    }
+
+    candidates
+}
+
+/// One S3 or S4 method definition discovered for a generic, for the
+/// dispatch-aware hover in `collect_dispatch_methods`/`render_dispatch_hover`.
+enum DispatchMethod {
+    /// A `generic.class <- function(...)` assignment, already resolved as a
+    /// regular symbol by cross-file scope resolution.
+    S3 { class: String, symbol: ScopedSymbol },
+    /// A `setMethod("generic", "class", ...)` call. These aren't
+    /// assignments, so scope resolution never sees them; `raw_call` is the
+    /// literal source line the call was found on.
+    S4 {
+        class: String,
+        source_uri: Url,
+        line: u32,
+        raw_call: String,
+    },
+}
+
+impl DispatchMethod {
+    fn class(&self) -> &str {
+        match self {
+            DispatchMethod::S3 { class, .. } => class,
+            DispatchMethod::S4 { class, .. } => class,
+        }
+    }
+}
+
+/// Extracts up to `count` leading comma-separated quoted string arguments
+/// (single- or double-quoted) from the start of `args`, stopping at the
+/// first non-quoted argument. Used for best-effort parsing of `setMethod`/
+/// `setGeneric` call arguments without a full R call-argument parser.
+fn extract_leading_quoted_args(args: &str, count: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut rest = args.trim_start();
+    while out.len() < count {
+        let quote = match rest.chars().next() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => break,
+        };
+        let Some(end) = rest[1..].find(quote) else {
+            break;
+        };
+        out.push(rest[1..=end].to_string());
+        rest = rest[end + 2..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    out
+}
+
+/// Scans `text` for single-line `setMethod("generic", "class", ...)` calls,
+/// returning `(generic, class, line)` for each one found. Best-effort: only
+/// recognizes calls whose first two arguments are literal quoted strings on
+/// the same line as the `setMethod(` call.
+fn find_set_method_calls(text: &str) -> Vec<(String, String, u32)> {
+    let mut calls = Vec::new();
+    for (line_idx, line) in text.lines().enumerate() {
+        if let Some(call_start) = line.find("setMethod(") {
+            let args = &line[call_start + "setMethod(".len()..];
+            if let [generic, class] = extract_leading_quoted_args(args, 2).as_slice() {
+                calls.push((generic.clone(), class.clone(), line_idx as u32));
+            }
+        }
+    }
+    calls
+}
+
+/// Collects every S3 (`generic.class <- function(...)`) and S4
+/// (`setMethod("generic", "class", ...)`) method definition for `generic`
+/// that's visible from `uri`'s cross-file scope, for dispatch-aware hover.
+/// S3 methods are already regular symbols in `cross_file_symbols`; S4
+/// methods are calls, not assignments, so the files already reachable
+/// through `cross_file_symbols` (plus `uri` itself) are scanned for them
+/// directly.
+fn collect_dispatch_methods(
+    state: &WorldState,
+    uri: &Url,
+    generic: &str,
+    cross_file_symbols: &HashMap<String, ScopedSymbol>,
+) -> Vec<DispatchMethod> {
+    let mut methods = Vec::new();
+    let prefix = format!("{}.", generic);
+
+    for (sym_name, symbol) in cross_file_symbols {
+        if symbol.kind == scope::SymbolKind::Function {
+            if let Some(class) = sym_name.strip_prefix(&prefix) {
+                methods.push(DispatchMethod::S3 {
+                    class: class.to_string(),
+                    symbol: symbol.clone(),
+                });
+            }
+        }
+    }
+
+    let mut candidate_uris: std::collections::HashSet<Url> = cross_file_symbols
+        .values()
+        .map(|symbol| symbol.source_uri.clone())
+        .collect();
+    candidate_uris.insert(uri.clone());
+    for candidate_uri in candidate_uris {
+        let Some(doc) = state.get_document(&candidate_uri) else {
+            continue;
+        };
+        for (call_generic, class, line) in find_set_method_calls(&doc.text()) {
+            if call_generic == generic {
+                methods.push(DispatchMethod::S4 {
+                    class,
+                    source_uri: candidate_uri.clone(),
+                    line,
+                    raw_call: doc
+                        .text()
+                        .lines()
+                        .nth(line as usize)
+                        .unwrap_or("")
+                        .trim()
+                        .to_string(),
+                });
+            }
+        }
+    }
+
+    methods.sort_by(|a, b| a.class().cmp(b.class()));
+    methods
+}
+
+/// Renders one dispatch method's fenced definition block, labeled with its
+/// class and origin, for the multi-method hover produced when `generic` has
+/// more than one applicable S3/S4 method in scope.
+fn render_dispatch_method(state: &WorldState, uri: &Url, method: &DispatchMethod) -> String {
+    match method {
+        DispatchMethod::S3 { class, symbol } => {
+            let body = render_hover_candidate(symbol, uri, state);
+            format!("**S3 method for class `{}`**\n\n{}", class, body)
+        }
+        DispatchMethod::S4 {
+            class,
+            source_uri,
+            line,
+            raw_call,
+        } => {
+            let origin = if source_uri == uri {
+                format!("this file, line {}", line + 1)
+            } else {
+                definition_link(source_uri, *line, state.workspace_folders.first())
+            };
+            format!(
+                "**S4 method for class `{}`**\n\n```r\n{}\n```\n\n{}",
+                class, raw_call, origin
+            )
+        }
+    }
+}
+
+/// Renders every applicable method definition for `generic` together,
+/// labeled by dispatched class, instead of resolving to a single arbitrary
+/// definition.
+fn render_dispatch_hover(
+    state: &WorldState,
+    uri: &Url,
+    generic: &str,
+    methods: &[DispatchMethod],
+) -> String {
+    let blocks: Vec<String> = methods
+        .iter()
+        .map(|method| render_dispatch_method(state, uri, method))
+        .collect();
+    format!(
+        "_{} method{} of `{}` found in scope:_\n\n{}",
+        methods.len(),
+        if methods.len() == 1 { "" } else { "s" },
+        generic,
+        blocks.join("\n\n---\n\n")
+    )
+}
+
+/// Renders one candidate's fenced definition block plus an origin label
+/// ("this file, line N" / the sourced file's relative path and line), for
+/// the ambiguous-hover case in `hover` where more than one candidate
+/// survives `collect_definition_candidates`.
+fn render_hover_candidate(symbol: &ScopedSymbol, uri: &Url, state: &WorldState) -> String {
+    let def_info = extract_definition_statement(symbol, state);
+    let statement = def_info
+        .as_ref()
+        .map(|d| d.statement.clone())
+        .unwrap_or_else(|| {
+            symbol
+                .signature
+                .clone()
+                .unwrap_or_else(|| symbol.name.to_string())
+        });
+    let line = def_info
+        .as_ref()
+        .map(|d| d.line)
+        .unwrap_or(symbol.defined_line);
+
+    let origin = if symbol.source_uri == *uri {
+        format!("this file, line {}", line + 1)
+    } else {
+        let relative_path =
+            compute_relative_path(&symbol.source_uri, state.workspace_folders.first());
+        format!("{}, line {}", relative_path, line + 1)
+    };
+
+    let docs_section = if state.hover_config.documentation {
+        def_info
+            .as_ref()
+            .and_then(|d| d.docs.as_ref())
+            .map(|docs| {
+                let cross_file_symbols = HashMap::new();
+                render_roxygen_markdown(
+                    docs,
+                    &symbol.source_uri,
+                    &cross_file_symbols,
+                    state.workspace_folders.first(),
+                    state.hover_config,
+                )
+            })
+            .filter(|rendered| !rendered.is_empty())
+            .map(|rendered| format!("{}\n\n", rendered))
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    format!("```r\n{}\n```\n\n{}{}", statement, docs_section, origin)
+}
+
+/// Wraps a rendered hover body in `HoverContents` using the markup kind the
+/// client asked for (`state.hover_config.markup`), so callers don't each
+/// have to remember to read the config.
+fn hover_contents(state: &WorldState, value: String) -> HoverContents {
+    HoverContents::Markup(MarkupContent {
+        kind: state.hover_config.markup,
+        value,
+    })
+}
+
+/// Provide hover information for the symbol at a given text document position.
+///
+/// Tries, in order:
+/// 1. Cross-file symbol resolution (including local definitions), returning an extracted definition or signature with source attribution.
+/// 2. Package exports discovered from the combined package scope, returning a signature and package attribution.
+/// 3. Cached R help text or a one-time lookup of R help for builtins and other symbols.
+///
+/// The produced hover content respects `state.hover_config`: `cross_file` restricts symbol resolution to the current file's local scope, `documentation` suppresses roxygen rendering, `help_fallback` skips the R subprocess / help-cache lookup, `signature_only` returns a minimal signature-only hover, and `markup` controls whether the content is Markdown or plain text.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use lsp_types::Position;
+/// # use url::Url;
+/// # use crate::state::WorldState;
+/// // Assuming `state` is available and `uri` refers to an open R document:
+/// let pos = Position::new(10, 4);
+/// let _ = hover(&state, &uri, pos);
+/// ```
+///
+/// Returns `Some(Hover)` when information (definition, signature, package attribution, or help text) is available for the identifier at `position`, `None` when no useful hover content can be produced.
+pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<Hover> {
+    let doc = state.get_document(uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
+
+    let line_text = text.lines().nth(position.line as usize).unwrap_or("");
     let byte_col = utf16_column_to_byte_offset(line_text, position.character);
     let row = position.line as usize;
 
@@ -2694,45 +4599,146 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
         ),
     };
 
-    // Try cross-file symbols (includes local scope with definition extraction)
+    // Try cross-file symbols (includes local scope with definition extraction).
+    // When `cross_file` is disabled, cap the traversal depth at 1 so only the
+    // current file's own artifacts are considered (see
+    // `get_cross_file_scope_with_max_depth`), instead of reimplementing
+    // local-only scope resolution from scratch.
     log::trace!("Calling get_cross_file_symbols for hover");
-    let cross_file_symbols = get_cross_file_symbols(state, uri, position.line, position.character);
+    let hover_max_depth = if state.hover_config.cross_file {
+        state.cross_file_config.max_chain_depth
+    } else {
+        1
+    };
+    let cross_file_symbols = get_cross_file_scope_with_max_depth(
+        state,
+        uri,
+        position.line,
+        position.character,
+        hover_max_depth,
+    )
+    .symbols;
     log::trace!(
         "Got {} symbols from cross-file scope",
         cross_file_symbols.len()
     );
+
+    // Dispatch-aware hover: `name` (e.g. `print` in `print(obj)`) may be an
+    // S3/S4 generic with multiple applicable method definitions (`print.foo`,
+    // `setMethod("print", "bar", ...)`) rather than a single one. Show every
+    // method discovered across the cross-file graph together, labeled by
+    // dispatched class, instead of resolving to one arbitrary definition.
+    // Dotted names (`print.foo`) are never treated as the generic themselves.
+    if !state.hover_config.signature_only && !name.contains('.') {
+        let dispatch_methods = collect_dispatch_methods(state, uri, name, &cross_file_symbols);
+        if !dispatch_methods.is_empty() {
+            let value = render_dispatch_hover(state, uri, name, &dispatch_methods);
+            return Some(Hover {
+                contents: hover_contents(state, value),
+                range: Some(node_range),
+            });
+        }
+    }
+
     if let Some(symbol) = cross_file_symbols.get(name) {
         log::trace!(
             "hover: found symbol '{}' in cross_file_symbols, source_uri={}",
             name,
             symbol.source_uri
         );
+        // Fast path for clients that only want the signature: skip ambiguous
+        // candidate collection, roxygen rendering, parameter-default links,
+        // and command-link actions entirely.
+        if state.hover_config.signature_only {
+            let signature = symbol.signature.clone().unwrap_or_else(|| name.to_string());
+            return Some(Hover {
+                contents: hover_contents(state, format!("```r\n{}\n```", signature)),
+                range: Some(node_range),
+            });
+        }
+
         let mut value = String::new();
 
         // Check if this is a package export (source_uri starts with "package:")
         // Package exports have URIs like "package:dplyr" or "package:base"
         let package_name = symbol.source_uri.as_str().strip_prefix("package:");
 
+        // When more than one in-scope file defines `name` (a local definition
+        // shadowing a sourced one, or two sourced files both defining it),
+        // show every candidate instead of silently picking the scope
+        // resolution's winner. Package exports are never ambiguous this way.
+        if package_name.is_none() {
+            let candidates = collect_definition_candidates(state, uri, name, symbol);
+            let hover_result = HoverResult {
+                exact: candidates.len() <= 1,
+                results: candidates
+                    .iter()
+                    .map(|c| render_hover_candidate(c, uri, state))
+                    .collect(),
+            };
+            if !hover_result.exact {
+                let mut value = hover_result.results.join("\n\n---\n\n");
+                value.push_str(&format!(
+                    "\n\n_Ambiguous: {} definitions of `{}` are in scope here._",
+                    hover_result.results.len(),
+                    name
+                ));
+                return Some(Hover {
+                    contents: hover_contents(state, value),
+                    range: Some(node_range),
+                });
+            }
+        }
+
         // Try to extract definition statement
         let workspace_root = state.workspace_folders.first();
+        let mut navigable_definition: Option<(Url, u32)> = None;
         match extract_definition_statement(symbol, state) {
             Some(def_info) => {
                 // Note: No escaping needed inside code blocks - markdown doesn't interpret special chars there
                 value.push_str(&format!("```r\n{}\n```\n\n", def_info.statement));
 
+                if state.hover_config.documentation {
+                    if let Some(docs) = &def_info.docs {
+                        let rendered = render_roxygen_markdown(
+                            docs,
+                            &def_info.source_uri,
+                            &cross_file_symbols,
+                            workspace_root,
+                            state.hover_config,
+                        );
+                        if !rendered.is_empty() {
+                            value.push_str(&rendered);
+                            value.push_str("\n\n");
+                        }
+                    }
+                }
+
                 // Add file location
                 if def_info.source_uri == *uri {
                     value.push_str(&format!("this file, line {}", def_info.line + 1));
                 } else {
-                    let relative_path = compute_relative_path(&def_info.source_uri, workspace_root);
-                    let absolute_path = def_info.source_uri.as_str();
-                    value.push_str(&format!(
-                        "[{}]({}), line {}",
-                        relative_path,
-                        absolute_path,
-                        def_info.line + 1
+                    value.push_str(&definition_link(
+                        &def_info.source_uri,
+                        def_info.line,
+                        workspace_root,
                     ));
                 }
+
+                if symbol.kind == scope::SymbolKind::Function {
+                    let defaults = linkify_function_parameter_defaults(
+                        &def_info.statement,
+                        &cross_file_symbols,
+                        workspace_root,
+                        state.hover_config,
+                    );
+                    if !defaults.is_empty() {
+                        value.push_str("\n\nDefaults: ");
+                        value.push_str(&defaults.join(", "));
+                    }
+                }
+
+                navigable_definition = Some((def_info.source_uri, def_info.line));
             }
             None => {
                 // Graceful fallback: show symbol info without definition statement
@@ -2744,29 +4750,29 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
                     package_name
                 );
                 if let Some(pkg) = package_name {
-                    // Try to get full help documentation from R
+                    // Try to get full help documentation from R, unless
+                    // `help_fallback` is disabled, in which case we skip the
+                    // subprocess call and fall straight to the signature.
                     log::trace!("hover: fetching R help for '{}' from package '{}'", name, pkg);
-                    let name_owned = name.to_string();
-                    let pkg_owned = pkg.to_string();
-                    if let Ok(help_result) = tokio::task::spawn_blocking(move || {
-                        crate::help::get_help(&name_owned, Some(&pkg_owned))
-                    })
-                    .await
-                    {
-                        log::trace!(
-                            "hover: get_help returned {:?}",
-                            help_result.as_ref().map(|s| s.len())
-                        );
-                        if let Some(help_text) = help_result {
-                            // Show full R documentation
-                            value.push_str(&format!("```\n{}\n```", help_text));
-                        } else if let Some(sig) = &symbol.signature {
-                            value.push_str(&format!("```r\n{}\n```\n", sig));
-                            value.push_str(&format!("\nfrom {{{}}}", pkg));
-                        } else {
-                            value.push_str(&format!("```r\n{}\n```\n", name));
-                            value.push_str(&format!("\nfrom {{{}}}", pkg));
-                        }
+                    let help_result = if state.hover_config.help_fallback {
+                        let name_owned = name.to_string();
+                        let pkg_owned = pkg.to_string();
+                        tokio::task::spawn_blocking(move || {
+                            crate::help::get_help(&name_owned, Some(&pkg_owned))
+                        })
+                        .await
+                        .ok()
+                        .flatten()
+                    } else {
+                        None
+                    };
+                    log::trace!(
+                        "hover: get_help returned {:?}",
+                        help_result.as_ref().map(|s| s.len())
+                    );
+                    if let Some(help_text) = help_result {
+                        // Show full R documentation
+                        value.push_str(&format!("```\n{}\n```", help_text));
                     } else if let Some(sig) = &symbol.signature {
                         value.push_str(&format!("```r\n{}\n```\n", sig));
                         value.push_str(&format!("\nfrom {{{}}}", pkg));
@@ -2777,26 +4783,51 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
                 } else if let Some(sig) = &symbol.signature {
                     value.push_str(&format!("```r\n{}\n```\n", sig));
                     if symbol.source_uri != *uri {
-                        let relative_path =
-                            compute_relative_path(&symbol.source_uri, workspace_root);
-                        value.push_str(&format!("\n*Defined in {}*", relative_path));
+                        value.push_str(&format!(
+                            "\nDefined in {}",
+                            definition_link(
+                                &symbol.source_uri,
+                                symbol.defined_line,
+                                workspace_root
+                            )
+                        ));
+                    }
+                    if symbol.kind == scope::SymbolKind::Function {
+                        let defaults = linkify_function_parameter_defaults(
+                            sig,
+                            &cross_file_symbols,
+                            workspace_root,
+                            state.hover_config,
+                        );
+                        if !defaults.is_empty() {
+                            value.push_str("\n\nDefaults: ");
+                            value.push_str(&defaults.join(", "));
+                        }
                     }
                 } else {
                     value.push_str(&format!("```r\n{}\n```\n", name));
                     if symbol.source_uri != *uri {
-                        let relative_path =
-                            compute_relative_path(&symbol.source_uri, workspace_root);
-                        value.push_str(&format!("\n*Defined in {}*", relative_path));
+                        value.push_str(&format!(
+                            "\nDefined in {}",
+                            definition_link(
+                                &symbol.source_uri,
+                                symbol.defined_line,
+                                workspace_root
+                            )
+                        ));
                     }
                 }
             }
         }
 
+        if let Some(pkg) = package_name {
+            append_open_help_action(&mut value, state, name, pkg);
+        } else if let Some((def_uri, def_line)) = navigable_definition {
+            append_goto_definition_action(&mut value, state, &def_uri, def_line);
+        }
+
         return Some(Hover {
-            contents: HoverContents::Markup(MarkupContent {
-                kind: MarkupKind::Markdown,
-                value,
-            }),
+            contents: hover_contents(state, value),
             range: Some(node_range),
         });
     }
@@ -2804,7 +4835,13 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
     // Check package exports from combined_exports cache (if packages enabled)
     // This surfaces package exports without blocking on R subprocess
     if state.cross_file_config.packages_enabled {
-        let scope = get_cross_file_scope(state, uri, position.line, position.character);
+        let scope = get_cross_file_scope_with_max_depth(
+            state,
+            uri,
+            position.line,
+            position.character,
+            hover_max_depth,
+        );
         let all_packages: Vec<String> = scope
             .inherited_packages
             .iter()
@@ -2818,31 +4855,32 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
         {
             let mut value = String::new();
 
-            // Try to get full help documentation from R
-            let name_owned = name.to_string();
-            let pkg_owned = pkg_name.to_string();
-            if let Ok(help_result) = tokio::task::spawn_blocking(move || {
-                crate::help::get_help(&name_owned, Some(&pkg_owned))
-            })
-            .await
-            {
-                if let Some(help_text) = help_result {
-                    // Show full R documentation
-                    value.push_str(&format!("```\n{}\n```", help_text));
-                } else {
-                    value.push_str(&format!("```r\n{}\n```\n", name));
-                    value.push_str(&format!("\nfrom {{{}}}", pkg_name));
-                }
+            // Try to get full help documentation from R, unless `help_fallback`
+            // is disabled, in which case we skip the subprocess call.
+            let help_result = if state.hover_config.help_fallback {
+                let name_owned = name.to_string();
+                let pkg_owned = pkg_name.to_string();
+                tokio::task::spawn_blocking(move || {
+                    crate::help::get_help(&name_owned, Some(&pkg_owned))
+                })
+                .await
+                .ok()
+                .flatten()
+            } else {
+                None
+            };
+            if let Some(help_text) = help_result {
+                // Show full R documentation
+                value.push_str(&format!("```\n{}\n```", help_text));
             } else {
                 value.push_str(&format!("```r\n{}\n```\n", name));
                 value.push_str(&format!("\nfrom {{{}}}", pkg_name));
             }
 
+            append_open_help_action(&mut value, state, name, pkg_name);
+
             return Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value,
-                }),
+                contents: hover_contents(state, value),
                 range: Some(node_range),
             });
         }
@@ -2853,10 +4891,7 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
     if let Some(cached) = state.help_cache.get(name) {
         if let Some(help_text) = cached {
             return Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value: format!("```\n{}\n```", help_text),
-                }),
+                contents: hover_contents(state, format!("```\n{}\n```", help_text)),
                 range: Some(node_range),
             });
         }
@@ -2864,24 +4899,23 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
         return None;
     }
 
-    // Try to get help from R subprocess
-    let name_owned = name.to_string();
-    if let Ok(help_text) =
-        tokio::task::spawn_blocking(move || crate::help::get_help(&name_owned, None)).await
-    {
-        if let Some(help_text) = help_text {
-            // Cache successful result
-            state
-                .help_cache
-                .insert(name.to_string(), Some(help_text.clone()));
-
-            return Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value: format!("```\n{}\n```", help_text),
-                }),
-                range: Some(node_range),
-            });
+    // Try to get help from R subprocess, unless `help_fallback` is disabled
+    if state.hover_config.help_fallback {
+        let name_owned = name.to_string();
+        if let Ok(help_text) =
+            tokio::task::spawn_blocking(move || crate::help::get_help(&name_owned, None)).await
+        {
+            if let Some(help_text) = help_text {
+                // Cache successful result
+                state
+                    .help_cache
+                    .insert(name.to_string(), Some(help_text.clone()));
+
+                return Some(Hover {
+                    contents: hover_contents(state, format!("```\n{}\n```", help_text)),
+                    range: Some(node_range),
+                });
+            }
         }
     }
 
@@ -2892,58 +4926,516 @@ pub async fn hover(state: &WorldState, uri: &Url, position: Position) -> Option<
 // Signature Help
 // ============================================================================
 
-pub fn signature_help(state: &WorldState, uri: &Url, position: Position) -> Option<SignatureHelp> {
+/// Sync half of `textDocument/signatureHelp`, computed while holding the
+/// state read lock. Resolves the enclosing call's callee and, when its
+/// signature is already known without an async fetch (cross-file scope),
+/// finishes the job immediately; otherwise it records which package to query
+/// and lets `resolve_signature_help` do that off the lock.
+pub struct SignatureHelpContext {
+    func_name: String,
+    positional_index: u32,
+    active_name: Option<String>,
+    /// Parameter spans already known (e.g. resolved from cross-file scope).
+    resolved_params: Option<Vec<String>>,
+    /// Package to query for a Usage-section signature, when `resolved_params` is None.
+    package_name: Option<String>,
+}
+
+/// Counts the top-level commas in `args_node` that fall before `point`
+/// (giving the 0-based positional argument index), and, if the cursor sits
+/// inside a named argument (`name = value`), returns that name so the caller
+/// can match it to a formal parameter instead of relying on position.
+fn active_argument(args_node: Node, point: Point, text: &str) -> (u32, Option<String>) {
+    let mut cursor = args_node.walk();
+    let mut positional_index = 0u32;
+    let mut active_name = None;
+
+    for child in args_node.children(&mut cursor) {
+        if child.kind() == "," && child.end_position() <= point {
+            positional_index += 1;
+        }
+        if child.kind() == "argument"
+            && child.start_position() <= point
+            && point <= child.end_position()
+        {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                active_name = Some(node_text(name_node, text).to_string());
+            }
+        }
+    }
+
+    (positional_index, active_name)
+}
+
+pub fn prepare_signature_help(
+    state: &WorldState,
+    uri: &Url,
+    position: Position,
+) -> Option<SignatureHelpContext> {
     let doc = state.get_document(uri)?;
     let tree = doc.tree.as_ref()?;
     let text = doc.text();
 
-    let point = Point::new(position.line as usize, position.character as usize);
+    let line_text = text.lines().nth(position.line as usize).unwrap_or("");
+    let byte_col = utf16_column_to_byte_offset(line_text, position.character);
+    let point = Point::new(position.line as usize, byte_col);
 
-    // Find enclosing call
-    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    let mut cursor_node = tree.root_node().descendant_for_point_range(point, point)?;
+    let (call_node, func_name) = loop {
+        if cursor_node.kind() == "call" {
+            if let Some(func_node) = cursor_node.child_by_field_name("function") {
+                break (cursor_node, node_text(func_node, &text).to_string());
+            }
+        }
+        cursor_node = cursor_node.parent()?;
+    };
 
-    loop {
-        if node.kind() == "call" {
-            let mut cursor = node.walk();
-            let children: Vec<_> = node.children(&mut cursor).collect();
+    let (positional_index, active_name) = match call_node.child_by_field_name("arguments") {
+        Some(args_node) => active_argument(args_node, point, &text),
+        None => (0, None),
+    };
 
-            if !children.is_empty() {
-                let func_node = children[0];
-                let func_name = node_text(func_node, &text);
-
-                return Some(SignatureHelp {
-                    signatures: vec![SignatureInformation {
-                        label: format!("{}(...)", func_name),
-                        documentation: None,
-                        parameters: None,
-                        active_parameter: None,
-                    }],
-                    active_signature: Some(0),
-                    active_parameter: None,
-                });
-            }
+    // Tier 1: cross-file scope (mirrors hover's first lookup).
+    let cross_file_symbols = get_cross_file_symbols(state, uri, position.line, position.character);
+    if let Some(symbol) = cross_file_symbols.get(func_name.as_str()) {
+        if let Some(signature) = &symbol.signature {
+            return Some(SignatureHelpContext {
+                func_name,
+                positional_index,
+                active_name,
+                resolved_params: Some(split_signature_parameters(signature)),
+                package_name: None,
+            });
         }
+    }
 
-        node = node.parent()?;
+    // Tier 1.5: locally-defined user functions. The cross-file scope's
+    // `signature` field above isn't populated yet, so this is currently the
+    // only path that resolves a same-file or same-workspace R function.
+    if let Some(signature) = find_user_function_signature(state, uri, &func_name) {
+        return Some(SignatureHelpContext {
+            func_name,
+            positional_index,
+            active_name,
+            resolved_params: Some(split_signature_parameters(&signature)),
+            package_name: None,
+        });
     }
-}
 
-// ============================================================================
-// Goto Definition
-// ============================================================================
+    // Tier 2: package exports (mirrors hover's find_package_for_symbol lookup).
+    if state.cross_file_config.packages_enabled {
+        let scope = get_cross_file_scope(state, uri, position.line, position.character);
+        let all_packages: Vec<String> = scope
+            .inherited_packages
+            .iter()
+            .chain(scope.loaded_packages.iter())
+            .cloned()
+            .collect();
 
-/// Locate the definition location for the identifier at the given position by searching
+        if let Some(pkg_name) = state
+            .package_library
+            .find_package_for_symbol(&func_name, &all_packages)
+        {
+            return Some(SignatureHelpContext {
+                func_name,
+                positional_index,
+                active_name,
+                resolved_params: None,
+                package_name: Some(pkg_name.to_string()),
+            });
+        }
+    }
+
+    // Tier 3: fall back to an unqualified help lookup (builtins etc.).
+    Some(SignatureHelpContext {
+        func_name,
+        positional_index,
+        active_name,
+        resolved_params: None,
+        package_name: None,
+    })
+}
+
+/// Async half of `textDocument/signatureHelp`. Fetches the Usage-section
+/// signature from R help (when `prepare_signature_help` couldn't resolve one
+/// without a subprocess call) and assembles the final `SignatureHelp`.
+pub async fn resolve_signature_help(ctx: SignatureHelpContext) -> Option<SignatureHelp> {
+    let params = match ctx.resolved_params {
+        Some(params) => params,
+        None => {
+            let name_owned = ctx.func_name.clone();
+            let pkg_owned = ctx.package_name.clone();
+            let help_text = tokio::task::spawn_blocking(move || {
+                crate::help::get_help(&name_owned, pkg_owned.as_deref())
+            })
+            .await
+            .ok()
+            .flatten();
+
+            match help_text
+                .as_deref()
+                .and_then(crate::help::extract_signature_from_help)
+            {
+                Some(signature) => split_signature_parameters(&signature),
+                None => return Some(bare_signature_help(&ctx.func_name)),
+            }
+        }
+    };
+
+    if params.is_empty() {
+        return Some(bare_signature_help(&ctx.func_name));
+    }
+
+    let active_parameter =
+        resolve_active_parameter(&params, ctx.positional_index, ctx.active_name.as_deref());
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label: format!("{}({})", ctx.func_name, params.join(", ")),
+            documentation: None,
+            parameters: Some(parameter_informations(&ctx.func_name, &params)),
+            active_parameter: Some(active_parameter),
+        }],
+        active_signature: Some(0),
+        active_parameter: Some(active_parameter),
+    })
+}
+
+/// Signature help for a callee we couldn't resolve to any parameter list.
+fn bare_signature_help(func_name: &str) -> SignatureHelp {
+    SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label: format!("{}(...)", func_name),
+            documentation: None,
+            parameters: None,
+            active_parameter: None,
+        }],
+        active_signature: Some(0),
+        active_parameter: None,
+    }
+}
+
+/// Splits the parameter list inside a signature's outermost parentheses on
+/// top-level commas, respecting nested parens/brackets/braces and string
+/// literals so that a default like `foo = c(1, 2)` isn't mis-split.
+fn split_signature_parameters(signature: &str) -> Vec<String> {
+    let Some(open) = signature.find('(') else {
+        return Vec::new();
+    };
+    let Some(close) = signature.rfind(')') else {
+        return Vec::new();
+    };
+    if close <= open {
+        return Vec::new();
+    }
+    let inner = &signature[open + 1..close];
+
+    let mut params = Vec::new();
+    let mut depth = 0i32;
+    let mut in_string = None;
+    let mut start = 0usize;
+
+    for (i, ch) in inner.char_indices() {
+        if let Some(quote) = in_string {
+            if ch == quote {
+                in_string = None;
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' => in_string = Some(ch),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(inner[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = inner[start..].trim();
+    if !last.is_empty() {
+        params.push(last.to_string());
+    }
+
+    params
+}
+
+/// Returns the bare name of a parameter span, stripping a ` = default` suffix.
+fn parameter_name(param: &str) -> &str {
+    param.split_once(" = ").map_or(param, |(name, _)| name)
+}
+
+/// Picks the active parameter index: a named argument (`name = value`) under
+/// the cursor matches its formal by name; otherwise the positional index is
+/// used, clamped to the trailing `...` parameter (if any) once arguments run
+/// past it.
+fn resolve_active_parameter(params: &[String], positional_index: u32, active_name: Option<&str>) -> u32 {
+    if let Some(name) = active_name {
+        if let Some(idx) = params.iter().position(|p| parameter_name(p) == name) {
+            return idx as u32;
+        }
+    }
+
+    if let Some(dots_idx) = params.iter().position(|p| p == "...") {
+        if positional_index as usize >= dots_idx {
+            return dots_idx as u32;
+        }
+    }
+
+    positional_index.min(params.len() as u32 - 1)
+}
+
+/// Builds `ParameterInformation` entries with UTF-16 offsets into the
+/// signature `label` (`func_name(p1, p2, ...)`), per the LSP offset-label form.
+fn parameter_informations(func_name: &str, params: &[String]) -> Vec<ParameterInformation> {
+    let mut offset = format!("{}(", func_name).encode_utf16().count() as u32;
+    let mut infos = Vec::with_capacity(params.len());
+
+    for (i, param) in params.iter().enumerate() {
+        let len = param.encode_utf16().count() as u32;
+        infos.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([offset, offset + len]),
+            documentation: None,
+        });
+        offset += len;
+        if i + 1 < params.len() {
+            offset += 2; // ", "
+        }
+    }
+
+    infos
+}
+
+// ============================================================================
+// Inlay Hints
+// ============================================================================
+
+/// Resolves a callee's parameter names through the same cross-file/package/help
+/// path `hover` and `signature_help` use, caching the result by `(symbol,
+/// source_uri)` (a `package:<name>` pseudo-URI for package exports) so the
+/// signature isn't re-parsed on every viewport change.
+async fn resolve_parameter_names(
+    state: &WorldState,
+    uri: &Url,
+    position: Position,
+    func_name: &str,
+) -> Option<Vec<String>> {
+    // Tier 1: cross-file scope (mirrors hover's first lookup).
+    let cross_file_symbols = get_cross_file_symbols(state, uri, position.line, position.character);
+    if let Some(symbol) = cross_file_symbols.get(func_name) {
+        let cache_key = (func_name.to_string(), symbol.source_uri.to_string());
+        if let Ok(cache) = state.signature_param_cache.read() {
+            if let Some(cached) = cache.get(&cache_key) {
+                return Some(cached.clone());
+            }
+        }
+        let signature = symbol.signature.as_ref()?;
+        let params = split_signature_parameters(signature);
+        if let Ok(mut cache) = state.signature_param_cache.write() {
+            cache.insert(cache_key, params.clone());
+        }
+        return Some(params);
+    }
+
+    // Tier 2/3: package export or unqualified builtin (mirrors hover's fallback chain).
+    let pkg_name = if state.cross_file_config.packages_enabled {
+        let scope = get_cross_file_scope(state, uri, position.line, position.character);
+        let all_packages: Vec<String> = scope
+            .inherited_packages
+            .iter()
+            .chain(scope.loaded_packages.iter())
+            .cloned()
+            .collect();
+        state
+            .package_library
+            .find_package_for_symbol(func_name, &all_packages)
+            .map(|p| p.to_string())
+    } else {
+        None
+    };
+
+    let cache_key = (
+        func_name.to_string(),
+        format!("package:{}", pkg_name.as_deref().unwrap_or("")),
+    );
+    if let Ok(cache) = state.signature_param_cache.read() {
+        if let Some(cached) = cache.get(&cache_key) {
+            return Some(cached.clone());
+        }
+    }
+
+    let name_owned = func_name.to_string();
+    let pkg_owned = pkg_name.clone();
+    let help_text =
+        tokio::task::spawn_blocking(move || crate::help::get_help(&name_owned, pkg_owned.as_deref()))
+            .await
+            .ok()
+            .flatten();
+
+    let signature = help_text
+        .as_deref()
+        .and_then(crate::help::extract_signature_from_help)?;
+    let params = split_signature_parameters(&signature);
+    if let Ok(mut cache) = state.signature_param_cache.write() {
+        cache.insert(cache_key, params.clone());
+    }
+    Some(params)
+}
+
+/// Collects `call` nodes whose line range overlaps `range`, pruning subtrees
+/// that fall entirely outside it.
+fn collect_calls_in_range<'a>(node: Node<'a>, range: Range, out: &mut Vec<Node<'a>>) {
+    if node.end_position().row < range.start.line as usize
+        || node.start_position().row > range.end.line as usize
+    {
+        return;
+    }
+
+    if node.kind() == "call" {
+        out.push(node);
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_calls_in_range(child, range, out);
+    }
+}
+
+/// `textDocument/inlayHint`: annotates positional call arguments with the
+/// parameter names they bind to. Named arguments (`name = value`) are left
+/// alone, and once a `...` parameter is reached no further positional
+/// arguments are annotated (R stops matching by position past `...`).
+pub async fn inlay_hint(state: &WorldState, uri: &Url, range: Range) -> Option<Vec<InlayHint>> {
+    let doc = state.get_document(uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
+
+    let mut calls = Vec::new();
+    collect_calls_in_range(tree.root_node(), range, &mut calls);
+
+    let mut hints = Vec::new();
+    for call_node in calls {
+        let Some(func_node) = call_node.child_by_field_name("function") else {
+            continue;
+        };
+        if func_node.kind() != "identifier" {
+            continue;
+        }
+        let Some(args_node) = call_node.child_by_field_name("arguments") else {
+            continue;
+        };
+
+        let func_name = node_text(func_node, &text).to_string();
+        let call_position = Position::new(
+            func_node.start_position().row as u32,
+            func_node.start_position().column as u32,
+        );
+        let Some(params) = resolve_parameter_names(state, uri, call_position, &func_name).await
+        else {
+            continue;
+        };
+
+        let mut positional_index = 0usize;
+        let mut cursor = args_node.walk();
+        for arg in args_node.children(&mut cursor) {
+            if arg.kind() != "argument" || arg.child_by_field_name("name").is_some() {
+                continue;
+            }
+            if positional_index >= params.len() || params[positional_index] == "..." {
+                break;
+            }
+            if let Some(value_node) = arg.child_by_field_name("value") {
+                hints.push(InlayHint {
+                    position: Position::new(
+                        value_node.start_position().row as u32,
+                        value_node.start_position().column as u32,
+                    ),
+                    label: InlayHintLabel::String(format!("{}:", params[positional_index])),
+                    kind: Some(InlayHintKind::PARAMETER),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: None,
+                    padding_right: Some(true),
+                    data: None,
+                });
+            }
+            positional_index += 1;
+        }
+    }
+
+    Some(hints)
+}
+
+// ============================================================================
+// Goto Definition
+// ============================================================================
+
+/// URI scheme used for synthetic, read-only documents that expose a package's `R/` source so
+/// goto-definition can land inside an installed library instead of stopping at `package:pkg`.
+pub const PACKAGE_SOURCE_URI_SCHEME: &str = "raven-package";
+
+/// Resolve a package export's pseudo-URI (`package:pkg`) to a navigable location inside a
+/// synthetic `raven-package:pkg/relative/path.R` document, when the package's `R/` source is
+/// available on disk (see [`PackageLibrary::find_exported_definition`]).
+///
+/// Returns `None` when the package isn't installed, or is installed without plain-text `R/`
+/// source (e.g. a byte-compiled lazy-load database), in which case goto-definition has no
+/// navigable target and callers should fall back to their previous "not navigable" behavior.
+fn package_export_location(state: &WorldState, package: &str, symbol: &str) -> Option<Location> {
+    let (relative_path, line, column) = state
+        .package_library
+        .find_exported_definition(package, symbol)?;
+    let virtual_uri = Url::parse(&format!(
+        "{}:{}/{}",
+        PACKAGE_SOURCE_URI_SCHEME,
+        package,
+        relative_path.to_string_lossy().replace('\\', "/")
+    ))
+    .ok()?;
+    let end_column = column + symbol.chars().map(|c| c.len_utf16() as u32).sum::<u32>();
+    Some(Location {
+        uri: virtual_uri,
+        range: Range {
+            start: Position::new(line, column),
+            end: Position::new(line, end_column),
+        },
+    })
+}
+
+/// Read the content of a synthetic `raven-package:pkg/relative/path.R` document produced by
+/// [`package_export_location`], for clients that implement a content provider for the
+/// `raven-package` scheme (mirroring how Deno's language server serves its own virtual
+/// `deno:` documents back to the editor on request).
+///
+/// Returns `None` if `uri` isn't a `raven-package:` URI, or the package/file can no longer be
+/// found on disk.
+pub fn read_package_source(state: &WorldState, uri: &Url) -> Option<String> {
+    let rest = uri
+        .as_str()
+        .strip_prefix(PACKAGE_SOURCE_URI_SCHEME)?
+        .strip_prefix(':')?;
+    let (package, relative_path) = rest.split_once('/')?;
+    state
+        .package_library
+        .read_source_file(package, std::path::Path::new(relative_path))
+}
+
+/// Locate the definition location for the identifier at the given position by searching
 /// the current document, cross-file symbols, open documents, and the workspace index.
 ///
 /// If the identifier is defined in the current document, its local definition is returned.
 /// Otherwise the function searches cross-file symbols and exported interfaces from open
 /// documents and the workspace. If the symbol originates from a package (pseudo-URI
-/// starting with "package:"), no navigable location is returned.
+/// starting with "package:"), the location points into a synthetic `raven-package:` document
+/// over the package's `R/` source when one can be found (see [`package_export_location`]);
+/// otherwise no navigable location is returned.
 ///
 /// # Returns
 ///
 /// `Some(Location)` pointing to the symbol's defining range when a navigable definition is found;
-/// `None` if no definition is found or if the symbol is a package export (non-navigable).
+/// `None` if no definition is found, or the symbol is a package export with no `R/` source to
+/// navigate into.
 ///
 /// # Examples
 ///
@@ -2961,9 +5453,7 @@ pub fn goto_definition(
     let content_provider = state.content_provider();
 
     // Try open document first, then workspace index
-    let doc = state
-        .get_document(uri)
-        .or_else(|| state.workspace_index.get(uri))?;
+    let doc = state.get_document_or_workspace(uri)?;
     let tree = doc.tree.as_ref()?;
     let text = doc.text();
 
@@ -2980,8 +5470,13 @@ pub fn goto_definition(
         let metadata = match file_path_context {
             crate::file_path_intellisense::FilePathContext::SourceCall { .. } => {
                 // Use get_enriched_metadata to get metadata with inherited_working_directory
-                // from parent files, not just the current file's directives
-                state.get_enriched_metadata(uri).unwrap_or_default()
+                // from parent files, not just the current file's directives.
+                // `doc` above already holds `uri`'s entry (when open), so pass
+                // it through rather than calling get_enriched_metadata, which
+                // would re-lock `documents` for the same URI and deadlock.
+                state
+                    .get_enriched_metadata_with_document(uri, Some(&*doc))
+                    .unwrap_or_default()
             }
             _ => Default::default(),
         };
@@ -3007,6 +5502,8 @@ pub fn goto_definition(
     }
 
     let name = node_text(node, &text);
+    let origin_selection_range = node_range(node);
+    let link_support = state.definition_link_support;
 
     // Search using position-aware scope resolution
     // This unifies same-file and cross-file lookups, respecting:
@@ -3014,25 +5511,29 @@ pub fn goto_definition(
     // 2. Function scope (locals don't leak)
     // 3. Shadowing (locals override globals)
     let scope = get_cross_file_scope(state, uri, position.line, position.character);
-    
+
     if let Some(symbol) = scope.symbols.get(name) {
         // Check if this is a package export (source_uri starts with "package:")
-        // Package exports have pseudo-URIs like "package:dplyr" that can't be navigated to
+        // Package exports have pseudo-URIs like "package:dplyr"; navigate into the package's
+        // R/ source when it's available on disk, otherwise there's nothing to jump to.
         // Validates: Requirements 11.1, 11.2
-        if symbol.source_uri.as_str().starts_with("package:") {
-            log::trace!(
-                "Symbol '{}' is from package '{}', no navigable source available",
-                name,
-                symbol
-                    .source_uri
-                    .as_str()
-                    .strip_prefix("package:")
-                    .unwrap_or("unknown")
-            );
-            return None;
+        if let Some(package) = symbol.source_uri.as_str().strip_prefix("package:") {
+            return match package_export_location(state, package, name) {
+                Some(location) => {
+                    goto_definition_response(vec![location], origin_selection_range, link_support)
+                }
+                None => {
+                    log::trace!(
+                        "Symbol '{}' is from package '{}', no navigable source available",
+                        name,
+                        package
+                    );
+                    None
+                }
+            };
         }
 
-        return Some(GotoDefinitionResponse::Scalar(Location {
+        let symbol_location = Location {
             uri: symbol.source_uri.clone(),
             range: Range {
                 start: Position::new(symbol.defined_line, symbol.defined_column),
@@ -3041,7 +5542,32 @@ pub fn goto_definition(
                     symbol.defined_column + name.chars().map(|c| c.len_utf16() as u32).sum::<u32>(),
                 ),
             },
-        }));
+        };
+
+        // When `symbol` resolves to the current file, check whether it was bound
+        // by a completed if/else that reassigns `name` in both branches (e.g.
+        // `if (cond) { x <- 1 } else { x <- 2 }` before the usage). Either
+        // branch could be the symbol's actual runtime value, so surface both
+        // definitions instead of silently collapsing to one.
+        if symbol.source_uri == *uri {
+            let conditional = find_conditional_reassignments(tree.root_node(), name, &text, point);
+            if conditional.len() > 1 {
+                let locations = conditional
+                    .into_iter()
+                    .map(|range| Location {
+                        uri: uri.clone(),
+                        range,
+                    })
+                    .collect();
+                return goto_definition_response(locations, origin_selection_range, link_support);
+            }
+        }
+
+        return goto_definition_response(
+            vec![symbol_location],
+            origin_selection_range,
+            link_support,
+        );
     }
 
     // Search all open documents using ContentProvider
@@ -3051,11 +5577,19 @@ pub fn goto_definition(
         }
         if let Some(artifacts) = content_provider.get_artifacts(&file_uri) {
             if let Some(symbol) = artifacts.exported_interface.get(name) {
-                // Skip package exports (they have pseudo-URIs that can't be navigated to)
-                if symbol.source_uri.as_str().starts_with("package:") {
+                // Package exports have pseudo-URIs like "package:dplyr"; navigate into the
+                // package's R/ source when available, otherwise skip and keep searching.
+                if let Some(package) = symbol.source_uri.as_str().strip_prefix("package:") {
+                    if let Some(location) = package_export_location(state, package, name) {
+                        return goto_definition_response(
+                            vec![location],
+                            origin_selection_range,
+                            link_support,
+                        );
+                    }
                     continue;
                 }
-                return Some(GotoDefinitionResponse::Scalar(Location {
+                let location = Location {
                     uri: symbol.source_uri.clone(),
                     range: Range {
                         start: Position::new(symbol.defined_line, symbol.defined_column),
@@ -3064,7 +5598,12 @@ pub fn goto_definition(
                             symbol.defined_column + name.len() as u32,
                         ),
                     },
-                }));
+                };
+                return goto_definition_response(
+                    vec![location],
+                    origin_selection_range,
+                    link_support,
+                );
             }
         }
     }
@@ -3076,11 +5615,19 @@ pub fn goto_definition(
         }
         if let Some(artifacts) = content_provider.get_artifacts(&file_uri) {
             if let Some(symbol) = artifacts.exported_interface.get(name) {
-                // Skip package exports (they have pseudo-URIs that can't be navigated to)
-                if symbol.source_uri.as_str().starts_with("package:") {
+                // Package exports have pseudo-URIs like "package:dplyr"; navigate into the
+                // package's R/ source when available, otherwise skip and keep searching.
+                if let Some(package) = symbol.source_uri.as_str().strip_prefix("package:") {
+                    if let Some(location) = package_export_location(state, package, name) {
+                        return goto_definition_response(
+                            vec![location],
+                            origin_selection_range,
+                            link_support,
+                        );
+                    }
                     continue;
                 }
-                return Some(GotoDefinitionResponse::Scalar(Location {
+                let location = Location {
                     uri: symbol.source_uri.clone(),
                     range: Range {
                         start: Position::new(symbol.defined_line, symbol.defined_column),
@@ -3089,23 +5636,34 @@ pub fn goto_definition(
                             symbol.defined_column + name.len() as u32,
                         ),
                     },
-                }));
+                };
+                return goto_definition_response(
+                    vec![location],
+                    origin_selection_range,
+                    link_support,
+                );
             }
         }
     }
 
     // Fallback: Search legacy open documents
-    for (file_uri, doc) in &state.documents {
+    for entry in state.documents.iter() {
+        let (file_uri, doc) = (entry.key(), entry.value());
         if file_uri == uri {
             continue;
         }
         if let Some(tree) = &doc.tree {
             let file_text = doc.text();
             if let Some(def_range) = find_definition_in_tree(tree.root_node(), name, &file_text) {
-                return Some(GotoDefinitionResponse::Scalar(Location {
+                let location = Location {
                     uri: file_uri.clone(),
                     range: def_range,
-                }));
+                };
+                return goto_definition_response(
+                    vec![location],
+                    origin_selection_range,
+                    link_support,
+                );
             }
         }
     }
@@ -3118,10 +5676,15 @@ pub fn goto_definition(
         if let Some(tree) = &doc.tree {
             let file_text = doc.text();
             if let Some(def_range) = find_definition_in_tree(tree.root_node(), name, &file_text) {
-                return Some(GotoDefinitionResponse::Scalar(Location {
+                let location = Location {
                     uri: file_uri.clone(),
                     range: def_range,
-                }));
+                };
+                return goto_definition_response(
+                    vec![location],
+                    origin_selection_range,
+                    link_support,
+                );
             }
         }
     }
@@ -3129,7 +5692,123 @@ pub fn goto_definition(
     None
 }
 
+/// Builds the right `GotoDefinitionResponse` variant for `locations`, per the
+/// LSP spec: `Link` (LocationLink, carrying `origin_selection_range` and a
+/// separate `target_selection_range`) when the client advertised
+/// `textDocument.definition.linkSupport`, otherwise `Scalar` for a single
+/// result or `Array` for several (older clients have no way to express
+/// "several LocationLinks", only a plain list of `Location`s). Returns `None`
+/// for an empty `locations`.
+fn goto_definition_response(
+    locations: Vec<Location>,
+    origin_selection_range: Range,
+    link_support: bool,
+) -> Option<GotoDefinitionResponse> {
+    if locations.is_empty() {
+        return None;
+    }
+
+    if link_support {
+        let links = locations
+            .into_iter()
+            .map(|location| LocationLink {
+                origin_selection_range: Some(origin_selection_range),
+                target_uri: location.uri,
+                target_range: location.range,
+                target_selection_range: location.range,
+            })
+            .collect();
+        return Some(GotoDefinitionResponse::Link(links));
+    }
+
+    if locations.len() == 1 {
+        Some(GotoDefinitionResponse::Scalar(
+            locations.into_iter().next().unwrap(),
+        ))
+    } else {
+        Some(GotoDefinitionResponse::Array(locations))
+    }
+}
+
+/// Finds an `if`/`else` (with braced consequence and alternative) that both
+/// reassigns `name` and completes before `usage_point`, returning the
+/// definition range from each branch. A usage after such a conditional could
+/// have bound to either branch's value, so callers should surface both
+/// instead of picking whichever one happens to come last in the source.
+///
+/// Returns the branches of whichever qualifying `if`/`else` is closest to
+/// `usage_point` (later candidates found during the walk overwrite earlier
+/// ones), or an empty `Vec` if `name` isn't reassigned by a complete
+/// conditional before `usage_point`.
+fn find_conditional_reassignments(
+    node: Node,
+    name: &str,
+    text: &str,
+    usage_point: Point,
+) -> Vec<Range> {
+    let mut best: Vec<Range> = Vec::new();
+    collect_conditional_reassignments(node, name, text, usage_point, &mut best);
+    best
+}
+
+fn collect_conditional_reassignments(
+    node: Node,
+    name: &str,
+    text: &str,
+    usage_point: Point,
+    best: &mut Vec<Range>,
+) {
+    if node.start_position() >= usage_point {
+        return;
+    }
+
+    if node.kind() == "if_statement" && node.end_position() <= usage_point {
+        let mut cursor = node.walk();
+        let mut consequence: Option<Node> = None;
+        let mut alternative: Option<Node> = None;
+        let mut else_seen = false;
+
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "else" => else_seen = true,
+                "braced_expression" if else_seen && alternative.is_none() => {
+                    alternative = Some(child);
+                }
+                "braced_expression" if !else_seen && consequence.is_none() => {
+                    consequence = Some(child);
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(consequence), Some(alternative)) = (consequence, alternative) {
+            let then_def = find_definition_in_tree_unscoped(consequence, name, text);
+            let else_def = find_definition_in_tree_unscoped(alternative, name, text);
+            if let (Some(then_range), Some(else_range)) = (then_def, else_def) {
+                *best = vec![then_range, else_range];
+            }
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_conditional_reassignments(child, name, text, usage_point, best);
+    }
+}
+
+/// Resolves `name` to its file-scope (top-level) binding first, falling back to
+/// the old unscoped "first LHS in document order" search when `name` isn't
+/// bound anywhere in this file (a free symbol, e.g. a builtin or a symbol
+/// defined in another file).
 fn find_definition_in_tree(node: Node, name: &str, text: &str) -> Option<Range> {
+    let scopes = LocalScopeTree::build(node, text);
+    if let Some(binding) = scopes.global_binding(name) {
+        return Some(node_range(binding));
+    }
+    find_definition_in_tree_unscoped(node, name, text)
+}
+
+fn find_definition_in_tree_unscoped(node: Node, name: &str, text: &str) -> Option<Range> {
     if node.kind() == "binary_operator" {
         let mut cursor = node.walk();
         let children: Vec<_> = node.children(&mut cursor).collect();
@@ -3143,23 +5822,14 @@ fn find_definition_in_tree(node: Node, name: &str, text: &str) -> Option<Range>
                 && lhs.kind() == "identifier"
                 && node_text(lhs, text) == name
             {
-                return Some(Range {
-                    start: Position::new(
-                        lhs.start_position().row as u32,
-                        lhs.start_position().column as u32,
-                    ),
-                    end: Position::new(
-                        lhs.end_position().row as u32,
-                        lhs.end_position().column as u32,
-                    ),
-                });
+                return Some(node_range(lhs));
             }
         }
     }
 
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
-        if let Some(range) = find_definition_in_tree(child, name, text) {
+        if let Some(range) = find_definition_in_tree_unscoped(child, name, text) {
             return Some(range);
         }
     }
@@ -3168,38 +5838,349 @@ fn find_definition_in_tree(node: Node, name: &str, text: &str) -> Option<Range>
 }
 
 // ============================================================================
-// References
+// Local Scope Resolution
 // ============================================================================
+//
+// A lightweight, single-file scope tree used to resolve identifiers to the
+// binding (parameter, or `<-`/`=`/`<<-` assignment) they actually refer to,
+// rather than matching every identifier with the same spelling anywhere in
+// the file. Each `function_definition` opens a child scope seeded with its
+// parameter names; each qualifying `binary_operator` binds its LHS in the
+// current scope (`<<-` walks outward to the nearest scope that already binds
+// the name, defaulting to the global/file scope). Resolution then walks from
+// a usage's innermost containing scope outward to the nearest binding,
+// mirroring R's own lexical scoping.
+
+struct LocalScope<'a> {
+    start: Point,
+    end: Point,
+    parent: Option<usize>,
+    bindings: HashMap<String, Node<'a>>,
+}
 
-pub fn references(state: &WorldState, uri: &Url, position: Position) -> Option<Vec<Location>> {
-    // Use ContentProvider for unified access
-    let content_provider = state.content_provider();
-
-    // Try open document first, then workspace index
-    let doc = state
-        .get_document(uri)
-        .or_else(|| state.workspace_index.get(uri))?;
-    let tree = doc.tree.as_ref()?;
-    let text = doc.text();
+struct LocalScopeTree<'a> {
+    scopes: Vec<LocalScope<'a>>,
+}
 
-    let point = Point::new(position.line as usize, position.character as usize);
-    let node = tree.root_node().descendant_for_point_range(point, point)?;
+fn point_within(point: Point, start: Point, end: Point) -> bool {
+    (point.row, point.column) >= (start.row, start.column)
+        && (point.row, point.column) <= (end.row, end.column)
+}
 
-    if node.kind() != "identifier" {
-        return None;
+impl<'a> LocalScopeTree<'a> {
+    /// Builds the scope tree for a whole file (or subtree), with scope 0 as
+    /// the global/file-level scope.
+    fn build(root: Node<'a>, text: &str) -> Self {
+        let mut tree = LocalScopeTree {
+            scopes: vec![LocalScope {
+                start: root.start_position(),
+                end: root.end_position(),
+                parent: None,
+                bindings: HashMap::new(),
+            }],
+        };
+        tree.walk(root, text, 0);
+        tree
     }
 
-    let name = node_text(node, &text);
-    let mut locations = Vec::new();
-
-    // Search current document
-    find_references_in_tree(tree.root_node(), name, &text, uri, &mut locations);
+    fn walk(&mut self, node: Node<'a>, text: &str, scope_idx: usize) {
+        if node.kind() == "function_definition" {
+            let child_idx = self.scopes.len();
+            self.scopes.push(LocalScope {
+                start: node.start_position(),
+                end: node.end_position(),
+                parent: Some(scope_idx),
+                bindings: HashMap::new(),
+            });
 
-    // Search all open documents using new DocumentStore
-    for file_uri in state.document_store.uris() {
-        if &file_uri == uri {
-            continue; // Already searched
-        }
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if child.kind() == "parameters" {
+                    let mut param_cursor = child.walk();
+                    for param in child.children(&mut param_cursor) {
+                        if param.kind() != "parameter" {
+                            continue;
+                        }
+                        let mut name_cursor = param.walk();
+                        if let Some(name_node) = param
+                            .children(&mut name_cursor)
+                            .find(|n| n.kind() == "identifier")
+                        {
+                            let param_name = node_text(name_node, text).to_string();
+                            self.scopes[child_idx].bindings.insert(param_name, name_node);
+                        }
+                    }
+                }
+            }
+
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                self.walk(child, text, child_idx);
+            }
+            return;
+        }
+
+        if node.kind() == "binary_operator" {
+            let mut cursor = node.walk();
+            let children: Vec<_> = node.children(&mut cursor).collect();
+            if children.len() >= 3 {
+                let lhs = children[0];
+                let op_text = node_text(children[1], text);
+                if matches!(op_text, "<-" | "=" | "<<-") && lhs.kind() == "identifier" {
+                    let name = node_text(lhs, text).to_string();
+                    if op_text == "<<-" {
+                        let target = self
+                            .enclosing_scope_with(scope_idx, &name)
+                            .unwrap_or(0);
+                        self.scopes[target].bindings.entry(name).or_insert(lhs);
+                    } else {
+                        self.scopes[scope_idx]
+                            .bindings
+                            .entry(name)
+                            .or_insert(lhs);
+                    }
+                }
+            }
+            for child in children {
+                self.walk(child, text, scope_idx);
+            }
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.walk(child, text, scope_idx);
+        }
+    }
+
+    /// Walks outward from `scope_idx` to find the nearest ancestor scope
+    /// (inclusive) that already binds `name`; used for `<<-`'s "assign in the
+    /// nearest scope that already has this name, else global" semantics.
+    fn enclosing_scope_with(&self, scope_idx: usize, name: &str) -> Option<usize> {
+        let mut idx = self.scopes[scope_idx].parent;
+        while let Some(i) = idx {
+            if self.scopes[i].bindings.contains_key(name) {
+                return Some(i);
+            }
+            idx = self.scopes[i].parent;
+        }
+        None
+    }
+
+    /// Finds the innermost scope whose range contains `point`, preferring
+    /// the most deeply nested match.
+    fn scope_at(&self, point: Point) -> usize {
+        let mut best = 0;
+        let mut best_depth = -1i64;
+        for (idx, scope) in self.scopes.iter().enumerate() {
+            if point_within(point, scope.start, scope.end) {
+                let depth = self.depth(idx);
+                if depth > best_depth {
+                    best = idx;
+                    best_depth = depth;
+                }
+            }
+        }
+        best
+    }
+
+    fn depth(&self, scope_idx: usize) -> i64 {
+        let mut depth = 0i64;
+        let mut idx = self.scopes[scope_idx].parent;
+        while let Some(i) = idx {
+            depth += 1;
+            idx = self.scopes[i].parent;
+        }
+        depth
+    }
+
+    /// Resolves `name` by walking outward from `scope_idx`, returning the
+    /// nearest binding node (parameter or assignment LHS).
+    fn resolve(&self, scope_idx: usize, name: &str) -> Option<Node<'a>> {
+        let mut idx = Some(scope_idx);
+        while let Some(i) = idx {
+            if let Some(node) = self.scopes[i].bindings.get(name) {
+                return Some(*node);
+            }
+            idx = self.scopes[i].parent;
+        }
+        None
+    }
+
+    /// Resolves `name` as seen from `point` (the usual "what does this usage
+    /// refer to" query).
+    fn resolve_at(&self, point: Point, name: &str) -> Option<Node<'a>> {
+        self.resolve(self.scope_at(point), name)
+    }
+
+    /// Like `resolve_at`, but also returns the index of the scope the
+    /// binding was found in. Used by the "Extract function" code action to
+    /// tell a binding from an enclosing (non-global) scope — which won't be
+    /// visible from a new top-level function — from a global one, which
+    /// will.
+    fn resolve_at_with_scope(&self, point: Point, name: &str) -> Option<(usize, Node<'a>)> {
+        let mut idx = Some(self.scope_at(point));
+        while let Some(i) = idx {
+            if let Some(node) = self.scopes[i].bindings.get(name) {
+                return Some((i, *node));
+            }
+            idx = self.scopes[i].parent;
+        }
+        None
+    }
+
+    /// Resolves `name` as bound at the file/global scope (scope 0), ignoring
+    /// any shadowing locals. Used when a usage's own position isn't known
+    /// (e.g. cross-file lookups into another document).
+    fn global_binding(&self, name: &str) -> Option<Node<'a>> {
+        self.scopes[0].bindings.get(name).copied()
+    }
+}
+
+/// Collects every `identifier` usage node in `root` whose lexical resolution
+/// (from its own position) points at the exact same binding node as
+/// `binding`, plus `binding` itself when `include_declaration` is true.
+/// Skips NSE-exempt positions (argument names, `$`/`@` RHS, formulas) using
+/// the same rules as `collect_usages_with_context`, and skips
+/// LHS-of-assignment identifiers (those are bindings, not usages).
+fn collect_scoped_occurrences<'a>(
+    root: Node<'a>,
+    text: &str,
+    scopes: &LocalScopeTree<'a>,
+    binding: Node<'a>,
+    name: &str,
+    include_declaration: bool,
+) -> Vec<Node<'a>> {
+    let mut usages = Vec::new();
+    let context = UsageContext::default();
+    collect_usages_with_context(root, text, &context, &mut usages);
+
+    let mut occurrences: Vec<Node<'a>> = usages
+        .into_iter()
+        .filter(|(used_name, node)| {
+            used_name == name
+                && scopes
+                    .resolve_at(node.start_position(), name)
+                    .is_some_and(|resolved| resolved.id() == binding.id())
+        })
+        .map(|(_, node)| node)
+        .collect();
+
+    if include_declaration && !occurrences.iter().any(|node| node.id() == binding.id()) {
+        occurrences.push(binding);
+    }
+    occurrences
+}
+
+/// Like `collect_scoped_occurrences`, but for rename: also includes
+/// `Definition`-role identifiers (every `<-`/`=`/`<<-` assignment to `name`
+/// in scope, not just the first), since renaming must touch every place the
+/// binding is assigned, not only where it's read. Still excludes `Skipped`
+/// positions (argument names, NSE call arguments, formula terms, `$`/`@`
+/// RHS) — those are a different name, not this binding.
+fn collect_renameable_occurrences<'a>(
+    root: Node<'a>,
+    text: &str,
+    scopes: &LocalScopeTree<'a>,
+    binding: Node<'a>,
+    name: &str,
+) -> Vec<Node<'a>> {
+    let mut identifiers = Vec::new();
+    collect_identifiers_with_roles(root, text, &UsageContext::default(), &mut identifiers);
+
+    let mut occurrences: Vec<Node<'a>> = identifiers
+        .into_iter()
+        .filter(|(ident_name, node, role)| {
+            *role != IdentifierRole::Skipped
+                && ident_name == name
+                && scopes
+                    .resolve_at(node.start_position(), name)
+                    .is_some_and(|resolved| resolved.id() == binding.id())
+        })
+        .map(|(_, node, _)| node)
+        .collect();
+
+    if !occurrences.iter().any(|node| node.id() == binding.id()) {
+        occurrences.push(binding);
+    }
+    occurrences
+}
+
+// ============================================================================
+// References
+// ============================================================================
+
+/// Finds every location where the symbol under `position` is genuinely used,
+/// reusing the same role-classified traversal (`collect_usages_with_context`)
+/// that backs undefined-variable diagnostics and `rename`, so the three
+/// features never disagree about what counts as a reference: NSE call
+/// arguments, formula terms, named-argument names, and the RHS of `$`/`@`
+/// are never reported. When `include_declaration` is true, the defining
+/// assignment is added to the results.
+///
+/// Searches the current document first (scope-aware when the cursor resolves
+/// to a local binding), then every other document and workspace-index entry
+/// reachable through `WorldState` (including files pulled in via `source()`
+/// resolution).
+pub fn references(
+    state: &WorldState,
+    uri: &Url,
+    position: Position,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    // Use ContentProvider for unified access
+    let content_provider = state.content_provider();
+
+    // Try open document first, then workspace index
+    let doc = state.get_document_or_workspace(uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
+
+    let point = Point::new(position.line as usize, position.character as usize);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+
+    if node.kind() != "identifier" {
+        return None;
+    }
+
+    let name = node_text(node, &text);
+    let mut locations = Vec::new();
+
+    // Search current document. Prefer scope-aware resolution (from the exact
+    // cursor position) so a shadowed local doesn't pull in unrelated
+    // same-named identifiers elsewhere in the file; fall back to the
+    // unscoped scan for free symbols (builtins, undefined globals).
+    let local_scopes = LocalScopeTree::build(tree.root_node(), &text);
+    if let Some(binding) = local_scopes.resolve_at(point, name) {
+        for occurrence in collect_scoped_occurrences(
+            tree.root_node(),
+            &text,
+            &local_scopes,
+            binding,
+            name,
+            include_declaration,
+        ) {
+            locations.push(Location {
+                uri: uri.clone(),
+                range: node_range(occurrence),
+            });
+        }
+    } else {
+        find_references_in_tree(
+            tree.root_node(),
+            name,
+            &text,
+            uri,
+            include_declaration,
+            &mut locations,
+        );
+    }
+
+    // Search all open documents using new DocumentStore
+    for file_uri in state.document_store.uris() {
+        if &file_uri == uri {
+            continue; // Already searched
+        }
         if let Some(content) = content_provider.get_content(&file_uri) {
             // Parse the content to search for references
             if let Some(doc_state) = state.document_store.get_without_touch(&file_uri) {
@@ -3209,6 +6190,7 @@ pub fn references(state: &WorldState, uri: &Url, position: Position) -> Option<V
                         name,
                         &content,
                         &file_uri,
+                        include_declaration,
                         &mut locations,
                     );
                 }
@@ -3228,13 +6210,15 @@ pub fn references(state: &WorldState, uri: &Url, position: Position) -> Option<V
                 name,
                 &file_text,
                 &file_uri,
+                include_declaration,
                 &mut locations,
             );
         }
     }
 
     // Fallback: Search legacy open documents
-    for (file_uri, doc) in &state.documents {
+    for entry in state.documents.iter() {
+        let (file_uri, doc) = (entry.key(), entry.value());
         if file_uri == uri {
             continue; // Already searched
         }
@@ -3244,7 +6228,14 @@ pub fn references(state: &WorldState, uri: &Url, position: Position) -> Option<V
         }
         if let Some(tree) = &doc.tree {
             let file_text = doc.text();
-            find_references_in_tree(tree.root_node(), name, &file_text, file_uri, &mut locations);
+            find_references_in_tree(
+                tree.root_node(),
+                name,
+                &file_text,
+                file_uri,
+                include_declaration,
+                &mut locations,
+            );
         }
     }
 
@@ -3259,14 +6250,51 @@ pub fn references(state: &WorldState, uri: &Url, position: Position) -> Option<V
         }
         if let Some(tree) = &doc.tree {
             let file_text = doc.text();
-            find_references_in_tree(tree.root_node(), name, &file_text, file_uri, &mut locations);
+            find_references_in_tree(
+                tree.root_node(),
+                name,
+                &file_text,
+                file_uri,
+                include_declaration,
+                &mut locations,
+            );
         }
     }
 
     Some(locations)
 }
 
+/// Resolves `name` to its file-scope (top-level) binding first, restricting
+/// results to occurrences that actually resolve to that binding (so a
+/// same-named local inside some unrelated function isn't counted). Falls
+/// back to the old unscoped scan when `name` isn't bound anywhere in this
+/// file (a free symbol from this file's point of view). The unscoped scan
+/// has no notion of "declaration" vs "usage", so `include_declaration` only
+/// affects the scoped path.
 fn find_references_in_tree(
+    node: Node,
+    name: &str,
+    text: &str,
+    uri: &Url,
+    include_declaration: bool,
+    locations: &mut Vec<Location>,
+) {
+    let scopes = LocalScopeTree::build(node, text);
+    if let Some(binding) = scopes.global_binding(name) {
+        for occurrence in
+            collect_scoped_occurrences(node, text, &scopes, binding, name, include_declaration)
+        {
+            locations.push(Location {
+                uri: uri.clone(),
+                range: node_range(occurrence),
+            });
+        }
+        return;
+    }
+    find_references_in_tree_unscoped(node, name, text, uri, locations);
+}
+
+fn find_references_in_tree_unscoped(
     node: Node,
     name: &str,
     text: &str,
@@ -3276,1977 +6304,3013 @@ fn find_references_in_tree(
     if node.kind() == "identifier" && node_text(node, text) == name {
         locations.push(Location {
             uri: uri.clone(),
-            range: Range {
-                start: Position::new(
-                    node.start_position().row as u32,
-                    node.start_position().column as u32,
-                ),
-                end: Position::new(
-                    node.end_position().row as u32,
-                    node.end_position().column as u32,
-                ),
-            },
+            range: node_range(node),
         });
     }
 
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
-        find_references_in_tree(child, name, text, uri, locations);
+        find_references_in_tree_unscoped(child, name, text, uri, locations);
     }
 }
 
 // ============================================================================
-// On Type Formatting (Indentation)
+// Rename
 // ============================================================================
 
-pub fn on_type_formatting(
+/// Renames the symbol under `position` to `new_name`, reusing the exact NSE
+/// classification `collect_usages_with_context`/`collect_renameable_occurrences`
+/// use for diagnostics and references: the same rules that decide what counts
+/// as a usage decide what's safe to rename. Refuses to rename a `Skipped`
+/// position (a named argument, an NSE call argument, a formula term, or the
+/// RHS of `$`/`@`) since it isn't a reference to the binding at all.
+///
+/// Mirrors `references`' search — current document first (scope-aware when
+/// the cursor resolves to a local binding), then every other document and
+/// workspace-index entry reachable through `WorldState` (including files
+/// pulled in via `source()` resolution) — so cross-file occurrences are
+/// renamed too.
+///
+/// Rejects `new_name` up front when it's an R reserved word (`if`, `function`,
+/// `TRUE`, `...`, etc.) — renaming to one would produce a file that no longer
+/// parses as the identifier it used to be.
+pub fn rename(
     state: &WorldState,
     uri: &Url,
     position: Position,
-) -> Option<Vec<TextEdit>> {
-    let doc = state.get_document(uri)?;
-    let text = doc.text();
-
-    // Simple indentation: match previous line's indentation
-    if position.line == 0 {
-        return None;
-    }
-
-    let prev_line_idx = position.line as usize - 1;
-    let lines: Vec<&str> = text.lines().collect();
-
-    if prev_line_idx >= lines.len() {
-        return None;
+    new_name: &str,
+) -> Result<Option<WorkspaceEdit>, String> {
+    if crate::reserved_words::is_reserved_word(new_name) {
+        return Err(format!(
+            "'{}' is a reserved word and cannot be used as an identifier",
+            new_name
+        ));
     }
 
-    let prev_line = lines[prev_line_idx];
-    let indent: String = prev_line
-        .chars()
-        .take_while(|c| c.is_whitespace())
-        .collect();
-
-    // Check if previous line ends with { or ( - add extra indent
-    let trimmed = prev_line.trim_end();
-    let extra_indent = if trimmed.ends_with('{') || trimmed.ends_with('(') {
-        "  "
-    } else {
-        ""
-    };
-
-    let new_indent = format!("{}{}", indent, extra_indent);
-
-    Some(vec![TextEdit {
-        range: Range {
-            start: Position::new(position.line, 0),
-            end: Position::new(position.line, 0),
-        },
-        new_text: new_indent,
-    }])
-}
-
-// ============================================================================
-// Utilities
-// ============================================================================
-
-fn node_text<'a>(node: Node<'a>, text: &'a str) -> &'a str {
-    &text[node.byte_range()]
+    Ok(rename_to(state, uri, position, new_name))
 }
 
-// ============================================================================
-// Signature Extraction (used in tests)
-// ============================================================================
-
-#[cfg(test)]
-fn extract_parameters(params_node: Node, text: &str) -> Vec<String> {
-    let mut parameters = Vec::new();
-    let mut cursor = params_node.walk();
+fn rename_to(
+    state: &WorldState,
+    uri: &Url,
+    position: Position,
+    new_name: &str,
+) -> Option<WorkspaceEdit> {
+    let content_provider = state.content_provider();
 
-    for child in params_node.children(&mut cursor) {
-        if child.kind() == "parameter" {
-            let mut param_cursor = child.walk();
-            let param_children: Vec<_> = child.children(&mut param_cursor).collect();
+    let doc = state.get_document_or_workspace(uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
 
-            // Check if this parameter contains dots
-            if let Some(_dots) = param_children.iter().find(|n| n.kind() == "dots") {
-                parameters.push("...".to_string());
-            } else if let Some(identifier) =
-                param_children.iter().find(|n| n.kind() == "identifier")
-            {
-                let param_name = node_text(*identifier, text);
+    let point = Point::new(position.line as usize, position.character as usize);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
 
-                // Check for default value
-                if param_children.len() >= 3 && param_children[1].kind() == "=" {
-                    let default_value = node_text(param_children[2], text);
-                    parameters.push(format!("{} = {}", param_name, default_value));
-                } else {
-                    parameters.push(param_name.to_string());
-                }
-            }
-        } else if child.kind() == "dots" {
-            parameters.push("...".to_string());
-        }
+    if node.kind() != "identifier" {
+        return None;
     }
 
-    parameters
-}
+    if classify_identifier(node, &text, &UsageContext::default()) == IdentifierRole::Skipped {
+        return None;
+    }
 
-#[cfg(test)]
-fn extract_function_signature(func_node: Node, func_name: &str, text: &str) -> String {
-    let mut cursor = func_node.walk();
+    let name = node_text(node, &text);
+    let mut locations = Vec::new();
 
-    for child in func_node.children(&mut cursor) {
-        if child.kind() == "parameters" {
-            let params = extract_parameters(child, text);
-            return format!("{}({})", func_name, params.join(", "));
+    // Search current document. Prefer scope-aware resolution (from the exact
+    // cursor position) so a shadowed local doesn't pull in unrelated
+    // same-named identifiers elsewhere in the file; fall back to the
+    // unscoped scan for free symbols (builtins, undefined globals).
+    let local_scopes = LocalScopeTree::build(tree.root_node(), &text);
+    if let Some(binding) = local_scopes.resolve_at(point, name) {
+        for occurrence in
+            collect_renameable_occurrences(tree.root_node(), &text, &local_scopes, binding, name)
+        {
+            locations.push(Location {
+                uri: uri.clone(),
+                range: node_range(occurrence),
+            });
         }
+    } else {
+        find_references_in_tree(tree.root_node(), name, &text, uri, true, &mut locations);
     }
 
-    format!("{}()", func_name)
-}
-
-#[cfg(test)]
-fn find_function_definition_node<'a>(node: Node<'a>, name: &str, text: &str) -> Option<Node<'a>> {
-    if node.kind() == "binary_operator" {
-        let mut cursor = node.walk();
-        let children: Vec<_> = node.children(&mut cursor).collect();
-
-        if children.len() >= 3 {
-            let lhs = children[0];
-            let op = children[1];
-            let rhs = children[2];
-
-            let op_text = node_text(op, text);
-            if matches!(op_text, "<-" | "=" | "<<-")
-                && lhs.kind() == "identifier"
-                && node_text(lhs, text) == name
-                && rhs.kind() == "function_definition"
-            {
-                return Some(rhs);
+    // Search all open documents using new DocumentStore
+    for file_uri in state.document_store.uris() {
+        if &file_uri == uri {
+            continue; // Already searched
+        }
+        if let Some(content) = content_provider.get_content(&file_uri) {
+            // Parse the content to search for references
+            if let Some(doc_state) = state.document_store.get_without_touch(&file_uri) {
+                if let Some(tree) = &doc_state.tree {
+                    find_references_in_tree(
+                        tree.root_node(),
+                        name,
+                        &content,
+                        &file_uri,
+                        true,
+                        &mut locations,
+                    );
+                }
             }
         }
     }
 
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        if let Some(func_node) = find_function_definition_node(child, name, text) {
-            return Some(func_node);
+    // Search workspace index using new WorkspaceIndex
+    for (file_uri, entry) in state.workspace_index_new.iter() {
+        if &file_uri == uri {
+            continue; // Already searched
+        }
+        if let Some(tree) = &entry.tree {
+            let file_text = entry.contents.to_string();
+            find_references_in_tree(
+                tree.root_node(),
+                name,
+                &file_text,
+                &file_uri,
+                true,
+                &mut locations,
+            );
         }
     }
 
-    None
-}
-
-#[cfg(test)]
-fn find_user_function_signature(
-    state: &WorldState,
-    current_uri: &Url,
-    name: &str,
-) -> Option<String> {
-    // 1. Search current document
-    if let Some(doc) = state.get_document(current_uri) {
+    // Fallback: Search legacy open documents
+    for entry in state.documents.iter() {
+        let (file_uri, doc) = (entry.key(), entry.value());
+        if file_uri == uri {
+            continue; // Already searched
+        }
+        // Skip if already found in new stores
+        if state.document_store.contains(file_uri) {
+            continue;
+        }
         if let Some(tree) = &doc.tree {
-            let text = doc.text();
-            if let Some(func_node) = find_function_definition_node(tree.root_node(), name, &text) {
-                return Some(extract_function_signature(func_node, name, &text));
-            }
+            let file_text = doc.text();
+            find_references_in_tree(
+                tree.root_node(),
+                name,
+                &file_text,
+                file_uri,
+                true,
+                &mut locations,
+            );
         }
     }
 
-    // 2. Search open documents (skip current_uri)
-    for (uri, doc) in &state.documents {
-        if uri == current_uri {
+    // Fallback: Search legacy workspace index
+    for (file_uri, doc) in &state.workspace_index {
+        if file_uri == uri {
+            continue; // Already searched
+        }
+        // Skip if already found in new stores
+        if state.workspace_index_new.contains(file_uri) {
             continue;
         }
         if let Some(tree) = &doc.tree {
-            let text = doc.text();
-            if let Some(func_node) = find_function_definition_node(tree.root_node(), name, &text) {
-                return Some(extract_function_signature(func_node, name, &text));
-            }
+            let file_text = doc.text();
+            find_references_in_tree(
+                tree.root_node(),
+                name,
+                &file_text,
+                file_uri,
+                true,
+                &mut locations,
+            );
         }
     }
 
-    // 3. Search workspace index
-    for doc in state.workspace_index.values() {
-        if let Some(tree) = &doc.tree {
-            let text = doc.text();
-            if let Some(func_node) = find_function_definition_node(tree.root_node(), name, &text) {
-                return Some(extract_function_signature(func_node, name, &text));
-            }
-        }
+    if locations.is_empty() {
+        return None;
     }
 
-    None
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for location in locations {
+        changes.entry(location.uri).or_default().push(TextEdit {
+            range: location.range,
+            new_text: new_name.to_string(),
+        });
+    }
+
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    })
 }
 
 // ============================================================================
-// Path Utilities
+// Call Hierarchy
 // ============================================================================
 
-/// Compute relative path from workspace root to target URI.
-/// If no workspace root or target is outside workspace, returns filename only.
-fn compute_relative_path(target_uri: &Url, workspace_root: Option<&Url>) -> String {
-    let Some(workspace_root) = workspace_root else {
-        return target_uri
-            .path_segments()
-            .and_then(|mut segments| segments.next_back())
-            .unwrap_or("unknown")
-            .to_string();
-    };
-
-    let Ok(workspace_path) = workspace_root.to_file_path() else {
-        return target_uri
-            .path_segments()
-            .and_then(|mut segments| segments.next_back())
-            .unwrap_or("unknown")
-            .to_string();
-    };
-
-    let Ok(target_path) = target_uri.to_file_path() else {
-        return target_uri
-            .path_segments()
-            .and_then(|mut segments| segments.next_back())
-            .unwrap_or("unknown")
-            .to_string();
-    };
+fn node_range(node: Node) -> Range {
+    Range {
+        start: Position::new(
+            node.start_position().row as u32,
+            node.start_position().column as u32,
+        ),
+        end: Position::new(
+            node.end_position().row as u32,
+            node.end_position().column as u32,
+        ),
+    }
+}
 
-    match target_path.strip_prefix(&workspace_path) {
-        Ok(relative) => relative.to_string_lossy().to_string(),
-        Err(_) => target_uri
-            .path_segments()
-            .and_then(|mut segments| segments.next_back())
-            .unwrap_or("unknown")
-            .to_string(),
+/// Fetches the parsed tree and content for a symbol's defining file, using the
+/// same tiers `extract_definition_statement` uses to reach it: open documents
+/// first, then the cross-file content cache with an on-the-spot parse.
+fn with_symbol_source<T>(
+    state: &WorldState,
+    symbol: &ScopedSymbol,
+    f: impl FnOnce(&tree_sitter::Tree, &str) -> Option<T>,
+) -> Option<T> {
+    if let Some(doc) = state.documents.get(&symbol.source_uri) {
+        let tree = doc.tree.as_ref()?;
+        return f(tree, &doc.text());
     }
+
+    let content = state.cross_file_file_cache.get(&symbol.source_uri)?;
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&tree_sitter_r::LANGUAGE.into()).ok()?;
+    let tree = parser.parse(&content, None)?;
+    f(&tree, &content)
 }
 
-// Note: escape_markdown is only used in tests now.
-// Code blocks (```r ... ```) don't need escaping - markdown doesn't interpret special chars inside them.
-#[cfg(test)]
-/// Escape markdown special characters in text.
-/// Characters to escape: * _ [ ] ( ) # ` \
-fn escape_markdown(text: &str) -> String {
-    text.chars()
-        .map(|c| match c {
-            '*' | '_' | '[' | ']' | '(' | ')' | '#' | '`' | '\\' => format!("\\{}", c),
-            _ => c.to_string(),
-        })
-        .collect()
+/// Locates the `function_definition`/`binary_operator` statement node at a
+/// symbol's defined position, reusing `find_function_statement`'s logic for
+/// mapping a symbol onto its enclosing function statement.
+fn function_definition_node<'a>(
+    tree: &'a tree_sitter::Tree,
+    symbol: &ScopedSymbol,
+    content: &str,
+) -> Option<Node<'a>> {
+    let line_text = content
+        .lines()
+        .nth(symbol.defined_line as usize)
+        .unwrap_or("");
+    let byte_col = utf16_column_to_byte_offset(line_text, symbol.defined_column);
+    let row = symbol.defined_line as usize;
+    let point_start = Point::new(row, byte_col);
+    let point_end = Point::new(row, next_utf8_char_boundary(line_text, byte_col));
+
+    let root = tree.root_node();
+    let node = root
+        .named_descendant_for_point_range(point_start, point_end)
+        .or_else(|| root.descendant_for_point_range(point_start, point_end))?;
+
+    Some(find_function_statement(node, content)?.node)
 }
 
-#[cfg(test)]
-fn hover_blocking(state: &WorldState, uri: &Url, position: Position) -> Option<Hover> {
-    if let Ok(handle) = tokio::runtime::Handle::try_current() {
-        handle.block_on(hover(state, uri, position))
-    } else {
-        tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap()
-            .block_on(hover(state, uri, position))
-    }
+/// Builds a `CallHierarchyItem` for a resolved function symbol: `range` spans
+/// the whole definition statement, `selection_range` just the name.
+fn call_hierarchy_item_for_symbol(
+    state: &WorldState,
+    symbol: &ScopedSymbol,
+) -> Option<CallHierarchyItem> {
+    with_symbol_source(state, symbol, |tree, content| {
+        let func_node = function_definition_node(tree, symbol, content)?;
+        let name_len = symbol.name.chars().map(|c| c.len_utf16() as u32).sum::<u32>();
+
+        Some(CallHierarchyItem {
+            name: symbol.name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri: symbol.source_uri.clone(),
+            range: node_range(func_node),
+            selection_range: Range {
+                start: Position::new(symbol.defined_line, symbol.defined_column),
+                end: Position::new(symbol.defined_line, symbol.defined_column + name_len),
+            },
+            data: None,
+        })
+    })
 }
 
-// ============================================================================
-// Tests
-// ============================================================================
+/// `prepareCallHierarchy`: resolves the identifier under the cursor to a
+/// defined function symbol, reusing the same position-aware scope resolution
+/// `goto_definition` uses. Package exports (`package:` pseudo-URIs) have no
+/// navigable definition and are skipped.
+pub fn prepare_call_hierarchy(
+    state: &WorldState,
+    uri: &Url,
+    position: Position,
+) -> Option<Vec<CallHierarchyItem>> {
+    let doc = state.get_document_or_workspace(uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashSet;
+    let point = Point::new(position.line as usize, position.character as usize);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+    if node.kind() != "identifier" {
+        return None;
+    }
+    let name = node_text(node, &text);
 
-    fn parse_r_code(code: &str) -> tree_sitter::Tree {
-        let mut parser = tree_sitter::Parser::new();
-        parser
-            .set_language(&tree_sitter_r::LANGUAGE.into())
-            .unwrap();
-        parser.parse(code, None).unwrap()
+    let scope = get_cross_file_scope(state, uri, position.line, position.character);
+    let symbol = scope.symbols.get(name)?;
+    if symbol.source_uri.as_str().starts_with("package:") {
+        return None;
+    }
+    if !matches!(symbol.kind, scope::SymbolKind::Function) {
+        return None;
     }
 
-    #[test]
-    fn test_function_parameters_recognized() {
-        let code = "f <- function(a, b) { a + b }";
-        let tree = parse_r_code(code);
-        let mut defined = HashSet::new();
-        collect_definitions(tree.root_node(), code, &mut defined);
+    let item = call_hierarchy_item_for_symbol(state, symbol)?;
+    Some(vec![item])
+}
 
-        assert!(defined.contains("f"), "Function name should be defined");
-        assert!(defined.contains("a"), "Parameter 'a' should be defined");
-        assert!(defined.contains("b"), "Parameter 'b' should be defined");
+/// If `node` is a named function definition (`name <- function(...)` or the
+/// right-assign form `function(...) -> name`), returns its name and the
+/// identifier node naming it. Anonymous definitions return `None` since they
+/// have no symbol to key incoming/outgoing calls by.
+fn named_function<'a>(node: Node<'a>, text: &str) -> Option<(String, Node<'a>)> {
+    if node.kind() != "binary_operator" {
+        return None;
     }
 
-    #[test]
-    fn test_single_parameter() {
-        let code = "square <- function(x) { x * x }";
-        let tree = parse_r_code(code);
-        let mut defined = HashSet::new();
-        collect_definitions(tree.root_node(), code, &mut defined);
-
-        assert!(defined.contains("square"));
-        assert!(defined.contains("x"));
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.children(&mut cursor).collect();
+    if children.len() < 3 {
+        return None;
     }
+    let (lhs, op, rhs) = (children[0], children[1], children[2]);
+    let op_text = node_text(op, text);
 
-    #[test]
-    fn test_no_parameters() {
-        let code = "get_pi <- function() { 3.14 }";
-        let tree = parse_r_code(code);
-        let mut defined = HashSet::new();
-        collect_definitions(tree.root_node(), code, &mut defined);
-
-        assert!(defined.contains("get_pi"));
+    if matches!(op_text, "<-" | "=" | "<<-") && lhs.kind() == "identifier" && rhs.kind() == "function_definition" {
+        return Some((node_text(lhs, text).to_string(), lhs));
     }
-
-    #[test]
-    fn test_builtin_functions() {
-        assert!(is_builtin("warning"));
-        assert!(is_builtin("any"));
-        assert!(is_builtin("is.na"));
-        assert!(is_builtin("sprintf"));
-        assert!(is_builtin("print"));
-        assert!(is_builtin("sum"));
-        assert!(is_builtin("mean"));
+    if op_text == "->" && lhs.kind() == "function_definition" && rhs.kind() == "identifier" {
+        return Some((node_text(rhs, text).to_string(), rhs));
     }
 
-    #[test]
-    fn test_builtin_constants() {
-        assert!(is_builtin("TRUE"));
-        assert!(is_builtin("FALSE"));
-        assert!(is_builtin("NULL"));
-        assert!(is_builtin("NA"));
-        assert!(is_builtin("Inf"));
-        assert!(is_builtin("NaN"));
-    }
+    None
+}
 
-    #[test]
-    fn test_not_builtin() {
-        assert!(!is_builtin("my_custom_function"));
-        assert!(!is_builtin("undefined_var"));
+/// Name of the pseudo-caller used to group calls made from top-level script
+/// code (outside any named function), since the root script is the implicit
+/// caller of such calls and otherwise has no symbol to key them by.
+const SCRIPT_LEVEL_CALLER_NAME: &str = "(top level)";
+
+/// Recursively finds `call` nodes whose callee is `target_name`, grouping
+/// call-site ranges by the enclosing named function that makes the call. A
+/// call made at the top level of the file (no enclosing function at all) is
+/// grouped under `SCRIPT_LEVEL_CALLER_NAME` instead of being dropped; a call
+/// nested inside an anonymous function is still dropped, since there's no
+/// symbol to attribute it to. `root_range` is the whole-document range used
+/// for the pseudo-caller's `range`/`selection_range`.
+fn collect_incoming_calls(
+    node: Node,
+    target_name: &str,
+    text: &str,
+    file_uri: &Url,
+    root_range: Range,
+    grouped: &mut HashMap<(Url, String), (Range, Range, Vec<Range>)>,
+) {
+    if node.kind() == "call" {
+        if let Some(func_node) = node.child_by_field_name("function") {
+            if func_node.kind() == "identifier" && node_text(func_node, text) == target_name {
+                let caller = match find_enclosing_function(func_node) {
+                    Some(caller) => named_function(caller, text)
+                        .map(|(name, name_node)| (name, node_range(caller), node_range(name_node))),
+                    None => Some((SCRIPT_LEVEL_CALLER_NAME.to_string(), root_range, root_range)),
+                };
+                if let Some((caller_name, caller_range, selection_range)) = caller {
+                    let key = (file_uri.clone(), caller_name);
+                    let entry = grouped
+                        .entry(key)
+                        .or_insert_with(|| (caller_range, selection_range, Vec::new()));
+                    entry.2.push(node_range(func_node));
+                }
+            }
+        }
     }
 
-    #[test]
-    fn test_nested_function_parameters() {
-        let code = "outer <- function(x) { inner <- function(y) { x + y }; inner }";
-        let tree = parse_r_code(code);
-        let mut defined = HashSet::new();
-        collect_definitions(tree.root_node(), code, &mut defined);
-
-        assert!(defined.contains("outer"));
-        assert!(defined.contains("x"));
-        assert!(defined.contains("inner"));
-        assert!(defined.contains("y"));
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_incoming_calls(child, target_name, text, file_uri, root_range, grouped);
     }
+}
 
-    #[test]
-    fn test_extract_parameters_simple() {
-        let code = "add <- function(a, b = 1) { }";
-        let tree = parse_r_code(code);
-
-        let func_node = find_function_definition(tree.root_node()).unwrap();
-        let mut cursor = func_node.walk();
-        let params_node = func_node
-            .children(&mut cursor)
-            .find(|n| n.kind() == "parameters")
-            .unwrap();
+/// `callHierarchy/incomingCalls`: scans open documents and the workspace
+/// index for calls to `item`, grouped by caller.
+pub fn call_hierarchy_incoming_calls(
+    state: &WorldState,
+    item: &CallHierarchyItem,
+) -> Option<Vec<CallHierarchyIncomingCall>> {
+    let mut grouped: HashMap<(Url, String), (Range, Range, Vec<Range>)> = HashMap::new();
 
-        let params = extract_parameters(params_node, code);
-        assert_eq!(params, vec!["a", "b = 1"]);
+    if let Some(doc) = state.get_document(&item.uri) {
+        if let Some(tree) = &doc.tree {
+            let root_range = node_range(tree.root_node());
+            collect_incoming_calls(
+                tree.root_node(),
+                &item.name,
+                &doc.text(),
+                &item.uri,
+                root_range,
+                &mut grouped,
+            );
+        }
     }
 
-    #[test]
-    fn test_extract_function_signature() {
-        let code = "add <- function(a, b = 1) { }";
-        let tree = parse_r_code(code);
-
-        let func_node = find_function_definition(tree.root_node()).unwrap();
-        let signature = extract_function_signature(func_node, "add", code);
-        assert_eq!(signature, "add(a, b = 1)");
+    for file_uri in state.document_store.uris() {
+        if file_uri == item.uri {
+            continue;
+        }
+        if let Some(doc_state) = state.document_store.get_without_touch(&file_uri) {
+            if let Some(tree) = &doc_state.tree {
+                let root_range = node_range(tree.root_node());
+                collect_incoming_calls(
+                    tree.root_node(),
+                    &item.name,
+                    &doc_state.text(),
+                    &file_uri,
+                    root_range,
+                    &mut grouped,
+                );
+            }
+        }
     }
 
-    #[test]
-    fn test_signature_simple_function() {
-        let code = "add <- function(a, b) { a + b }";
-        let tree = parse_r_code(code);
-
-        let func_node = find_function_definition_node(tree.root_node(), "add", code).unwrap();
-        let signature = extract_function_signature(func_node, "add", code);
-        assert_eq!(signature, "add(a, b)");
+    for (file_uri, entry) in state.workspace_index_new.iter() {
+        if file_uri == item.uri {
+            continue;
+        }
+        if let Some(tree) = &entry.tree {
+            let file_text = entry.contents.to_string();
+            let root_range = node_range(tree.root_node());
+            collect_incoming_calls(
+                tree.root_node(),
+                &item.name,
+                &file_text,
+                &file_uri,
+                root_range,
+                &mut grouped,
+            );
+        }
     }
 
-    #[test]
-    fn test_signature_no_parameters() {
-        let code = "get_pi <- function() { 3.14 }";
-        let tree = parse_r_code(code);
-
-        let func_node = find_function_definition_node(tree.root_node(), "get_pi", code).unwrap();
-        let signature = extract_function_signature(func_node, "get_pi", code);
-        assert_eq!(signature, "get_pi()");
+    for entry in state.documents.iter() {
+        let (file_uri, doc) = (entry.key(), entry.value());
+        if file_uri == &item.uri || state.document_store.contains(file_uri) {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            let file_text = doc.text();
+            let root_range = node_range(tree.root_node());
+            collect_incoming_calls(
+                tree.root_node(),
+                &item.name,
+                &file_text,
+                file_uri,
+                root_range,
+                &mut grouped,
+            );
+        }
     }
 
-    #[test]
-    fn test_signature_with_defaults() {
-        let code = "greet <- function(name = \"World\") { }";
-        let tree = parse_r_code(code);
-
-        let func_node = find_function_definition_node(tree.root_node(), "greet", code).unwrap();
-        let signature = extract_function_signature(func_node, "greet", code);
-        assert_eq!(signature, "greet(name = \"World\")");
+    for (file_uri, doc) in &state.workspace_index {
+        if file_uri == &item.uri || state.workspace_index_new.contains(file_uri) {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            let file_text = doc.text();
+            let root_range = node_range(tree.root_node());
+            collect_incoming_calls(
+                tree.root_node(),
+                &item.name,
+                &file_text,
+                file_uri,
+                root_range,
+                &mut grouped,
+            );
+        }
     }
 
-    #[test]
-    fn test_signature_with_dots() {
-        let code = "wrapper <- function(...) { }";
-        let tree = parse_r_code(code);
+    Some(
+        grouped
+            .into_iter()
+            .map(
+                |((uri, name), (range, selection_range, from_ranges))| CallHierarchyIncomingCall {
+                    from: CallHierarchyItem {
+                        name,
+                        kind: SymbolKind::FUNCTION,
+                        tags: None,
+                        detail: None,
+                        uri,
+                        range,
+                        selection_range,
+                        data: None,
+                    },
+                    from_ranges,
+                },
+            )
+            .collect(),
+    )
+}
 
-        let func_node = find_function_definition_node(tree.root_node(), "wrapper", code).unwrap();
-        let signature = extract_function_signature(func_node, "wrapper", code);
-        assert_eq!(signature, "wrapper(...)");
+/// Recursively walks a function body collecting its outgoing calls, resolving
+/// each callee through the same position-aware scope `goto_definition` uses
+/// so locals and shadowed names resolve correctly.
+fn collect_outgoing_calls(
+    state: &WorldState,
+    uri: &Url,
+    node: Node,
+    text: &str,
+    calls: &mut HashMap<(Url, String), (CallHierarchyItem, Vec<Range>)>,
+) {
+    if node.kind() == "call" {
+        if let Some(func_node) = node.child_by_field_name("function") {
+            if func_node.kind() == "identifier" {
+                let name = node_text(func_node, text);
+                let call_pos = Position::new(
+                    func_node.start_position().row as u32,
+                    func_node.start_position().column as u32,
+                );
+                let scope = get_cross_file_scope(state, uri, call_pos.line, call_pos.character);
+                if let Some(symbol) = scope.symbols.get(name) {
+                    if !symbol.source_uri.as_str().starts_with("package:")
+                        && matches!(symbol.kind, scope::SymbolKind::Function)
+                    {
+                        if let Some(target_item) = call_hierarchy_item_for_symbol(state, symbol) {
+                            let key = (symbol.source_uri.clone(), name.to_string());
+                            let entry = calls.entry(key).or_insert_with(|| (target_item, Vec::new()));
+                            entry.1.push(node_range(func_node));
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    #[test]
-    fn test_compute_relative_path_with_workspace_root() {
-        let workspace_root = Url::parse("file:///workspace/").unwrap();
-        let target_uri = Url::parse("file:///workspace/src/main.R").unwrap();
-
-        let result = compute_relative_path(&target_uri, Some(&workspace_root));
-        assert_eq!(result, "src/main.R");
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_outgoing_calls(state, uri, child, text, calls);
     }
+}
 
-    #[test]
-    fn test_compute_relative_path_without_workspace_root() {
-        let target_uri = Url::parse("file:///workspace/src/main.R").unwrap();
+/// `callHierarchy/outgoingCalls`: walks `item`'s body and resolves each call
+/// it makes, grouped by callee.
+pub fn call_hierarchy_outgoing_calls(
+    state: &WorldState,
+    item: &CallHierarchyItem,
+) -> Option<Vec<CallHierarchyOutgoingCall>> {
+    let doc = state.get_document_or_workspace(&item.uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
 
-        let result = compute_relative_path(&target_uri, None);
-        assert_eq!(result, "main.R");
-    }
+    let point = Point::new(
+        item.range.start.line as usize,
+        item.range.start.character as usize,
+    );
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+    let func_node = find_enclosing_function(node)?;
 
-    #[test]
-    fn test_compute_relative_path_outside_workspace() {
-        let workspace_root = Url::parse("file:///workspace/").unwrap();
-        let target_uri = Url::parse("file:///other/path/script.R").unwrap();
+    let mut calls: HashMap<(Url, String), (CallHierarchyItem, Vec<Range>)> = HashMap::new();
+    collect_outgoing_calls(state, &item.uri, func_node, &text, &mut calls);
 
-        let result = compute_relative_path(&target_uri, Some(&workspace_root));
-        assert_eq!(result, "script.R");
-    }
+    Some(
+        calls
+            .into_values()
+            .map(|(to, from_ranges)| CallHierarchyOutgoingCall { to, from_ranges })
+            .collect(),
+    )
+}
 
-    #[test]
-    fn test_escape_markdown_all_special_chars() {
-        let input = "*_[]()#`\\";
-        let expected = "\\*\\_\\[\\]\\(\\)\\#\\`\\\\";
+// ============================================================================
+// On Type Formatting (Indentation)
+// ============================================================================
 
-        let result = escape_markdown(input);
-        assert_eq!(result, expected);
+pub fn on_type_formatting(
+    state: &WorldState,
+    uri: &Url,
+    position: Position,
+) -> Option<Vec<TextEdit>> {
+    let doc = state.get_document(uri)?;
+    let text = doc.text();
+
+    // Simple indentation: match previous line's indentation
+    if position.line == 0 {
+        return None;
     }
 
-    #[test]
-    fn test_escape_markdown_no_special_chars() {
-        let input = "hello world 123";
+    let prev_line_idx = position.line as usize - 1;
+    let lines: Vec<&str> = text.lines().collect();
 
-        let result = escape_markdown(input);
-        assert_eq!(result, input);
+    if prev_line_idx >= lines.len() {
+        return None;
     }
 
-    #[test]
-    fn test_escape_markdown_mixed_content() {
-        let input = "function(x) { x * 2 }";
-        let expected = "function\\(x\\) { x \\* 2 }";
+    let prev_line = lines[prev_line_idx];
+    let indent: String = prev_line
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
 
-        let result = escape_markdown(input);
-        assert_eq!(result, expected);
-    }
+    // Check if previous line ends with { or ( - add extra indent
+    let trimmed = prev_line.trim_end();
+    let extra_indent = if trimmed.ends_with('{') || trimmed.ends_with('(') {
+        "  "
+    } else {
+        ""
+    };
 
-    fn find_function_definition(node: Node) -> Option<Node> {
-        if node.kind() == "function_definition" {
-            return Some(node);
-        }
+    let new_indent = format!("{}{}", indent, extra_indent);
 
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if let Some(func) = find_function_definition(child) {
-                return Some(func);
-            }
-        }
-        None
-    }
+    Some(vec![TextEdit {
+        range: Range {
+            start: Position::new(position.line, 0),
+            end: Position::new(position.line, 0),
+        },
+        new_text: new_indent,
+    }])
+}
 
-    // ========================================================================
-    // Extract Operator Tests (Task 6.1)
-    // Tests for skip-nse-undefined-checks feature
-    // Validates: Requirements 1.1, 1.2, 1.3
-    // ========================================================================
+// ============================================================================
+// Utilities
+// ============================================================================
 
-    /// Test that df$column does not produce a diagnostic for 'column'
-    /// Validates: Requirement 1.1 - RHS of $ operator should be skipped
-    #[test]
-    fn test_extract_operator_dollar_rhs_skipped() {
-        let code = "df$column";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+fn node_text<'a>(node: Node<'a>, text: &'a str) -> &'a str {
+    &text[node.byte_range()]
+}
 
-        // 'df' should be collected as a usage (LHS is checked)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(df_used, "LHS 'df' should be collected as usage");
+// ============================================================================
+// Signature Extraction
+// ============================================================================
+//
+// Extracts a flat `"name(p1, p2 = default, ...)"` signature string for a
+// locally-defined R function, for the local-function tier of signature help
+// (see `find_user_function_signature`, used by `prepare_signature_help`).
 
-        // 'column' should NOT be collected as a usage (RHS is skipped)
-        let column_used = used.iter().any(|(name, _)| name == "column");
-        assert!(
-            !column_used,
-            "RHS 'column' should NOT be collected as usage for $ operator"
-        );
-    }
+fn extract_parameters(params_node: Node, text: &str) -> Vec<String> {
+    let mut parameters = Vec::new();
+    let mut cursor = params_node.walk();
 
-    /// Test that obj@slot does not produce a diagnostic for 'slot'
-    /// Validates: Requirement 1.2 - RHS of @ operator should be skipped
-    #[test]
-    fn test_extract_operator_at_rhs_skipped() {
-        let code = "obj@slot";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    for child in params_node.children(&mut cursor) {
+        if child.kind() == "parameter" {
+            let mut param_cursor = child.walk();
+            let param_children: Vec<_> = child.children(&mut param_cursor).collect();
 
-        // 'obj' should be collected as a usage (LHS is checked)
-        let obj_used = used.iter().any(|(name, _)| name == "obj");
-        assert!(obj_used, "LHS 'obj' should be collected as usage");
+            // Check if this parameter contains dots
+            if let Some(_dots) = param_children.iter().find(|n| n.kind() == "dots") {
+                parameters.push("...".to_string());
+            } else if let Some(identifier) =
+                param_children.iter().find(|n| n.kind() == "identifier")
+            {
+                let param_name = node_text(*identifier, text);
 
-        // 'slot' should NOT be collected as a usage (RHS is skipped)
-        let slot_used = used.iter().any(|(name, _)| name == "slot");
-        assert!(
-            !slot_used,
-            "RHS 'slot' should NOT be collected as usage for @ operator"
-        );
+                // Check for default value
+                if param_children.len() >= 3 && param_children[1].kind() == "=" {
+                    let default_value = node_text(param_children[2], text);
+                    parameters.push(format!("{} = {}", param_name, default_value));
+                } else {
+                    parameters.push(param_name.to_string());
+                }
+            }
+        } else if child.kind() == "dots" {
+            parameters.push("...".to_string());
+        }
     }
 
-    /// Test that undefined$column produces a diagnostic for 'undefined' (LHS is still checked)
-    /// Validates: Requirement 1.3 - LHS of extract operators should still be checked
-    #[test]
-    fn test_extract_operator_lhs_checked() {
-        let code = "undefined$column";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    parameters
+}
 
-        // 'undefined' should be collected as a usage (LHS is checked)
-        let undefined_used = used.iter().any(|(name, _)| name == "undefined");
-        assert!(
-            undefined_used,
-            "LHS 'undefined' should be collected as usage"
-        );
+fn extract_function_signature(func_node: Node, func_name: &str, text: &str) -> String {
+    let mut cursor = func_node.walk();
 
-        // 'column' should NOT be collected as a usage (RHS is skipped)
-        let column_used = used.iter().any(|(name, _)| name == "column");
-        assert!(
-            !column_used,
-            "RHS 'column' should NOT be collected as usage"
-        );
+    for child in func_node.children(&mut cursor) {
+        if child.kind() == "parameters" {
+            let params = extract_parameters(child, text);
+            return format!("{}({})", func_name, params.join(", "));
+        }
     }
 
-    // ==================== Call-Like Argument Tests ====================
-    // These tests verify that identifiers inside call-like arguments are skipped
-    // (Requirements 2.1, 2.2, 2.3, 2.4)
-
-    /// Test that subset(df, x > 5) does not produce a diagnostic for 'x'
-    /// Validates: Requirement 2.1 - Identifiers inside function call arguments should be skipped
-    #[test]
-    fn test_call_arguments_skipped() {
-        let code = "subset(df, x > 5)";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    format!("{}()", func_name)
+}
 
-        // 'subset' should be collected as a usage (function name is checked)
-        let subset_used = used.iter().any(|(name, _)| name == "subset");
-        assert!(
-            subset_used,
-            "Function name 'subset' should be collected as usage"
-        );
+fn find_function_definition_node<'a>(node: Node<'a>, name: &str, text: &str) -> Option<Node<'a>> {
+    if node.kind() == "binary_operator" {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
 
-        // 'df' should NOT be collected as a usage (inside call arguments)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(
-            !df_used,
-            "'df' inside call arguments should NOT be collected as usage"
-        );
+        if children.len() >= 3 {
+            let lhs = children[0];
+            let op = children[1];
+            let rhs = children[2];
 
-        // 'x' should NOT be collected as a usage (inside call arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside call arguments should NOT be collected as usage"
-        );
+            let op_text = node_text(op, text);
+            if matches!(op_text, "<-" | "=" | "<<-")
+                && lhs.kind() == "identifier"
+                && node_text(lhs, text) == name
+                && rhs.kind() == "function_definition"
+            {
+                return Some(rhs);
+            }
+        }
     }
 
-    /// Test that df[x > 5, ] does not produce a diagnostic for 'x'
-    /// Validates: Requirement 2.2 - Identifiers inside subset ([) arguments should be skipped
-    #[test]
-    fn test_subset_arguments_skipped() {
-        let code = "df[x > 5, ]";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if let Some(func_node) = find_function_definition_node(child, name, text) {
+            return Some(func_node);
+        }
+    }
 
-        // 'df' should be collected as a usage (the object being subsetted is checked)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(
-            df_used,
-            "'df' (object being subsetted) should be collected as usage"
-        );
+    None
+}
 
-        // 'x' should NOT be collected as a usage (inside subset arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside subset arguments should NOT be collected as usage"
-        );
+/// Finds a locally-defined R function (in the current document, other open
+/// documents, or the workspace index) and extracts its flat signature
+/// string. Used as the local-function tier of signature help, ahead of the
+/// package/help-text fallback.
+fn find_user_function_signature(
+    state: &WorldState,
+    current_uri: &Url,
+    name: &str,
+) -> Option<String> {
+    // 1. Search current document
+    if let Some(doc) = state.get_document(current_uri) {
+        if let Some(tree) = &doc.tree {
+            let text = doc.text();
+            if let Some(func_node) = find_function_definition_node(tree.root_node(), name, &text) {
+                return Some(extract_function_signature(func_node, name, &text));
+            }
+        }
     }
 
-    /// Test that df[[x]] does not produce a diagnostic for 'x'
-    /// Validates: Requirement 2.3 - Identifiers inside subset2 ([[) arguments should be skipped
-    #[test]
-    fn test_subset2_arguments_skipped() {
-        let code = "df[[x]]";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
-
-        // 'df' should be collected as a usage (the object being subsetted is checked)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(
-            df_used,
-            "'df' (object being subsetted) should be collected as usage"
-        );
+    // 2. Search open documents (skip current_uri)
+    for entry in state.documents.iter() {
+        let (uri, doc) = (entry.key(), entry.value());
+        if uri == current_uri {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            let text = doc.text();
+            if let Some(func_node) = find_function_definition_node(tree.root_node(), name, &text) {
+                return Some(extract_function_signature(func_node, name, &text));
+            }
+        }
+    }
 
-        // 'x' should NOT be collected as a usage (inside subset2 arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside subset2 arguments should NOT be collected as usage"
-        );
+    // 3. Search workspace index
+    for doc in state.workspace_index.values() {
+        if let Some(tree) = &doc.tree {
+            let text = doc.text();
+            if let Some(func_node) = find_function_definition_node(tree.root_node(), name, &text) {
+                return Some(extract_function_signature(func_node, name, &text));
+            }
+        }
     }
 
-    /// Test that undefined_func(x) produces a diagnostic for 'undefined_func'
-    /// Validates: Requirement 2.4 - Function names should still be checked
-    #[test]
-    fn test_function_name_checked() {
-        let code = "undefined_func(x)";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    None
+}
 
-        // 'undefined_func' should be collected as a usage (function name is checked)
-        let func_used = used.iter().any(|(name, _)| name == "undefined_func");
-        assert!(
-            func_used,
-            "Function name 'undefined_func' should be collected as usage"
-        );
-
-        // 'x' should NOT be collected as a usage (inside call arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside call arguments should NOT be collected as usage"
-        );
+// ============================================================================
+// Mismatched Argument Count
+// ============================================================================
+//
+// Mirrors rust-analyzer's `mismatched_arg_count`: for each call to a
+// locally-defined function, resolves the callee via
+// `find_user_function_signature` and parses its parameter list with the same
+// `split_signature_parameters`/`parameter_name` helpers signature help
+// already uses, rather than re-deriving parameter shape from the AST.
+// Flags too few arguments for the function's required (no-default)
+// parameters, and - when the function has no `...` - too many positional
+// arguments. Named arguments are matched against parameter names first;
+// a name that matches no parameter doesn't consume a positional slot either
+// way, since (with no `...`) that call would already fail in R for an
+// unrelated reason (an unused argument) that this detector doesn't cover.
+
+fn collect_arg_count_diagnostics(
+    state: &WorldState,
+    uri: &Url,
+    node: Node,
+    text: &str,
+    meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if node.kind() == "call" {
+        if let Some(func_node) = node.child_by_field_name("function") {
+            if func_node.kind() == "identifier" {
+                let func_name = node_text(func_node, text);
+                if let Some(signature) = find_user_function_signature(state, uri, func_name) {
+                    let params = split_signature_parameters(&signature);
+                    check_call_arg_count(
+                        node,
+                        func_node,
+                        text,
+                        func_name,
+                        &params,
+                        meta,
+                        diagnostics,
+                    );
+                }
+            }
+        }
     }
 
-    // ==================== Formula Tests (Task 6.3) ====================
-    // These tests verify that identifiers inside formula expressions are skipped
-    // (Requirements 3.1, 3.2, 3.4)
-
-    /// Test that ~ x does not produce a diagnostic for 'x'
-    /// Validates: Requirement 3.1 - Identifiers inside unary formula expressions should be skipped
-    #[test]
-    fn test_unary_formula_skipped() {
-        let code = "~ x";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_arg_count_diagnostics(state, uri, child, text, meta, diagnostics);
+    }
+}
 
-        // 'x' should NOT be collected as a usage (inside formula)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside unary formula should NOT be collected as usage"
-        );
+/// Checks one call site's argument count against `params` (as returned by
+/// `split_signature_parameters`) and pushes a diagnostic for too few or too
+/// many arguments.
+fn check_call_arg_count(
+    call_node: Node,
+    func_node: Node,
+    text: &str,
+    func_name: &str,
+    params: &[String],
+    meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if crate::cross_file::directive::is_line_ignored(meta, call_node.start_position().row as u32) {
+        return;
     }
 
-    /// Test that y ~ x + z does not produce diagnostics for 'y', 'x', 'z'
-    /// Validates: Requirement 3.2 - Identifiers inside binary formula expressions should be skipped
-    #[test]
-    fn test_binary_formula_skipped() {
-        let code = "y ~ x + z";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    // The diagnostic should point at the supplied arguments, not the callee
+    // name - that's what's wrong, and what a fix would need to touch. Falls
+    // back to the function name if the call has no "arguments" field at all
+    // (shouldn't happen for a "call" node, but `child_by_field_name` is
+    // fallible).
+    let range_node = call_node
+        .child_by_field_name("arguments")
+        .unwrap_or(func_node);
+
+    let has_dots = params.iter().any(|p| p == "...");
+    let named_params: Vec<&str> = params
+        .iter()
+        .filter(|p| *p != "...")
+        .map(|p| parameter_name(p))
+        .collect();
+    let required_params: Vec<&str> = params
+        .iter()
+        .filter(|p| *p != "..." && !p.contains(" = "))
+        .map(|p| parameter_name(p))
+        .collect();
 
-        // 'y' should NOT be collected as a usage (LHS of formula)
-        let y_used = used.iter().any(|(name, _)| name == "y");
-        assert!(
-            !y_used,
-            "'y' inside binary formula should NOT be collected as usage"
-        );
+    let mut positional_count = 0usize;
+    let mut named_total = 0usize;
+    let mut matched_names = std::collections::HashSet::new();
 
-        // 'x' should NOT be collected as a usage (RHS of formula)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside binary formula should NOT be collected as usage"
-        );
+    if let Some(args_node) = call_node.child_by_field_name("arguments") {
+        let mut cursor = args_node.walk();
+        for arg in args_node.children(&mut cursor) {
+            if arg.kind() != "argument" {
+                continue;
+            }
+            if let Some(name_node) = arg.child_by_field_name("name") {
+                named_total += 1;
+                let name = node_text(name_node, text);
+                if named_params.contains(&name) {
+                    matched_names.insert(name);
+                }
+            } else {
+                positional_count += 1;
+            }
+        }
+    }
 
-        // 'z' should NOT be collected as a usage (RHS of formula)
-        let z_used = used.iter().any(|(name, _)| name == "z");
-        assert!(
-            !z_used,
-            "'z' inside binary formula should NOT be collected as usage"
+    let total_supplied = positional_count + named_total;
+    let unmatched_required = required_params
+        .iter()
+        .filter(|name| !matched_names.contains(*name))
+        .count();
+
+    if positional_count < unmatched_required {
+        push_arg_count_diagnostic(
+            range_node,
+            format!(
+                "Function '{}' requires at least {} argument(s) but {} were supplied",
+                func_name,
+                required_params.len(),
+                total_supplied
+            ),
+            diagnostics,
         );
+        return;
     }
 
-    /// Test that lm(y ~ x, data = df) does not produce diagnostics for 'y', 'x'
-    /// Validates: Requirement 3.4 - Formulas nested inside call arguments should have both contexts apply
-    #[test]
-    fn test_formula_inside_call_arguments_skipped() {
-        let code = "lm(y ~ x, data = df)";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    if !has_dots {
+        let remaining_slots = named_params.len() - matched_names.len();
+        if positional_count > remaining_slots {
+            push_arg_count_diagnostic(
+                range_node,
+                format!(
+                    "Function '{}' accepts at most {} argument(s) but {} were supplied",
+                    func_name,
+                    named_params.len(),
+                    total_supplied
+                ),
+                diagnostics,
+            );
+        }
+    }
+}
 
-        // 'lm' should be collected as a usage (function name is checked)
-        let lm_used = used.iter().any(|(name, _)| name == "lm");
-        assert!(lm_used, "Function name 'lm' should be collected as usage");
+fn push_arg_count_diagnostic(range_node: Node, message: String, diagnostics: &mut Vec<Diagnostic>) {
+    let (code, code_description) = diagnostic_code(diagnostic_codes::ARG_COUNT_MISMATCH);
+    diagnostics.push(Diagnostic {
+        range: Range {
+            start: Position::new(
+                range_node.start_position().row as u32,
+                range_node.start_position().column as u32,
+            ),
+            end: Position::new(
+                range_node.end_position().row as u32,
+                range_node.end_position().column as u32,
+            ),
+        },
+        severity: Some(DiagnosticSeverity::ERROR),
+        code,
+        code_description,
+        message,
+        ..Default::default()
+    });
+}
 
-        // 'y' should NOT be collected as a usage (inside formula inside call arguments)
-        let y_used = used.iter().any(|(name, _)| name == "y");
-        assert!(
-            !y_used,
-            "'y' inside formula in call arguments should NOT be collected as usage"
-        );
+// ============================================================================
+// Unused Definitions
+// ============================================================================
+//
+// Mirrors rust-analyzer's `unused` tag: for each `<-`/`=` assignment to a
+// plain identifier, resolves the binding via `LocalScopeTree` and asks
+// `collect_scoped_occurrences` whether anything - including a nested
+// function body, since scoping is respected rather than a flat name match -
+// ever reads it back. `<<-` is left alone, since it's reaching into an
+// enclosing scope to set what's usually a deliberate global rather than a
+// local the current scope owns. A name reassigned more than once in the
+// same scope is also left alone: `LocalScopeTree` only records the first
+// binding per scope, so a later store can never resolve as "used" here
+// even when it genuinely is, and flagging it would be a false positive.
+// Names read by another open file or the legacy workspace index are
+// excluded too, since "never read *here*" isn't dead code when the file
+// exports it.
+
+const UNUSED_DEFINITION_DIAGNOSTIC_MARKER: &str = "is assigned but never used";
+
+/// Substring every naming-convention-violation diagnostic message contains
+/// (see `collect_naming_convention_diagnostics` below), used by `code_action`
+/// to recognize which incoming `context.diagnostics` entries its "Rename to
+/// ..." quick fix applies to.
+const INCORRECT_CASE_DIAGNOSTIC_MARKER: &str = "does not match the configured naming convention";
+
+/// Prefix every undefined-variable diagnostic message starts with (see
+/// `collect_undefined_variables_position_aware` above), used by `code_action`
+/// to recognize which incoming `context.diagnostics` entries its "Add missing
+/// source()/library()" quick fix applies to and to recover the flagged name.
+const UNDEFINED_VARIABLE_DIAGNOSTIC_MARKER: &str = "Undefined variable: ";
+
+fn collect_unused_definition_diagnostics(
+    state: &WorldState,
+    uri: &Url,
+    root: Node,
+    text: &str,
+    meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let scopes = LocalScopeTree::build(root, text);
 
-        // 'x' should NOT be collected as a usage (inside formula inside call arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside formula in call arguments should NOT be collected as usage"
-        );
+    let mut assignments = Vec::new();
+    collect_definition_assignments(root, text, &scopes, &mut assignments);
 
-        // 'df' should NOT be collected as a usage (inside call arguments)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(
-            !df_used,
-            "'df' inside call arguments should NOT be collected as usage"
-        );
+    let mut counts: HashMap<(usize, String), usize> = HashMap::new();
+    for (scope_idx, name, _) in &assignments {
+        *counts.entry((*scope_idx, name.clone())).or_insert(0) += 1;
     }
 
-    // ==================== Edge Case Tests (Task 6.4) ====================
-    // These tests verify edge cases for the NSE skip logic
-    // (Requirements 1.1, 1.2, 2.1, 3.1)
+    for (scope_idx, name, lhs) in &assignments {
+        if counts[&(*scope_idx, name.clone())] > 1 {
+            continue;
+        }
 
-    /// Test deeply nested formulas: ~ (~ (~ x)) - all identifiers should be skipped
-    /// Validates: Requirement 3.1 - Identifiers inside formula expressions should be skipped
-    #[test]
-    fn test_deeply_nested_formulas() {
-        let code = "~ (~ (~ x))";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+        let row = lhs.start_position().row as u32;
+        if crate::cross_file::directive::is_line_ignored(meta, row) {
+            continue;
+        }
 
-        // 'x' should NOT be collected as a usage (inside deeply nested formula)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside deeply nested formula should NOT be collected as usage"
-        );
+        if is_referenced_cross_file(state, uri, name) {
+            continue;
+        }
 
-        // No identifiers should be collected at all
-        assert!(
-            used.is_empty(),
-            "No identifiers should be collected from deeply nested formula"
-        );
+        let occurrences = collect_scoped_occurrences(root, text, &scopes, *lhs, name, false);
+        if occurrences.is_empty() {
+            let (code, code_description) = diagnostic_code(diagnostic_codes::UNUSED_DEFINITION);
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position::new(row, lhs.start_position().column as u32),
+                    end: Position::new(row, lhs.end_position().column as u32),
+                },
+                severity: Some(DiagnosticSeverity::HINT),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                code,
+                code_description,
+                message: format!("'{}' {}", name, UNUSED_DEFINITION_DIAGNOSTIC_MARKER),
+                ..Default::default()
+            });
+        }
     }
+}
 
-    /// Test nested call arguments: f(g(h(x))) - all identifiers in all argument levels should be skipped
-    /// Validates: Requirement 2.1 - Identifiers inside call arguments should be skipped
-    #[test]
-    fn test_nested_call_arguments() {
-        let code = "f(g(h(x)))";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+/// Collects `(scope, name, lhs)` for every `<-`/`=` assignment to a plain
+/// identifier, recording which `LocalScopeTree` scope each falls in so the
+/// caller can tell a single binding from a same-scope reassignment. `<<-`
+/// is excluded on purpose - see the module doc comment above.
+fn collect_definition_assignments<'a>(
+    node: Node<'a>,
+    text: &str,
+    scopes: &LocalScopeTree<'a>,
+    out: &mut Vec<(usize, String, Node<'a>)>,
+) {
+    if node.kind() == "binary_operator" {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+        if children.len() >= 3 {
+            let lhs = children[0];
+            let op_text = node_text(children[1], text);
+            if matches!(op_text, "<-" | "=") && lhs.kind() == "identifier" {
+                let scope_idx = scopes.scope_at(lhs.start_position());
+                out.push((scope_idx, node_text(lhs, text).to_string(), lhs));
+            }
+        }
+    }
 
-        // 'f' should be collected as a usage (outermost function name is checked)
-        let f_used = used.iter().any(|(name, _)| name == "f");
-        assert!(f_used, "Function name 'f' should be collected as usage");
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_definition_assignments(child, text, scopes, out);
+    }
+}
 
-        // 'g' should NOT be collected as a usage (inside f's arguments)
-        let g_used = used.iter().any(|(name, _)| name == "g");
-        assert!(
-            !g_used,
-            "'g' inside call arguments should NOT be collected as usage"
-        );
+/// Flags assignment targets whose name doesn't match the user-configured
+/// naming convention (`naming_convention`/`naming_convention_severity` in
+/// `CrossFileConfig`), porting rust-analyzer's `incorrect_case` lint to R.
+/// Disabled unless `naming_convention_severity` is set, since R codebases
+/// vary too much in style to guess a default. Reserved words and dotted
+/// S3-method names (e.g. `print.foo`) are exempt - the dot there is
+/// dispatch syntax, not a naming-convention violation.
+fn collect_naming_convention_diagnostics(
+    state: &WorldState,
+    root: Node,
+    text: &str,
+    meta: &crate::cross_file::CrossFileMetadata,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some(severity) = state.cross_file_config.naming_convention_severity else {
+        return;
+    };
+    let convention = state.cross_file_config.naming_convention;
 
-        // 'h' should NOT be collected as a usage (inside g's arguments, which is inside f's arguments)
-        let h_used = used.iter().any(|(name, _)| name == "h");
-        assert!(
-            !h_used,
-            "'h' inside nested call arguments should NOT be collected as usage"
-        );
+    let mut targets = Vec::new();
+    collect_naming_targets(root, text, &mut targets);
 
-        // 'x' should NOT be collected as a usage (inside h's arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside deeply nested call arguments should NOT be collected as usage"
-        );
+    let mut seen = std::collections::HashSet::new();
+    for (name, lhs) in targets {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        if crate::reserved_words::is_reserved_word(&name) || name.contains('.') {
+            continue;
+        }
+        if convention.matches(&name) {
+            continue;
+        }
+        let row = lhs.start_position().row as u32;
+        if crate::cross_file::directive::is_line_ignored(meta, row) {
+            continue;
+        }
+        let suggested = convention.suggest(&name);
+        if suggested == name {
+            continue;
+        }
 
-        // Only 'f' should be collected
-        assert_eq!(
-            used.len(),
-            1,
-            "Only the outermost function name should be collected"
-        );
+        let (code, code_description) = diagnostic_code(diagnostic_codes::INCORRECT_CASE);
+        diagnostics.push(Diagnostic {
+            range: Range {
+                start: Position::new(row, lhs.start_position().column as u32),
+                end: Position::new(row, lhs.end_position().column as u32),
+            },
+            severity: Some(severity),
+            code,
+            code_description,
+            message: format!(
+                "'{}' {INCORRECT_CASE_DIAGNOSTIC_MARKER} ({}); consider renaming to '{}'",
+                name,
+                convention.label(),
+                suggested
+            ),
+            ..Default::default()
+        });
     }
+}
 
-    /// Test mixed contexts: df$col[x > 5] - 'col' skipped (extract RHS), 'x' skipped (subset arguments), 'df' checked
-    /// Validates: Requirements 1.1, 1.2, 2.1 - Extract RHS and subset arguments should be skipped
-    #[test]
-    fn test_mixed_contexts() {
-        let code = "df$col[x > 5]";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+/// Collects `(name, lhs)` for every `<-`/`=` assignment to a plain
+/// identifier, for `collect_naming_convention_diagnostics` above. Unlike
+/// `collect_definition_assignments`, scope isn't tracked here - a badly
+/// named local is just as worth flagging as a badly named global, and each
+/// name is only reported once regardless of how many scopes reuse it.
+fn collect_naming_targets<'a>(node: Node<'a>, text: &str, out: &mut Vec<(String, Node<'a>)>) {
+    if node.kind() == "binary_operator" {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+        if children.len() >= 3 {
+            let lhs = children[0];
+            let op_text = node_text(children[1], text);
+            if matches!(op_text, "<-" | "=") && lhs.kind() == "identifier" {
+                out.push((node_text(lhs, text).to_string(), lhs));
+            }
+        }
+    }
 
-        // 'df' should be collected as a usage (LHS of extract operator is checked)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(
-            df_used,
-            "'df' (LHS of extract operator) should be collected as usage"
-        );
-
-        // 'col' should NOT be collected as a usage (RHS of extract operator)
-        let col_used = used.iter().any(|(name, _)| name == "col");
-        assert!(
-            !col_used,
-            "'col' (RHS of extract operator) should NOT be collected as usage"
-        );
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_naming_targets(child, text, out);
+    }
+}
 
-        // 'x' should NOT be collected as a usage (inside subset arguments)
-        let x_used = used.iter().any(|(name, _)| name == "x");
-        assert!(
-            !x_used,
-            "'x' inside subset arguments should NOT be collected as usage"
-        );
+/// Substring every unsourced-file diagnostic message contains, used by
+/// `code_action` to recognize which incoming `context.diagnostics` entries
+/// its "Add source() to an entry file" quick fix applies to.
+const UNSOURCED_FILE_DIAGNOSTIC_MARKER: &str = "is not reached by any source() chain";
+
+/// Flags `uri` as orphaned - rust-analyzer's `unlinked_file` diagnostic
+/// ported to R - when it defines at least one top-level symbol but
+/// `cross_file_graph` has no ancestor that reaches it via a `source()`
+/// chain, so those definitions are invisible to the rest of the workspace.
+/// Disabled unless `unsourced_file_severity` is set (see its doc comment for
+/// why this needs an explicit opt-in). Files with no top-level definitions
+/// are exempt - a pure script with nothing to export isn't "orphaned", it's
+/// just an entry point.
+fn collect_unsourced_file_diagnostics(
+    state: &WorldState,
+    uri: &Url,
+    root: Node,
+    text: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some(severity) = state.cross_file_config.unsourced_file_severity else {
+        return;
+    };
 
-        // Only 'df' should be collected
-        assert_eq!(
-            used.len(),
-            1,
-            "Only 'df' should be collected in mixed context"
-        );
+    let mut bindings = Vec::new();
+    collect_block_bindings(root, text, &mut bindings);
+    if bindings.is_empty() {
+        return;
     }
 
-    /// Test chained extracts: df$a$b$c - only 'df' should be checked, all others are RHS of extract operators
-    /// Validates: Requirements 1.1, 1.2 - RHS of extract operators should be skipped
-    #[test]
-    fn test_chained_extracts() {
-        let code = "df$a$b$c";
-        let tree = parse_r_code(code);
-        let mut used = Vec::new();
-        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+    let ancestors = state
+        .cross_file_graph
+        .get_transitive_dependents(uri, state.cross_file_config.max_chain_depth);
+    if !ancestors.is_empty() {
+        return;
+    }
 
-        // 'df' should be collected as a usage (leftmost identifier is checked)
-        let df_used = used.iter().any(|(name, _)| name == "df");
-        assert!(
-            df_used,
-            "'df' (leftmost identifier) should be collected as usage"
-        );
+    let (code, code_description) = diagnostic_code(diagnostic_codes::UNSOURCED_FILE);
+    diagnostics.push(Diagnostic {
+        range: Range {
+            start: Position::new(0, 0),
+            end: Position::new(0, 0),
+        },
+        severity: Some(severity),
+        code,
+        code_description,
+        message: format!(
+            "This file defines {} but {}, so they won't resolve cross-file",
+            if bindings.len() == 1 {
+                "a symbol".to_string()
+            } else {
+                format!("{} symbols", bindings.len())
+            },
+            UNSOURCED_FILE_DIAGNOSTIC_MARKER
+        ),
+        ..Default::default()
+    });
+}
 
-        // 'a' should NOT be collected as a usage (RHS of first extract operator)
-        let a_used = used.iter().any(|(name, _)| name == "a");
-        assert!(
-            !a_used,
-            "'a' (RHS of extract operator) should NOT be collected as usage"
-        );
+/// Finds an open document, other than `skip_uri`, that nothing else sources
+/// - an entry point candidate - to offer as the target of the "Add
+/// source()" quick fix for [`UNSOURCED_FILE_DIAGNOSTIC_MARKER`] diagnostics.
+/// Mirrors `find_top_level_definition_uri`'s document enumeration order.
+fn find_entry_document_for_source_quickfix(state: &WorldState, skip_uri: &Url) -> Option<Url> {
+    let is_entry = |uri: &Url| state.cross_file_graph.get_dependents(uri).is_empty();
 
-        // 'b' should NOT be collected as a usage (RHS of second extract operator)
-        let b_used = used.iter().any(|(name, _)| name == "b");
-        assert!(
-            !b_used,
-            "'b' (RHS of extract operator) should NOT be collected as usage"
-        );
+    for file_uri in state.document_store.uris() {
+        if file_uri != *skip_uri && is_entry(&file_uri) {
+            return Some(file_uri);
+        }
+    }
 
-        // 'c' should NOT be collected as a usage (RHS of third extract operator)
-        let c_used = used.iter().any(|(name, _)| name == "c");
-        assert!(
-            !c_used,
-            "'c' (RHS of extract operator) should NOT be collected as usage"
-        );
+    for (file_uri, _) in state.workspace_index_new.iter() {
+        if file_uri != *skip_uri && is_entry(&file_uri) {
+            return Some(file_uri);
+        }
+    }
 
-        // Only 'df' should be collected
-        assert_eq!(
-            used.len(),
-            1,
-            "Only 'df' should be collected in chained extracts"
-        );
+    for entry in state.documents.iter() {
+        let file_uri = entry.key();
+        if file_uri != skip_uri && !state.document_store.contains(file_uri) && is_entry(file_uri) {
+            return Some(file_uri.clone());
+        }
     }
 
-    // ========================================================================
-    // Completion Precedence Tests (Task 11.2)
-    // Tests for completion precedence: local > package exports > cross-file
-    // Validates: Requirements 9.4, 9.5
-    // ========================================================================
+    None
+}
 
-    /// Test that local definitions take precedence over package exports in completions.
-    /// Validates: Requirement 9.4 - Local definitions > package exports
-    #[test]
-    fn test_completion_local_over_package_exports() {
-        use crate::package_library::PackageInfo;
-        use crate::state::{Document, WorldState};
-        use tower_lsp::lsp_types::{CompletionResponse, Position};
+/// One file's diagnostics in a [`check_workspace`] result.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceCheckFile {
+    pub uri: Url,
+    pub diagnostics: Vec<Diagnostic>,
+}
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            // Create a WorldState with a package that exports "mutate"
-            let mut state = WorldState::new(vec![]);
+/// Aggregate result of a `raven/checkWorkspace` request: every reachable
+/// file's diagnostics in one shot, grouped by URI, so a client can populate
+/// its whole problems panel without opening every buffer first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckWorkspaceResult {
+    pub files: Vec<WorkspaceCheckFile>,
+}
 
-            // Add a package with "mutate" export
-            let mut exports = std::collections::HashSet::new();
-            exports.insert("mutate".to_string());
-            exports.insert("filter".to_string());
-            let pkg_info = PackageInfo::new("dplyr".to_string(), exports);
-            state.package_library.insert_package(pkg_info).await;
+/// Walks `cross_file_graph` from every root file - one nothing else sources,
+/// per [`find_entry_document_for_source_quickfix`]'s `is_entry` convention -
+/// and returns the aggregated diagnostic set for the whole reachable graph in
+/// one shot, analogous to `cargo check` building the full unit graph instead
+/// of one crate at a time.
+///
+/// For each open document the walk reaches, this reuses [`diagnostics`]
+/// wholesale, so missing packages, unresolved cross-file symbols, and every
+/// other per-file check are found exactly as they would be on open/change.
+/// It additionally flags `source()` edges whose target isn't in
+/// `state.documents`: [`diagnostics`] can't see past the open-document
+/// boundary, so a target that exists on disk but was never opened would
+/// otherwise look clean. Files reachable only through a `source()` cycle
+/// have no well-defined root, so every open document is walked regardless of
+/// whether a root reaches it.
+pub fn check_workspace(state: &WorldState) -> CheckWorkspaceResult {
+    let is_entry = |uri: &Url| state.cross_file_graph.get_dependents(uri).is_empty();
+
+    let mut roots: Vec<Url> = state
+        .documents
+        .iter()
+        .map(|entry| entry.key().clone())
+        .filter(is_entry)
+        .collect();
+    for entry in state.documents.iter() {
+        let uri = entry.key();
+        if !roots.contains(uri) {
+            roots.push(uri.clone());
+        }
+    }
 
-            // Create a document that defines "mutate" locally and loads dplyr
-            let code = r#"library(dplyr)
-mutate <- function(x) { x * 2 }
-result <- "#;
-            let uri = Url::parse("file:///test.R").unwrap();
-            let doc = Document::new(code, None);
-            state.documents.insert(uri.clone(), doc);
+    let mut visited: HashSet<Url> = HashSet::new();
+    let mut untracked_targets: HashMap<Url, Vec<DependencyEdge>> = HashMap::new();
+    for root in &roots {
+        walk_cross_file_graph(state, root, &mut visited, &mut untracked_targets);
+    }
 
-            // Get completions at the end of the file (after "result <- ")
-            let position = Position::new(2, 10);
-            let completions = super::completion(&state, &uri, position);
+    let mut per_file: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    for uri in &visited {
+        if state.documents.contains_key(uri) {
+            per_file
+                .entry(uri.clone())
+                .or_default()
+                .extend(diagnostics(state, uri));
+        }
+    }
 
-            assert!(completions.is_some(), "Should return completions");
+    let (code, code_description) = diagnostic_code(diagnostic_codes::UNTRACKED_SOURCE_TARGET);
+    for (from, edges) in untracked_targets {
+        let entry = per_file.entry(from).or_default();
+        for edge in edges {
+            let position = Position::new(
+                edge.call_site_line.unwrap_or(0),
+                edge.call_site_column.unwrap_or(0),
+            );
+            entry.push(Diagnostic {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                severity: Some(DiagnosticSeverity::INFORMATION),
+                code: code.clone(),
+                code_description: code_description.clone(),
+                message: format!(
+                    "'{}' is sourced but not open, so its symbols aren't included in this check",
+                    edge.to
+                ),
+                ..Default::default()
+            });
+        }
+    }
 
-            if let Some(CompletionResponse::Array(items)) = completions {
-                // Find the "mutate" completion item
-                let mutate_items: Vec<_> = items.iter()
-                    .filter(|item| item.label == "mutate")
-                    .collect();
+    let mut files: Vec<WorkspaceCheckFile> = per_file
+        .into_iter()
+        .map(|(uri, diagnostics)| WorkspaceCheckFile { uri, diagnostics })
+        .collect();
+    files.sort_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()));
 
-                // There should be exactly one "mutate" item (the local definition)
-                assert_eq!(
-                    mutate_items.len(),
-                    1,
-                    "Should have exactly one 'mutate' completion (local definition takes precedence)"
-                );
+    CheckWorkspaceResult { files }
+}
 
-                // The local definition should NOT have package attribution
-                let mutate_item = mutate_items[0];
-                assert!(
-                    mutate_item.detail.is_none() || !mutate_item.detail.as_ref().unwrap().contains("{dplyr}"),
-                    "Local 'mutate' should not have package attribution"
-                );
-            } else {
-                panic!("Expected CompletionResponse::Array");
-            }
-        });
+/// Depth-first walk of `cross_file_graph`'s forward edges starting at `uri`,
+/// recording every visited file in `visited` and every edge whose target
+/// isn't an open document in `untracked_targets`, keyed by the edge's source
+/// file. Guards against revisiting (and against cycles looping forever) via
+/// `visited`.
+fn walk_cross_file_graph(
+    state: &WorldState,
+    uri: &Url,
+    visited: &mut HashSet<Url>,
+    untracked_targets: &mut HashMap<Url, Vec<DependencyEdge>>,
+) {
+    if !visited.insert(uri.clone()) {
+        return;
     }
 
-    /// Test that package exports take precedence over cross-file symbols in completions.
-    /// Validates: Requirement 9.5 - Package exports > cross-file symbols
-    #[test]
-    fn test_completion_package_over_cross_file() {
-        use crate::package_library::PackageInfo;
-        use crate::state::{Document, WorldState};
-        use tower_lsp::lsp_types::{CompletionResponse, Position};
+    for edge in state.cross_file_graph.get_dependencies(uri) {
+        if !state.documents.contains_key(&edge.to) {
+            untracked_targets
+                .entry(edge.from.clone())
+                .or_default()
+                .push(edge.clone());
+        }
+        walk_cross_file_graph(state, &edge.to, visited, untracked_targets);
+    }
+}
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            // Create a WorldState with a package that exports "helper_func"
-            let mut state = WorldState::new(vec![]);
+/// Returns true if `name` is read anywhere outside `uri` - in another open
+/// document or in the legacy workspace index - so a same-named definition
+/// in `uri` shouldn't be flagged as dead just because nothing in this file
+/// reads it back.
+fn is_referenced_cross_file(state: &WorldState, uri: &Url, name: &str) -> bool {
+    for entry in state.documents.iter() {
+        let (file_uri, doc) = (entry.key(), entry.value());
+        if file_uri == uri {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            if document_uses_identifier(tree.root_node(), &doc.text(), name) {
+                return true;
+            }
+        }
+    }
 
-            // Add a package with "helper_func" export
-            let mut exports = std::collections::HashSet::new();
-            exports.insert("helper_func".to_string());
-            let pkg_info = PackageInfo::new("testpkg".to_string(), exports);
-            state.package_library.insert_package(pkg_info).await;
+    for (file_uri, doc) in &state.workspace_index {
+        if file_uri == uri {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            if document_uses_identifier(tree.root_node(), &doc.text(), name) {
+                return true;
+            }
+        }
+    }
 
-            // Create main file that loads testpkg
-            let main_code = r#"library(testpkg)
-result <- "#;
-            let main_uri = Url::parse("file:///main.R").unwrap();
-            let main_doc = Document::new(main_code, None);
-            state.documents.insert(main_uri.clone(), main_doc);
+    false
+}
 
-            // Create a helper file that defines "helper_func"
-            let helper_code = r#"helper_func <- function(x) { x + 1 }"#;
-            let helper_uri = Url::parse("file:///helper.R").unwrap();
-            let helper_doc = Document::new(helper_code, None);
-            state.documents.insert(helper_uri.clone(), helper_doc);
+fn document_uses_identifier(root: Node, text: &str, name: &str) -> bool {
+    let mut usages = Vec::new();
+    collect_usages_with_context(root, text, &UsageContext::default(), &mut usages);
+    usages.iter().any(|(used_name, _)| used_name == name)
+}
 
-            // Note: In a real scenario, the cross-file symbol would come from scope resolution
-            // through source() calls. For this test, we verify that package exports are added
-            // before cross-file symbols in the completion list.
+/// Deletes the dead assignment statement flagged by the unused-definition
+/// diagnostic (see `collect_unused_definition_diagnostics` above). Reuses
+/// `find_assignment_statement` - the same walk-up-to-the-enclosing-statement
+/// logic hover/goto-definition's `extract_statement_from_tree` relies on -
+/// to find the statement to remove, then deletes its lines outright,
+/// absorbing the trailing newline the same way `remove_unused_import_edit`
+/// does.
+fn unused_definition_fix_edit(
+    root: Node,
+    uri: &Url,
+    text: &str,
+    diagnostic_range: Range,
+) -> Option<WorkspaceEdit> {
+    let point = Point::new(
+        diagnostic_range.start.line as usize,
+        diagnostic_range.start.character as usize,
+    );
+    let node = root
+        .named_descendant_for_point_range(point, point)
+        .or_else(|| root.descendant_for_point_range(point, point))?;
+    let statement = find_assignment_statement(node, text)?.node;
 
-            // Get completions at the end of main file
-            let position = Position::new(1, 10);
-            let completions = super::completion(&state, &main_uri, position);
+    let start_line = statement.start_position().row as u32;
+    let end_line = statement.end_position().row as u32;
+    let total_lines = text.lines().count() as u32;
 
-            assert!(completions.is_some(), "Should return completions");
+    let end = if end_line + 1 < total_lines {
+        Position::new(end_line + 1, 0)
+    } else {
+        let last_line_len = text
+            .lines()
+            .nth(end_line as usize)
+            .map(|l| l.len())
+            .unwrap_or(0) as u32;
+        Position::new(end_line, last_line_len)
+    };
 
-            if let Some(CompletionResponse::Array(items)) = completions {
-                // Find the "helper_func" completion item
-                let helper_items: Vec<_> = items
-                    .iter()
-                    .filter(|item| item.label == "helper_func")
-                    .collect();
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: Range {
+                start: Position::new(start_line, 0),
+                end,
+            },
+            new_text: String::new(),
+        }],
+    );
 
-                // There should be at least one "helper_func" item (from package)
-                assert!(
-                    !helper_items.is_empty(),
-                    "Should have 'helper_func' completion from package"
-                );
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    })
+}
 
-                // The first (and only) helper_func should be from the package
-                let helper_item = helper_items[0];
-                assert!(
-                    helper_item
-                        .detail
-                        .as_ref()
-                        .map_or(false, |d| d.contains("{testpkg}")),
-                    "helper_func should have package attribution {{testpkg}}"
-                );
-            } else {
-                panic!("Expected CompletionResponse::Array");
-            }
-        });
-    }
+// ============================================================================
+// Code Actions
+// ============================================================================
+//
+// "Extract function": lifts the complete statements spanned by a selection
+// into a new top-level function, replacing the selection with a call to it.
+// Reuses `LocalScopeTree` (see "Local Scope Resolution" above) to tell a
+// variable bound outside the selection (which becomes a parameter) from one
+// that's local to it, and `collect_scoped_occurrences` to tell whether a
+// local assigned inside the selection is still read afterwards (which makes
+// it part of the return value).
+//
+// "Remove unused import": deletes a `library()`/`require()` statement flagged
+// by the unused-import diagnostic (see `collect_unused_library_diagnostics`
+// below). Rather than re-deriving which call is unused, this matches against
+// the diagnostics the client already sent back in `context.diagnostics` —
+// the standard LSP quick-fix idiom — so the two stay in lockstep for free.
+//
+// "Move 'else' onto the same line as '}'": fixes an orphaned-else diagnostic
+// (see `collect_else_newline_errors` below) by replacing the whitespace
+// between the closing brace and `else`/`else if` with a single space. Same
+// match-against-`context.diagnostics` idiom as the unused-import fix.
+//
+// "Convert '=' to '<-' assignment" (and its reverse): rewrites just the
+// operator token of a top-level assignment. Relies on the grammar already
+// parsing a named call argument (`name = value`) as an `argument` node
+// rather than a `binary_operator`, the same distinction `collect_usages`
+// leans on, so no extra checking is needed to avoid call arguments.
+//
+// "Extract expression to variable": inserts `name <- <expr>` on the line
+// above the selected expression, matching its indentation, and replaces the
+// selection with `name`.
 
-    /// Test that keywords take precedence over all other completions.
-    /// Validates: Implicit requirement - keywords should always be available
-    #[test]
-    fn test_completion_keywords_always_present() {
-        use crate::package_library::PackageInfo;
-        use crate::state::{Document, WorldState};
-        use tower_lsp::lsp_types::{CompletionItemKind, CompletionResponse, Position};
+const EXTRACT_FUNCTION_TITLE: &str = "Extract function";
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            // Create a WorldState with a package that exports "if" (hypothetically)
-            let mut state = WorldState::new(vec![]);
+pub fn code_action(
+    state: &WorldState,
+    uri: &Url,
+    range: Range,
+    context_diagnostics: &[Diagnostic],
+    only: Option<&[CodeActionKind]>,
+) -> Option<Vec<CodeActionOrCommand>> {
+    let wants = |kind: CodeActionKind| match only {
+        Some(only) => only.iter().any(|k| kind.as_str().starts_with(k.as_str())),
+        None => true,
+    };
 
-            // Add a package with "if" export (edge case - shouldn't override keyword)
-            let mut exports = std::collections::HashSet::new();
-            exports.insert("if".to_string());
-            let pkg_info = PackageInfo::new("badpkg".to_string(), exports);
-            state.package_library.insert_package(pkg_info).await;
+    let doc = state.get_document(uri)?;
+    let tree = doc.tree.as_ref()?;
+    let text = doc.text();
 
-            // Create a document that loads the package
-            let code = r#"library(badpkg)
-x <- "#;
-            let uri = Url::parse("file:///test.R").unwrap();
-            let doc = Document::new(code, None);
-            state.documents.insert(uri.clone(), doc);
+    let mut actions = Vec::new();
 
-            // Get completions
-            let position = Position::new(1, 5);
-            let completions = super::completion(&state, &uri, position);
+    if wants(EXTRACT_FUNCTION_KIND) {
+        if let Some(edit) = extract_function_edit(tree.root_node(), &text, uri, range) {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: EXTRACT_FUNCTION_TITLE.to_string(),
+                kind: Some(EXTRACT_FUNCTION_KIND),
+                edit: Some(edit),
+                ..Default::default()
+            }));
+        }
 
-            assert!(completions.is_some(), "Should return completions");
+        if let Some(edit) = extract_variable_edit(tree.root_node(), &text, uri, range) {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: EXTRACT_VARIABLE_TITLE.to_string(),
+                kind: Some(EXTRACT_VARIABLE_KIND),
+                edit: Some(edit),
+                ..Default::default()
+            }));
+        }
+    }
 
-            if let Some(CompletionResponse::Array(items)) = completions {
-                // Find the "if" completion item
-                let if_items: Vec<_> = items.iter().filter(|item| item.label == "if").collect();
+    if wants(NORMALIZE_ASSIGNMENT_KIND) {
+        if let Some((edit, title)) =
+            convert_assignment_operator_edit(tree.root_node(), &text, uri, range)
+        {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(NORMALIZE_ASSIGNMENT_KIND),
+                edit: Some(edit),
+                ..Default::default()
+            }));
+        }
+    }
 
-                // There should be exactly one "if" item (the keyword)
-                assert_eq!(
-                    if_items.len(),
-                    1,
-                    "Should have exactly one 'if' completion (keyword takes precedence)"
-                );
+    if wants(QUALIFY_CALL_KIND) {
+        for (edit, title) in qualify_call_edits(state, uri, tree.root_node(), &text, range) {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(QUALIFY_CALL_KIND),
+                edit: Some(edit),
+                ..Default::default()
+            }));
+        }
+    }
 
-                // The "if" should be a keyword, not a function from package
-                let if_item = if_items[0];
-                assert_eq!(
-                    if_item.kind,
-                    Some(CompletionItemKind::KEYWORD),
-                    "'if' should be a KEYWORD, not a function from package"
+    if wants(CodeActionKind::QUICKFIX) {
+        for diagnostic in context_diagnostics {
+            if !ranges_overlap(diagnostic.range, range) {
+                continue;
+            }
+            if diagnostic.message.contains(UNUSED_LIBRARY_DIAGNOSTIC_MARKER) {
+                let edit = remove_unused_import_edit(uri, &text, diagnostic.range.start.line);
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Remove unused import: {}", diagnostic.message),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(edit),
+                    ..Default::default()
+                }));
+            } else if diagnostic.message.contains(ELSE_NEWLINE_DIAGNOSTIC_MARKER) {
+                if let Some(edit) = else_newline_fix_edit(uri, &text, diagnostic.range) {
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: "Move 'else' onto the same line as '}'".to_string(),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                }
+            } else if diagnostic
+                .message
+                .contains(UNUSED_DEFINITION_DIAGNOSTIC_MARKER)
+            {
+                if let Some(edit) =
+                    unused_definition_fix_edit(tree.root_node(), uri, &text, diagnostic.range)
+                {
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: "Delete unused assignment".to_string(),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                }
+            } else if diagnostic
+                .message
+                .contains(UNLOADED_NAMESPACE_PACKAGE_DIAGNOSTIC_MARKER)
+            {
+                if let Some(package) = diagnostic.message.split('\'').nth(1) {
+                    let edit = insert_library_call_edit(uri, package);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Add library({})", package),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                }
+            } else if let Some(rest) = diagnostic
+                .message
+                .strip_prefix(UNDEFINED_VARIABLE_DIAGNOSTIC_MARKER)
+            {
+                let (name, suggestion) = split_did_you_mean_suggestion(rest);
+                if let Some(suggestion) = suggestion {
+                    let mut changes = HashMap::new();
+                    changes.insert(
+                        uri.clone(),
+                        vec![TextEdit {
+                            range: diagnostic.range,
+                            new_text: suggestion.to_string(),
+                        }],
+                    );
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Change '{}' to '{}'", name, suggestion),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            document_changes: None,
+                            change_annotations: None,
+                        }),
+                        ..Default::default()
+                    }));
+                }
+
+                if let Some(def_uri) = find_top_level_definition_uri(state, uri, name) {
+                    let relative_path =
+                        compute_relative_path(&def_uri, state.workspace_folders.first());
+                    let edit = insert_source_call_edit(uri, &relative_path);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Add source(\"{}\")", relative_path),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                } else if let Some(package) = state
+                    .package_library
+                    .find_package_for_symbol(name, &state.package_library.cached_package_names())
+                {
+                    let edit = insert_library_call_edit(uri, &package);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Add library({})", package),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                }
+            } else if diagnostic
+                .message
+                .contains(INCORRECT_CASE_DIAGNOSTIC_MARKER)
+            {
+                let point = Point::new(
+                    diagnostic.range.start.line as usize,
+                    diagnostic.range.start.character as usize,
                 );
-            } else {
-                panic!("Expected CompletionResponse::Array");
+                if let Some(node) = tree.root_node().descendant_for_point_range(point, point) {
+                    let name = node_text(node, &text);
+                    let suggested = state.cross_file_config.naming_convention.suggest(&name);
+                    if suggested != name {
+                        if let Some(edit) =
+                            rename_to(state, uri, diagnostic.range.start, &suggested)
+                        {
+                            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                                title: format!("Rename '{}' to '{}'", name, suggested),
+                                kind: Some(CodeActionKind::QUICKFIX),
+                                diagnostics: Some(vec![diagnostic.clone()]),
+                                edit: Some(edit),
+                                ..Default::default()
+                            }));
+                        }
+                    }
+                }
+            } else if diagnostic
+                .message
+                .contains(UNSOURCED_FILE_DIAGNOSTIC_MARKER)
+            {
+                if let Some(entry_uri) = find_entry_document_for_source_quickfix(state, uri) {
+                    let relative_path = compute_relative_path(uri, state.workspace_folders.first());
+                    let edit = insert_source_call_edit(&entry_uri, &relative_path);
+                    let entry_name = entry_uri
+                        .path_segments()
+                        .and_then(|mut s| s.next_back().map(|s| s.to_string()))
+                        .unwrap_or_else(|| entry_uri.to_string());
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Add source(\"{}\") to {}", relative_path, entry_name),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                }
+            } else if diagnostic
+                .message
+                .contains(MISSING_PACKAGE_DIAGNOSTIC_MARKER)
+            {
+                if let Some(package) = diagnostic.message.split('\'').nth(1) {
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Install '{}'", package),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        command: Some(Command {
+                            title: format!("install.packages(\"{}\")", package),
+                            command: INSTALL_PACKAGE_COMMAND.to_string(),
+                            arguments: Some(vec![serde_json::json!({ "package": package })]),
+                        }),
+                        ..Default::default()
+                    }));
+
+                    if let Some(suggestion) = first_missing_package_suggestion(&diagnostic.message)
+                    {
+                        let mut changes = HashMap::new();
+                        changes.insert(
+                            uri.clone(),
+                            vec![TextEdit {
+                                range: diagnostic.range,
+                                new_text: format!("library({})", suggestion),
+                            }],
+                        );
+                        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                            title: format!("Replace with '{}'", suggestion),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            diagnostics: Some(vec![diagnostic.clone()]),
+                            edit: Some(WorkspaceEdit {
+                                changes: Some(changes),
+                                document_changes: None,
+                                change_annotations: None,
+                            }),
+                            ..Default::default()
+                        }));
+                    }
+                }
+            } else if diagnostic
+                .message
+                .contains(OUT_OF_SCOPE_SYMBOL_DIAGNOSTIC_MARKER)
+            {
+                for (edit, title) in
+                    qualify_call_edits(state, uri, tree.root_node(), &text, diagnostic.range)
+                {
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(edit),
+                        ..Default::default()
+                    }));
+                }
             }
-        });
+        }
     }
 
-    /// Verifies completion precedence where local definitions shadow package exports, and package exports take precedence over cross-file symbols.
-    ///
-    /// Sets up a WorldState with a package ("dplyr") that exports several symbols, opens a document that loads that package and defines a local `mutate` (which should shadow the package export) and `my_func`, then requests completions at a position and asserts:
-    /// - the local `mutate` appears once with no package attribution,
-    /// - `filter` and `select` appear once each with package attribution `{dplyr}`,
-    /// - `my_func` appears as a function completion.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// // Arrange: create state, insert package exports and document, then call completion.
-    /// // Assert: see comments above for expected precedence behavior.
-    /// ```
-    #[test]
-    fn test_completion_full_precedence_chain() {
-        use crate::package_library::PackageInfo;
-        use crate::state::{Document, WorldState};
-        use tower_lsp::lsp_types::{CompletionItemKind, CompletionResponse, Position};
+    if actions.is_empty() {
+        None
+    } else {
+        Some(actions)
+    }
+}
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            let mut state = WorldState::new(vec![]);
+fn ranges_overlap(a: Range, b: Range) -> bool {
+    let a = (
+        (a.start.line, a.start.character),
+        (a.end.line, a.end.character),
+    );
+    let b = (
+        (b.start.line, b.start.character),
+        (b.end.line, b.end.character),
+    );
+    a.0 <= b.1 && b.0 <= a.1
+}
 
-            // Add packages with various exports
-            let mut dplyr_exports = std::collections::HashSet::new();
-            dplyr_exports.insert("mutate".to_string());
-            dplyr_exports.insert("filter".to_string());
-            dplyr_exports.insert("select".to_string());
-            let dplyr_info = PackageInfo::new("dplyr".to_string(), dplyr_exports);
-            state.package_library.insert_package(dplyr_info).await;
+/// Deletes the whole `library()`/`require()` statement on `line`, trimming
+/// its trailing newline by extending the deletion to the start of the next
+/// line. The last line of a document has no trailing newline to take, so it
+/// falls back to deleting just its own content.
+fn remove_unused_import_edit(uri: &Url, text: &str, line: u32) -> WorkspaceEdit {
+    let total_lines = text.lines().count() as u32;
+    let line_len = text
+        .lines()
+        .nth(line as usize)
+        .map(|l| l.len())
+        .unwrap_or(0) as u32;
 
-            // Create a document that:
-            // 1. Loads dplyr (provides mutate, filter, select)
-            // 2. Defines "mutate" locally (should shadow package export)
-            // 3. Defines "my_func" locally
-            let code = r#"library(dplyr)
-mutate <- function(df, ...) { df }
-my_func <- function(x) { x }
-result <- "#;
-            let uri = Url::parse("file:///test.R").unwrap();
-            let doc = Document::new(code, None);
-            state.documents.insert(uri.clone(), doc);
+    let end = if line + 1 < total_lines {
+        Position::new(line + 1, 0)
+    } else {
+        Position::new(line, line_len)
+    };
 
-            // Get completions at the end
-            let position = Position::new(3, 10);
-            let completions = super::completion(&state, &uri, position);
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: Range {
+                start: Position::new(line, 0),
+                end,
+            },
+            new_text: String::new(),
+        }],
+    );
 
-            assert!(completions.is_some(), "Should return completions");
+    WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    }
+}
 
-            if let Some(CompletionResponse::Array(items)) = completions {
-                // Check "mutate" - should be local (no package attribution)
-                let mutate_items: Vec<_> =
-                    items.iter().filter(|item| item.label == "mutate").collect();
-                assert_eq!(mutate_items.len(), 1, "Should have exactly one 'mutate'");
-                assert!(
-                    mutate_items[0].detail.is_none()
-                        || !mutate_items[0].detail.as_ref().unwrap().contains("{dplyr}"),
-                    "Local 'mutate' should not have package attribution"
-                );
+/// Splits a right-trimmed line ending at the closing `}` (optionally followed
+/// by a trailing `#` comment) into the byte offset right after the `}` and
+/// the comment text, if any. Returns `None` when the line doesn't end in `}`
+/// and isn't `}` followed by a line comment, signalling the caller should
+/// bail out rather than risk mangling unrelated text.
+fn split_trailing_brace_comment(line: &str) -> Option<(usize, Option<&str>)> {
+    if line.ends_with('}') {
+        return Some((line.len(), None));
+    }
+    let brace_idx = line.rfind('}')?;
+    let rest = line[brace_idx + 1..].trim_start();
+    if rest.starts_with('#') {
+        Some((brace_idx + 1, Some(rest)))
+    } else {
+        None
+    }
+}
 
-                // Check "filter" - should be from package (has attribution)
-                let filter_items: Vec<_> =
-                    items.iter().filter(|item| item.label == "filter").collect();
-                assert_eq!(filter_items.len(), 1, "Should have exactly one 'filter'");
-                assert!(
-                    filter_items[0]
-                        .detail
-                        .as_ref()
-                        .map_or(false, |d| d.contains("{dplyr}")),
-                    "'filter' should have package attribution {{dplyr}}"
-                );
+/// Builds the fix for an orphaned-else diagnostic: replaces the whitespace
+/// (blank lines, indentation) between the preceding `}` and `diagnostic_range`
+/// (which points at the `else` keyword) with a single space, pulling `else`/
+/// `else if` up onto the same line as the closing brace. Walks backward from
+/// `diagnostic_range.start` purely over the document text - mirroring
+/// `remove_unused_import_edit`'s re-derive-from-the-diagnostic approach rather
+/// than threading the original AST node through - so it works regardless of
+/// how deeply the `if`/`else` is nested. If the brace's line carries a
+/// trailing `#` comment, that comment would otherwise swallow the joined
+/// `else` into itself, so it's relocated to the end of the `else` line
+/// instead of being dropped in place. Standalone comment lines between the
+/// `}` and `else` can't simply be collapsed away either (they'd be deleted,
+/// and joining `else` onto one would comment it out), so each is relocated
+/// to its own line directly above the `}`, preserving their original order.
+/// Returns `None` if the text immediately before the whitespace isn't `}`
+/// (or `}` plus a comment), meaning the diagnostic and document text have
+/// gone out of sync.
+fn else_newline_fix_edit(uri: &Url, text: &str, diagnostic_range: Range) -> Option<WorkspaceEdit> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut row = diagnostic_range.start.line as usize;
+    let mut col = diagnostic_range.start.character as usize;
+    let mut standalone_comments: Vec<String> = Vec::new();
 
-                // Check "select" - should be from package (has attribution)
-                let select_items: Vec<_> =
-                    items.iter().filter(|item| item.label == "select").collect();
-                assert_eq!(select_items.len(), 1, "Should have exactly one 'select'");
-                assert!(
-                    select_items[0]
-                        .detail
-                        .as_ref()
-                        .map_or(false, |d| d.contains("{dplyr}")),
-                    "'select' should have package attribution {{dplyr}}"
-                );
+    loop {
+        let line = lines.get(row)?;
+        let before = &line[..col.min(line.len())];
+        let trimmed = before.trim_end();
 
-                // Check "my_func" - should be local (no package attribution)
-                let my_func_items: Vec<_> = items
-                    .iter()
-                    .filter(|item| item.label == "my_func")
-                    .collect();
-                assert_eq!(my_func_items.len(), 1, "Should have exactly one 'my_func'");
-                assert_eq!(
-                    my_func_items[0].kind,
-                    Some(CompletionItemKind::FUNCTION),
-                    "'my_func' should be a FUNCTION"
-                );
-            } else {
-                panic!("Expected CompletionResponse::Array");
-            }
-        });
-    }
+        if trimmed.is_empty() {
+            row = row.checked_sub(1)?;
+            col = lines[row].len();
+            continue;
+        }
 
-    /// Test that seen_names correctly prevents duplicates across all sources.
-    /// Validates: Requirements 9.3, 9.4, 9.5 - duplicate exports show all packages
-    #[test]
-    fn test_completion_duplicate_exports_show_all_packages() {
-        use crate::package_library::PackageInfo;
-        use crate::state::{Document, WorldState};
-        use tower_lsp::lsp_types::{CompletionResponse, Position};
+        let leading = trimmed.trim_start();
+        if leading.starts_with('#') {
+            // A whole-line comment between `}` and `else`: remember it (most
+            // recent first) and keep walking back for the actual brace line.
+            standalone_comments.push(leading.to_string());
+            row = row.checked_sub(1)?;
+            col = lines[row].len();
+            continue;
+        }
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            let mut state = WorldState::new(vec![]);
+        let (brace_end, trailing_comment) = split_trailing_brace_comment(trimmed)?;
+        let brace_row = row;
 
-            // Add two packages that both export "common_func"
-            let mut pkg1_exports = std::collections::HashSet::new();
-            pkg1_exports.insert("common_func".to_string());
-            pkg1_exports.insert("pkg1_only".to_string());
-            let pkg1_info = PackageInfo::new("pkg1".to_string(), pkg1_exports);
-            state.package_library.insert_package(pkg1_info).await;
+        let mut edits = vec![TextEdit {
+            range: Range {
+                start: Position::new(brace_row as u32, brace_end as u32),
+                end: diagnostic_range.start,
+            },
+            new_text: " ".to_string(),
+        }];
 
-            let mut pkg2_exports = std::collections::HashSet::new();
-            pkg2_exports.insert("common_func".to_string());
-            pkg2_exports.insert("pkg2_only".to_string());
-            let pkg2_info = PackageInfo::new("pkg2".to_string(), pkg2_exports);
-            state.package_library.insert_package(pkg2_info).await;
-
-            // Create a document that loads both packages
-            let code = r#"library(pkg1)
-library(pkg2)
-x <- "#;
-            let uri = Url::parse("file:///test.R").unwrap();
-            let doc = Document::new(code, None);
-            state.documents.insert(uri.clone(), doc);
+        if let Some(comment) = trailing_comment {
+            let else_row = diagnostic_range.start.line as usize;
+            let else_line_end = lines.get(else_row).map(|l| l.len()).unwrap_or(0) as u32;
+            edits.push(TextEdit {
+                range: Range {
+                    start: Position::new(else_row as u32, else_line_end),
+                    end: Position::new(else_row as u32, else_line_end),
+                },
+                new_text: format!(" {comment}"),
+            });
+        }
 
-            // Get completions
-            let position = Position::new(2, 5);
-            let completions = super::completion(&state, &uri, position);
+        if !standalone_comments.is_empty() {
+            let indent: String = lines[brace_row]
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .collect();
+            let mut inserted = String::new();
+            for comment in standalone_comments.iter().rev() {
+                inserted.push_str(&indent);
+                inserted.push_str(comment);
+                inserted.push('\n');
+            }
+            edits.push(TextEdit {
+                range: Range {
+                    start: Position::new(brace_row as u32, 0),
+                    end: Position::new(brace_row as u32, 0),
+                },
+                new_text: inserted,
+            });
+        }
 
-            assert!(completions.is_some(), "Should return completions");
+        let mut changes = HashMap::new();
+        changes.insert(uri.clone(), edits);
+        return Some(WorkspaceEdit {
+            changes: Some(changes),
+            document_changes: None,
+            change_annotations: None,
+        });
+    }
+}
 
-            if let Some(CompletionResponse::Array(items)) = completions {
-                // Requirement 9.3: When multiple packages export same symbol, show all with attribution
-                // Check that "common_func" appears twice (once for each package)
-                let common_items: Vec<_> = items
-                    .iter()
-                    .filter(|item| item.label == "common_func")
-                    .collect();
-                assert_eq!(
-                    common_items.len(),
-                    2,
-                    "Should have two 'common_func' entries (one per package)"
-                );
+const EXTRACT_FUNCTION_KIND: CodeActionKind = CodeActionKind::REFACTOR_EXTRACT;
+const EXTRACT_VARIABLE_KIND: CodeActionKind = CodeActionKind::REFACTOR_EXTRACT;
+const EXTRACT_VARIABLE_TITLE: &str = "Extract expression to variable";
+const NORMALIZE_ASSIGNMENT_KIND: CodeActionKind = CodeActionKind::REFACTOR_REWRITE;
+const QUALIFY_CALL_KIND: CodeActionKind = CodeActionKind::REFACTOR_REWRITE;
+
+/// Command id for the "Install '<pkg>'" missing-package quick fix. Since
+/// Raven has no R session of its own to run `install.packages()` in, the
+/// command just asks the client to surface the call for the user to run,
+/// the same arm's-length relationship `raven.hoverOpenHelp` has with help text.
+pub const INSTALL_PACKAGE_COMMAND: &str = "raven.installPackage";
+
+/// Parses the arguments of a `raven.installPackage` command invocation back
+/// into the package name to install.
+pub fn parse_install_package_command_args(arguments: &[serde_json::Value]) -> Option<String> {
+    let arg = arguments.first()?;
+    arg.get("package")?.as_str().map(|s| s.to_string())
+}
 
-                // Both packages should be represented
-                let has_pkg1 = common_items
-                    .iter()
-                    .any(|item| item.detail.as_ref().map_or(false, |d| d.contains("{pkg1}")));
-                let has_pkg2 = common_items
-                    .iter()
-                    .any(|item| item.detail.as_ref().map_or(false, |d| d.contains("{pkg2}")));
-                assert!(has_pkg1, "'common_func' should have entry from pkg1");
-                assert!(has_pkg2, "'common_func' should have entry from pkg2");
+fn lowest_common_ancestor<'a>(a: Node<'a>, b: Node<'a>) -> Option<Node<'a>> {
+    let mut ancestors = std::collections::HashSet::new();
+    let mut cursor = Some(a);
+    while let Some(node) = cursor {
+        ancestors.insert(node.id());
+        cursor = node.parent();
+    }
 
-                // Check that unique exports from both packages are present
-                let pkg1_only_items: Vec<_> = items
-                    .iter()
-                    .filter(|item| item.label == "pkg1_only")
-                    .collect();
-                assert_eq!(pkg1_only_items.len(), 1, "Should have 'pkg1_only'");
+    let mut cursor = Some(b);
+    while let Some(node) = cursor {
+        if ancestors.contains(&node.id()) {
+            return Some(node);
+        }
+        cursor = node.parent();
+    }
+    None
+}
 
-                let pkg2_only_items: Vec<_> = items
-                    .iter()
-                    .filter(|item| item.label == "pkg2_only")
-                    .collect();
-                assert_eq!(pkg2_only_items.len(), 1, "Should have 'pkg2_only'");
-            } else {
-                panic!("Expected CompletionResponse::Array");
-            }
-        });
+/// Finds the smallest run of complete sibling statements (direct named
+/// children of a `program` or `brace_list`) that covers `[start, end)`,
+/// returning the parent node and the first/last covered child indices.
+fn selected_statements<'a>(
+    root: Node<'a>,
+    start: Point,
+    end: Point,
+) -> Option<(Node<'a>, usize, usize)> {
+    let start_node = root.descendant_for_point_range(start, start)?;
+    let end_node = root.descendant_for_point_range(end, end)?;
+
+    let mut block = lowest_common_ancestor(start_node, end_node)?;
+    while !matches!(block.kind(), "program" | "brace_list") {
+        block = block.parent()?;
     }
 
-    // ========================================================================
-    // Backward Directive Path Resolution Tests
-    // Tests for fix-backward-directive-path-resolution spec
-    // Validates: Requirements 1.2, 3.2
-    // ========================================================================
+    let mut cursor = block.walk();
+    let children: Vec<Node> = block
+        .children(&mut cursor)
+        .filter(|child| child.is_named())
+        .collect();
 
-    /// Test that backward directive paths resolve relative to file's directory, ignoring @lsp-cd.
-    ///
-    /// This test reproduces a bug where `collect_ambiguous_parent_diagnostics` was using
-    /// `PathContext::from_metadata` (which respects @lsp-cd) instead of `PathContext::new`
-    /// (which ignores @lsp-cd) for backward directive resolution.
-    ///
-    /// Scenario:
-    /// - Child file at `subdir/child.r` contains:
-    ///   - `@lsp-cd ..` (sets working directory to parent/workspace root)
-    ///   - `@lsp-run-by: program.r` (declares parent file)
-    /// - The backward directive should resolve `program.r` relative to `subdir/` (file's directory)
-    ///   NOT relative to the workspace root (the @lsp-cd directory)
-    ///
-    /// Validates: Requirements 1.2, 3.2
-    #[test]
-    fn test_backward_directive_ignores_lsp_cd() {
-        use crate::cross_file::path_resolve::PathContext;
-        use crate::cross_file::types::CrossFileMetadata;
+    let first_idx = children.iter().position(|child| {
+        let p = child.end_position();
+        (p.row, p.column) > (start.row, start.column)
+    })?;
+    let last_idx = children.iter().rposition(|child| {
+        let p = child.start_position();
+        (p.row, p.column) < (end.row, end.column)
+    })?;
 
-        // Simulate a child file at /project/subdir/child.r
-        let child_uri = Url::parse("file:///project/subdir/child.r").unwrap();
-        let workspace_root = Url::parse("file:///project").unwrap();
+    if first_idx > last_idx {
+        return None;
+    }
 
-        // Metadata with @lsp-cd .. (points to /project, the workspace root)
-        let meta = CrossFileMetadata {
-            working_directory: Some("..".to_string()),
-            ..Default::default()
-        };
+    Some((block, first_idx, last_idx))
+}
 
-        // PathContext::new should ignore @lsp-cd
-        let ctx_new = PathContext::new(&child_uri, Some(&workspace_root)).unwrap();
+/// Returns true if a node of `kind` overlaps `[start, end)` without being
+/// fully contained by it — i.e. the selection cuts through the middle of it.
+fn crosses_boundary(node: Node, start: Point, end: Point, kind: &str) -> bool {
+    if node.kind() == kind {
+        let ns = (node.start_position().row, node.start_position().column);
+        let ne = (node.end_position().row, node.end_position().column);
+        let selection = (start.row, start.column);
+        let selection_end = (end.row, end.column);
+
+        let overlaps = ns < selection_end && selection < ne;
+        let fully_contained = selection <= ns && ne <= selection_end;
+        if overlaps && !fully_contained {
+            return true;
+        }
+    }
 
-        // PathContext::from_metadata should respect @lsp-cd
-        let ctx_from_meta =
-            PathContext::from_metadata(&child_uri, &meta, Some(&workspace_root)).unwrap();
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .any(|child| crosses_boundary(child, start, end, kind))
+}
 
-        // Verify that PathContext::new ignores @lsp-cd
-        // The effective working directory should be the file's directory: /project/subdir
-        assert_eq!(
-            ctx_new.effective_working_directory(),
-            std::path::PathBuf::from("/project/subdir"),
-            "PathContext::new should use file's directory, ignoring @lsp-cd"
-        );
+/// Walks up from `node` to the direct child of `root` that contains it —
+/// the statement the extracted function should be inserted above.
+fn enclosing_top_level_statement<'a>(root: Node<'a>, mut node: Node<'a>) -> Option<Node<'a>> {
+    loop {
+        let parent = node.parent()?;
+        if parent.id() == root.id() {
+            return Some(node);
+        }
+        node = parent;
+    }
+}
 
-        // Verify that PathContext::from_metadata respects @lsp-cd
-        // The effective working directory should be /project (the @lsp-cd directory)
-        assert_eq!(
-            ctx_from_meta.effective_working_directory(),
-            std::path::PathBuf::from("/project"),
-            "PathContext::from_metadata should use @lsp-cd directory"
-        );
+/// Collects `name <- value` / `name = value` assignment targets directly in
+/// `node`'s scope (not descending into nested `function_definition` bodies,
+/// whose locals don't leak into the enclosing scope), in source order, one
+/// entry per distinct name.
+fn collect_block_bindings<'a>(node: Node<'a>, text: &str, out: &mut Vec<(String, Node<'a>)>) {
+    if node.kind() == "function_definition" {
+        return;
+    }
 
-        // Now test path resolution for a backward directive path "program.r"
-        let backward_path = "program.r";
+    if node.kind() == "binary_operator" {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+        if children.len() >= 3 {
+            let lhs = children[0];
+            let op_text = node_text(children[1], text);
+            if matches!(op_text, "<-" | "=") && lhs.kind() == "identifier" {
+                let name = node_text(lhs, text).to_string();
+                if !out.iter().any(|(existing, _)| existing == &name) {
+                    out.push((name, lhs));
+                }
+            }
+        }
+    }
 
-        // With PathContext::new (correct for backward directives):
-        // "program.r" should resolve to /project/subdir/program.r
-        let resolved_new = crate::cross_file::path_resolve::resolve_path(backward_path, &ctx_new);
-        assert_eq!(
-            resolved_new,
-            Some(std::path::PathBuf::from("/project/subdir/program.r")),
-            "Backward directive 'program.r' should resolve relative to file's directory"
-        );
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_block_bindings(child, text, out);
+    }
+}
 
-        // With PathContext::from_metadata (incorrect for backward directives):
-        // "program.r" would resolve to /project/program.r (wrong!)
-        let resolved_from_meta =
-            crate::cross_file::path_resolve::resolve_path(backward_path, &ctx_from_meta);
-        assert_eq!(
-            resolved_from_meta,
-            Some(std::path::PathBuf::from("/project/program.r")),
-            "With @lsp-cd, 'program.r' would incorrectly resolve to workspace root"
-        );
+/// Searches every indexed document (the same five sources
+/// `call_hierarchy_incoming_calls` draws on) for a top-level definition of
+/// `name`, skipping `skip_uri` — the file reporting the undefined-variable
+/// diagnostic. Reuses `collect_block_bindings` since a top-level binding is
+/// exactly what it already collects. Returns the first matching file found;
+/// callers only need one candidate to offer a `source()` quick fix.
+fn find_top_level_definition_uri(state: &WorldState, skip_uri: &Url, name: &str) -> Option<Url> {
+    fn defines(tree: &tree_sitter::Tree, text: &str, name: &str) -> bool {
+        let mut bindings = Vec::new();
+        collect_block_bindings(tree.root_node(), text, &mut bindings);
+        bindings.iter().any(|(bound_name, _)| bound_name == name)
+    }
 
-        // The key assertion: the two resolutions are DIFFERENT
-        // This demonstrates why using PathContext::new is essential for backward directives
-        assert_ne!(
-            resolved_new, resolved_from_meta,
-            "PathContext::new and PathContext::from_metadata should produce different results when @lsp-cd is present"
-        );
+    for file_uri in state.document_store.uris() {
+        if file_uri == *skip_uri {
+            continue;
+        }
+        if let Some(doc_state) = state.document_store.get_without_touch(&file_uri) {
+            if let Some(tree) = &doc_state.tree {
+                if defines(tree, &doc_state.text(), name) {
+                    return Some(file_uri);
+                }
+            }
+        }
     }
 
-    // ========================================================================
-    // Else Newline Syntax Error Tests (Task 1.3)
-    // Tests for else-newline-syntax-error feature
-    // Validates: Requirements 2.1, 2.2, 2.3, 2.4
-    // ========================================================================
+    for (file_uri, entry) in state.workspace_index_new.iter() {
+        if file_uri == *skip_uri {
+            continue;
+        }
+        if let Some(tree) = &entry.tree {
+            if defines(tree, &entry.contents.to_string(), name) {
+                return Some(file_uri);
+            }
+        }
+    }
 
-    /// Test that `if (x) {y}\nelse {z}` emits a diagnostic for orphaned else.
-    /// Validates: Requirement 2.1 - else on new line after closing brace should emit diagnostic
-    #[test]
-    fn test_else_newline_basic_invalid_pattern() {
-        let code = "if (x) {y}\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    for entry in state.documents.iter() {
+        let (file_uri, doc) = (entry.key(), entry.value());
+        if file_uri == skip_uri || state.document_store.contains(file_uri) {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            if defines(tree, &doc.text(), name) {
+                return Some(file_uri.clone());
+            }
+        }
+    }
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned else on new line"
-        );
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Diagnostic severity should be ERROR"
-        );
-        assert!(
-            diagnostics[0].message.contains("else"),
-            "Diagnostic message should mention 'else'"
-        );
-        assert!(
-            diagnostics[0].message.contains("same line"),
-            "Diagnostic message should mention 'same line'"
-        );
+    for (file_uri, doc) in &state.workspace_index {
+        if file_uri == skip_uri || state.workspace_index_new.contains(file_uri) {
+            continue;
+        }
+        if let Some(tree) = &doc.tree {
+            if defines(tree, &doc.text(), name) {
+                return Some(file_uri.clone());
+            }
+        }
     }
 
-    /// Test that `if (x) {y} else {z}` does NOT emit a diagnostic.
-    /// Validates: Requirement 2.3 - else on same line as closing brace should not emit diagnostic
-    #[test]
-    fn test_else_newline_basic_valid_pattern() {
-        let code = "if (x) {y} else {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    None
+}
 
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit diagnostic when else is on same line as closing brace"
-        );
+/// Picks an unused name for the extracted function, based on a plain text
+/// search since the user is expected to rename it immediately after.
+fn unique_function_name(text: &str) -> String {
+    let base = "extracted_function";
+    if !text.contains(base) {
+        return base.to_string();
+    }
+    let mut n = 2;
+    loop {
+        let candidate = format!("{}_{}", base, n);
+        if !text.contains(&candidate) {
+            return candidate;
+        }
+        n += 1;
     }
+}
 
-    /// Test that multi-line valid if-else does NOT emit a diagnostic.
-    /// `if (x) {\n  y\n} else {\n  z\n}` - else on same line as closing brace
-    /// Validates: Requirement 2.4 - multi-line with else on same line as brace should not emit diagnostic
-    #[test]
-    fn test_else_newline_multiline_valid_pattern() {
-        let code = "if (x) {\n  y\n} else {\n  z\n}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+fn extract_function_edit(root: Node, text: &str, uri: &Url, range: Range) -> Option<WorkspaceEdit> {
+    let start = Point::new(range.start.line as usize, range.start.character as usize);
+    let end = Point::new(range.end.line as usize, range.end.character as usize);
+    if (start.row, start.column) >= (end.row, end.column) {
+        return None;
+    }
 
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit diagnostic when else is on same line as closing brace (multi-line)"
-        );
+    if crosses_boundary(root, start, end, "function_definition")
+        || crosses_boundary(root, start, end, "binary_operator")
+    {
+        return None;
     }
 
-    /// Test that multi-line invalid if-else emits a diagnostic.
-    /// `if (x) {\n  y\n}\nelse {\n  z\n}` - else on new line after closing brace
-    /// Validates: Requirement 2.2 - multi-line if with else on new line after brace should emit diagnostic
-    #[test]
-    fn test_else_newline_multiline_invalid_pattern() {
-        let code = "if (x) {\n  y\n}\nelse {\n  z\n}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let (block, first_idx, last_idx) = selected_statements(root, start, end)?;
+    let mut cursor = block.walk();
+    let children: Vec<Node> = block
+        .children(&mut cursor)
+        .filter(|child| child.is_named())
+        .collect();
+    let stmt_start = children[first_idx].start_position();
+    let stmt_end = children[last_idx].end_position();
+    let in_block = |p: Point| {
+        (p.row, p.column) >= (stmt_start.row, stmt_start.column)
+            && (p.row, p.column) < (stmt_end.row, stmt_end.column)
+    };
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned else on new line (multi-line)"
-        );
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Diagnostic severity should be ERROR"
-        );
-    }
+    let scopes = LocalScopeTree::build(root, text);
 
-    /// Test that the diagnostic range covers the `else` keyword exactly.
-    /// Validates: Requirement 3.2 - diagnostic range should highlight the else keyword
-    #[test]
-    fn test_else_newline_diagnostic_range() {
-        let code = "if (x) {y}\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    // Parameters: identifiers used in the selection whose binding lives in an
+    // enclosing (non-global) scope won't be visible from the new top-level
+    // function, so they need to be passed in. A binding in the global scope
+    // (0) stays visible since the new function is also inserted at the top
+    // level; a binding inside the selection itself is just a local.
+    let mut usages = Vec::new();
+    collect_usages_with_context(root, text, &UsageContext::default(), &mut usages);
 
-        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+    let mut params: Vec<String> = Vec::new();
+    for (name, node) in &usages {
+        if !in_block(node.start_position()) {
+            continue;
+        }
+        let Some((scope_idx, binding)) = scopes.resolve_at_with_scope(node.start_position(), name)
+        else {
+            continue;
+        };
+        if scope_idx == 0 || in_block(binding.start_position()) {
+            continue;
+        }
+        if !params.contains(name) {
+            params.push(name.clone());
+        }
+    }
 
-        let diag = &diagnostics[0];
-        // "else" starts at line 1 (0-indexed), column 0
-        assert_eq!(
-            diag.range.start.line, 1,
-            "Diagnostic should start on line 1 (0-indexed)"
-        );
-        assert_eq!(
-            diag.range.start.character, 0,
-            "Diagnostic should start at column 0"
-        );
-        // "else" is 4 characters long
-        assert_eq!(
-            diag.range.end.line, 1,
-            "Diagnostic should end on line 1"
-        );
-        assert_eq!(
-            diag.range.end.character, 4,
-            "Diagnostic should end at column 4 (covering 'else')"
-        );
+    // Return value: a name first assigned inside the selection (in its own
+    // enclosing scope) that's still read somewhere outside the selection.
+    let mut block_bindings = Vec::new();
+    for idx in first_idx..=last_idx {
+        collect_block_bindings(children[idx], text, &mut block_bindings);
     }
 
-    // ========================================================================
-    // Nested If-Else Tests (Task 2.1)
-    // Tests for nested if-else detection
-    // Validates: Requirements 2.5
-    // ========================================================================
+    let block_scope_idx = scopes.scope_at(stmt_start);
+    let mut return_names: Vec<String> = Vec::new();
+    for (name, _) in &block_bindings {
+        let Some(binding) = scopes.scopes[block_scope_idx].bindings.get(name) else {
+            continue;
+        };
+        if !in_block(binding.start_position()) {
+            continue;
+        }
+        let occurrences = collect_scoped_occurrences(root, text, &scopes, *binding, name, true);
+        let used_after = occurrences
+            .iter()
+            .any(|occurrence| !in_block(occurrence.start_position()));
+        if used_after {
+            return_names.push(name.clone());
+        }
+    }
 
-    /// Test that nested valid if-else does NOT emit a diagnostic.
-    /// `if (a) { if (b) {c} else {d} } else {e}` - all else on same line as closing brace
-    /// Validates: Requirement 2.5 - nested if-else with valid else placement should not emit diagnostic
-    #[test]
-    fn test_else_newline_nested_valid_pattern() {
-        let code = "if (a) { if (b) {c} else {d} } else {e}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let func_name = unique_function_name(text);
+    let call_indent = " ".repeat(children[first_idx].start_position().column);
 
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit diagnostic when all else keywords are on same line as closing brace (nested)"
-        );
-    }
+    // Re-indent the extracted statements as a function body: the first line
+    // has no leading whitespace to strip, later lines have their original
+    // base indentation replaced with a 2-space body indent.
+    let base_indent = " ".repeat(stmt_start.column);
+    let body: String = text[children[first_idx].start_byte()..children[last_idx].end_byte()]
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let stripped = if i == 0 {
+                line
+            } else {
+                line.strip_prefix(base_indent.as_str()).unwrap_or(line)
+            };
+            format!("  {}", stripped)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let return_stmt = match return_names.len() {
+        0 => String::new(),
+        1 => format!("\n  return({})", return_names[0]),
+        _ => {
+            let fields = return_names
+                .iter()
+                .map(|name| format!("{} = {}", name, name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("\n  return(list({}))", fields)
+        }
+    };
 
-    /// Test that nested invalid if-else emits a diagnostic for the inner orphaned else.
-    /// `if (a) { if (b) {c}\nelse {d} }` - inner else on new line after closing brace
-    /// Validates: Requirement 2.5 - nested if-else with orphaned else should emit diagnostic
-    #[test]
-    fn test_else_newline_nested_invalid_inner_else() {
-        let code = "if (a) { if (b) {c}\nelse {d} }";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let top_level_stmt = enclosing_top_level_statement(root, children[first_idx])?;
+    let insert_pos = Position::new(top_level_stmt.start_position().row as u32, 0);
+    let function_text = format!(
+        "{} <- function({}) {{\n{}{}\n}}\n\n",
+        func_name,
+        params.join(", "),
+        body,
+        return_stmt
+    );
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned inner else on new line (nested)"
-        );
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Diagnostic severity should be ERROR"
-        );
-        // The inner else is on line 1 (0-indexed)
-        assert_eq!(
-            diagnostics[0].range.start.line, 1,
-            "Diagnostic should be on line 1 (0-indexed) where the orphaned else is"
-        );
-    }
+    let call_args = params.join(", ");
+    let call_text = match return_names.len() {
+        0 => format!("{}({})", func_name, call_args),
+        1 => format!("{} <- {}({})", return_names[0], func_name, call_args),
+        _ => {
+            let mut lines = vec![format!(".extracted <- {}({})", func_name, call_args)];
+            for name in &return_names {
+                lines.push(format!("{}{} <- .extracted${}", call_indent, name, name));
+            }
+            lines.join("\n")
+        }
+    };
 
-    /// Test that nested invalid if-else with outer orphaned else emits a diagnostic.
-    /// `if (a) { if (b) {c} else {d} }\nelse {e}` - outer else on new line
-    /// Validates: Requirement 2.5 - nested if-else with orphaned outer else should emit diagnostic
-    #[test]
-    fn test_else_newline_nested_invalid_outer_else() {
-        let code = "if (a) { if (b) {c} else {d} }\nelse {e}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![
+            TextEdit {
+                range: Range {
+                    start: insert_pos,
+                    end: insert_pos,
+                },
+                new_text: function_text,
+            },
+            TextEdit {
+                range: Range {
+                    start: Position::new(stmt_start.row as u32, stmt_start.column as u32),
+                    end: Position::new(stmt_end.row as u32, stmt_end.column as u32),
+                },
+                new_text: call_text,
+            },
+        ],
+    );
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned outer else on new line (nested)"
-        );
-        // The outer else is on line 1 (0-indexed)
-        assert_eq!(
-            diagnostics[0].range.start.line, 1,
-            "Diagnostic should be on line 1 (0-indexed) where the orphaned outer else is"
-        );
-    }
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    })
+}
 
-    /// Test that deeply nested if-else with multiple orphaned else keywords emits multiple diagnostics.
-    /// Validates: Requirement 2.5 - all orphaned else at any nesting level should be detected
-    #[test]
-    fn test_else_newline_deeply_nested_multiple_invalid() {
-        // Both inner and outer else are on new lines
-        let code = "if (a) { if (b) {c}\nelse {d} }\nelse {e}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+/// Picks an unused name for the extracted variable, based on a plain text
+/// search since the user is expected to rename it immediately after. Mirrors
+/// `unique_function_name`.
+fn unique_variable_name(text: &str) -> String {
+    let base = "extracted_value";
+    if !text.contains(base) {
+        return base.to_string();
+    }
+    let mut n = 2;
+    loop {
+        let candidate = format!("{}_{}", base, n);
+        if !text.contains(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
 
-        assert_eq!(
-            diagnostics.len(),
-            2,
-            "Should emit two diagnostics for both orphaned else keywords (nested)"
-        );
+/// "Extract expression to variable": inserts `name <- <expr>` on the line
+/// above the selection, indented to match that line, and replaces the
+/// selection with a reference to `name`. The selected node is the smallest
+/// named node covering `range`; whole blocks and the target of an assignment
+/// aren't meaningful expressions to extract, so those are rejected.
+fn extract_variable_edit(root: Node, text: &str, uri: &Url, range: Range) -> Option<WorkspaceEdit> {
+    let start = Point::new(range.start.line as usize, range.start.character as usize);
+    let end = Point::new(range.end.line as usize, range.end.character as usize);
+    let node = root.named_descendant_for_point_range(start, end)?;
+
+    if matches!(node.kind(), "program" | "brace_list") {
+        return None;
+    }
+    if let Some(parent) = node.parent() {
+        if parent.kind() == "binary_operator" {
+            let mut cursor = parent.walk();
+            let children: Vec<_> = parent.children(&mut cursor).collect();
+            if children.len() >= 2 {
+                let assignment_target = if children[0].id() == node.id() {
+                    Some(children[1])
+                } else if children.len() >= 3 && children[2].id() == node.id() {
+                    Some(children[1])
+                } else {
+                    None
+                };
+                if let Some(op) = assignment_target {
+                    if matches!(node_text(op, text), "<-" | "=" | "<<-" | "->" | "->>") {
+                        return None;
+                    }
+                }
+            }
+        }
     }
 
-    // ========================================================================
-    // Else If Pattern Tests (Task 2.2)
-    // Tests for `else if` on new line detection
-    // Validates: Requirements 5.2
-    // ========================================================================
+    let node_start = node.start_position();
+    let node_end = node.end_position();
+    let line_len = text
+        .lines()
+        .nth(node_start.row)
+        .map(|line| line.len() - line.trim_start().len())
+        .unwrap_or(0);
+    let indent = " ".repeat(line_len);
+
+    let name = unique_variable_name(text);
+    let selected_text = &text[node.start_byte()..node.end_byte()];
+    let insert_pos = Position::new(node_start.row as u32, 0);
+
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![
+            TextEdit {
+                range: Range {
+                    start: insert_pos,
+                    end: insert_pos,
+                },
+                new_text: format!("{}{} <- {}\n", indent, name, selected_text),
+            },
+            TextEdit {
+                range: Range {
+                    start: Position::new(node_start.row as u32, node_start.column as u32),
+                    end: Position::new(node_end.row as u32, node_end.column as u32),
+                },
+                new_text: name,
+            },
+        ],
+    );
 
-    /// Test that `if (x) {y}\nelse if (z) {w}` emits a diagnostic for orphaned else.
-    /// Validates: Requirement 5.2 - `else if` on new line should emit diagnostic
-    #[test]
-    fn test_else_newline_else_if_on_new_line() {
-        let code = "if (x) {y}\nelse if (z) {w}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    })
+}
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned 'else if' on new line"
-        );
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Diagnostic severity should be ERROR"
-        );
-        // The else is on line 1 (0-indexed), column 0
-        assert_eq!(
-            diagnostics[0].range.start.line, 1,
-            "Diagnostic should start on line 1 (0-indexed)"
-        );
-        assert_eq!(
-            diagnostics[0].range.start.character, 0,
-            "Diagnostic should start at column 0"
-        );
+/// Walks up from the node at `range` to the nearest enclosing `binary_operator`
+/// whose operator is `<-` or `=` — the two assignment spellings this action
+/// converts between. A named call argument (`name = value`) parses as an
+/// `argument` node rather than a `binary_operator`, so it's never mistaken
+/// for one of these.
+fn find_assignment_operator<'a>(
+    root: Node<'a>,
+    text: &str,
+    start: Point,
+    end: Point,
+) -> Option<Node<'a>> {
+    let mut node = root.descendant_for_point_range(start, end)?;
+    loop {
+        if node.kind() == "binary_operator" {
+            let mut cursor = node.walk();
+            let children: Vec<_> = node.children(&mut cursor).collect();
+            if children.len() >= 2 && matches!(node_text(children[1], text), "<-" | "=") {
+                return Some(node);
+            }
+        }
+        node = node.parent()?;
     }
+}
 
-    /// Test that `if (x) {y} else if (z) {w}` does NOT emit a diagnostic.
-    /// Validates: Requirement 5.2 - valid `else if` on same line should not emit diagnostic
-    #[test]
-    fn test_else_newline_else_if_on_same_line() {
-        let code = "if (x) {y} else if (z) {w}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+/// "Convert '=' to '<-' assignment" (and its reverse): rewrites just the
+/// operator token of a top-level assignment, leaving both operands alone.
+fn convert_assignment_operator_edit(
+    root: Node,
+    text: &str,
+    uri: &Url,
+    range: Range,
+) -> Option<(WorkspaceEdit, String)> {
+    let start = Point::new(range.start.line as usize, range.start.character as usize);
+    let end = Point::new(range.end.line as usize, range.end.character as usize);
+    let node = find_assignment_operator(root, text, start, end)?;
 
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit diagnostic when 'else if' is on same line as closing brace"
-        );
-    }
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.children(&mut cursor).collect();
+    let op_node = children[1];
+    let (new_op, title) = match node_text(op_node, text) {
+        "<-" => ("=", "Convert '<-' to '=' assignment"),
+        "=" => ("<-", "Convert '=' to '<-' assignment"),
+        _ => return None,
+    };
 
-    /// Test that multi-line `else if` on new line emits a diagnostic.
-    /// `if (x) {\n  y\n}\nelse if (z) {\n  w\n}` - else if on new line after closing brace
-    /// Validates: Requirement 5.2 - multi-line `else if` on new line should emit diagnostic
-    #[test]
-    fn test_else_newline_else_if_multiline_invalid() {
-        let code = "if (x) {\n  y\n}\nelse if (z) {\n  w\n}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let op_start = op_node.start_position();
+    let op_end = op_node.end_position();
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: Range {
+                start: Position::new(op_start.row as u32, op_start.column as u32),
+                end: Position::new(op_end.row as u32, op_end.column as u32),
+            },
+            new_text: new_op.to_string(),
+        }],
+    );
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned 'else if' on new line (multi-line)"
-        );
-        // The else is on line 3 (0-indexed)
-        assert_eq!(
-            diagnostics[0].range.start.line, 3,
-            "Diagnostic should be on line 3 (0-indexed) where the orphaned else is"
-        );
+    Some((
+        WorkspaceEdit {
+            changes: Some(changes),
+            document_changes: None,
+            change_annotations: None,
+        },
+        title.to_string(),
+    ))
+}
+
+/// Offers to rewrite a bare call `fn(...)` as `pkg::fn(...)`, analogous to
+/// rust-analyzer's replace-qualified-name-with-use but in the opposite
+/// direction: R has no imports to add, so qualifying the call site is the
+/// only way to pin down which attached package's `fn` is meant. One action
+/// per candidate package when the name is masked (exported by more than
+/// one attached package, e.g. `filter` from both `dplyr` and `stats`); none
+/// when it's exported by zero or by every attached package already searched
+/// (nothing to disambiguate). The edit only inserts `pkg::` immediately
+/// before the function-name node, leaving the call's arguments untouched.
+fn qualify_call_edits(
+    state: &WorldState,
+    uri: &Url,
+    root: Node,
+    text: &str,
+    range: Range,
+) -> Vec<(WorkspaceEdit, String)> {
+    let start = Point::new(range.start.line as usize, range.start.character as usize);
+    let end = Point::new(range.end.line as usize, range.end.character as usize);
+    let Some(node) = root
+        .named_descendant_for_point_range(start, end)
+        .or_else(|| root.descendant_for_point_range(start, end))
+    else {
+        return Vec::new();
+    };
+
+    if node.kind() != "identifier" {
+        return Vec::new();
+    }
+    let Some(call_node) = node.parent() else {
+        return Vec::new();
+    };
+    if call_node.kind() != "call" || call_node.child_by_field_name("function") != Some(node) {
+        return Vec::new();
     }
 
-    /// Test that valid multi-line `else if` does NOT emit a diagnostic.
-    /// `if (x) {\n  y\n} else if (z) {\n  w\n}` - else if on same line as closing brace
-    /// Validates: Requirement 5.2 - valid multi-line `else if` should not emit diagnostic
-    #[test]
-    fn test_else_newline_else_if_multiline_valid() {
-        let code = "if (x) {\n  y\n} else if (z) {\n  w\n}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let func_name = node_text(node, text);
+    let scope = get_cross_file_scope(state, uri, range.start.line, range.start.character);
+    let loaded_packages: Vec<String> = scope
+        .inherited_packages
+        .iter()
+        .chain(scope.loaded_packages.iter())
+        .cloned()
+        .collect();
 
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit diagnostic when 'else if' is on same line as closing brace (multi-line)"
-        );
+    let candidates = state
+        .package_library
+        .find_all_packages_for_symbol(func_name, &loaded_packages);
+    if candidates.is_empty() {
+        return Vec::new();
     }
 
-    // ========================================================================
-    // Blank Lines Tests (Task 2.3)
-    // Tests for blank lines between `}` and `else`
-    // Validates: Requirements 5.4
-    // ========================================================================
+    let func_start = node.start_position();
+    let insert_pos = Position::new(func_start.row as u32, func_start.column as u32);
 
-    /// Test that `if (x) {y}\n\nelse {z}` emits a diagnostic for orphaned else.
-    /// Validates: Requirement 5.4 - blank lines between `}` and `else` should emit diagnostic
-    #[test]
-    fn test_else_newline_blank_lines_between_brace_and_else() {
-        let code = "if (x) {y}\n\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    candidates
+        .into_iter()
+        .map(|package| {
+            let mut changes = HashMap::new();
+            changes.insert(
+                uri.clone(),
+                vec![TextEdit {
+                    range: Range {
+                        start: insert_pos,
+                        end: insert_pos,
+                    },
+                    new_text: format!("{}::", package),
+                }],
+            );
+            (
+                WorkspaceEdit {
+                    changes: Some(changes),
+                    document_changes: None,
+                    change_annotations: None,
+                },
+                format!("Qualify as {}::{}", package, func_name),
+            )
+        })
+        .collect()
+}
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned else with blank line between"
-        );
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Diagnostic severity should be ERROR"
-        );
-        // The else is on line 2 (0-indexed) due to the blank line
-        assert_eq!(
-            diagnostics[0].range.start.line, 2,
-            "Diagnostic should start on line 2 (0-indexed) after blank line"
-        );
-        assert_eq!(
-            diagnostics[0].range.start.character, 0,
-            "Diagnostic should start at column 0"
-        );
-    }
+// ============================================================================
+// Path Utilities
+// ============================================================================
 
-    /// Test that multiple blank lines between `}` and `else` still emit a diagnostic.
-    /// Validates: Requirement 5.4 - multiple blank lines should still trigger diagnostic
-    #[test]
-    fn test_else_newline_multiple_blank_lines() {
-        let code = "if (x) {y}\n\n\n\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+/// Compute relative path from workspace root to target URI.
+/// If no workspace root or target is outside workspace, returns filename only.
+fn compute_relative_path(target_uri: &Url, workspace_root: Option<&Url>) -> String {
+    let Some(workspace_root) = workspace_root else {
+        return target_uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or("unknown")
+            .to_string();
+    };
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned else with multiple blank lines"
-        );
-        // The else is on line 4 (0-indexed) due to multiple blank lines
-        assert_eq!(
-            diagnostics[0].range.start.line, 4,
-            "Diagnostic should start on line 4 (0-indexed) after multiple blank lines"
-        );
-    }
+    let Ok(workspace_path) = workspace_root.to_file_path() else {
+        return target_uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or("unknown")
+            .to_string();
+    };
 
-    /// Test that multi-line if with blank lines before else emits a diagnostic.
-    /// `if (x) {\n  y\n}\n\nelse {\n  z\n}` - blank line between closing brace and else
-    /// Validates: Requirement 5.4 - multi-line with blank lines should emit diagnostic
-    #[test]
-    fn test_else_newline_multiline_with_blank_lines() {
-        let code = "if (x) {\n  y\n}\n\nelse {\n  z\n}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let Ok(target_path) = target_uri.to_file_path() else {
+        return target_uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or("unknown")
+            .to_string();
+    };
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit exactly one diagnostic for orphaned else with blank line (multi-line)"
-        );
-        // The closing brace is on line 2 (0-indexed), else is on line 4
-        assert_eq!(
-            diagnostics[0].range.start.line, 4,
-            "Diagnostic should be on line 4 (0-indexed) where the orphaned else is"
-        );
+    match target_path.strip_prefix(&workspace_path) {
+        Ok(relative) => relative.to_string_lossy().to_string(),
+        Err(_) => target_uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or("unknown")
+            .to_string(),
     }
+}
 
-    // ========================================================================
-    // Edge Case Tests (Task 2.4)
-    // Additional edge case tests for else-newline detection
-    // Validates: Requirements 5.1, 5.3
-    // ========================================================================
+/// Builds a clickable `[path:line](uri#Lline)` Markdown link to a definition
+/// site, e.g. `[utils.R:3](file:///.../utils.R#L3)`. `line` is 0-based.
+fn definition_link(uri: &Url, line: u32, workspace_root: Option<&Url>) -> String {
+    let relative_path = compute_relative_path(uri, workspace_root);
+    format!("[{}:{}]({}#L{})", relative_path, line + 1, uri, line + 1)
+}
 
-    /// Test that standalone `else` without preceding `if` does NOT emit a duplicate diagnostic.
-    /// Tree-sitter handles this as a general syntax error, so we should not emit our
-    /// newline-specific diagnostic to avoid duplicates.
-    /// Validates: Requirement 5.1 - standalone else should not emit newline-specific diagnostic
-    #[test]
-    fn test_else_newline_standalone_else_no_duplicate() {
-        let code = "else {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+/// Renders a roxygen2 comment block (as collected by `collect_roxygen_docs`)
+/// as Markdown suitable for appending under a hover code block: the leading
+/// free-text paragraph becomes the description, `@param name desc` becomes a
+/// bolded parameter list, `@details` and `@seealso` become labeled sections,
+/// `@return`/`@returns` becomes a "Returns:" line, and `@examples` content
+/// becomes a fenced ```r block. Inline `\code{}`/`\link{}` and `[fn()]` markup
+/// is converted within the description, parameter, details, returns, and
+/// seealso text (but left verbatim inside `@examples`, which is already code).
+fn render_roxygen_markdown(
+    docs: &str,
+    def_uri: &Url,
+    cross_file_symbols: &HashMap<String, ScopedSymbol>,
+    workspace_root: Option<&Url>,
+    hover_config: HoverConfig,
+) -> String {
+    let lines: Vec<&str> = docs.lines().collect();
+    let mut idx = 0;
+
+    let mut description = Vec::new();
+    while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+        description.push(lines[idx]);
+        idx += 1;
+    }
 
-        // The standalone else is a syntax error handled by tree-sitter.
-        // Our detector should NOT emit a diagnostic for this case to avoid duplicates.
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit newline-specific diagnostic for standalone else (tree-sitter handles this)"
-        );
+    let mut params: Vec<(String, String)> = Vec::new();
+    let mut returns: Option<String> = None;
+    let mut details: Option<String> = None;
+    let mut seealso: Option<String> = None;
+    let mut examples: Vec<String> = Vec::new();
+
+    while idx < lines.len() {
+        let line = lines[idx].trim_start();
+        if let Some(rest) = line.strip_prefix("@param ") {
+            let mut body = vec![rest.trim_start().to_string()];
+            idx += 1;
+            while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+                body.push(lines[idx].trim().to_string());
+                idx += 1;
+            }
+            let joined = body.join(" ");
+            match joined.split_once(char::is_whitespace) {
+                Some((param_name, desc)) => {
+                    params.push((param_name.to_string(), desc.trim().to_string()))
+                }
+                None => params.push((joined, String::new())),
+            }
+        } else if line.starts_with("@returns") || line.starts_with("@return") {
+            let rest = line
+                .strip_prefix("@returns")
+                .or_else(|| line.strip_prefix("@return"))
+                .unwrap_or("")
+                .trim_start();
+            let mut body = vec![rest.to_string()];
+            idx += 1;
+            while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+                body.push(lines[idx].trim().to_string());
+                idx += 1;
+            }
+            returns = Some(body.join(" ").trim().to_string());
+        } else if line.starts_with("@details") {
+            let rest = line.strip_prefix("@details").unwrap_or("").trim_start();
+            let mut body = vec![rest.to_string()];
+            idx += 1;
+            while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+                body.push(lines[idx].trim().to_string());
+                idx += 1;
+            }
+            details = Some(body.join("\n").trim().to_string());
+        } else if line.starts_with("@seealso") {
+            let rest = line.strip_prefix("@seealso").unwrap_or("").trim_start();
+            let mut body = vec![rest.to_string()];
+            idx += 1;
+            while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+                body.push(lines[idx].trim().to_string());
+                idx += 1;
+            }
+            seealso = Some(body.join(" ").trim().to_string());
+        } else if line.starts_with("@examples") {
+            let rest = line.strip_prefix("@examples").unwrap_or("").trim_start();
+            if !rest.is_empty() {
+                examples.push(rest.to_string());
+            }
+            idx += 1;
+            while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+                examples.push(lines[idx].to_string());
+                idx += 1;
+            }
+        } else {
+            // Unrecognized tag (@export, ...): skip it and its continuation
+            // lines rather than misreading them as description.
+            idx += 1;
+            while idx < lines.len() && !lines[idx].trim_start().starts_with('@') {
+                idx += 1;
+            }
+        }
     }
 
-    /// Test that comments on the same line as closing brace, with else on new line, emits diagnostic.
-    /// `if (x) {y} # comment\nelse {z}` - else is on a new line, so diagnostic should be emitted
-    /// Validates: Requirement 5.3 - comments between `}` and `else` on same line should not prevent
-    /// diagnostic when else is actually on a new line
-    #[test]
-    fn test_else_newline_comment_same_line_else_new_line() {
-        let code = "if (x) {y} # comment\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    let mut out = String::new();
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit diagnostic when else is on new line even with comment after closing brace"
-        );
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Diagnostic severity should be ERROR"
-        );
-        // The else is on line 1 (0-indexed)
-        assert_eq!(
-            diagnostics[0].range.start.line, 1,
-            "Diagnostic should start on line 1 (0-indexed) where the orphaned else is"
-        );
+    let description_text = description.join("\n").trim().to_string();
+    if !description_text.is_empty() {
+        out.push_str(&convert_inline_roxygen_markup(
+            &description_text,
+            def_uri,
+            cross_file_symbols,
+            workspace_root,
+            hover_config,
+        ));
+        out.push('\n');
     }
 
-    /// Test that comments between `}` and `else` on the SAME line does NOT emit diagnostic.
-    /// `if (x) {y} # comment else {z}` - this is actually invalid R syntax, but if else were
-    /// somehow on the same line, we should not emit diagnostic.
-    /// Note: In practice, `# comment else {z}` makes `else {z}` part of the comment.
-    /// This test verifies the valid case: `if (x) {y} else {z} # comment`
-    /// Validates: Requirement 5.3 - comments on same line should not affect detection
-    #[test]
-    fn test_else_newline_comment_after_else_same_line() {
-        let code = "if (x) {y} else {z} # comment";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    if !params.is_empty() {
+        out.push('\n');
+        for (name, desc) in &params {
+            let desc = convert_inline_roxygen_markup(
+                desc,
+                def_uri,
+                cross_file_symbols,
+                workspace_root,
+                hover_config,
+            );
+            out.push_str(&format!("- **{}**: {}\n", name, desc));
+        }
+    }
 
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should NOT emit diagnostic when else is on same line as closing brace (with trailing comment)"
-        );
+    if let Some(details_text) = &details {
+        if !details_text.is_empty() {
+            out.push('\n');
+            let details_md = convert_inline_roxygen_markup(
+                details_text,
+                def_uri,
+                cross_file_symbols,
+                workspace_root,
+                hover_config,
+            );
+            out.push_str(&details_md);
+            out.push('\n');
+        }
     }
 
-    // ========================================================================
-    // Diagnostic Properties Tests (Task 3.3)
-    // Comprehensive tests for diagnostic properties
-    // Validates: Requirements 3.1, 3.2, 3.3, 3.4
-    // ========================================================================
+    if let Some(returns_text) = &returns {
+        out.push('\n');
+        let returns_md = convert_inline_roxygen_markup(
+            returns_text,
+            def_uri,
+            cross_file_symbols,
+            workspace_root,
+            hover_config,
+        );
+        out.push_str(&format!("Returns: {}\n", returns_md));
+    }
 
-    /// Comprehensive test for all diagnostic properties.
-    /// Validates: Requirements 3.1 (severity), 3.2 (range), 3.3 (message), 3.4 (source)
-    #[test]
-    fn test_else_newline_diagnostic_properties_comprehensive() {
-        let code = "if (x) {y}\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+    if let Some(seealso_text) = &seealso {
+        if !seealso_text.is_empty() {
+            out.push('\n');
+            let seealso_md = convert_inline_roxygen_markup(
+                seealso_text,
+                def_uri,
+                cross_file_symbols,
+                workspace_root,
+                hover_config,
+            );
+            out.push_str(&format!("See also: {}\n", seealso_md));
+        }
+    }
 
-        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+    if !examples.is_empty() {
+        out.push('\n');
+        out.push_str("```r\n");
+        out.push_str(&examples.join("\n"));
+        out.push_str("\n```\n");
+    }
 
-        let diag = &diagnostics[0];
+    out.trim_end().to_string()
+}
 
-        // Requirement 3.1: Diagnostic severity SHALL be ERROR
-        assert_eq!(
-            diag.severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Requirement 3.1: Diagnostic severity should be ERROR"
-        );
+/// Converts roxygen inline markup within `text`: `\code{x}` (and the
+/// double-backslash form some roxygen blocks use) becomes `` `x` ``, and
+/// `\link{fn}` / `\link[pkg]{fn}` / `[fn()]` become a Markdown link to `fn`'s
+/// resolved definition when it resolves in `cross_file_symbols`, a
+/// `command:` link to R help for base/library functions when
+/// `hover_config` allows it, or `` `fn` `` otherwise.
+fn convert_inline_roxygen_markup(
+    text: &str,
+    def_uri: &Url,
+    cross_file_symbols: &HashMap<String, ScopedSymbol>,
+    workspace_root: Option<&Url>,
+    hover_config: HoverConfig,
+) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let remaining = &text[pos..];
+
+        if let Some(rest) = remaining
+            .strip_prefix("\\code{")
+            .or_else(|| remaining.strip_prefix("\\\\code{"))
+        {
+            if let Some(end) = rest.find('}') {
+                out.push('`');
+                out.push_str(&rest[..end]);
+                out.push('`');
+                pos += (remaining.len() - rest.len()) + end + 1;
+                continue;
+            }
+        }
 
-        // Requirement 3.3: Diagnostic message SHALL be descriptive
-        assert_eq!(
-            diag.message,
-            "In R, 'else' must appear on the same line as the closing '}' of the if block",
-            "Requirement 3.3: Diagnostic message should match expected text exactly"
-        );
+        if let Some(rest) = remaining.strip_prefix("\\link[") {
+            if let Some(bracket_end) = rest.find(']') {
+                let package = &rest[..bracket_end];
+                let after_bracket = &rest[bracket_end + 1..];
+                if let Some(brace_rest) = after_bracket.strip_prefix('{') {
+                    if let Some(end) = brace_rest.find('}') {
+                        out.push_str(&roxygen_link(
+                            &brace_rest[..end],
+                            Some(package),
+                            def_uri,
+                            cross_file_symbols,
+                            workspace_root,
+                            hover_config,
+                        ));
+                        pos += (remaining.len() - brace_rest.len()) + end + 1;
+                        continue;
+                    }
+                }
+            }
+        }
 
-        // Requirement 3.2: Diagnostic range SHALL highlight the `else` keyword
-        // "else" is on line 1 (0-indexed), columns 0-4
-        assert_eq!(
-            diag.range.start.line, 1,
-            "Requirement 3.2: Diagnostic range start line should be 1 (0-indexed)"
-        );
-        assert_eq!(
-            diag.range.start.character, 0,
-            "Requirement 3.2: Diagnostic range start character should be 0"
-        );
-        assert_eq!(
-            diag.range.end.line, 1,
-            "Requirement 3.2: Diagnostic range end line should be 1"
-        );
-        assert_eq!(
-            diag.range.end.character, 4,
-            "Requirement 3.2: Diagnostic range end character should be 4 (covering 'else')"
-        );
-    }
+        if let Some(rest) = remaining.strip_prefix("\\link{") {
+            if let Some(end) = rest.find('}') {
+                out.push_str(&roxygen_link(
+                    &rest[..end],
+                    None,
+                    def_uri,
+                    cross_file_symbols,
+                    workspace_root,
+                    hover_config,
+                ));
+                pos += (remaining.len() - rest.len()) + end + 1;
+                continue;
+            }
+        }
 
-    /// Test that diagnostic severity is ERROR for multi-line patterns.
-    /// Validates: Requirement 3.1 - severity should be ERROR
-    #[test]
-    fn test_else_newline_diagnostic_severity_multiline() {
-        let code = "if (condition) {\n  print(1)\n}\nelse {\n  print(2)\n}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
+        if remaining.starts_with('[') {
+            if let Some(end) = remaining.find(']') {
+                let inner = &remaining[1..end];
+                if let Some(fn_name) = inner.strip_suffix("()") {
+                    out.push_str(&roxygen_link(
+                        fn_name,
+                        None,
+                        def_uri,
+                        cross_file_symbols,
+                        workspace_root,
+                        hover_config,
+                    ));
+                    pos += end + 1;
+                    continue;
+                }
+            }
+        }
 
-        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
-        assert_eq!(
-            diagnostics[0].severity,
-            Some(DiagnosticSeverity::ERROR),
-            "Requirement 3.1: Diagnostic severity should be ERROR for multi-line patterns"
-        );
+        let ch = remaining.chars().next().unwrap();
+        out.push(ch);
+        pos += ch.len_utf8();
     }
 
-    /// Test that diagnostic range accurately covers the else keyword in various positions.
-    /// Validates: Requirement 3.2 - range should highlight else keyword
-    #[test]
-    fn test_else_newline_diagnostic_range_with_indentation() {
-        // else is indented with spaces
-        let code = "if (x) {y}\n    else {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
-
-        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+    out
+}
 
-        let diag = &diagnostics[0];
-        // "else" starts at line 1, column 4 (after 4 spaces)
-        assert_eq!(
-            diag.range.start.line, 1,
-            "Diagnostic should start on line 1"
-        );
-        assert_eq!(
-            diag.range.start.character, 4,
-            "Diagnostic should start at column 4 (after indentation)"
-        );
-        assert_eq!(
-            diag.range.end.character, 8,
-            "Diagnostic should end at column 8 (covering 'else')"
-        );
+/// Builds a Markdown link to `fn_name`'s resolved definition, mirroring the
+/// `compute_relative_path`-based link hover already shows for symbols defined
+/// in another file, with a `#L{line}` fragment so editors can jump straight
+/// to the definition. `package` is the explicit package name from a
+/// `\link[pkg]{fn}` reference, if any.
+///
+/// When `fn_name` isn't a known cross-file symbol, falls back to a
+/// `command:` link opening R help — using `package` when given, or "base"
+/// when `fn_name` is a recognized builtin — provided the client supports
+/// command links and `hover_config.link_unresolved_refs_to_help` is set.
+/// Otherwise falls back to a plain backtick span.
+fn roxygen_link(
+    fn_name: &str,
+    package: Option<&str>,
+    def_uri: &Url,
+    cross_file_symbols: &HashMap<String, ScopedSymbol>,
+    workspace_root: Option<&Url>,
+    hover_config: HoverConfig,
+) -> String {
+    match cross_file_symbols.get(fn_name) {
+        Some(symbol) if symbol.source_uri == *def_uri => {
+            format!(
+                "[{}]({}#L{})",
+                fn_name,
+                symbol.source_uri.as_str(),
+                symbol.defined_line + 1
+            )
+        }
+        Some(symbol) => {
+            let relative_path = compute_relative_path(&symbol.source_uri, workspace_root);
+            format!(
+                "[{} ({})]({}#L{})",
+                fn_name,
+                relative_path,
+                symbol.source_uri.as_str(),
+                symbol.defined_line + 1
+            )
+        }
+        None => {
+            let help_package = package
+                .map(|pkg| pkg.to_string())
+                .or_else(|| is_builtin(fn_name).then(|| "base".to_string()));
+            match help_package {
+                Some(pkg)
+                    if hover_config.supports_command_links
+                        && hover_config.link_unresolved_refs_to_help =>
+                {
+                    let args = serde_json::json!([{
+                        "name": fn_name,
+                        "package": pkg,
+                    }]);
+                    command_markdown_link(&format!("`{}`", fn_name), HOVER_OPEN_HELP_COMMAND, &args)
+                }
+                _ => format!("`{}`", fn_name),
+            }
+        }
     }
+}
 
-    /// Test that diagnostic message contains key information.
-    /// Validates: Requirement 3.3 - message should be descriptive
-    #[test]
-    fn test_else_newline_diagnostic_message_content() {
-        let code = "if (x) {y}\nelse {z}";
-        let tree = parse_r_code(code);
-        let mut diagnostics = Vec::new();
-        super::collect_else_newline_errors(tree.root_node(), code, &mut diagnostics);
-
-        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+/// Finds parameter defaults in a function's signature (or full definition
+/// text) that are bare identifiers resolving to a known cross-file symbol,
+/// and renders each as a `roxygen_link`. Used by `hover` to linkify a
+/// function's referenced defaults, e.g. `f <- function(x = default_config)`
+/// links `default_config` to where it's defined.
+fn linkify_function_parameter_defaults(
+    function_text: &str,
+    cross_file_symbols: &HashMap<String, ScopedSymbol>,
+    workspace_root: Option<&Url>,
+    hover_config: HoverConfig,
+) -> Vec<String> {
+    let mut parser = tree_sitter::Parser::new();
+    if parser
+        .set_language(&tree_sitter_r::LANGUAGE.into())
+        .is_err()
+    {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(function_text, None) else {
+        return Vec::new();
+    };
 
-        let message = &diagnostics[0].message;
+    let mut defaults = Vec::new();
+    collect_parameter_default_identifiers(tree.root_node(), function_text, &mut defaults);
+
+    defaults
+        .into_iter()
+        .filter(|name| cross_file_symbols.contains_key(name))
+        .map(|name| {
+            let def_uri = cross_file_symbols[&name].source_uri.clone();
+            roxygen_link(
+                &name,
+                None,
+                &def_uri,
+                cross_file_symbols,
+                workspace_root,
+                hover_config,
+            )
+        })
+        .collect()
+}
 
-        // Message should mention 'else'
-        assert!(
-            message.contains("else"),
-            "Requirement 3.3: Message should mention 'else'"
-        );
+fn collect_parameter_default_identifiers(node: Node, text: &str, out: &mut Vec<String>) {
+    if node.kind() == "parameters" {
+        let mut param_cursor = node.walk();
+        for param in node.children(&mut param_cursor) {
+            if param.kind() != "parameter" {
+                continue;
+            }
+            let children: Vec<_> = param.children(&mut param.walk()).collect();
+            if children.len() == 3
+                && node_text(children[1], text) == "="
+                && children[2].kind() == "identifier"
+            {
+                out.push(node_text(children[2], text).to_string());
+            }
+        }
+    }
 
-        // Message should mention 'same line'
-        assert!(
-            message.contains("same line"),
-            "Requirement 3.3: Message should mention 'same line'"
-        );
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_parameter_default_identifiers(child, text, out);
+    }
+}
 
-        // Message should mention the closing brace
-        assert!(
-            message.contains("}") || message.contains("closing"),
-            "Requirement 3.3: Message should mention the closing brace"
-        );
+// Note: escape_markdown is only used in tests now.
+// Code blocks (```r ... ```) don't need escaping - markdown doesn't interpret special chars inside them.
+#[cfg(test)]
+/// Escape markdown special characters in text.
+/// Characters to escape: * _ [ ] ( ) # ` \
+fn escape_markdown(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '*' | '_' | '[' | ']' | '(' | ')' | '#' | '`' | '\\' => format!("\\{}", c),
+            _ => c.to_string(),
+        })
+        .collect()
+}
 
-        // Message should mention 'if'
-        assert!(
-            message.contains("if"),
-            "Requirement 3.3: Message should mention 'if'"
-        );
+#[cfg(test)]
+fn hover_blocking(state: &WorldState, uri: &Url, position: Position) -> Option<Hover> {
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.block_on(hover(state, uri, position))
+    } else {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(hover(state, uri, position))
     }
 }
 
+// ============================================================================
+// Tests
+// ============================================================================
+
 #[cfg(test)]
-mod proptests {
+mod tests {
     use super::*;
-    use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
-    use crate::state::Document;
-    use proptest::prelude::*;
     use std::collections::HashSet;
 
-    // Helper to parse R code for property tests
     fn parse_r_code(code: &str) -> tree_sitter::Tree {
         let mut parser = tree_sitter::Parser::new();
         parser
@@ -5255,4281 +9319,9504 @@ mod proptests {
         parser.parse(code, None).unwrap()
     }
 
-    // Helper to filter out R reserved keywords from generated identifiers
-    fn is_r_reserved(s: &str) -> bool {
-        matches!(
-            s,
-            "for"
-                | "if"
-                | "in"
-                | "else"
-                | "while"
-                | "repeat"
-                | "next"
-                | "break"
-                | "function"
-                | "return"
-                | "true"
-                | "false"
-                | "null"
-                | "inf"
-                | "nan"
-        )
-    }
+    #[test]
+    fn test_function_parameters_recognized() {
+        let code = "f <- function(a, b) { a + b }";
+        let tree = parse_r_code(code);
+        let mut defined = HashSet::new();
+        collect_definitions(tree.root_node(), code, &mut defined);
 
-    proptest! {
-        #[test]
-        fn test_library_require_extraction(pkg_name in "[a-z]{3,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))) {
-            let code_library = format!("library({})", pkg_name);
-            let code_require = format!("require({})", pkg_name);
-            let code_loadns = format!("loadNamespace(\"{}\")", pkg_name);
+        assert!(defined.contains("f"), "Function name should be defined");
+        assert!(defined.contains("a"), "Parameter 'a' should be defined");
+        assert!(defined.contains("b"), "Parameter 'b' should be defined");
+    }
 
-            let doc1 = Document::new(&code_library, None);
-            let doc2 = Document::new(&code_require, None);
-            let doc3 = Document::new(&code_loadns, None);
+    #[test]
+    fn test_single_parameter() {
+        let code = "square <- function(x) { x * x }";
+        let tree = parse_r_code(code);
+        let mut defined = HashSet::new();
+        collect_definitions(tree.root_node(), code, &mut defined);
 
-            prop_assert!(doc1.loaded_packages.contains(&pkg_name));
-            prop_assert!(doc2.loaded_packages.contains(&pkg_name));
-            prop_assert!(doc3.loaded_packages.contains(&pkg_name));
-        }
+        assert!(defined.contains("square"));
+        assert!(defined.contains("x"));
+    }
 
-        #[test]
-        fn test_multiple_library_calls(pkg_count in 1usize..5) {
-            let packages: Vec<String> = (0..pkg_count)
-                .map(|i| format!("pkg{}", i))
-                .collect();
+    #[test]
+    fn test_no_parameters() {
+        let code = "get_pi <- function() { 3.14 }";
+        let tree = parse_r_code(code);
+        let mut defined = HashSet::new();
+        collect_definitions(tree.root_node(), code, &mut defined);
 
-            let code = packages.iter()
-                .map(|p| format!("library({})", p))
-                .collect::<Vec<_>>()
-                .join("\n");
+        assert!(defined.contains("get_pi"));
+    }
 
-            let doc = Document::new(&code, None);
+    #[test]
+    fn test_builtin_functions() {
+        assert!(is_builtin("warning"));
+        assert!(is_builtin("any"));
+        assert!(is_builtin("is.na"));
+        assert!(is_builtin("sprintf"));
+        assert!(is_builtin("print"));
+        assert!(is_builtin("sum"));
+        assert!(is_builtin("mean"));
+    }
 
-            for pkg in &packages {
-                prop_assert!(doc.loaded_packages.contains(pkg));
-            }
-            prop_assert_eq!(doc.loaded_packages.len(), pkg_count);
-        }
+    #[test]
+    fn test_builtin_constants() {
+        assert!(is_builtin("TRUE"));
+        assert!(is_builtin("FALSE"));
+        assert!(is_builtin("NULL"));
+        assert!(is_builtin("NA"));
+        assert!(is_builtin("Inf"));
+        assert!(is_builtin("NaN"));
+    }
 
-        #[test]
-        fn test_mixed_symbol_types(
-            var_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            builtin in prop::sample::select(vec!["print", "sum", "mean", "length"])
-        ) {
-            let code = format!(
-                "{} <- 42\n{} <- function() {{}}\n{}({})",
-                var_name, func_name, builtin, var_name
-            );
+    #[test]
+    fn test_not_builtin() {
+        assert!(!is_builtin("my_custom_function"));
+        assert!(!is_builtin("undefined_var"));
+    }
 
-            let tree = parse_r_code(&code);
-            let mut defined = HashSet::new();
-            collect_definitions(tree.root_node(), &code, &mut defined);
+    #[test]
+    fn test_nested_function_parameters() {
+        let code = "outer <- function(x) { inner <- function(y) { x + y }; inner }";
+        let tree = parse_r_code(code);
+        let mut defined = HashSet::new();
+        collect_definitions(tree.root_node(), code, &mut defined);
 
-            prop_assert!(defined.contains(&var_name));
-            prop_assert!(defined.contains(&func_name));
-            prop_assert!(is_builtin(&builtin));
-        }
+        assert!(defined.contains("outer"));
+        assert!(defined.contains("x"));
+        assert!(defined.contains("inner"));
+        assert!(defined.contains("y"));
+    }
 
-        #[test]
-        fn test_named_arguments_not_flagged(
-            func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            arg_name in "[a-z]{2,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            value in 1i32..100
-        ) {
-            let code = format!("{}({} = {})", func_name, arg_name, value);
+    #[test]
+    fn test_extract_parameters_simple() {
+        let code = "add <- function(a, b = 1) { }";
+        let tree = parse_r_code(code);
 
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages(tree.root_node(), &code, &mut used);
+        let func_node = find_function_definition(tree.root_node()).unwrap();
+        let mut cursor = func_node.walk();
+        let params_node = func_node
+            .children(&mut cursor)
+            .find(|n| n.kind() == "parameters")
+            .unwrap();
 
-            // func_name should be in used, but arg_name should NOT be
-            let func_used = used.iter().any(|(name, _)| name == &func_name);
-            let arg_used = used.iter().any(|(name, _)| name == &arg_name);
+        let params = extract_parameters(params_node, code);
+        assert_eq!(params, vec!["a", "b = 1"]);
+    }
 
-            prop_assert!(func_used, "Function name should be collected as usage");
-            prop_assert!(!arg_used, "Named argument should NOT be collected as usage");
-        }
+    #[test]
+    fn test_extract_function_signature() {
+        let code = "add <- function(a, b = 1) { }";
+        let tree = parse_r_code(code);
 
-        #[test]
-        fn test_multiple_named_arguments(
-            arg_count in 1usize..4
-        ) {
-            let args: Vec<String> = (0..arg_count)
-                .map(|i| format!("arg{} = {}", i, i + 1))
-                .collect();
+        let func_node = find_function_definition(tree.root_node()).unwrap();
+        let signature = extract_function_signature(func_node, "add", code);
+        assert_eq!(signature, "add(a, b = 1)");
+    }
 
-            let code = format!("func({})", args.join(", "));
+    #[test]
+    fn test_signature_simple_function() {
+        let code = "add <- function(a, b) { a + b }";
+        let tree = parse_r_code(code);
 
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages(tree.root_node(), &code, &mut used);
+        let func_node = find_function_definition_node(tree.root_node(), "add", code).unwrap();
+        let signature = extract_function_signature(func_node, "add", code);
+        assert_eq!(signature, "add(a, b)");
+    }
 
-            // None of the argument names should be flagged as usages
-            for i in 0..arg_count {
-                let arg_name = format!("arg{}", i);
-                let arg_used = used.iter().any(|(name, _)| name == &arg_name);
-                prop_assert!(!arg_used, "Named argument {} should not be flagged", arg_name);
-            }
-        }
+    #[test]
+    fn test_signature_no_parameters() {
+        let code = "get_pi <- function() { 3.14 }";
+        let tree = parse_r_code(code);
 
-        #[test]
-        fn test_parameter_extraction_completeness(
-            param_count in 1usize..5,
-            has_defaults in prop::collection::vec(any::<bool>(), 1..5)
-        ) {
-            let param_count = param_count.min(has_defaults.len());
-            let mut params = Vec::new();
+        let func_node = find_function_definition_node(tree.root_node(), "get_pi", code).unwrap();
+        let signature = extract_function_signature(func_node, "get_pi", code);
+        assert_eq!(signature, "get_pi()");
+    }
 
-            for i in 0..param_count {
-                if has_defaults[i] {
-                    params.push(format!("p{} = {}", i, i + 1));
-                } else {
-                    params.push(format!("p{}", i));
-                }
-            }
+    #[test]
+    fn test_signature_with_defaults() {
+        let code = "greet <- function(name = \"World\") { }";
+        let tree = parse_r_code(code);
 
-            let code = format!("f <- function({}) {{}}", params.join(", "));
-            let tree = parse_r_code(&code);
+        let func_node = find_function_definition_node(tree.root_node(), "greet", code).unwrap();
+        let signature = extract_function_signature(func_node, "greet", code);
+        assert_eq!(signature, "greet(name = \"World\")");
+    }
 
-            // Find function definition node
-            let func_node = find_function_definition_node(tree.root_node(), "f", &code).unwrap();
-            let signature = extract_function_signature(func_node, "f", &code);
+    #[test]
+    fn test_signature_with_dots() {
+        let code = "wrapper <- function(...) { }";
+        let tree = parse_r_code(code);
 
-            // All parameters should be present in signature
-            for i in 0..param_count {
-                let param_name = format!("p{}", i);
-                prop_assert!(signature.contains(&param_name),
-                    "Parameter {} should be in signature: {}", param_name, signature);
-            }
-        }
+        let func_node = find_function_definition_node(tree.root_node(), "wrapper", code).unwrap();
+        let signature = extract_function_signature(func_node, "wrapper", code);
+        assert_eq!(signature, "wrapper(...)");
+    }
 
-        #[test]
-        fn test_assignment_operators_recognized(
-            func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            op in prop::sample::select(vec!["<-", "=", "<<-"])
-        ) {
-            let code = format!("{} {} function() {{}}", func_name, op);
-            let tree = parse_r_code(&code);
+    /// `prepare_signature_help` tracks the active parameter by position.
+    #[test]
+    fn test_signature_help_positional_active_parameter() {
+        use crate::state::{Document, WorldState};
 
-            let func_def = find_function_definition_node(tree.root_node(), &func_name, &code);
-            prop_assert!(func_def.is_some(), "Function definition should be found for operator {}", op);
+        let code = "add <- function(a, b) { a + b }\nadd(1, ";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            if let Some(node) = func_def {
-                prop_assert_eq!(node.kind(), "function_definition");
-            }
-        }
+        let ctx = super::prepare_signature_help(&state, &uri, Position::new(1, 7)).unwrap();
+        assert_eq!(ctx.positional_index, 1);
+        assert_eq!(ctx.active_name, None);
+    }
 
-        #[test]
-        fn test_search_priority(func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))) {
-            use crate::state::{WorldState, Document};
-            use tower_lsp::lsp_types::Url;
+    /// A named argument (`name = value`) matches `activeParameter` to the
+    /// formal parameter literally named `name`, not the positional count.
+    #[test]
+    fn test_signature_help_named_argument_overrides_position() {
+        use crate::state::{Document, WorldState};
 
-            let current_uri = Url::parse("file:///current.R").unwrap();
-            let other_uri = Url::parse("file:///other.R").unwrap();
-            let workspace_uri = Url::parse("file:///workspace.R").unwrap();
+        let code = "add <- function(a, b) { a + b }\nadd(b = ";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            // Create function definitions with different signatures
-            let current_code = format!("{} <- function(a) {{ a }}", func_name);
-            let other_code = format!("{} <- function(b, c) {{ b + c }}", func_name);
-            let workspace_code = format!("{} <- function(x, y, z) {{ x + y + z }}", func_name);
+        let ctx = super::prepare_signature_help(&state, &uri, Position::new(1, 8)).unwrap();
+        assert_eq!(ctx.active_name.as_deref(), Some("b"));
+        let params = ctx.resolved_params.clone().unwrap();
+        assert_eq!(
+            resolve_active_parameter(&params, ctx.positional_index, ctx.active_name.as_deref()),
+            1
+        );
+    }
 
-            let mut state = WorldState::new(vec![]);
-            state.documents.insert(current_uri.clone(), Document::new(&current_code, None));
-            state.documents.insert(other_uri.clone(), Document::new(&other_code, None));
-            state.workspace_index.insert(workspace_uri.clone(), Document::new(&workspace_code, None));
+    /// Only commas belonging to the innermost call count toward the active
+    /// parameter; a nested call's own commas don't leak into the outer one.
+    #[test]
+    fn test_signature_help_nested_call_counts_only_inner_commas() {
+        use crate::state::{Document, WorldState};
 
-            // Search should return current document's definition first
-            let signature = find_user_function_signature(&state, &current_uri, &func_name);
-            prop_assert!(signature.is_some());
+        let code = "outer <- function(a, b) { a }\ninner <- function(x, y) { x }\nouter(inner(1, ";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            if let Some(sig) = signature {
-                prop_assert!(sig.contains("(a)"), "Should return current document's signature: {}", sig);
-                prop_assert!(!sig.contains("(b, c)"), "Should not return other document's signature");
-                prop_assert!(!sig.contains("(x, y, z)"), "Should not return workspace signature");
-            }
-        }
+        let ctx = super::prepare_signature_help(&state, &uri, Position::new(2, 15)).unwrap();
+        assert_eq!(ctx.func_name, "inner");
+        assert_eq!(ctx.positional_index, 1);
     }
 
+    /// A cursor sitting right after `(` with no arguments yet resolves to
+    /// `activeParameter` 0.
     #[test]
-    fn test_extract_definition_statement_variable() {
-        use crate::cross_file::scope::SymbolKind;
-
-        let code = "x <- 42\ny <- x + 1";
-        let tree = parse_r_code(code);
+    fn test_signature_help_empty_call_defaults_to_first_parameter() {
+        use crate::state::{Document, WorldState};
 
-        let symbol = ScopedSymbol {
-            name: "x".to_string(),
-            kind: SymbolKind::Variable,
-            source_uri: Url::parse("file:///test.R").unwrap(),
-            defined_line: 0,
-            defined_column: 0,
-            signature: None,
-        };
+        let code = "add <- function(a, b) { a + b }\nadd(";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        let result = extract_statement_from_tree(&tree, &symbol, code);
-        assert!(result.is_some());
-        let info = result.unwrap();
-        assert_eq!(info.statement, "x <- 42");
+        let ctx = super::prepare_signature_help(&state, &uri, Position::new(1, 4)).unwrap();
+        assert_eq!(ctx.positional_index, 0);
+        assert_eq!(ctx.active_name, None);
     }
 
+    /// Once the positional index reaches `...`, every later argument stays
+    /// pinned on `...` rather than overflowing past the end of the parameter list.
     #[test]
-    fn test_extract_definition_statement_function() {
-        let code = "f <- function(a, b) {\n  a + b\n}";
-        let tree = parse_r_code(code);
-
-        let symbol = ScopedSymbol {
-            name: "f".to_string(),
-            kind: SymbolKind::Function,
-            source_uri: Url::parse("file:///test.R").unwrap(),
-            defined_line: 0,
-            defined_column: 0,
-            signature: Some("f(a, b)".to_string()),
-        };
+    fn test_resolve_active_parameter_dots_absorbs_trailing_args() {
+        let params = vec!["a".to_string(), "...".to_string()];
+        assert_eq!(resolve_active_parameter(&params, 1, None), 1);
+        assert_eq!(resolve_active_parameter(&params, 5, None), 1);
+    }
 
-        let result = extract_statement_from_tree(&tree, &symbol, code);
-        assert!(result.is_some());
-        let info = result.unwrap();
-        assert_eq!(info.statement, "f <- function(a, b) {\n  a + b\n}");
+    fn is_arg_count_diagnostic(d: &Diagnostic) -> bool {
+        d.code
+            == Some(NumberOrString::String(
+                diagnostic_codes::ARG_COUNT_MISMATCH.to_string(),
+            ))
     }
 
+    /// Too few arguments for a function's required parameters should be flagged.
     #[test]
-    fn test_extract_definition_statement_truncation() {
-        let mut code = "long_func <- function() {\n".to_string();
-        for i in 1..=15 {
-            code.push_str(&format!("  line_{}\n", i));
-        }
-        code.push('}');
+    fn test_arg_count_too_few_required() {
+        use crate::state::{Document, WorldState};
 
-        let tree = parse_r_code(&code);
+        let code = "add <- function(a, b) { a + b }\nadd(1)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        let symbol = ScopedSymbol {
-            name: "long_func".to_string(),
-            kind: SymbolKind::Function,
-            source_uri: Url::parse("file:///test.R").unwrap(),
-            defined_line: 0,
-            defined_column: 0,
-            signature: None,
-        };
-
-        let result = extract_statement_from_tree(&tree, &symbol, &code);
-        assert!(result.is_some());
-        let info = result.unwrap();
-
-        // Should be truncated to 10 lines with ellipsis
-        let lines: Vec<&str> = info.statement.lines().collect();
-        assert_eq!(lines.len(), 11); // 10 lines + "..."
-        assert_eq!(lines[10], "...");
+        let diags = super::diagnostics(&state, &uri);
+        let arg_count_diags: Vec<_> = diags
+            .iter()
+            .filter(|d| is_arg_count_diagnostic(d))
+            .collect();
+        assert_eq!(arg_count_diags.len(), 1);
+        assert!(arg_count_diags[0].message.contains("requires at least 2"));
     }
 
+    /// The diagnostic range should cover the call's argument list, not the
+    /// callee name - that's what's wrong and what a fix would touch.
     #[test]
-    fn test_extract_definition_statement_assignment_operators() {
-        let test_cases = vec![
-            ("x <- 42", "<-"),
-            ("y = 100", "="),
-            ("z <<- 'global'", "<<-"),
-        ];
-
-        for (code, op) in test_cases {
-            let tree = parse_r_code(code);
-            let var_name = code.split_whitespace().next().unwrap();
+    fn test_arg_count_range_covers_argument_list() {
+        use crate::state::{Document, WorldState};
 
-            let symbol = ScopedSymbol {
-                name: var_name.to_string(),
-                kind: SymbolKind::Variable,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+        let code = "add <- function(a, b) { a + b }\nadd(1)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let result = extract_statement_from_tree(&tree, &symbol, code);
-            assert!(
-                result.is_some(),
-                "Should extract statement for operator {}",
-                op
-            );
-            let info = result.unwrap();
-            assert_eq!(info.statement, code);
-        }
+        let diags = super::diagnostics(&state, &uri);
+        let arg_count_diags: Vec<_> = diags
+            .iter()
+            .filter(|d| is_arg_count_diagnostic(d))
+            .collect();
+        assert_eq!(arg_count_diags.len(), 1);
+        let range = arg_count_diags[0].range;
+        assert_eq!(range.start.line, 1);
+        // "add(1)" - the callee name "add" spans columns 0..3; the argument
+        // list starts at the opening paren (column 3), not column 0.
+        assert!(
+            range.start.character >= 3,
+            "range should start at the argument list, not the callee name: {:?}",
+            range
+        );
     }
 
+    /// Too many positional arguments to a function without `...` should be flagged.
     #[test]
-    fn test_extract_definition_statement_for_loop_iterator() {
-        let code = "for (i in 1:10) {\n  print(i)\n}";
-        let tree = parse_r_code(code);
+    fn test_arg_count_too_many_no_dots() {
+        use crate::state::{Document, WorldState};
 
-        let symbol = ScopedSymbol {
-            name: "i".to_string(),
-            kind: SymbolKind::Variable,
-            source_uri: Url::parse("file:///test.R").unwrap(),
-            defined_line: 0,
-            defined_column: 5, // Position of 'i' in for loop
-            signature: None,
-        };
+        let code = "add <- function(a, b) { a + b }\nadd(1, 2, 3)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        let result = extract_statement_from_tree(&tree, &symbol, code);
-        assert!(result.is_some());
-        let info = result.unwrap();
-        assert_eq!(info.statement, "for (i in 1:10) {\n  print(i)\n}");
+        let diags = super::diagnostics(&state, &uri);
+        let arg_count_diags: Vec<_> = diags
+            .iter()
+            .filter(|d| is_arg_count_diagnostic(d))
+            .collect();
+        assert_eq!(arg_count_diags.len(), 1);
+        assert!(arg_count_diags[0].message.contains("accepts at most 2"));
     }
 
+    /// `...` disables the too-many-arguments check entirely.
     #[test]
-    fn test_readlines_named_arg() {
-        // This is the exact code from collate.r line 13
-        let code = r#"run_hash <- trimws(readLines("output/oos/latest_hash.txt", n = 1))"#;
-        let tree = parse_r_code(code);
+    fn test_arg_count_dots_allows_extra_positional() {
+        use crate::state::{Document, WorldState};
 
-        let mut used = Vec::new();
-        collect_usages(tree.root_node(), code, &mut used);
+        let code = "wrapper <- function(a, ...) { a }\nwrapper(1, 2, 3, 4)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        eprintln!("\n=== Collected usages ===");
-        for (name, node) in &used {
-            eprintln!("  '{}' (kind: {})", name, node.kind());
-        }
+        let diags = super::diagnostics(&state, &uri);
+        assert!(
+            diags.iter().all(|d| !is_arg_count_diagnostic(d)),
+            "should not flag extra positional arguments when '...' is present"
+        );
+    }
 
-        // trimws and readLines should be collected, but n should NOT be
-        let trimws_used = used.iter().any(|(name, _)| name == "trimws");
-        let readlines_used = used.iter().any(|(name, _)| name == "readLines");
-        let n_used = used.iter().any(|(name, _)| name == "n");
+    /// A named argument matching a required parameter satisfies it, so the
+    /// remaining positional arguments are checked against what's left.
+    #[test]
+    fn test_arg_count_named_argument_satisfies_required() {
+        use crate::state::{Document, WorldState};
 
-        assert!(trimws_used, "trimws should be collected");
-        assert!(readlines_used, "readLines should be collected");
+        let code = "greet <- function(name, greeting) { }\ngreet(greeting = \"hi\", name = \"Bo\")";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let diags = super::diagnostics(&state, &uri);
         assert!(
-            !n_used,
-            "n should NOT be collected as it's a named argument"
+            diags.iter().all(|d| !is_arg_count_diagnostic(d)),
+            "both required parameters are satisfied by name, so no diagnostic should fire"
         );
     }
 
-    proptest! {
-        #![proptest_config(ProptestConfig {
-            cases: 100,
-            .. ProptestConfig::default()
-        })]
-        #[test]
-        fn test_user_defined_priority_over_builtins(
-            builtin in prop::sample::select(vec!["print", "sum", "mean", "length"])
-        ) {
-            use crate::state::{WorldState, Document};
-            use tower_lsp::lsp_types::Url;
-
-            let uri = Url::parse("file:///test.R").unwrap();
+    /// A named argument whose name matches no parameter isn't counted toward
+    /// the positional tally, so it can't mask a too-few-arguments error.
+    #[test]
+    fn test_arg_count_unknown_named_argument_not_counted() {
+        use crate::state::{Document, WorldState};
 
-            // Create code with user-defined function that shadows a built-in
-            let code = format!("{} <- function(x, y) {{ x + y }}", builtin);
+        let code = "add <- function(a, b) { a + b }\nadd(unknown = 1)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let mut state = WorldState::new(vec![]);
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+        let diags = super::diagnostics(&state, &uri);
+        let arg_count_diags: Vec<_> = diags
+            .iter()
+            .filter(|d| is_arg_count_diagnostic(d))
+            .collect();
+        assert_eq!(arg_count_diags.len(), 1);
+        assert!(arg_count_diags[0].message.contains("requires at least 2"));
+    }
 
-            // Should return user-defined signature, not built-in
-            let signature = find_user_function_signature(&state, &uri, &builtin);
-            prop_assert!(signature.is_some(), "Should find user-defined function");
+    /// A call that satisfies the required parameters exactly should not be flagged.
+    #[test]
+    fn test_arg_count_exact_match_no_diagnostic() {
+        use crate::state::{Document, WorldState};
 
-            if let Some(sig) = signature {
-                prop_assert!(sig.contains("(x, y)"), "Should return user-defined signature: {}", sig);
-                prop_assert!(sig.contains(&builtin), "Should contain function name: {}", sig);
-            }
-        }
+        let code = "add <- function(a, b = 1) { a + b }\nadd(1)\nadd(1, 2)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        #[test]
-        fn test_signature_format_correctness(
-            func_name in "[a-z][a-z0-9_]{2,10}",
-            param_count in 0usize..5
-        ) {
-            let params: Vec<String> = (0..param_count)
-                .map(|i| format!("p{}", i))
-                .collect();
+        let diags = super::diagnostics(&state, &uri);
+        assert!(
+            diags.iter().all(|d| !is_arg_count_diagnostic(d)),
+            "one required + one defaulted parameter should accept 1 or 2 arguments"
+        );
+    }
 
-            let code = format!("{} <- function({}) {{}}", func_name, params.join(", "));
-            let tree = parse_r_code(&code);
+    fn is_unused_definition_diagnostic(d: &Diagnostic) -> bool {
+        d.code
+            == Some(NumberOrString::String(
+                diagnostic_codes::UNUSED_DEFINITION.to_string(),
+            ))
+    }
 
-            let func_node = find_function_definition_node(tree.root_node(), &func_name, &code).unwrap();
-            let signature = extract_function_signature(func_node, &func_name, &code);
+    /// A local variable that's assigned and never read back should be flagged.
+    #[test]
+    fn test_unused_definition_flags_dead_assignment() {
+        use crate::state::{Document, WorldState};
 
-            // Verify format: name(params)
-            prop_assert!(signature.starts_with(&func_name), "Signature should start with function name");
-            prop_assert!(signature.contains('('), "Signature should contain opening parenthesis");
-            prop_assert!(signature.ends_with(')'), "Signature should end with closing parenthesis");
+        let code = "x <- 1\ny <- 2\nprint(y)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let expected = format!("{}({})", func_name, params.join(", "));
-            prop_assert_eq!(signature, expected, "Signature format should match expected pattern");
-        }
+        let diags = super::diagnostics(&state, &uri);
+        let unused: Vec<_> = diags
+            .iter()
+            .filter(|d| is_unused_definition_diagnostic(d))
+            .collect();
+        assert_eq!(unused.len(), 1);
+        assert!(unused[0].message.contains("'x'"));
+        assert_eq!(unused[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+    }
 
-        #[test]
-        // Feature: enhanced-variable-detection-hover, Property 10: Variable hover definition extraction
-        fn prop_variable_hover_definition_extraction(
-            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            value in 1i32..1000
-        ) {
-            let code = format!("{} <- {}", var_name, value);
-            let tree = parse_r_code(&code);
+    /// A variable read inside a nested function body counts as used against
+    /// the enclosing definition - scoping, not flat name matching.
+    #[test]
+    fn test_unused_definition_respects_nested_scope() {
+        use crate::state::{Document, WorldState};
 
-            let symbol = ScopedSymbol {
-                name: var_name.clone(),
-                kind: SymbolKind::Variable,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+        let code = "x <- 1\nf <- function() { print(x) }";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract definition for variable");
+        let diags = super::diagnostics(&state, &uri);
+        assert!(
+            diags.iter().all(|d| !is_unused_definition_diagnostic(d)),
+            "x is read from the nested function, so it isn't dead"
+        );
+    }
 
-            let info = def_info.unwrap();
-            prop_assert_eq!(info.statement, code, "Should include complete definition statement");
-        }
+    /// Function parameters are never flagged, even when unused.
+    #[test]
+    fn test_unused_definition_skips_parameters() {
+        use crate::state::{Document, WorldState};
 
-        #[test]
-        // Feature: enhanced-variable-detection-hover, Property 11: Function hover signature extraction
-        fn prop_function_hover_signature_extraction(
-            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            param_count in 0usize..3
-        ) {
-            let params: Vec<String> = (0..param_count)
-                .map(|i| format!("p{}", i))
-                .collect();
+        let code = "f <- function(unused) { 1 }";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let code = format!("{} <- function({}) {{}}", func_name, params.join(", "));
-            let tree = parse_r_code(&code);
+        let diags = super::diagnostics(&state, &uri);
+        assert!(diags.iter().all(|d| !is_unused_definition_diagnostic(d)));
+    }
 
-            let symbol = ScopedSymbol {
-                name: func_name.clone(),
-                kind: SymbolKind::Function,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+    /// `<<-` assignments are skipped, since they're usually deliberate globals.
+    #[test]
+    fn test_unused_definition_skips_global_assignment() {
+        use crate::state::{Document, WorldState};
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract definition for function");
+        let code = "f <- function() { unused <<- 1 }";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let info = def_info.unwrap();
-            prop_assert!(info.statement.contains(&func_name), "Should include function name");
-            prop_assert!(info.statement.contains("function"), "Should include function keyword");
+        let diags = super::diagnostics(&state, &uri);
+        assert!(diags.iter().all(|d| !is_unused_definition_diagnostic(d)));
+    }
 
-            for param in &params {
-                prop_assert!(info.statement.contains(param), "Should include parameter {}", param);
-            }
-        }
+    /// A name read by another open document isn't flagged, since it may be
+    /// this file's exported interface rather than dead code.
+    #[test]
+    fn test_unused_definition_skips_cross_file_reference() {
+        use crate::state::{Document, WorldState};
 
-        #[test]
-        // Feature: enhanced-variable-detection-hover, Property 12: Multi-line definition handling
-        fn prop_multiline_definition_handling(
-            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            line_count in 5usize..15
-        ) {
-            let mut code = format!("{} <- function() {{\n", func_name);
-            for i in 1..line_count {
-                code.push_str(&format!("  line_{}\n", i));
-            }
-            code.push('}');
+        let code = "shared_value <- 1";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///lib.R").unwrap();
+        let other_uri = Url::parse("file:///main.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        state
+            .documents
+            .insert(other_uri, Document::new("print(shared_value)", None));
 
-            let tree = parse_r_code(&code);
+        let diags = super::diagnostics(&state, &uri);
+        assert!(diags.iter().all(|d| !is_unused_definition_diagnostic(d)));
+    }
 
-            let symbol = ScopedSymbol {
-                name: func_name.clone(),
-                kind: SymbolKind::Function,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+    /// The quick-fix deletes the whole dead assignment statement.
+    #[test]
+    fn test_unused_definition_fix_deletes_statement() {
+        use crate::state::{Document, WorldState};
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract multi-line definition");
+        let code = "x <- 1\ny <- 2\nprint(y)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let info = def_info.unwrap();
-            let lines: Vec<&str> = info.statement.lines().collect();
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| is_unused_definition_diagnostic(d))
+            .unwrap();
 
-            // The generated code has (line_count + 1) total lines (header + (line_count-1) body lines + closing brace).
-            // Truncation happens when total lines > 10, i.e. when line_count > 9.
-            if line_count > 9 {
-                prop_assert_eq!(lines.len(), 11, "Should truncate to 10 lines + ellipsis");
-                prop_assert_eq!(lines[10], "...", "Should end with ellipsis when truncated");
-            } else {
-                // The generated code includes the function header line and a closing brace line.
-                let expected_lines = line_count + 1;
-                prop_assert_eq!(lines.len(), expected_lines, "Should include all lines when <= 10");
-                prop_assert!(!info.statement.contains("..."), "Should not have ellipsis when not truncated");
-            }
-        }
+        let actions =
+            super::code_action(&state, &uri, diagnostic.range, &[diagnostic.clone()], None)
+                .unwrap();
+        let fix = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Delete unused assignment" =>
+                {
+                    action.edit.as_ref()
+                }
+                _ => None,
+            })
+            .unwrap();
 
-        #[test]
-        // Feature: enhanced-variable-detection-hover, Property 13: Markdown code block formatting
-        fn prop_markdown_code_block_formatting(
-            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            special_chars in prop::sample::select(vec!["*", "_", "[", "]", "(", ")", "#", "`", "\\"])
-        ) {
-            let code = format!("{} <- \"value with {} chars\"", var_name, special_chars);
-            let escaped = escape_markdown(&code);
-            let formatted = format!("```r\n{}\n```", escaped);
+        let edits = &fix.changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(0, 0));
+        assert_eq!(edits[0].range.end, Position::new(1, 0));
+    }
 
-            prop_assert!(formatted.starts_with("```r\n"), "Should start with R code block marker");
-            prop_assert!(formatted.ends_with("\n```"), "Should end with code block marker");
-            prop_assert!(formatted.contains(&format!("\\{}", special_chars)), "Should escape special markdown characters");
-        }
+    /// A `diagnostics.severityOverrides` entry remaps a code's severity.
+    #[test]
+    fn test_severity_override_remaps_severity() {
+        use crate::cross_file::{DiagnosticCode, DiagnosticSeverityConfig};
+        use crate::state::{Document, WorldState};
+        use std::collections::HashMap;
 
-        #[test]
-        // Feature: enhanced-variable-detection-hover, Property 14: Same-file location format
-        fn prop_same_file_location_format(
-            line_num in 0u32..100
-        ) {
-            let uri = Url::parse("file:///test.R").unwrap();
-            let def_info = DefinitionInfo {
-                statement: "test_var <- 42".to_string(),
-                source_uri: uri.clone(),
-                line: line_num,
-                column: 0,
-            };
+        let code = "x <- 1\ny <- 2\nprint(y)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let mut value = String::new();
-            value.push_str(&format!("```r\n{}\n```\n\n", escape_markdown(&def_info.statement)));
+        let mut raw = HashMap::new();
+        raw.insert(
+            DiagnosticCode::UnusedDefinition.as_str().to_string(),
+            "warning".to_string(),
+        );
+        state.cross_file_config.diagnostic_severity_overrides =
+            DiagnosticSeverityConfig::from_map(&raw);
 
-            if def_info.source_uri == uri {
-                value.push_str(&format!("this file, line {}", def_info.line + 1));
-            }
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| is_unused_definition_diagnostic(d))
+            .unwrap();
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::WARNING));
+    }
 
-            prop_assert!(value.contains("this file"), "Should indicate same file");
-            prop_assert!(value.contains(&format!("line {}", line_num + 1)), "Should show 1-based line number");
-            prop_assert!(!value.contains("file://"), "Should not contain file URI for same file");
-        }
+    /// A `diagnostics.severityOverrides` entry set to `off` drops the diagnostic.
+    #[test]
+    fn test_severity_override_off_drops_diagnostic() {
+        use crate::cross_file::{DiagnosticCode, DiagnosticSeverityConfig};
+        use crate::state::{Document, WorldState};
+        use std::collections::HashMap;
 
-        #[test]
-        // Feature: enhanced-variable-detection-hover, Property 15: Cross-file hyperlink format
-        fn prop_cross_file_hyperlink_format(
-            line_num in 0u32..100
-        ) {
-            let current_uri = Url::parse("file:///workspace/main.R").unwrap();
-            let def_uri = Url::parse("file:///workspace/utils/helper.R").unwrap();
-            let workspace_root = Some(Url::parse("file:///workspace/").unwrap());
+        let code = "x <- 1\ny <- 2\nprint(y)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let def_info = DefinitionInfo {
-                statement: "helper_func <- function() {}".to_string(),
-                source_uri: def_uri.clone(),
-                line: line_num,
-                column: 0,
-            };
+        let mut raw = HashMap::new();
+        raw.insert(
+            DiagnosticCode::UnusedDefinition.as_str().to_string(),
+            "off".to_string(),
+        );
+        state.cross_file_config.diagnostic_severity_overrides =
+            DiagnosticSeverityConfig::from_map(&raw);
 
-            let mut value = String::new();
-            value.push_str(&format!("```r\n{}\n```\n\n", escape_markdown(&def_info.statement)));
+        let diags = super::diagnostics(&state, &uri);
+        assert!(diags.iter().all(|d| !is_unused_definition_diagnostic(d)));
+    }
 
-            if def_info.source_uri != current_uri {
-                let relative_path = compute_relative_path(&def_info.source_uri, workspace_root.as_ref());
-                let absolute_path = def_info.source_uri.as_str();
-                value.push_str(&format!("[{}]({}), line {}", relative_path, absolute_path, def_info.line + 1));
-            }
+    /// A `diagnostics.severityOverrides` entry can downgrade the cross-file
+    /// undefined-variable check to a hint, same as any other code.
+    #[test]
+    fn test_severity_override_downgrades_undefined_variable_to_hint() {
+        use crate::cross_file::{DiagnosticCode, DiagnosticSeverityConfig};
+        use crate::state::{Document, WorldState};
+        use std::collections::HashMap;
 
-            prop_assert!(value.contains("[utils/helper.R]"), "Should show relative path in brackets");
-            prop_assert!(value.contains("(file:///workspace/utils/helper.R)"), "Should show absolute URI in parentheses");
-            prop_assert!(value.contains(&format!("line {}", line_num + 1)), "Should show 1-based line number");
-            prop_assert!(value.contains(", line"), "Should separate path and line with comma");
-        }
+        let code = "print(totally_undefined)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        state.cross_file_config.undefined_variables_enabled = true;
 
-        #[test]
-        // Property 21: Definition statement and location separation
-        fn prop_definition_statement_location_separation(
-            statement in "[a-z_]+ <- [a-z0-9_(){}]+",
-            line_num in 0u32..100
-        ) {
-            let def_info = DefinitionInfo {
-                statement: statement.clone(),
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                line: line_num,
-                column: 0,
-            };
+        let mut raw = HashMap::new();
+        raw.insert(
+            DiagnosticCode::UndefinedVariable.as_str().to_string(),
+            "hint".to_string(),
+        );
+        state.cross_file_config.diagnostic_severity_overrides =
+            DiagnosticSeverityConfig::from_map(&raw);
 
-            let escaped_statement = escape_markdown(&def_info.statement);
-            let mut value = String::new();
-            value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
-            value.push_str(&format!("this file, line {}", def_info.line + 1));
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| d.message.contains("totally_undefined"))
+            .expect("expected an undefined-variable diagnostic");
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::HINT));
+    }
 
-            // Should have exactly one blank line between definition and location
-            prop_assert!(value.contains("```\n\nthis file"), "Should have blank line separator");
-            prop_assert!(!value.contains("```\nthis file"), "Should not have zero blank lines");
-            prop_assert!(!value.contains("```\n\n\nthis file"), "Should not have multiple blank lines");
-        }
+    fn is_unloaded_namespace_package_diagnostic(d: &Diagnostic) -> bool {
+        d.code
+            == Some(NumberOrString::String(
+                diagnostic_codes::UNLOADED_NAMESPACE_PACKAGE.to_string(),
+            ))
+    }
 
-        #[test]
-        // Property 22: Definition statement truncation
-        fn prop_definition_statement_truncation(
-            line_count in 11usize..20
-        ) {
-            let mut statement = "long_func <- function() {\n".to_string();
-            for i in 1..line_count {
-                statement.push_str(&format!("  line_{}\n", i));
-            }
-            statement.push('}');
+    /// `pkg::fn()` is flagged when `pkg` was never loaded with `library()`.
+    #[test]
+    fn test_unloaded_namespace_package_flagged() {
+        use crate::state::{Document, WorldState};
 
-            let tree = parse_r_code(&statement);
-            let symbol = ScopedSymbol {
-                name: "long_func".to_string(),
-                kind: SymbolKind::Function,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+        let code = "dplyr::mutate(df, x = 1)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &statement);
-            prop_assert!(def_info.is_some(), "Should extract definition");
+        let diags = super::diagnostics(&state, &uri);
+        let matches: Vec<_> = diags
+            .iter()
+            .filter(|d| is_unloaded_namespace_package_diagnostic(d))
+            .collect();
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].message.contains("dplyr"));
+    }
 
-            let info = def_info.unwrap();
-            let lines: Vec<&str> = info.statement.lines().collect();
+    /// `pkg::fn()` is not flagged once `library(pkg)` appears earlier in the file.
+    #[test]
+    fn test_unloaded_namespace_package_skips_loaded_package() {
+        use crate::state::{Document, WorldState};
 
-            prop_assert_eq!(lines.len(), 11, "Should truncate to 10 lines + ellipsis");
-            prop_assert_eq!(lines[10], "...", "Should end with ellipsis");
-        }
+        let code = "library(dplyr)\ndplyr::mutate(df, x = 1)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        #[test]
-        // Property 23: Indentation preservation
-        fn prop_indentation_preservation(
-            indent_size in 0usize..8,
-            line_count in 2usize..6
-        ) {
-            let indent = " ".repeat(indent_size);
-            let mut statement = format!("{}func <- function() {{\n", indent);
-            for i in 1..line_count {
-                statement.push_str(&format!("{}  line_{}\n", indent, i));
-            }
-            statement.push_str(&format!("{}}}", indent));
+        let diags = super::diagnostics(&state, &uri);
+        assert!(
+            diags
+                .iter()
+                .all(|d| !is_unloaded_namespace_package_diagnostic(d)),
+            "should not flag a package already loaded earlier in the file"
+        );
+    }
 
-            let tree = parse_r_code(&statement);
-            let symbol = ScopedSymbol {
-                name: "func".to_string(),
-                kind: SymbolKind::Function,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: indent_size as u32,
-                signature: None,
-            };
+    /// The quick fix for an unloaded-namespace-package diagnostic inserts a
+    /// `library(pkg)` call at the top of the file.
+    #[test]
+    fn test_unloaded_namespace_package_fix_inserts_library_call() {
+        use crate::state::{Document, WorldState};
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &statement);
-            prop_assert!(def_info.is_some(), "Should extract definition");
+        let code = "dplyr::mutate(df, x = 1)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let info = def_info.unwrap();
-            let lines: Vec<&str> = info.statement.lines().collect();
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| is_unloaded_namespace_package_diagnostic(d))
+            .unwrap();
 
-            // Check that indentation is preserved
-            for line in &lines {
-                if !line.trim().is_empty() {
-                    prop_assert!(line.starts_with(&indent), "Should preserve original indentation: '{}'", line);
-                }
-            }
-        }
+        let actions =
+            super::code_action(&state, &uri, diagnostic.range, &[diagnostic.clone()], None)
+                .unwrap();
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a CodeAction");
+        };
+        let edits = &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "library(dplyr)\n");
+        assert_eq!(edits[0].range.start, Position::new(0, 0));
+    }
 
-        #[test]
-        // Property 24: Markdown character escaping
-        fn prop_markdown_character_escaping(
-            special_char in prop::sample::select(vec!["*", "_", "[", "]", "(", ")", "#", "`", "\\"])
-        ) {
-            let statement = format!("var <- \"value with {} char\"", special_char);
-            let escaped = escape_markdown(&statement);
-
-            let expected_escaped = format!("\\{}", special_char);
-            prop_assert!(escaped.contains(&expected_escaped),
-                "Should escape '{}' to '{}' in: '{}'", special_char, expected_escaped, escaped);
-
-            // Verify it's properly formatted in hover content
-            let hover_content = format!("```r\n{}\n```", escaped);
-            prop_assert!(hover_content.contains(&expected_escaped),
-                "Should contain escaped character in hover content");
-        }
-
-        #[test]
-        // Property 28: Assignment operator extraction
-        fn prop_assignment_operator_extraction(
-            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            op in prop::sample::select(vec!["<-", "=", "<<-"]),
-            value in 1i32..1000
-        ) {
-            let code = format!("{} {} {}", var_name, op, value);
-            let tree = parse_r_code(&code);
+    #[test]
+    fn test_prepare_call_hierarchy_resolves_function_definition() {
+        use crate::state::{Document, WorldState};
 
-            let symbol = ScopedSymbol {
-                name: var_name.clone(),
-                kind: SymbolKind::Variable,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+        let code = "add <- function(a, b) { a + b }\nadd(1, 2)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract assignment statement");
+        let items = super::prepare_call_hierarchy(&state, &uri, Position::new(1, 0)).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "add");
+        assert_eq!(items[0].uri, uri);
+    }
 
-            let info = def_info.unwrap();
-            let statement = &info.statement;
-            prop_assert_eq!(statement, &code, "Should include complete assignment statement");
-            prop_assert!(statement.contains(&op), "Should include assignment operator {}", op);
-        }
+    #[test]
+    fn test_call_hierarchy_incoming_calls_groups_by_caller() {
+        use crate::state::{Document, WorldState};
 
-        #[test]
-        // Property 29: Inline function extraction
-        fn prop_inline_function_extraction(
-            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            param_count in 0usize..3
-        ) {
-            let params: Vec<String> = (0..param_count)
-                .map(|i| format!("p{}", i))
-                .collect();
+        let code = "add <- function(a, b) { a + b }\ncaller <- function() { add(1, 2) }";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let code = format!("{} <- function({}) {{ {} }}", func_name, params.join(", "), "x + 1");
-            let tree = parse_r_code(&code);
+        let item = super::prepare_call_hierarchy(&state, &uri, Position::new(0, 0))
+            .unwrap()
+            .remove(0);
+        let incoming = super::call_hierarchy_incoming_calls(&state, &item).unwrap();
 
-            let symbol = ScopedSymbol {
-                name: func_name.clone(),
-                kind: SymbolKind::Function,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 0,
-                signature: None,
-            };
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].from.name, "caller");
+        assert_eq!(incoming[0].from_ranges.len(), 1);
+    }
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract function definition");
+    #[test]
+    fn test_call_hierarchy_incoming_calls_groups_top_level_call_under_script() {
+        use crate::state::{Document, WorldState};
 
-            let info = def_info.unwrap();
-            prop_assert!(info.statement.contains("function"), "Should include function keyword");
-            prop_assert!(info.statement.contains(&format!("({})", params.join(", "))), "Should include function signature");
-        }
+        let code = "add <- function(a, b) { a + b }\nadd(1, 2)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        #[test]
-        // Property 30: Loop iterator definition extraction
-        fn prop_loop_iterator_definition_extraction(
-            iterator in "[a-z][a-z0-9_]{1,5}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            range_end in 5i32..20
-        ) {
-            let code = format!("for ({} in 1:{}) {{\n  print({})\n}}", iterator, range_end, iterator);
-            let tree = parse_r_code(&code);
+        let item = super::prepare_call_hierarchy(&state, &uri, Position::new(0, 0))
+            .unwrap()
+            .remove(0);
+        let incoming = super::call_hierarchy_incoming_calls(&state, &item).unwrap();
 
-            let symbol = ScopedSymbol {
-                name: iterator.clone(),
-                kind: SymbolKind::Variable,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: 5, // Position of iterator in for loop
-                signature: None,
-            };
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].from.name, SCRIPT_LEVEL_CALLER_NAME);
+    }
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract for loop definition");
+    #[test]
+    fn test_call_hierarchy_outgoing_calls_resolves_callees() {
+        use crate::state::{Document, WorldState};
 
-            let info = def_info.unwrap();
-            prop_assert!(info.statement.contains("for"), "Should include for loop header");
-            prop_assert!(info.statement.contains(&format!("{} in", iterator)), "Should include iterator definition");
-        }
+        let code = "helper <- function(x) { x + 1 }\nmain <- function() { helper(1) }";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        #[test]
-        // Property 31: Function parameter definition extraction
-        fn prop_function_parameter_definition_extraction(
-            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            param_name in "[a-z][a-z0-9_]{1,5}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            has_default in any::<bool>()
-        ) {
-            let param_def = if has_default {
-                format!("{} = 42", param_name)
-            } else {
-                param_name.clone()
-            };
+        let item = super::prepare_call_hierarchy(&state, &uri, Position::new(1, 0))
+            .unwrap()
+            .remove(0);
+        let outgoing = super::call_hierarchy_outgoing_calls(&state, &item).unwrap();
 
-            let code = format!("{} <- function({}) {{\n  {}\n}}", func_name, param_def, param_name);
-            let tree = parse_r_code(&code);
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].to.name, "helper");
+        assert_eq!(outgoing[0].from_ranges.len(), 1);
+    }
 
-            let symbol = ScopedSymbol {
-                name: param_name.clone(),
-                kind: SymbolKind::Variable,
-                source_uri: Url::parse("file:///test.R").unwrap(),
-                defined_line: 0,
-                defined_column: func_name.len() as u32 + 15, // Approximate position in function signature
-                signature: None,
-            };
+    #[test]
+    fn test_call_hierarchy_outgoing_calls_resolves_shadowed_builtin_to_local_definition() {
+        use crate::state::{Document, WorldState};
 
-            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
-            prop_assert!(def_info.is_some(), "Should extract function definition for parameter");
+        // `mean` shadows the base R builtin of the same name; outgoing calls
+        // from `main` should resolve to the local definition, not be dropped
+        // as an unresolvable package call.
+        let code = "mean <- function(x) { sum(x) / length(x) }\nmain <- function() { mean(1:3) }";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            let info = def_info.unwrap();
-            prop_assert!(info.statement.contains("function"), "Should include function keyword");
-            prop_assert!(info.statement.contains(&param_name), "Should include parameter name in signature");
-        }
+        let item = super::prepare_call_hierarchy(&state, &uri, Position::new(1, 0))
+            .unwrap()
+            .remove(0);
+        let outgoing = super::call_hierarchy_outgoing_calls(&state, &item).unwrap();
 
-        #[test]
-        // Property 16: File URI protocol
-        fn prop_file_uri_protocol(
-            path_segments in prop::collection::vec("[a-z]{3,8}", 1..4)
-        ) {
-            let path = format!("/{}", path_segments.join("/"));
-            let uri = Url::parse(&format!("file://{}/test.R", path)).unwrap();
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].to.name, "mean");
+        assert_eq!(outgoing[0].to.uri, uri);
+    }
 
-            let def_info = DefinitionInfo {
-                statement: "test_var <- 42".to_string(),
-                source_uri: uri.clone(),
-                line: 0,
-                column: 0,
-            };
+    #[test]
+    fn test_selection_range_expands_identifier_to_program() {
+        use crate::state::{Document, WorldState};
 
-            let current_uri = Url::parse("file:///workspace/main.R").unwrap();
-            let mut value = String::new();
-            value.push_str(&format!("```r\n{}\n```\n\n", escape_markdown(&def_info.statement)));
+        let code = "f <- function() {\n  result <- g(name = x, y)\n}";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            if def_info.source_uri != current_uri {
-                let relative_path = compute_relative_path(&def_info.source_uri, None);
-                let absolute_path = def_info.source_uri.as_str();
-                value.push_str(&format!("[{}]({}), line {}", relative_path, absolute_path, def_info.line + 1));
+        // Cursor on `x`, the value of the named argument `name = x`.
+        let ranges = super::selection_range(&state, &uri, vec![Position::new(1, 21)]).unwrap();
+        assert_eq!(ranges.len(), 1);
+
+        // Walk the parent chain and collect each level's text, outermost last.
+        let lines: Vec<&str> = code.lines().collect();
+        let text_of = |range: &Range| -> String {
+            if range.start.line == range.end.line {
+                lines[range.start.line as usize]
+                    [range.start.character as usize..range.end.character as usize]
+                    .to_string()
+            } else {
+                format!("<{}..{}>", range.start.line, range.end.line)
             }
+        };
 
-            prop_assert!(value.contains("file://"), "Cross-file URI should use file:// protocol");
-            prop_assert!(value.contains(&format!("file://{}/test.R", path)), "Should contain absolute path with file:// protocol");
+        let mut levels = Vec::new();
+        let mut outermost_range = ranges[0].range;
+        let mut current = Some(&ranges[0]);
+        while let Some(sel) = current {
+            levels.push(text_of(&sel.range));
+            outermost_range = sel.range;
+            current = sel.parent.as_deref();
         }
 
-        #[test]
-        // Property 17: Relative path calculation
-        fn prop_relative_path_calculation(
-            workspace_depth in 1usize..3,
-            file_depth in 1usize..3
-        ) {
-            let workspace_segments: Vec<String> = (0..workspace_depth).map(|i| format!("ws{}", i)).collect();
-            let file_segments: Vec<String> = (0..file_depth).map(|i| format!("dir{}", i)).collect();
+        assert_eq!(levels[0], "x");
+        assert_eq!(levels[1], "name = x"); // argument wraps the identifier
+        assert_eq!(levels[2], "g(name = x, y)"); // call
+        assert_eq!(levels[3], "result <- g(name = x, y)"); // assignment
 
-            let workspace_root = Url::parse(&format!("file:///{}/", workspace_segments.join("/"))).unwrap();
-            let target_uri = Url::parse(&format!("file:///{}/{}/test.R", workspace_segments.join("/"), file_segments.join("/"))).unwrap();
+        // The outermost level is the whole program.
+        assert_eq!(outermost_range.start, Position::new(0, 0));
+        assert_eq!(outermost_range.end.line, 2);
+    }
 
-            let relative_path = compute_relative_path(&target_uri, Some(&workspace_root));
+    #[test]
+    fn test_selection_range_dedups_identifier_and_unnamed_argument() {
+        use crate::state::{Document, WorldState};
 
-            prop_assert!(relative_path.contains(&file_segments.join("/")), "Should contain file path relative to workspace");
-            prop_assert!(!relative_path.starts_with('/'), "Relative path should not start with /");
-            prop_assert!(relative_path.ends_with("test.R"), "Should end with filename");
-        }
+        let code = "f(x)";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        #[test]
-        // Property 18: LSP Markdown markup kind
-        fn prop_lsp_markdown_markup_kind(
-            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            use crate::state::{WorldState, Document};
+        let ranges = super::selection_range(&state, &uri, vec![Position::new(0, 2)]).unwrap();
+        assert_eq!(ranges.len(), 1);
 
-            let library_paths = vec![];
-            let mut state = WorldState::new(library_paths);
+        // An unnamed argument's node shares its byte range with the
+        // identifier it wraps, so that level is deduplicated away: the first
+        // expansion from `x` should jump straight to the whole call.
+        let first = &ranges[0];
+        assert_eq!(
+            first.range,
+            Range {
+                start: Position::new(0, 2),
+                end: Position::new(0, 3)
+            }
+        );
+        let parent = first.parent.as_deref().unwrap();
+        assert_eq!(
+            parent.range,
+            Range {
+                start: Position::new(0, 0),
+                end: Position::new(0, 4)
+            }
+        );
+    }
 
-            let uri = Url::parse("file:///test.R").unwrap();
-            let code = format!("{} <- 42", var_name);
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+    #[test]
+    fn test_selection_range_multiple_positions() {
+        use crate::state::{Document, WorldState};
 
-            let position = Position::new(0, 5);
-            let hover_result = hover_blocking(&state, &uri, position);
+        let code = "a <- 1\nb <- 2";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-            if let Some(hover) = hover_result {
-                if let HoverContents::Markup(content) = hover.contents {
-                    prop_assert_eq!(content.kind, MarkupKind::Markdown, "Hover content should use Markdown markup kind");
-                } else {
-                    prop_assert!(false, "Hover should return Markup content");
-                }
+        let ranges =
+            super::selection_range(&state, &uri, vec![Position::new(0, 0), Position::new(1, 0)])
+                .unwrap();
+
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(
+            ranges[0].range,
+            Range {
+                start: Position::new(0, 0),
+                end: Position::new(0, 1)
             }
-        }
+        );
+        assert_eq!(
+            ranges[1].range,
+            Range {
+                start: Position::new(1, 0),
+                end: Position::new(1, 1)
+            }
+        );
+    }
 
-        #[test]
-        // Property 19: Cross-file definition resolution
-        fn prop_cross_file_definition_resolution(
-            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            use crate::state::{WorldState, Document};
+    #[test]
+    fn test_compute_relative_path_with_workspace_root() {
+        let workspace_root = Url::parse("file:///workspace/").unwrap();
+        let target_uri = Url::parse("file:///workspace/src/main.R").unwrap();
 
-            let library_paths = vec![];
-            let mut state = WorldState::new(library_paths);
-
-            let main_uri = Url::parse("file:///main.R").unwrap();
-            let utils_uri = Url::parse("file:///utils.R").unwrap();
+        let result = compute_relative_path(&target_uri, Some(&workspace_root));
+        assert_eq!(result, "src/main.R");
+    }
 
-            let main_code = format!("source(\"utils.R\")\nresult <- {}(42)", func_name);
-            let utils_code = format!("{} <- function(x) {{ x * 2 }}", func_name);
+    #[test]
+    fn test_compute_relative_path_without_workspace_root() {
+        let target_uri = Url::parse("file:///workspace/src/main.R").unwrap();
 
-            state.documents.insert(main_uri.clone(), Document::new(&main_code, None));
-            state.documents.insert(utils_uri.clone(), Document::new(&utils_code, None));
+        let result = compute_relative_path(&target_uri, None);
+        assert_eq!(result, "main.R");
+    }
 
-            // Update cross-file graph
-            state.cross_file_graph.update_file(&main_uri, &crate::cross_file::extract_metadata(&main_code), None, |_| None);
-            state.cross_file_graph.update_file(&utils_uri, &crate::cross_file::extract_metadata(&utils_code), None, |_| None);
+    #[test]
+    fn test_compute_relative_path_outside_workspace() {
+        let workspace_root = Url::parse("file:///workspace/").unwrap();
+        let target_uri = Url::parse("file:///other/path/script.R").unwrap();
 
-            let position = Position::new(1, 10); // Position after source() call
-            let cross_file_symbols = get_cross_file_symbols(&state, &main_uri, position.line, position.character);
+        let result = compute_relative_path(&target_uri, Some(&workspace_root));
+        assert_eq!(result, "script.R");
+    }
 
-            prop_assert!(cross_file_symbols.contains_key(&func_name), "Should resolve cross-file symbol using dependency graph");
+    #[test]
+    fn test_definition_link_format() {
+        let uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let workspace_root = Url::parse("file:///workspace/").unwrap();
 
-            if let Some(symbol) = cross_file_symbols.get(&func_name) {
-                prop_assert_eq!(&symbol.source_uri, &utils_uri, "Should locate definition in sourced file");
-            }
-        }
+        let link = definition_link(&uri, 2, Some(&workspace_root));
 
-        #[test]
-        // Property 20: Scope-based definition selection
-        fn prop_scope_based_definition_selection(
-            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            use crate::state::{WorldState, Document};
+        assert_eq!(link, "[utils.R:3](file:///workspace/utils.R#L3)");
+    }
 
-            let library_paths = vec![];
-            let mut state = WorldState::new(library_paths);
+    #[test]
+    fn test_collect_roxygen_docs_collects_contiguous_block() {
+        let content = "#' Add two numbers\n#' @param a first number\nadd <- function(a) a";
+        let docs = collect_roxygen_docs(content, 2).unwrap();
+        assert_eq!(docs, "Add two numbers\n@param a first number");
+    }
 
-            let uri = Url::parse("file:///test.R").unwrap();
-            let code = format!(
-                "{} <- function(a) {{ a }}\nsource(\"utils.R\")\n{} <- function(b, c) {{ b + c }}\nresult <- {}(1, 2)",
-                func_name, func_name, func_name
-            );
+    #[test]
+    fn test_collect_roxygen_docs_stops_at_blank_line() {
+        let content = "#' unrelated comment\n\nadd <- function(a) a";
+        let docs = collect_roxygen_docs(content, 2);
+        assert!(docs.is_none());
+    }
 
-            let utils_uri = Url::parse("file:///utils.R").unwrap();
-            let utils_code = format!("{} <- function(x, y, z) {{ x + y + z }}", func_name);
+    #[test]
+    fn test_collect_roxygen_docs_none_when_no_comment_above() {
+        let content = "add <- function(a) a";
+        let docs = collect_roxygen_docs(content, 0);
+        assert!(docs.is_none());
+    }
 
-            state.documents.insert(uri.clone(), Document::new(&code, None));
-            state.documents.insert(utils_uri.clone(), Document::new(&utils_code, None));
+    #[test]
+    fn test_render_roxygen_markdown_full_block() {
+        let docs = "Adds two numbers.\n@param a First value.\n@param b Second value.\n@return The sum.\n@examples\nadd(1, 2)";
+        let uri = Url::parse("file:///test.R").unwrap();
+        let symbols = HashMap::new();
 
-            // Update cross-file graph
-            state.cross_file_graph.update_file(&uri, &crate::cross_file::extract_metadata(&code), None, |_| None);
-            state.cross_file_graph.update_file(&utils_uri, &crate::cross_file::extract_metadata(&utils_code), None, |_| None);
+        let rendered = render_roxygen_markdown(docs, &uri, &symbols, None, HoverConfig::default());
 
-            let position = Position::new(3, 10); // Position of function usage
-            let cross_file_symbols = get_cross_file_symbols(&state, &uri, position.line, position.character);
+        assert!(rendered.starts_with("Adds two numbers."));
+        assert!(rendered.contains("- **a**: First value."));
+        assert!(rendered.contains("- **b**: Second value."));
+        assert!(rendered.contains("Returns: The sum."));
+        assert!(rendered.contains("```r\nadd(1, 2)\n```"));
+    }
 
-            prop_assert!(cross_file_symbols.contains_key(&func_name), "Should find symbol definition");
+    #[test]
+    fn test_render_roxygen_markdown_details_and_seealso() {
+        let docs = "Adds two numbers.\n@details Uses base R `+`.\n@param a First value.\n@return The sum.\n@seealso \\code{subtract}";
+        let uri = Url::parse("file:///test.R").unwrap();
+        let symbols = HashMap::new();
 
-            if let Some(symbol) = cross_file_symbols.get(&func_name) {
-                // Should select the local definition (line 2) that's in scope, not the earlier one or utils.R
-                prop_assert_eq!(&symbol.source_uri, &uri, "Should select definition from same file");
-                prop_assert_eq!(symbol.defined_line, 2, "Should select the definition that's in scope at reference position");
-            }
-        }
+        let rendered = render_roxygen_markdown(docs, &uri, &symbols, None, HoverConfig::default());
 
-        // ========================================================================
-        // Feature: skip-nse-undefined-checks
-        // Property-based tests for NSE context skipping in undefined variable checks
-        // ========================================================================
+        assert!(rendered.contains("Uses base R `+`."));
+        assert!(rendered.contains("See also: `subtract`"));
+    }
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 1: Extract Operator RHS Skipped
-        /// For any R code containing an extract operator ($ or @), the identifier on the
-        /// right-hand side SHALL NOT be collected as a usage.
-        fn prop_skip_nse_extract_operator_rhs_skipped(
-            lhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            rhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            op in prop::sample::select(vec!["$", "@"])
-        ) {
-            let code = format!("{}{}{}", lhs, op, rhs);
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+    #[test]
+    fn test_linkify_function_parameter_defaults_resolves_known_symbol() {
+        let uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let mut symbols = HashMap::new();
+        symbols.insert(
+            "default_config".to_string(),
+            ScopedSymbol {
+                name: "default_config".to_string(),
+                kind: scope::SymbolKind::Variable,
+                source_uri: uri.clone(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            },
+        );
 
-            let rhs_used = used.iter().any(|(name, _)| name == &rhs);
-            prop_assert!(!rhs_used, "RHS '{}' of extract operator should NOT be collected", rhs);
-        }
+        let defaults = linkify_function_parameter_defaults(
+            "f <- function(x = default_config, y = 1) { x }",
+            &symbols,
+            None,
+            HoverConfig::default(),
+        );
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 2: Extract Operator LHS Checked
-        /// For any R code containing an extract operator ($ or @), the identifier on the
-        /// left-hand side SHALL be collected as a usage.
-        fn prop_skip_nse_extract_operator_lhs_checked(
-            lhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            rhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            op in prop::sample::select(vec!["$", "@"])
-        ) {
-            let code = format!("{}{}{}", lhs, op, rhs);
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+        assert_eq!(
+            defaults,
+            vec!["[default_config](file:///workspace/utils.R#L1)"]
+        );
+    }
 
-            let lhs_used = used.iter().any(|(name, _)| name == &lhs);
-            prop_assert!(lhs_used, "LHS '{}' of extract operator should be collected", lhs);
-        }
+    #[test]
+    fn test_linkify_function_parameter_defaults_ignores_unresolved_and_literal_defaults() {
+        let symbols = HashMap::new();
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 3: Call-Like Arguments Skipped
-        /// For any R code containing a call-like node (call, subset, subset2), identifiers
-        /// inside the arguments field SHALL NOT be collected as usages.
-        fn prop_skip_nse_call_like_arguments_skipped(
-            func in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            arg in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            call_type in prop::sample::select(vec!["call", "subset", "subset2"])
-        ) {
-            let code = match call_type {
-                "call" => format!("{}({})", func, arg),
-                "subset" => format!("{}[{}]", func, arg),
-                "subset2" => format!("{}[[{}]]", func, arg),
-                _ => unreachable!(),
-            };
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+        let defaults = linkify_function_parameter_defaults(
+            "f <- function(x = 1, y = unknown_symbol) { x }",
+            &symbols,
+            None,
+            HoverConfig::default(),
+        );
 
-            let arg_used = used.iter().any(|(name, _)| name == &arg);
-            prop_assert!(!arg_used, "Argument '{}' inside {} should NOT be collected", arg, call_type);
-        }
+        assert!(defaults.is_empty());
+    }
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 4: Function Names Checked
-        /// For any R code containing a function call, the function name SHALL be collected
-        /// as a usage.
-        fn prop_skip_nse_function_names_checked(
-            func in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            arg in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            let code = format!("{}({})", func, arg);
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+    #[test]
+    fn test_convert_inline_roxygen_markup_code_and_unresolved_link() {
+        let uri = Url::parse("file:///test.R").unwrap();
+        let symbols = HashMap::new();
 
-            let func_used = used.iter().any(|(name, _)| name == &func);
-            prop_assert!(func_used, "Function name '{}' should be collected", func);
-        }
+        let rendered = convert_inline_roxygen_markup(
+            "See \\code{x} and [helper()].",
+            &uri,
+            &symbols,
+            None,
+            HoverConfig::default(),
+        );
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 5: Formula Expressions Skipped
-        /// For any R code containing a formula expression (unary ~ or binary ~), identifiers
-        /// inside the formula SHALL NOT be collected as usages.
-        fn prop_skip_nse_formula_expressions_skipped(
-            var in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            formula_type in prop::sample::select(vec!["unary", "binary"])
-        ) {
-            let code = match formula_type {
-                "unary" => format!("~ {}", var),
-                "binary" => format!("y ~ {}", var),
-                _ => unreachable!(),
-            };
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+        assert_eq!(rendered, "See `x` and `helper`.");
+    }
 
-            let var_used = used.iter().any(|(name, _)| name == &var);
-            prop_assert!(!var_used, "Variable '{}' inside {} formula should NOT be collected", var, formula_type);
-        }
+    #[test]
+    fn test_convert_inline_roxygen_markup_link_with_package_falls_back_to_help() {
+        let uri = Url::parse("file:///test.R").unwrap();
+        let symbols = HashMap::new();
+        let mut hover_config = HoverConfig::default();
+        hover_config.supports_command_links = true;
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 6: Nested Skip Contexts
-        /// For any R code where a formula appears inside call arguments, identifiers in the
-        /// formula SHALL NOT be collected (both skip contexts apply).
-        fn prop_skip_nse_nested_formula_in_call(
-            func in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            lhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            rhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            let code = format!("{}({} ~ {})", func, lhs, rhs);
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+        let rendered = convert_inline_roxygen_markup(
+            "See \\link[dplyr]{mutate}.",
+            &uri,
+            &symbols,
+            None,
+            hover_config,
+        );
 
-            let lhs_used = used.iter().any(|(name, _)| name == &lhs);
-            let rhs_used = used.iter().any(|(name, _)| name == &rhs);
-            prop_assert!(!lhs_used, "Formula LHS '{}' inside call should NOT be collected", lhs);
-            prop_assert!(!rhs_used, "Formula RHS '{}' inside call should NOT be collected", rhs);
-        }
+        assert!(rendered.contains("command:raven.hoverOpenHelp"));
+    }
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 7: Existing Skip Rules Preserved
-        /// For any R code containing assignments or named arguments, the existing skip rules
-        /// SHALL continue to work (assignment LHS and named argument names are skipped).
-        fn prop_skip_nse_existing_rules_preserved(
-            var in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            op in prop::sample::select(vec!["<-", "=", "<<-"]),
-            arg_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Test assignment LHS
-            let assign_code = format!("{} {} 42", var, op);
-            let tree = parse_r_code(&assign_code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &assign_code, &UsageContext::default(), &mut used);
-            let var_used = used.iter().any(|(name, _)| name == &var);
-            prop_assert!(!var_used, "Assignment LHS '{}' with '{}' should NOT be collected", var, op);
+    #[test]
+    fn test_convert_inline_roxygen_markup_unresolved_builtin_without_command_links_stays_code() {
+        let uri = Url::parse("file:///test.R").unwrap();
+        let symbols = HashMap::new();
 
-            // Test named argument
-            let named_arg_code = format!("func({} = 1)", arg_name);
-            let tree2 = parse_r_code(&named_arg_code);
-            let mut used2 = Vec::new();
-            collect_usages_with_context(tree2.root_node(), &named_arg_code, &UsageContext::default(), &mut used2);
-            let arg_used = used2.iter().any(|(name, _)| name == &arg_name);
-            prop_assert!(!arg_used, "Named argument '{}' should NOT be collected", arg_name);
-        }
+        let rendered = convert_inline_roxygen_markup(
+            "See \\link{sum}.",
+            &uri,
+            &symbols,
+            None,
+            HoverConfig::default(),
+        );
 
-        #[test]
-        /// Feature: skip-nse-undefined-checks, Property 8: Non-Skipped Contexts Checked
-        /// For any R code containing an identifier NOT in a skip context, the identifier
-        /// SHALL be collected as a usage.
-        fn prop_skip_nse_non_skipped_contexts_checked(
-            var in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Standalone identifier (not in any skip context)
-            let code = var.clone();
-            let tree = parse_r_code(&code);
-            let mut used = Vec::new();
-            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+        assert_eq!(rendered, "See `sum`.");
+    }
 
-            let var_used = used.iter().any(|(name, _)| name == &var);
-            prop_assert!(var_used, "Standalone identifier '{}' should be collected", var);
-        }
+    #[test]
+    fn test_convert_inline_roxygen_markup_resolves_link_in_same_file() {
+        let uri = Url::parse("file:///test.R").unwrap();
+        let mut symbols = HashMap::new();
+        symbols.insert(
+            "helper".to_string(),
+            ScopedSymbol {
+                name: "helper".to_string(),
+                kind: scope::SymbolKind::Function,
+                source_uri: uri.clone(),
+                defined_line: 4,
+                defined_column: 0,
+                signature: None,
+            },
+        );
 
-        // ========================================================================
-        // **Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion**
-        // **Validates: Requirements 3.1, 3.2, 3.3**
-        //
-        // For any R code containing a reserved word used as an identifier (in any
-        // syntactic position), the undefined variable checker SHALL NOT emit an
-        // "Undefined variable" diagnostic for that reserved word.
-        // ========================================================================
+        let rendered = convert_inline_roxygen_markup(
+            "See \\link{helper}.",
+            &uri,
+            &symbols,
+            None,
+            HoverConfig::default(),
+        );
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion
-        ///
-        /// For any R code containing a reserved word used as an identifier (in any
-        /// syntactic position), the undefined variable checker SHALL NOT emit an
-        /// "Undefined variable" diagnostic for that reserved word.
-        ///
-        /// **Validates: Requirements 3.1, 3.2, 3.3**
-        fn prop_reserved_words_not_flagged_as_undefined_standalone(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
-        ) {
-            use crate::state::{WorldState, Document};
-            use crate::cross_file::directive::parse_directives;
+        assert_eq!(rendered, "See [helper](file:///test.R#L5).");
+    }
 
-            // Create code with just the reserved word as a standalone identifier
-            let code = reserved_word.to_string();
-            let tree = parse_r_code(&code);
+    #[test]
+    fn test_escape_markdown_all_special_chars() {
+        let input = "*_[]()#`\\";
+        let expected = "\\*\\_\\[\\]\\(\\)\\#\\`\\\\";
 
-            let mut state = WorldState::new(vec![]);
-            state.cross_file_config.undefined_variables_enabled = true;
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+        let result = escape_markdown(input);
+        assert_eq!(result, expected);
+    }
 
-            let directive_meta = parse_directives(&code);
-            let mut diagnostics = Vec::new();
+    #[test]
+    fn test_escape_markdown_no_special_chars() {
+        let input = "hello world 123";
 
-            collect_undefined_variables_position_aware(
-                &state,
-                &uri,
-                tree.root_node(),
-                &code,
-                &[],
-                &[],
-                &state.package_library,
-                &directive_meta,
-                &mut diagnostics,
-            );
+        let result = escape_markdown(input);
+        assert_eq!(result, input);
+    }
 
-            // Filter for "Undefined variable" diagnostics for this reserved word
-            let undefined_diags: Vec<_> = diagnostics
-                .iter()
-                .filter(|d| d.message.contains(&format!("Undefined variable: {}", reserved_word)))
-                .collect();
+    #[test]
+    fn test_escape_markdown_mixed_content() {
+        let input = "function(x) { x * 2 }";
+        let expected = "function\\(x\\) { x \\* 2 }";
 
-            prop_assert!(
-                undefined_diags.is_empty(),
-                "Reserved word '{}' should NOT produce 'Undefined variable' diagnostic, but got: {:?}",
-                reserved_word,
-                undefined_diags
-            );
-        }
+        let result = escape_markdown(input);
+        assert_eq!(result, expected);
+    }
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion
-        ///
-        /// For any R code containing a reserved word used in an expression context,
-        /// the undefined variable checker SHALL NOT emit an "Undefined variable"
-        /// diagnostic for that reserved word.
-        ///
-        /// **Validates: Requirements 3.1, 3.2, 3.3**
-        fn prop_reserved_words_not_flagged_as_undefined_in_expression(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS),
-            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            use crate::state::{WorldState, Document};
-            use crate::cross_file::directive::parse_directives;
+    fn find_function_definition(node: Node) -> Option<Node> {
+        if node.kind() == "function_definition" {
+            return Some(node);
+        }
 
-            // Create code with reserved word used in an expression (e.g., x <- else)
-            // This is syntactically invalid R, but the undefined variable checker
-            // should still not flag the reserved word as undefined
-            let code = format!("{} <- {}", var_name, reserved_word);
-            let tree = parse_r_code(&code);
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if let Some(func) = find_function_definition(child) {
+                return Some(func);
+            }
+        }
+        None
+    }
 
-            let mut state = WorldState::new(vec![]);
-            state.cross_file_config.undefined_variables_enabled = true;
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+    // ========================================================================
+    // Extract Operator Tests (Task 6.1)
+    // Tests for skip-nse-undefined-checks feature
+    // Validates: Requirements 1.1, 1.2, 1.3
+    // ========================================================================
 
-            let directive_meta = parse_directives(&code);
-            let mut diagnostics = Vec::new();
+    /// Test that df$column does not produce a diagnostic for 'column'
+    /// Validates: Requirement 1.1 - RHS of $ operator should be skipped
+    #[test]
+    fn test_extract_operator_dollar_rhs_skipped() {
+        let code = "df$column";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            collect_undefined_variables_position_aware(
-                &state,
-                &uri,
-                tree.root_node(),
-                &code,
-                &[],
-                &[],
-                &state.package_library,
-                &directive_meta,
-                &mut diagnostics,
-            );
+        // 'df' should be collected as a usage (LHS is checked)
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(df_used, "LHS 'df' should be collected as usage");
 
-            // Filter for "Undefined variable" diagnostics for this reserved word
-            let undefined_diags: Vec<_> = diagnostics
-                .iter()
-                .filter(|d| d.message.contains(&format!("Undefined variable: {}", reserved_word)))
-                .collect();
+        // 'column' should NOT be collected as a usage (RHS is skipped)
+        let column_used = used.iter().any(|(name, _)| name == "column");
+        assert!(
+            !column_used,
+            "RHS 'column' should NOT be collected as usage for $ operator"
+        );
+    }
 
-            prop_assert!(
-                undefined_diags.is_empty(),
-                "Reserved word '{}' in expression should NOT produce 'Undefined variable' diagnostic, but got: {:?}",
-                reserved_word,
-                undefined_diags
-            );
-        }
+    /// Test that obj@slot does not produce a diagnostic for 'slot'
+    /// Validates: Requirement 1.2 - RHS of @ operator should be skipped
+    #[test]
+    fn test_extract_operator_at_rhs_skipped() {
+        let code = "obj@slot";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion
-        ///
-        /// For any R code containing a reserved word used in a function call context,
-        /// the undefined variable checker SHALL NOT emit an "Undefined variable"
-        /// diagnostic for that reserved word.
-        ///
-        /// **Validates: Requirements 3.1, 3.2, 3.3**
-        fn prop_reserved_words_not_flagged_as_undefined_in_call(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
-        ) {
-            use crate::state::{WorldState, Document};
-            use crate::cross_file::directive::parse_directives;
+        // 'obj' should be collected as a usage (LHS is checked)
+        let obj_used = used.iter().any(|(name, _)| name == "obj");
+        assert!(obj_used, "LHS 'obj' should be collected as usage");
 
-            // Create code with reserved word used as a function argument
-            // e.g., print(else) - syntactically invalid but tests the checker
-            let code = format!("print({})", reserved_word);
-            let tree = parse_r_code(&code);
+        // 'slot' should NOT be collected as a usage (RHS is skipped)
+        let slot_used = used.iter().any(|(name, _)| name == "slot");
+        assert!(
+            !slot_used,
+            "RHS 'slot' should NOT be collected as usage for @ operator"
+        );
+    }
 
-            let mut state = WorldState::new(vec![]);
-            state.cross_file_config.undefined_variables_enabled = true;
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+    /// Test that undefined$column produces a diagnostic for 'undefined' (LHS is still checked)
+    /// Validates: Requirement 1.3 - LHS of extract operators should still be checked
+    #[test]
+    fn test_extract_operator_lhs_checked() {
+        let code = "undefined$column";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            let directive_meta = parse_directives(&code);
-            let mut diagnostics = Vec::new();
+        // 'undefined' should be collected as a usage (LHS is checked)
+        let undefined_used = used.iter().any(|(name, _)| name == "undefined");
+        assert!(
+            undefined_used,
+            "LHS 'undefined' should be collected as usage"
+        );
 
-            collect_undefined_variables_position_aware(
-                &state,
-                &uri,
-                tree.root_node(),
-                &code,
-                &[],
-                &[],
-                &state.package_library,
-                &directive_meta,
-                &mut diagnostics,
-            );
+        // 'column' should NOT be collected as a usage (RHS is skipped)
+        let column_used = used.iter().any(|(name, _)| name == "column");
+        assert!(
+            !column_used,
+            "RHS 'column' should NOT be collected as usage"
+        );
+    }
 
-            // Filter for "Undefined variable" diagnostics for this reserved word
-            let undefined_diags: Vec<_> = diagnostics
-                .iter()
-                .filter(|d| d.message.contains(&format!("Undefined variable: {}", reserved_word)))
-                .collect();
+    // ==================== Call-Like Argument Tests ====================
+    // These tests verify that identifiers inside call-like arguments are skipped
+    // (Requirements 2.1, 2.2, 2.3, 2.4)
 
-            prop_assert!(
-                undefined_diags.is_empty(),
-                "Reserved word '{}' in function call should NOT produce 'Undefined variable' diagnostic, but got: {:?}",
-                reserved_word,
-                undefined_diags
-            );
-        }
+    /// Test that subset(df, x > 5) does not produce a diagnostic for 'x'
+    /// Validates: Requirement 2.1 - Identifiers inside function call arguments should be skipped
+    #[test]
+    fn test_call_arguments_skipped() {
+        let code = "subset(df, x > 5)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion (Negative Control)
-        ///
-        /// For any R code containing a non-reserved identifier that is not defined,
-        /// the undefined variable checker SHALL emit an "Undefined variable" diagnostic.
-        /// This is a negative control to ensure the checker is working correctly.
-        ///
-        /// **Validates: Requirements 3.1, 3.2, 3.3**
-        fn prop_non_reserved_undefined_vars_are_flagged(
-            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            use crate::state::{WorldState, Document};
-            use crate::cross_file::directive::parse_directives;
+        // 'subset' should be collected as a usage (function name is checked)
+        let subset_used = used.iter().any(|(name, _)| name == "subset");
+        assert!(
+            subset_used,
+            "Function name 'subset' should be collected as usage"
+        );
 
-            // Create code with just the non-reserved identifier (undefined)
-            let code = var_name.clone();
-            let tree = parse_r_code(&code);
+        // 'df' should NOT be collected as a usage (inside call arguments)
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(
+            !df_used,
+            "'df' inside call arguments should NOT be collected as usage"
+        );
 
-            let mut state = WorldState::new(vec![]);
-            state.cross_file_config.undefined_variables_enabled = true;
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+        // 'x' should NOT be collected as a usage (inside call arguments)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside call arguments should NOT be collected as usage"
+        );
+    }
 
-            let directive_meta = parse_directives(&code);
-            let mut diagnostics = Vec::new();
+    /// Test that df[x > 5, ] does not produce a diagnostic for 'x'
+    /// Validates: Requirement 2.2 - Identifiers inside subset ([) arguments should be skipped
+    #[test]
+    fn test_subset_arguments_skipped() {
+        let code = "df[x > 5, ]";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            collect_undefined_variables_position_aware(
-                &state,
-                &uri,
-                tree.root_node(),
-                &code,
-                &[],
-                &[],
-                &state.package_library,
-                &directive_meta,
-                &mut diagnostics,
-            );
+        // 'df' should be collected as a usage (the object being subsetted is checked)
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(
+            df_used,
+            "'df' (object being subsetted) should be collected as usage"
+        );
 
-            // Filter for "Undefined variable" diagnostics for this variable
-            let undefined_diags: Vec<_> = diagnostics
-                .iter()
-                .filter(|d| d.message.contains(&format!("Undefined variable: {}", var_name)))
-                .collect();
+        // 'x' should NOT be collected as a usage (inside subset arguments)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside subset arguments should NOT be collected as usage"
+        );
+    }
 
-            prop_assert!(
-                !undefined_diags.is_empty(),
-                "Non-reserved undefined variable '{}' SHOULD produce 'Undefined variable' diagnostic",
-                var_name
-            );
-        }
+    /// Test that df[[x]] does not produce a diagnostic for 'x'
+    /// Validates: Requirement 2.3 - Identifiers inside subset2 ([[) arguments should be skipped
+    #[test]
+    fn test_subset2_arguments_skipped() {
+        let code = "df[[x]]";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-        // ========================================================================
-        // **Feature: reserved-keyword-handling, Property 4: Completion Exclusion**
-        // **Validates: Requirements 5.1, 5.2, 5.3**
-        //
-        // For any completion request that aggregates identifiers from document, scope,
-        // workspace index, or package sources, the completion provider SHALL NOT include
-        // reserved words in the identifier completion list. Keyword completions (with
-        // CompletionItemKind::KEYWORD) may still include reserved words.
-        // ========================================================================
+        // 'df' should be collected as a usage (the object being subsetted is checked)
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(
+            df_used,
+            "'df' (object being subsetted) should be collected as usage"
+        );
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 4: Completion Exclusion
-        ///
-        /// For any R code containing an assignment to a reserved word, the completion
-        /// provider SHALL NOT include that reserved word as an identifier completion
-        /// (FUNCTION or VARIABLE kind). Reserved words MAY still appear as keyword
-        /// completions (KEYWORD kind).
-        ///
-        /// **Validates: Requirements 5.1, 5.2, 5.3**
-        fn prop_reserved_words_not_in_identifier_completions(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
-        ) {
-            use crate::state::{WorldState, Document};
+        // 'x' should NOT be collected as a usage (inside subset2 arguments)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside subset2 arguments should NOT be collected as usage"
+        );
+    }
 
-            // Create code with assignment to reserved word (e.g., "else <- 1")
-            // This is syntactically invalid R, but tests that even if such code exists,
-            // the completion provider won't suggest the reserved word as an identifier
-            let code = format!("{} <- 1", reserved_word);
+    /// Test that undefined_func(x) produces diagnostics for both 'undefined_func' and 'x':
+    /// 'undefined_func' isn't on the NSE allowlist, so its argument is a checked usage.
+    /// Validates: Requirement 2.4 - Function names should still be checked
+    #[test]
+    fn test_function_name_checked() {
+        let code = "undefined_func(x)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            let mut state = WorldState::new(vec![]);
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+        // 'undefined_func' should be collected as a usage (function name is checked)
+        let func_used = used.iter().any(|(name, _)| name == "undefined_func");
+        assert!(
+            func_used,
+            "Function name 'undefined_func' should be collected as usage"
+        );
 
-            // Request completions at the end of the document
-            let position = Position::new(0, code.len() as u32);
-            let response = completion(&state, &uri, position);
+        // 'x' SHOULD be collected as a usage: 'undefined_func' is an ordinary call,
+        // not on the NSE allowlist, so its arguments are checked like any other.
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            x_used,
+            "'x' inside an ordinary (non-NSE) call's arguments SHOULD be collected as usage"
+        );
+    }
 
-            prop_assert!(response.is_some(), "Completion should return a response");
+    /// Test that blanket_nse_skip restores the old behavior: every call's
+    /// arguments are skipped regardless of the allowlist.
+    #[test]
+    fn test_blanket_nse_skip_mode_skips_ordinary_call_arguments() {
+        let code = "undefined_func(x)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        let context = UsageContext {
+            blanket_nse_skip: true,
+            ..UsageContext::default()
+        };
+        collect_usages_with_context(tree.root_node(), code, &context, &mut used);
 
-            if let Some(CompletionResponse::Array(items)) = response {
-                // Check that reserved word does NOT appear as identifier completion
-                let identifier_completions: Vec<_> = items
-                    .iter()
-                    .filter(|item| {
-                        item.label == reserved_word
-                            && matches!(
-                                item.kind,
-                                Some(CompletionItemKind::FUNCTION) | Some(CompletionItemKind::VARIABLE)
-                            )
-                    })
-                    .collect();
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' should NOT be collected as usage when blanket_nse_skip is enabled"
+        );
+    }
 
-                prop_assert!(
-                    identifier_completions.is_empty(),
-                    "Reserved word '{}' should NOT appear as identifier completion (FUNCTION/VARIABLE), but found: {:?}",
-                    reserved_word,
-                    identifier_completions
-                );
+    // ==================== Formula Tests (Task 6.3) ====================
+    // These tests verify that identifiers inside formula expressions are skipped
+    // (Requirements 3.1, 3.2, 3.4)
 
-                // Verify reserved word DOES appear as keyword completion (positive control)
-                let keyword_completions: Vec<_> = items
-                    .iter()
-                    .filter(|item| {
-                        item.label == reserved_word && item.kind == Some(CompletionItemKind::KEYWORD)
-                    })
-                    .collect();
+    /// Test that ~ x does not produce a diagnostic for 'x'
+    /// Validates: Requirement 3.1 - Identifiers inside unary formula expressions should be skipped
+    #[test]
+    fn test_unary_formula_skipped() {
+        let code = "~ x";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-                prop_assert!(
-                    !keyword_completions.is_empty(),
-                    "Reserved word '{}' SHOULD appear as keyword completion (KEYWORD kind)",
-                    reserved_word
-                );
-            }
-        }
+        // 'x' should NOT be collected as a usage (inside formula)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside unary formula should NOT be collected as usage"
+        );
+    }
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 4: Completion Exclusion
-        ///
-        /// For any R code containing a function definition with a reserved word name,
-        /// the completion provider SHALL NOT include that reserved word as a function
-        /// completion. Reserved words MAY still appear as keyword completions.
-        ///
-        /// **Validates: Requirements 5.1, 5.2, 5.3**
-        fn prop_reserved_words_not_in_function_completions(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
-        ) {
-            use crate::state::{WorldState, Document};
+    /// Test that y ~ x + z does not produce diagnostics for 'y', 'x', 'z'
+    /// Validates: Requirement 3.2 - Identifiers inside binary formula expressions should be skipped
+    #[test]
+    fn test_binary_formula_skipped() {
+        let code = "y ~ x + z";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            // Create code with function definition using reserved word name
-            // (e.g., "if <- function() {}")
-            let code = format!("{} <- function() {{}}", reserved_word);
+        // 'y' should NOT be collected as a usage (LHS of formula)
+        let y_used = used.iter().any(|(name, _)| name == "y");
+        assert!(
+            !y_used,
+            "'y' inside binary formula should NOT be collected as usage"
+        );
 
-            let mut state = WorldState::new(vec![]);
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+        // 'x' should NOT be collected as a usage (RHS of formula)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside binary formula should NOT be collected as usage"
+        );
 
-            // Request completions at the end of the document
-            let position = Position::new(0, code.len() as u32);
-            let response = completion(&state, &uri, position);
+        // 'z' should NOT be collected as a usage (RHS of formula)
+        let z_used = used.iter().any(|(name, _)| name == "z");
+        assert!(
+            !z_used,
+            "'z' inside binary formula should NOT be collected as usage"
+        );
+    }
 
-            prop_assert!(response.is_some(), "Completion should return a response");
+    /// Test that lm(y ~ x, data = df) does not produce diagnostics for 'y', 'x' (formula
+    /// terms are always skipped), but 'df' IS checked since 'lm' isn't on the NSE allowlist.
+    /// Validates: Requirement 3.4 - Formulas nested inside call arguments should have both contexts apply
+    #[test]
+    fn test_formula_inside_call_arguments_skipped() {
+        let code = "lm(y ~ x, data = df)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            if let Some(CompletionResponse::Array(items)) = response {
-                // Check that reserved word does NOT appear as function completion
-                let function_completions: Vec<_> = items
-                    .iter()
-                    .filter(|item| {
-                        item.label == reserved_word && item.kind == Some(CompletionItemKind::FUNCTION)
-                    })
-                    .collect();
+        // 'lm' should be collected as a usage (function name is checked)
+        let lm_used = used.iter().any(|(name, _)| name == "lm");
+        assert!(lm_used, "Function name 'lm' should be collected as usage");
 
-                prop_assert!(
-                    function_completions.is_empty(),
-                    "Reserved word '{}' should NOT appear as function completion, but found: {:?}",
-                    reserved_word,
-                    function_completions
-                );
-            }
-        }
+        // 'y' should NOT be collected as a usage (inside formula, regardless of call)
+        let y_used = used.iter().any(|(name, _)| name == "y");
+        assert!(
+            !y_used,
+            "'y' inside formula in call arguments should NOT be collected as usage"
+        );
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 4: Completion Exclusion (Negative Control)
-        ///
-        /// For any R code containing an assignment to a non-reserved identifier,
-        /// the completion provider SHALL include that identifier as a completion.
-        /// This is a negative control to ensure the completion provider is working correctly.
-        ///
-        /// **Validates: Requirements 5.1, 5.2, 5.3**
-        fn prop_non_reserved_identifiers_in_completions(
-            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            use crate::state::{WorldState, Document};
+        // 'x' should NOT be collected as a usage (inside formula, regardless of call)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside formula in call arguments should NOT be collected as usage"
+        );
 
-            // Create code with assignment to non-reserved identifier
-            let code = format!("{} <- 1", var_name);
+        // 'df' SHOULD be collected as a usage: 'lm' is an ordinary call, not on the
+        // NSE allowlist, so its (non-formula) arguments are checked.
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(
+            df_used,
+            "'df' inside an ordinary (non-NSE) call's arguments SHOULD be collected as usage"
+        );
+    }
 
-            let mut state = WorldState::new(vec![]);
-            let uri = Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), Document::new(&code, None));
+    // ==================== Edge Case Tests (Task 6.4) ====================
+    // These tests verify edge cases for the NSE skip logic
+    // (Requirements 1.1, 1.2, 2.1, 3.1)
 
-            // Request completions at the end of the document
-            let position = Position::new(0, code.len() as u32);
-            let response = completion(&state, &uri, position);
+    /// Test deeply nested formulas: ~ (~ (~ x)) - all identifiers should be skipped
+    /// Validates: Requirement 3.1 - Identifiers inside formula expressions should be skipped
+    #[test]
+    fn test_deeply_nested_formulas() {
+        let code = "~ (~ (~ x))";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            prop_assert!(response.is_some(), "Completion should return a response");
+        // 'x' should NOT be collected as a usage (inside deeply nested formula)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside deeply nested formula should NOT be collected as usage"
+        );
 
-            if let Some(CompletionResponse::Array(items)) = response {
-                // Check that non-reserved identifier DOES appear as completion
-                let var_completions: Vec<_> = items
-                    .iter()
-                    .filter(|item| item.label == var_name)
-                    .collect();
+        // No identifiers should be collected at all
+        assert!(
+            used.is_empty(),
+            "No identifiers should be collected from deeply nested formula"
+        );
+    }
 
-                prop_assert!(
-                    !var_completions.is_empty(),
-                    "Non-reserved identifier '{}' SHOULD appear in completions",
-                    var_name
-                );
-            }
-        }
+    /// Test nested call arguments: f(g(h(x))) - none of f/g/h are on the NSE allowlist,
+    /// so every identifier at every level is a checked usage.
+    /// Validates: Requirement 2.1 (allowlist-gated) - only known NSE functions skip arguments
+    #[test]
+    fn test_nested_call_arguments() {
+        let code = "f(g(h(x)))";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-        // ========================================================================
-        // **Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion**
-        // **Validates: Requirements 6.1, 6.2**
-        //
-        // For any document symbol collection where a candidate symbol name is a
-        // reserved word, the provider SHALL NOT include it in the emitted symbol list.
-        // ========================================================================
+        for name in ["f", "g", "h", "x"] {
+            assert!(
+                used.iter().any(|(n, _)| n == name),
+                "'{name}' should be collected as usage: none of f/g/h are NSE functions"
+            );
+        }
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion
-        ///
-        /// For any R code containing an assignment to a reserved word (e.g., `else <- 1`),
-        /// the document symbol provider SHALL NOT include that reserved word in the
-        /// emitted symbol list.
-        ///
-        /// **Validates: Requirements 6.1, 6.2**
-        fn prop_reserved_words_not_in_document_symbols(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
-        ) {
-            // Create code with assignment to reserved word (e.g., "else <- 1")
-            // This is syntactically invalid R, but tests that even if such code exists,
-            // the document symbol provider won't include the reserved word as a symbol
-            let code = format!("{} <- 1", reserved_word);
-            let tree = parse_r_code(&code);
+        assert_eq!(used.len(), 4, "All four identifiers should be collected");
+    }
 
-            let mut symbols = Vec::new();
-            collect_symbols(tree.root_node(), &code, &mut symbols);
+    /// Test that an NSE call nested inside another NSE call's arguments still has its
+    /// own arguments skipped: with(df, subset(inner, y))'s inner identifiers are all NSE-skipped.
+    #[test]
+    fn test_nested_nse_calls_skip_all_levels() {
+        let code = "with(df, subset(inner, y))";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            // Check that reserved word does NOT appear in document symbols
-            let reserved_symbols: Vec<_> = symbols
-                .iter()
-                .filter(|sym| sym.name == reserved_word)
-                .collect();
+        // 'with' is the outermost function name and is checked
+        assert!(used.iter().any(|(name, _)| name == "with"));
 
-            prop_assert!(
-                reserved_symbols.is_empty(),
-                "Reserved word '{}' should NOT appear in document symbols, but found: {:?}",
-                reserved_word,
-                reserved_symbols.iter().map(|s| &s.name).collect::<Vec<_>>()
+        // Everything inside 'with's arguments - including the nested 'subset' call and
+        // its own arguments - stays skipped, since we're already inside an NSE-skip zone.
+        for name in ["df", "subset", "inner", "y"] {
+            assert!(
+                !used.iter().any(|(n, _)| n == name),
+                "'{name}' inside nested NSE call arguments should NOT be collected as usage"
             );
         }
+    }
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion
-        ///
-        /// For any R code containing a function definition with a reserved word name
-        /// (e.g., `if <- function() {}`), the document symbol provider SHALL NOT
-        /// include that reserved word in the emitted symbol list.
-        ///
-        /// **Validates: Requirements 6.1, 6.2**
-        fn prop_reserved_words_not_in_document_symbols_function(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
-        ) {
-            // Create code with function definition using reserved word name
-            // (e.g., "if <- function() {}")
-            let code = format!("{} <- function() {{}}", reserved_word);
-            let tree = parse_r_code(&code);
-
-            let mut symbols = Vec::new();
-            collect_symbols(tree.root_node(), &code, &mut symbols);
-
-            // Check that reserved word does NOT appear in document symbols
-            let reserved_symbols: Vec<_> = symbols
-                .iter()
-                .filter(|sym| sym.name == reserved_word)
-                .collect();
+    /// Test that an ordinary (non-NSE) call nested inside an NSE call's arguments is
+    /// still skipped, since the enclosing NSE context applies to everything beneath it.
+    #[test]
+    fn test_ordinary_call_nested_inside_nse_call_stays_skipped() {
+        let code = "with(df, mean(typo_var))";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            prop_assert!(
-                reserved_symbols.is_empty(),
-                "Reserved word '{}' should NOT appear in document symbols (function), but found: {:?}",
-                reserved_word,
-                reserved_symbols.iter().map(|s| &s.name).collect::<Vec<_>>()
+        assert!(used.iter().any(|(name, _)| name == "with"));
+        for name in ["df", "mean", "typo_var"] {
+            assert!(
+                !used.iter().any(|(n, _)| n == name),
+                "'{name}' inside with(...)'s arguments should NOT be collected as usage"
             );
         }
+    }
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion (Negative Control)
-        ///
-        /// For any R code containing an assignment to a non-reserved identifier,
-        /// the document symbol provider SHALL include that identifier in the symbol list.
-        /// This is a negative control to ensure the document symbol provider is working correctly.
-        ///
-        /// **Validates: Requirements 6.1, 6.2**
-        fn prop_non_reserved_identifiers_in_document_symbols(
-            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Create code with assignment to non-reserved identifier
-            let code = format!("{} <- 1", var_name);
-            let tree = parse_r_code(&code);
+    /// Test the motivating example from the allowlist feature: mean(df$col) checks 'df'
+    /// (and would check 'col' too if it weren't the RHS of $) since 'mean' isn't NSE.
+    #[test]
+    fn test_ordinary_call_with_extract_operator_argument() {
+        let code = "mean(df$col)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            let mut symbols = Vec::new();
-            collect_symbols(tree.root_node(), &code, &mut symbols);
+        assert!(used.iter().any(|(name, _)| name == "mean"));
+        assert!(
+            used.iter().any(|(name, _)| name == "df"),
+            "'df' (LHS of $) should be collected as usage inside a non-NSE call's arguments"
+        );
+        assert!(
+            !used.iter().any(|(name, _)| name == "col"),
+            "'col' (RHS of $) should NOT be collected as usage, regardless of call context"
+        );
+    }
 
-            // Check that non-reserved identifier DOES appear in document symbols
-            let var_symbols: Vec<_> = symbols
-                .iter()
-                .filter(|sym| sym.name == var_name)
-                .collect();
+    /// Test that a user-configured allowlist addition (e.g. a custom NSE wrapper or a
+    /// namespaced `pkg::fn` call) skips its arguments just like a built-in entry.
+    #[test]
+    fn test_custom_allowlist_entry_skips_arguments() {
+        let code = "my_nse_fn(x)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        let mut allowlist = default_nse_allowlist();
+        allowlist.insert("my_nse_fn".to_string());
+        let context = UsageContext {
+            nse_allowlist: std::sync::Arc::new(allowlist),
+            ..UsageContext::default()
+        };
+        collect_usages_with_context(tree.root_node(), code, &context, &mut used);
 
-            prop_assert!(
-                !var_symbols.is_empty(),
-                "Non-reserved identifier '{}' SHOULD appear in document symbols",
-                var_name
+        assert!(used.iter().any(|(name, _)| name == "my_nse_fn"));
+        assert!(
+            !used.iter().any(|(name, _)| name == "x"),
+            "'x' should NOT be collected as usage once 'my_nse_fn' is added to the allowlist"
+        );
+    }
+
+    /// Test that a namespaced call (`dplyr::mutate`) is recognized via its built-in
+    /// allowlist entry.
+    #[test]
+    fn test_namespaced_nse_call_recognized() {
+        let code = "dplyr::mutate(df, y = x * 2)";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
+
+        assert!(used.iter().any(|(name, _)| name == "dplyr"));
+        assert!(used.iter().any(|(name, _)| name == "mutate"));
+        for name in ["df", "y", "x"] {
+            assert!(
+                !used.iter().any(|(n, _)| n == name),
+                "'{name}' inside dplyr::mutate's arguments should NOT be collected as usage"
             );
         }
+    }
 
-        #[test]
-        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion
-        ///
-        /// For any R code containing multiple assignments where some are to reserved words
-        /// and some are to non-reserved identifiers, the document symbol provider SHALL
-        /// include only the non-reserved identifiers in the symbol list.
-        ///
-        /// **Validates: Requirements 6.1, 6.2**
-        fn prop_mixed_reserved_and_non_reserved_document_symbols(
-            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS),
-            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Create code with both reserved and non-reserved assignments
-            let code = format!("{} <- 1\n{} <- 2", reserved_word, var_name);
-            let tree = parse_r_code(&code);
+    /// Test mixed contexts: df$col[x > 5] - 'col' skipped (extract RHS), 'x' skipped (subset arguments), 'df' checked
+    /// Validates: Requirements 1.1, 1.2, 2.1 - Extract RHS and subset arguments should be skipped
+    #[test]
+    fn test_mixed_contexts() {
+        let code = "df$col[x > 5]";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-            let mut symbols = Vec::new();
-            collect_symbols(tree.root_node(), &code, &mut symbols);
+        // 'df' should be collected as a usage (LHS of extract operator is checked)
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(
+            df_used,
+            "'df' (LHS of extract operator) should be collected as usage"
+        );
 
-            // Check that reserved word does NOT appear in document symbols
-            let reserved_symbols: Vec<_> = symbols
-                .iter()
-                .filter(|sym| sym.name == reserved_word)
-                .collect();
+        // 'col' should NOT be collected as a usage (RHS of extract operator)
+        let col_used = used.iter().any(|(name, _)| name == "col");
+        assert!(
+            !col_used,
+            "'col' (RHS of extract operator) should NOT be collected as usage"
+        );
 
-            prop_assert!(
-                reserved_symbols.is_empty(),
-                "Reserved word '{}' should NOT appear in document symbols",
-                reserved_word
-            );
+        // 'x' should NOT be collected as a usage (inside subset arguments)
+        let x_used = used.iter().any(|(name, _)| name == "x");
+        assert!(
+            !x_used,
+            "'x' inside subset arguments should NOT be collected as usage"
+        );
 
-            // Check that non-reserved identifier DOES appear in document symbols
-            let var_symbols: Vec<_> = symbols
-                .iter()
-                .filter(|sym| sym.name == var_name)
-                .collect();
+        // Only 'df' should be collected
+        assert_eq!(
+            used.len(),
+            1,
+            "Only 'df' should be collected in mixed context"
+        );
+    }
 
-            prop_assert!(
-                !var_symbols.is_empty(),
-                "Non-reserved identifier '{}' SHOULD appear in document symbols",
-                var_name
-            );
-        }
+    /// Test chained extracts: df$a$b$c - only 'df' should be checked, all others are RHS of extract operators
+    /// Validates: Requirements 1.1, 1.2 - RHS of extract operators should be skipped
+    #[test]
+    fn test_chained_extracts() {
+        let code = "df$a$b$c";
+        let tree = parse_r_code(code);
+        let mut used = Vec::new();
+        collect_usages_with_context(tree.root_node(), code, &UsageContext::default(), &mut used);
 
-        // ========================================================================
-        // **Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection**
-        // **Validates: Requirements 1.1, 2.1, 2.2**
-        //
-        // For any R code where an `else` keyword starts on a different line than
-        // the closing `}` of the preceding `if` block, the detector SHALL emit
-        // exactly one diagnostic for that `else`.
-        // ========================================================================
+        // 'df' should be collected as a usage (leftmost identifier is checked)
+        let df_used = used.iter().any(|(name, _)| name == "df");
+        assert!(
+            df_used,
+            "'df' (leftmost identifier) should be collected as usage"
+        );
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection
-        ///
-        /// For any R code where an `else` keyword starts on a different line than
-        /// the closing `}` of the preceding `if` block, the detector SHALL emit
-        /// exactly one diagnostic for that `else`.
-        ///
-        /// **Validates: Requirements 1.1, 2.1, 2.2**
-        fn prop_orphaned_else_detection(
-            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            blank_lines in 0usize..3
-        ) {
-            // Generate code with else on a new line after closing brace
-            // Pattern: if (condition) {body1}\n[blank_lines]\nelse {body2}
-            let newlines = "\n".repeat(blank_lines + 1);
-            let code = format!("if ({}) {{{}}}{newlines}else {{{}}}", condition, body1, body2);
+        // 'a' should NOT be collected as a usage (RHS of first extract operator)
+        let a_used = used.iter().any(|(name, _)| name == "a");
+        assert!(
+            !a_used,
+            "'a' (RHS of extract operator) should NOT be collected as usage"
+        );
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+        // 'b' should NOT be collected as a usage (RHS of second extract operator)
+        let b_used = used.iter().any(|(name, _)| name == "b");
+        assert!(
+            !b_used,
+            "'b' (RHS of extract operator) should NOT be collected as usage"
+        );
 
-            // Should emit exactly one diagnostic for the orphaned else
-            prop_assert_eq!(
-                diagnostics.len(),
-                1,
-                "Should emit exactly one diagnostic for orphaned else on new line. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
+        // 'c' should NOT be collected as a usage (RHS of third extract operator)
+        let c_used = used.iter().any(|(name, _)| name == "c");
+        assert!(
+            !c_used,
+            "'c' (RHS of extract operator) should NOT be collected as usage"
+        );
 
-            // Verify diagnostic severity is ERROR
-            prop_assert_eq!(
-                diagnostics[0].severity,
-                Some(DiagnosticSeverity::ERROR),
-                "Diagnostic severity should be ERROR"
-            );
+        // Only 'df' should be collected
+        assert_eq!(
+            used.len(),
+            1,
+            "Only 'df' should be collected in chained extracts"
+        );
+    }
 
-            // Verify diagnostic message mentions 'else' and 'same line'
-            prop_assert!(
-                diagnostics[0].message.contains("else"),
-                "Diagnostic message should mention 'else'"
-            );
-            prop_assert!(
-                diagnostics[0].message.contains("same line"),
-                "Diagnostic message should mention 'same line'"
-            );
-        }
+    // ========================================================================
+    // Completion Precedence Tests (Task 11.2)
+    // Tests for completion precedence: local > package exports > cross-file
+    // Validates: Requirements 9.4, 9.5
+    // ========================================================================
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection (Multi-line if block)
-        ///
-        /// For any R code with a multi-line if block where `else` appears on a new line
-        /// after the closing `}`, the detector SHALL emit exactly one diagnostic.
-        ///
-        /// **Validates: Requirements 1.1, 2.1, 2.2**
-        fn prop_orphaned_else_detection_multiline_if(
-            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body_lines in 1usize..4,
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate multi-line if block with else on new line
-            // Pattern: if (condition) {\n  body_line1\n  body_line2\n}\nelse {body2}
-            let body_content: String = (0..body_lines)
-                .map(|i| format!("  line{}", i))
-                .collect::<Vec<_>>()
-                .join("\n");
+    /// Test that local definitions take precedence over package exports in completions.
+    /// Validates: Requirement 9.4 - Local definitions > package exports
+    #[test]
+    fn test_completion_local_over_package_exports() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
 
-            let code = format!(
-                "if ({}) {{\n{}\n}}\nelse {{{}}}",
-                condition, body_content, body2
-            );
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // Create a WorldState with a package that exports "mutate"
+            let mut state = WorldState::new(vec![]);
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+            // Add a package with "mutate" export
+            let mut exports = std::collections::HashSet::new();
+            exports.insert("mutate".to_string());
+            exports.insert("filter".to_string());
+            let pkg_info = PackageInfo::new("dplyr".to_string(), exports);
+            state.package_library.insert_package(pkg_info).await;
 
-            // Should emit exactly one diagnostic for the orphaned else
-            prop_assert_eq!(
-                diagnostics.len(),
-                1,
-                "Should emit exactly one diagnostic for orphaned else after multi-line if block. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
+            // Create a document that defines "mutate" locally and loads dplyr
+            let code = r#"library(dplyr)
+mutate <- function(x) { x * 2 }
+result <- "#;
+            let uri = Url::parse("file:///test.R").unwrap();
+            let doc = Document::new(code, None);
+            state.documents.insert(uri.clone(), doc);
 
-            // Verify diagnostic severity is ERROR
-            prop_assert_eq!(
-                diagnostics[0].severity,
-                Some(DiagnosticSeverity::ERROR),
-                "Diagnostic severity should be ERROR"
-            );
-        }
+            // Get completions at the end of the file (after "result <- ")
+            let position = Position::new(2, 10);
+            let completions = super::completion(&state, &uri, position);
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection (else if pattern)
-        ///
-        /// For any R code where `else if` appears on a new line after the closing `}`,
-        /// the detector SHALL emit exactly one diagnostic for the orphaned `else`.
-        ///
-        /// **Validates: Requirements 1.1, 2.1, 2.2**
-        fn prop_orphaned_else_if_detection(
-            cond1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            cond2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate code with else if on a new line
-            // Pattern: if (cond1) {body1}\nelse if (cond2) {body2}
-            let code = format!(
-                "if ({}) {{{}}}\nelse if ({}) {{{}}}",
-                cond1, body1, cond2, body2
-            );
+            assert!(completions.is_some(), "Should return completions");
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+            if let Some(CompletionResponse::Array(items)) = completions {
+                // Find the "mutate" completion item
+                let mutate_items: Vec<_> = items.iter()
+                    .filter(|item| item.label == "mutate")
+                    .collect();
 
-            // Should emit exactly one diagnostic for the orphaned else
-            prop_assert_eq!(
-                diagnostics.len(),
-                1,
-                "Should emit exactly one diagnostic for orphaned 'else if' on new line. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
+                // There should be exactly one "mutate" item (the local definition)
+                assert_eq!(
+                    mutate_items.len(),
+                    1,
+                    "Should have exactly one 'mutate' completion (local definition takes precedence)"
+                );
 
-            // Verify diagnostic severity is ERROR
-            prop_assert_eq!(
-                diagnostics[0].severity,
-                Some(DiagnosticSeverity::ERROR),
-                "Diagnostic severity should be ERROR"
-            );
-        }
+                // The local definition should NOT have package attribution
+                let mutate_item = mutate_items[0];
+                assert!(
+                    mutate_item.detail.is_none() || !mutate_item.detail.as_ref().unwrap().contains("{dplyr}"),
+                    "Local 'mutate' should not have package attribution"
+                );
+            } else {
+                panic!("Expected CompletionResponse::Array");
+            }
+        });
+    }
 
-        // ========================================================================
-        // **Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic**
-        // **Validates: Requirements 1.2, 1.3, 2.3, 2.4**
-        //
-        // For any R code where an `else` keyword appears on the same line as the
-        // closing `}` of the preceding `if` block, the detector SHALL NOT emit
-        // a diagnostic for that `else`.
-        // ========================================================================
+    /// Test that package exports take precedence over cross-file symbols in completions.
+    /// Validates: Requirement 9.5 - Package exports > cross-file symbols
+    #[test]
+    fn test_completion_package_over_cross_file() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (Single line)
-        ///
-        /// For any R code where `else` appears on the same line as the closing `}`
-        /// of the preceding `if` block (single line format), the detector SHALL NOT
-        /// emit a diagnostic.
-        ///
-        /// **Validates: Requirements 1.2, 1.3, 2.3**
-        fn prop_valid_else_no_diagnostic_single_line(
-            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate valid single-line if-else code
-            // Pattern: if (condition) {body1} else {body2}
-            let code = format!("if ({}) {{{}}} else {{{}}}", condition, body1, body2);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // Create a WorldState with a package that exports "helper_func"
+            let mut state = WorldState::new(vec![]);
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+            // Add a package with "helper_func" export
+            let mut exports = std::collections::HashSet::new();
+            exports.insert("helper_func".to_string());
+            let pkg_info = PackageInfo::new("testpkg".to_string(), exports);
+            state.package_library.insert_package(pkg_info).await;
 
-            // Should NOT emit any diagnostic for valid else on same line
-            prop_assert_eq!(
-                diagnostics.len(),
-                0,
-                "Should NOT emit diagnostic for valid else on same line. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
-        }
+            // Create main file that loads testpkg
+            let main_code = r#"library(testpkg)
+result <- "#;
+            let main_uri = Url::parse("file:///main.R").unwrap();
+            let main_doc = Document::new(main_code, None);
+            state.documents.insert(main_uri.clone(), main_doc);
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (Multi-line with else on same line as brace)
-        ///
-        /// For any R code with a multi-line if block where `else` appears on the same
-        /// line as the closing `}`, the detector SHALL NOT emit a diagnostic.
-        ///
-        /// **Validates: Requirements 1.2, 1.3, 2.4**
-        fn prop_valid_else_no_diagnostic_multiline(
-            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body_lines in 1usize..4,
-            body2_lines in 1usize..4
-        ) {
-            // Generate multi-line if block with else on same line as closing brace
-            // Pattern: if (condition) {\n  body_line1\n  body_line2\n} else {\n  body2_line1\n}
-            let body1_content: String = (0..body_lines)
-                .map(|i| format!("  line{}", i))
-                .collect::<Vec<_>>()
-                .join("\n");
+            // Create a helper file that defines "helper_func"
+            let helper_code = r#"helper_func <- function(x) { x + 1 }"#;
+            let helper_uri = Url::parse("file:///helper.R").unwrap();
+            let helper_doc = Document::new(helper_code, None);
+            state.documents.insert(helper_uri.clone(), helper_doc);
 
-            let body2_content: String = (0..body2_lines)
-                .map(|i| format!("  else_line{}", i))
-                .collect::<Vec<_>>()
-                .join("\n");
+            // Note: In a real scenario, the cross-file symbol would come from scope resolution
+            // through source() calls. For this test, we verify that package exports are added
+            // before cross-file symbols in the completion list.
 
-            let code = format!(
-                "if ({}) {{\n{}\n}} else {{\n{}\n}}",
-                condition, body1_content, body2_content
-            );
+            // Get completions at the end of main file
+            let position = Position::new(1, 10);
+            let completions = super::completion(&state, &main_uri, position);
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+            assert!(completions.is_some(), "Should return completions");
 
-            // Should NOT emit any diagnostic for valid else on same line as closing brace
-            prop_assert_eq!(
-                diagnostics.len(),
-                0,
-                "Should NOT emit diagnostic for valid multi-line if-else. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
-        }
+            if let Some(CompletionResponse::Array(items)) = completions {
+                // Find the "helper_func" completion item
+                let helper_items: Vec<_> = items
+                    .iter()
+                    .filter(|item| item.label == "helper_func")
+                    .collect();
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (else if on same line)
-        ///
-        /// For any R code where `else if` appears on the same line as the closing `}`
-        /// of the preceding `if` block, the detector SHALL NOT emit a diagnostic.
-        ///
-        /// **Validates: Requirements 1.2, 1.3, 2.3, 2.4**
-        fn prop_valid_else_if_no_diagnostic(
-            cond1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            cond2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate valid if-else if code with else if on same line as closing brace
-            // Pattern: if (cond1) {body1} else if (cond2) {body2}
-            let code = format!(
-                "if ({}) {{{}}} else if ({}) {{{}}}",
-                cond1, body1, cond2, body2
-            );
+                // There should be at least one "helper_func" item (from package)
+                assert!(
+                    !helper_items.is_empty(),
+                    "Should have 'helper_func' completion from package"
+                );
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+                // The first (and only) helper_func should be from the package
+                let helper_item = helper_items[0];
+                assert!(
+                    helper_item
+                        .detail
+                        .as_ref()
+                        .map_or(false, |d| d.contains("{testpkg}")),
+                    "helper_func should have package attribution {{testpkg}}"
+                );
+            } else {
+                panic!("Expected CompletionResponse::Array");
+            }
+        });
+    }
 
-            // Should NOT emit any diagnostic for valid else if on same line
-            prop_assert_eq!(
-                diagnostics.len(),
-                0,
-                "Should NOT emit diagnostic for valid 'else if' on same line. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
-        }
+    /// Test that keywords take precedence over all other completions.
+    /// Validates: Implicit requirement - keywords should always be available
+    #[test]
+    fn test_completion_keywords_always_present() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionItemKind, CompletionResponse, Position};
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (Nested valid if-else)
-        ///
-        /// For any nested if-else structure where all `else` keywords appear on the same
-        /// line as their preceding closing `}`, the detector SHALL NOT emit any diagnostic.
-        ///
-        /// **Validates: Requirements 1.2, 1.3, 2.3, 2.4**
-        fn prop_valid_nested_else_no_diagnostic(
-            outer_cond in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            inner_cond in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body3 in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate valid nested if-else code
-            // Pattern: if (outer_cond) { if (inner_cond) {body1} else {body2} } else {body3}
-            let code = format!(
-                "if ({}) {{ if ({}) {{{}}} else {{{}}} }} else {{{}}}",
-                outer_cond, inner_cond, body1, body2, body3
-            );
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // Create a WorldState with a package that exports "if" (hypothetically)
+            let mut state = WorldState::new(vec![]);
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+            // Add a package with "if" export (edge case - shouldn't override keyword)
+            let mut exports = std::collections::HashSet::new();
+            exports.insert("if".to_string());
+            let pkg_info = PackageInfo::new("badpkg".to_string(), exports);
+            state.package_library.insert_package(pkg_info).await;
 
-            // Should NOT emit any diagnostic for valid nested if-else
-            prop_assert_eq!(
-                diagnostics.len(),
-                0,
-                "Should NOT emit diagnostic for valid nested if-else. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
-        }
+            // Create a document that loads the package
+            let code = r#"library(badpkg)
+x <- "#;
+            let uri = Url::parse("file:///test.R").unwrap();
+            let doc = Document::new(code, None);
+            state.documents.insert(uri.clone(), doc);
 
-        // ========================================================================
-        // **Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy**
-        // **Validates: Requirements 3.2**
-        //
-        // For any detected orphaned `else`, the diagnostic range SHALL start at the
-        // beginning of the `else` keyword and end at the end of the `else` keyword.
-        // ========================================================================
+            // Get completions
+            let position = Position::new(1, 5);
+            let completions = super::completion(&state, &uri, position);
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy
-        ///
-        /// For any detected orphaned `else`, the diagnostic range SHALL start at the
-        /// beginning of the `else` keyword and end at the end of the `else` keyword.
-        ///
-        /// **Validates: Requirements 3.2**
-        fn prop_diagnostic_range_accuracy(
-            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            blank_lines in 0usize..3
-        ) {
-            // Generate code with else on a new line after closing brace
-            // Pattern: if (condition) {body1}\n[blank_lines]\nelse {body2}
-            let newlines = "\n".repeat(blank_lines + 1);
-            let code = format!("if ({}) {{{}}}{newlines}else {{{}}}", condition, body1, body2);
+            assert!(completions.is_some(), "Should return completions");
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+            if let Some(CompletionResponse::Array(items)) = completions {
+                // Find the "if" completion item
+                let if_items: Vec<_> = items.iter().filter(|item| item.label == "if").collect();
 
-            // Should emit exactly one diagnostic
-            prop_assert_eq!(
-                diagnostics.len(),
-                1,
-                "Should emit exactly one diagnostic. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
+                // There should be exactly one "if" item (the keyword)
+                assert_eq!(
+                    if_items.len(),
+                    1,
+                    "Should have exactly one 'if' completion (keyword takes precedence)"
+                );
 
-            let diagnostic = &diagnostics[0];
+                // The "if" should be a keyword, not a function from package
+                let if_item = if_items[0];
+                assert_eq!(
+                    if_item.kind,
+                    Some(CompletionItemKind::KEYWORD),
+                    "'if' should be a KEYWORD, not a function from package"
+                );
+            } else {
+                panic!("Expected CompletionResponse::Array");
+            }
+        });
+    }
 
-            // Calculate expected position of "else" in the generated code
-            // The "else" keyword starts after: "if (condition) {body1}" + newlines
-            let prefix = format!("if ({}) {{{}}}{newlines}", condition, body1);
-            let else_line = prefix.matches('\n').count() as u32;
-            let else_column = 0u32; // "else" starts at column 0 on its line
+    /// `else` is only offered on a line following a complete `if` block
+    /// without its own `else` clause, not at an arbitrary statement position.
+    #[test]
+    fn test_completion_else_keyword_context_sensitive() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
 
-            // Verify diagnostic range starts at the beginning of "else"
-            prop_assert_eq!(
-                diagnostic.range.start.line,
-                else_line,
-                "Diagnostic start line should match else position. Code: '{}', Expected line: {}, Got: {}",
-                code,
-                else_line,
-                diagnostic.range.start.line
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+
+        let code_no_if = "x <- 1\n";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code_no_if, None));
+        let completions = super::completion(&state, &uri, Position::new(1, 0));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(
+                items.iter().all(|item| item.label != "else"),
+                "'else' shouldn't be offered with no preceding if block"
             );
-            prop_assert_eq!(
-                diagnostic.range.start.character,
-                else_column,
-                "Diagnostic start column should match else position. Code: '{}', Expected column: {}, Got: {}",
-                code,
-                else_column,
-                diagnostic.range.start.character
+        }
+
+        let code_with_if = "if (TRUE) {\n  1\n}\n";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code_with_if, None));
+        let completions = super::completion(&state, &uri, Position::new(3, 0));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(
+                items.iter().any(|item| item.label == "else"),
+                "'else' should be offered right after a complete if block"
             );
+        }
+    }
 
-            // Verify diagnostic range ends at the end of "else" (4 characters)
-            // The "else" keyword is 4 characters long
-            prop_assert_eq!(
-                diagnostic.range.end.line,
-                else_line,
-                "Diagnostic end line should be same as start line. Code: '{}', Expected: {}, Got: {}",
-                code,
-                else_line,
-                diagnostic.range.end.line
+    /// `in` is only offered while completing an unfinished `for (var ` header.
+    #[test]
+    fn test_completion_in_keyword_context_sensitive() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
+
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+
+        let code = "for (i ";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        let completions = super::completion(&state, &uri, Position::new(0, code.len() as u32));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(
+                items.iter().any(|item| item.label == "in"),
+                "'in' should be offered inside an unfinished for-header"
             );
-            prop_assert_eq!(
-                diagnostic.range.end.character,
-                else_column + 4,
-                "Diagnostic end column should be start + 4 (length of 'else'). Code: '{}', Expected: {}, Got: {}",
-                code,
-                else_column + 4,
-                diagnostic.range.end.character
+        }
+
+        let code_elsewhere = "x <- 1\n";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code_elsewhere, None));
+        let completions = super::completion(&state, &uri, Position::new(1, 0));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(
+                items.iter().all(|item| item.label != "in"),
+                "'in' shouldn't be offered outside a for-header"
             );
         }
+    }
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy (Multi-line if block)
-        ///
-        /// For any detected orphaned `else` after a multi-line if block, the diagnostic
-        /// range SHALL accurately cover the `else` keyword.
-        ///
-        /// **Validates: Requirements 3.2**
-        fn prop_diagnostic_range_accuracy_multiline(
-            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body_lines in 1usize..4,
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate multi-line if block with else on new line
-            // Pattern: if (condition) {\n  body_line1\n  body_line2\n}\nelse {body2}
-            let body_content: String = (0..body_lines)
-                .map(|i| format!("  line{}", i))
-                .collect::<Vec<_>>()
-                .join("\n");
+    /// `break`/`next` are only offered inside a loop body, and not inside a
+    /// function nested within one.
+    #[test]
+    fn test_completion_break_next_keywords_inside_loop_only() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
+
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+
+        let code_in_loop = "for (i in 1:10) {\n  \n}\n";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code_in_loop, None));
+        let completions = super::completion(&state, &uri, Position::new(1, 2));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(items.iter().any(|item| item.label == "break"));
+            assert!(items.iter().any(|item| item.label == "next"));
+        }
+
+        let code_outside_loop = "x <- 1\n";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code_outside_loop, None));
+        let completions = super::completion(&state, &uri, Position::new(1, 0));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(items.iter().all(|item| item.label != "break"));
+            assert!(items.iter().all(|item| item.label != "next"));
+        }
+
+        let code_nested_function = "for (i in 1:10) {\n  f <- function() {\n    \n  }\n}\n";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code_nested_function, None));
+        let completions = super::completion(&state, &uri, Position::new(2, 4));
+        if let Some(CompletionResponse::Array(items)) = completions {
+            assert!(
+                items.iter().all(|item| item.label != "break"),
+                "'break' shouldn't apply to a loop outside a nested function"
+            );
+        }
+    }
+
+    /// Verifies completion precedence where local definitions shadow package exports, and package exports take precedence over cross-file symbols.
+    ///
+    /// Sets up a WorldState with a package ("dplyr") that exports several symbols, opens a document that loads that package and defines a local `mutate` (which should shadow the package export) and `my_func`, then requests completions at a position and asserts:
+    /// - the local `mutate` appears once with no package attribution,
+    /// - `filter` and `select` appear once each with package attribution `{dplyr}`,
+    /// - `my_func` appears as a function completion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Arrange: create state, insert package exports and document, then call completion.
+    /// // Assert: see comments above for expected precedence behavior.
+    /// ```
+    #[test]
+    fn test_completion_full_precedence_chain() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionItemKind, CompletionResponse, Position};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
+
+            // Add packages with various exports
+            let mut dplyr_exports = std::collections::HashSet::new();
+            dplyr_exports.insert("mutate".to_string());
+            dplyr_exports.insert("filter".to_string());
+            dplyr_exports.insert("select".to_string());
+            let dplyr_info = PackageInfo::new("dplyr".to_string(), dplyr_exports);
+            state.package_library.insert_package(dplyr_info).await;
+
+            // Create a document that:
+            // 1. Loads dplyr (provides mutate, filter, select)
+            // 2. Defines "mutate" locally (should shadow package export)
+            // 3. Defines "my_func" locally
+            let code = r#"library(dplyr)
+mutate <- function(df, ...) { df }
+my_func <- function(x) { x }
+result <- "#;
+            let uri = Url::parse("file:///test.R").unwrap();
+            let doc = Document::new(code, None);
+            state.documents.insert(uri.clone(), doc);
+
+            // Get completions at the end
+            let position = Position::new(3, 10);
+            let completions = super::completion(&state, &uri, position);
+
+            assert!(completions.is_some(), "Should return completions");
+
+            if let Some(CompletionResponse::Array(items)) = completions {
+                // Check "mutate" - should be local (no package attribution)
+                let mutate_items: Vec<_> =
+                    items.iter().filter(|item| item.label == "mutate").collect();
+                assert_eq!(mutate_items.len(), 1, "Should have exactly one 'mutate'");
+                assert!(
+                    mutate_items[0].detail.is_none()
+                        || !mutate_items[0].detail.as_ref().unwrap().contains("{dplyr}"),
+                    "Local 'mutate' should not have package attribution"
+                );
+
+                // Check "filter" - should be from package (has attribution)
+                let filter_items: Vec<_> =
+                    items.iter().filter(|item| item.label == "filter").collect();
+                assert_eq!(filter_items.len(), 1, "Should have exactly one 'filter'");
+                assert!(
+                    filter_items[0]
+                        .detail
+                        .as_ref()
+                        .map_or(false, |d| d.contains("{dplyr}")),
+                    "'filter' should have package attribution {{dplyr}}"
+                );
+
+                // Check "select" - should be from package (has attribution)
+                let select_items: Vec<_> =
+                    items.iter().filter(|item| item.label == "select").collect();
+                assert_eq!(select_items.len(), 1, "Should have exactly one 'select'");
+                assert!(
+                    select_items[0]
+                        .detail
+                        .as_ref()
+                        .map_or(false, |d| d.contains("{dplyr}")),
+                    "'select' should have package attribution {{dplyr}}"
+                );
+
+                // Check "my_func" - should be local (no package attribution)
+                let my_func_items: Vec<_> = items
+                    .iter()
+                    .filter(|item| item.label == "my_func")
+                    .collect();
+                assert_eq!(my_func_items.len(), 1, "Should have exactly one 'my_func'");
+                assert_eq!(
+                    my_func_items[0].kind,
+                    Some(CompletionItemKind::FUNCTION),
+                    "'my_func' should be a FUNCTION"
+                );
+            } else {
+                panic!("Expected CompletionResponse::Array");
+            }
+        });
+    }
+
+    /// Test that seen_names correctly prevents duplicates across all sources.
+    /// Validates: Requirements 9.3, 9.4, 9.5 - duplicate exports show all packages
+    #[test]
+    fn test_completion_duplicate_exports_show_all_packages() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
+
+            // Add two packages that both export "common_func"
+            let mut pkg1_exports = std::collections::HashSet::new();
+            pkg1_exports.insert("common_func".to_string());
+            pkg1_exports.insert("pkg1_only".to_string());
+            let pkg1_info = PackageInfo::new("pkg1".to_string(), pkg1_exports);
+            state.package_library.insert_package(pkg1_info).await;
+
+            let mut pkg2_exports = std::collections::HashSet::new();
+            pkg2_exports.insert("common_func".to_string());
+            pkg2_exports.insert("pkg2_only".to_string());
+            let pkg2_info = PackageInfo::new("pkg2".to_string(), pkg2_exports);
+            state.package_library.insert_package(pkg2_info).await;
+
+            // Create a document that loads both packages
+            let code = r#"library(pkg1)
+library(pkg2)
+x <- "#;
+            let uri = Url::parse("file:///test.R").unwrap();
+            let doc = Document::new(code, None);
+            state.documents.insert(uri.clone(), doc);
+
+            // Get completions
+            let position = Position::new(2, 5);
+            let completions = super::completion(&state, &uri, position);
+
+            assert!(completions.is_some(), "Should return completions");
+
+            if let Some(CompletionResponse::Array(items)) = completions {
+                // Requirement 9.3: When multiple packages export same symbol, show all with attribution
+                // Check that "common_func" appears twice (once for each package)
+                let common_items: Vec<_> = items
+                    .iter()
+                    .filter(|item| item.label == "common_func")
+                    .collect();
+                assert_eq!(
+                    common_items.len(),
+                    2,
+                    "Should have two 'common_func' entries (one per package)"
+                );
+
+                // Both packages should be represented
+                let has_pkg1 = common_items
+                    .iter()
+                    .any(|item| item.detail.as_ref().map_or(false, |d| d.contains("{pkg1}")));
+                let has_pkg2 = common_items
+                    .iter()
+                    .any(|item| item.detail.as_ref().map_or(false, |d| d.contains("{pkg2}")));
+                assert!(has_pkg1, "'common_func' should have entry from pkg1");
+                assert!(has_pkg2, "'common_func' should have entry from pkg2");
+
+                // Check that unique exports from both packages are present
+                let pkg1_only_items: Vec<_> = items
+                    .iter()
+                    .filter(|item| item.label == "pkg1_only")
+                    .collect();
+                assert_eq!(pkg1_only_items.len(), 1, "Should have 'pkg1_only'");
+
+                let pkg2_only_items: Vec<_> = items
+                    .iter()
+                    .filter(|item| item.label == "pkg2_only")
+                    .collect();
+                assert_eq!(pkg2_only_items.len(), 1, "Should have 'pkg2_only'");
+            } else {
+                panic!("Expected CompletionResponse::Array");
+            }
+        });
+    }
+
+    /// Resolving the local `mutate` from `test_completion_full_precedence_chain`
+    /// should report that it shadows the `{dplyr}` export and show the
+    /// declaring line, using only the provenance recorded on `item.data`.
+    #[test]
+    fn test_completion_item_resolve_reports_shadowed_package() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
+
+            let mut dplyr_exports = std::collections::HashSet::new();
+            dplyr_exports.insert("mutate".to_string());
+            let dplyr_info = PackageInfo::new("dplyr".to_string(), dplyr_exports);
+            state.package_library.insert_package(dplyr_info).await;
+
+            let code = r#"library(dplyr)
+mutate <- function(df, ...) { df }
+result <- "#;
+            let uri = Url::parse("file:///test.R").unwrap();
+            let doc = Document::new(code, None);
+            state.documents.insert(uri.clone(), doc);
+
+            let position = Position::new(2, 10);
+            let completions = super::completion(&state, &uri, position);
+            let Some(CompletionResponse::Array(items)) = completions else {
+                panic!("Expected CompletionResponse::Array");
+            };
+
+            let mutate_item = items
+                .into_iter()
+                .find(|item| item.label == "mutate")
+                .expect("'mutate' should be present");
+
+            let document_contents: std::collections::HashMap<Url, String> =
+                [(uri, code.to_string())].into_iter().collect();
+            let help_cache = state.help_cache.clone();
+            let resolved =
+                super::completion_item_resolve(mutate_item, &help_cache, &document_contents);
+
+            let documentation = match resolved.documentation {
+                Some(Documentation::MarkupContent(content)) => content.value,
+                other => panic!("Expected markup documentation, got {:?}", other),
+            };
+            assert!(
+                documentation.contains("Shadows export from {dplyr}"),
+                "documentation should mention the shadowed package: {documentation}"
+            );
+            assert!(
+                documentation.contains("mutate <- function(df, ...) { df }"),
+                "documentation should include the declaring line: {documentation}"
+            );
+        });
+    }
+
+    /// Resolving one of the two `common_func` entries from
+    /// `test_completion_duplicate_exports_show_all_packages` should list every
+    /// contributing package, not just the one it was attributed to.
+    #[test]
+    fn test_completion_item_resolve_reports_all_contributing_packages() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::{CompletionResponse, Position};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
+
+            let mut pkg1_exports = std::collections::HashSet::new();
+            pkg1_exports.insert("common_func".to_string());
+            let pkg1_info = PackageInfo::new("pkg1".to_string(), pkg1_exports);
+            state.package_library.insert_package(pkg1_info).await;
+
+            let mut pkg2_exports = std::collections::HashSet::new();
+            pkg2_exports.insert("common_func".to_string());
+            let pkg2_info = PackageInfo::new("pkg2".to_string(), pkg2_exports);
+            state.package_library.insert_package(pkg2_info).await;
+
+            let code = r#"library(pkg1)
+library(pkg2)
+x <- "#;
+            let uri = Url::parse("file:///test.R").unwrap();
+            let doc = Document::new(code, None);
+            state.documents.insert(uri.clone(), doc);
+
+            let position = Position::new(2, 5);
+            let completions = super::completion(&state, &uri, position);
+            let Some(CompletionResponse::Array(items)) = completions else {
+                panic!("Expected CompletionResponse::Array");
+            };
+
+            let common_item = items
+                .into_iter()
+                .find(|item| item.label == "common_func")
+                .expect("'common_func' should be present");
+
+            let document_contents: std::collections::HashMap<Url, String> =
+                [(uri, code.to_string())].into_iter().collect();
+            let help_cache = state.help_cache.clone();
+            let resolved =
+                super::completion_item_resolve(common_item, &help_cache, &document_contents);
+
+            let documentation = match resolved.documentation {
+                Some(Documentation::MarkupContent(content)) => content.value,
+                other => panic!("Expected markup documentation, got {:?}", other),
+            };
+            assert!(
+                documentation.contains("{pkg1}") && documentation.contains("{pkg2}"),
+                "documentation should list both contributing packages: {documentation}"
+            );
+        });
+    }
+
+    // ========================================================================
+    // Backward Directive Path Resolution Tests
+    // Tests for fix-backward-directive-path-resolution spec
+    // Validates: Requirements 1.2, 3.2
+    // ========================================================================
+
+    /// Test that backward directive paths resolve relative to file's directory, ignoring @lsp-cd.
+    ///
+    /// This test reproduces a bug where `collect_ambiguous_parent_diagnostics` was using
+    /// `PathContext::from_metadata` (which respects @lsp-cd) instead of `PathContext::new`
+    /// (which ignores @lsp-cd) for backward directive resolution.
+    ///
+    /// Scenario:
+    /// - Child file at `subdir/child.r` contains:
+    ///   - `@lsp-cd ..` (sets working directory to parent/workspace root)
+    ///   - `@lsp-run-by: program.r` (declares parent file)
+    /// - The backward directive should resolve `program.r` relative to `subdir/` (file's directory)
+    ///   NOT relative to the workspace root (the @lsp-cd directory)
+    ///
+    /// Validates: Requirements 1.2, 3.2
+    #[test]
+    fn test_backward_directive_ignores_lsp_cd() {
+        use crate::cross_file::path_resolve::PathContext;
+        use crate::cross_file::types::CrossFileMetadata;
+
+        // Simulate a child file at /project/subdir/child.r
+        let child_uri = Url::parse("file:///project/subdir/child.r").unwrap();
+        let workspace_root = Url::parse("file:///project").unwrap();
+
+        // Metadata with @lsp-cd .. (points to /project, the workspace root)
+        let meta = CrossFileMetadata {
+            working_directory: Some("..".to_string()),
+            ..Default::default()
+        };
+
+        // PathContext::new should ignore @lsp-cd
+        let ctx_new = PathContext::new(&child_uri, Some(&workspace_root)).unwrap();
+
+        // PathContext::from_metadata should respect @lsp-cd
+        let ctx_from_meta =
+            PathContext::from_metadata(&child_uri, &meta, Some(&workspace_root)).unwrap();
+
+        // Verify that PathContext::new ignores @lsp-cd
+        // The effective working directory should be the file's directory: /project/subdir
+        assert_eq!(
+            ctx_new.effective_working_directory(),
+            std::path::PathBuf::from("/project/subdir"),
+            "PathContext::new should use file's directory, ignoring @lsp-cd"
+        );
+
+        // Verify that PathContext::from_metadata respects @lsp-cd
+        // The effective working directory should be /project (the @lsp-cd directory)
+        assert_eq!(
+            ctx_from_meta.effective_working_directory(),
+            std::path::PathBuf::from("/project"),
+            "PathContext::from_metadata should use @lsp-cd directory"
+        );
+
+        // Now test path resolution for a backward directive path "program.r"
+        let backward_path = "program.r";
+
+        // With PathContext::new (correct for backward directives):
+        // "program.r" should resolve to /project/subdir/program.r
+        let resolved_new = crate::cross_file::path_resolve::resolve_path(backward_path, &ctx_new);
+        assert_eq!(
+            resolved_new,
+            Some(std::path::PathBuf::from("/project/subdir/program.r")),
+            "Backward directive 'program.r' should resolve relative to file's directory"
+        );
+
+        // With PathContext::from_metadata (incorrect for backward directives):
+        // "program.r" would resolve to /project/program.r (wrong!)
+        let resolved_from_meta =
+            crate::cross_file::path_resolve::resolve_path(backward_path, &ctx_from_meta);
+        assert_eq!(
+            resolved_from_meta,
+            Some(std::path::PathBuf::from("/project/program.r")),
+            "With @lsp-cd, 'program.r' would incorrectly resolve to workspace root"
+        );
+
+        // The key assertion: the two resolutions are DIFFERENT
+        // This demonstrates why using PathContext::new is essential for backward directives
+        assert_ne!(
+            resolved_new, resolved_from_meta,
+            "PathContext::new and PathContext::from_metadata should produce different results when @lsp-cd is present"
+        );
+    }
+
+    // ========================================================================
+    // Else Newline Syntax Error Tests (Task 1.3)
+    // Tests for else-newline-syntax-error feature
+    // Validates: Requirements 2.1, 2.2, 2.3, 2.4
+    // ========================================================================
+
+    /// Test that `if (x) {y}\nelse {z}` emits a diagnostic for orphaned else.
+    /// Validates: Requirement 2.1 - else on new line after closing brace should emit diagnostic
+    #[test]
+    fn test_else_newline_basic_invalid_pattern() {
+        let code = "if (x) {y}\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned else on new line"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Diagnostic severity should be ERROR"
+        );
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(
+                diagnostic_codes::ELSE_ON_NEW_LINE.to_string()
+            )),
+            "Diagnostic code should identify the orphaned-else rule"
+        );
+    }
+
+    /// Test that `if (x) {y} else {z}` does NOT emit a diagnostic.
+    /// Validates: Requirement 2.3 - else on same line as closing brace should not emit diagnostic
+    #[test]
+    fn test_else_newline_basic_valid_pattern() {
+        let code = "if (x) {y} else {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit diagnostic when else is on same line as closing brace"
+        );
+    }
+
+    /// Test that multi-line valid if-else does NOT emit a diagnostic.
+    /// `if (x) {\n  y\n} else {\n  z\n}` - else on same line as closing brace
+    /// Validates: Requirement 2.4 - multi-line with else on same line as brace should not emit diagnostic
+    #[test]
+    fn test_else_newline_multiline_valid_pattern() {
+        let code = "if (x) {\n  y\n} else {\n  z\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit diagnostic when else is on same line as closing brace (multi-line)"
+        );
+    }
+
+    /// Test that multi-line invalid if-else emits a diagnostic.
+    /// `if (x) {\n  y\n}\nelse {\n  z\n}` - else on new line after closing brace
+    /// Validates: Requirement 2.2 - multi-line if with else on new line after brace should emit diagnostic
+    #[test]
+    fn test_else_newline_multiline_invalid_pattern() {
+        let code = "if (x) {\n  y\n}\nelse {\n  z\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned else on new line (multi-line)"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Diagnostic severity should be ERROR"
+        );
+    }
+
+    /// Test that the diagnostic range covers the `else` keyword exactly.
+    /// Validates: Requirement 3.2 - diagnostic range should highlight the else keyword
+    #[test]
+    fn test_else_newline_diagnostic_range() {
+        let code = "if (x) {y}\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+
+        let diag = &diagnostics[0];
+        // "else" starts at line 1 (0-indexed), column 0
+        assert_eq!(
+            diag.range.start.line, 1,
+            "Diagnostic should start on line 1 (0-indexed)"
+        );
+        assert_eq!(
+            diag.range.start.character, 0,
+            "Diagnostic should start at column 0"
+        );
+        // "else" is 4 characters long
+        assert_eq!(
+            diag.range.end.line, 1,
+            "Diagnostic should end on line 1"
+        );
+        assert_eq!(
+            diag.range.end.character, 4,
+            "Diagnostic should end at column 4 (covering 'else')"
+        );
+    }
+
+    /// Validates: related information should point at the closing `}` of the
+    /// preceding `if` block so clients can highlight both ends of the problem.
+    #[test]
+    fn test_else_newline_diagnostic_related_information_covers_brace() {
+        let code = "if (x) {y}\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+
+        let related = diagnostics[0]
+            .related_information
+            .as_ref()
+            .expect("orphaned-else diagnostic should carry related information");
+        assert_eq!(related.len(), 1);
+
+        let info = &related[0];
+        assert_eq!(info.location.uri, Url::parse("file:///test.R").unwrap());
+        // "if (x) {y}" - the closing '}' is the last character on line 0
+        assert_eq!(info.location.range.start.line, 0);
+        assert_eq!(info.location.range.start.character, 9);
+        assert_eq!(info.location.range.end.line, 0);
+        assert_eq!(info.location.range.end.character, 10);
+    }
+
+    /// Validates: the nested if/else detection path (Case 2 in
+    /// `collect_else_newline_errors`, where tree-sitter parses `else` as a
+    /// keyword child of the `if_statement` rather than an identifier sibling)
+    /// also attaches related information pointing at the closing `}` of the
+    /// consequence block.
+    #[test]
+    fn test_else_newline_nested_related_information_covers_brace() {
+        let code = "if (a) { if (b) {c}\nelse {d} }";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+
+        let related = diagnostics[0]
+            .related_information
+            .as_ref()
+            .expect("nested orphaned-else diagnostic should carry related information");
+        assert_eq!(related.len(), 1);
+
+        let info = &related[0];
+        // The inner consequence "{c}" closes on line 0, right after "if (b) "
+        assert_eq!(info.location.range.start.line, 0);
+        assert_eq!(info.location.range.start.character, 18);
+        assert_eq!(info.location.range.end.line, 0);
+        assert_eq!(info.location.range.end.character, 19);
+    }
+
+    // ========================================================================
+    // Else Newline Quick Fix Tests
+    // Tests for the "Move 'else' onto the same line as '}'" code action
+    // ========================================================================
+
+    /// Basic case: `if (x) {y}\nelse {z}` - the fix should pull `else` up onto
+    /// the closing brace's line with a single separating space.
+    #[test]
+    fn test_else_newline_fix_basic() {
+        let code = "if (x) {y}\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let edit =
+            super::else_newline_fix_edit(&uri, code, diagnostics[0].range).expect("fix expected");
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, " ");
+        assert_eq!(edits[0].range.start, Position::new(0, 10));
+        assert_eq!(edits[0].range.end, Position::new(1, 0));
+    }
+
+    /// Multi-line case with blank lines between the brace and `else`: the
+    /// whole gap (not just a single newline) should collapse to one space.
+    #[test]
+    fn test_else_newline_fix_multiline() {
+        let code = "if (x) {\n  y\n}\n\nelse {\n  z\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let edit =
+            super::else_newline_fix_edit(&uri, code, diagnostics[0].range).expect("fix expected");
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].new_text, " ");
+        // Closing brace of the consequence is on line 2, column 1
+        assert_eq!(edits[0].range.start, Position::new(2, 1));
+        assert_eq!(edits[0].range.end, Position::new(4, 0));
+
+        let mut fixed = code.to_string();
+        let start = byte_offset_for_test(code, edits[0].range.start);
+        let end = byte_offset_for_test(code, edits[0].range.end);
+        fixed.replace_range(start..end, &edits[0].new_text);
+        assert_eq!(fixed, "if (x) {\n  y\n} else {\n  z\n}");
+    }
+
+    /// Nested case: the inner orphaned else gets its own independent fix.
+    #[test]
+    fn test_else_newline_fix_nested() {
+        let code = "if (a) { if (b) {c}\nelse {d} }";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let edit =
+            super::else_newline_fix_edit(&uri, code, diagnostics[0].range).expect("fix expected");
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].new_text, " ");
+
+        let mut fixed = code.to_string();
+        let start = byte_offset_for_test(code, edits[0].range.start);
+        let end = byte_offset_for_test(code, edits[0].range.end);
+        fixed.replace_range(start..end, &edits[0].new_text);
+        assert_eq!(fixed, "if (a) { if (b) {c} else {d} }");
+    }
+
+    /// A comment trailing the closing brace would otherwise swallow the
+    /// joined `else` into itself (`} # comment else {` comments out the
+    /// whole else branch), so the fix relocates it to the end of the `else`
+    /// line instead of leaving it in place.
+    #[test]
+    fn test_else_newline_fix_preserves_trailing_comment() {
+        let code = "if (x) {y} # comment\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let edit =
+            super::else_newline_fix_edit(&uri, code, diagnostics[0].range).expect("fix expected");
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(
+            edits.len(),
+            2,
+            "should emit a collapse edit plus a comment relocation"
+        );
+
+        // Apply right-to-left so earlier offsets aren't shifted by later edits.
+        let mut sorted_edits = edits.clone();
+        sorted_edits.sort_by_key(|e| std::cmp::Reverse(byte_offset_for_test(code, e.range.start)));
+        let mut fixed = code.to_string();
+        for edit in &sorted_edits {
+            let start = byte_offset_for_test(code, edit.range.start);
+            let end = byte_offset_for_test(code, edit.range.end);
+            fixed.replace_range(start..end, &edit.new_text);
+        }
+        assert_eq!(fixed, "if (x) {y} else {z} # comment");
+    }
+
+    /// A standalone comment line between `}` and `else` can't be collapsed
+    /// away (it would be deleted) or joined onto the `else` line (it would
+    /// comment out the whole else branch), so it's relocated to its own line
+    /// directly above the closing brace.
+    #[test]
+    fn test_else_newline_fix_relocates_standalone_comment() {
+        let code = "if (x) {\n  y\n}\n# a comment\nelse {\n  z\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let edit =
+            super::else_newline_fix_edit(&uri, code, diagnostics[0].range).expect("fix expected");
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(
+            edits.len(),
+            2,
+            "should emit a collapse edit plus a comment relocation"
+        );
+
+        let mut sorted_edits = edits.clone();
+        sorted_edits.sort_by_key(|e| std::cmp::Reverse(byte_offset_for_test(code, e.range.start)));
+        let mut fixed = code.to_string();
+        for edit in &sorted_edits {
+            let start = byte_offset_for_test(code, edit.range.start);
+            let end = byte_offset_for_test(code, edit.range.end);
+            fixed.replace_range(start..end, &edit.new_text);
+        }
+        assert_eq!(fixed, "if (x) {\n  y\n# a comment\n} else {\n  z\n}");
+    }
+
+    /// Multiple standalone comment lines between `}` and `else` are all
+    /// relocated above the brace, preserving their original order.
+    #[test]
+    fn test_else_newline_fix_relocates_multiple_standalone_comments() {
+        let code = "if (x) {\n  y\n}\n# first\n# second\nelse {\n  z\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let edit =
+            super::else_newline_fix_edit(&uri, code, diagnostics[0].range).expect("fix expected");
+        let edits = &edit.changes.unwrap()[&uri];
+
+        let mut sorted_edits = edits.clone();
+        sorted_edits.sort_by_key(|e| std::cmp::Reverse(byte_offset_for_test(code, e.range.start)));
+        let mut fixed = code.to_string();
+        for edit in &sorted_edits {
+            let start = byte_offset_for_test(code, edit.range.start);
+            let end = byte_offset_for_test(code, edit.range.end);
+            fixed.replace_range(start..end, &edit.new_text);
+        }
+        assert_eq!(fixed, "if (x) {\n  y\n# first\n# second\n} else {\n  z\n}");
+    }
+
+    /// Applying `else_newline_fix_edit` and re-running the detector on the
+    /// result must yield zero diagnostics, for every shape the fix handles
+    /// (plain gap, multiline, nested, trailing comment, standalone comments).
+    #[test]
+    fn test_else_newline_fix_round_trip_yields_zero_diagnostics() {
+        let cases = [
+            "if (x) {y}\nelse {z}",
+            "if (x) {\n  y\n}\n\nelse {\n  z\n}",
+            "if (a) { if (b) {c}\nelse {d} }",
+            "if (x) {y} # comment\nelse {z}",
+            "if (x) {\n  y\n}\n# a comment\nelse {\n  z\n}",
+            "if (x) {\n  y\n}\n# first\n# second\nelse {\n  z\n}",
+        ];
+        let uri = Url::parse("file:///test.R").unwrap();
+
+        for code in cases {
+            let tree = parse_r_code(code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(tree.root_node(), code, &uri, &mut diagnostics);
+            assert_eq!(diagnostics.len(), 1, "expected one diagnostic for {code:?}");
+
+            let edit = super::else_newline_fix_edit(&uri, code, diagnostics[0].range)
+                .expect("fix expected");
+            let edits = &edit.changes.unwrap()[&uri];
+
+            let mut sorted_edits = edits.clone();
+            sorted_edits
+                .sort_by_key(|e| std::cmp::Reverse(byte_offset_for_test(code, e.range.start)));
+            let mut fixed = code.to_string();
+            for edit in &sorted_edits {
+                let start = byte_offset_for_test(code, edit.range.start);
+                let end = byte_offset_for_test(code, edit.range.end);
+                fixed.replace_range(start..end, &edit.new_text);
+            }
+
+            let fixed_tree = parse_r_code(&fixed);
+            let mut fixed_diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                fixed_tree.root_node(),
+                &fixed,
+                &uri,
+                &mut fixed_diagnostics,
+            );
+            assert!(
+                fixed_diagnostics.is_empty(),
+                "fix for {code:?} produced {fixed:?}, which still has else-newline diagnostics: {fixed_diagnostics:?}"
+            );
+        }
+    }
+
+    /// `code_action` should surface the fix as a `quickfix` CodeAction when
+    /// the request range overlaps the diagnostic, and filter it out otherwise.
+    #[test]
+    fn test_else_newline_code_action_quickfix() {
+        use crate::state::{Document, WorldState};
+
+        let code = "if (x) {y}\nelse {z}";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let actions = super::code_action(&state, &uri, diagnostics[0].range, &diagnostics, None)
+            .expect("expected a quickfix action");
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            CodeActionOrCommand::CodeAction(action) => {
+                assert_eq!(action.kind, Some(CodeActionKind::QUICKFIX));
+                assert!(action.edit.is_some());
+            }
+            CodeActionOrCommand::Command(_) => panic!("expected a CodeAction, not a Command"),
+        }
+
+        // A range that doesn't overlap the diagnostic should yield no actions.
+        let far_range = Range {
+            start: Position::new(0, 0),
+            end: Position::new(0, 1),
+        };
+        assert!(super::code_action(&state, &uri, far_range, &diagnostics, None).is_none());
+    }
+
+    /// `code_action` offers "Convert '=' to '<-' assignment" for a top-level
+    /// `=` assignment, rewriting only the operator.
+    #[test]
+    fn test_convert_equals_to_arrow_assignment() {
+        use crate::state::{Document, WorldState};
+
+        let code = "x = 1";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let range = Range {
+            start: Position::new(0, 0),
+            end: Position::new(0, 0),
+        };
+        let actions =
+            super::code_action(&state, &uri, range, &[], None).expect("expected a refactor action");
+        let action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Convert '=' to '<-' assignment" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected the convert-assignment action");
+        assert_eq!(action.kind, Some(CodeActionKind::REFACTOR_REWRITE));
+
+        let edit = action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "<-");
+        assert_eq!(
+            edits[0].range,
+            Range {
+                start: Position::new(0, 2),
+                end: Position::new(0, 3)
+            }
+        );
+    }
+
+    /// The reverse direction: `<-` converts to `=`, and a named call argument
+    /// (which parses as an `argument` node, not a `binary_operator`) is left
+    /// alone since it was never offered the action in the first place.
+    #[test]
+    fn test_convert_arrow_to_equals_assignment_ignores_named_argument() {
+        use crate::state::{Document, WorldState};
+
+        let code = "f(x = 1)\ny <- 2";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        // Cursor inside the named argument: no assignment-conversion action.
+        let arg_range = Range {
+            start: Position::new(0, 2),
+            end: Position::new(0, 2),
+        };
+        let actions = super::code_action(&state, &uri, arg_range, &[], None).unwrap_or_default();
+        assert!(actions.iter().all(|a| !matches!(a,
+            CodeActionOrCommand::CodeAction(action) if action.title.contains("assignment"))));
+
+        // Cursor on the `<-` assignment: offers the reverse conversion.
+        let assign_range = Range {
+            start: Position::new(1, 0),
+            end: Position::new(1, 0),
+        };
+        let actions = super::code_action(&state, &uri, assign_range, &[], None)
+            .expect("expected a refactor action");
+        let action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Convert '<-' to '=' assignment" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected the convert-assignment action");
+        let edit = action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits[0].new_text, "=");
+    }
+
+    /// `code_action` offers "Extract expression to variable" for a selected
+    /// expression, inserting `name <- <expr>` above with matching indentation
+    /// and replacing the selection with the new name.
+    #[test]
+    fn test_extract_variable_inserts_above_with_matching_indentation() {
+        use crate::state::{Document, WorldState};
+
+        let code = "f <- function() {\n  g(1 + 2)\n}";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        // Select `1 + 2` on line 1.
+        let range = Range {
+            start: Position::new(1, 4),
+            end: Position::new(1, 9),
+        };
+        let actions =
+            super::code_action(&state, &uri, range, &[], None).expect("expected a refactor action");
+        let action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Extract expression to variable" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected the extract-variable action");
+        assert_eq!(action.kind, Some(CodeActionKind::REFACTOR_EXTRACT));
+
+        let edit = action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            edits[0].range,
+            Range {
+                start: Position::new(1, 0),
+                end: Position::new(1, 0)
+            }
+        );
+        assert_eq!(edits[0].new_text, "  extracted_value <- 1 + 2\n");
+        assert_eq!(
+            edits[1].range,
+            Range {
+                start: Position::new(1, 4),
+                end: Position::new(1, 9)
+            }
+        );
+        assert_eq!(edits[1].new_text, "extracted_value");
+    }
+
+    /// A file-scoped `@lsp-allow` directive (one that appears before any real
+    /// code) suppresses every diagnostic with the matching code, anywhere in
+    /// the file.
+    #[test]
+    fn test_else_newline_file_scoped_allow_directive_suppresses_all() {
+        use crate::state::{Document, WorldState};
+
+        let code = "# @lsp-allow: raven::else-on-new-line\nif (x) {y}\nelse {z}";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let diagnostics = super::diagnostics(&state, &uri);
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "file-scoped @lsp-allow should suppress the orphaned-else diagnostic"
+        );
+    }
+
+    /// A line-scoped `@lsp-allow` directive (one that appears after real code
+    /// has already been seen) only suppresses diagnostics on the line
+    /// immediately following it, leaving other instances of the same code
+    /// reported elsewhere in the file.
+    #[test]
+    fn test_else_newline_line_scoped_allow_directive_suppresses_one() {
+        use crate::state::{Document, WorldState};
+
+        let code =
+            "if (a) {x}\n# @lsp-allow: raven::else-on-new-line\nelse {y}\n\nif (b) {z}\nelse {w}";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let diagnostics = super::diagnostics(&state, &uri);
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "line-scoped @lsp-allow should suppress only the else it directly precedes"
+        );
+        assert_eq!(
+            diagnostics[0].range.start.line, 5,
+            "the unallowed orphaned else (line 5) should still be reported"
+        );
+    }
+
+    /// Test-only helper: converts a (line, character) `Position` (as produced
+    /// by this file's byte-column diagnostics) into a byte offset into `text`.
+    #[cfg(test)]
+    fn byte_offset_for_test(text: &str, position: Position) -> usize {
+        let mut offset = 0;
+        for (i, line) in text.split('\n').enumerate() {
+            if i == position.line as usize {
+                return offset + position.character as usize;
+            }
+            offset += line.len() + 1;
+        }
+        offset
+    }
+
+    // ========================================================================
+    // Nested If-Else Tests (Task 2.1)
+    // Tests for nested if-else detection
+    // Validates: Requirements 2.5
+    // ========================================================================
+
+    /// Test that nested valid if-else does NOT emit a diagnostic.
+    /// `if (a) { if (b) {c} else {d} } else {e}` - all else on same line as closing brace
+    /// Validates: Requirement 2.5 - nested if-else with valid else placement should not emit diagnostic
+    #[test]
+    fn test_else_newline_nested_valid_pattern() {
+        let code = "if (a) { if (b) {c} else {d} } else {e}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit diagnostic when all else keywords are on same line as closing brace (nested)"
+        );
+    }
+
+    /// Test that nested invalid if-else emits a diagnostic for the inner orphaned else.
+    /// `if (a) { if (b) {c}\nelse {d} }` - inner else on new line after closing brace
+    /// Validates: Requirement 2.5 - nested if-else with orphaned else should emit diagnostic
+    #[test]
+    fn test_else_newline_nested_invalid_inner_else() {
+        let code = "if (a) { if (b) {c}\nelse {d} }";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned inner else on new line (nested)"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Diagnostic severity should be ERROR"
+        );
+        // The inner else is on line 1 (0-indexed)
+        assert_eq!(
+            diagnostics[0].range.start.line, 1,
+            "Diagnostic should be on line 1 (0-indexed) where the orphaned else is"
+        );
+    }
+
+    /// Test that nested invalid if-else with outer orphaned else emits a diagnostic.
+    /// `if (a) { if (b) {c} else {d} }\nelse {e}` - outer else on new line
+    /// Validates: Requirement 2.5 - nested if-else with orphaned outer else should emit diagnostic
+    #[test]
+    fn test_else_newline_nested_invalid_outer_else() {
+        let code = "if (a) { if (b) {c} else {d} }\nelse {e}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned outer else on new line (nested)"
+        );
+        // The outer else is on line 1 (0-indexed)
+        assert_eq!(
+            diagnostics[0].range.start.line, 1,
+            "Diagnostic should be on line 1 (0-indexed) where the orphaned outer else is"
+        );
+    }
+
+    /// Test that deeply nested if-else with multiple orphaned else keywords emits multiple diagnostics.
+    /// Validates: Requirement 2.5 - all orphaned else at any nesting level should be detected
+    #[test]
+    fn test_else_newline_deeply_nested_multiple_invalid() {
+        // Both inner and outer else are on new lines
+        let code = "if (a) { if (b) {c}\nelse {d} }\nelse {e}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            2,
+            "Should emit two diagnostics for both orphaned else keywords (nested)"
+        );
+    }
+
+    // ========================================================================
+    // Else If Pattern Tests (Task 2.2)
+    // Tests for `else if` on new line detection
+    // Validates: Requirements 5.2
+    // ========================================================================
+
+    /// Test that `if (x) {y}\nelse if (z) {w}` emits a diagnostic for orphaned else.
+    /// Validates: Requirement 5.2 - `else if` on new line should emit diagnostic
+    #[test]
+    fn test_else_newline_else_if_on_new_line() {
+        let code = "if (x) {y}\nelse if (z) {w}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned 'else if' on new line"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Diagnostic severity should be ERROR"
+        );
+        // The else is on line 1 (0-indexed), column 0
+        assert_eq!(
+            diagnostics[0].range.start.line, 1,
+            "Diagnostic should start on line 1 (0-indexed)"
+        );
+        assert_eq!(
+            diagnostics[0].range.start.character, 0,
+            "Diagnostic should start at column 0"
+        );
+    }
+
+    /// Test that `if (x) {y} else if (z) {w}` does NOT emit a diagnostic.
+    /// Validates: Requirement 5.2 - valid `else if` on same line should not emit diagnostic
+    #[test]
+    fn test_else_newline_else_if_on_same_line() {
+        let code = "if (x) {y} else if (z) {w}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit diagnostic when 'else if' is on same line as closing brace"
+        );
+    }
+
+    /// Test that multi-line `else if` on new line emits a diagnostic.
+    /// `if (x) {\n  y\n}\nelse if (z) {\n  w\n}` - else if on new line after closing brace
+    /// Validates: Requirement 5.2 - multi-line `else if` on new line should emit diagnostic
+    #[test]
+    fn test_else_newline_else_if_multiline_invalid() {
+        let code = "if (x) {\n  y\n}\nelse if (z) {\n  w\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned 'else if' on new line (multi-line)"
+        );
+        // The else is on line 3 (0-indexed)
+        assert_eq!(
+            diagnostics[0].range.start.line, 3,
+            "Diagnostic should be on line 3 (0-indexed) where the orphaned else is"
+        );
+    }
+
+    /// Test that valid multi-line `else if` does NOT emit a diagnostic.
+    /// `if (x) {\n  y\n} else if (z) {\n  w\n}` - else if on same line as closing brace
+    /// Validates: Requirement 5.2 - valid multi-line `else if` should not emit diagnostic
+    #[test]
+    fn test_else_newline_else_if_multiline_valid() {
+        let code = "if (x) {\n  y\n} else if (z) {\n  w\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit diagnostic when 'else if' is on same line as closing brace (multi-line)"
+        );
+    }
+
+    // ========================================================================
+    // Blank Lines Tests (Task 2.3)
+    // Tests for blank lines between `}` and `else`
+    // Validates: Requirements 5.4
+    // ========================================================================
+
+    /// Test that `if (x) {y}\n\nelse {z}` emits a diagnostic for orphaned else.
+    /// Validates: Requirement 5.4 - blank lines between `}` and `else` should emit diagnostic
+    #[test]
+    fn test_else_newline_blank_lines_between_brace_and_else() {
+        let code = "if (x) {y}\n\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned else with blank line between"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Diagnostic severity should be ERROR"
+        );
+        // The else is on line 2 (0-indexed) due to the blank line
+        assert_eq!(
+            diagnostics[0].range.start.line, 2,
+            "Diagnostic should start on line 2 (0-indexed) after blank line"
+        );
+        assert_eq!(
+            diagnostics[0].range.start.character, 0,
+            "Diagnostic should start at column 0"
+        );
+    }
+
+    /// Test that multiple blank lines between `}` and `else` still emit a diagnostic.
+    /// Validates: Requirement 5.4 - multiple blank lines should still trigger diagnostic
+    #[test]
+    fn test_else_newline_multiple_blank_lines() {
+        let code = "if (x) {y}\n\n\n\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned else with multiple blank lines"
+        );
+        // The else is on line 4 (0-indexed) due to multiple blank lines
+        assert_eq!(
+            diagnostics[0].range.start.line, 4,
+            "Diagnostic should start on line 4 (0-indexed) after multiple blank lines"
+        );
+    }
+
+    /// Test that multi-line if with blank lines before else emits a diagnostic.
+    /// `if (x) {\n  y\n}\n\nelse {\n  z\n}` - blank line between closing brace and else
+    /// Validates: Requirement 5.4 - multi-line with blank lines should emit diagnostic
+    #[test]
+    fn test_else_newline_multiline_with_blank_lines() {
+        let code = "if (x) {\n  y\n}\n\nelse {\n  z\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit exactly one diagnostic for orphaned else with blank line (multi-line)"
+        );
+        // The closing brace is on line 2 (0-indexed), else is on line 4
+        assert_eq!(
+            diagnostics[0].range.start.line, 4,
+            "Diagnostic should be on line 4 (0-indexed) where the orphaned else is"
+        );
+    }
+
+    // ========================================================================
+    // Edge Case Tests (Task 2.4)
+    // Additional edge case tests for else-newline detection
+    // Validates: Requirements 5.1, 5.3
+    // ========================================================================
+
+    /// Test that standalone `else` without preceding `if` does NOT emit a duplicate diagnostic.
+    /// Tree-sitter handles this as a general syntax error, so we should not emit our
+    /// newline-specific diagnostic to avoid duplicates.
+    /// Validates: Requirement 5.1 - standalone else should not emit newline-specific diagnostic
+    #[test]
+    fn test_else_newline_standalone_else_no_duplicate() {
+        let code = "else {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        // The standalone else is a syntax error handled by tree-sitter.
+        // Our detector should NOT emit a diagnostic for this case to avoid duplicates.
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit newline-specific diagnostic for standalone else (tree-sitter handles this)"
+        );
+    }
+
+    /// Test that comments on the same line as closing brace, with else on new line, emits diagnostic.
+    /// `if (x) {y} # comment\nelse {z}` - else is on a new line, so diagnostic should be emitted
+    /// Validates: Requirement 5.3 - comments between `}` and `else` on same line should not prevent
+    /// diagnostic when else is actually on a new line
+    #[test]
+    fn test_else_newline_comment_same_line_else_new_line() {
+        let code = "if (x) {y} # comment\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit diagnostic when else is on new line even with comment after closing brace"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Diagnostic severity should be ERROR"
+        );
+        // The else is on line 1 (0-indexed)
+        assert_eq!(
+            diagnostics[0].range.start.line, 1,
+            "Diagnostic should start on line 1 (0-indexed) where the orphaned else is"
+        );
+    }
+
+    /// Test that comments between `}` and `else` on the SAME line does NOT emit diagnostic.
+    /// `if (x) {y} # comment else {z}` - this is actually invalid R syntax, but if else were
+    /// somehow on the same line, we should not emit diagnostic.
+    /// Note: In practice, `# comment else {z}` makes `else {z}` part of the comment.
+    /// This test verifies the valid case: `if (x) {y} else {z} # comment`
+    /// Validates: Requirement 5.3 - comments on same line should not affect detection
+    #[test]
+    fn test_else_newline_comment_after_else_same_line() {
+        let code = "if (x) {y} else {z} # comment";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should NOT emit diagnostic when else is on same line as closing brace (with trailing comment)"
+        );
+    }
+
+    // ========================================================================
+    // Diagnostic Properties Tests (Task 3.3)
+    // Comprehensive tests for diagnostic properties
+    // Validates: Requirements 3.1, 3.2, 3.3, 3.4
+    // ========================================================================
+
+    /// Comprehensive test for all diagnostic properties.
+    /// Validates: Requirements 3.1 (severity), 3.2 (range), 3.3 (message), 3.4 (source)
+    #[test]
+    fn test_else_newline_diagnostic_properties_comprehensive() {
+        let code = "if (x) {y}\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+
+        let diag = &diagnostics[0];
+
+        // Requirement 3.1: Diagnostic severity SHALL be ERROR
+        assert_eq!(
+            diag.severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Requirement 3.1: Diagnostic severity should be ERROR"
+        );
+
+        // Requirement 3.3: Diagnostic message SHALL be descriptive
+        assert_eq!(
+            diag.message,
+            "In R, 'else' must appear on the same line as the closing '}' of the if block",
+            "Requirement 3.3: Diagnostic message should match expected text exactly"
+        );
+
+        // Diagnostic code SHALL identify the rule independent of message wording
+        assert_eq!(
+            diag.code,
+            Some(NumberOrString::String(
+                diagnostic_codes::ELSE_ON_NEW_LINE.to_string()
+            )),
+            "Diagnostic code should identify the orphaned-else rule"
+        );
+
+        // Requirement 3.2: Diagnostic range SHALL highlight the `else` keyword
+        // "else" is on line 1 (0-indexed), columns 0-4
+        assert_eq!(
+            diag.range.start.line, 1,
+            "Requirement 3.2: Diagnostic range start line should be 1 (0-indexed)"
+        );
+        assert_eq!(
+            diag.range.start.character, 0,
+            "Requirement 3.2: Diagnostic range start character should be 0"
+        );
+        assert_eq!(
+            diag.range.end.line, 1,
+            "Requirement 3.2: Diagnostic range end line should be 1"
+        );
+        assert_eq!(
+            diag.range.end.character, 4,
+            "Requirement 3.2: Diagnostic range end character should be 4 (covering 'else')"
+        );
+    }
+
+    /// Test that diagnostic severity is ERROR for multi-line patterns.
+    /// Validates: Requirement 3.1 - severity should be ERROR
+    #[test]
+    fn test_else_newline_diagnostic_severity_multiline() {
+        let code = "if (condition) {\n  print(1)\n}\nelse {\n  print(2)\n}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::ERROR),
+            "Requirement 3.1: Diagnostic severity should be ERROR for multi-line patterns"
+        );
+    }
+
+    /// Test that diagnostic range accurately covers the else keyword in various positions.
+    /// Validates: Requirement 3.2 - range should highlight else keyword
+    #[test]
+    fn test_else_newline_diagnostic_range_with_indentation() {
+        // else is indented with spaces
+        let code = "if (x) {y}\n    else {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+
+        let diag = &diagnostics[0];
+        // "else" starts at line 1, column 4 (after 4 spaces)
+        assert_eq!(
+            diag.range.start.line, 1,
+            "Diagnostic should start on line 1"
+        );
+        assert_eq!(
+            diag.range.start.character, 4,
+            "Diagnostic should start at column 4 (after indentation)"
+        );
+        assert_eq!(
+            diag.range.end.character, 8,
+            "Diagnostic should end at column 8 (covering 'else')"
+        );
+    }
+
+    /// Test that the diagnostic carries the stable `raven::else-on-new-line`
+    /// code, which is what clients and `code_action` should key off of rather
+    /// than matching substrings of the human-readable message.
+    /// Validates: Requirement 3.3 - diagnostic should be identifiable
+    #[test]
+    fn test_else_newline_diagnostic_code_content() {
+        let code = "if (x) {y}\nelse {z}";
+        let tree = parse_r_code(code);
+        let mut diagnostics = Vec::new();
+        super::collect_else_newline_errors(
+            tree.root_node(),
+            code,
+            &Url::parse("file:///test.R").unwrap(),
+            &mut diagnostics,
+        );
+
+        assert_eq!(diagnostics.len(), 1, "Should emit exactly one diagnostic");
+
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(
+                diagnostic_codes::ELSE_ON_NEW_LINE.to_string()
+            )),
+            "Requirement 3.3: Diagnostic code should identify the orphaned-else rule"
+        );
+        assert!(
+            diagnostics[0].code_description.is_some(),
+            "Diagnostic should link to documentation via code_description"
+        );
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    use crate::state::Document;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    // Helper to parse R code for property tests
+    fn parse_r_code(code: &str) -> tree_sitter::Tree {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_r::LANGUAGE.into())
+            .unwrap();
+        parser.parse(code, None).unwrap()
+    }
+
+    // Helper to filter out R reserved keywords from generated identifiers
+    fn is_r_reserved(s: &str) -> bool {
+        matches!(
+            s,
+            "for"
+                | "if"
+                | "in"
+                | "else"
+                | "while"
+                | "repeat"
+                | "next"
+                | "break"
+                | "function"
+                | "return"
+                | "true"
+                | "false"
+                | "null"
+                | "inf"
+                | "nan"
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn test_library_require_extraction(pkg_name in "[a-z]{3,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))) {
+            let code_library = format!("library({})", pkg_name);
+            let code_require = format!("require({})", pkg_name);
+            let code_loadns = format!("loadNamespace(\"{}\")", pkg_name);
+
+            let doc1 = Document::new(&code_library, None);
+            let doc2 = Document::new(&code_require, None);
+            let doc3 = Document::new(&code_loadns, None);
+
+            prop_assert!(doc1.loaded_packages.contains(&pkg_name));
+            prop_assert!(doc2.loaded_packages.contains(&pkg_name));
+            prop_assert!(doc3.loaded_packages.contains(&pkg_name));
+        }
+
+        #[test]
+        fn test_multiple_library_calls(pkg_count in 1usize..5) {
+            let packages: Vec<String> = (0..pkg_count)
+                .map(|i| format!("pkg{}", i))
+                .collect();
+
+            let code = packages.iter()
+                .map(|p| format!("library({})", p))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let doc = Document::new(&code, None);
+
+            for pkg in &packages {
+                prop_assert!(doc.loaded_packages.contains(pkg));
+            }
+            prop_assert_eq!(doc.loaded_packages.len(), pkg_count);
+        }
+
+        #[test]
+        fn test_mixed_symbol_types(
+            var_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            builtin in prop::sample::select(vec!["print", "sum", "mean", "length"])
+        ) {
+            let code = format!(
+                "{} <- 42\n{} <- function() {{}}\n{}({})",
+                var_name, func_name, builtin, var_name
+            );
+
+            let tree = parse_r_code(&code);
+            let mut defined = HashSet::new();
+            collect_definitions(tree.root_node(), &code, &mut defined);
+
+            prop_assert!(defined.contains(&var_name));
+            prop_assert!(defined.contains(&func_name));
+            prop_assert!(is_builtin(&builtin));
+        }
+
+        #[test]
+        fn test_named_arguments_not_flagged(
+            func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            arg_name in "[a-z]{2,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            value in 1i32..100
+        ) {
+            let code = format!("{}({} = {})", func_name, arg_name, value);
+
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages(tree.root_node(), &code, &mut used);
+
+            // func_name should be in used, but arg_name should NOT be
+            let func_used = used.iter().any(|(name, _)| name == &func_name);
+            let arg_used = used.iter().any(|(name, _)| name == &arg_name);
+
+            prop_assert!(func_used, "Function name should be collected as usage");
+            prop_assert!(!arg_used, "Named argument should NOT be collected as usage");
+        }
+
+        #[test]
+        fn test_multiple_named_arguments(
+            arg_count in 1usize..4
+        ) {
+            let args: Vec<String> = (0..arg_count)
+                .map(|i| format!("arg{} = {}", i, i + 1))
+                .collect();
+
+            let code = format!("func({})", args.join(", "));
+
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages(tree.root_node(), &code, &mut used);
+
+            // None of the argument names should be flagged as usages
+            for i in 0..arg_count {
+                let arg_name = format!("arg{}", i);
+                let arg_used = used.iter().any(|(name, _)| name == &arg_name);
+                prop_assert!(!arg_used, "Named argument {} should not be flagged", arg_name);
+            }
+        }
+
+        #[test]
+        fn test_parameter_extraction_completeness(
+            param_count in 1usize..5,
+            has_defaults in prop::collection::vec(any::<bool>(), 1..5)
+        ) {
+            let param_count = param_count.min(has_defaults.len());
+            let mut params = Vec::new();
+
+            for i in 0..param_count {
+                if has_defaults[i] {
+                    params.push(format!("p{} = {}", i, i + 1));
+                } else {
+                    params.push(format!("p{}", i));
+                }
+            }
+
+            let code = format!("f <- function({}) {{}}", params.join(", "));
+            let tree = parse_r_code(&code);
+
+            // Find function definition node
+            let func_node = find_function_definition_node(tree.root_node(), "f", &code).unwrap();
+            let signature = extract_function_signature(func_node, "f", &code);
+
+            // All parameters should be present in signature
+            for i in 0..param_count {
+                let param_name = format!("p{}", i);
+                prop_assert!(signature.contains(&param_name),
+                    "Parameter {} should be in signature: {}", param_name, signature);
+            }
+        }
+
+        #[test]
+        fn test_assignment_operators_recognized(
+            func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            op in prop::sample::select(vec!["<-", "=", "<<-"])
+        ) {
+            let code = format!("{} {} function() {{}}", func_name, op);
+            let tree = parse_r_code(&code);
+
+            let func_def = find_function_definition_node(tree.root_node(), &func_name, &code);
+            prop_assert!(func_def.is_some(), "Function definition should be found for operator {}", op);
+
+            if let Some(node) = func_def {
+                prop_assert_eq!(node.kind(), "function_definition");
+            }
+        }
+
+        #[test]
+        fn test_search_priority(func_name in "[a-z]{3,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))) {
+            use crate::state::{WorldState, Document};
+            use tower_lsp::lsp_types::Url;
+
+            let current_uri = Url::parse("file:///current.R").unwrap();
+            let other_uri = Url::parse("file:///other.R").unwrap();
+            let workspace_uri = Url::parse("file:///workspace.R").unwrap();
+
+            // Create function definitions with different signatures
+            let current_code = format!("{} <- function(a) {{ a }}", func_name);
+            let other_code = format!("{} <- function(b, c) {{ b + c }}", func_name);
+            let workspace_code = format!("{} <- function(x, y, z) {{ x + y + z }}", func_name);
+
+            let mut state = WorldState::new(vec![]);
+            state.documents.insert(current_uri.clone(), Document::new(&current_code, None));
+            state.documents.insert(other_uri.clone(), Document::new(&other_code, None));
+            state.workspace_index.insert(workspace_uri.clone(), Document::new(&workspace_code, None));
+
+            // Search should return current document's definition first
+            let signature = find_user_function_signature(&state, &current_uri, &func_name);
+            prop_assert!(signature.is_some());
+
+            if let Some(sig) = signature {
+                prop_assert!(sig.contains("(a)"), "Should return current document's signature: {}", sig);
+                prop_assert!(!sig.contains("(b, c)"), "Should not return other document's signature");
+                prop_assert!(!sig.contains("(x, y, z)"), "Should not return workspace signature");
+            }
+        }
+    }
+
+    #[test]
+    fn test_extract_definition_statement_variable() {
+        use crate::cross_file::scope::SymbolKind;
+
+        let code = "x <- 42\ny <- x + 1";
+        let tree = parse_r_code(code);
+
+        let symbol = ScopedSymbol {
+            name: "x".to_string(),
+            kind: SymbolKind::Variable,
+            source_uri: Url::parse("file:///test.R").unwrap(),
+            defined_line: 0,
+            defined_column: 0,
+            signature: None,
+        };
+
+        let result = extract_statement_from_tree(&tree, &symbol, code);
+        assert!(result.is_some());
+        let info = result.unwrap();
+        assert_eq!(info.statement, "x <- 42");
+    }
+
+    #[test]
+    fn test_extract_definition_statement_function() {
+        let code = "f <- function(a, b) {\n  a + b\n}";
+        let tree = parse_r_code(code);
+
+        let symbol = ScopedSymbol {
+            name: "f".to_string(),
+            kind: SymbolKind::Function,
+            source_uri: Url::parse("file:///test.R").unwrap(),
+            defined_line: 0,
+            defined_column: 0,
+            signature: Some("f(a, b)".to_string()),
+        };
+
+        let result = extract_statement_from_tree(&tree, &symbol, code);
+        assert!(result.is_some());
+        let info = result.unwrap();
+        assert_eq!(info.statement, "f <- function(a, b) {\n  a + b\n}");
+    }
+
+    #[test]
+    fn test_extract_definition_statement_truncation() {
+        let mut code = "long_func <- function() {\n".to_string();
+        for i in 1..=15 {
+            code.push_str(&format!("  line_{}\n", i));
+        }
+        code.push('}');
+
+        let tree = parse_r_code(&code);
+
+        let symbol = ScopedSymbol {
+            name: "long_func".to_string(),
+            kind: SymbolKind::Function,
+            source_uri: Url::parse("file:///test.R").unwrap(),
+            defined_line: 0,
+            defined_column: 0,
+            signature: None,
+        };
+
+        let result = extract_statement_from_tree(&tree, &symbol, &code);
+        assert!(result.is_some());
+        let info = result.unwrap();
+
+        // Should be truncated to 10 lines with ellipsis
+        let lines: Vec<&str> = info.statement.lines().collect();
+        assert_eq!(lines.len(), 11); // 10 lines + "..."
+        assert_eq!(lines[10], "...");
+    }
+
+    #[test]
+    fn test_extract_definition_statement_assignment_operators() {
+        let test_cases = vec![
+            ("x <- 42", "<-"),
+            ("y = 100", "="),
+            ("z <<- 'global'", "<<-"),
+        ];
+
+        for (code, op) in test_cases {
+            let tree = parse_r_code(code);
+            let var_name = code.split_whitespace().next().unwrap();
+
+            let symbol = ScopedSymbol {
+                name: var_name.to_string(),
+                kind: SymbolKind::Variable,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let result = extract_statement_from_tree(&tree, &symbol, code);
+            assert!(
+                result.is_some(),
+                "Should extract statement for operator {}",
+                op
+            );
+            let info = result.unwrap();
+            assert_eq!(info.statement, code);
+        }
+    }
+
+    #[test]
+    fn test_extract_definition_statement_for_loop_iterator() {
+        let code = "for (i in 1:10) {\n  print(i)\n}";
+        let tree = parse_r_code(code);
+
+        let symbol = ScopedSymbol {
+            name: "i".to_string(),
+            kind: SymbolKind::Variable,
+            source_uri: Url::parse("file:///test.R").unwrap(),
+            defined_line: 0,
+            defined_column: 5, // Position of 'i' in for loop
+            signature: None,
+        };
+
+        let result = extract_statement_from_tree(&tree, &symbol, code);
+        assert!(result.is_some());
+        let info = result.unwrap();
+        assert_eq!(info.statement, "for (i in 1:10) {\n  print(i)\n}");
+    }
+
+    #[test]
+    fn test_readlines_named_arg() {
+        // This is the exact code from collate.r line 13
+        let code = r#"run_hash <- trimws(readLines("output/oos/latest_hash.txt", n = 1))"#;
+        let tree = parse_r_code(code);
+
+        let mut used = Vec::new();
+        collect_usages(tree.root_node(), code, &mut used);
+
+        eprintln!("\n=== Collected usages ===");
+        for (name, node) in &used {
+            eprintln!("  '{}' (kind: {})", name, node.kind());
+        }
+
+        // trimws and readLines should be collected, but n should NOT be
+        let trimws_used = used.iter().any(|(name, _)| name == "trimws");
+        let readlines_used = used.iter().any(|(name, _)| name == "readLines");
+        let n_used = used.iter().any(|(name, _)| name == "n");
+
+        assert!(trimws_used, "trimws should be collected");
+        assert!(readlines_used, "readLines should be collected");
+        assert!(
+            !n_used,
+            "n should NOT be collected as it's a named argument"
+        );
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig {
+            cases: 100,
+            .. ProptestConfig::default()
+        })]
+        #[test]
+        fn test_user_defined_priority_over_builtins(
+            builtin in prop::sample::select(vec!["print", "sum", "mean", "length"])
+        ) {
+            use crate::state::{WorldState, Document};
+            use tower_lsp::lsp_types::Url;
+
+            let uri = Url::parse("file:///test.R").unwrap();
+
+            // Create code with user-defined function that shadows a built-in
+            let code = format!("{} <- function(x, y) {{ x + y }}", builtin);
+
+            let mut state = WorldState::new(vec![]);
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            // Should return user-defined signature, not built-in
+            let signature = find_user_function_signature(&state, &uri, &builtin);
+            prop_assert!(signature.is_some(), "Should find user-defined function");
+
+            if let Some(sig) = signature {
+                prop_assert!(sig.contains("(x, y)"), "Should return user-defined signature: {}", sig);
+                prop_assert!(sig.contains(&builtin), "Should contain function name: {}", sig);
+            }
+        }
+
+        #[test]
+        fn test_signature_format_correctness(
+            func_name in "[a-z][a-z0-9_]{2,10}",
+            param_count in 0usize..5
+        ) {
+            let params: Vec<String> = (0..param_count)
+                .map(|i| format!("p{}", i))
+                .collect();
+
+            let code = format!("{} <- function({}) {{}}", func_name, params.join(", "));
+            let tree = parse_r_code(&code);
+
+            let func_node = find_function_definition_node(tree.root_node(), &func_name, &code).unwrap();
+            let signature = extract_function_signature(func_node, &func_name, &code);
+
+            // Verify format: name(params)
+            prop_assert!(signature.starts_with(&func_name), "Signature should start with function name");
+            prop_assert!(signature.contains('('), "Signature should contain opening parenthesis");
+            prop_assert!(signature.ends_with(')'), "Signature should end with closing parenthesis");
+
+            let expected = format!("{}({})", func_name, params.join(", "));
+            prop_assert_eq!(signature, expected, "Signature format should match expected pattern");
+        }
+
+        #[test]
+        // Feature: enhanced-variable-detection-hover, Property 10: Variable hover definition extraction
+        fn prop_variable_hover_definition_extraction(
+            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            value in 1i32..1000
+        ) {
+            let code = format!("{} <- {}", var_name, value);
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: var_name.clone(),
+                kind: SymbolKind::Variable,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract definition for variable");
+
+            let info = def_info.unwrap();
+            prop_assert_eq!(info.statement, code, "Should include complete definition statement");
+        }
+
+        #[test]
+        // Feature: enhanced-variable-detection-hover, Property 11: Function hover signature extraction
+        fn prop_function_hover_signature_extraction(
+            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            param_count in 0usize..3
+        ) {
+            let params: Vec<String> = (0..param_count)
+                .map(|i| format!("p{}", i))
+                .collect();
+
+            let code = format!("{} <- function({}) {{}}", func_name, params.join(", "));
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: func_name.clone(),
+                kind: SymbolKind::Function,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract definition for function");
+
+            let info = def_info.unwrap();
+            prop_assert!(info.statement.contains(&func_name), "Should include function name");
+            prop_assert!(info.statement.contains("function"), "Should include function keyword");
+
+            for param in &params {
+                prop_assert!(info.statement.contains(param), "Should include parameter {}", param);
+            }
+        }
+
+        #[test]
+        // Feature: enhanced-variable-detection-hover, Property 12: Multi-line definition handling
+        fn prop_multiline_definition_handling(
+            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            line_count in 5usize..15
+        ) {
+            let mut code = format!("{} <- function() {{\n", func_name);
+            for i in 1..line_count {
+                code.push_str(&format!("  line_{}\n", i));
+            }
+            code.push('}');
+
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: func_name.clone(),
+                kind: SymbolKind::Function,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract multi-line definition");
+
+            let info = def_info.unwrap();
+            let lines: Vec<&str> = info.statement.lines().collect();
+
+            // The generated code has (line_count + 1) total lines (header + (line_count-1) body lines + closing brace).
+            // Truncation happens when total lines > 10, i.e. when line_count > 9.
+            if line_count > 9 {
+                prop_assert_eq!(lines.len(), 11, "Should truncate to 10 lines + ellipsis");
+                prop_assert_eq!(lines[10], "...", "Should end with ellipsis when truncated");
+            } else {
+                // The generated code includes the function header line and a closing brace line.
+                let expected_lines = line_count + 1;
+                prop_assert_eq!(lines.len(), expected_lines, "Should include all lines when <= 10");
+                prop_assert!(!info.statement.contains("..."), "Should not have ellipsis when not truncated");
+            }
+        }
+
+        #[test]
+        // Feature: enhanced-variable-detection-hover, Property 13: Markdown code block formatting
+        fn prop_markdown_code_block_formatting(
+            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            special_chars in prop::sample::select(vec!["*", "_", "[", "]", "(", ")", "#", "`", "\\"])
+        ) {
+            let code = format!("{} <- \"value with {} chars\"", var_name, special_chars);
+            let escaped = escape_markdown(&code);
+            let formatted = format!("```r\n{}\n```", escaped);
+
+            prop_assert!(formatted.starts_with("```r\n"), "Should start with R code block marker");
+            prop_assert!(formatted.ends_with("\n```"), "Should end with code block marker");
+            prop_assert!(formatted.contains(&format!("\\{}", special_chars)), "Should escape special markdown characters");
+        }
+
+        #[test]
+        // Feature: enhanced-variable-detection-hover, Property 14: Same-file location format
+        fn prop_same_file_location_format(
+            line_num in 0u32..100
+        ) {
+            let uri = Url::parse("file:///test.R").unwrap();
+            let def_info = DefinitionInfo {
+                statement: "test_var <- 42".to_string(),
+                source_uri: uri.clone(),
+                line: line_num,
+                column: 0,
+                docs: None,
+            };
+
+            let mut value = String::new();
+            value.push_str(&format!("```r\n{}\n```\n\n", escape_markdown(&def_info.statement)));
+
+            if def_info.source_uri == uri {
+                value.push_str(&format!("this file, line {}", def_info.line + 1));
+            }
+
+            prop_assert!(value.contains("this file"), "Should indicate same file");
+            prop_assert!(value.contains(&format!("line {}", line_num + 1)), "Should show 1-based line number");
+            prop_assert!(!value.contains("file://"), "Should not contain file URI for same file");
+        }
+
+        #[test]
+        // Feature: enhanced-variable-detection-hover, Property 15: Cross-file hyperlink format
+        fn prop_cross_file_hyperlink_format(
+            line_num in 0u32..100
+        ) {
+            let current_uri = Url::parse("file:///workspace/main.R").unwrap();
+            let def_uri = Url::parse("file:///workspace/utils/helper.R").unwrap();
+            let workspace_root = Some(Url::parse("file:///workspace/").unwrap());
+
+            let def_info = DefinitionInfo {
+                statement: "helper_func <- function() {}".to_string(),
+                source_uri: def_uri.clone(),
+                line: line_num,
+                column: 0,
+                docs: None,
+            };
+
+            let mut value = String::new();
+            value.push_str(&format!("```r\n{}\n```\n\n", escape_markdown(&def_info.statement)));
+
+            if def_info.source_uri != current_uri {
+                let relative_path = compute_relative_path(&def_info.source_uri, workspace_root.as_ref());
+                let absolute_path = def_info.source_uri.as_str();
+                value.push_str(&format!("[{}]({}), line {}", relative_path, absolute_path, def_info.line + 1));
+            }
+
+            prop_assert!(value.contains("[utils/helper.R]"), "Should show relative path in brackets");
+            prop_assert!(value.contains("(file:///workspace/utils/helper.R)"), "Should show absolute URI in parentheses");
+            prop_assert!(value.contains(&format!("line {}", line_num + 1)), "Should show 1-based line number");
+            prop_assert!(value.contains(", line"), "Should separate path and line with comma");
+        }
+
+        #[test]
+        // Property 21: Definition statement and location separation
+        fn prop_definition_statement_location_separation(
+            statement in "[a-z_]+ <- [a-z0-9_(){}]+",
+            line_num in 0u32..100
+        ) {
+            let def_info = DefinitionInfo {
+                statement: statement.clone(),
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                line: line_num,
+                column: 0,
+                docs: None,
+            };
+
+            let escaped_statement = escape_markdown(&def_info.statement);
+            let mut value = String::new();
+            value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
+            value.push_str(&format!("this file, line {}", def_info.line + 1));
+
+            // Should have exactly one blank line between definition and location
+            prop_assert!(value.contains("```\n\nthis file"), "Should have blank line separator");
+            prop_assert!(!value.contains("```\nthis file"), "Should not have zero blank lines");
+            prop_assert!(!value.contains("```\n\n\nthis file"), "Should not have multiple blank lines");
+        }
+
+        #[test]
+        // Property 22: Definition statement truncation
+        fn prop_definition_statement_truncation(
+            line_count in 11usize..20
+        ) {
+            let mut statement = "long_func <- function() {\n".to_string();
+            for i in 1..line_count {
+                statement.push_str(&format!("  line_{}\n", i));
+            }
+            statement.push('}');
+
+            let tree = parse_r_code(&statement);
+            let symbol = ScopedSymbol {
+                name: "long_func".to_string(),
+                kind: SymbolKind::Function,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &statement);
+            prop_assert!(def_info.is_some(), "Should extract definition");
+
+            let info = def_info.unwrap();
+            let lines: Vec<&str> = info.statement.lines().collect();
+
+            prop_assert_eq!(lines.len(), 11, "Should truncate to 10 lines + ellipsis");
+            prop_assert_eq!(lines[10], "...", "Should end with ellipsis");
+        }
+
+        #[test]
+        // Property 23: Indentation preservation
+        fn prop_indentation_preservation(
+            indent_size in 0usize..8,
+            line_count in 2usize..6
+        ) {
+            let indent = " ".repeat(indent_size);
+            let mut statement = format!("{}func <- function() {{\n", indent);
+            for i in 1..line_count {
+                statement.push_str(&format!("{}  line_{}\n", indent, i));
+            }
+            statement.push_str(&format!("{}}}", indent));
+
+            let tree = parse_r_code(&statement);
+            let symbol = ScopedSymbol {
+                name: "func".to_string(),
+                kind: SymbolKind::Function,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: indent_size as u32,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &statement);
+            prop_assert!(def_info.is_some(), "Should extract definition");
+
+            let info = def_info.unwrap();
+            let lines: Vec<&str> = info.statement.lines().collect();
+
+            // Check that indentation is preserved
+            for line in &lines {
+                if !line.trim().is_empty() {
+                    prop_assert!(line.starts_with(&indent), "Should preserve original indentation: '{}'", line);
+                }
+            }
+        }
+
+        #[test]
+        // Property 24: Markdown character escaping
+        fn prop_markdown_character_escaping(
+            special_char in prop::sample::select(vec!["*", "_", "[", "]", "(", ")", "#", "`", "\\"])
+        ) {
+            let statement = format!("var <- \"value with {} char\"", special_char);
+            let escaped = escape_markdown(&statement);
+
+            let expected_escaped = format!("\\{}", special_char);
+            prop_assert!(escaped.contains(&expected_escaped),
+                "Should escape '{}' to '{}' in: '{}'", special_char, expected_escaped, escaped);
+
+            // Verify it's properly formatted in hover content
+            let hover_content = format!("```r\n{}\n```", escaped);
+            prop_assert!(hover_content.contains(&expected_escaped),
+                "Should contain escaped character in hover content");
+        }
+
+        #[test]
+        // Property 28: Assignment operator extraction
+        fn prop_assignment_operator_extraction(
+            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            op in prop::sample::select(vec!["<-", "=", "<<-"]),
+            value in 1i32..1000
+        ) {
+            let code = format!("{} {} {}", var_name, op, value);
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: var_name.clone(),
+                kind: SymbolKind::Variable,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract assignment statement");
+
+            let info = def_info.unwrap();
+            let statement = &info.statement;
+            prop_assert_eq!(statement, &code, "Should include complete assignment statement");
+            prop_assert!(statement.contains(&op), "Should include assignment operator {}", op);
+        }
+
+        #[test]
+        // Property 29: Inline function extraction
+        fn prop_inline_function_extraction(
+            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            param_count in 0usize..3
+        ) {
+            let params: Vec<String> = (0..param_count)
+                .map(|i| format!("p{}", i))
+                .collect();
+
+            let code = format!("{} <- function({}) {{ {} }}", func_name, params.join(", "), "x + 1");
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: func_name.clone(),
+                kind: SymbolKind::Function,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 0,
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract function definition");
+
+            let info = def_info.unwrap();
+            prop_assert!(info.statement.contains("function"), "Should include function keyword");
+            prop_assert!(info.statement.contains(&format!("({})", params.join(", "))), "Should include function signature");
+        }
+
+        #[test]
+        // Property 30: Loop iterator definition extraction
+        fn prop_loop_iterator_definition_extraction(
+            iterator in "[a-z][a-z0-9_]{1,5}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            range_end in 5i32..20
+        ) {
+            let code = format!("for ({} in 1:{}) {{\n  print({})\n}}", iterator, range_end, iterator);
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: iterator.clone(),
+                kind: SymbolKind::Variable,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: 5, // Position of iterator in for loop
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract for loop definition");
+
+            let info = def_info.unwrap();
+            prop_assert!(info.statement.contains("for"), "Should include for loop header");
+            prop_assert!(info.statement.contains(&format!("{} in", iterator)), "Should include iterator definition");
+        }
+
+        #[test]
+        // Property 31: Function parameter definition extraction
+        fn prop_function_parameter_definition_extraction(
+            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            param_name in "[a-z][a-z0-9_]{1,5}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            has_default in any::<bool>()
+        ) {
+            let param_def = if has_default {
+                format!("{} = 42", param_name)
+            } else {
+                param_name.clone()
+            };
+
+            let code = format!("{} <- function({}) {{\n  {}\n}}", func_name, param_def, param_name);
+            let tree = parse_r_code(&code);
+
+            let symbol = ScopedSymbol {
+                name: param_name.clone(),
+                kind: SymbolKind::Variable,
+                source_uri: Url::parse("file:///test.R").unwrap(),
+                defined_line: 0,
+                defined_column: func_name.len() as u32 + 15, // Approximate position in function signature
+                signature: None,
+            };
+
+            let def_info = extract_statement_from_tree(&tree, &symbol, &code);
+            prop_assert!(def_info.is_some(), "Should extract function definition for parameter");
+
+            let info = def_info.unwrap();
+            prop_assert!(info.statement.contains("function"), "Should include function keyword");
+            prop_assert!(info.statement.contains(&param_name), "Should include parameter name in signature");
+        }
+
+        #[test]
+        // Property 16: File URI protocol
+        fn prop_file_uri_protocol(
+            path_segments in prop::collection::vec("[a-z]{3,8}", 1..4)
+        ) {
+            let path = format!("/{}", path_segments.join("/"));
+            let uri = Url::parse(&format!("file://{}/test.R", path)).unwrap();
+
+            let def_info = DefinitionInfo {
+                statement: "test_var <- 42".to_string(),
+                source_uri: uri.clone(),
+                line: 0,
+                column: 0,
+                docs: None,
+            };
+
+            let current_uri = Url::parse("file:///workspace/main.R").unwrap();
+            let mut value = String::new();
+            value.push_str(&format!("```r\n{}\n```\n\n", escape_markdown(&def_info.statement)));
+
+            if def_info.source_uri != current_uri {
+                let relative_path = compute_relative_path(&def_info.source_uri, None);
+                let absolute_path = def_info.source_uri.as_str();
+                value.push_str(&format!("[{}]({}), line {}", relative_path, absolute_path, def_info.line + 1));
+            }
+
+            prop_assert!(value.contains("file://"), "Cross-file URI should use file:// protocol");
+            prop_assert!(value.contains(&format!("file://{}/test.R", path)), "Should contain absolute path with file:// protocol");
+        }
+
+        #[test]
+        // Property 17: Relative path calculation
+        fn prop_relative_path_calculation(
+            workspace_depth in 1usize..3,
+            file_depth in 1usize..3
+        ) {
+            let workspace_segments: Vec<String> = (0..workspace_depth).map(|i| format!("ws{}", i)).collect();
+            let file_segments: Vec<String> = (0..file_depth).map(|i| format!("dir{}", i)).collect();
+
+            let workspace_root = Url::parse(&format!("file:///{}/", workspace_segments.join("/"))).unwrap();
+            let target_uri = Url::parse(&format!("file:///{}/{}/test.R", workspace_segments.join("/"), file_segments.join("/"))).unwrap();
+
+            let relative_path = compute_relative_path(&target_uri, Some(&workspace_root));
+
+            prop_assert!(relative_path.contains(&file_segments.join("/")), "Should contain file path relative to workspace");
+            prop_assert!(!relative_path.starts_with('/'), "Relative path should not start with /");
+            prop_assert!(relative_path.ends_with("test.R"), "Should end with filename");
+        }
+
+        #[test]
+        // Property 18: LSP Markdown markup kind
+        fn prop_lsp_markdown_markup_kind(
+            var_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            use crate::state::{WorldState, Document};
+
+            let library_paths = vec![];
+            let mut state = WorldState::new(library_paths);
+
+            let uri = Url::parse("file:///test.R").unwrap();
+            let code = format!("{} <- 42", var_name);
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            let position = Position::new(0, 5);
+            let hover_result = hover_blocking(&state, &uri, position);
+
+            if let Some(hover) = hover_result {
+                if let HoverContents::Markup(content) = hover.contents {
+                    prop_assert_eq!(content.kind, MarkupKind::Markdown, "Hover content should use Markdown markup kind");
+                } else {
+                    prop_assert!(false, "Hover should return Markup content");
+                }
+            }
+        }
+
+        #[test]
+        // Property 19: Cross-file definition resolution
+        fn prop_cross_file_definition_resolution(
+            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            use crate::state::{WorldState, Document};
+
+            let library_paths = vec![];
+            let mut state = WorldState::new(library_paths);
+
+            let main_uri = Url::parse("file:///main.R").unwrap();
+            let utils_uri = Url::parse("file:///utils.R").unwrap();
+
+            let main_code = format!("source(\"utils.R\")\nresult <- {}(42)", func_name);
+            let utils_code = format!("{} <- function(x) {{ x * 2 }}", func_name);
+
+            state.documents.insert(main_uri.clone(), Document::new(&main_code, None));
+            state.documents.insert(utils_uri.clone(), Document::new(&utils_code, None));
+
+            // Update cross-file graph
+            state.cross_file_graph.update_file(&main_uri, &crate::cross_file::extract_metadata(&main_code), None, |_| None);
+            state.cross_file_graph.update_file(&utils_uri, &crate::cross_file::extract_metadata(&utils_code), None, |_| None);
+
+            let position = Position::new(1, 10); // Position after source() call
+            let cross_file_symbols = get_cross_file_symbols(&state, &main_uri, position.line, position.character);
+
+            prop_assert!(cross_file_symbols.contains_key(&func_name), "Should resolve cross-file symbol using dependency graph");
+
+            if let Some(symbol) = cross_file_symbols.get(&func_name) {
+                prop_assert_eq!(&symbol.source_uri, &utils_uri, "Should locate definition in sourced file");
+            }
+        }
+
+        #[test]
+        // Property 20: Scope-based definition selection
+        fn prop_scope_based_definition_selection(
+            func_name in "[a-z][a-z0-9_]{2,10}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            use crate::state::{WorldState, Document};
+
+            let library_paths = vec![];
+            let mut state = WorldState::new(library_paths);
+
+            let uri = Url::parse("file:///test.R").unwrap();
+            let code = format!(
+                "{} <- function(a) {{ a }}\nsource(\"utils.R\")\n{} <- function(b, c) {{ b + c }}\nresult <- {}(1, 2)",
+                func_name, func_name, func_name
+            );
+
+            let utils_uri = Url::parse("file:///utils.R").unwrap();
+            let utils_code = format!("{} <- function(x, y, z) {{ x + y + z }}", func_name);
+
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+            state.documents.insert(utils_uri.clone(), Document::new(&utils_code, None));
+
+            // Update cross-file graph
+            state.cross_file_graph.update_file(&uri, &crate::cross_file::extract_metadata(&code), None, |_| None);
+            state.cross_file_graph.update_file(&utils_uri, &crate::cross_file::extract_metadata(&utils_code), None, |_| None);
+
+            let position = Position::new(3, 10); // Position of function usage
+            let cross_file_symbols = get_cross_file_symbols(&state, &uri, position.line, position.character);
+
+            prop_assert!(cross_file_symbols.contains_key(&func_name), "Should find symbol definition");
+
+            if let Some(symbol) = cross_file_symbols.get(&func_name) {
+                // Should select the local definition (line 2) that's in scope, not the earlier one or utils.R
+                prop_assert_eq!(&symbol.source_uri, &uri, "Should select definition from same file");
+                prop_assert_eq!(symbol.defined_line, 2, "Should select the definition that's in scope at reference position");
+            }
+        }
+
+        // ========================================================================
+        // Feature: skip-nse-undefined-checks
+        // Property-based tests for NSE context skipping in undefined variable checks
+        // ========================================================================
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 1: Extract Operator RHS Skipped
+        /// For any R code containing an extract operator ($ or @), the identifier on the
+        /// right-hand side SHALL NOT be collected as a usage.
+        fn prop_skip_nse_extract_operator_rhs_skipped(
+            lhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            rhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            op in prop::sample::select(vec!["$", "@"])
+        ) {
+            let code = format!("{}{}{}", lhs, op, rhs);
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let rhs_used = used.iter().any(|(name, _)| name == &rhs);
+            prop_assert!(!rhs_used, "RHS '{}' of extract operator should NOT be collected", rhs);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 2: Extract Operator LHS Checked
+        /// For any R code containing an extract operator ($ or @), the identifier on the
+        /// left-hand side SHALL be collected as a usage.
+        fn prop_skip_nse_extract_operator_lhs_checked(
+            lhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            rhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            op in prop::sample::select(vec!["$", "@"])
+        ) {
+            let code = format!("{}{}{}", lhs, op, rhs);
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let lhs_used = used.iter().any(|(name, _)| name == &lhs);
+            prop_assert!(lhs_used, "LHS '{}' of extract operator should be collected", lhs);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 3: Call-Like Arguments Skipped
+        /// For any R code containing a call-like node (call, subset, subset2), identifiers
+        /// inside the arguments field SHALL NOT be collected as usages.
+        fn prop_skip_nse_call_like_arguments_skipped(
+            func in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            arg in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            call_type in prop::sample::select(vec!["call", "subset", "subset2"])
+        ) {
+            let code = match call_type {
+                "call" => format!("{}({})", func, arg),
+                "subset" => format!("{}[{}]", func, arg),
+                "subset2" => format!("{}[[{}]]", func, arg),
+                _ => unreachable!(),
+            };
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let arg_used = used.iter().any(|(name, _)| name == &arg);
+            prop_assert!(!arg_used, "Argument '{}' inside {} should NOT be collected", arg, call_type);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 4: Function Names Checked
+        /// For any R code containing a function call, the function name SHALL be collected
+        /// as a usage.
+        fn prop_skip_nse_function_names_checked(
+            func in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            arg in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            let code = format!("{}({})", func, arg);
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let func_used = used.iter().any(|(name, _)| name == &func);
+            prop_assert!(func_used, "Function name '{}' should be collected", func);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 5: Formula Expressions Skipped
+        /// For any R code containing a formula expression (unary ~ or binary ~), identifiers
+        /// inside the formula SHALL NOT be collected as usages.
+        fn prop_skip_nse_formula_expressions_skipped(
+            var in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            formula_type in prop::sample::select(vec!["unary", "binary"])
+        ) {
+            let code = match formula_type {
+                "unary" => format!("~ {}", var),
+                "binary" => format!("y ~ {}", var),
+                _ => unreachable!(),
+            };
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let var_used = used.iter().any(|(name, _)| name == &var);
+            prop_assert!(!var_used, "Variable '{}' inside {} formula should NOT be collected", var, formula_type);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 6: Nested Skip Contexts
+        /// For any R code where a formula appears inside call arguments, identifiers in the
+        /// formula SHALL NOT be collected (both skip contexts apply).
+        fn prop_skip_nse_nested_formula_in_call(
+            func in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            lhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            rhs in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            let code = format!("{}({} ~ {})", func, lhs, rhs);
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let lhs_used = used.iter().any(|(name, _)| name == &lhs);
+            let rhs_used = used.iter().any(|(name, _)| name == &rhs);
+            prop_assert!(!lhs_used, "Formula LHS '{}' inside call should NOT be collected", lhs);
+            prop_assert!(!rhs_used, "Formula RHS '{}' inside call should NOT be collected", rhs);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 7: Existing Skip Rules Preserved
+        /// For any R code containing assignments or named arguments, the existing skip rules
+        /// SHALL continue to work (assignment LHS and named argument names are skipped).
+        fn prop_skip_nse_existing_rules_preserved(
+            var in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            op in prop::sample::select(vec!["<-", "=", "<<-"]),
+            arg_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Test assignment LHS
+            let assign_code = format!("{} {} 42", var, op);
+            let tree = parse_r_code(&assign_code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &assign_code, &UsageContext::default(), &mut used);
+            let var_used = used.iter().any(|(name, _)| name == &var);
+            prop_assert!(!var_used, "Assignment LHS '{}' with '{}' should NOT be collected", var, op);
+
+            // Test named argument
+            let named_arg_code = format!("func({} = 1)", arg_name);
+            let tree2 = parse_r_code(&named_arg_code);
+            let mut used2 = Vec::new();
+            collect_usages_with_context(tree2.root_node(), &named_arg_code, &UsageContext::default(), &mut used2);
+            let arg_used = used2.iter().any(|(name, _)| name == &arg_name);
+            prop_assert!(!arg_used, "Named argument '{}' should NOT be collected", arg_name);
+        }
+
+        #[test]
+        /// Feature: skip-nse-undefined-checks, Property 8: Non-Skipped Contexts Checked
+        /// For any R code containing an identifier NOT in a skip context, the identifier
+        /// SHALL be collected as a usage.
+        fn prop_skip_nse_non_skipped_contexts_checked(
+            var in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Standalone identifier (not in any skip context)
+            let code = var.clone();
+            let tree = parse_r_code(&code);
+            let mut used = Vec::new();
+            collect_usages_with_context(tree.root_node(), &code, &UsageContext::default(), &mut used);
+
+            let var_used = used.iter().any(|(name, _)| name == &var);
+            prop_assert!(var_used, "Standalone identifier '{}' should be collected", var);
+        }
+
+        // ========================================================================
+        // **Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion**
+        // **Validates: Requirements 3.1, 3.2, 3.3**
+        //
+        // For any R code containing a reserved word used as an identifier (in any
+        // syntactic position), the undefined variable checker SHALL NOT emit an
+        // "Undefined variable" diagnostic for that reserved word.
+        // ========================================================================
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion
+        ///
+        /// For any R code containing a reserved word used as an identifier (in any
+        /// syntactic position), the undefined variable checker SHALL NOT emit an
+        /// "Undefined variable" diagnostic for that reserved word.
+        ///
+        /// **Validates: Requirements 3.1, 3.2, 3.3**
+        fn prop_reserved_words_not_flagged_as_undefined_standalone(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
+        ) {
+            use crate::state::{WorldState, Document};
+            use crate::cross_file::directive::parse_directives;
+
+            // Create code with just the reserved word as a standalone identifier
+            let code = reserved_word.to_string();
+            let tree = parse_r_code(&code);
+
+            let mut state = WorldState::new(vec![]);
+            state.cross_file_config.undefined_variables_enabled = true;
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            let directive_meta = parse_directives(&code);
+            let mut diagnostics = Vec::new();
+
+            collect_undefined_variables_position_aware(
+                &state,
+                &uri,
+                tree.root_node(),
+                &code,
+                &[],
+                &[],
+                &state.package_library,
+                &directive_meta,
+                &mut diagnostics,
+            );
+
+            // Filter for "Undefined variable" diagnostics for this reserved word
+            let undefined_diags: Vec<_> = diagnostics
+                .iter()
+                .filter(|d| d.message.contains(&format!("Undefined variable: {}", reserved_word)))
+                .collect();
+
+            prop_assert!(
+                undefined_diags.is_empty(),
+                "Reserved word '{}' should NOT produce 'Undefined variable' diagnostic, but got: {:?}",
+                reserved_word,
+                undefined_diags
+            );
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion
+        ///
+        /// For any R code containing a reserved word used in an expression context,
+        /// the undefined variable checker SHALL NOT emit an "Undefined variable"
+        /// diagnostic for that reserved word.
+        ///
+        /// **Validates: Requirements 3.1, 3.2, 3.3**
+        fn prop_reserved_words_not_flagged_as_undefined_in_expression(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS),
+            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            use crate::state::{WorldState, Document};
+            use crate::cross_file::directive::parse_directives;
+
+            // Create code with reserved word used in an expression (e.g., x <- else)
+            // This is syntactically invalid R, but the undefined variable checker
+            // should still not flag the reserved word as undefined
+            let code = format!("{} <- {}", var_name, reserved_word);
+            let tree = parse_r_code(&code);
+
+            let mut state = WorldState::new(vec![]);
+            state.cross_file_config.undefined_variables_enabled = true;
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            let directive_meta = parse_directives(&code);
+            let mut diagnostics = Vec::new();
+
+            collect_undefined_variables_position_aware(
+                &state,
+                &uri,
+                tree.root_node(),
+                &code,
+                &[],
+                &[],
+                &state.package_library,
+                &directive_meta,
+                &mut diagnostics,
+            );
+
+            // Filter for "Undefined variable" diagnostics for this reserved word
+            let undefined_diags: Vec<_> = diagnostics
+                .iter()
+                .filter(|d| d.message.contains(&format!("Undefined variable: {}", reserved_word)))
+                .collect();
+
+            prop_assert!(
+                undefined_diags.is_empty(),
+                "Reserved word '{}' in expression should NOT produce 'Undefined variable' diagnostic, but got: {:?}",
+                reserved_word,
+                undefined_diags
+            );
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion
+        ///
+        /// For any R code containing a reserved word used in a function call context,
+        /// the undefined variable checker SHALL NOT emit an "Undefined variable"
+        /// diagnostic for that reserved word.
+        ///
+        /// **Validates: Requirements 3.1, 3.2, 3.3**
+        fn prop_reserved_words_not_flagged_as_undefined_in_call(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
+        ) {
+            use crate::state::{WorldState, Document};
+            use crate::cross_file::directive::parse_directives;
+
+            // Create code with reserved word used as a function argument
+            // e.g., print(else) - syntactically invalid but tests the checker
+            let code = format!("print({})", reserved_word);
+            let tree = parse_r_code(&code);
+
+            let mut state = WorldState::new(vec![]);
+            state.cross_file_config.undefined_variables_enabled = true;
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            let directive_meta = parse_directives(&code);
+            let mut diagnostics = Vec::new();
+
+            collect_undefined_variables_position_aware(
+                &state,
+                &uri,
+                tree.root_node(),
+                &code,
+                &[],
+                &[],
+                &state.package_library,
+                &directive_meta,
+                &mut diagnostics,
+            );
+
+            // Filter for "Undefined variable" diagnostics for this reserved word
+            let undefined_diags: Vec<_> = diagnostics
+                .iter()
+                .filter(|d| d.message.contains(&format!("Undefined variable: {}", reserved_word)))
+                .collect();
+
+            prop_assert!(
+                undefined_diags.is_empty(),
+                "Reserved word '{}' in function call should NOT produce 'Undefined variable' diagnostic, but got: {:?}",
+                reserved_word,
+                undefined_diags
+            );
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 3: Undefined Variable Check Exclusion (Negative Control)
+        ///
+        /// For any R code containing a non-reserved identifier that is not defined,
+        /// the undefined variable checker SHALL emit an "Undefined variable" diagnostic.
+        /// This is a negative control to ensure the checker is working correctly.
+        ///
+        /// **Validates: Requirements 3.1, 3.2, 3.3**
+        fn prop_non_reserved_undefined_vars_are_flagged(
+            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            use crate::state::{WorldState, Document};
+            use crate::cross_file::directive::parse_directives;
+
+            // Create code with just the non-reserved identifier (undefined)
+            let code = var_name.clone();
+            let tree = parse_r_code(&code);
+
+            let mut state = WorldState::new(vec![]);
+            state.cross_file_config.undefined_variables_enabled = true;
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            let directive_meta = parse_directives(&code);
+            let mut diagnostics = Vec::new();
+
+            collect_undefined_variables_position_aware(
+                &state,
+                &uri,
+                tree.root_node(),
+                &code,
+                &[],
+                &[],
+                &state.package_library,
+                &directive_meta,
+                &mut diagnostics,
+            );
+
+            // Filter for "Undefined variable" diagnostics for this variable
+            let undefined_diags: Vec<_> = diagnostics
+                .iter()
+                .filter(|d| d.message.contains(&format!("Undefined variable: {}", var_name)))
+                .collect();
+
+            prop_assert!(
+                !undefined_diags.is_empty(),
+                "Non-reserved undefined variable '{}' SHOULD produce 'Undefined variable' diagnostic",
+                var_name
+            );
+        }
+
+        // ========================================================================
+        // **Feature: reserved-keyword-handling, Property 4: Completion Exclusion**
+        // **Validates: Requirements 5.1, 5.2, 5.3**
+        //
+        // For any completion request that aggregates identifiers from document, scope,
+        // workspace index, or package sources, the completion provider SHALL NOT include
+        // reserved words in the identifier completion list. Keyword completions (with
+        // CompletionItemKind::KEYWORD) may still include reserved words.
+        // ========================================================================
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 4: Completion Exclusion
+        ///
+        /// For any R code containing an assignment to a reserved word, the completion
+        /// provider SHALL NOT include that reserved word as an identifier completion
+        /// (FUNCTION or VARIABLE kind). Reserved words MAY still appear as keyword
+        /// completions (KEYWORD kind).
+        ///
+        /// **Validates: Requirements 5.1, 5.2, 5.3**
+        fn prop_reserved_words_not_in_identifier_completions(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
+        ) {
+            use crate::state::{WorldState, Document};
+
+            // Create code with assignment to reserved word (e.g., "else <- 1")
+            // This is syntactically invalid R, but tests that even if such code exists,
+            // the completion provider won't suggest the reserved word as an identifier
+            let code = format!("{} <- 1", reserved_word);
+
+            let mut state = WorldState::new(vec![]);
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            // Request completions at the end of the document
+            let position = Position::new(0, code.len() as u32);
+            let response = completion(&state, &uri, position);
+
+            prop_assert!(response.is_some(), "Completion should return a response");
+
+            if let Some(CompletionResponse::Array(items)) = response {
+                // Check that reserved word does NOT appear as identifier completion
+                let identifier_completions: Vec<_> = items
+                    .iter()
+                    .filter(|item| {
+                        item.label == reserved_word
+                            && matches!(
+                                item.kind,
+                                Some(CompletionItemKind::FUNCTION) | Some(CompletionItemKind::VARIABLE)
+                            )
+                    })
+                    .collect();
+
+                prop_assert!(
+                    identifier_completions.is_empty(),
+                    "Reserved word '{}' should NOT appear as identifier completion (FUNCTION/VARIABLE), but found: {:?}",
+                    reserved_word,
+                    identifier_completions
+                );
+
+                // Verify reserved word DOES appear as keyword completion (positive control)
+                let keyword_completions: Vec<_> = items
+                    .iter()
+                    .filter(|item| {
+                        item.label == reserved_word && item.kind == Some(CompletionItemKind::KEYWORD)
+                    })
+                    .collect();
+
+                prop_assert!(
+                    !keyword_completions.is_empty(),
+                    "Reserved word '{}' SHOULD appear as keyword completion (KEYWORD kind)",
+                    reserved_word
+                );
+            }
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 4: Completion Exclusion
+        ///
+        /// For any R code containing a function definition with a reserved word name,
+        /// the completion provider SHALL NOT include that reserved word as a function
+        /// completion. Reserved words MAY still appear as keyword completions.
+        ///
+        /// **Validates: Requirements 5.1, 5.2, 5.3**
+        fn prop_reserved_words_not_in_function_completions(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
+        ) {
+            use crate::state::{WorldState, Document};
+
+            // Create code with function definition using reserved word name
+            // (e.g., "if <- function() {}")
+            let code = format!("{} <- function() {{}}", reserved_word);
+
+            let mut state = WorldState::new(vec![]);
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            // Request completions at the end of the document
+            let position = Position::new(0, code.len() as u32);
+            let response = completion(&state, &uri, position);
+
+            prop_assert!(response.is_some(), "Completion should return a response");
+
+            if let Some(CompletionResponse::Array(items)) = response {
+                // Check that reserved word does NOT appear as function completion
+                let function_completions: Vec<_> = items
+                    .iter()
+                    .filter(|item| {
+                        item.label == reserved_word && item.kind == Some(CompletionItemKind::FUNCTION)
+                    })
+                    .collect();
+
+                prop_assert!(
+                    function_completions.is_empty(),
+                    "Reserved word '{}' should NOT appear as function completion, but found: {:?}",
+                    reserved_word,
+                    function_completions
+                );
+            }
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 4: Completion Exclusion (Negative Control)
+        ///
+        /// For any R code containing an assignment to a non-reserved identifier,
+        /// the completion provider SHALL include that identifier as a completion.
+        /// This is a negative control to ensure the completion provider is working correctly.
+        ///
+        /// **Validates: Requirements 5.1, 5.2, 5.3**
+        fn prop_non_reserved_identifiers_in_completions(
+            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            use crate::state::{WorldState, Document};
+
+            // Create code with assignment to non-reserved identifier
+            let code = format!("{} <- 1", var_name);
+
+            let mut state = WorldState::new(vec![]);
+            let uri = Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), Document::new(&code, None));
+
+            // Request completions at the end of the document
+            let position = Position::new(0, code.len() as u32);
+            let response = completion(&state, &uri, position);
+
+            prop_assert!(response.is_some(), "Completion should return a response");
+
+            if let Some(CompletionResponse::Array(items)) = response {
+                // Check that non-reserved identifier DOES appear as completion
+                let var_completions: Vec<_> = items
+                    .iter()
+                    .filter(|item| item.label == var_name)
+                    .collect();
+
+                prop_assert!(
+                    !var_completions.is_empty(),
+                    "Non-reserved identifier '{}' SHOULD appear in completions",
+                    var_name
+                );
+            }
+        }
+
+        // ========================================================================
+        // **Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion**
+        // **Validates: Requirements 6.1, 6.2**
+        //
+        // For any document symbol collection where a candidate symbol name is a
+        // reserved word, the provider SHALL NOT include it in the emitted symbol list.
+        // ========================================================================
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion
+        ///
+        /// For any R code containing an assignment to a reserved word (e.g., `else <- 1`),
+        /// the document symbol provider SHALL NOT include that reserved word in the
+        /// emitted symbol list.
+        ///
+        /// **Validates: Requirements 6.1, 6.2**
+        fn prop_reserved_words_not_in_document_symbols(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
+        ) {
+            // Create code with assignment to reserved word (e.g., "else <- 1")
+            // This is syntactically invalid R, but tests that even if such code exists,
+            // the document symbol provider won't include the reserved word as a symbol
+            let code = format!("{} <- 1", reserved_word);
+            let tree = parse_r_code(&code);
+
+            let mut symbols = Vec::new();
+            collect_symbols(tree.root_node(), &code, &mut symbols);
+
+            // Check that reserved word does NOT appear in document symbols
+            let reserved_symbols: Vec<_> = symbols
+                .iter()
+                .filter(|sym| sym.name == reserved_word)
+                .collect();
+
+            prop_assert!(
+                reserved_symbols.is_empty(),
+                "Reserved word '{}' should NOT appear in document symbols, but found: {:?}",
+                reserved_word,
+                reserved_symbols.iter().map(|s| &s.name).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion
+        ///
+        /// For any R code containing a function definition with a reserved word name
+        /// (e.g., `if <- function() {}`), the document symbol provider SHALL NOT
+        /// include that reserved word in the emitted symbol list.
+        ///
+        /// **Validates: Requirements 6.1, 6.2**
+        fn prop_reserved_words_not_in_document_symbols_function(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS)
+        ) {
+            // Create code with function definition using reserved word name
+            // (e.g., "if <- function() {}")
+            let code = format!("{} <- function() {{}}", reserved_word);
+            let tree = parse_r_code(&code);
+
+            let mut symbols = Vec::new();
+            collect_symbols(tree.root_node(), &code, &mut symbols);
+
+            // Check that reserved word does NOT appear in document symbols
+            let reserved_symbols: Vec<_> = symbols
+                .iter()
+                .filter(|sym| sym.name == reserved_word)
+                .collect();
+
+            prop_assert!(
+                reserved_symbols.is_empty(),
+                "Reserved word '{}' should NOT appear in document symbols (function), but found: {:?}",
+                reserved_word,
+                reserved_symbols.iter().map(|s| &s.name).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion (Negative Control)
+        ///
+        /// For any R code containing an assignment to a non-reserved identifier,
+        /// the document symbol provider SHALL include that identifier in the symbol list.
+        /// This is a negative control to ensure the document symbol provider is working correctly.
+        ///
+        /// **Validates: Requirements 6.1, 6.2**
+        fn prop_non_reserved_identifiers_in_document_symbols(
+            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Create code with assignment to non-reserved identifier
+            let code = format!("{} <- 1", var_name);
+            let tree = parse_r_code(&code);
+
+            let mut symbols = Vec::new();
+            collect_symbols(tree.root_node(), &code, &mut symbols);
+
+            // Check that non-reserved identifier DOES appear in document symbols
+            let var_symbols: Vec<_> = symbols
+                .iter()
+                .filter(|sym| sym.name == var_name)
+                .collect();
+
+            prop_assert!(
+                !var_symbols.is_empty(),
+                "Non-reserved identifier '{}' SHOULD appear in document symbols",
+                var_name
+            );
+        }
+
+        #[test]
+        /// Feature: reserved-keyword-handling, Property 5: Document Symbol Exclusion
+        ///
+        /// For any R code containing multiple assignments where some are to reserved words
+        /// and some are to non-reserved identifiers, the document symbol provider SHALL
+        /// include only the non-reserved identifiers in the symbol list.
+        ///
+        /// **Validates: Requirements 6.1, 6.2**
+        fn prop_mixed_reserved_and_non_reserved_document_symbols(
+            reserved_word in prop::sample::select(crate::reserved_words::RESERVED_WORDS),
+            var_name in "[a-z][a-z0-9_]{2,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Create code with both reserved and non-reserved assignments
+            let code = format!("{} <- 1\n{} <- 2", reserved_word, var_name);
+            let tree = parse_r_code(&code);
+
+            let mut symbols = Vec::new();
+            collect_symbols(tree.root_node(), &code, &mut symbols);
+
+            // Check that reserved word does NOT appear in document symbols
+            let reserved_symbols: Vec<_> = symbols
+                .iter()
+                .filter(|sym| sym.name == reserved_word)
+                .collect();
+
+            prop_assert!(
+                reserved_symbols.is_empty(),
+                "Reserved word '{}' should NOT appear in document symbols",
+                reserved_word
+            );
+
+            // Check that non-reserved identifier DOES appear in document symbols
+            let var_symbols: Vec<_> = symbols
+                .iter()
+                .filter(|sym| sym.name == var_name)
+                .collect();
+
+            prop_assert!(
+                !var_symbols.is_empty(),
+                "Non-reserved identifier '{}' SHOULD appear in document symbols",
+                var_name
+            );
+        }
+
+        // ========================================================================
+        // **Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection**
+        // **Validates: Requirements 1.1, 2.1, 2.2**
+        //
+        // For any R code where an `else` keyword starts on a different line than
+        // the closing `}` of the preceding `if` block, the detector SHALL emit
+        // exactly one diagnostic for that `else`.
+        // ========================================================================
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection
+        ///
+        /// For any R code where an `else` keyword starts on a different line than
+        /// the closing `}` of the preceding `if` block, the detector SHALL emit
+        /// exactly one diagnostic for that `else`.
+        ///
+        /// **Validates: Requirements 1.1, 2.1, 2.2**
+        fn prop_orphaned_else_detection(
+            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            blank_lines in 0usize..3
+        ) {
+            // Generate code with else on a new line after closing brace
+            // Pattern: if (condition) {body1}\n[blank_lines]\nelse {body2}
+            let newlines = "\n".repeat(blank_lines + 1);
+            let code = format!("if ({}) {{{}}}{newlines}else {{{}}}", condition, body1, body2);
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should emit exactly one diagnostic for the orphaned else
+            prop_assert_eq!(
+                diagnostics.len(),
+                1,
+                "Should emit exactly one diagnostic for orphaned else on new line. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+
+            // Verify diagnostic severity is ERROR
+            prop_assert_eq!(
+                diagnostics[0].severity,
+                Some(DiagnosticSeverity::ERROR),
+                "Diagnostic severity should be ERROR"
+            );
+
+            // Verify diagnostic code identifies the orphaned-else rule
+            prop_assert_eq!(
+                diagnostics[0].code.clone(),
+                Some(NumberOrString::String(
+                    diagnostic_codes::ELSE_ON_NEW_LINE.to_string()
+                )),
+                "Diagnostic code should identify the orphaned-else rule"
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection (Multi-line if block)
+        ///
+        /// For any R code with a multi-line if block where `else` appears on a new line
+        /// after the closing `}`, the detector SHALL emit exactly one diagnostic.
+        ///
+        /// **Validates: Requirements 1.1, 2.1, 2.2**
+        fn prop_orphaned_else_detection_multiline_if(
+            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body_lines in 1usize..4,
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate multi-line if block with else on new line
+            // Pattern: if (condition) {\n  body_line1\n  body_line2\n}\nelse {body2}
+            let body_content: String = (0..body_lines)
+                .map(|i| format!("  line{}", i))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let code = format!(
+                "if ({}) {{\n{}\n}}\nelse {{{}}}",
+                condition, body_content, body2
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should emit exactly one diagnostic for the orphaned else
+            prop_assert_eq!(
+                diagnostics.len(),
+                1,
+                "Should emit exactly one diagnostic for orphaned else after multi-line if block. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+
+            // Verify diagnostic severity is ERROR
+            prop_assert_eq!(
+                diagnostics[0].severity,
+                Some(DiagnosticSeverity::ERROR),
+                "Diagnostic severity should be ERROR"
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 1: Orphaned Else Detection (else if pattern)
+        ///
+        /// For any R code where `else if` appears on a new line after the closing `}`,
+        /// the detector SHALL emit exactly one diagnostic for the orphaned `else`.
+        ///
+        /// **Validates: Requirements 1.1, 2.1, 2.2**
+        fn prop_orphaned_else_if_detection(
+            cond1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            cond2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate code with else if on a new line
+            // Pattern: if (cond1) {body1}\nelse if (cond2) {body2}
+            let code = format!(
+                "if ({}) {{{}}}\nelse if ({}) {{{}}}",
+                cond1, body1, cond2, body2
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should emit exactly one diagnostic for the orphaned else
+            prop_assert_eq!(
+                diagnostics.len(),
+                1,
+                "Should emit exactly one diagnostic for orphaned 'else if' on new line. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+
+            // Verify diagnostic severity is ERROR
+            prop_assert_eq!(
+                diagnostics[0].severity,
+                Some(DiagnosticSeverity::ERROR),
+                "Diagnostic severity should be ERROR"
+            );
+        }
+
+        // ========================================================================
+        // **Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic**
+        // **Validates: Requirements 1.2, 1.3, 2.3, 2.4**
+        //
+        // For any R code where an `else` keyword appears on the same line as the
+        // closing `}` of the preceding `if` block, the detector SHALL NOT emit
+        // a diagnostic for that `else`.
+        // ========================================================================
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (Single line)
+        ///
+        /// For any R code where `else` appears on the same line as the closing `}`
+        /// of the preceding `if` block (single line format), the detector SHALL NOT
+        /// emit a diagnostic.
+        ///
+        /// **Validates: Requirements 1.2, 1.3, 2.3**
+        fn prop_valid_else_no_diagnostic_single_line(
+            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate valid single-line if-else code
+            // Pattern: if (condition) {body1} else {body2}
+            let code = format!("if ({}) {{{}}} else {{{}}}", condition, body1, body2);
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should NOT emit any diagnostic for valid else on same line
+            prop_assert_eq!(
+                diagnostics.len(),
+                0,
+                "Should NOT emit diagnostic for valid else on same line. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (Multi-line with else on same line as brace)
+        ///
+        /// For any R code with a multi-line if block where `else` appears on the same
+        /// line as the closing `}`, the detector SHALL NOT emit a diagnostic.
+        ///
+        /// **Validates: Requirements 1.2, 1.3, 2.4**
+        fn prop_valid_else_no_diagnostic_multiline(
+            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body_lines in 1usize..4,
+            body2_lines in 1usize..4
+        ) {
+            // Generate multi-line if block with else on same line as closing brace
+            // Pattern: if (condition) {\n  body_line1\n  body_line2\n} else {\n  body2_line1\n}
+            let body1_content: String = (0..body_lines)
+                .map(|i| format!("  line{}", i))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let body2_content: String = (0..body2_lines)
+                .map(|i| format!("  else_line{}", i))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let code = format!(
+                "if ({}) {{\n{}\n}} else {{\n{}\n}}",
+                condition, body1_content, body2_content
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should NOT emit any diagnostic for valid else on same line as closing brace
+            prop_assert_eq!(
+                diagnostics.len(),
+                0,
+                "Should NOT emit diagnostic for valid multi-line if-else. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (else if on same line)
+        ///
+        /// For any R code where `else if` appears on the same line as the closing `}`
+        /// of the preceding `if` block, the detector SHALL NOT emit a diagnostic.
+        ///
+        /// **Validates: Requirements 1.2, 1.3, 2.3, 2.4**
+        fn prop_valid_else_if_no_diagnostic(
+            cond1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            cond2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate valid if-else if code with else if on same line as closing brace
+            // Pattern: if (cond1) {body1} else if (cond2) {body2}
+            let code = format!(
+                "if ({}) {{{}}} else if ({}) {{{}}}",
+                cond1, body1, cond2, body2
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should NOT emit any diagnostic for valid else if on same line
+            prop_assert_eq!(
+                diagnostics.len(),
+                0,
+                "Should NOT emit diagnostic for valid 'else if' on same line. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 2: Valid Else No Diagnostic (Nested valid if-else)
+        ///
+        /// For any nested if-else structure where all `else` keywords appear on the same
+        /// line as their preceding closing `}`, the detector SHALL NOT emit any diagnostic.
+        ///
+        /// **Validates: Requirements 1.2, 1.3, 2.3, 2.4**
+        fn prop_valid_nested_else_no_diagnostic(
+            outer_cond in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            inner_cond in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body3 in "[a-z][a-z0-9_]{1,6}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate valid nested if-else code
+            // Pattern: if (outer_cond) { if (inner_cond) {body1} else {body2} } else {body3}
+            let code = format!(
+                "if ({}) {{ if ({}) {{{}}} else {{{}}} }} else {{{}}}",
+                outer_cond, inner_cond, body1, body2, body3
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should NOT emit any diagnostic for valid nested if-else
+            prop_assert_eq!(
+                diagnostics.len(),
+                0,
+                "Should NOT emit diagnostic for valid nested if-else. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+        }
+
+        // ========================================================================
+        // **Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy**
+        // **Validates: Requirements 3.2**
+        //
+        // For any detected orphaned `else`, the diagnostic range SHALL start at the
+        // beginning of the `else` keyword and end at the end of the `else` keyword.
+        // ========================================================================
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy
+        ///
+        /// For any detected orphaned `else`, the diagnostic range SHALL start at the
+        /// beginning of the `else` keyword and end at the end of the `else` keyword.
+        ///
+        /// **Validates: Requirements 3.2**
+        fn prop_diagnostic_range_accuracy(
+            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            blank_lines in 0usize..3
+        ) {
+            // Generate code with else on a new line after closing brace
+            // Pattern: if (condition) {body1}\n[blank_lines]\nelse {body2}
+            let newlines = "\n".repeat(blank_lines + 1);
+            let code = format!("if ({}) {{{}}}{newlines}else {{{}}}", condition, body1, body2);
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should emit exactly one diagnostic
+            prop_assert_eq!(
+                diagnostics.len(),
+                1,
+                "Should emit exactly one diagnostic. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+
+            let diagnostic = &diagnostics[0];
+
+            // Calculate expected position of "else" in the generated code
+            // The "else" keyword starts after: "if (condition) {body1}" + newlines
+            let prefix = format!("if ({}) {{{}}}{newlines}", condition, body1);
+            let else_line = prefix.matches('\n').count() as u32;
+            let else_column = 0u32; // "else" starts at column 0 on its line
+
+            // Verify diagnostic range starts at the beginning of "else"
+            prop_assert_eq!(
+                diagnostic.range.start.line,
+                else_line,
+                "Diagnostic start line should match else position. Code: '{}', Expected line: {}, Got: {}",
+                code,
+                else_line,
+                diagnostic.range.start.line
+            );
+            prop_assert_eq!(
+                diagnostic.range.start.character,
+                else_column,
+                "Diagnostic start column should match else position. Code: '{}', Expected column: {}, Got: {}",
+                code,
+                else_column,
+                diagnostic.range.start.character
+            );
+
+            // Verify diagnostic range ends at the end of "else" (4 characters)
+            // The "else" keyword is 4 characters long
+            prop_assert_eq!(
+                diagnostic.range.end.line,
+                else_line,
+                "Diagnostic end line should be same as start line. Code: '{}', Expected: {}, Got: {}",
+                code,
+                else_line,
+                diagnostic.range.end.line
+            );
+            prop_assert_eq!(
+                diagnostic.range.end.character,
+                else_column + 4,
+                "Diagnostic end column should be start + 4 (length of 'else'). Code: '{}', Expected: {}, Got: {}",
+                code,
+                else_column + 4,
+                diagnostic.range.end.character
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy (Multi-line if block)
+        ///
+        /// For any detected orphaned `else` after a multi-line if block, the diagnostic
+        /// range SHALL accurately cover the `else` keyword.
+        ///
+        /// **Validates: Requirements 3.2**
+        fn prop_diagnostic_range_accuracy_multiline(
+            condition in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body_lines in 1usize..4,
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate multi-line if block with else on new line
+            // Pattern: if (condition) {\n  body_line1\n  body_line2\n}\nelse {body2}
+            let body_content: String = (0..body_lines)
+                .map(|i| format!("  line{}", i))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let code = format!(
+                "if ({}) {{\n{}\n}}\nelse {{{}}}",
+                condition, body_content, body2
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should emit exactly one diagnostic
+            prop_assert_eq!(
+                diagnostics.len(),
+                1,
+                "Should emit exactly one diagnostic. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+
+            let diagnostic = &diagnostics[0];
+
+            // Calculate expected position of "else" in the generated code
+            // Line count: 1 (if line) + body_lines + 1 (closing brace line) = body_lines + 2
+            // But 0-indexed, so else is on line (body_lines + 2)
+            let else_line = (body_lines + 2) as u32;
+            let else_column = 0u32; // "else" starts at column 0 on its line
+
+            // Verify diagnostic range starts at the beginning of "else"
+            prop_assert_eq!(
+                diagnostic.range.start.line,
+                else_line,
+                "Diagnostic start line should match else position. Code: '{}', Expected line: {}, Got: {}",
+                code,
+                else_line,
+                diagnostic.range.start.line
+            );
+            prop_assert_eq!(
+                diagnostic.range.start.character,
+                else_column,
+                "Diagnostic start column should match else position. Code: '{}', Expected column: {}, Got: {}",
+                code,
+                else_column,
+                diagnostic.range.start.character
+            );
+
+            // Verify diagnostic range ends at the end of "else" (4 characters)
+            prop_assert_eq!(
+                diagnostic.range.end.line,
+                else_line,
+                "Diagnostic end line should be same as start line. Code: '{}', Expected: {}, Got: {}",
+                code,
+                else_line,
+                diagnostic.range.end.line
+            );
+            prop_assert_eq!(
+                diagnostic.range.end.character,
+                else_column + 4,
+                "Diagnostic end column should be start + 4 (length of 'else'). Code: '{}', Expected: {}, Got: {}",
+                code,
+                else_column + 4,
+                diagnostic.range.end.character
+            );
+        }
+
+        #[test]
+        /// Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy (else if pattern)
+        ///
+        /// For any detected orphaned `else if` on a new line, the diagnostic range SHALL
+        /// accurately cover the `else` keyword (not the entire `else if`).
+        ///
+        /// **Validates: Requirements 3.2**
+        fn prop_diagnostic_range_accuracy_else_if(
+            cond1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            cond2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
+            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
+        ) {
+            // Generate code with else if on a new line
+            // Pattern: if (cond1) {body1}\nelse if (cond2) {body2}
+            let code = format!(
+                "if ({}) {{{}}}\nelse if ({}) {{{}}}",
+                cond1, body1, cond2, body2
+            );
+
+            let tree = parse_r_code(&code);
+            let mut diagnostics = Vec::new();
+            super::collect_else_newline_errors(
+                tree.root_node(),
+                &code,
+                &Url::parse("file:///test.R").unwrap(),
+                &mut diagnostics,
+            );
+
+            // Should emit exactly one diagnostic
+            prop_assert_eq!(
+                diagnostics.len(),
+                1,
+                "Should emit exactly one diagnostic. Code: '{}', Diagnostics: {:?}",
+                code,
+                diagnostics
+            );
+
+            let diagnostic = &diagnostics[0];
+
+            // The "else" keyword is on line 1 (0-indexed), column 0
+            let else_line = 1u32;
+            let else_column = 0u32;
+
+            // Verify diagnostic range starts at the beginning of "else"
+            prop_assert_eq!(
+                diagnostic.range.start.line,
+                else_line,
+                "Diagnostic start line should match else position. Code: '{}', Expected line: {}, Got: {}",
+                code,
+                else_line,
+                diagnostic.range.start.line
+            );
+            prop_assert_eq!(
+                diagnostic.range.start.character,
+                else_column,
+                "Diagnostic start column should match else position. Code: '{}', Expected column: {}, Got: {}",
+                code,
+                else_column,
+                diagnostic.range.start.character
+            );
+
+            // Verify diagnostic range ends at the end of "else" (4 characters)
+            // Note: The diagnostic should cover just "else", not "else if"
+            prop_assert_eq!(
+                diagnostic.range.end.line,
+                else_line,
+                "Diagnostic end line should be same as start line. Code: '{}', Expected: {}, Got: {}",
+                code,
+                else_line,
+                diagnostic.range.end.line
+            );
+            prop_assert_eq!(
+                diagnostic.range.end.character,
+                else_column + 4,
+                "Diagnostic end column should be start + 4 (length of 'else'). Code: '{}', Expected: {}, Got: {}",
+                code,
+                else_column + 4,
+                diagnostic.range.end.character
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::r_env;
+    use crate::state::{Document, WorldState};
+
+    #[test]
+    fn test_base_package_functions() {
+        // Test that base R functions are recognized
+        let library_paths = r_env::find_library_paths();
+        let _state = WorldState::new(library_paths);
+
+        let code = "library(stats)\nx <- rnorm(100)\ny <- mean(x)";
+        let doc = Document::new(code, None);
+
+        // rnorm and mean should be recognized (rnorm from stats, mean from base)
+        assert!(doc.loaded_packages.contains(&"stats".to_string()));
+    }
+
+    #[test]
+    fn test_no_spurious_errors_with_common_packages() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        // Test code that uses common package functions
+        let test_cases = vec![
+            ("library(stats)\nx <- rnorm(100)", vec!["rnorm"]),
+            (
+                "library(utils)\ndata <- read.csv('file.csv')",
+                vec!["read.csv"],
+            ),
+            ("require(graphics)\nplot(1:10)", vec!["plot"]),
+        ];
+
+        for (code, expected_funcs) in test_cases {
+            let doc = Document::new(code, None);
+            let uri = tower_lsp::lsp_types::Url::parse("file:///test.R").unwrap();
+            state.documents.insert(uri.clone(), doc);
+
+            let diagnostics = diagnostics(&state, &uri);
+
+            // Check that expected functions don't generate undefined variable errors
+            for func in expected_funcs {
+                let has_error = diagnostics.iter().any(|d| d.message.contains(func));
+                assert!(
+                    !has_error,
+                    "Function {} should not generate undefined variable error",
+                    func
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_package_exports_loaded() {
+        let library_paths = r_env::find_library_paths();
+        let state = WorldState::new(library_paths);
+
+        // Try to load stats package metadata
+        if let Some(stats_pkg) = state.library.get("stats") {
+            // stats should export common functions
+            assert!(
+                !stats_pkg.exports.is_empty(),
+                "stats package should have exports"
+            );
+
+            // Check for some known stats exports
+            let has_common_funcs = stats_pkg
+                .exports
+                .iter()
+                .any(|e| e == "rnorm" || e == "lm" || e == "t.test");
+            assert!(
+                has_common_funcs,
+                "stats should export common statistical functions"
+            );
+        }
+    }
+
+    #[test]
+    fn test_hover_shows_definition_statement() {
+        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        // Create a test document
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = "my_var <- 42\nresult <- my_var + 1";
+        let doc = Document::new(code, None);
+        state.documents.insert(uri.clone(), doc);
+
+        // Create a scoped symbol with definition info
+        let symbol = ScopedSymbol {
+            name: "my_var".to_string(),
+            kind: SymbolKind::Variable,
+            source_uri: uri.clone(),
+            defined_line: 0,
+            defined_column: 0,
+            signature: None,
+        };
+
+        // Test hover on the symbol
+        // Mock get_cross_file_symbols to return our test symbol
+        // Note: In a real test, we'd need to set up the cross-file state properly
+        // For now, we'll test the definition extraction directly
+        let def_info = extract_definition_statement(&symbol, &state);
+        assert!(def_info.is_some());
+        let def_info = def_info.unwrap();
+        assert_eq!(def_info.statement, "my_var <- 42");
+    }
+
+    #[test]
+    fn test_hover_same_file_location_format() {
+        let library_paths = r_env::find_library_paths();
+        let _state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let def_info = DefinitionInfo {
+            statement: "my_var <- 42".to_string(),
+            source_uri: uri.clone(),
+            line: 0, // 0-based
+            column: 0,
+            docs: None,
+        };
+
+        // Test same-file location formatting
+        let escaped_statement = escape_markdown(&def_info.statement);
+        let mut value = String::new();
+        value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
+
+        if def_info.source_uri == uri {
+            value.push_str(&format!("this file, line {}", def_info.line + 1)); // 1-based
+        }
+
+        assert!(value.contains("```r\nmy\\_var <- 42\n```"));
+        assert!(value.contains("this file, line 1"));
+        assert!(value.contains("\n\n")); // Blank line separator
+    }
+
+    #[test]
+    fn test_hover_cross_file_hyperlink_format() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+        state.workspace_folders = vec![Url::parse("file:///workspace/").unwrap()];
+
+        let current_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let def_uri = Url::parse("file:///workspace/utils/helper.R").unwrap();
+
+        let def_info = DefinitionInfo {
+            statement: "helper_func <- function(x) { x + 1 }".to_string(),
+            source_uri: def_uri.clone(),
+            line: 5, // 0-based
+            column: 0,
+            docs: None,
+        };
+
+        // Test cross-file location formatting
+        let escaped_statement = escape_markdown(&def_info.statement);
+        let mut value = String::new();
+        value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
+
+        if def_info.source_uri != current_uri {
+            let relative_path =
+                compute_relative_path(&def_info.source_uri, state.workspace_folders.first());
+            let absolute_path = def_info.source_uri.as_str();
+            value.push_str(&format!(
+                "[{}]({}), line {}",
+                relative_path,
+                absolute_path,
+                def_info.line + 1
+            ));
+        }
+
+        assert!(value.contains("```r\nhelper\\_func <- function\\(x\\) { x + 1 }\n```"));
+        assert!(value.contains("[utils/helper.R](file:///workspace/utils/helper.R), line 6"));
+        assert!(value.contains("\n\n")); // Blank line separator
+    }
+
+    #[test]
+    fn test_hover_markdown_code_block_formatting() {
+        let statement = "my_var <- c(1, 2, 3) # comment with *special* chars";
+        let escaped = escape_markdown(statement);
+
+        let formatted = format!("```r\n{}\n```", escaped);
+
+        assert!(formatted.starts_with("```r\n"));
+        assert!(formatted.ends_with("\n```"));
+        assert!(formatted.contains("\\*special\\*")); // Markdown chars should be escaped
+    }
+
+    #[test]
+    fn test_hover_blank_line_separator() {
+        let def_info = DefinitionInfo {
+            statement: "test_func <- function() {}".to_string(),
+            source_uri: Url::parse("file:///test.R").unwrap(),
+            line: 0,
+            column: 0,
+            docs: None,
+        };
+
+        let escaped_statement = escape_markdown(&def_info.statement);
+        let mut value = String::new();
+        value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
+        value.push_str("this file, line 1");
+
+        // Should have exactly one blank line between code block and location
+        assert!(value.contains("```\n\nthis file"));
+        assert!(!value.contains("```\n\n\nthis file")); // Not two blank lines
+        assert!(!value.contains("```\nthis file")); // Not zero blank lines
+    }
+
+    #[test]
+    fn test_cross_file_hover_resolution() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        // Create main.R that sources utils.R
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+
+        let main_code = r#"source("utils.R")
+result <- helper_func(42)"#;
+
+        let utils_code = r#"helper_func <- function(x) {
+    x * 2
+}"#;
+
+        // Add documents to state
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        // Update cross-file graph
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+
+        // Test hover on helper_func in main.R (line 1, after source call)
+        let position = Position::new(1, 10); // Position of "helper_func"
+        let hover_result = hover_blocking(&state, &main_uri, position);
+
+        assert!(hover_result.is_some());
+        let hover = hover_result.unwrap();
+
+        if let HoverContents::Markup(content) = hover.contents {
+            // Code blocks don't need escaping - content should be unescaped
+            assert!(content.value.contains("helper_func"));
+            assert!(content.value.contains("function(x)"));
+            assert!(content.value.contains("utils.R")); // Should show cross-file source
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_symbol_shadowing() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        // Create files with shadowing: local definition should take precedence
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+
+        let main_code = r#"source("utils.R")
+my_func <- function(a, b) { a + b }  # Local definition shadows utils.R
+result <- my_func(1, 2)"#;
+
+        let utils_code = r#"my_func <- function(x) { x * 2 }  # Will be shadowed"#;
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        // Update cross-file graph
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+
+        // Test hover on my_func usage (should show local definition, not utils.R)
+        let position = Position::new(2, 10); // Position of "my_func" in usage
+        let hover_result = hover_blocking(&state, &main_uri, position);
+
+        assert!(hover_result.is_some());
+        let hover = hover_result.unwrap();
+
+        if let HoverContents::Markup(content) = hover.contents {
+            // Code blocks don't need escaping - content should be unescaped
+            assert!(content.value.contains("my_func"));
+            assert!(content.value.contains("(a, b)")); // Local signature, not (x)
+            assert!(content.value.contains("this file")); // Should be local, not cross-file
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    /// Both the local definition and the one shadowed from `utils.R` are
+    /// visible in-scope, so hover should show both candidates instead of
+    /// silently picking the local one.
+    #[test]
+    fn test_hover_shows_all_candidates_when_shadowed() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+
+        let main_code = r#"source("utils.R")
+my_func <- function(a, b) { a + b }
+result <- my_func(1, 2)"#;
+
+        let utils_code = r#"my_func <- function(x) { x * 2 }"#;
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+
+        let position = Position::new(2, 10); // Position of "my_func" in usage
+        let hover = hover_blocking(&state, &main_uri, position).expect("expected hover");
+
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(content.value.contains("(a, b)"), "local signature missing");
+            assert!(content.value.contains("(x)"), "shadowed signature missing");
+            assert!(content.value.contains("this file"));
+            assert!(content.value.contains("utils.R"));
+            assert!(content.value.contains("Ambiguous"));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    /// Each candidate in the ambiguous multi-block hover renders its own
+    /// roxygen docs, not just the signature and origin line.
+    #[test]
+    fn test_hover_ambiguous_candidates_include_roxygen_docs() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+
+        let main_code = r#"source("utils.R")
+#' Local variant.
+my_func <- function(a, b) { a + b }
+result <- my_func(1, 2)"#;
+
+        let utils_code = "#' Shadowed variant.\nmy_func <- function(x) { x * 2 }";
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+
+        let position = Position::new(3, 10); // Position of "my_func" in usage
+        let hover = hover_blocking(&state, &main_uri, position).expect("expected hover");
+
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(content.value.contains("Local variant."));
+            assert!(content.value.contains("Shadowed variant."));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    /// When only one definition is in scope, hover stays on the existing
+    /// single-candidate rendering rather than the ambiguous multi-block one.
+    #[test]
+    fn test_hover_stays_exact_with_single_candidate() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = "my_func <- function(a, b) { a + b }\nresult <- my_func(1, 2)";
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let position = Position::new(1, 10); // Position of "my_func" in usage
+        let hover = hover_blocking(&state, &uri, position).expect("expected hover");
+
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(!content.value.contains("Ambiguous"));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_builtin_function_fallback() {
+        let library_paths = r_env::find_library_paths();
+        let state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = r#"result <- mean(c(1, 2, 3))"#;
+
+        let doc = Document::new(code, None);
+        let tree = doc.tree.as_ref().unwrap();
+        let text = doc.text();
+
+        // Find the "mean" identifier
+        let point = tree_sitter::Point::new(0, 10); // Position of "mean"
+        let node = tree
+            .root_node()
+            .descendant_for_point_range(point, point)
+            .unwrap();
+        assert_eq!(node.kind(), "identifier");
+        assert_eq!(&text[node.byte_range()], "mean");
+
+        // Test hover should fall back to R help for built-in functions
+        let position = Position::new(0, 10);
+
+        // Mock the state with the document
+        let mut test_state = state;
+        test_state.documents.insert(uri.clone(), doc);
+
+        let hover_result = hover_blocking(&test_state, &uri, position);
+
+        // Should return hover info (either from help cache or R subprocess)
+        // The exact content depends on R availability, but structure should be consistent
+        if let Some(hover) = hover_result {
+            if let HoverContents::Markup(content) = hover.contents {
+                assert!(content.kind == MarkupKind::Markdown);
+                assert!(content.value.starts_with("```"));
+                assert!(content.value.ends_with("```"));
+            } else {
+                panic!("Expected markup content");
+            }
+        }
+        // Note: We don't assert hover_result.is_some() because R might not be available in CI
+    }
+
+    #[test]
+    fn test_hover_help_fallback_disabled_skips_r_subprocess() {
+        // Unlike test_hover_builtin_function_fallback, this is fully
+        // deterministic: with help_fallback off, hover never shells out to R,
+        // so a builtin with no local definition always yields no hover.
+        let library_paths = r_env::find_library_paths();
+        let mut test_state = WorldState::new(library_paths);
+        test_state.hover_config.help_fallback = false;
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = r#"result <- mean(c(1, 2, 3))"#;
+        let doc = Document::new(code, None);
+        test_state.documents.insert(uri.clone(), doc);
+
+        let position = Position::new(0, 10); // "mean"
+        let hover_result = hover_blocking(&test_state, &uri, position);
+        assert!(hover_result.is_none());
+    }
+
+    #[test]
+    fn test_hover_cross_file_disabled_restricts_to_local_scope() {
+        let library_paths = r_env::find_library_paths();
+        let mut test_state = WorldState::new(library_paths);
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let main_code = "source(\"helper.R\")\nhelper()";
+
+        let helper_uri = Url::parse("file:///workspace/helper.R").unwrap();
+        let helper_code = "helper <- function() 1";
+
+        test_state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        test_state
+            .documents
+            .insert(helper_uri.clone(), Document::new(helper_code, None));
+
+        test_state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        test_state.cross_file_graph.update_file(
+            &helper_uri,
+            &crate::cross_file::extract_metadata(helper_code),
+            None,
+            |_| None,
+        );
+
+        let position = Position::new(1, 0); // "helper" call in main.R
+
+        test_state.hover_config.cross_file = true;
+        let with_cross_file = hover_blocking(&test_state, &main_uri, position);
+        assert!(
+            with_cross_file.is_some(),
+            "expected helper() to resolve via the source() chain"
+        );
+
+        test_state.hover_config.cross_file = false;
+        let without_cross_file = hover_blocking(&test_state, &main_uri, position);
+        assert!(
+            without_cross_file.is_none(),
+            "disabling cross_file should restrict resolution to main.R's own scope"
+        );
+    }
+
+    #[test]
+    fn test_hover_signature_only_returns_minimal_hover() {
+        let library_paths = r_env::find_library_paths();
+        let mut test_state = WorldState::new(library_paths);
+        test_state.hover_config.signature_only = true;
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = "#' Adds one\n#' @param x a number\nadd_one <- function(x) x + 1\nadd_one(1)";
+        let doc = Document::new(code, None);
+        test_state.documents.insert(uri.clone(), doc);
+
+        let position = Position::new(3, 0); // "add_one" call site
+        let hover = hover_blocking(&test_state, &uri, position).expect("expected a hover result");
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(content.value.starts_with("```r\n"));
+            assert!(content.value.ends_with("```"));
+            assert!(
+                !content.value.contains("Adds one"),
+                "signature_only should skip roxygen documentation: {}",
+                content.value
+            );
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_documentation_disabled_omits_roxygen_docs() {
+        let library_paths = r_env::find_library_paths();
+        let mut test_state = WorldState::new(library_paths);
+        test_state.hover_config.documentation = false;
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = "#' Adds one\nadd_one <- function(x) x + 1\nadd_one(1)";
+        let doc = Document::new(code, None);
+        test_state.documents.insert(uri.clone(), doc);
+
+        let position = Position::new(2, 0); // "add_one" call site
+        let hover = hover_blocking(&test_state, &uri, position).expect("expected a hover result");
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(!content.value.contains("Adds one"));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_plaintext_markup_config() {
+        let library_paths = r_env::find_library_paths();
+        let mut test_state = WorldState::new(library_paths);
+        test_state.hover_config.markup = MarkupKind::PlainText;
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = "add_one <- function(x) x + 1\nadd_one(1)";
+        let doc = Document::new(code, None);
+        test_state.documents.insert(uri.clone(), doc);
+
+        let position = Position::new(1, 0); // "add_one" call site
+        let hover = hover_blocking(&test_state, &uri, position).expect("expected a hover result");
+        if let HoverContents::Markup(content) = hover.contents {
+            assert_eq!(content.kind, MarkupKind::PlainText);
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_s3_generic_shows_all_methods() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///workspace/main.R").unwrap();
+        let code = r#"summary.foo <- function(object, ...) { "foo summary" }
+summary.bar <- function(object, ...) { "bar summary" }
+summary(x)"#;
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        state.cross_file_graph.update_file(
+            &uri,
+            &crate::cross_file::extract_metadata(code),
+            None,
+            |_| None,
+        );
+
+        let position = Position::new(2, 0); // "summary" call site
+        let hover = hover_blocking(&state, &uri, position).expect("expected a hover result");
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(content.value.contains("2 methods of `summary`"));
+            assert!(content.value.contains("class `foo`"));
+            assert!(content.value.contains("class `bar`"));
+            assert!(content.value.contains("summary.foo"));
+            assert!(content.value.contains("summary.bar"));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_s4_set_method_calls_grouped_with_s3() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///workspace/main.R").unwrap();
+        let code = r#"area.square <- function(shape) { shape$side^2 }
+setMethod("area", "circle", function(shape) { pi * shape$radius^2 })
+area(x)"#;
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        state.cross_file_graph.update_file(
+            &uri,
+            &crate::cross_file::extract_metadata(code),
+            None,
+            |_| None,
+        );
+
+        let position = Position::new(2, 0); // "area" call site
+        let hover = hover_blocking(&state, &uri, position).expect("expected a hover result");
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(content.value.contains("2 methods of `area`"));
+            assert!(content.value.contains("S3 method for class `square`"));
+            assert!(content.value.contains("S4 method for class `circle`"));
+            assert!(content.value.contains("setMethod(\"area\", \"circle\""));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_single_function_not_treated_as_dispatch() {
+        // A single, plain (non-dotted) function definition should hover
+        // normally rather than through the dispatch-grouping path.
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///workspace/main.R").unwrap();
+        let code = "helper <- function(x) { x + 1 }\nhelper(1)";
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        state.cross_file_graph.update_file(
+            &uri,
+            &crate::cross_file::extract_metadata(code),
+            None,
+            |_| None,
+        );
+
+        let position = Position::new(1, 0); // "helper" call site
+        let hover = hover_blocking(&state, &uri, position).expect("expected a hover result");
+        if let HoverContents::Markup(content) = hover.contents {
+            assert!(!content.value.contains("methods of"));
+            assert!(content.value.contains("helper <- function(x)"));
+        } else {
+            panic!("Expected markup content");
+        }
+    }
+
+    #[test]
+    fn test_hover_undefined_symbol_returns_none() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///test.R").unwrap();
+        let code = r#"result <- undefined_symbol_that_does_not_exist"#;
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        // Test hover on undefined symbol
+        let position = Position::new(0, 10); // Position of "undefined_symbol_that_does_not_exist"
+        let hover_result = hover_blocking(&state, &uri, position);
+
+        // Should return None for truly undefined symbols (after trying all fallbacks)
+        // This tests the graceful handling when no definition is found anywhere
+        assert!(hover_result.is_none());
+    }
+
+    #[test]
+    fn test_hover_graceful_fallback_missing_definition_file() {
+        use crate::cross_file::ScopedSymbol;
+
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let missing_uri = Url::parse("file:///workspace/missing.R").unwrap(); // File doesn't exist
+
+        let main_code = r#"# Symbol from missing file
+result <- missing_func(42)"#;
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+
+        // Create a scoped symbol that references a missing file
+        let symbol = ScopedSymbol {
+            name: "missing_func".to_string(),
+            kind: crate::cross_file::SymbolKind::Function,
+            source_uri: missing_uri, // This file doesn't exist in state
+            defined_line: 0,
+            defined_column: 0,
+            signature: Some("missing_func(x)".to_string()),
+        };
+
+        // Test extract_definition_statement with missing file (should return None)
+        let def_info = extract_definition_statement(&symbol, &state);
+        assert!(
+            def_info.is_none(),
+            "Should return None when source file is missing"
+        );
+
+        // The hover function should gracefully fall back to showing just the signature
+        // This is tested implicitly in the hover function's match arm for None from extract_definition_statement
+    }
+
+    #[test]
+    fn test_hover_position_aware_scope_resolution() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
+        let code = r#"# Before source call - symbol not available
+result1 <- helper_func(1)  # Should not resolve
+
+source("utils.R")
+
+# After source call - symbol available  
+result2 <- helper_func(2)  # Should resolve"#;
+
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let utils_code = r#"helper_func <- function(x) { x * 2 }"#;
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        // Update cross-file graph
+        state.cross_file_graph.update_file(
+            &uri,
+            &crate::cross_file::extract_metadata(code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+
+        // Test hover before source call (line 1) - should not find cross-file symbol
+        let position_before = Position::new(1, 11); // "helper_func" before source()
+        let cross_file_symbols_before = get_cross_file_symbols(
+            &state,
+            &uri,
+            position_before.line,
+            position_before.character,
+        );
+        assert!(
+            !cross_file_symbols_before.contains_key("helper_func"),
+            "Symbol should not be available before source() call"
+        );
+
+        // Test hover after source call (line 5) - should find cross-file symbol
+        let position_after = Position::new(5, 11); // "helper_func" after source()
+        let cross_file_symbols_after =
+            get_cross_file_symbols(&state, &uri, position_after.line, position_after.character);
+        assert!(
+            cross_file_symbols_after.contains_key("helper_func"),
+            "Symbol should be available after source() call"
+        );
+    }
+
+    #[test]
+    fn test_hover_uses_dependency_graph_correctly() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        // Create a chain: main.R -> utils.R -> helpers.R
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let helpers_uri = Url::parse("file:///workspace/helpers.R").unwrap();
+
+        let main_code = r#"source("utils.R")
+result <- process_data(42)"#;
+
+        let utils_code = r#"source("helpers.R")
+process_data <- function(x) {
+    transform_value(x) + 10
+}"#;
+
+        let helpers_code = r#"transform_value <- function(x) { x * 2 }"#;
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+        state
+            .documents
+            .insert(helpers_uri.clone(), Document::new(helpers_code, None));
+
+        // Update cross-file graph for all files
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &helpers_uri,
+            &crate::cross_file::extract_metadata(helpers_code),
+            None,
+            |_| None,
+        );
+
+        // Test hover on transform_value in utils.R (should resolve through chain)
+        let position = Position::new(2, 4); // "transform_value" in utils.R
+        let cross_file_symbols =
+            get_cross_file_symbols(&state, &utils_uri, position.line, position.character);
+
+        assert!(
+            cross_file_symbols.contains_key("transform_value"),
+            "Should resolve symbol through dependency chain"
+        );
+
+        let symbol = &cross_file_symbols["transform_value"];
+        assert_eq!(
+            symbol.source_uri, helpers_uri,
+            "Should trace back to helpers.R"
+        );
+    }
+
+    // ============================================================================
+    // Task 17: Enhanced Variable Detection Hover Integration Tests
+    // ============================================================================
+
+    #[test]
+    fn test_complete_workflow_for_loops_and_functions() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
+        let code = r#"# Test for loops and function parameters
+process_data <- function(data, threshold = 0.5, ...) {
+    filtered <- data[data > threshold]
+    for (i in 1:10) {
+        for (j in 1:5) {
+            result <- i * j
+            if (result > threshold) {
+                print(result)
+            }
+        }
+    }
+    for (item in filtered) {
+        print(item)
+    }
+    return(filtered)
+}"#;
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        // Test scope resolution includes all iterators and parameters
+        let positions = vec![
+            (Position::new(5, 12), "result", true), // result inside nested loop
+            (Position::new(4, 12), "i", true),      // i iterator
+            (Position::new(4, 18), "j", true),      // j iterator
+            (Position::new(12, 14), "item", true),  // item used inside the loop body
+            (Position::new(2, 20), "data", true),   // function parameter
+            (Position::new(6, 27), "threshold", true), // function parameter with default
+            (Position::new(14, 14), "filtered", true), // local variable used in return(filtered)
+        ];
+
+        for (position, symbol_name, should_exist) in positions {
+            let symbols = get_cross_file_symbols(&state, &uri, position.line, position.character);
+            if should_exist {
+                assert!(
+                    symbols.contains_key(symbol_name),
+                    "Symbol '{}' should be in scope at line {}, col {}",
+                    symbol_name,
+                    position.line + 1,
+                    position.character
+                );
+            } else {
+                assert!(
+                    !symbols.contains_key(symbol_name),
+                    "Symbol '{}' should NOT be in scope at line {}, col {}",
+                    symbol_name,
+                    position.line + 1,
+                    position.character
+                );
+            }
+        }
+
+        // Test no false-positive undefined variable diagnostics
+        let diagnostics = diagnostics(&state, &uri);
+        let undefined_errors: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.message.contains("undefined") || d.message.contains("not found"))
+            .collect();
+
+        assert!(
+            undefined_errors.is_empty(),
+            "Should not have undefined variable errors for loop iterators and function parameters: {:?}",
+            undefined_errors
+        );
+
+        // Test hover shows definition statements (no escaping needed in code blocks)
+        let hover_tests = vec![
+            (Position::new(4, 12), "i", "for (i in 1:10)"),
+            (Position::new(4, 18), "j", "for (j in 1:5)"),
+            (Position::new(12, 14), "item", "for (item in filtered)"),
+            (
+                Position::new(2, 20),
+                "data",
+                "process_data <- function(data, threshold = 0.5, ...)",
+            ),
+        ];
+
+        for (position, symbol_name, expected_statement) in hover_tests {
+            let hover_result = hover_blocking(&state, &uri, position);
+            if let Some(hover) = hover_result {
+                if let HoverContents::Markup(content) = hover.contents {
+                    assert!(
+                        content.value.contains(expected_statement),
+                        "Hover for '{}' should contain '{}', got: {}",
+                        symbol_name,
+                        expected_statement,
+                        content.value
+                    );
+                    assert!(
+                        content.value.contains("this file"),
+                        "Hover should show file location"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_realistic_r_code_patterns() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        // Create main file with realistic patterns
+        let main_uri = Url::parse("file:///workspace/analysis.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let helpers_uri = Url::parse("file:///workspace/helpers.R").unwrap();
+
+        let main_code = r#"# Analysis script with realistic patterns
+source("utils.R")
+source("helpers.R", local = TRUE)
+
+# Nested loops with multiple iterators
+results <- list()
+for (i in 1:10) {
+    for (j in 1:5) {
+        value <- i * j
+        results[[paste0(i, "_", j)]] <- value
+    }
+}
+
+# Function with parameters and locals
+analyze_data <- function(dataset, 
+                        min_threshold = 0.1,
+                        max_threshold = 0.9,
+                        ...) {
+    # Multi-line function definition
+    cleaned <- dataset[!is.na(dataset)]
+    
+    for (threshold in seq(min_threshold, max_threshold, 0.1)) {
+        filtered <- cleaned[cleaned > threshold]
+        cat("Threshold:", threshold, "Count:", length(filtered), "\n")
+    }
+    
+    return(cleaned)
+}
+
+# Code with markdown special characters
+comment_with_stars <- "This has *asterisks* and _underscores_"
+backtick_var <- `special name with spaces`
+"#;
+
+        let utils_code = r#"# Utility functions
+utility_func <- function(x, y = 2) {
+    x ^ y
+}
+
+CONSTANT_VALUE <- 42
+"#;
+
+        let helpers_code = r#"# Helper functions (sourced with local=TRUE)
+helper_transform <- function(data) {
+    data * 2
+}
+"#;
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+        state
+            .documents
+            .insert(helpers_uri.clone(), Document::new(helpers_code, None));
+
+        // Update cross-file graph
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &helpers_uri,
+            &crate::cross_file::extract_metadata(helpers_code),
+            None,
+            |_| None,
+        );
+
+        // Test nested loop iterators are in scope
+        let nested_loop_position = Position::new(8, 8); // Inside nested loop
+        let symbols = get_cross_file_symbols(
+            &state,
+            &main_uri,
+            nested_loop_position.line,
+            nested_loop_position.character,
+        );
+
+        assert!(
+            symbols.contains_key("i"),
+            "Outer loop iterator 'i' should be in scope"
+        );
+        assert!(
+            symbols.contains_key("j"),
+            "Inner loop iterator 'j' should be in scope"
+        );
+        assert!(
+            symbols.contains_key("value"),
+            "Local variable 'value' should be in scope"
+        );
+
+        // Test function parameters are in scope within function
+        let function_body_position = Position::new(19, 4); // Inside analyze_data function
+        let func_symbols = get_cross_file_symbols(
+            &state,
+            &main_uri,
+            function_body_position.line,
+            function_body_position.character,
+        );
+
+        assert!(
+            func_symbols.contains_key("dataset"),
+            "Function parameter 'dataset' should be in scope"
+        );
+        assert!(
+            func_symbols.contains_key("min_threshold"),
+            "Function parameter 'min_threshold' should be in scope"
+        );
+        assert!(
+            func_symbols.contains_key("max_threshold"),
+            "Function parameter 'max_threshold' should be in scope"
+        );
+        assert!(
+            func_symbols.contains_key("cleaned"),
+            "Local variable 'cleaned' should be in scope"
+        );
 
-            let code = format!(
-                "if ({}) {{\n{}\n}}\nelse {{{}}}",
-                condition, body_content, body2
-            );
+        // Test cross-file symbols are resolved correctly
+        let after_source_position = Position::new(4, 0); // After source() calls
+        let cross_symbols = get_cross_file_symbols(
+            &state,
+            &main_uri,
+            after_source_position.line,
+            after_source_position.character,
+        );
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+        assert!(
+            cross_symbols.contains_key("utility_func"),
+            "Should resolve utility_func from utils.R"
+        );
+        assert!(
+            cross_symbols.contains_key("CONSTANT_VALUE"),
+            "Should resolve CONSTANT_VALUE from utils.R"
+        );
+        // Note: helper_transform should NOT be available due to local=TRUE
 
-            // Should emit exactly one diagnostic
-            prop_assert_eq!(
-                diagnostics.len(),
-                1,
-                "Should emit exactly one diagnostic. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
+        // Test hover shows proper formatting for multi-line definitions
+        let multi_line_func_position = Position::new(13, 0); // analyze_data function name
+        let hover_result = hover_blocking(&state, &main_uri, multi_line_func_position);
 
-            let diagnostic = &diagnostics[0];
+        if let Some(hover) = hover_result {
+            if let HoverContents::Markup(content) = hover.contents {
+                assert!(content.value.contains("analyze_data <- function(dataset,"));
+                assert!(content.value.contains("this file"));
+                // Should handle markdown special characters properly
+                assert!(!content.value.contains("*asterisks*")); // Should be escaped
+            }
+        }
 
-            // Calculate expected position of "else" in the generated code
-            // Line count: 1 (if line) + body_lines + 1 (closing brace line) = body_lines + 2
-            // But 0-indexed, so else is on line (body_lines + 2)
-            let else_line = (body_lines + 2) as u32;
-            let else_column = 0u32; // "else" starts at column 0 on its line
+        // Test no false positives for valid symbols
+        let diagnostics = diagnostics(&state, &main_uri);
+        let undefined_errors: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.message.contains("undefined"))
+            .collect();
 
-            // Verify diagnostic range starts at the beginning of "else"
-            prop_assert_eq!(
-                diagnostic.range.start.line,
-                else_line,
-                "Diagnostic start line should match else position. Code: '{}', Expected line: {}, Got: {}",
-                code,
-                else_line,
-                diagnostic.range.start.line
+        // Should not report undefined errors for loop iterators, function parameters, or cross-file symbols
+        for error in &undefined_errors {
+            assert!(
+                !error.message.contains("i "),
+                "Should not report 'i' as undefined"
             );
-            prop_assert_eq!(
-                diagnostic.range.start.character,
-                else_column,
-                "Diagnostic start column should match else position. Code: '{}', Expected column: {}, Got: {}",
-                code,
-                else_column,
-                diagnostic.range.start.character
+            assert!(
+                !error.message.contains("j "),
+                "Should not report 'j' as undefined"
             );
-
-            // Verify diagnostic range ends at the end of "else" (4 characters)
-            prop_assert_eq!(
-                diagnostic.range.end.line,
-                else_line,
-                "Diagnostic end line should be same as start line. Code: '{}', Expected: {}, Got: {}",
-                code,
-                else_line,
-                diagnostic.range.end.line
+            assert!(
+                !error.message.contains("dataset"),
+                "Should not report 'dataset' as undefined"
             );
-            prop_assert_eq!(
-                diagnostic.range.end.character,
-                else_column + 4,
-                "Diagnostic end column should be start + 4 (length of 'else'). Code: '{}', Expected: {}, Got: {}",
-                code,
-                else_column + 4,
-                diagnostic.range.end.character
+            assert!(
+                !error.message.contains("utility_func"),
+                "Should not report 'utility_func' as undefined"
             );
         }
+    }
 
-        #[test]
-        /// Feature: else-newline-syntax-error, Property 4: Diagnostic Range Accuracy (else if pattern)
-        ///
-        /// For any detected orphaned `else if` on a new line, the diagnostic range SHALL
-        /// accurately cover the `else` keyword (not the entire `else if`).
-        ///
-        /// **Validates: Requirements 3.2**
-        fn prop_diagnostic_range_accuracy_else_if(
-            cond1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            cond2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body1 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s)),
-            body2 in "[a-z][a-z0-9_]{1,8}".prop_filter("Not reserved", |s| !is_r_reserved(s))
-        ) {
-            // Generate code with else if on a new line
-            // Pattern: if (cond1) {body1}\nelse if (cond2) {body2}
-            let code = format!(
-                "if ({}) {{{}}}\nelse if ({}) {{{}}}",
-                cond1, body1, cond2, body2
-            );
+    #[test]
+    fn test_cross_file_local_scope_isolation() {
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
 
-            let tree = parse_r_code(&code);
-            let mut diagnostics = Vec::new();
-            super::collect_else_newline_errors(tree.root_node(), &code, &mut diagnostics);
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let local_uri = Url::parse("file:///workspace/local_source.R").unwrap();
+        let global_uri = Url::parse("file:///workspace/global_source.R").unwrap();
 
-            // Should emit exactly one diagnostic
-            prop_assert_eq!(
-                diagnostics.len(),
-                1,
-                "Should emit exactly one diagnostic. Code: '{}', Diagnostics: {:?}",
-                code,
-                diagnostics
-            );
+        let main_code = r#"# Test local vs global sourcing
+source("global_source.R")           # Global scope
+source("local_source.R", local = TRUE)  # Local scope
 
-            let diagnostic = &diagnostics[0];
+# These should be available from global source
+global_result <- global_func(42)
 
-            // The "else" keyword is on line 1 (0-indexed), column 0
-            let else_line = 1u32;
-            let else_column = 0u32;
+# These should NOT be available from local source
+# local_func(42)  # Would be undefined
+"#;
 
-            // Verify diagnostic range starts at the beginning of "else"
-            prop_assert_eq!(
-                diagnostic.range.start.line,
-                else_line,
-                "Diagnostic start line should match else position. Code: '{}', Expected line: {}, Got: {}",
-                code,
-                else_line,
-                diagnostic.range.start.line
-            );
-            prop_assert_eq!(
-                diagnostic.range.start.character,
-                else_column,
-                "Diagnostic start column should match else position. Code: '{}', Expected column: {}, Got: {}",
-                code,
-                else_column,
-                diagnostic.range.start.character
-            );
+        let global_code = r#"global_func <- function(x) { x + 1 }
+global_var <- 100"#;
 
-            // Verify diagnostic range ends at the end of "else" (4 characters)
-            // Note: The diagnostic should cover just "else", not "else if"
-            prop_assert_eq!(
-                diagnostic.range.end.line,
-                else_line,
-                "Diagnostic end line should be same as start line. Code: '{}', Expected: {}, Got: {}",
-                code,
-                else_line,
-                diagnostic.range.end.line
-            );
-            prop_assert_eq!(
-                diagnostic.range.end.character,
-                else_column + 4,
-                "Diagnostic end column should be start + 4 (length of 'else'). Code: '{}', Expected: {}, Got: {}",
-                code,
-                else_column + 4,
-                diagnostic.range.end.character
-            );
-        }
-    }
-}
+        let local_code = r#"local_func <- function(x) { x * 2 }
+local_var <- 200"#;
 
-#[cfg(test)]
-mod integration_tests {
-    use super::*;
-    use crate::r_env;
-    use crate::state::{Document, WorldState};
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(global_uri.clone(), Document::new(global_code, None));
+        state
+            .documents
+            .insert(local_uri.clone(), Document::new(local_code, None));
 
-    #[test]
-    fn test_base_package_functions() {
-        // Test that base R functions are recognized
-        let library_paths = r_env::find_library_paths();
-        let _state = WorldState::new(library_paths);
+        // Update cross-file graph
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &global_uri,
+            &crate::cross_file::extract_metadata(global_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &local_uri,
+            &crate::cross_file::extract_metadata(local_code),
+            None,
+            |_| None,
+        );
 
-        let code = "library(stats)\nx <- rnorm(100)\ny <- mean(x)";
-        let doc = Document::new(code, None);
+        // Test symbols after both source calls
+        let position = Position::new(5, 0); // After both source() calls
+        let symbols = get_cross_file_symbols(&state, &main_uri, position.line, position.character);
+
+        // Global source symbols should be available
+        assert!(
+            symbols.contains_key("global_func"),
+            "global_func should be available from global source"
+        );
+        assert!(
+            symbols.contains_key("global_var"),
+            "global_var should be available from global source"
+        );
+
+        // Local source symbols should NOT be available in main scope
+        assert!(
+            !symbols.contains_key("local_func"),
+            "local_func should NOT be available from local source"
+        );
+        assert!(
+            !symbols.contains_key("local_var"),
+            "local_var should NOT be available from local source"
+        );
 
-        // rnorm and mean should be recognized (rnorm from stats, mean from base)
-        assert!(doc.loaded_packages.contains(&"stats".to_string()));
+        // Test hover on global symbol shows cross-file location
+        let hover_position = Position::new(5, 16); // "global_func" usage
+        let hover_result = hover_blocking(&state, &main_uri, hover_position);
+
+        if let Some(hover) = hover_result {
+            if let HoverContents::Markup(content) = hover.contents {
+                assert!(content.value.contains("global_func"));
+                assert!(
+                    content.value.contains("global_source.R"),
+                    "Should show cross-file source"
+                );
+            }
+        }
     }
 
     #[test]
-    fn test_no_spurious_errors_with_common_packages() {
+    fn test_hover_hyperlink_formatting_with_special_paths() {
         let library_paths = r_env::find_library_paths();
         let mut state = WorldState::new(library_paths);
+        state.workspace_folders = vec![Url::parse("file:///workspace/").unwrap()];
 
-        // Test code that uses common package functions
-        let test_cases = vec![
-            ("library(stats)\nx <- rnorm(100)", vec!["rnorm"]),
-            (
-                "library(utils)\ndata <- read.csv('file.csv')",
-                vec!["read.csv"],
-            ),
-            ("require(graphics)\nplot(1:10)", vec!["plot"]),
-        ];
+        // Test various path scenarios
+        let main_uri = Url::parse("file:///workspace/src/analysis/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils/helpers with spaces.R").unwrap();
 
-        for (code, expected_funcs) in test_cases {
-            let doc = Document::new(code, None);
-            let uri = tower_lsp::lsp_types::Url::parse("file:///test.R").unwrap();
-            state.documents.insert(uri.clone(), doc);
+        let main_code = r#"source("../../utils/helpers with spaces.R")
+result <- helper_with_spaces(42)"#;
 
-            let diagnostics = diagnostics(&state, &uri);
+        let utils_code = r#"helper_with_spaces <- function(x) {
+    # Function with special characters in filename
+    x * 2
+}"#;
 
-            // Check that expected functions don't generate undefined variable errors
-            for func in expected_funcs {
-                let has_error = diagnostics.iter().any(|d| d.message.contains(func));
-                assert!(
-                    !has_error,
-                    "Function {} should not generate undefined variable error",
-                    func
-                );
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        // Update cross-file graph
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
+
+        // Test hover shows proper hyperlink formatting
+        let position = Position::new(1, 10); // "helper_with_spaces"
+        let hover_result = hover_blocking(&state, &main_uri, position);
+
+        if let Some(hover) = hover_result {
+            if let HoverContents::Markup(content) = hover.contents {
+                // Should contain properly formatted hyperlink
+                assert!(content.value.contains("[utils/helpers with spaces.R:1]"));
+                assert!(content
+                    .value
+                    .contains("file:///workspace/utils/helpers%20with%20spaces.R#L1"));
             }
         }
     }
 
+    // ============================================================================
+    // Tests for hover package info - Task 12.1
+    // ============================================================================
+
     #[test]
-    fn test_package_exports_loaded() {
-        let library_paths = r_env::find_library_paths();
-        let state = WorldState::new(library_paths);
+    fn test_hover_shows_package_name_for_package_exports() {
+        // Test that hover displays package name for package exports
+        // Validates: Requirement 10.1
+        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
 
-        // Try to load stats package metadata
-        if let Some(stats_pkg) = state.library.get("stats") {
-            // stats should export common functions
-            assert!(
-                !stats_pkg.exports.is_empty(),
-                "stats package should have exports"
-            );
+        // Create a symbol with a package URI
+        let package_uri = Url::parse("package:dplyr").unwrap();
+        let symbol = ScopedSymbol {
+            name: "mutate".to_string(),
+            kind: SymbolKind::Variable,
+            source_uri: package_uri,
+            defined_line: 0,
+            defined_column: 0,
+            signature: None,
+        };
 
-            // Check for some known stats exports
-            let has_common_funcs = stats_pkg
-                .exports
-                .iter()
-                .any(|e| e == "rnorm" || e == "lm" || e == "t.test");
-            assert!(
-                has_common_funcs,
-                "stats should export common statistical functions"
-            );
+        // Verify the package name can be extracted from the URI
+        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
+        assert_eq!(
+            package_name,
+            Some("dplyr"),
+            "Should extract package name from URI"
+        );
+
+        // Test the formatting that would be used in hover
+        let mut value = String::new();
+        value.push_str(&format!("```r\n{}\n```\n", symbol.name));
+        if let Some(pkg) = package_name {
+            value.push_str(&format!("\nfrom {{{}}}", pkg));
         }
+
+        assert!(
+            value.contains("```r\nmutate\n```"),
+            "Should contain symbol name in code block"
+        );
+        assert!(
+            value.contains("from {dplyr}"),
+            "Should contain package name in braces"
+        );
     }
 
     #[test]
-    fn test_hover_shows_definition_statement() {
-        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    fn test_hover_package_uri_detection() {
+        // Test that package URIs are correctly detected
+        // Validates: Requirement 10.1
 
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+        // Package URIs should be detected
+        let package_uri = Url::parse("package:ggplot2").unwrap();
+        assert!(
+            package_uri.as_str().starts_with("package:"),
+            "Package URI should start with 'package:'"
+        );
+        assert_eq!(
+            package_uri.as_str().strip_prefix("package:"),
+            Some("ggplot2")
+        );
 
-        // Create a test document
-        let uri = Url::parse("file:///test.R").unwrap();
-        let code = "my_var <- 42\nresult <- my_var + 1";
-        let doc = Document::new(code, None);
-        state.documents.insert(uri.clone(), doc);
+        // Base package URI should also be detected
+        let base_uri = Url::parse("package:base").unwrap();
+        assert!(
+            base_uri.as_str().starts_with("package:"),
+            "Base package URI should start with 'package:'"
+        );
+        assert_eq!(base_uri.as_str().strip_prefix("package:"), Some("base"));
 
-        // Create a scoped symbol with definition info
+        // File URIs should NOT be detected as packages
+        let file_uri = Url::parse("file:///test.R").unwrap();
+        assert!(
+            !file_uri.as_str().starts_with("package:"),
+            "File URI should not start with 'package:'"
+        );
+        assert_eq!(file_uri.as_str().strip_prefix("package:"), None);
+    }
+
+    #[test]
+    fn test_hover_local_definition_not_shown_as_package() {
+        // Test that local definitions are not shown as package exports
+        // Validates: Requirement 10.4 (shadowing)
+        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+
+        // Create a symbol with a file URI (local definition)
+        let file_uri = Url::parse("file:///workspace/main.R").unwrap();
         let symbol = ScopedSymbol {
-            name: "my_var".to_string(),
-            kind: SymbolKind::Variable,
-            source_uri: uri.clone(),
-            defined_line: 0,
+            name: "mutate".to_string(),
+            kind: SymbolKind::Function,
+            source_uri: file_uri.clone(),
+            defined_line: 5,
             defined_column: 0,
-            signature: None,
+            signature: Some("mutate <- function(x) { x + 1 }".to_string()),
         };
 
-        // Test hover on the symbol
-        // Mock get_cross_file_symbols to return our test symbol
-        // Note: In a real test, we'd need to set up the cross-file state properly
-        // For now, we'll test the definition extraction directly
-        let def_info = extract_definition_statement(&symbol, &state);
-        assert!(def_info.is_some());
-        let def_info = def_info.unwrap();
-        assert_eq!(def_info.statement, "my_var <- 42");
+        // Verify this is NOT detected as a package export
+        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
+        assert_eq!(
+            package_name, None,
+            "Local definition should not be detected as package export"
+        );
+    }
+
+    // ============================================================================
+    // Tests for collect_missing_package_diagnostics - Task 10.3
+    // ============================================================================
+
+    #[test]
+    fn test_missing_package_diagnostic_emitted() {
+        // Test that a diagnostic is emitted for a non-installed package
+        // Validates: Requirement 15.1
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "__nonexistent_package_xyz__".to_string(),
+                line: 0,
+                column: 30,
+                function_scope: None,
+            });
+
+        let state = WorldState::new(Vec::new());
+        let mut diagnostics = Vec::new();
+
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
+
+        assert_eq!(
+            diagnostics.len(),
+            1,
+            "Should emit one diagnostic for missing package"
+        );
+        assert!(diagnostics[0]
+            .message
+            .contains("__nonexistent_package_xyz__"));
+        assert!(diagnostics[0].message.contains("not installed"));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_missing_package_diagnostic_not_emitted_for_base_package() {
+        // Test that no diagnostic is emitted for base packages
+        // Validates: Requirement 15.1 (base packages are always available)
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "base".to_string(),
+                line: 0,
+                column: 15,
+                function_scope: None,
+            });
+
+        let mut state = WorldState::new(Vec::new());
+        // Ensure base is in base_packages by creating a new PackageLibrary
+        let mut base_packages = std::collections::HashSet::new();
+        base_packages.insert("base".to_string());
+        let mut pkg_lib = crate::package_library::PackageLibrary::new_empty();
+        pkg_lib.set_base_packages(base_packages);
+        state.package_library = std::sync::Arc::new(pkg_lib);
+
+        let mut diagnostics = Vec::new();
+
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
+
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should not emit diagnostic for base package"
+        );
     }
 
     #[test]
-    fn test_hover_same_file_location_format() {
-        let library_paths = r_env::find_library_paths();
-        let _state = WorldState::new(library_paths);
-
-        let uri = Url::parse("file:///test.R").unwrap();
-        let def_info = DefinitionInfo {
-            statement: "my_var <- 42".to_string(),
-            source_uri: uri.clone(),
-            line: 0, // 0-based
-            column: 0,
-        };
+    fn test_missing_package_diagnostic_ignored_line() {
+        // Test that diagnostics are not emitted for ignored lines
+        // Validates: Requirement 15.1 with @lsp-ignore support
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "__nonexistent_package_xyz__".to_string(),
+                line: 5,
+                column: 30,
+                function_scope: None,
+            });
+        // Mark line 5 as ignored
+        meta.ignored_lines.insert(5);
 
-        // Test same-file location formatting
-        let escaped_statement = escape_markdown(&def_info.statement);
-        let mut value = String::new();
-        value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
+        let state = WorldState::new(Vec::new());
+        let mut diagnostics = Vec::new();
 
-        if def_info.source_uri == uri {
-            value.push_str(&format!("this file, line {}", def_info.line + 1)); // 1-based
-        }
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
 
-        assert!(value.contains("```r\nmy\\_var <- 42\n```"));
-        assert!(value.contains("this file, line 1"));
-        assert!(value.contains("\n\n")); // Blank line separator
+        assert_eq!(
+            diagnostics.len(),
+            0,
+            "Should not emit diagnostic for ignored line"
+        );
     }
 
     #[test]
-    fn test_hover_cross_file_hyperlink_format() {
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
-        state.workspace_folders = vec![Url::parse("file:///workspace/").unwrap()];
+    fn test_missing_package_diagnostic_multiple_packages() {
+        // Test that diagnostics are emitted for multiple missing packages
+        // Validates: Requirement 15.1
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "__missing_pkg1__".to_string(),
+                line: 0,
+                column: 20,
+                function_scope: None,
+            });
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "__missing_pkg2__".to_string(),
+                line: 1,
+                column: 20,
+                function_scope: None,
+            });
 
-        let current_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let def_uri = Url::parse("file:///workspace/utils/helper.R").unwrap();
+        let state = WorldState::new(Vec::new());
+        let mut diagnostics = Vec::new();
 
-        let def_info = DefinitionInfo {
-            statement: "helper_func <- function(x) { x + 1 }".to_string(),
-            source_uri: def_uri.clone(),
-            line: 5, // 0-based
-            column: 0,
-        };
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
 
-        // Test cross-file location formatting
-        let escaped_statement = escape_markdown(&def_info.statement);
-        let mut value = String::new();
-        value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
+        assert_eq!(
+            diagnostics.len(),
+            2,
+            "Should emit diagnostics for both missing packages"
+        );
+        assert!(diagnostics[0].message.contains("__missing_pkg1__"));
+        assert!(diagnostics[1].message.contains("__missing_pkg2__"));
+    }
 
-        if def_info.source_uri != current_uri {
-            let relative_path =
-                compute_relative_path(&def_info.source_uri, state.workspace_folders.first());
-            let absolute_path = def_info.source_uri.as_str();
-            value.push_str(&format!(
-                "[{}]({}), line {}",
-                relative_path,
-                absolute_path,
-                def_info.line + 1
-            ));
+    /// Creates a `PackageLibrary` whose `lib_paths` point at a temporary
+    /// directory containing one subdirectory per name in `installed`, so
+    /// `installed_package_names` reports exactly that set.
+    fn package_library_with_installed(installed: &[&str]) -> (tempfile::TempDir, WorldState) {
+        use tempfile::TempDir;
+
+        let tmp = TempDir::new().expect("create temp lib dir");
+        for name in installed {
+            std::fs::create_dir(tmp.path().join(name)).expect("create package dir");
         }
+        let mut state = WorldState::new(Vec::new());
+        let mut pkg_lib = crate::package_library::PackageLibrary::new_empty();
+        pkg_lib.set_lib_paths(vec![tmp.path().to_path_buf()]);
+        state.package_library = std::sync::Arc::new(pkg_lib);
+        (tmp, state)
+    }
 
-        assert!(value.contains("```r\nhelper\\_func <- function\\(x\\) { x + 1 }\n```"));
-        assert!(value.contains("[utils/helper.R](file:///workspace/utils/helper.R), line 6"));
-        assert!(value.contains("\n\n")); // Blank line separator
+    #[test]
+    fn test_missing_package_diagnostic_suggests_close_typo() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "dpylr".to_string(),
+                line: 0,
+                column: 14,
+                function_scope: None,
+            });
+
+        let mut diagnostics = Vec::new();
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("not installed"));
+        assert!(
+            diagnostics[0].message.contains("Did you mean 'dplyr'?"),
+            "message was: {}",
+            diagnostics[0].message
+        );
     }
 
     #[test]
-    fn test_hover_markdown_code_block_formatting() {
-        let statement = "my_var <- c(1, 2, 3) # comment with *special* chars";
-        let escaped = escape_markdown(statement);
+    fn test_missing_package_diagnostic_no_suggestion_when_nothing_close() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "__totally_unrelated_name__".to_string(),
+                line: 0,
+                column: 30,
+                function_scope: None,
+            });
 
-        let formatted = format!("```r\n{}\n```", escaped);
+        let mut diagnostics = Vec::new();
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
 
-        assert!(formatted.starts_with("```r\n"));
-        assert!(formatted.ends_with("\n```"));
-        assert!(formatted.contains("\\*special\\*")); // Markdown chars should be escaped
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].message.contains("Did you mean"));
     }
 
     #[test]
-    fn test_hover_blank_line_separator() {
-        let def_info = DefinitionInfo {
-            statement: "test_func <- function() {}".to_string(),
-            source_uri: Url::parse("file:///test.R").unwrap(),
-            line: 0,
-            column: 0,
-        };
+    fn test_missing_package_diagnostic_suggests_top_three_sorted() {
+        let (_tmp, state) =
+            package_library_with_installed(&["stringr", "stringi", "stringx", "purrr"]);
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "string".to_string(),
+                line: 0,
+                column: 15,
+                function_scope: None,
+            });
 
-        let escaped_statement = escape_markdown(&def_info.statement);
-        let mut value = String::new();
-        value.push_str(&format!("```r\n{}\n```\n\n", escaped_statement));
-        value.push_str("this file, line 1");
+        let mut diagnostics = Vec::new();
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
 
-        // Should have exactly one blank line between code block and location
-        assert!(value.contains("```\n\nthis file"));
-        assert!(!value.contains("```\n\n\nthis file")); // Not two blank lines
-        assert!(!value.contains("```\nthis file")); // Not zero blank lines
+        assert_eq!(diagnostics.len(), 1);
+        assert!(
+            diagnostics[0]
+                .message
+                .contains("'stringi', 'stringr', 'stringx'"),
+            "message was: {}",
+            diagnostics[0].message
+        );
+        assert!(!diagnostics[0].message.contains("purrr"));
     }
 
     #[test]
-    fn test_cross_file_hover_resolution() {
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+    fn test_missing_package_diagnostic_excludes_base_package_candidates() {
+        let (_tmp, mut state) = package_library_with_installed(&["stats4"]);
+        let mut base_packages = std::collections::HashSet::new();
+        base_packages.insert("stats".to_string());
+        let mut pkg_lib = crate::package_library::PackageLibrary::new_empty();
+        pkg_lib.set_lib_paths(state.package_library.lib_paths().to_vec());
+        pkg_lib.set_base_packages(base_packages);
+        state.package_library = std::sync::Arc::new(pkg_lib);
 
-        // Create main.R that sources utils.R
-        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "stat".to_string(),
+                line: 0,
+                column: 13,
+                function_scope: None,
+            });
 
-        let main_code = r#"source("utils.R")
-result <- helper_func(42)"#;
+        let mut diagnostics = Vec::new();
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
 
-        let utils_code = r#"helper_func <- function(x) {
-    x * 2
-}"#;
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].message.contains("'stats'"));
+    }
 
-        // Add documents to state
-        state
-            .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
-        state
-            .documents
-            .insert(utils_uri.clone(), Document::new(utils_code, None));
+    #[test]
+    fn test_missing_package_diagnostic_no_suggestion_for_empty_name() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: String::new(),
+                line: 0,
+                column: 0,
+                function_scope: None,
+            });
 
-        // Update cross-file graph
-        state.cross_file_graph.update_file(
-            &main_uri,
-            &crate::cross_file::extract_metadata(main_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &utils_uri,
-            &crate::cross_file::extract_metadata(utils_code),
-            None,
-            |_| None,
-        );
+        let mut diagnostics = Vec::new();
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
 
-        // Test hover on helper_func in main.R (line 1, after source call)
-        let position = Position::new(1, 10); // Position of "helper_func"
-        let hover_result = hover_blocking(&state, &main_uri, position);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].message.contains("Did you mean"));
+    }
 
-        assert!(hover_result.is_some());
-        let hover = hover_result.unwrap();
+    #[test]
+    fn test_missing_package_diagnostic_ignores_case_only_difference() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let mut meta = crate::cross_file::CrossFileMetadata::default();
+        meta.library_calls
+            .push(crate::cross_file::source_detect::LibraryCall {
+                package: "Dplyr".to_string(),
+                line: 0,
+                column: 14,
+                function_scope: None,
+            });
 
-        if let HoverContents::Markup(content) = hover.contents {
-            // Code blocks don't need escaping - content should be unescaped
-            assert!(content.value.contains("helper_func"));
-            assert!(content.value.contains("function(x)"));
-            assert!(content.value.contains("utils.R")); // Should show cross-file source
-        } else {
-            panic!("Expected markup content");
-        }
+        let mut diagnostics = Vec::new();
+        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].message.contains("Did you mean"));
     }
 
+    // ============================================================================
+    // Tests for hover shadowing - Task 12.3
+    // ============================================================================
+
     #[test]
-    fn test_hover_symbol_shadowing() {
+    fn test_hover_local_definition_shadows_package_export() {
+        // Test that when a local definition shadows a package export,
+        // hover shows the local definition, not the package export.
+        // Validates: Requirement 10.4
         let library_paths = r_env::find_library_paths();
         let mut state = WorldState::new(library_paths);
 
-        // Create files with shadowing: local definition should take precedence
-        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
-
-        let main_code = r#"source("utils.R")
-my_func <- function(a, b) { a + b }  # Local definition shadows utils.R
-result <- my_func(1, 2)"#;
+        let uri = Url::parse("file:///workspace/main.R").unwrap();
 
-        let utils_code = r#"my_func <- function(x) { x * 2 }  # Will be shadowed"#;
+        // Code that loads a package and then defines a local function with the same name
+        // as a package export. The local definition should shadow the package export.
+        let code = r#"library(dplyr)
+mutate <- function(x, y) { x + y }  # Local definition shadows dplyr::mutate
+result <- mutate(1, 2)"#;
 
         state
             .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
-        state
-            .documents
-            .insert(utils_uri.clone(), Document::new(utils_code, None));
+            .insert(uri.clone(), Document::new(code, None));
 
-        // Update cross-file graph
-        state.cross_file_graph.update_file(
-            &main_uri,
-            &crate::cross_file::extract_metadata(main_code),
-            None,
-            |_| None,
-        );
+        // Update cross-file graph with metadata
         state.cross_file_graph.update_file(
-            &utils_uri,
-            &crate::cross_file::extract_metadata(utils_code),
+            &uri,
+            &crate::cross_file::extract_metadata(code),
             None,
             |_| None,
         );
 
-        // Test hover on my_func usage (should show local definition, not utils.R)
-        let position = Position::new(2, 10); // Position of "my_func" in usage
-        let hover_result = hover_blocking(&state, &main_uri, position);
+        // Test hover on "mutate" usage (line 2, position 10)
+        let position = Position::new(2, 10);
+        let hover_result = hover_blocking(&state, &uri, position);
 
-        assert!(hover_result.is_some());
+        assert!(hover_result.is_some(), "Hover should return a result");
         let hover = hover_result.unwrap();
 
         if let HoverContents::Markup(content) = hover.contents {
-            // Code blocks don't need escaping - content should be unescaped
-            assert!(content.value.contains("my_func"));
-            assert!(content.value.contains("(a, b)")); // Local signature, not (x)
-            assert!(content.value.contains("this file")); // Should be local, not cross-file
+            // Should show local definition signature (x, y), not dplyr's mutate
+            assert!(
+                content.value.contains("mutate"),
+                "Should contain function name"
+            );
+            assert!(
+                content.value.contains("(x, y)"),
+                "Should show local signature (x, y), not dplyr's signature"
+            );
+            // Should NOT show package attribution
+            assert!(
+                !content.value.contains("{dplyr}"),
+                "Should NOT show package attribution for shadowed symbol"
+            );
+            // Should show local file location
+            assert!(
+                content.value.contains("this file"),
+                "Should show local file location"
+            );
         } else {
             panic!("Expected markup content");
         }
     }
 
     #[test]
-    fn test_hover_builtin_function_fallback() {
-        let library_paths = r_env::find_library_paths();
-        let state = WorldState::new(library_paths);
+    fn test_hover_shadowing_scope_resolution_returns_local() {
+        // Test that scope resolution returns the local definition when it shadows a package export.
+        // This verifies the underlying mechanism that hover relies on.
+        // Validates: Requirement 10.4
+        use crate::cross_file::scope::{compute_artifacts, scope_at_position_with_packages};
+        use std::collections::HashSet;
 
-        let uri = Url::parse("file:///test.R").unwrap();
-        let code = r#"result <- mean(c(1, 2, 3))"#;
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
+
+        // Code with library() and local definition of same name
+        let code = r#"library(dplyr)
+filter <- function(x) { x > 0 }
+result <- filter(c(1, -2, 3))"#;
 
+        // Use Document::new to parse the code (same as other tests)
         let doc = Document::new(code, None);
-        let tree = doc.tree.as_ref().unwrap();
-        let text = doc.text();
+        let tree = doc.tree.as_ref().expect("Should parse successfully");
+        let artifacts = compute_artifacts(&uri, tree, code);
 
-        // Find the "mean" identifier
-        let point = tree_sitter::Point::new(0, 10); // Position of "mean"
-        let node = tree
-            .root_node()
-            .descendant_for_point_range(point, point)
-            .unwrap();
-        assert_eq!(node.kind(), "identifier");
-        assert_eq!(&text[node.byte_range()], "mean");
+        // Create a mock package exports callback that returns "filter" for dplyr
+        let get_exports = |pkg: &str| -> HashSet<String> {
+            if pkg == "dplyr" {
+                let mut exports = HashSet::new();
+                exports.insert("filter".to_string());
+                exports
+            } else {
+                HashSet::new()
+            }
+        };
 
-        // Test hover should fall back to R help for built-in functions
-        let position = Position::new(0, 10);
+        let base_exports = HashSet::new();
 
-        // Mock the state with the document
-        let mut test_state = state;
-        test_state.documents.insert(uri.clone(), doc);
+        // Query scope at line 2 (after both library and local definition)
+        let scope = scope_at_position_with_packages(&artifacts, 2, 10, &get_exports, &base_exports);
 
-        let hover_result = hover_blocking(&test_state, &uri, position);
+        // Symbol should be in scope
+        assert!(
+            scope.symbols.contains_key("filter"),
+            "filter should be in scope"
+        );
 
-        // Should return hover info (either from help cache or R subprocess)
-        // The exact content depends on R availability, but structure should be consistent
-        if let Some(hover) = hover_result {
-            if let HoverContents::Markup(content) = hover.contents {
-                assert!(content.kind == MarkupKind::Markdown);
-                assert!(content.value.starts_with("```"));
-                assert!(content.value.ends_with("```"));
-            } else {
-                panic!("Expected markup content");
+        // The symbol should be from the local definition, not the package
+        let symbol = scope.symbols.get("filter").unwrap();
+        assert!(
+            !symbol.source_uri.as_str().starts_with("package:"),
+            "filter should be from local definition, not package. Got URI: '{}'",
+            symbol.source_uri.as_str()
+        );
+        assert_eq!(
+            symbol.source_uri, uri,
+            "filter should be from the local file"
+        );
+    }
+
+    #[test]
+    fn test_hover_package_export_shown_when_no_local_shadow() {
+        // Test that when there's no local definition, hover shows the package export.
+        // This is the complement to test_hover_local_definition_shadows_package_export.
+        // Validates: Requirements 10.1, 10.4
+        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+
+        // Create a symbol that represents a package export
+        let package_uri = Url::parse("package:dplyr").unwrap();
+        let symbol = ScopedSymbol {
+            name: "mutate".to_string(),
+            kind: SymbolKind::Function,
+            source_uri: package_uri.clone(),
+            defined_line: 0,
+            defined_column: 0,
+            signature: Some("mutate(.data, ...)".to_string()),
+        };
+
+        // Verify this IS detected as a package export
+        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
+        assert_eq!(
+            package_name,
+            Some("dplyr"),
+            "Package export should be detected"
+        );
+
+        // Verify the formatting that would be used in hover
+        let mut value = String::new();
+        if let Some(pkg) = package_name {
+            if let Some(sig) = &symbol.signature {
+                value.push_str(&format!("```r\n{}\n```\n", sig));
             }
+            value.push_str(&format!("\nfrom {{{}}}", pkg));
         }
-        // Note: We don't assert hover_result.is_some() because R might not be available in CI
+
+        assert!(
+            value.contains("mutate(.data, ...)"),
+            "Should show function signature"
+        );
+        assert!(
+            value.contains("from {dplyr}"),
+            "Should show package attribution"
+        );
     }
 
     #[test]
-    fn test_hover_undefined_symbol_returns_none() {
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+    fn test_hover_shadowing_position_aware() {
+        // Test that shadowing is position-aware: before the local definition,
+        // the package export should be shown; after, the local definition.
+        // Validates: Requirement 10.4
+        use crate::cross_file::scope::{compute_artifacts, scope_at_position_with_packages};
+        use std::collections::HashSet;
 
-        let uri = Url::parse("file:///test.R").unwrap();
-        let code = r#"result <- undefined_symbol_that_does_not_exist"#;
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-        state
-            .documents
-            .insert(uri.clone(), Document::new(code, None));
+        // Code with library() first, then local definition later
+        let code = r#"library(dplyr)
+x <- mutate(df, y = 1)  # Uses package export
+mutate <- function(x) { x + 1 }  # Local definition
+z <- mutate(5)  # Uses local definition"#;
 
-        // Test hover on undefined symbol
-        let position = Position::new(0, 10); // Position of "undefined_symbol_that_does_not_exist"
-        let hover_result = hover_blocking(&state, &uri, position);
+        // Use Document::new to parse the code (same as other tests)
+        let doc = Document::new(code, None);
+        let tree = doc.tree.as_ref().expect("Should parse successfully");
+        let artifacts = compute_artifacts(&uri, tree, code);
 
-        // Should return None for truly undefined symbols (after trying all fallbacks)
-        // This tests the graceful handling when no definition is found anywhere
-        assert!(hover_result.is_none());
+        // Create a mock package exports callback
+        let get_exports = |pkg: &str| -> HashSet<String> {
+            if pkg == "dplyr" {
+                let mut exports = HashSet::new();
+                exports.insert("mutate".to_string());
+                exports
+            } else {
+                HashSet::new()
+            }
+        };
+
+        let base_exports = HashSet::new();
+
+        // Query scope at line 1 (before local definition) - should get package export
+        let scope_before =
+            scope_at_position_with_packages(&artifacts, 1, 5, &get_exports, &base_exports);
+        assert!(
+            scope_before.symbols.contains_key("mutate"),
+            "mutate should be in scope before local def"
+        );
+        let symbol_before = scope_before.symbols.get("mutate").unwrap();
+        assert!(
+            symbol_before.source_uri.as_str().starts_with("package:"),
+            "Before local definition, mutate should be from package. Got URI: '{}'",
+            symbol_before.source_uri.as_str()
+        );
+
+        // Query scope at line 3 (after local definition) - should get local definition
+        let scope_after =
+            scope_at_position_with_packages(&artifacts, 3, 5, &get_exports, &base_exports);
+        assert!(
+            scope_after.symbols.contains_key("mutate"),
+            "mutate should be in scope after local def"
+        );
+        let symbol_after = scope_after.symbols.get("mutate").unwrap();
+        assert!(
+            !symbol_after.source_uri.as_str().starts_with("package:"),
+            "After local definition, mutate should be from local file. Got URI: '{}'",
+            symbol_after.source_uri.as_str()
+        );
+        assert_eq!(
+            symbol_after.source_uri, uri,
+            "mutate should be from the local file"
+        );
     }
 
-    #[test]
-    fn test_hover_graceful_fallback_missing_definition_file() {
-        use crate::cross_file::ScopedSymbol;
-
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
-
-        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let missing_uri = Url::parse("file:///workspace/missing.R").unwrap(); // File doesn't exist
-
-        let main_code = r#"# Symbol from missing file
-result <- missing_func(42)"#;
+    // ============================================================================
+    // Tests for goto_definition package handling - Task 13.1
+    // ============================================================================
 
-        state
-            .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
+    /// Verifies that symbols originating from packages are treated as non-navigable.
+    ///
+    /// This test constructs a `ScopedSymbol` whose `source_uri` uses the `package:`
+    /// scheme and asserts that such URIs are recognized as package exports (which
+    /// goto-definition should not navigate into).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    /// use url::Url;
+    ///
+    /// let package_uri = Url::parse("package:dplyr").unwrap();
+    /// let symbol = ScopedSymbol {
+    ///     name: "mutate".to_string(),
+    ///     kind: SymbolKind::Function,
+    ///     source_uri: package_uri.clone(),
+    ///     defined_line: 0,
+    ///     defined_column: 0,
+    ///     signature: Some("mutate(.data, ...)".to_string()),
+    /// };
+    ///
+    /// assert!(symbol.source_uri.as_str().starts_with("package:"));
+    /// let is_package_export = symbol.source_uri.as_str().starts_with("package:");
+    /// assert!(is_package_export);
+    /// let package_name = symbol.source_uri.as_str().strip_prefix("package:");
+    /// assert_eq!(package_name, Some("dplyr"));
+    /// ```
+    #[test]
+    fn test_goto_definition_returns_none_for_package_exports() {
+        // Test that goto_definition returns None for package exports
+        // since package source files are not navigable
+        // Validates: Requirements 11.1, 11.2
+        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
 
-        // Create a scoped symbol that references a missing file
+        // Create a symbol with a package URI
+        let package_uri = Url::parse("package:dplyr").unwrap();
         let symbol = ScopedSymbol {
-            name: "missing_func".to_string(),
-            kind: crate::cross_file::SymbolKind::Function,
-            source_uri: missing_uri, // This file doesn't exist in state
+            name: "mutate".to_string(),
+            kind: SymbolKind::Function,
+            source_uri: package_uri.clone(),
             defined_line: 0,
             defined_column: 0,
-            signature: Some("missing_func(x)".to_string()),
+            signature: Some("mutate(.data, ...)".to_string()),
         };
 
-        // Test extract_definition_statement with missing file (should return None)
-        let def_info = extract_definition_statement(&symbol, &state);
+        // Verify the package URI is detected correctly
         assert!(
-            def_info.is_none(),
-            "Should return None when source file is missing"
+            symbol.source_uri.as_str().starts_with("package:"),
+            "Package export should have package: URI prefix"
         );
 
-        // The hover function should gracefully fall back to showing just the signature
-        // This is tested implicitly in the hover function's match arm for None from extract_definition_statement
+        // The goto_definition logic should skip package exports
+        // This test verifies the detection logic used in goto_definition
+        let is_package_export = symbol.source_uri.as_str().starts_with("package:");
+        assert!(is_package_export, "Should detect package export");
+
+        // Extract package name for logging
+        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
+        assert_eq!(package_name, Some("dplyr"), "Should extract package name");
     }
 
     #[test]
-    fn test_hover_position_aware_scope_resolution() {
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+    fn test_goto_definition_navigates_to_local_definition() {
+        // Test that goto_definition navigates to local definitions (not package exports)
+        // Validates: Requirement 11.3 (shadowing)
+        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
 
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
-        let code = r#"# Before source call - symbol not available
-result1 <- helper_func(1)  # Should not resolve
+        // Create a symbol with a file URI (local definition)
+        let file_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let symbol = ScopedSymbol {
+            name: "mutate".to_string(),
+            kind: SymbolKind::Function,
+            source_uri: file_uri.clone(),
+            defined_line: 5,
+            defined_column: 0,
+            signature: Some("mutate <- function(x) { x + 1 }".to_string()),
+        };
 
-source("utils.R")
+        // Verify this is NOT a package export
+        assert!(
+            !symbol.source_uri.as_str().starts_with("package:"),
+            "Local definition should not have package: URI prefix"
+        );
 
-# After source call - symbol available  
-result2 <- helper_func(2)  # Should resolve"#;
+        // The goto_definition logic should navigate to local definitions
+        let is_package_export = symbol.source_uri.as_str().starts_with("package:");
+        assert!(!is_package_export, "Should not detect as package export");
 
-        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
-        let utils_code = r#"helper_func <- function(x) { x * 2 }"#;
+        // Verify the location would be correct
+        let expected_line = symbol.defined_line;
+        let expected_column = symbol.defined_column;
+        assert_eq!(expected_line, 5, "Should navigate to correct line");
+        assert_eq!(expected_column, 0, "Should navigate to correct column");
+    }
 
-        state
-            .documents
-            .insert(uri.clone(), Document::new(code, None));
-        state
-            .documents
-            .insert(utils_uri.clone(), Document::new(utils_code, None));
+    #[test]
+    fn test_goto_definition_package_uri_formats() {
+        // Test various package URI formats are correctly detected
+        // Validates: Requirements 11.1, 11.2
 
-        // Update cross-file graph
-        state.cross_file_graph.update_file(
-            &uri,
-            &crate::cross_file::extract_metadata(code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &utils_uri,
-            &crate::cross_file::extract_metadata(utils_code),
-            None,
-            |_| None,
-        );
+        // Standard package URI
+        let dplyr_uri = Url::parse("package:dplyr").unwrap();
+        assert!(dplyr_uri.as_str().starts_with("package:"));
+        assert_eq!(dplyr_uri.as_str().strip_prefix("package:"), Some("dplyr"));
 
-        // Test hover before source call (line 1) - should not find cross-file symbol
-        let position_before = Position::new(1, 11); // "helper_func" before source()
-        let cross_file_symbols_before = get_cross_file_symbols(
-            &state,
-            &uri,
-            position_before.line,
-            position_before.character,
-        );
-        assert!(
-            !cross_file_symbols_before.contains_key("helper_func"),
-            "Symbol should not be available before source() call"
-        );
+        // Base package URI
+        let base_uri = Url::parse("package:base").unwrap();
+        assert!(base_uri.as_str().starts_with("package:"));
+        assert_eq!(base_uri.as_str().strip_prefix("package:"), Some("base"));
 
-        // Test hover after source call (line 5) - should find cross-file symbol
-        let position_after = Position::new(5, 11); // "helper_func" after source()
-        let cross_file_symbols_after =
-            get_cross_file_symbols(&state, &uri, position_after.line, position_after.character);
-        assert!(
-            cross_file_symbols_after.contains_key("helper_func"),
-            "Symbol should be available after source() call"
+        // Package with dots in name
+        let data_table_uri = Url::parse("package:data.table").unwrap();
+        assert!(data_table_uri.as_str().starts_with("package:"));
+        assert_eq!(
+            data_table_uri.as_str().strip_prefix("package:"),
+            Some("data.table")
         );
-    }
 
-    #[test]
-    fn test_hover_uses_dependency_graph_correctly() {
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+        // File URIs should NOT be detected as packages
+        let file_uri = Url::parse("file:///workspace/test.R").unwrap();
+        assert!(!file_uri.as_str().starts_with("package:"));
+        assert_eq!(file_uri.as_str().strip_prefix("package:"), None);
+    }
 
-        // Create a chain: main.R -> utils.R -> helpers.R
-        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
-        let helpers_uri = Url::parse("file:///workspace/helpers.R").unwrap();
+    // ============================================================================
+    // Tests for navigating into installed package sources (package_export_location)
+    // ============================================================================
 
-        let main_code = r#"source("utils.R")
-result <- process_data(42)"#;
+    #[test]
+    fn test_package_export_location_navigates_into_r_source() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let pkg_dir = _tmp.path().join("dplyr");
+        let r_dir = pkg_dir.join("R");
+        std::fs::create_dir(&r_dir).unwrap();
+        std::fs::write(r_dir.join("mutate.R"), "mutate <- function(x) x\n").unwrap();
+
+        let location = package_export_location(&state, "dplyr", "mutate")
+            .expect("should find dplyr::mutate's R source");
+
+        assert_eq!(location.uri.as_str(), "raven-package:dplyr/R/mutate.R");
+        assert_eq!(location.range.start, Position::new(0, 0));
+        assert_eq!(location.range.end, Position::new(0, "mutate".len() as u32));
+    }
 
-        let utils_code = r#"source("helpers.R")
-process_data <- function(x) {
-    transform_value(x) + 10
-}"#;
+    #[test]
+    fn test_package_export_location_none_without_r_source() {
+        // `dplyr` is "installed" (its directory exists) but has no R/ directory,
+        // matching a byte-compiled library install with no source to point at.
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
 
-        let helpers_code = r#"transform_value <- function(x) { x * 2 }"#;
+        assert!(package_export_location(&state, "dplyr", "mutate").is_none());
+    }
 
-        state
-            .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
-        state
-            .documents
-            .insert(utils_uri.clone(), Document::new(utils_code, None));
-        state
-            .documents
-            .insert(helpers_uri.clone(), Document::new(helpers_code, None));
+    #[test]
+    fn test_package_export_location_none_for_unknown_package() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
 
-        // Update cross-file graph for all files
-        state.cross_file_graph.update_file(
-            &main_uri,
-            &crate::cross_file::extract_metadata(main_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &utils_uri,
-            &crate::cross_file::extract_metadata(utils_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &helpers_uri,
-            &crate::cross_file::extract_metadata(helpers_code),
-            None,
-            |_| None,
-        );
+        assert!(package_export_location(&state, "__not_installed__", "mutate").is_none());
+    }
 
-        // Test hover on transform_value in utils.R (should resolve through chain)
-        let position = Position::new(2, 4); // "transform_value" in utils.R
-        let cross_file_symbols =
-            get_cross_file_symbols(&state, &utils_uri, position.line, position.character);
+    #[test]
+    fn test_read_package_source_round_trips_through_virtual_uri() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let r_dir = _tmp.path().join("dplyr").join("R");
+        std::fs::create_dir(&r_dir).unwrap();
+        std::fs::write(r_dir.join("mutate.R"), "mutate <- function(x) x\n").unwrap();
 
-        assert!(
-            cross_file_symbols.contains_key("transform_value"),
-            "Should resolve symbol through dependency chain"
-        );
+        let location = package_export_location(&state, "dplyr", "mutate").unwrap();
+        let content = read_package_source(&state, &location.uri)
+            .expect("should read the synthetic document's content");
 
-        let symbol = &cross_file_symbols["transform_value"];
-        assert_eq!(
-            symbol.source_uri, helpers_uri,
-            "Should trace back to helpers.R"
-        );
+        assert_eq!(content, "mutate <- function(x) x\n");
+    }
+
+    #[test]
+    fn test_read_package_source_none_for_non_package_uri() {
+        let (_tmp, state) = package_library_with_installed(&["dplyr"]);
+        let uri = Url::parse("file:///workspace/main.R").unwrap();
+
+        assert!(read_package_source(&state, &uri).is_none());
     }
 
     // ============================================================================
-    // Task 17: Enhanced Variable Detection Hover Integration Tests
+    // Tests for goto_definition shadowing behavior - Task 13.2
     // ============================================================================
 
     #[test]
-    fn test_complete_workflow_for_loops_and_functions() {
+    fn test_goto_definition_local_shadows_package_export() {
+        // Test that when a local definition shadows a package export,
+        // goto_definition navigates to the local definition, not the package.
+        // Validates: Requirement 11.3
+
         let library_paths = r_env::find_library_paths();
         let mut state = WorldState::new(library_paths);
 
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
-        let code = r#"# Test for loops and function parameters
-process_data <- function(data, threshold = 0.5, ...) {
-    filtered <- data[data > threshold]
-    for (i in 1:10) {
-        for (j in 1:5) {
-            result <- i * j
-            if (result > threshold) {
-                print(result)
-            }
-        }
-    }
-    for (item in filtered) {
-        print(item)
-    }
-    return(filtered)
-}"#;
+        let uri = Url::parse("file:///workspace/main.R").unwrap();
+
+        // Code that loads a package and then defines a local function with the same name
+        // as a package export. The local definition should shadow the package export.
+        // "mutate" is defined locally on line 1 (0-indexed), shadowing dplyr::mutate
+        let code = r#"library(dplyr)
+mutate <- function(x, y) { x + y }
+result <- mutate(1, 2)"#;
 
         state
             .documents
             .insert(uri.clone(), Document::new(code, None));
 
-        // Test scope resolution includes all iterators and parameters
-        let positions = vec![
-            (Position::new(5, 12), "result", true), // result inside nested loop
-            (Position::new(4, 12), "i", true),      // i iterator
-            (Position::new(4, 18), "j", true),      // j iterator
-            (Position::new(12, 14), "item", true),  // item used inside the loop body
-            (Position::new(2, 20), "data", true),   // function parameter
-            (Position::new(6, 27), "threshold", true), // function parameter with default
-            (Position::new(14, 14), "filtered", true), // local variable used in return(filtered)
-        ];
-
-        for (position, symbol_name, should_exist) in positions {
-            let symbols = get_cross_file_symbols(&state, &uri, position.line, position.character);
-            if should_exist {
-                assert!(
-                    symbols.contains_key(symbol_name),
-                    "Symbol '{}' should be in scope at line {}, col {}",
-                    symbol_name,
-                    position.line + 1,
-                    position.character
-                );
-            } else {
-                assert!(
-                    !symbols.contains_key(symbol_name),
-                    "Symbol '{}' should NOT be in scope at line {}, col {}",
-                    symbol_name,
-                    position.line + 1,
-                    position.character
-                );
-            }
-        }
+        // Update cross-file graph with metadata
+        state.cross_file_graph.update_file(
+            &uri,
+            &crate::cross_file::extract_metadata(code),
+            None,
+            |_| None,
+        );
 
-        // Test no false-positive undefined variable diagnostics
-        let diagnostics = diagnostics(&state, &uri);
-        let undefined_errors: Vec<_> = diagnostics
-            .iter()
-            .filter(|d| d.message.contains("undefined") || d.message.contains("not found"))
-            .collect();
+        // Test goto_definition on "mutate" usage (line 2, position 10 - within "mutate")
+        let position = Position::new(2, 10);
+        let result = goto_definition(&state, &uri, position);
 
+        // Should navigate to local definition, not return None (which would happen for package exports)
         assert!(
-            undefined_errors.is_empty(),
-            "Should not have undefined variable errors for loop iterators and function parameters: {:?}",
-            undefined_errors
+            result.is_some(),
+            "goto_definition should return a result for shadowed symbol"
         );
 
-        // Test hover shows definition statements (no escaping needed in code blocks)
-        let hover_tests = vec![
-            (Position::new(4, 12), "i", "for (i in 1:10)"),
-            (Position::new(4, 18), "j", "for (j in 1:5)"),
-            (Position::new(12, 14), "item", "for (item in filtered)"),
-            (
-                Position::new(2, 20),
-                "data",
-                "process_data <- function(data, threshold = 0.5, ...)",
-            ),
-        ];
-
-        for (position, symbol_name, expected_statement) in hover_tests {
-            let hover_result = hover_blocking(&state, &uri, position);
-            if let Some(hover) = hover_result {
-                if let HoverContents::Markup(content) = hover.contents {
-                    assert!(
-                        content.value.contains(expected_statement),
-                        "Hover for '{}' should contain '{}', got: {}",
-                        symbol_name,
-                        expected_statement,
-                        content.value
-                    );
-                    assert!(
-                        content.value.contains("this file"),
-                        "Hover should show file location"
-                    );
-                }
-            }
+        if let Some(GotoDefinitionResponse::Scalar(location)) = result {
+            // Should navigate to the local definition on line 1
+            assert_eq!(location.uri, uri, "Should navigate to the same file");
+            assert_eq!(
+                location.range.start.line, 1,
+                "Should navigate to line 1 where local mutate is defined"
+            );
+            assert_eq!(
+                location.range.start.character, 0,
+                "Should navigate to column 0"
+            );
+        } else {
+            panic!("Expected Scalar response");
         }
     }
 
     #[test]
-    fn test_realistic_r_code_patterns() {
+    fn test_goto_definition_local_definition_found_first() {
+        // Test that goto_definition searches the current document first,
+        // ensuring local definitions are found before cross-file symbols.
+        // This is the core mechanism that enables shadowing.
+        // Validates: Requirement 11.3
+
         let library_paths = r_env::find_library_paths();
         let mut state = WorldState::new(library_paths);
 
-        // Create main file with realistic patterns
-        let main_uri = Url::parse("file:///workspace/analysis.R").unwrap();
-        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
-        let helpers_uri = Url::parse("file:///workspace/helpers.R").unwrap();
-
-        let main_code = r#"# Analysis script with realistic patterns
-source("utils.R")
-source("helpers.R", local = TRUE)
-
-# Nested loops with multiple iterators
-results <- list()
-for (i in 1:10) {
-    for (j in 1:5) {
-        value <- i * j
-        results[[paste0(i, "_", j)]] <- value
-    }
-}
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-# Function with parameters and locals
-analyze_data <- function(dataset, 
-                        min_threshold = 0.1,
-                        max_threshold = 0.9,
-                        ...) {
-    # Multi-line function definition
-    cleaned <- dataset[!is.na(dataset)]
-    
-    for (threshold in seq(min_threshold, max_threshold, 0.1)) {
-        filtered <- cleaned[cleaned > threshold]
-        cat("Threshold:", threshold, "Count:", length(filtered), "\n")
-    }
-    
-    return(cleaned)
-}
+        // Simple code with a local function definition and usage
+        let code = r#"my_func <- function(a, b) { a + b }
+result <- my_func(1, 2)"#;
 
-# Code with markdown special characters
-comment_with_stars <- "This has *asterisks* and _underscores_"
-backtick_var <- `special name with spaces`
-"#;
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        let utils_code = r#"# Utility functions
-utility_func <- function(x, y = 2) {
-    x ^ y
-}
+        // Test goto_definition on "my_func" usage (line 1, position 10)
+        let position = Position::new(1, 10);
+        let result = goto_definition(&state, &uri, position);
 
-CONSTANT_VALUE <- 42
-"#;
+        assert!(
+            result.is_some(),
+            "goto_definition should find local definition"
+        );
 
-        let helpers_code = r#"# Helper functions (sourced with local=TRUE)
-helper_transform <- function(data) {
-    data * 2
-}
-"#;
+        if let Some(GotoDefinitionResponse::Scalar(location)) = result {
+            assert_eq!(location.uri, uri, "Should navigate to the same file");
+            assert_eq!(
+                location.range.start.line, 0,
+                "Should navigate to line 0 where my_func is defined"
+            );
+        } else {
+            panic!("Expected Scalar response");
+        }
+    }
 
-        state
-            .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
-        state
-            .documents
-            .insert(utils_uri.clone(), Document::new(utils_code, None));
-        state
-            .documents
-            .insert(helpers_uri.clone(), Document::new(helpers_code, None));
+    /// Verifies that scope resolution prefers local definitions over package exports for goto-definition.
+    ///
+    /// Constructs a document containing a `library()` call and a local function named `filter`, computes
+    /// the cross-file scope at a position after the local definition, and asserts that the `filter`
+    /// symbol resolves to the local file (not a `package:` URI) and has the expected definition line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Confirms a local `filter` shadows the `dplyr` export when resolving definitions.
+    /// ```
+    #[test]
+    fn test_goto_definition_shadowing_scope_resolution() {
+        // Test that scope resolution correctly returns local definitions over package exports.
+        // This verifies the underlying mechanism that goto_definition relies on.
+        // Validates: Requirement 11.3
+        use crate::cross_file::scope::{compute_artifacts, scope_at_position_with_packages};
+        use std::collections::HashSet;
 
-        // Update cross-file graph
-        state.cross_file_graph.update_file(
-            &main_uri,
-            &crate::cross_file::extract_metadata(main_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &utils_uri,
-            &crate::cross_file::extract_metadata(utils_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &helpers_uri,
-            &crate::cross_file::extract_metadata(helpers_code),
-            None,
-            |_| None,
-        );
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-        // Test nested loop iterators are in scope
-        let nested_loop_position = Position::new(8, 8); // Inside nested loop
-        let symbols = get_cross_file_symbols(
-            &state,
-            &main_uri,
-            nested_loop_position.line,
-            nested_loop_position.character,
-        );
+        // Code with library() and local definition of same name
+        let code = r#"library(dplyr)
+filter <- function(x) { x > 0 }
+result <- filter(c(1, -2, 3))"#;
 
-        assert!(
-            symbols.contains_key("i"),
-            "Outer loop iterator 'i' should be in scope"
-        );
-        assert!(
-            symbols.contains_key("j"),
-            "Inner loop iterator 'j' should be in scope"
-        );
-        assert!(
-            symbols.contains_key("value"),
-            "Local variable 'value' should be in scope"
-        );
+        let doc = Document::new(code, None);
+        let tree = doc.tree.as_ref().expect("Should parse successfully");
+        let artifacts = compute_artifacts(&uri, tree, code);
 
-        // Test function parameters are in scope within function
-        let function_body_position = Position::new(19, 4); // Inside analyze_data function
-        let func_symbols = get_cross_file_symbols(
-            &state,
-            &main_uri,
-            function_body_position.line,
-            function_body_position.character,
-        );
+        // Create a mock package exports callback that returns "filter" for dplyr
+        let get_exports = |pkg: &str| -> HashSet<String> {
+            if pkg == "dplyr" {
+                let mut exports = HashSet::new();
+                exports.insert("filter".to_string());
+                exports
+            } else {
+                HashSet::new()
+            }
+        };
 
-        assert!(
-            func_symbols.contains_key("dataset"),
-            "Function parameter 'dataset' should be in scope"
-        );
-        assert!(
-            func_symbols.contains_key("min_threshold"),
-            "Function parameter 'min_threshold' should be in scope"
-        );
-        assert!(
-            func_symbols.contains_key("max_threshold"),
-            "Function parameter 'max_threshold' should be in scope"
-        );
-        assert!(
-            func_symbols.contains_key("cleaned"),
-            "Local variable 'cleaned' should be in scope"
-        );
+        let base_exports = HashSet::new();
 
-        // Test cross-file symbols are resolved correctly
-        let after_source_position = Position::new(4, 0); // After source() calls
-        let cross_symbols = get_cross_file_symbols(
-            &state,
-            &main_uri,
-            after_source_position.line,
-            after_source_position.character,
-        );
+        // Query scope at line 2 (after both library and local definition)
+        let scope = scope_at_position_with_packages(&artifacts, 2, 10, &get_exports, &base_exports);
 
+        // Symbol should be in scope
         assert!(
-            cross_symbols.contains_key("utility_func"),
-            "Should resolve utility_func from utils.R"
+            scope.symbols.contains_key("filter"),
+            "filter should be in scope"
         );
+
+        // The symbol should be from the local definition, not the package
+        let symbol = scope.symbols.get("filter").unwrap();
         assert!(
-            cross_symbols.contains_key("CONSTANT_VALUE"),
-            "Should resolve CONSTANT_VALUE from utils.R"
+            !symbol.source_uri.as_str().starts_with("package:"),
+            "filter should be from local definition, not package. Got URI: '{}'",
+            symbol.source_uri.as_str()
+        );
+        assert_eq!(
+            symbol.source_uri, uri,
+            "filter should be from the local file"
         );
-        // Note: helper_transform should NOT be available due to local=TRUE
-
-        // Test hover shows proper formatting for multi-line definitions
-        let multi_line_func_position = Position::new(13, 0); // analyze_data function name
-        let hover_result = hover_blocking(&state, &main_uri, multi_line_func_position);
-
-        if let Some(hover) = hover_result {
-            if let HoverContents::Markup(content) = hover.contents {
-                assert!(content.value.contains("analyze_data <- function(dataset,"));
-                assert!(content.value.contains("this file"));
-                // Should handle markdown special characters properly
-                assert!(!content.value.contains("*asterisks*")); // Should be escaped
-            }
-        }
-
-        // Test no false positives for valid symbols
-        let diagnostics = diagnostics(&state, &main_uri);
-        let undefined_errors: Vec<_> = diagnostics
-            .iter()
-            .filter(|d| d.message.contains("undefined"))
-            .collect();
 
-        // Should not report undefined errors for loop iterators, function parameters, or cross-file symbols
-        for error in &undefined_errors {
-            assert!(
-                !error.message.contains("i "),
-                "Should not report 'i' as undefined"
-            );
-            assert!(
-                !error.message.contains("j "),
-                "Should not report 'j' as undefined"
-            );
-            assert!(
-                !error.message.contains("dataset"),
-                "Should not report 'dataset' as undefined"
-            );
-            assert!(
-                !error.message.contains("utility_func"),
-                "Should not report 'utility_func' as undefined"
-            );
-        }
+        // Verify the definition position matches the local definition
+        assert_eq!(symbol.defined_line, 1, "filter should be defined on line 1");
     }
 
     #[test]
-    fn test_cross_file_local_scope_isolation() {
+    fn test_goto_definition_shadowing_position_aware() {
+        // Test that shadowing is position-aware: before the local definition,
+        // the package export would be used; after, the local definition.
+        // For goto_definition, this means:
+        // - Before local def: returns None (package export, not navigable)
+        // - After local def: returns local definition location
+        // Validates: Requirement 11.3
+
         let library_paths = r_env::find_library_paths();
         let mut state = WorldState::new(library_paths);
 
-        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let local_uri = Url::parse("file:///workspace/local_source.R").unwrap();
-        let global_uri = Url::parse("file:///workspace/global_source.R").unwrap();
-
-        let main_code = r#"# Test local vs global sourcing
-source("global_source.R")           # Global scope
-source("local_source.R", local = TRUE)  # Local scope
-
-# These should be available from global source
-global_result <- global_func(42)
-
-# These should NOT be available from local source
-# local_func(42)  # Would be undefined
-"#;
-
-        let global_code = r#"global_func <- function(x) { x + 1 }
-global_var <- 100"#;
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-        let local_code = r#"local_func <- function(x) { x * 2 }
-local_var <- 200"#;
+        // Code where package is loaded, then used, then shadowed, then used again
+        // Line 0: library(dplyr)
+        // Line 1: x <- filter(data)  # Uses dplyr::filter
+        // Line 2: filter <- function(x) { x > 0 }  # Local definition
+        // Line 3: y <- filter(data)  # Uses local filter
+        let code = r#"library(dplyr)
+x <- filter(data)
+filter <- function(x) { x > 0 }
+y <- filter(data)"#;
 
         state
             .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
-        state
-            .documents
-            .insert(global_uri.clone(), Document::new(global_code, None));
-        state
-            .documents
-            .insert(local_uri.clone(), Document::new(local_code, None));
-
-        // Update cross-file graph
-        state.cross_file_graph.update_file(
-            &main_uri,
-            &crate::cross_file::extract_metadata(main_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &global_uri,
-            &crate::cross_file::extract_metadata(global_code),
-            None,
-            |_| None,
-        );
+            .insert(uri.clone(), Document::new(code, None));
         state.cross_file_graph.update_file(
-            &local_uri,
-            &crate::cross_file::extract_metadata(local_code),
+            &uri,
+            &crate::cross_file::extract_metadata(code),
             None,
             |_| None,
         );
 
-        // Test symbols after both source calls
-        let position = Position::new(5, 0); // After both source() calls
-        let symbols = get_cross_file_symbols(&state, &main_uri, position.line, position.character);
+        // Test goto_definition on "filter" usage AFTER local definition (line 3, position 5)
+        let position_after = Position::new(3, 5);
+        let result_after = goto_definition(&state, &uri, position_after);
 
-        // Global source symbols should be available
-        assert!(
-            symbols.contains_key("global_func"),
-            "global_func should be available from global source"
-        );
+        // After local definition, should navigate to local definition
         assert!(
-            symbols.contains_key("global_var"),
-            "global_var should be available from global source"
+            result_after.is_some(),
+            "goto_definition should find local definition after shadowing"
         );
 
-        // Local source symbols should NOT be available in main scope
-        assert!(
-            !symbols.contains_key("local_func"),
-            "local_func should NOT be available from local source"
-        );
-        assert!(
-            !symbols.contains_key("local_var"),
-            "local_var should NOT be available from local source"
-        );
+        if let Some(GotoDefinitionResponse::Scalar(location)) = result_after {
+            assert_eq!(location.uri, uri, "Should navigate to the same file");
+            assert_eq!(
+                location.range.start.line, 2,
+                "Should navigate to line 2 where local filter is defined"
+            );
+        } else {
+            panic!("Expected Scalar response");
+        }
+    }
 
-        // Test hover on global symbol shows cross-file location
-        let hover_position = Position::new(5, 16); // "global_func" usage
-        let hover_result = hover_blocking(&state, &main_uri, hover_position);
+    #[test]
+    fn test_goto_definition_multiple_local_definitions() {
+        // Test that goto_definition finds the first local definition when
+        // there are multiple definitions of the same symbol.
+        // Validates: Requirement 11.3
+
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
+
+        // Code with multiple definitions of the same symbol
+        let code = r#"x <- 1
+x <- 2
+y <- x"#;
+
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        // Test goto_definition on "x" usage (line 2, position 5)
+        let position = Position::new(2, 5);
+        let result = goto_definition(&state, &uri, position);
 
-        if let Some(hover) = hover_result {
-            if let HoverContents::Markup(content) = hover.contents {
-                assert!(content.value.contains("global_func"));
-                assert!(
-                    content.value.contains("global_source.R"),
-                    "Should show cross-file source"
-                );
-            }
+        assert!(result.is_some(), "goto_definition should find definition");
+
+        if let Some(GotoDefinitionResponse::Scalar(location)) = result {
+            assert_eq!(location.uri, uri, "Should navigate to the same file");
+            // Position-aware definition finding returns the latest definition before usage
+            // So it should be line 1 (x <- 2), not line 0 (x <- 1)
+            assert_eq!(
+                location.range.start.line, 1,
+                "Should navigate to latest definition on line 1"
+            );
+        } else {
+            panic!("Expected Scalar response");
         }
     }
 
     #[test]
-    fn test_hover_hyperlink_formatting_with_special_paths() {
+    fn test_goto_definition_conditional_reassignment_returns_array_without_link_support() {
+        // A usage after a complete if/else that reassigns the same symbol in
+        // both branches could have bound to either one; without linkSupport
+        // the client can only express this as an Array of Locations.
         let library_paths = r_env::find_library_paths();
         let mut state = WorldState::new(library_paths);
-        state.workspace_folders = vec![Url::parse("file:///workspace/").unwrap()];
-
-        // Test various path scenarios
-        let main_uri = Url::parse("file:///workspace/src/analysis/main.R").unwrap();
-        let utils_uri = Url::parse("file:///workspace/utils/helpers with spaces.R").unwrap();
+        assert!(!state.definition_link_support);
 
-        let main_code = r#"source("../../utils/helpers with spaces.R")
-result <- helper_with_spaces(42)"#;
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-        let utils_code = r#"helper_with_spaces <- function(x) {
-    # Function with special characters in filename
-    x * 2
-}"#;
+        let code = r#"if (cond) { x <- 1 } else { x <- 2 }
+y <- x"#;
 
         state
             .documents
-            .insert(main_uri.clone(), Document::new(main_code, None));
-        state
-            .documents
-            .insert(utils_uri.clone(), Document::new(utils_code, None));
-
-        // Update cross-file graph
-        state.cross_file_graph.update_file(
-            &main_uri,
-            &crate::cross_file::extract_metadata(main_code),
-            None,
-            |_| None,
-        );
-        state.cross_file_graph.update_file(
-            &utils_uri,
-            &crate::cross_file::extract_metadata(utils_code),
-            None,
-            |_| None,
-        );
+            .insert(uri.clone(), Document::new(code, None));
 
-        // Test hover shows proper hyperlink formatting
-        let position = Position::new(1, 10); // "helper_with_spaces"
-        let hover_result = hover_blocking(&state, &main_uri, position);
+        // Usage of "x" on line 1, position 5
+        let position = Position::new(1, 5);
+        let result = goto_definition(&state, &uri, position);
 
-        if let Some(hover) = hover_result {
-            if let HoverContents::Markup(content) = hover.contents {
-                // Should contain properly formatted hyperlink
-                assert!(content.value.contains("[utils/helpers with spaces.R]"));
-                assert!(content
-                    .value
-                    .contains("file:///workspace/utils/helpers%20with%20spaces.R"));
-                assert!(content.value.contains("line 1"));
+        match result {
+            Some(GotoDefinitionResponse::Array(locations)) => {
+                assert_eq!(locations.len(), 2, "Should surface both branch bindings");
+                assert!(locations.iter().all(|loc| loc.uri == uri));
+                let lines: Vec<u32> = locations.iter().map(|loc| loc.range.start.line).collect();
+                assert!(lines.contains(&0), "Should include the 'then' branch");
             }
+            Some(GotoDefinitionResponse::Scalar(_)) => panic!("Expected Array response, got Scalar"),
+            Some(GotoDefinitionResponse::Link(_)) => panic!("Expected Array response, got Link"),
+            None => panic!("Expected Array response, got None"),
         }
     }
 
-    // ============================================================================
-    // Tests for hover package info - Task 12.1
-    // ============================================================================
-
     #[test]
-    fn test_hover_shows_package_name_for_package_exports() {
-        // Test that hover displays package name for package exports
-        // Validates: Requirement 10.1
-        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    fn test_goto_definition_conditional_reassignment_returns_link_with_link_support() {
+        // Same scenario, but with a client that advertised linkSupport: every
+        // result (including multiple ones) comes back as LocationLink, each
+        // carrying the origin identifier span.
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+        state.definition_link_support = true;
 
-        // Create a symbol with a package URI
-        let package_uri = Url::parse("package:dplyr").unwrap();
-        let symbol = ScopedSymbol {
-            name: "mutate".to_string(),
-            kind: SymbolKind::Variable,
-            source_uri: package_uri,
-            defined_line: 0,
-            defined_column: 0,
-            signature: None,
-        };
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-        // Verify the package name can be extracted from the URI
-        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
-        assert_eq!(
-            package_name,
-            Some("dplyr"),
-            "Should extract package name from URI"
-        );
+        let code = r#"if (cond) { x <- 1 } else { x <- 2 }
+y <- x"#;
 
-        // Test the formatting that would be used in hover
-        let mut value = String::new();
-        value.push_str(&format!("```r\n{}\n```\n", symbol.name));
-        if let Some(pkg) = package_name {
-            value.push_str(&format!("\nfrom {{{}}}", pkg));
-        }
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        assert!(
-            value.contains("```r\nmutate\n```"),
-            "Should contain symbol name in code block"
-        );
-        assert!(
-            value.contains("from {dplyr}"),
-            "Should contain package name in braces"
-        );
+        let position = Position::new(1, 5);
+        let result = goto_definition(&state, &uri, position);
+
+        match result {
+            Some(GotoDefinitionResponse::Link(links)) => {
+                assert_eq!(links.len(), 2, "Should surface both branch bindings");
+                for link in &links {
+                    assert_eq!(link.target_uri, uri);
+                    assert_eq!(
+                        link.origin_selection_range,
+                        Some(Range {
+                            start: Position::new(1, 5),
+                            end: Position::new(1, 6),
+                        })
+                    );
+                }
+            }
+            Some(GotoDefinitionResponse::Scalar(_)) => panic!("Expected Link response, got Scalar"),
+            Some(GotoDefinitionResponse::Array(_)) => panic!("Expected Link response, got Array"),
+            None => panic!("Expected Link response, got None"),
+        }
     }
 
     #[test]
-    fn test_hover_package_uri_detection() {
-        // Test that package URIs are correctly detected
-        // Validates: Requirement 10.1
-
-        // Package URIs should be detected
-        let package_uri = Url::parse("package:ggplot2").unwrap();
-        assert!(
-            package_uri.as_str().starts_with("package:"),
-            "Package URI should start with 'package:'"
-        );
-        assert_eq!(
-            package_uri.as_str().strip_prefix("package:"),
-            Some("ggplot2")
-        );
+    fn test_goto_definition_single_binding_returns_link_with_link_support() {
+        // A single, unambiguous definition should still come back as a Link
+        // (not Scalar) once the client has advertised linkSupport.
+        let library_paths = r_env::find_library_paths();
+        let mut state = WorldState::new(library_paths);
+        state.definition_link_support = true;
 
-        // Base package URI should also be detected
-        let base_uri = Url::parse("package:base").unwrap();
-        assert!(
-            base_uri.as_str().starts_with("package:"),
-            "Base package URI should start with 'package:'"
-        );
-        assert_eq!(base_uri.as_str().strip_prefix("package:"), Some("base"));
+        let uri = Url::parse("file:///workspace/test.R").unwrap();
 
-        // File URIs should NOT be detected as packages
-        let file_uri = Url::parse("file:///test.R").unwrap();
-        assert!(
-            !file_uri.as_str().starts_with("package:"),
-            "File URI should not start with 'package:'"
-        );
-        assert_eq!(file_uri.as_str().strip_prefix("package:"), None);
-    }
+        let code = r#"x <- 1
+y <- x"#;
 
-    #[test]
-    fn test_hover_local_definition_not_shown_as_package() {
-        // Test that local definitions are not shown as package exports
-        // Validates: Requirement 10.4 (shadowing)
-        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        // Create a symbol with a file URI (local definition)
-        let file_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let symbol = ScopedSymbol {
-            name: "mutate".to_string(),
-            kind: SymbolKind::Function,
-            source_uri: file_uri.clone(),
-            defined_line: 5,
-            defined_column: 0,
-            signature: Some("mutate <- function(x) { x + 1 }".to_string()),
-        };
+        let position = Position::new(1, 5);
+        let result = goto_definition(&state, &uri, position);
 
-        // Verify this is NOT detected as a package export
-        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
-        assert_eq!(
-            package_name, None,
-            "Local definition should not be detected as package export"
-        );
+        match result {
+            Some(GotoDefinitionResponse::Link(links)) => {
+                assert_eq!(links.len(), 1);
+                assert_eq!(links[0].target_range.start.line, 0);
+            }
+            Some(GotoDefinitionResponse::Scalar(_)) => panic!("Expected Link response, got Scalar"),
+            Some(GotoDefinitionResponse::Array(_)) => panic!("Expected Link response, got Array"),
+            None => panic!("Expected Link response, got None"),
+        }
     }
+}
 
-    // ============================================================================
-    // Tests for collect_missing_package_diagnostics - Task 10.3
-    // ============================================================================
-
-    #[test]
-    fn test_missing_package_diagnostic_emitted() {
-        // Test that a diagnostic is emitted for a non-installed package
-        // Validates: Requirement 15.1
-        let mut meta = crate::cross_file::CrossFileMetadata::default();
-        meta.library_calls
-            .push(crate::cross_file::source_detect::LibraryCall {
-                package: "__nonexistent_package_xyz__".to_string(),
-                line: 0,
-                column: 30,
-                function_scope: None,
-            });
+#[cfg(test)]
+mod position_aware_tests {
+    use std::path::PathBuf;
+    use tower_lsp::lsp_types::{Position, Url, Range, Diagnostic};
+    use crate::handlers::{goto_definition, collect_undefined_variables_position_aware};
+    use crate::state::{WorldState, Document};
+    use crate::cross_file::directive::parse_directives;
 
-        let state = WorldState::new(Vec::new());
-        let mut diagnostics = Vec::new();
+    fn parse_r_code(code: &str) -> tree_sitter::Tree {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&tree_sitter_r::LANGUAGE.into()).unwrap();
+        parser.parse(code, None).unwrap()
+    }
 
-        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
+    fn create_test_state() -> WorldState {
+        WorldState::new(vec![])
+    }
 
-        assert_eq!(
-            diagnostics.len(),
-            1,
-            "Should emit one diagnostic for missing package"
-        );
-        assert!(diagnostics[0]
-            .message
-            .contains("__nonexistent_package_xyz__"));
-        assert!(diagnostics[0].message.contains("not installed"));
-        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    fn add_document(state: &mut WorldState, uri_str: &str, content: &str) -> Url {
+        let uri = Url::parse(uri_str).expect("Invalid URI");
+        let document = Document::new(content, None);
+        state.documents.insert(uri.clone(), document);
+        uri
     }
 
     #[test]
-    fn test_missing_package_diagnostic_not_emitted_for_base_package() {
-        // Test that no diagnostic is emitted for base packages
-        // Validates: Requirement 15.1 (base packages are always available)
-        let mut meta = crate::cross_file::CrossFileMetadata::default();
-        meta.library_calls
-            .push(crate::cross_file::source_detect::LibraryCall {
-                package: "base".to_string(),
-                line: 0,
-                column: 15,
-                function_scope: None,
-            });
-
-        let mut state = WorldState::new(Vec::new());
-        // Ensure base is in base_packages by creating a new PackageLibrary
-        let mut base_packages = std::collections::HashSet::new();
-        base_packages.insert("base".to_string());
-        let mut pkg_lib = crate::package_library::PackageLibrary::new_empty();
-        pkg_lib.set_base_packages(base_packages);
-        state.package_library = std::sync::Arc::new(pkg_lib);
-
+    fn test_diagnostics_undefined_forward_reference() {
+        let mut state = create_test_state();
+        let code = "
+x
+x <- 1
+";
+        // Line 1: x (usage) - should be undefined
+        // Line 2: x <- 1 (definition)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        let tree = parse_r_code(code);
+        let root = tree.root_node();
+        let directive_meta = parse_directives(code);
+        
         let mut diagnostics = Vec::new();
-
-        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
-
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should not emit diagnostic for base package"
+        collect_undefined_variables_position_aware(
+            &state,
+            &uri,
+            root,
+            code,
+            &[], // deprecated loaded_packages
+            &[], // workspace_imports
+            &state.package_library,
+            &directive_meta,
+            &mut diagnostics
         );
+        
+        assert_eq!(diagnostics.len(), 1, "Should have 1 diagnostic");
+        assert!(diagnostics[0].message.contains("Undefined variable: x"));
+        assert_eq!(diagnostics[0].range.start.line, 1);
     }
 
     #[test]
-    fn test_missing_package_diagnostic_ignored_line() {
-        // Test that diagnostics are not emitted for ignored lines
-        // Validates: Requirement 15.1 with @lsp-ignore support
-        let mut meta = crate::cross_file::CrossFileMetadata::default();
-        meta.library_calls
-            .push(crate::cross_file::source_detect::LibraryCall {
-                package: "__nonexistent_package_xyz__".to_string(),
-                line: 5,
-                column: 30,
-                function_scope: None,
-            });
-        // Mark line 5 as ignored
-        meta.ignored_lines.insert(5);
-
-        let state = WorldState::new(Vec::new());
+    fn test_diagnostics_defined_before_usage() {
+        let mut state = create_test_state();
+        let code = "
+x <- 1
+x
+";
+        // Line 1: x <- 1
+        // Line 2: x (usage)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        let tree = parse_r_code(code);
+        let root = tree.root_node();
+        let directive_meta = parse_directives(code);
+        
         let mut diagnostics = Vec::new();
-
-        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
-
-        assert_eq!(
-            diagnostics.len(),
-            0,
-            "Should not emit diagnostic for ignored line"
+        collect_undefined_variables_position_aware(
+            &state,
+            &uri,
+            root,
+            code,
+            &[],
+            &[],
+            &state.package_library,
+            &directive_meta,
+            &mut diagnostics
         );
+        
+        assert_eq!(diagnostics.len(), 0, "Should have 0 diagnostics");
     }
 
     #[test]
-    fn test_missing_package_diagnostic_multiple_packages() {
-        // Test that diagnostics are emitted for multiple missing packages
-        // Validates: Requirement 15.1
-        let mut meta = crate::cross_file::CrossFileMetadata::default();
-        meta.library_calls
-            .push(crate::cross_file::source_detect::LibraryCall {
-                package: "__missing_pkg1__".to_string(),
-                line: 0,
-                column: 20,
-                function_scope: None,
-            });
-        meta.library_calls
-            .push(crate::cross_file::source_detect::LibraryCall {
-                package: "__missing_pkg2__".to_string(),
-                line: 1,
-                column: 20,
-                function_scope: None,
-            });
-
-        let state = WorldState::new(Vec::new());
+    fn test_diagnostics_redefined_later() {
+        let mut state = create_test_state();
+        let code = "
+x <- 1
+x
+x <- 2
+";
+        // Line 1: x <- 1
+        // Line 2: x (usage) - defined by line 1
+        // Line 3: x <- 2
+        let uri = add_document(&mut state, "file:///test.R", code);
+        let tree = parse_r_code(code);
+        let root = tree.root_node();
+        let directive_meta = parse_directives(code);
+        
         let mut diagnostics = Vec::new();
-
-        collect_missing_package_diagnostics(&state, &meta, &mut diagnostics);
-
-        assert_eq!(
-            diagnostics.len(),
-            2,
-            "Should emit diagnostics for both missing packages"
+        collect_undefined_variables_position_aware(
+            &state,
+            &uri,
+            root,
+            code,
+            &[],
+            &[],
+            &state.package_library,
+            &directive_meta,
+            &mut diagnostics
         );
-        assert!(diagnostics[0].message.contains("__missing_pkg1__"));
-        assert!(diagnostics[1].message.contains("__missing_pkg2__"));
+        
+        assert_eq!(diagnostics.len(), 0, "Should have 0 diagnostics");
     }
 
-    // ============================================================================
-    // Tests for hover shadowing - Task 12.3
-    // ============================================================================
-
     #[test]
-    fn test_hover_local_definition_shadows_package_export() {
-        // Test that when a local definition shadows a package export,
-        // hover shows the local definition, not the package export.
-        // Validates: Requirement 10.4
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
-
-        let uri = Url::parse("file:///workspace/main.R").unwrap();
-
-        // Code that loads a package and then defines a local function with the same name
-        // as a package export. The local definition should shadow the package export.
-        let code = r#"library(dplyr)
-mutate <- function(x, y) { x + y }  # Local definition shadows dplyr::mutate
-result <- mutate(1, 2)"#;
-
-        state
-            .documents
-            .insert(uri.clone(), Document::new(code, None));
-
-        // Update cross-file graph with metadata
-        state.cross_file_graph.update_file(
-            &uri,
-            &crate::cross_file::extract_metadata(code),
-            None,
-            |_| None,
-        );
+    fn test_goto_definition_same_file_before_usage() {
+        let mut state = create_test_state();
+        let code = "
+x <- 1
+x
+";
+        // Line 1: x <- 1
+        // Line 2: x (usage)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        
+        // Usage at line 2, col 0
+        let pos = Position::new(2, 0);
+        let result = goto_definition(&state, &uri, pos);
+        
+        assert!(result.is_some(), "Should find definition");
+        let location = match result.unwrap() {
+            tower_lsp::lsp_types::GotoDefinitionResponse::Scalar(loc) => loc,
+            _ => panic!("Expected Scalar location"),
+        };
+        
+        assert_eq!(location.uri, uri);
+        assert_eq!(location.range.start.line, 1, "Definition should be on line 1");
+    }
 
-        // Test hover on "mutate" usage (line 2, position 10)
-        let position = Position::new(2, 10);
-        let hover_result = hover_blocking(&state, &uri, position);
+    #[test]
+    fn test_goto_definition_same_file_after_usage() {
+        let mut state = create_test_state();
+        let code = "
+x
+x <- 1
+";
+        // Line 1: x (usage)
+        // Line 2: x <- 1 (definition)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        
+        // Usage at line 1, col 0
+        let pos = Position::new(1, 0);
+        let result = goto_definition(&state, &uri, pos);
+        
+        assert!(result.is_none(), "Should NOT find definition appearing after usage");
+    }
 
-        assert!(hover_result.is_some(), "Hover should return a result");
-        let hover = hover_result.unwrap();
+    #[test]
+    fn test_goto_definition_function_scope_no_leak() {
+        let mut state = create_test_state();
+        let code = "
+f <- function() {
+    local_var <- 1
+}
+local_var
+";
+        // Line 1: f <- ...
+        // Line 2:     local_var <- 1
+        // Line 3: }
+        // Line 4: local_var (usage)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        
+        // Usage at line 4, col 0
+        let pos = Position::new(4, 0);
+        let result = goto_definition(&state, &uri, pos);
+        
+        assert!(result.is_none(), "Function-local variable should not be visible outside");
+    }
 
-        if let HoverContents::Markup(content) = hover.contents {
-            // Should show local definition signature (x, y), not dplyr's mutate
-            assert!(
-                content.value.contains("mutate"),
-                "Should contain function name"
-            );
-            assert!(
-                content.value.contains("(x, y)"),
-                "Should show local signature (x, y), not dplyr's signature"
-            );
-            // Should NOT show package attribution
-            assert!(
-                !content.value.contains("{dplyr}"),
-                "Should NOT show package attribution for shadowed symbol"
-            );
-            // Should show local file location
-            assert!(
-                content.value.contains("this file"),
-                "Should show local file location"
-            );
-        } else {
-            panic!("Expected markup content");
-        }
+    #[test]
+    fn test_goto_definition_shadowing() {
+        let mut state = create_test_state();
+        let code = "
+x <- 1
+f <- function() {
+    x <- 2
+    x
+}
+";
+        // Line 1: x <- 1 (global)
+        // Line 2: f <- ...
+        // Line 3:     x <- 2 (local)
+        // Line 4:     x (usage)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        
+        // Usage at line 4, col 4
+        let pos = Position::new(4, 4);
+        let result = goto_definition(&state, &uri, pos);
+        
+        assert!(result.is_some());
+        let location = match result.unwrap() {
+            tower_lsp::lsp_types::GotoDefinitionResponse::Scalar(loc) => loc,
+            _ => panic!("Expected Scalar location"),
+        };
+        
+        assert_eq!(location.range.start.line, 3, "Should resolve to local definition (line 3)");
     }
 
     #[test]
-    fn test_hover_shadowing_scope_resolution_returns_local() {
-        // Test that scope resolution returns the local definition when it shadows a package export.
-        // This verifies the underlying mechanism that hover relies on.
-        // Validates: Requirement 10.4
-        use crate::cross_file::scope::{compute_artifacts, scope_at_position_with_packages};
-        use std::collections::HashSet;
-
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
-
-        // Code with library() and local definition of same name
-        let code = r#"library(dplyr)
-filter <- function(x) { x > 0 }
-result <- filter(c(1, -2, 3))"#;
-
-        // Use Document::new to parse the code (same as other tests)
-        let doc = Document::new(code, None);
-        let tree = doc.tree.as_ref().expect("Should parse successfully");
-        let artifacts = compute_artifacts(&uri, tree, code);
-
-        // Create a mock package exports callback that returns "filter" for dplyr
-        let get_exports = |pkg: &str| -> HashSet<String> {
-            if pkg == "dplyr" {
-                let mut exports = HashSet::new();
-                exports.insert("filter".to_string());
-                exports
-            } else {
-                HashSet::new()
-            }
+    fn test_goto_definition_sequential_redefinition() {
+        let mut state = create_test_state();
+        let code = "
+x <- 1
+x <- 2
+x
+";
+        // Line 1: x <- 1
+        // Line 2: x <- 2
+        // Line 3: x (usage)
+        let uri = add_document(&mut state, "file:///test.R", code);
+        
+        // Usage at line 3, col 0
+        let pos = Position::new(3, 0);
+        let result = goto_definition(&state, &uri, pos);
+        
+        assert!(result.is_some());
+        let location = match result.unwrap() {
+            tower_lsp::lsp_types::GotoDefinitionResponse::Scalar(loc) => loc,
+            _ => panic!("Expected Scalar location"),
         };
+        
+        assert_eq!(location.range.start.line, 2, "Should resolve to latest definition (line 2)");
+    }
 
-        let base_exports = HashSet::new();
-
-        // Query scope at line 2 (after both library and local definition)
-        let scope = scope_at_position_with_packages(&artifacts, 2, 10, &get_exports, &base_exports);
+    #[test]
+    fn test_rename_rejects_reserved_word() {
+        let mut state = create_test_state();
+        let code = "old_name <- 1\nold_name\n";
+        let uri = add_document(&mut state, "file:///test.R", code);
 
-        // Symbol should be in scope
-        assert!(
-            scope.symbols.contains_key("filter"),
-            "filter should be in scope"
-        );
+        let result = rename(&state, &uri, Position::new(0, 0), "function");
 
-        // The symbol should be from the local definition, not the package
-        let symbol = scope.symbols.get("filter").unwrap();
-        assert!(
-            !symbol.source_uri.as_str().starts_with("package:"),
-            "filter should be from local definition, not package. Got URI: '{}'",
-            symbol.source_uri.as_str()
-        );
         assert_eq!(
-            symbol.source_uri, uri,
-            "filter should be from the local file"
+            result,
+            Err("'function' is a reserved word and cannot be used as an identifier".to_string())
         );
     }
 
     #[test]
-    fn test_hover_package_export_shown_when_no_local_shadow() {
-        // Test that when there's no local definition, hover shows the package export.
-        // This is the complement to test_hover_local_definition_shadows_package_export.
-        // Validates: Requirements 10.1, 10.4
-        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    fn test_rename_renames_all_occurrences_in_same_file() {
+        let mut state = create_test_state();
+        let code = "old_name <- 1\nresult <- old_name + 1\n";
+        let uri = add_document(&mut state, "file:///test.R", code);
 
-        // Create a symbol that represents a package export
-        let package_uri = Url::parse("package:dplyr").unwrap();
-        let symbol = ScopedSymbol {
-            name: "mutate".to_string(),
-            kind: SymbolKind::Function,
-            source_uri: package_uri.clone(),
-            defined_line: 0,
-            defined_column: 0,
-            signature: Some("mutate(.data, ...)".to_string()),
-        };
+        let edit = rename(&state, &uri, Position::new(0, 0), "new_name")
+            .expect("reserved-word check should pass")
+            .expect("should find occurrences to rename");
 
-        // Verify this IS detected as a package export
-        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
+        let edits = edit
+            .changes
+            .expect("should have changes")
+            .remove(&uri)
+            .unwrap();
         assert_eq!(
-            package_name,
-            Some("dplyr"),
-            "Package export should be detected"
+            edits.len(),
+            2,
+            "Should rename both the definition and the usage"
         );
+        assert!(edits.iter().all(|e| e.new_text == "new_name"));
+    }
 
-        // Verify the formatting that would be used in hover
-        let mut value = String::new();
-        if let Some(pkg) = package_name {
-            if let Some(sig) = &symbol.signature {
-                value.push_str(&format!("```r\n{}\n```\n", sig));
-            }
-            value.push_str(&format!("\nfrom {{{}}}", pkg));
-        }
+    #[test]
+    fn test_rename_refuses_named_argument_position() {
+        let mut state = create_test_state();
+        let code = "old_name <- 1\nf(old_name = 2)\n";
+        let uri = add_document(&mut state, "file:///test.R", code);
 
-        assert!(
-            value.contains("mutate(.data, ...)"),
-            "Should show function signature"
-        );
-        assert!(
-            value.contains("from {dplyr}"),
-            "Should show package attribution"
-        );
+        // Position is on the `old_name` used as a named-argument name, not a
+        // usage of the `old_name` binding, so rename should refuse entirely
+        // rather than touch the call's argument name.
+        let edit = rename(&state, &uri, Position::new(1, 2), "new_name");
+
+        assert_eq!(edit, Ok(None));
     }
 
+    /// When a bare call's name is exported by exactly one attached package,
+    /// `code_action` offers a single "Qualify as pkg::fn" rewrite.
     #[test]
-    fn test_hover_shadowing_position_aware() {
-        // Test that shadowing is position-aware: before the local definition,
-        // the package export should be shown; after, the local definition.
-        // Validates: Requirement 10.4
-        use crate::cross_file::scope::{compute_artifacts, scope_at_position_with_packages};
-        use std::collections::HashSet;
-
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
+    fn test_code_action_qualify_call_single_candidate() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
 
-        // Code with library() first, then local definition later
-        let code = r#"library(dplyr)
-x <- mutate(df, y = 1)  # Uses package export
-mutate <- function(x) { x + 1 }  # Local definition
-z <- mutate(5)  # Uses local definition"#;
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
 
-        // Use Document::new to parse the code (same as other tests)
-        let doc = Document::new(code, None);
-        let tree = doc.tree.as_ref().expect("Should parse successfully");
-        let artifacts = compute_artifacts(&uri, tree, code);
+            let mut exports = std::collections::HashSet::new();
+            exports.insert("filter".to_string());
+            state
+                .package_library
+                .insert_package(PackageInfo::new("dplyr".to_string(), exports))
+                .await;
 
-        // Create a mock package exports callback
-        let get_exports = |pkg: &str| -> HashSet<String> {
-            if pkg == "dplyr" {
-                let mut exports = HashSet::new();
-                exports.insert("mutate".to_string());
-                exports
-            } else {
-                HashSet::new()
-            }
-        };
+            let code = "library(dplyr)\nfilter(df, x > 1)\n";
+            let uri = Url::parse("file:///test.R").unwrap();
+            state
+                .documents
+                .insert(uri.clone(), Document::new(code, None));
 
-        let base_exports = HashSet::new();
+            // Cursor on the `filter` call name.
+            let range = Range {
+                start: Position::new(1, 0),
+                end: Position::new(1, 0),
+            };
+            let actions = super::code_action(&state, &uri, range, &[], None)
+                .expect("expected a refactor action");
 
-        // Query scope at line 1 (before local definition) - should get package export
-        let scope_before =
-            scope_at_position_with_packages(&artifacts, 1, 5, &get_exports, &base_exports);
-        assert!(
-            scope_before.symbols.contains_key("mutate"),
-            "mutate should be in scope before local def"
-        );
-        let symbol_before = scope_before.symbols.get("mutate").unwrap();
-        assert!(
-            symbol_before.source_uri.as_str().starts_with("package:"),
-            "Before local definition, mutate should be from package. Got URI: '{}'",
-            symbol_before.source_uri.as_str()
-        );
+            let qualify_actions: Vec<_> = actions
+                .iter()
+                .filter_map(|a| match a {
+                    CodeActionOrCommand::CodeAction(action)
+                        if action.title.starts_with("Qualify as") =>
+                    {
+                        Some(action)
+                    }
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(qualify_actions.len(), 1);
+            assert_eq!(qualify_actions[0].title, "Qualify as dplyr::filter");
+            assert_eq!(
+                qualify_actions[0].kind,
+                Some(CodeActionKind::REFACTOR_REWRITE)
+            );
 
-        // Query scope at line 3 (after local definition) - should get local definition
-        let scope_after =
-            scope_at_position_with_packages(&artifacts, 3, 5, &get_exports, &base_exports);
-        assert!(
-            scope_after.symbols.contains_key("mutate"),
-            "mutate should be in scope after local def"
-        );
-        let symbol_after = scope_after.symbols.get("mutate").unwrap();
-        assert!(
-            !symbol_after.source_uri.as_str().starts_with("package:"),
-            "After local definition, mutate should be from local file. Got URI: '{}'",
-            symbol_after.source_uri.as_str()
-        );
-        assert_eq!(
-            symbol_after.source_uri, uri,
-            "mutate should be from the local file"
-        );
+            let edit = qualify_actions[0].edit.as_ref().unwrap();
+            let edits = &edit.changes.as_ref().unwrap()[&uri];
+            assert_eq!(edits.len(), 1);
+            assert_eq!(edits[0].new_text, "dplyr::");
+            assert_eq!(
+                edits[0].range,
+                Range {
+                    start: Position::new(1, 0),
+                    end: Position::new(1, 0),
+                }
+            );
+        });
     }
 
-    // ============================================================================
-    // Tests for goto_definition package handling - Task 13.1
-    // ============================================================================
-
-    /// Verifies that symbols originating from packages are treated as non-navigable.
-    ///
-    /// This test constructs a `ScopedSymbol` whose `source_uri` uses the `package:`
-    /// scheme and asserts that such URIs are recognized as package exports (which
-    /// goto-definition should not navigate into).
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
-    /// use url::Url;
-    ///
-    /// let package_uri = Url::parse("package:dplyr").unwrap();
-    /// let symbol = ScopedSymbol {
-    ///     name: "mutate".to_string(),
-    ///     kind: SymbolKind::Function,
-    ///     source_uri: package_uri.clone(),
-    ///     defined_line: 0,
-    ///     defined_column: 0,
-    ///     signature: Some("mutate(.data, ...)".to_string()),
-    /// };
-    ///
-    /// assert!(symbol.source_uri.as_str().starts_with("package:"));
-    /// let is_package_export = symbol.source_uri.as_str().starts_with("package:");
-    /// assert!(is_package_export);
-    /// let package_name = symbol.source_uri.as_str().strip_prefix("package:");
-    /// assert_eq!(package_name, Some("dplyr"));
-    /// ```
+    /// When a call's name is exported by several attached packages, one
+    /// "Qualify as pkg::fn" action is offered per candidate package.
     #[test]
-    fn test_goto_definition_returns_none_for_package_exports() {
-        // Test that goto_definition returns None for package exports
-        // since package source files are not navigable
-        // Validates: Requirements 11.1, 11.2
-        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    fn test_code_action_qualify_call_multiple_candidates() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
 
-        // Create a symbol with a package URI
-        let package_uri = Url::parse("package:dplyr").unwrap();
-        let symbol = ScopedSymbol {
-            name: "mutate".to_string(),
-            kind: SymbolKind::Function,
-            source_uri: package_uri.clone(),
-            defined_line: 0,
-            defined_column: 0,
-            signature: Some("mutate(.data, ...)".to_string()),
-        };
+            let mut dplyr_exports = std::collections::HashSet::new();
+            dplyr_exports.insert("filter".to_string());
+            state
+                .package_library
+                .insert_package(PackageInfo::new("dplyr".to_string(), dplyr_exports))
+                .await;
 
-        // Verify the package URI is detected correctly
-        assert!(
-            symbol.source_uri.as_str().starts_with("package:"),
-            "Package export should have package: URI prefix"
-        );
+            let mut stats_exports = std::collections::HashSet::new();
+            stats_exports.insert("filter".to_string());
+            state
+                .package_library
+                .insert_package(PackageInfo::new("stats".to_string(), stats_exports))
+                .await;
 
-        // The goto_definition logic should skip package exports
-        // This test verifies the detection logic used in goto_definition
-        let is_package_export = symbol.source_uri.as_str().starts_with("package:");
-        assert!(is_package_export, "Should detect package export");
+            let code = "library(dplyr)\nlibrary(stats)\nfilter(df, x > 1)\n";
+            let uri = Url::parse("file:///test.R").unwrap();
+            state
+                .documents
+                .insert(uri.clone(), Document::new(code, None));
 
-        // Extract package name for logging
-        let package_name = symbol.source_uri.as_str().strip_prefix("package:");
-        assert_eq!(package_name, Some("dplyr"), "Should extract package name");
+            let range = Range {
+                start: Position::new(2, 0),
+                end: Position::new(2, 0),
+            };
+            let actions = super::code_action(&state, &uri, range, &[], None)
+                .expect("expected refactor actions");
+
+            let mut titles: Vec<_> = actions
+                .iter()
+                .filter_map(|a| match a {
+                    CodeActionOrCommand::CodeAction(action)
+                        if action.title.starts_with("Qualify as") =>
+                    {
+                        Some(action.title.clone())
+                    }
+                    _ => None,
+                })
+                .collect();
+            titles.sort();
+            assert_eq!(
+                titles,
+                vec!["Qualify as dplyr::filter", "Qualify as stats::filter"]
+            );
+        });
     }
 
+    /// No "Qualify as" action is offered when the cursor isn't on a bare
+    /// call's function-name position.
     #[test]
-    fn test_goto_definition_navigates_to_local_definition() {
-        // Test that goto_definition navigates to local definitions (not package exports)
-        // Validates: Requirement 11.3 (shadowing)
-        use crate::cross_file::scope::{ScopedSymbol, SymbolKind};
+    fn test_code_action_qualify_call_ignores_non_call_position() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
 
-        // Create a symbol with a file URI (local definition)
-        let file_uri = Url::parse("file:///workspace/main.R").unwrap();
-        let symbol = ScopedSymbol {
-            name: "mutate".to_string(),
-            kind: SymbolKind::Function,
-            source_uri: file_uri.clone(),
-            defined_line: 5,
-            defined_column: 0,
-            signature: Some("mutate <- function(x) { x + 1 }".to_string()),
-        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
 
-        // Verify this is NOT a package export
-        assert!(
-            !symbol.source_uri.as_str().starts_with("package:"),
-            "Local definition should not have package: URI prefix"
-        );
+            let mut exports = std::collections::HashSet::new();
+            exports.insert("filter".to_string());
+            state
+                .package_library
+                .insert_package(PackageInfo::new("dplyr".to_string(), exports))
+                .await;
 
-        // The goto_definition logic should navigate to local definitions
-        let is_package_export = symbol.source_uri.as_str().starts_with("package:");
-        assert!(!is_package_export, "Should not detect as package export");
+            let code = "library(dplyr)\nfilter(df, x > 1)\n";
+            let uri = Url::parse("file:///test.R").unwrap();
+            state
+                .documents
+                .insert(uri.clone(), Document::new(code, None));
 
-        // Verify the location would be correct
-        let expected_line = symbol.defined_line;
-        let expected_column = symbol.defined_column;
-        assert_eq!(expected_line, 5, "Should navigate to correct line");
-        assert_eq!(expected_column, 0, "Should navigate to correct column");
+            // Cursor on the `df` argument, not the `filter` call name.
+            let range = Range {
+                start: Position::new(1, 7),
+                end: Position::new(1, 7),
+            };
+            let actions = super::code_action(&state, &uri, range, &[], None).unwrap_or_default();
+            assert!(
+                actions.iter().all(|a| !matches!(a,
+                CodeActionOrCommand::CodeAction(action) if action.title.starts_with("Qualify as")))
+            );
+        });
     }
 
+    // ========================================================================
+    // Tests for missing-package and out-of-scope-symbol quick fixes
+    // ========================================================================
+
     #[test]
-    fn test_goto_definition_package_uri_formats() {
-        // Test various package URI formats are correctly detected
-        // Validates: Requirements 11.1, 11.2
+    fn test_code_action_offers_install_and_replace_for_missing_package() {
+        use crate::state::{Document, WorldState};
 
-        // Standard package URI
-        let dplyr_uri = Url::parse("package:dplyr").unwrap();
-        assert!(dplyr_uri.as_str().starts_with("package:"));
-        assert_eq!(dplyr_uri.as_str().strip_prefix("package:"), Some("dplyr"));
+        let (_tempdir, mut state) = package_library_with_installed(&["dplyr"]);
 
-        // Base package URI
-        let base_uri = Url::parse("package:base").unwrap();
-        assert!(base_uri.as_str().starts_with("package:"));
-        assert_eq!(base_uri.as_str().strip_prefix("package:"), Some("base"));
+        let code = "library(dplyor)\n";
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        // Package with dots in name
-        let data_table_uri = Url::parse("package:data.table").unwrap();
-        assert!(data_table_uri.as_str().starts_with("package:"));
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| d.message.contains(MISSING_PACKAGE_DIAGNOSTIC_MARKER))
+            .expect("expected a missing-package diagnostic");
+        assert!(diagnostic.message.contains("Did you mean 'dplyr'?"));
+
+        let actions = code_action(&state, &uri, diagnostic.range, &[diagnostic.clone()], None)
+            .expect("expected quick fixes");
+
+        let install_action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action) if action.title == "Install 'dplyor'" => {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected an Install quick fix");
+        let command = install_action.command.as_ref().unwrap();
+        assert_eq!(command.command, INSTALL_PACKAGE_COMMAND);
         assert_eq!(
-            data_table_uri.as_str().strip_prefix("package:"),
-            Some("data.table")
+            command.arguments.as_ref().unwrap()[0],
+            serde_json::json!({ "package": "dplyor" })
         );
 
-        // File URIs should NOT be detected as packages
-        let file_uri = Url::parse("file:///workspace/test.R").unwrap();
-        assert!(!file_uri.as_str().starts_with("package:"));
-        assert_eq!(file_uri.as_str().strip_prefix("package:"), None);
+        let replace_action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Replace with 'dplyr'" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected a Replace quick fix");
+        let edit = replace_action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "library(dplyr)");
     }
 
-    // ============================================================================
-    // Tests for goto_definition shadowing behavior - Task 13.2
-    // ============================================================================
-
     #[test]
-    fn test_goto_definition_local_shadows_package_export() {
-        // Test that when a local definition shadows a package export,
-        // goto_definition navigates to the local definition, not the package.
-        // Validates: Requirement 11.3
-
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
-
-        let uri = Url::parse("file:///workspace/main.R").unwrap();
+    fn test_code_action_omits_replace_when_no_package_suggestion() {
+        use crate::state::{Document, WorldState};
 
-        // Code that loads a package and then defines a local function with the same name
-        // as a package export. The local definition should shadow the package export.
-        // "mutate" is defined locally on line 1 (0-indexed), shadowing dplyr::mutate
-        let code = r#"library(dplyr)
-mutate <- function(x, y) { x + y }
-result <- mutate(1, 2)"#;
+        let (_tempdir, mut state) = package_library_with_installed(&[]);
 
+        let code = "library(totallyunrelatedpkg)\n";
+        let uri = Url::parse("file:///test.R").unwrap();
         state
             .documents
             .insert(uri.clone(), Document::new(code, None));
 
-        // Update cross-file graph with metadata
-        state.cross_file_graph.update_file(
-            &uri,
-            &crate::cross_file::extract_metadata(code),
-            None,
-            |_| None,
-        );
-
-        // Test goto_definition on "mutate" usage (line 2, position 10 - within "mutate")
-        let position = Position::new(2, 10);
-        let result = goto_definition(&state, &uri, position);
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| d.message.contains(MISSING_PACKAGE_DIAGNOSTIC_MARKER))
+            .expect("expected a missing-package diagnostic");
 
-        // Should navigate to local definition, not return None (which would happen for package exports)
+        let actions = code_action(&state, &uri, diagnostic.range, &[diagnostic.clone()], None)
+            .expect("expected an Install quick fix");
         assert!(
-            result.is_some(),
-            "goto_definition should return a result for shadowed symbol"
+            actions
+                .iter()
+                .all(|a| !matches!(a, CodeActionOrCommand::CodeAction(action) if action.title.starts_with("Replace with")))
         );
+    }
 
-        if let Some(GotoDefinitionResponse::Scalar(location)) = result {
-            // Should navigate to the local definition on line 1
-            assert_eq!(location.uri, uri, "Should navigate to the same file");
-            assert_eq!(
-                location.range.start.line, 1,
-                "Should navigate to line 1 where local mutate is defined"
+    #[test]
+    fn test_code_action_qualifies_out_of_scope_symbol_already_loaded() {
+        use crate::package_library::PackageInfo;
+        use crate::state::{Document, WorldState};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut state = WorldState::new(vec![]);
+
+            let mut exports = std::collections::HashSet::new();
+            exports.insert("helper_func".to_string());
+            state
+                .package_library
+                .insert_package(PackageInfo::new("utilpkg".to_string(), exports))
+                .await;
+
+            let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+            let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+            let main_code = "library(utilpkg)\nresult <- helper_func(42)\nsource(\"utils.R\")\n";
+            let utils_code = "helper_func <- function(x) {\n    x * 2\n}";
+
+            state
+                .documents
+                .insert(main_uri.clone(), Document::new(main_code, None));
+            state
+                .documents
+                .insert(utils_uri.clone(), Document::new(utils_code, None));
+            state.cross_file_graph.update_file(
+                &main_uri,
+                &crate::cross_file::extract_metadata(main_code),
+                None,
+                |_| None,
             );
-            assert_eq!(
-                location.range.start.character, 0,
-                "Should navigate to column 0"
+            state.cross_file_graph.update_file(
+                &utils_uri,
+                &crate::cross_file::extract_metadata(utils_code),
+                None,
+                |_| None,
             );
-        } else {
-            panic!("Expected Scalar response");
-        }
+
+            let diags = super::diagnostics(&state, &main_uri);
+            let diagnostic = diags
+                .iter()
+                .find(|d| d.message.contains(OUT_OF_SCOPE_SYMBOL_DIAGNOSTIC_MARKER))
+                .expect("expected an out-of-scope-symbol diagnostic");
+
+            let actions = code_action(
+                &state,
+                &main_uri,
+                diagnostic.range,
+                &[diagnostic.clone()],
+                None,
+            )
+            .expect("expected a qualify quick fix");
+            let qualify_action = actions
+                .iter()
+                .find_map(|a| match a {
+                    CodeActionOrCommand::CodeAction(action)
+                        if action.title == "Qualify as utilpkg::helper_func" =>
+                    {
+                        Some(action)
+                    }
+                    _ => None,
+                })
+                .expect("expected a Qualify quick fix for the already-loaded package");
+            let edit = qualify_action.edit.as_ref().unwrap();
+            let edits = &edit.changes.as_ref().unwrap()[&main_uri];
+            assert_eq!(edits[0].new_text, "utilpkg::");
+        });
     }
 
+    // ========================================================================
+    // Tests for "Did you mean ...?" undefined-variable suggestions
+    // ========================================================================
+
     #[test]
-    fn test_goto_definition_local_definition_found_first() {
-        // Test that goto_definition searches the current document first,
-        // ensuring local definitions are found before cross-file symbols.
-        // This is the core mechanism that enables shadowing.
-        // Validates: Requirement 11.3
+    fn test_suggest_similar_identifier_case_insensitive_exact_match() {
+        let candidates = ["myVar", "other"];
+        assert_eq!(
+            super::suggest_similar_identifier("myvar", candidates.into_iter()),
+            Some("myVar")
+        );
+    }
 
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+    #[test]
+    fn test_suggest_similar_identifier_closest_typo() {
+        let candidates = ["total_count", "unrelated_name"];
+        assert_eq!(
+            super::suggest_similar_identifier("total_counr", candidates.into_iter()),
+            Some("total_count")
+        );
+    }
 
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
+    #[test]
+    fn test_suggest_similar_identifier_none_within_threshold() {
+        let candidates = ["zzz", "completely_different_name"];
+        assert_eq!(
+            super::suggest_similar_identifier("abc", candidates.into_iter()),
+            None
+        );
+    }
 
-        // Simple code with a local function definition and usage
-        let code = r#"my_func <- function(a, b) { a + b }
-result <- my_func(1, 2)"#;
+    #[test]
+    fn test_suggest_similar_identifier_ignores_self() {
+        let candidates = ["total_count"];
+        assert_eq!(
+            super::suggest_similar_identifier("total_count", candidates.into_iter()),
+            None
+        );
+    }
+
+    /// `collect_undefined_variables_position_aware` appends a "Did you
+    /// mean ...?" hint when a defined identifier is a close typo match.
+    #[test]
+    fn test_undefined_variable_suggests_close_match() {
+        use crate::cross_file::directive::parse_directives;
+        use crate::state::{Document, WorldState};
+
+        let code = "total_count <- 1\nprint(total_counr)\n";
+        let tree = parse_r_code(code);
 
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.undefined_variables_enabled = true;
+        let uri = Url::parse("file:///test.R").unwrap();
         state
             .documents
             .insert(uri.clone(), Document::new(code, None));
 
-        // Test goto_definition on "my_func" usage (line 1, position 10)
-        let position = Position::new(1, 10);
-        let result = goto_definition(&state, &uri, position);
-
-        assert!(
-            result.is_some(),
-            "goto_definition should find local definition"
+        let directive_meta = parse_directives(code);
+        let mut diagnostics = Vec::new();
+        super::collect_undefined_variables_position_aware(
+            &state,
+            &uri,
+            tree.root_node(),
+            code,
+            &[],
+            &[],
+            &state.package_library,
+            &directive_meta,
+            &mut diagnostics,
         );
 
-        if let Some(GotoDefinitionResponse::Scalar(location)) = result {
-            assert_eq!(location.uri, uri, "Should navigate to the same file");
-            assert_eq!(
-                location.range.start.line, 0,
-                "Should navigate to line 0 where my_func is defined"
-            );
-        } else {
-            panic!("Expected Scalar response");
-        }
+        let undefined = diagnostics
+            .iter()
+            .find(|d| d.message.contains("Undefined variable: total_counr"))
+            .expect("expected an undefined-variable diagnostic for the typo");
+        assert_eq!(
+            undefined.message,
+            "Undefined variable: total_counr. Did you mean `total_count`?"
+        );
     }
 
-    /// Verifies that scope resolution prefers local definitions over package exports for goto-definition.
-    ///
-    /// Constructs a document containing a `library()` call and a local function named `filter`, computes
-    /// the cross-file scope at a position after the local definition, and asserts that the `filter`
-    /// symbol resolves to the local file (not a `package:` URI) and has the expected definition line.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// // Confirms a local `filter` shadows the `dplyr` export when resolving definitions.
-    /// ```
+    /// `code_action` offers a quickfix that rewrites the typo to the
+    /// suggested identifier from a "Did you mean ...?" diagnostic.
     #[test]
-    fn test_goto_definition_shadowing_scope_resolution() {
-        // Test that scope resolution correctly returns local definitions over package exports.
-        // This verifies the underlying mechanism that goto_definition relies on.
-        // Validates: Requirement 11.3
-        use crate::cross_file::scope::{compute_artifacts, scope_at_position_with_packages};
-        use std::collections::HashSet;
+    fn test_code_action_quickfix_applies_did_you_mean_suggestion() {
+        use crate::state::{Document, WorldState};
 
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
+        let code = "total_count <- 1\nprint(total_counr)\n";
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.undefined_variables_enabled = true;
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        // Code with library() and local definition of same name
-        let code = r#"library(dplyr)
-filter <- function(x) { x > 0 }
-result <- filter(c(1, -2, 3))"#;
+        let diagnostic = Diagnostic {
+            range: Range {
+                start: Position::new(1, 6),
+                end: Position::new(1, 17),
+            },
+            message: "Undefined variable: total_counr. Did you mean `total_count`?".to_string(),
+            ..Default::default()
+        };
 
-        let doc = Document::new(code, None);
-        let tree = doc.tree.as_ref().expect("Should parse successfully");
-        let artifacts = compute_artifacts(&uri, tree, code);
+        let actions =
+            super::code_action(&state, &uri, diagnostic.range, &[diagnostic.clone()], None)
+                .expect("expected a quickfix action");
+        let action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Change 'total_counr' to 'total_count'" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected the did-you-mean quickfix");
 
-        // Create a mock package exports callback that returns "filter" for dplyr
-        let get_exports = |pkg: &str| -> HashSet<String> {
-            if pkg == "dplyr" {
-                let mut exports = HashSet::new();
-                exports.insert("filter".to_string());
-                exports
-            } else {
-                HashSet::new()
-            }
-        };
+        let edit = action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "total_count");
+        assert_eq!(edits[0].range, diagnostic.range);
+    }
 
-        let base_exports = HashSet::new();
+    // ========================================================================
+    // Tests for naming-convention diagnostics and rename quickfix
+    // ========================================================================
 
-        // Query scope at line 2 (after both library and local definition)
-        let scope = scope_at_position_with_packages(&artifacts, 2, 10, &get_exports, &base_exports);
+    #[test]
+    fn test_naming_convention_disabled_by_default() {
+        use crate::state::{Document, WorldState};
 
-        // Symbol should be in scope
-        assert!(
-            scope.symbols.contains_key("filter"),
-            "filter should be in scope"
-        );
+        let code = "totalCount <- 1\n";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        // The symbol should be from the local definition, not the package
-        let symbol = scope.symbols.get("filter").unwrap();
+        let diags = super::diagnostics(&state, &uri);
         assert!(
-            !symbol.source_uri.as_str().starts_with("package:"),
-            "filter should be from local definition, not package. Got URI: '{}'",
-            symbol.source_uri.as_str()
-        );
-        assert_eq!(
-            symbol.source_uri, uri,
-            "filter should be from the local file"
+            diags
+                .iter()
+                .all(|d| !d.message.contains(INCORRECT_CASE_DIAGNOSTIC_MARKER)),
+            "naming-convention diagnostics must be opt-in"
         );
-
-        // Verify the definition position matches the local definition
-        assert_eq!(symbol.defined_line, 1, "filter should be defined on line 1");
     }
 
     #[test]
-    fn test_goto_definition_shadowing_position_aware() {
-        // Test that shadowing is position-aware: before the local definition,
-        // the package export would be used; after, the local definition.
-        // For goto_definition, this means:
-        // - Before local def: returns None (package export, not navigable)
-        // - After local def: returns local definition location
-        // Validates: Requirement 11.3
+    fn test_naming_convention_flags_camel_case_against_snake_case() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::DiagnosticSeverity;
 
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+        let code = "totalCount <- 1\n";
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.naming_convention_severity = Some(DiagnosticSeverity::HINT);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
+        let diags = super::diagnostics(&state, &uri);
+        let naming_diag = diags
+            .iter()
+            .find(|d| d.message.contains(INCORRECT_CASE_DIAGNOSTIC_MARKER))
+            .expect("expected a naming-convention diagnostic for 'totalCount'");
+        assert!(naming_diag.message.contains("snake_case"));
+        assert!(naming_diag.message.contains("total_count"));
+    }
 
-        // Code where package is loaded, then used, then shadowed, then used again
-        // Line 0: library(dplyr)
-        // Line 1: x <- filter(data)  # Uses dplyr::filter
-        // Line 2: filter <- function(x) { x > 0 }  # Local definition
-        // Line 3: y <- filter(data)  # Uses local filter
-        let code = r#"library(dplyr)
-x <- filter(data)
-filter <- function(x) { x > 0 }
-y <- filter(data)"#;
+    #[test]
+    fn test_naming_convention_exempts_dotted_s3_method_name() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::DiagnosticSeverity;
 
+        let code = "print.myClass <- function(x) { x }\n";
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.naming_convention_severity = Some(DiagnosticSeverity::HINT);
+        let uri = Url::parse("file:///test.R").unwrap();
         state
             .documents
             .insert(uri.clone(), Document::new(code, None));
-        state.cross_file_graph.update_file(
-            &uri,
-            &crate::cross_file::extract_metadata(code),
-            None,
-            |_| None,
-        );
-
-        // Test goto_definition on "filter" usage AFTER local definition (line 3, position 5)
-        let position_after = Position::new(3, 5);
-        let result_after = goto_definition(&state, &uri, position_after);
 
-        // After local definition, should navigate to local definition
+        let diags = super::diagnostics(&state, &uri);
         assert!(
-            result_after.is_some(),
-            "goto_definition should find local definition after shadowing"
+            diags
+                .iter()
+                .all(|d| !d.message.contains(INCORRECT_CASE_DIAGNOSTIC_MARKER)),
+            "dotted S3-method names must be exempt from the naming convention check"
         );
-
-        if let Some(GotoDefinitionResponse::Scalar(location)) = result_after {
-            assert_eq!(location.uri, uri, "Should navigate to the same file");
-            assert_eq!(
-                location.range.start.line, 2,
-                "Should navigate to line 2 where local filter is defined"
-            );
-        } else {
-            panic!("Expected Scalar response");
-        }
     }
 
     #[test]
-    fn test_goto_definition_multiple_local_definitions() {
-        // Test that goto_definition finds the first local definition when
-        // there are multiple definitions of the same symbol.
-        // Validates: Requirement 11.3
+    fn test_code_action_offers_naming_convention_rename_quickfix() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::DiagnosticSeverity;
 
-        let library_paths = r_env::find_library_paths();
-        let mut state = WorldState::new(library_paths);
+        let code = "totalCount <- 1\nprint(totalCount)\n";
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.naming_convention_severity = Some(DiagnosticSeverity::HINT);
+        let uri = Url::parse("file:///test.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
 
-        let uri = Url::parse("file:///workspace/test.R").unwrap();
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| d.message.contains(INCORRECT_CASE_DIAGNOSTIC_MARKER))
+            .expect("expected a naming-convention diagnostic")
+            .clone();
+
+        let actions =
+            super::code_action(&state, &uri, diagnostic.range, &[diagnostic.clone()], None)
+                .expect("expected a quickfix action");
+        let action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Rename 'totalCount' to 'total_count'" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected the naming-convention rename quickfix");
 
-        // Code with multiple definitions of the same symbol
-        let code = r#"x <- 1
-x <- 2
-y <- x"#;
+        let edit = action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert!(edits.iter().all(|e| e.new_text == "total_count"));
+        // Both the definition and the usage should be renamed.
+        assert_eq!(edits.len(), 2);
+    }
 
+    #[test]
+    fn test_unsourced_file_diagnostic_is_opt_in() {
+        use crate::state::{Document, WorldState};
+
+        let code = "helper <- function(x) x\n";
+        let mut state = WorldState::new(vec![]);
+        let uri = Url::parse("file:///test.R").unwrap();
         state
             .documents
             .insert(uri.clone(), Document::new(code, None));
 
-        // Test goto_definition on "x" usage (line 2, position 5)
-        let position = Position::new(2, 5);
-        let result = goto_definition(&state, &uri, position);
+        let diags = super::diagnostics(&state, &uri);
+        assert!(
+            diags
+                .iter()
+                .all(|d| !d.message.contains(UNSOURCED_FILE_DIAGNOSTIC_MARKER)),
+            "unsourced-file diagnostics must be opt-in"
+        );
+    }
 
-        assert!(result.is_some(), "goto_definition should find definition");
+    #[test]
+    fn test_unsourced_file_flags_file_with_no_incoming_source_edge() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::DiagnosticSeverity;
 
-        if let Some(GotoDefinitionResponse::Scalar(location)) = result {
-            assert_eq!(location.uri, uri, "Should navigate to the same file");
-            // Position-aware definition finding returns the latest definition before usage
-            // So it should be line 1 (x <- 2), not line 0 (x <- 1)
-            assert_eq!(
-                location.range.start.line, 1,
-                "Should navigate to latest definition on line 1"
-            );
-        } else {
-            panic!("Expected Scalar response");
-        }
+        let code = "helper_func <- function(x) {\n    x * 2\n}";
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.unsourced_file_severity = Some(DiagnosticSeverity::WARNING);
+        let uri = Url::parse("file:///workspace/utils.R").unwrap();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let diags = super::diagnostics(&state, &uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| d.message.contains(UNSOURCED_FILE_DIAGNOSTIC_MARKER))
+            .expect("expected an unsourced-file diagnostic");
+        assert_eq!(diagnostic.range.start, Position::new(0, 0));
     }
-}
 
-#[cfg(test)]
-mod position_aware_tests {
-    use std::path::PathBuf;
-    use tower_lsp::lsp_types::{Position, Url, Range, Diagnostic};
-    use crate::handlers::{goto_definition, collect_undefined_variables_position_aware};
-    use crate::state::{WorldState, Document};
-    use crate::cross_file::directive::parse_directives;
+    #[test]
+    fn test_unsourced_file_silent_once_reached_by_source_chain() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::DiagnosticSeverity;
 
-    fn parse_r_code(code: &str) -> tree_sitter::Tree {
-        let mut parser = tree_sitter::Parser::new();
-        parser.set_language(&tree_sitter_r::LANGUAGE.into()).unwrap();
-        parser.parse(code, None).unwrap()
-    }
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.unsourced_file_severity = Some(DiagnosticSeverity::WARNING);
 
-    fn create_test_state() -> WorldState {
-        WorldState::new(vec![])
-    }
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let main_code = "source(\"utils.R\")\nresult <- helper_func(42)";
+        let utils_code = "helper_func <- function(x) {\n    x * 2\n}";
 
-    fn add_document(state: &mut WorldState, uri_str: &str, content: &str) -> Url {
-        let uri = Url::parse(uri_str).expect("Invalid URI");
-        let document = Document::new(content, None);
-        state.documents.insert(uri.clone(), document);
-        uri
-    }
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
+        );
 
-    #[test]
-    fn test_diagnostics_undefined_forward_reference() {
-        let mut state = create_test_state();
-        let code = "
-x
-x <- 1
-";
-        // Line 1: x (usage) - should be undefined
-        // Line 2: x <- 1 (definition)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        let tree = parse_r_code(code);
-        let root = tree.root_node();
-        let directive_meta = parse_directives(code);
-        
-        let mut diagnostics = Vec::new();
-        collect_undefined_variables_position_aware(
-            &state,
-            &uri,
-            root,
-            code,
-            &[], // deprecated loaded_packages
-            &[], // workspace_imports
-            &state.package_library,
-            &directive_meta,
-            &mut diagnostics
+        let diags = super::diagnostics(&state, &utils_uri);
+        assert!(
+            diags
+                .iter()
+                .all(|d| !d.message.contains(UNSOURCED_FILE_DIAGNOSTIC_MARKER)),
+            "utils.R is sourced by main.R, so it must not be flagged as orphaned"
         );
-        
-        assert_eq!(diagnostics.len(), 1, "Should have 1 diagnostic");
-        assert!(diagnostics[0].message.contains("Undefined variable: x"));
-        assert_eq!(diagnostics[0].range.start.line, 1);
     }
 
     #[test]
-    fn test_diagnostics_defined_before_usage() {
-        let mut state = create_test_state();
-        let code = "
-x <- 1
-x
-";
-        // Line 1: x <- 1
-        // Line 2: x (usage)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        let tree = parse_r_code(code);
-        let root = tree.root_node();
-        let directive_meta = parse_directives(code);
-        
-        let mut diagnostics = Vec::new();
-        collect_undefined_variables_position_aware(
+    fn test_code_action_offers_source_quickfix_for_unsourced_file() {
+        use crate::state::{Document, WorldState};
+        use tower_lsp::lsp_types::DiagnosticSeverity;
+
+        let mut state = WorldState::new(vec![]);
+        state.cross_file_config.unsourced_file_severity = Some(DiagnosticSeverity::WARNING);
+        state.workspace_folders = vec![Url::parse("file:///workspace/").unwrap()];
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let main_code = "result <- 1\n";
+        let utils_code = "helper_func <- function(x) {\n    x * 2\n}";
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+
+        let diags = super::diagnostics(&state, &utils_uri);
+        let diagnostic = diags
+            .iter()
+            .find(|d| d.message.contains(UNSOURCED_FILE_DIAGNOSTIC_MARKER))
+            .expect("expected an unsourced-file diagnostic");
+
+        let actions = code_action(
             &state,
-            &uri,
-            root,
-            code,
-            &[],
-            &[],
-            &state.package_library,
-            &directive_meta,
-            &mut diagnostics
-        );
-        
-        assert_eq!(diagnostics.len(), 0, "Should have 0 diagnostics");
+            &utils_uri,
+            diagnostic.range,
+            &[diagnostic.clone()],
+            None,
+        )
+        .expect("expected a quickfix action");
+        let action = actions
+            .iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(action)
+                    if action.title == "Add source(\"utils.R\") to main.R" =>
+                {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .expect("expected the add-source quickfix targeting main.R");
+
+        let edit = action.edit.as_ref().unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&main_uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "source(\"utils.R\")\n");
     }
 
     #[test]
-    fn test_diagnostics_redefined_later() {
-        let mut state = create_test_state();
-        let code = "
-x <- 1
-x
-x <- 2
-";
-        // Line 1: x <- 1
-        // Line 2: x (usage) - defined by line 1
-        // Line 3: x <- 2
-        let uri = add_document(&mut state, "file:///test.R", code);
-        let tree = parse_r_code(code);
-        let root = tree.root_node();
-        let directive_meta = parse_directives(code);
-        
-        let mut diagnostics = Vec::new();
-        collect_undefined_variables_position_aware(
-            &state,
-            &uri,
-            root,
-            code,
-            &[],
-            &[],
-            &state.package_library,
-            &directive_meta,
-            &mut diagnostics
+    fn test_check_workspace_aggregates_diagnostics_by_uri() {
+        use crate::state::{Document, WorldState};
+
+        let mut state = WorldState::new(vec![]);
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let utils_uri = Url::parse("file:///workspace/utils.R").unwrap();
+        let main_code = "source(\"utils.R\")\nresult <- helper_func(undefined_var)";
+        let utils_code = "helper_func <- function(x) {\n    x * 2\n}";
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state
+            .documents
+            .insert(utils_uri.clone(), Document::new(utils_code, None));
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &utils_uri,
+            &crate::cross_file::extract_metadata(utils_code),
+            None,
+            |_| None,
         );
-        
-        assert_eq!(diagnostics.len(), 0, "Should have 0 diagnostics");
-    }
 
-    #[test]
-    fn test_goto_definition_same_file_before_usage() {
-        let mut state = create_test_state();
-        let code = "
-x <- 1
-x
-";
-        // Line 1: x <- 1
-        // Line 2: x (usage)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        
-        // Usage at line 2, col 0
-        let pos = Position::new(2, 0);
-        let result = goto_definition(&state, &uri, pos);
-        
-        assert!(result.is_some(), "Should find definition");
-        let location = match result.unwrap() {
-            tower_lsp::lsp_types::GotoDefinitionResponse::Scalar(loc) => loc,
-            _ => panic!("Expected Scalar location"),
-        };
-        
-        assert_eq!(location.uri, uri);
-        assert_eq!(location.range.start.line, 1, "Definition should be on line 1");
-    }
+        let result = super::check_workspace(&state);
+        assert_eq!(result.files.len(), 2, "both files should be reported");
 
-    #[test]
-    fn test_goto_definition_same_file_after_usage() {
-        let mut state = create_test_state();
-        let code = "
-x
-x <- 1
-";
-        // Line 1: x (usage)
-        // Line 2: x <- 1 (definition)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        
-        // Usage at line 1, col 0
-        let pos = Position::new(1, 0);
-        let result = goto_definition(&state, &uri, pos);
-        
-        assert!(result.is_none(), "Should NOT find definition appearing after usage");
-    }
+        let main_entry = result
+            .files
+            .iter()
+            .find(|f| f.uri == main_uri)
+            .expect("main.R should be in the result");
+        assert!(
+            main_entry
+                .diagnostics
+                .iter()
+                .any(|d| d.message.contains("undefined_var")),
+            "main.R's own diagnostics should come through unchanged"
+        );
 
-    #[test]
-    fn test_goto_definition_function_scope_no_leak() {
-        let mut state = create_test_state();
-        let code = "
-f <- function() {
-    local_var <- 1
-}
-local_var
-";
-        // Line 1: f <- ...
-        // Line 2:     local_var <- 1
-        // Line 3: }
-        // Line 4: local_var (usage)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        
-        // Usage at line 4, col 0
-        let pos = Position::new(4, 0);
-        let result = goto_definition(&state, &uri, pos);
-        
-        assert!(result.is_none(), "Function-local variable should not be visible outside");
+        // Sorted by URI so a client gets a stable problems-panel order.
+        assert!(result.files[0].uri.as_str() <= result.files[1].uri.as_str());
     }
 
     #[test]
-    fn test_goto_definition_shadowing() {
-        let mut state = create_test_state();
-        let code = "
-x <- 1
-f <- function() {
-    x <- 2
-    x
-}
-";
-        // Line 1: x <- 1 (global)
-        // Line 2: f <- ...
-        // Line 3:     x <- 2 (local)
-        // Line 4:     x (usage)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        
-        // Usage at line 4, col 4
-        let pos = Position::new(4, 4);
-        let result = goto_definition(&state, &uri, pos);
-        
-        assert!(result.is_some());
-        let location = match result.unwrap() {
-            tower_lsp::lsp_types::GotoDefinitionResponse::Scalar(loc) => loc,
-            _ => panic!("Expected Scalar location"),
-        };
-        
-        assert_eq!(location.range.start.line, 3, "Should resolve to local definition (line 3)");
+    fn test_check_workspace_flags_untracked_source_target() {
+        use crate::state::{Document, WorldState};
+
+        let mut state = WorldState::new(vec![]);
+
+        let main_uri = Url::parse("file:///workspace/main.R").unwrap();
+        let main_code = "source(\"not_open.R\")\n";
+
+        state
+            .documents
+            .insert(main_uri.clone(), Document::new(main_code, None));
+        state.cross_file_graph.update_file(
+            &main_uri,
+            &crate::cross_file::extract_metadata(main_code),
+            None,
+            |_| None,
+        );
+
+        let result = super::check_workspace(&state);
+        let main_entry = result
+            .files
+            .iter()
+            .find(|f| f.uri == main_uri)
+            .expect("main.R should be in the result");
+        let diagnostic = main_entry
+            .diagnostics
+            .iter()
+            .find(|d| {
+                d.code
+                    == Some(NumberOrString::String(
+                        diagnostic_codes::UNTRACKED_SOURCE_TARGET.to_string(),
+                    ))
+            })
+            .expect("expected an untracked-source-target diagnostic");
+        assert!(diagnostic.message.contains("not_open.R"));
     }
 
     #[test]
-    fn test_goto_definition_sequential_redefinition() {
-        let mut state = create_test_state();
-        let code = "
-x <- 1
-x <- 2
-x
-";
-        // Line 1: x <- 1
-        // Line 2: x <- 2
-        // Line 3: x (usage)
-        let uri = add_document(&mut state, "file:///test.R", code);
-        
-        // Usage at line 3, col 0
-        let pos = Position::new(3, 0);
-        let result = goto_definition(&state, &uri, pos);
-        
-        assert!(result.is_some());
-        let location = match result.unwrap() {
-            tower_lsp::lsp_types::GotoDefinitionResponse::Scalar(loc) => loc,
-            _ => panic!("Expected Scalar location"),
-        };
-        
-        assert_eq!(location.range.start.line, 2, "Should resolve to latest definition (line 2)");
+    fn test_check_workspace_handles_cycles_without_hanging() {
+        use crate::state::{Document, WorldState};
+
+        let mut state = WorldState::new(vec![]);
+
+        let a_uri = Url::parse("file:///workspace/a.R").unwrap();
+        let b_uri = Url::parse("file:///workspace/b.R").unwrap();
+        let a_code = "source(\"b.R\")\n";
+        let b_code = "source(\"a.R\")\n";
+
+        state
+            .documents
+            .insert(a_uri.clone(), Document::new(a_code, None));
+        state
+            .documents
+            .insert(b_uri.clone(), Document::new(b_code, None));
+        state.cross_file_graph.update_file(
+            &a_uri,
+            &crate::cross_file::extract_metadata(a_code),
+            None,
+            |_| None,
+        );
+        state.cross_file_graph.update_file(
+            &b_uri,
+            &crate::cross_file::extract_metadata(b_code),
+            None,
+            |_| None,
+        );
+
+        // A mutual source() cycle has no root; the call simply needs to
+        // terminate and still report both files.
+        let result = super::check_workspace(&state);
+        assert_eq!(result.files.len(), 2);
     }
 }
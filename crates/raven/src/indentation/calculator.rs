@@ -3,7 +3,7 @@
 //! This module computes the correct indentation amount based on the
 //! detected context and user configuration (tab size, style preference).
 
-use super::context::IndentContext;
+use super::context::{IndentContext, OperatorType};
 
 /// Configuration for indentation calculation.
 #[derive(Debug, Clone, PartialEq)]
@@ -38,6 +38,11 @@ pub enum IndentationStyle {
     /// RStudio-minus style: all arguments indent +tab_size from
     /// previous line regardless of paren position.
     RStudioMinus,
+    /// Align pipe chain continuations to the chain's first pipe operator
+    /// (or, failing that, its leading expression) instead of a flat
+    /// +tab_size indent, so `%>%`/`|>` line up vertically across stages.
+    /// Falls back to the same behavior as `RStudio` for non-pipe contexts.
+    AlignToPipe,
     /// Off: disable Tier 2 AST-aware indentation entirely.
     /// The onTypeFormatting handler returns None (no edits),
     /// leaving only Tier 1 declarative rules active.
@@ -64,8 +69,15 @@ pub fn calculate_indentation(
         IndentContext::AfterContinuationOperator {
             chain_start_line,
             chain_start_col,
-            operator_type: _,
+            operator_type,
         } => {
+            if config.style == IndentationStyle::AlignToPipe
+                && matches!(operator_type, OperatorType::Pipe | OperatorType::MagrittrPipe)
+            {
+                if let Some(pipe_col) = pipe_operator_column(source, chain_start_line, config.tab_size) {
+                    return pipe_col;
+                }
+            }
             // Align to chain start column (RHS of assignment if present)
             // but ensure at least one tab_size indent from the line start.
             let line_indent = get_line_indent(source, chain_start_line, config.tab_size);
@@ -90,6 +102,16 @@ pub fn calculate_indentation(
                     // Always indent from opener line + tab_size
                     get_line_indent(source, opener_line, config.tab_size).saturating_add(config.tab_size)
                 }
+                IndentationStyle::AlignToPipe => {
+                    // AlignToPipe only changes pipe-chain continuation
+                    // alignment; function-argument alignment falls back to
+                    // the same behavior as RStudio.
+                    if has_content_on_opener_line {
+                        opener_col.saturating_add(1)
+                    } else {
+                        get_line_indent(source, opener_line, config.tab_size).saturating_add(config.tab_size)
+                    }
+                }
                 IndentationStyle::Off => {
                     // Off should be handled before reaching calculate_indentation
                     // (the handler returns None early). Fallback to basic indent.
@@ -137,6 +159,56 @@ pub fn get_line_indent(source: &str, line: u32, tab_size: u32) -> u32 {
         .unwrap_or(0)
 }
 
+/// Finds the column of the first pipe operator (`|>`, `%>%`, or a custom
+/// `%word%` infix) on `line`, expressed in the file's configured indent
+/// units (tabs expand to `tab_size` columns). Falls back to `None` when the
+/// line has no pipe operator, in which case the caller should fall back to
+/// the chain's leading-expression column instead.
+fn pipe_operator_column(source: &str, line: u32, tab_size: u32) -> Option<u32> {
+    let line_text = source.lines().nth(line as usize)?;
+
+    if let Some(byte_idx) = line_text.find("|>") {
+        return Some(column_at_byte(line_text, byte_idx, tab_size));
+    }
+
+    // Magrittr pipe %>% or a custom infix %word%: find a %...% span whose
+    // contents look like an operator (mirrors context.rs's own
+    // `line_ends_with_operator` validity check).
+    let bytes = line_text.as_bytes();
+    let mut open: Option<usize> = None;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b != b'%' {
+            continue;
+        }
+        match open {
+            None => open = Some(i),
+            Some(start) => {
+                let between = &line_text[start + 1..i];
+                if !between.is_empty()
+                    && between.chars().all(|c| {
+                        c.is_alphanumeric()
+                            || matches!(c, '.' | '>' | '<' | '*' | '/' | '|' | '&' | '!' | '=')
+                    })
+                {
+                    return Some(column_at_byte(line_text, start, tab_size));
+                }
+                open = Some(i);
+            }
+        }
+    }
+
+    None
+}
+
+/// Converts a byte offset within `line_text` to a column expressed in the
+/// file's configured indent units (tabs expand to `tab_size` columns).
+fn column_at_byte(line_text: &str, byte_idx: usize, tab_size: u32) -> u32 {
+    line_text[..byte_idx.min(line_text.len())]
+        .chars()
+        .map(|c| if c == '\t' { tab_size } else { 1 })
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -461,6 +533,163 @@ mod tests {
         }
     }
 
+    // ========================================================================
+    // AlignToPipe Style Tests
+    // ========================================================================
+
+    #[test]
+    fn test_align_to_pipe_aligns_to_native_pipe_operator() {
+        use super::super::context::OperatorType;
+
+        let source = "result <- data |>\n";
+        let config = IndentationConfig {
+            tab_size: 2,
+            insert_spaces: true,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        let context = IndentContext::AfterContinuationOperator {
+            chain_start_line: 0,
+            chain_start_col: 0,
+            operator_type: OperatorType::Pipe,
+        };
+
+        let indent = calculate_indentation(context, config, source);
+        assert_eq!(indent, "result <- data ".len() as u32);
+    }
+
+    #[test]
+    fn test_align_to_pipe_aligns_to_magrittr_pipe_operator() {
+        use super::super::context::OperatorType;
+
+        let source = "result <- data %>%\n";
+        let config = IndentationConfig {
+            tab_size: 2,
+            insert_spaces: true,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        let context = IndentContext::AfterContinuationOperator {
+            chain_start_line: 0,
+            chain_start_col: 0,
+            operator_type: OperatorType::MagrittrPipe,
+        };
+
+        let indent = calculate_indentation(context, config, source);
+        assert_eq!(indent, "result <- data ".len() as u32);
+    }
+
+    #[test]
+    fn test_align_to_pipe_falls_back_when_no_operator_on_anchor_line() {
+        use super::super::context::OperatorType;
+
+        // Chain start line has no pipe operator (e.g. heuristic picked a
+        // line without one) - fall back to the flat max(chain_start_col,
+        // line_indent + tab_size) behavior.
+        let source = "data\n";
+        let config = IndentationConfig {
+            tab_size: 2,
+            insert_spaces: true,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        let context = IndentContext::AfterContinuationOperator {
+            chain_start_line: 0,
+            chain_start_col: 0,
+            operator_type: OperatorType::Pipe,
+        };
+
+        let indent = calculate_indentation(context, config, source);
+        assert_eq!(indent, 2); // max(0, 0 + 2)
+    }
+
+    #[test]
+    fn test_align_to_pipe_does_not_affect_non_pipe_operators() {
+        use super::super::context::OperatorType;
+
+        // Plus chains keep the flat-indent behavior even under AlignToPipe.
+        let source = "total <- a +\n";
+        let config = IndentationConfig {
+            tab_size: 2,
+            insert_spaces: true,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        let context = IndentContext::AfterContinuationOperator {
+            chain_start_line: 0,
+            chain_start_col: 0,
+            operator_type: OperatorType::Plus,
+        };
+
+        let indent = calculate_indentation(context, config, source);
+        assert_eq!(indent, 2); // max(0, 0 + 2), unaffected by the pipe on no line
+    }
+
+    #[test]
+    fn test_align_to_pipe_expands_tabs_to_tab_size_columns() {
+        use super::super::context::OperatorType;
+
+        let source = "\tresult <- data |>\n";
+        let config = IndentationConfig {
+            tab_size: 4,
+            insert_spaces: false,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        let context = IndentContext::AfterContinuationOperator {
+            chain_start_line: 0,
+            chain_start_col: 1,
+            operator_type: OperatorType::Pipe,
+        };
+
+        let indent = calculate_indentation(context, config, source);
+        // 1 leading tab (= 4 columns) + len("result <- data ") chars.
+        assert_eq!(indent, 4 + "result <- data ".len() as u32);
+    }
+
+    #[test]
+    fn test_align_to_pipe_aligns_custom_infix_operator() {
+        use super::super::context::OperatorType;
+
+        let source = "result <- data %||%\n";
+        let config = IndentationConfig {
+            tab_size: 2,
+            insert_spaces: true,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        // %||% isn't classified as Pipe/MagrittrPipe by OperatorType, so this
+        // intentionally exercises the fallback (CustomInfix isn't aligned).
+        let context = IndentContext::AfterContinuationOperator {
+            chain_start_line: 0,
+            chain_start_col: 0,
+            operator_type: OperatorType::CustomInfix,
+        };
+
+        let indent = calculate_indentation(context, config, source);
+        assert_eq!(indent, 2); // max(0, 0 + 2)
+    }
+
+    #[test]
+    fn test_align_to_pipe_inside_parens_falls_back_to_rstudio_behavior() {
+        // AlignToPipe only changes pipe-chain alignment; function-argument
+        // alignment behaves like RStudio.
+        let config = IndentationConfig {
+            tab_size: 2,
+            insert_spaces: true,
+            style: IndentationStyle::AlignToPipe,
+        };
+
+        let context = IndentContext::InsideParens {
+            opener_line: 0,
+            opener_col: 4,
+            has_content_on_opener_line: true,
+        };
+
+        let indent = calculate_indentation(context, config, "");
+        assert_eq!(indent, 5); // opener_col + 1, same as RStudio
+    }
+
     // ========================================================================
     // Function Argument Alignment Tests (Task 5.3)
     // ========================================================================
@@ -24,8 +24,11 @@ pub use formatter::format_indentation;
 
 /// Returns the LSP capability options for on-type formatting.
 ///
-/// This registers the newline character (`\n`) as the trigger for on-type formatting,
-/// enabling AST-aware indentation when the user presses Enter.
+/// Registers the newline character (`\n`) as the primary trigger, enabling
+/// AST-aware indentation when the user presses Enter, plus the closing
+/// delimiters `)`, `]`, `}` as additional triggers so the handler also runs
+/// the moment a block is closed (both to dedent the closing delimiter and to
+/// clean up VS Code's duplicate auto-closed bracket).
 ///
 /// # Requirements
 ///
@@ -34,7 +37,7 @@ pub use formatter::format_indentation;
 pub fn on_type_formatting_capability() -> DocumentOnTypeFormattingOptions {
     DocumentOnTypeFormattingOptions {
         first_trigger_character: "\n".to_string(),
-        more_trigger_character: None,
+        more_trigger_character: Some(vec![")".to_string(), "]".to_string(), "}".to_string()]),
     }
 }
 
@@ -43,7 +46,8 @@ pub fn on_type_formatting_capability() -> DocumentOnTypeFormattingOptions {
 mod tests {
     use super::on_type_formatting_capability;
 
-    /// Test that server capabilities include onTypeFormatting with trigger "\n".
+    /// Test that server capabilities include onTypeFormatting with trigger "\n"
+    /// plus the closing delimiters as additional triggers.
     ///
     /// **Validates: Requirement 8.1** - Register `textDocument/onTypeFormatting`
     /// capability with trigger character `"\n"`.
@@ -57,10 +61,12 @@ mod tests {
             "first_trigger_character should be newline"
         );
 
-        // Verify more_trigger_character is None
+        // Verify the closing delimiters are registered so dedent-on-close and
+        // duplicate-bracket cleanup actually fire.
         assert_eq!(
-            capability.more_trigger_character, None,
-            "more_trigger_character should be None"
+            capability.more_trigger_character,
+            Some(vec![")".to_string(), "]".to_string(), "}".to_string()]),
+            "more_trigger_character should include the closing delimiters"
         );
     }
 }
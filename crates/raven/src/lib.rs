@@ -33,5 +33,6 @@ pub mod perf;
 pub mod r_env;
 pub mod r_subprocess;
 pub mod roxygen;
+pub mod string_utils;
 pub mod utf16;
 pub mod workspace_index;
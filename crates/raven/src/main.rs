@@ -26,6 +26,7 @@ mod r_subprocess;
 mod reserved_words;
 mod roxygen;
 mod state;
+mod string_utils;
 mod workspace_index;
 
 #[cfg(any(test, feature = "test-support"))]
@@ -43,6 +44,7 @@ fn print_usage() {
         r#"
 Usage: raven [OPTIONS]
        raven analysis-stats <path> [--csv] [--only <phase>]
+       raven check <paths...> [--format <format>]
 
 Available options:
 
@@ -57,6 +59,11 @@ analysis-stats <path>        Profile workspace analysis phases
   --only <phase>             Run only the specified phase
                              (scan, parse, metadata, scope, packages)
 
+check <paths...>             Lint files/directories headlessly, printing one
+                             JSON diagnostic per line; exits non-zero on any
+                             error-severity diagnostic
+  --format <format>          Output format (default, and only supported: json)
+
 "#
     );
 }
@@ -90,6 +97,24 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+
+        if first == "check" {
+            env_logger::init();
+            let mut rest = args.into_iter().skip(1);
+            match cli::check::parse_args(&mut rest) {
+                Ok(check_args) => {
+                    let records = cli::check::run_check(&check_args);
+                    cli::check::print_records_json(&records);
+                    if cli::check::has_error(&records) {
+                        std::process::exit(1);
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    return Err(anyhow::anyhow!("check: {}", e));
+                }
+            }
+        }
     }
 
     for arg in &args {
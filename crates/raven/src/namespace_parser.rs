@@ -354,6 +354,27 @@ pub fn parse_description_depends(description_path: &Path) -> Result<Vec<String>>
     Ok(parse_description_field(&content, "Depends"))
 }
 
+/// Extracts package names from an arbitrary DESCRIPTION (DCF) field, e.g.
+/// `"Imports"`, `"LinkingTo"`, or `"Suggests"` - the generalized form of
+/// [`parse_description_depends`], which only ever reads `"Depends"`.
+///
+/// Returns an empty vector if `field_name` is absent, the same as
+/// `parse_description_depends` does for a missing `Depends` field.
+pub fn parse_description_field_names(
+    description_path: &Path,
+    field_name: &str,
+) -> Result<Vec<String>> {
+    let content = fs::read_to_string(description_path).map_err(|e| {
+        anyhow!(
+            "Failed to read DESCRIPTION file {:?}: {}",
+            description_path,
+            e
+        )
+    })?;
+
+    Ok(parse_description_field(&content, field_name))
+}
+
 /// Extracts the value of a named field from DESCRIPTION (DCF) content and parses it into package names.
 ///
 /// The function locates `field_name:` at the start of a line, accumulates its value including continuation
@@ -434,6 +455,91 @@ fn parse_depends_value(value: &str) -> Vec<String> {
         .collect()
 }
 
+/// Locates the top-level definition of `symbol` within a package's `R/` source directory.
+///
+/// Installed packages that keep plain-text sources under `R/` (source installs, packages
+/// loaded via `devtools::load_all`, or builds configured with `--with-keep.source`) can be
+/// searched directly: `.R` files are visited in sorted order and each line is checked for a
+/// top-level `symbol <- function` or `symbol = function` assignment. Byte-compiled or
+/// binary-only installations have no `.R` files to search and simply yield `None`.
+///
+/// # Returns
+///
+/// `Some((relative_path, line, column))` for the first matching assignment found, with
+/// `relative_path` relative to `package_dir` and `line`/`column` 0-based; `None` if the
+/// package has no `R/` directory or no file defines `symbol` at the top level.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// // Given a package directory containing `R/mutate.R` with `mutate <- function(x) x`,
+/// // `find_exported_definition` returns Some((PathBuf::from("R/mutate.R"), 0, 0)).
+/// let _ = Path::new("R");
+/// ```
+pub fn find_exported_definition(
+    package_dir: &Path,
+    symbol: &str,
+) -> Option<(std::path::PathBuf, u32, u32)> {
+    let r_dir = package_dir.join("R");
+    if !r_dir.is_dir() {
+        return None;
+    }
+
+    let mut entries: Vec<std::path::PathBuf> = fs::read_dir(&r_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("R"))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Some(line) = find_top_level_assignment_line(&content, symbol) {
+            let relative = path
+                .strip_prefix(package_dir)
+                .unwrap_or(&path)
+                .to_path_buf();
+            return Some((relative, line, 0));
+        }
+    }
+
+    None
+}
+
+/// Finds the 0-based line number of a top-level `symbol <- function` or `symbol = function`
+/// assignment in R source text.
+fn find_top_level_assignment_line(content: &str, symbol: &str) -> Option<u32> {
+    for (index, line) in content.lines().enumerate() {
+        // Top level means starting at column 0; an indented assignment sits
+        // inside some enclosing block.
+        let trimmed = line;
+        let Some(rest) = trimmed.strip_prefix(symbol) else {
+            continue;
+        };
+        if rest
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '.' || c == '_')
+        {
+            // `rest` continues the identifier (e.g. matching "mutate" against
+            // "mutate_all"), so this isn't actually an assignment to `symbol`.
+            continue;
+        }
+        let rest = rest.trim_start();
+        let Some(rest) = rest.strip_prefix("<-").or_else(|| rest.strip_prefix('=')) else {
+            continue;
+        };
+        if rest.trim_start().starts_with("function") {
+            return Some(index as u32);
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1102,6 +1208,73 @@ importFrom(magrittr, "%>%")
         assert!(!exports.iter().any(|e| e.contains("tibble")));
     }
 
+    // Tests for find_exported_definition
+
+    #[test]
+    fn test_find_exported_definition_locates_top_level_function() {
+        let tmp = tempfile::TempDir::new().expect("create temp package dir");
+        let r_dir = tmp.path().join("R");
+        std::fs::create_dir(&r_dir).unwrap();
+        std::fs::write(
+            r_dir.join("mutate.R"),
+            "mutate <- function(x, ...) {\n  x\n}\n",
+        )
+        .unwrap();
+
+        let result = find_exported_definition(tmp.path(), "mutate");
+        assert_eq!(result, Some((std::path::PathBuf::from("R/mutate.R"), 0, 0)));
+    }
+
+    #[test]
+    fn test_find_exported_definition_searches_multiple_files() {
+        let tmp = tempfile::TempDir::new().expect("create temp package dir");
+        let r_dir = tmp.path().join("R");
+        std::fs::create_dir(&r_dir).unwrap();
+        std::fs::write(r_dir.join("a_helpers.R"), "helper <- function() NULL\n").unwrap();
+        std::fs::write(
+            r_dir.join("b_filter.R"),
+            "x <- 1\nfilter <- function(df) df\n",
+        )
+        .unwrap();
+
+        let result = find_exported_definition(tmp.path(), "filter");
+        assert_eq!(
+            result,
+            Some((std::path::PathBuf::from("R/b_filter.R"), 1, 0))
+        );
+    }
+
+    #[test]
+    fn test_find_exported_definition_no_r_directory_returns_none() {
+        let tmp = tempfile::TempDir::new().expect("create temp package dir");
+        assert_eq!(find_exported_definition(tmp.path(), "mutate"), None);
+    }
+
+    #[test]
+    fn test_find_exported_definition_symbol_not_found_returns_none() {
+        let tmp = tempfile::TempDir::new().expect("create temp package dir");
+        let r_dir = tmp.path().join("R");
+        std::fs::create_dir(&r_dir).unwrap();
+        std::fs::write(r_dir.join("mutate.R"), "mutate <- function(x) x\n").unwrap();
+
+        assert_eq!(find_exported_definition(tmp.path(), "filter"), None);
+    }
+
+    #[test]
+    fn test_find_exported_definition_does_not_match_identifier_prefix() {
+        let tmp = tempfile::TempDir::new().expect("create temp package dir");
+        let r_dir = tmp.path().join("R");
+        std::fs::create_dir(&r_dir).unwrap();
+        std::fs::write(
+            r_dir.join("mutate.R"),
+            "mutate_all <- function(x) x\nmutate <- function(x) x\n",
+        )
+        .unwrap();
+
+        let result = find_exported_definition(tmp.path(), "mutate");
+        assert_eq!(result, Some((std::path::PathBuf::from("R/mutate.R"), 1, 0)));
+    }
+
     // ============================================================================
     // Property-Based Tests for NAMESPACE Parsing
     // Feature: package-function-awareness, Property 5: Package Export Round-Trip
@@ -14,7 +14,9 @@
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::sync::RwLock;
+use tree_sitter::{Node, Tree};
 
 use crate::r_subprocess::RSubprocess;
 
@@ -56,6 +58,73 @@ pub const TIDYMODELS_PACKAGES: &[&str] = &[
     "yardstick",
 ];
 
+/// Package names a document references: every `library(pkg)`, `require(pkg)`,
+/// and `requireNamespace("pkg")` argument, plus the namespace in every
+/// `pkg::fn`/`pkg:::fn` call, each validated with
+/// [`crate::r_subprocess::is_valid_package_name`].
+///
+/// This is the source-driven counterpart to `state::extract_loaded_packages`:
+/// that function only tracks calls that *attach* a package (for completion
+/// visibility), while this one also picks up `requireNamespace` and `::`
+/// usage that references a package without attaching it, so the resolver can
+/// still pull in its exports and transitive `Depends`. Results are returned
+/// in first-seen order with duplicates dropped.
+pub fn collect_referenced_packages(tree: &Option<Tree>, text: &str) -> Vec<String> {
+    let Some(tree) = tree else {
+        return Vec::new();
+    };
+
+    fn record(name: &str, seen: &mut HashSet<String>, out: &mut Vec<String>) {
+        if crate::r_subprocess::is_valid_package_name(name) && seen.insert(name.to_string()) {
+            out.push(name.to_string());
+        }
+    }
+
+    fn visit_node(node: Node, text: &str, seen: &mut HashSet<String>, out: &mut Vec<String>) {
+        if node.kind() == "call" {
+            if let Some(func_node) = node.child_by_field_name("function") {
+                let func_text = &text[func_node.byte_range()];
+                if func_text == "library" || func_text == "require" || func_text == "requireNamespace"
+                {
+                    if let Some(args_node) = node.child_by_field_name("arguments") {
+                        for i in 0..args_node.child_count() {
+                            if let Some(child) = args_node.child(i) {
+                                if child.kind() == "argument" {
+                                    if let Some(value_node) = child.child_by_field_name("value") {
+                                        let value_text = &text[value_node.byte_range()];
+                                        let pkg_name =
+                                            value_text.trim_matches(|c: char| c == '"' || c == '\'');
+                                        record(pkg_name, seen, out);
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        } else if node.kind() == "namespace_operator" {
+            // Children: [namespace_identifier, "::" or ":::", function_identifier]
+            let mut cursor = node.walk();
+            if let Some(ns_node) = node.children(&mut cursor).next() {
+                let ns_name = &text[ns_node.byte_range()];
+                record(ns_name, seen, out);
+            }
+        }
+
+        for i in 0..node.child_count() {
+            if let Some(child) = node.child(i) {
+                visit_node(child, text, seen, out);
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    visit_node(tree.root_node(), text, &mut seen, &mut out);
+    out
+}
+
 /// Cached package information
 ///
 /// Stores all relevant information about an R package including its exports,
@@ -145,6 +214,11 @@ pub struct PackageLibrary {
     base_packages: HashSet<String>,
     /// Base package exports (combined from all base packages)
     base_exports: HashSet<String>,
+    /// Package directory mtime at the time each `packages` entry was cached.
+    /// Checked on every `get_package` lookup so a reinstalled/updated
+    /// package (e.g. after switching an renv library) is re-queried instead
+    /// of serving stale exports for the lifetime of the process.
+    package_dir_mtimes: RwLock<HashMap<String, SystemTime>>,
     /// R subprocess interface (None if R is unavailable)
     #[allow(dead_code)] // Will be used in task 3.3
     r_subprocess: Option<RSubprocess>,
@@ -164,6 +238,7 @@ impl PackageLibrary {
             combined_exports: RwLock::new(HashMap::new()),
             base_packages: HashSet::new(),
             base_exports: HashSet::new(),
+            package_dir_mtimes: RwLock::new(HashMap::new()),
             r_subprocess: None,
         }
     }
@@ -183,6 +258,7 @@ impl PackageLibrary {
             combined_exports: RwLock::new(HashMap::new()),
             base_packages: HashSet::new(),
             base_exports: HashSet::new(),
+            package_dir_mtimes: RwLock::new(HashMap::new()),
             r_subprocess,
         }
     }
@@ -380,12 +456,14 @@ impl PackageLibrary {
     pub async fn invalidate(&self, name: &str) {
         let mut cache = self.packages.write().await;
         cache.remove(name);
+        self.package_dir_mtimes.write().await.remove(name);
     }
 
     /// Clear all cached packages
     pub async fn clear_cache(&self) {
         let mut cache = self.packages.write().await;
         cache.clear();
+        self.package_dir_mtimes.write().await.clear();
     }
 
     /// Prefetch packages by loading their exports into cache
@@ -458,6 +536,72 @@ impl PackageLibrary {
         None
     }
 
+    /// Find every loaded package that exports a symbol (synchronous, cached-only)
+    ///
+    /// Like `find_package_for_symbol`, but collects every match instead of
+    /// stopping at the first — used by the "qualify call with package" code
+    /// action to tell an unambiguous export (exactly one candidate) from a
+    /// masked one (several candidates, one action offered per package).
+    pub fn find_all_packages_for_symbol(
+        &self,
+        symbol: &str,
+        loaded_packages: &[String],
+    ) -> Vec<String> {
+        let combined_cache = self.combined_exports.try_read().ok();
+        let packages_cache = self.packages.try_read().ok();
+
+        loaded_packages
+            .iter()
+            .filter(|pkg_name| {
+                combined_cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(pkg_name.as_str()))
+                    .is_some_and(|exports| exports.contains(symbol))
+                    || packages_cache
+                        .as_ref()
+                        .and_then(|cache| cache.get(pkg_name.as_str()))
+                        .is_some_and(|info| info.exports.contains(symbol))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// All package names with cached export info (synchronous, cached-only)
+    ///
+    /// Used as the candidate list for `find_package_for_symbol` when the
+    /// caller doesn't already know which packages are loaded — e.g. the
+    /// "Add missing library()" code action, which only has a symbol name and
+    /// needs to search whatever packages have been queried so far.
+    pub fn cached_package_names(&self) -> Vec<String> {
+        let Ok(cache) = self.packages.try_read() else {
+            return Vec::new();
+        };
+        cache.keys().cloned().collect()
+    }
+
+    /// All installed package names: every base package plus every
+    /// subdirectory of every library path, deduplicated. Unlike
+    /// `cached_package_names`, this doesn't depend on a package having been
+    /// queried/loaded yet, so it's suitable as the candidate list for "did
+    /// you mean" typo suggestions on an unresolved `library()` call. This is
+    /// a cheap filesystem scan, not an R subprocess round trip.
+    pub fn installed_package_names(&self) -> Vec<String> {
+        let mut names: HashSet<String> = self.base_packages.clone();
+        for lib_path in &self.lib_paths {
+            let Ok(entries) = std::fs::read_dir(lib_path) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    if let Some(name) = entry.file_name().to_str() {
+                        names.insert(name.to_string());
+                    }
+                }
+            }
+        }
+        names.into_iter().collect()
+    }
+
     /// Set the library paths
     ///
     /// This is used during initialization to set the library paths
@@ -651,10 +795,22 @@ impl PackageLibrary {
     /// Requirement 4.4: WHEN the package is `tidymodels`, THE Package_Resolver SHALL also load
     /// exports from the tidymodels packages
     pub async fn get_package(&self, name: &str) -> Option<Arc<PackageInfo>> {
-        // Step 1: Check cache first
+        // Step 1: Check cache first, but only trust it while the package's
+        // on-disk directory mtime still matches what we cached - a reinstall
+        // or library switch should be picked up instead of serving stale
+        // exports for the rest of the process lifetime.
         if let Some(cached) = self.get_cached_package(name).await {
-            log::trace!("Package '{}' found in cache", name);
-            return Some(cached);
+            let current_mtime = self.package_dir_mtime(name);
+            let cached_mtime = self.package_dir_mtimes.read().await.get(name).copied();
+            if current_mtime == cached_mtime {
+                log::trace!("Package '{}' found in cache", name);
+                return Some(cached);
+            }
+            log::trace!(
+                "Package '{}' directory mtime changed since caching, invalidating",
+                name
+            );
+            self.invalidate(name).await;
         }
 
         log::trace!("Package '{}' not in cache, attempting to load", name);
@@ -727,13 +883,71 @@ impl PackageLibrary {
             info.is_meta_package
         );
 
-        // Insert into cache
+        // Insert into cache, recording the directory mtime it was loaded at
+        // (or clearing any stale record if the package isn't on disk).
         self.insert_package(info).await;
+        match self.package_dir_mtime(name) {
+            Some(mtime) => {
+                self.package_dir_mtimes
+                    .write()
+                    .await
+                    .insert(name.to_string(), mtime);
+            }
+            None => {
+                self.package_dir_mtimes.write().await.remove(name);
+            }
+        }
 
         // Return the cached version
         self.get_cached_package(name).await
     }
 
+    /// The last-modified time of `name`'s installed directory, if it's
+    /// present in one of `lib_paths`. Used to decide whether a cached
+    /// `PackageInfo` is still fresh.
+    fn package_dir_mtime(&self, name: &str) -> Option<SystemTime> {
+        let dir = self.find_package_directory(name)?;
+        std::fs::metadata(&dir).ok()?.modified().ok()
+    }
+
+    /// Resolve every package a document references into its `PackageInfo`,
+    /// following each package's `depends` edges until the transitive closure
+    /// is exhausted.
+    ///
+    /// This is the connection point between real editor state and the
+    /// subprocess/filesystem query layer: [`collect_referenced_packages`]
+    /// finds the `library()`/`require()`/`requireNamespace()`/`::` usage in
+    /// `tree`, and each name is then loaded via [`get_package`](Self::get_package)
+    /// (cache, R subprocess, or NAMESPACE/DESCRIPTION fallback, in that
+    /// order), which also populates the shared package cache for later
+    /// lookups. The `R` pseudo-dependency never appears in `depends` since
+    /// `parse_depends_field` already strips it.
+    pub async fn resolve_document_packages(
+        &self,
+        tree: &Option<Tree>,
+        text: &str,
+    ) -> HashMap<String, Arc<PackageInfo>> {
+        let mut resolved: HashMap<String, Arc<PackageInfo>> = HashMap::new();
+        let mut worklist: Vec<String> = collect_referenced_packages(tree, text);
+
+        while let Some(name) = worklist.pop() {
+            if resolved.contains_key(&name) {
+                continue;
+            }
+            let Some(info) = self.get_package(&name).await else {
+                continue;
+            };
+            for dep in &info.depends {
+                if !resolved.contains_key(dep) {
+                    worklist.push(dep.clone());
+                }
+            }
+            resolved.insert(name, info);
+        }
+
+        resolved
+    }
+
     /// Load package exports and depends from filesystem (NAMESPACE/DESCRIPTION files)
     ///
     /// This is the fallback when R subprocess is unavailable.
@@ -815,6 +1029,37 @@ impl PackageLibrary {
         }
     }
 
+    /// Locate the real on-disk definition of an exported symbol for goto-definition.
+    ///
+    /// Finds `package`'s directory in `lib_paths` and, if its `R/` source is present
+    /// (see [`namespace_parser::find_exported_definition`]), returns the path (relative to
+    /// the package directory), 0-based line, and 0-based column of `symbol`'s top-level
+    /// assignment. Returns `None` if the package isn't installed or its exports are only
+    /// available as a compiled lazy-load database with no `.R` source to point at.
+    pub fn find_exported_definition(
+        &self,
+        package: &str,
+        symbol: &str,
+    ) -> Option<(std::path::PathBuf, u32, u32)> {
+        let package_dir = self.find_package_directory(package)?;
+        crate::namespace_parser::find_exported_definition(&package_dir, symbol)
+    }
+
+    /// Read the contents of one of `package`'s files, given a path relative to its package
+    /// directory (as returned by [`find_exported_definition`](Self::find_exported_definition)).
+    ///
+    /// Used to serve the content of the synthetic `raven-package:` documents that
+    /// goto-definition points at. Returns `None` if the package can't be found or the file
+    /// can't be read.
+    pub fn read_source_file(
+        &self,
+        package: &str,
+        relative_path: &std::path::Path,
+    ) -> Option<String> {
+        let package_dir = self.find_package_directory(package)?;
+        std::fs::read_to_string(package_dir.join(relative_path)).ok()
+    }
+
     /// Find the package directory in lib_paths
     ///
     /// Searches each library path for a directory with the package name.
@@ -1097,6 +1342,51 @@ mod tests {
         assert!(!lib.is_cached("testpkg").await);
     }
 
+    #[tokio::test]
+    async fn test_find_all_packages_for_symbol_returns_every_match() {
+        let lib = PackageLibrary::new_empty();
+
+        let mut dplyr_exports = HashSet::new();
+        dplyr_exports.insert("filter".to_string());
+        lib.insert_package(PackageInfo::new("dplyr".to_string(), dplyr_exports))
+            .await;
+
+        let mut stats_exports = HashSet::new();
+        stats_exports.insert("filter".to_string());
+        lib.insert_package(PackageInfo::new("stats".to_string(), stats_exports))
+            .await;
+
+        let mut tidyr_exports = HashSet::new();
+        tidyr_exports.insert("pivot_longer".to_string());
+        lib.insert_package(PackageInfo::new("tidyr".to_string(), tidyr_exports))
+            .await;
+
+        let loaded = vec![
+            "dplyr".to_string(),
+            "stats".to_string(),
+            "tidyr".to_string(),
+        ];
+        let mut matches = lib.find_all_packages_for_symbol("filter", &loaded);
+        matches.sort();
+
+        assert_eq!(matches, vec!["dplyr".to_string(), "stats".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_find_all_packages_for_symbol_no_match() {
+        let lib = PackageLibrary::new_empty();
+
+        let mut exports = HashSet::new();
+        exports.insert("mutate".to_string());
+        lib.insert_package(PackageInfo::new("dplyr".to_string(), exports))
+            .await;
+
+        let loaded = vec!["dplyr".to_string()];
+        assert!(lib
+            .find_all_packages_for_symbol("filter", &loaded)
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_package_library_clear_cache() {
         let lib = PackageLibrary::new_empty();
@@ -1656,6 +1946,58 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_find_exported_definition_locates_r_source() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        let pkg_dir = tmp.path().join("dplyr");
+        let r_dir = pkg_dir.join("R");
+        std::fs::create_dir_all(&r_dir).unwrap();
+        std::fs::write(r_dir.join("mutate.R"), "mutate <- function(x) x\n").unwrap();
+
+        let mut lib = PackageLibrary::new_empty();
+        lib.set_lib_paths(vec![tmp.path().to_path_buf()]);
+
+        let result = lib.find_exported_definition("dplyr", "mutate");
+        assert_eq!(result, Some((PathBuf::from("R/mutate.R"), 0, 0)));
+    }
+
+    #[test]
+    fn test_find_exported_definition_unknown_package_returns_none() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        let mut lib = PackageLibrary::new_empty();
+        lib.set_lib_paths(vec![tmp.path().to_path_buf()]);
+
+        assert!(lib.find_exported_definition("dplyr", "mutate").is_none());
+    }
+
+    #[test]
+    fn test_read_source_file_returns_contents() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        let pkg_dir = tmp.path().join("dplyr");
+        let r_dir = pkg_dir.join("R");
+        std::fs::create_dir_all(&r_dir).unwrap();
+        std::fs::write(r_dir.join("mutate.R"), "mutate <- function(x) x\n").unwrap();
+
+        let mut lib = PackageLibrary::new_empty();
+        lib.set_lib_paths(vec![tmp.path().to_path_buf()]);
+
+        let content = lib.read_source_file("dplyr", &PathBuf::from("R/mutate.R"));
+        assert_eq!(content, Some("mutate <- function(x) x\n".to_string()));
+    }
+
+    #[test]
+    fn test_read_source_file_missing_file_returns_none() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        std::fs::create_dir(tmp.path().join("dplyr")).unwrap();
+
+        let mut lib = PackageLibrary::new_empty();
+        lib.set_lib_paths(vec![tmp.path().to_path_buf()]);
+
+        assert!(lib
+            .read_source_file("dplyr", &PathBuf::from("R/missing.R"))
+            .is_none());
+    }
+
     #[tokio::test]
     async fn test_load_package_from_filesystem() {
         // Test the filesystem fallback loading
@@ -2915,4 +3257,101 @@ mod tests {
         let result = lib.get_exports_for_completions(&[]);
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn test_collect_referenced_packages_attach_calls() {
+        let text = "library(dplyr)\nrequire(\"ggplot2\")\nrequireNamespace('rlang')\n";
+        let doc = crate::state::Document::new(text, None);
+
+        let packages = collect_referenced_packages(&doc.tree, text);
+
+        assert_eq!(packages, vec!["dplyr", "ggplot2", "rlang"]);
+    }
+
+    #[test]
+    fn test_collect_referenced_packages_namespace_operator() {
+        let text = "x <- dplyr::mutate(df, y = 1)\nstats:::filter(x)\n";
+        let doc = crate::state::Document::new(text, None);
+
+        let packages = collect_referenced_packages(&doc.tree, text);
+
+        assert_eq!(packages, vec!["dplyr", "stats"]);
+    }
+
+    #[test]
+    fn test_collect_referenced_packages_deduplicates_in_first_seen_order() {
+        let text = "library(dplyr)\nx <- dplyr::mutate(df, y = 1)\nlibrary(ggplot2)\n";
+        let doc = crate::state::Document::new(text, None);
+
+        let packages = collect_referenced_packages(&doc.tree, text);
+
+        assert_eq!(packages, vec!["dplyr", "ggplot2"]);
+    }
+
+    #[test]
+    fn test_collect_referenced_packages_rejects_invalid_names() {
+        // `"not a pkg"` isn't a valid R package name, so it must be dropped
+        // rather than handed to the subprocess layer.
+        let text = "library(\"not a pkg\")\nlibrary(dplyr)\n";
+        let doc = crate::state::Document::new(text, None);
+
+        let packages = collect_referenced_packages(&doc.tree, text);
+
+        assert_eq!(packages, vec!["dplyr"]);
+    }
+
+    #[test]
+    fn test_collect_referenced_packages_none_tree_is_empty() {
+        assert!(collect_referenced_packages(&None, "").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_document_packages_follows_depends_transitively() {
+        let lib = PackageLibrary::new_empty();
+
+        let mut base_exports = HashSet::new();
+        base_exports.insert("tibble".to_string());
+        lib.insert_package(PackageInfo::with_details(
+            "tibble".to_string(),
+            base_exports,
+            Vec::new(),
+            Vec::new(),
+        ))
+        .await;
+
+        let mut dplyr_exports = HashSet::new();
+        dplyr_exports.insert("mutate".to_string());
+        lib.insert_package(PackageInfo::with_details(
+            "dplyr".to_string(),
+            dplyr_exports,
+            vec!["tibble".to_string()],
+            Vec::new(),
+        ))
+        .await;
+
+        let text = "library(dplyr)\n";
+        let doc = crate::state::Document::new(text, None);
+
+        let resolved = lib.resolve_document_packages(&doc.tree, text).await;
+
+        assert!(resolved.contains_key("dplyr"));
+        assert!(
+            resolved.contains_key("tibble"),
+            "tibble should be pulled in via dplyr's Depends"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_document_packages_handles_uninstalled_package() {
+        let lib = PackageLibrary::new_empty();
+        let text = "library(notinstalledpkg)\n";
+        let doc = crate::state::Document::new(text, None);
+
+        let resolved = lib.resolve_document_packages(&doc.tree, text).await;
+
+        // No subprocess and nothing on disk: get_package still returns a
+        // (cached, empty) PackageInfo rather than failing the resolve.
+        assert!(resolved.contains_key("notinstalledpkg"));
+        assert!(resolved["notinstalledpkg"].exports.is_empty());
+    }
 }
@@ -9,17 +9,303 @@
 #![allow(dead_code)]
 
 use anyhow::{anyhow, Result};
+use serde::Serialize;
 use std::path::PathBuf;
-use tokio::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::RwLock;
 
 use crate::parameter_resolver::ParameterInfo;
+use crate::string_utils::levenshtein_distance;
+
+/// Marker R is told to `cat()` once a submitted expression's output is fully
+/// flushed, so the reader side of a persistent [`RSession`] knows where one
+/// command's output ends and the next begins.
+const SESSION_DONE_MARKER: &str = "__RLSP_DONE__";
 
 /// R subprocess interface for package queries
 pub struct RSubprocess {
-    /// Path to R executable
+    /// Path to R executable. Unused (empty) when `static_profile` is set.
     r_path: PathBuf,
     /// Working directory for R subprocess
     working_dir: Option<PathBuf>,
+    /// Long-lived `R --slave` process reused across queries, lazily spawned
+    /// on first use. `None` until the first call, and reset to `None` if the
+    /// session dies so the next call respawns it.
+    session: AsyncMutex<Option<RSession>>,
+    /// Generation handed to the next spawned [`RSession`], so each instance
+    /// can be identified unambiguously even across respawns. See
+    /// [`Self::in_flight_generation`].
+    next_session_generation: AtomicU64,
+    /// Generation of the session with a call currently in flight that
+    /// hasn't yet confirmed completion, or `0` if none. Set just before
+    /// writing to the session's stdin and cleared just after reading its
+    /// done markers, both while still holding `session`'s lock - see
+    /// [`Self::execute_r_code_via_session`] and
+    /// [`Self::reset_session_after_timeout`]. This lets a caller that wins
+    /// the lock race against a cancelled, timed-out call tell that the
+    /// session it just acquired still has that call's output pending, and
+    /// respawn instead of reading corrupted output from it.
+    in_flight_generation: AtomicU64,
+    /// When set, every query is answered from this fixed profile instead of
+    /// spawning R - see [`StaticRProfile`].
+    static_profile: Option<StaticRProfile>,
+    /// Memoizes `get_lib_paths`/`get_base_packages`/`get_package_exports`/
+    /// `get_package_depends` results so repeated queries don't each spawn a
+    /// fresh R process. See [`QueryCache`].
+    query_cache: QueryCache,
+}
+
+/// Per-query-kind memoization for [`RSubprocess`]'s four metadata queries.
+///
+/// `lib_paths`/`base_packages` rarely change once discovered, so they're
+/// cached for the lifetime of the `RSubprocess` (cleared only by
+/// [`RSubprocess::clear_cache`]). `exports`/`depends` are keyed by package
+/// name and checked against that package's installed-directory mtime before
+/// being trusted - the same freshness check Cargo runs before reusing a
+/// build artifact instead of re-invoking rustc.
+#[derive(Default)]
+struct QueryCache {
+    lib_paths: RwLock<Option<Vec<PathBuf>>>,
+    base_packages: RwLock<Option<Vec<String>>>,
+    exports: RwLock<std::collections::HashMap<String, Vec<String>>>,
+    depends: RwLock<std::collections::HashMap<String, Vec<String>>>,
+    mtimes: RwLock<std::collections::HashMap<String, Option<SystemTime>>>,
+}
+
+impl QueryCache {
+    /// Returns the cached exports for `package` if present and its recorded
+    /// mtime still matches `current_mtime`, discarding the entry otherwise.
+    async fn cached_exports(&self, package: &str, current_mtime: Option<SystemTime>) -> Option<Vec<String>> {
+        if self.mtimes.read().await.get(package).copied() != Some(current_mtime) {
+            return None;
+        }
+        self.exports.read().await.get(package).cloned()
+    }
+
+    /// Returns the cached depends for `package` if present and its recorded
+    /// mtime still matches `current_mtime`, discarding the entry otherwise.
+    async fn cached_depends(&self, package: &str, current_mtime: Option<SystemTime>) -> Option<Vec<String>> {
+        if self.mtimes.read().await.get(package).copied() != Some(current_mtime) {
+            return None;
+        }
+        self.depends.read().await.get(package).cloned()
+    }
+
+    async fn store_exports(&self, package: &str, exports: Vec<String>, mtime: Option<SystemTime>) {
+        self.exports.write().await.insert(package.to_string(), exports);
+        self.mtimes.write().await.insert(package.to_string(), mtime);
+    }
+
+    async fn store_depends(&self, package: &str, depends: Vec<String>, mtime: Option<SystemTime>) {
+        self.depends.write().await.insert(package.to_string(), depends);
+        self.mtimes.write().await.insert(package.to_string(), mtime);
+    }
+
+    async fn clear(&self) {
+        *self.lib_paths.write().await = None;
+        *self.base_packages.write().await = None;
+        self.exports.write().await.clear();
+        self.depends.write().await.clear();
+        self.mtimes.write().await.clear();
+    }
+}
+
+/// A fixed R version, library path list, and base-package list supplied by
+/// configuration instead of discovered by probing a live R process.
+///
+/// Mirrors libR-sys's `LIBRSYS_R_VERSION` escape hatch: in containers,
+/// remote dev, and cross-compilation setups R may be absent or too slow to
+/// probe, but the user already knows their R version and library layout.
+/// `RSubprocess::from_static_profile` builds an instance that answers
+/// `get_lib_paths`/`get_base_packages` directly from this struct, and
+/// `get_package_exports`/`get_package_depends` by reading each installed
+/// package's `NAMESPACE`/`DESCRIPTION` files under `lib_paths` rather than
+/// invoking `getNamespaceExports`/`packageDescription`.
+#[derive(Debug, Clone)]
+pub struct StaticRProfile {
+    /// The R version this profile describes, e.g. `"4.3.1"`.
+    pub r_version: String,
+    /// Library paths to search for installed packages, in search order.
+    pub lib_paths: Vec<PathBuf>,
+    /// Packages considered always-available (normally R's startup packages).
+    pub base_packages: Vec<String>,
+}
+
+/// A [`RSubprocess::query_json`] request: which metadata to fetch, and for
+/// package-scoped queries, which packages.
+#[derive(Debug, Clone)]
+pub enum QueryRequest {
+    /// Report R's library search paths.
+    LibPaths,
+    /// Report each named package's exports.
+    PackageExports(Vec<String>),
+    /// Report each named package's `Depends` field.
+    PackageDepends(Vec<String>),
+}
+
+/// One `query_json` result, internally tagged with a `kind` field (e.g.
+/// `"kind": "lib-paths"`) so a consumer can dispatch on it without decoding
+/// the rest of the object first - the same shape as a `cargo
+/// --message-format=json` message.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum QueryReport {
+    LibPaths(LibPathsReport),
+    PackageExports(PackageExportsReport),
+    PackageDepends(PackageDependsReport),
+}
+
+/// Wire format for a [`QueryRequest::LibPaths`] report.
+#[derive(Debug, Serialize)]
+pub struct LibPathsReport {
+    pub r_path: String,
+    pub r_version: String,
+    pub lib_paths: Vec<String>,
+}
+
+/// Wire format for one package's entry in a [`QueryRequest::PackageExports`] report.
+#[derive(Debug, Serialize)]
+pub struct PackageExportsReport {
+    pub r_path: String,
+    pub r_version: String,
+    pub package: String,
+    pub exports: Vec<String>,
+}
+
+/// Wire format for one package's entry in a [`QueryRequest::PackageDepends`] report.
+#[derive(Debug, Serialize)]
+pub struct PackageDependsReport {
+    pub r_path: String,
+    pub r_version: String,
+    pub package: String,
+    pub depends: Vec<String>,
+}
+
+/// A long-lived R process with piped stdio, used to avoid paying R's startup
+/// cost on every single query (package exports, depends, etc.).
+///
+/// Each submitted expression is followed by a sentinel `cat()` on stdout and
+/// on stderr; the reader consumes lines up to its marker to delimit that
+/// command's output. This assumes a single command's output is modest in
+/// size (true for the package-metadata queries this module issues) - a
+/// command that floods stdout or stderr before the other pipe is drained
+/// could deadlock, which is why this is paired with a one-shot fallback
+/// rather than relied on unconditionally.
+struct RSession {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+    /// Identifies this instance across respawns - see
+    /// [`RSubprocess::in_flight_generation`].
+    generation: u64,
+}
+
+impl RSession {
+    /// Spawns `R --slave --no-save --no-restore` with piped stdio.
+    async fn spawn(
+        r_path: &std::path::Path,
+        working_dir: Option<&std::path::Path>,
+        generation: u64,
+    ) -> Result<Self> {
+        let mut cmd = Command::new(r_path);
+        cmd.args(["--slave", "--no-save", "--no-restore"]);
+        if let Some(wd) = working_dir {
+            cmd.current_dir(wd);
+        }
+        cmd.stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| anyhow!("Failed to spawn persistent R session: {e}"))?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to capture R session stdin"))?;
+        let stdout = BufReader::new(
+            child
+                .stdout
+                .take()
+                .ok_or_else(|| anyhow!("Failed to capture R session stdout"))?,
+        );
+        let stderr = BufReader::new(
+            child
+                .stderr
+                .take()
+                .ok_or_else(|| anyhow!("Failed to capture R session stderr"))?,
+        );
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            generation,
+        })
+    }
+
+    /// Submits `r_code`, then reads stdout and stderr up to their sentinel
+    /// markers. Returns `Ok(stdout)` if stderr was empty, `Err` with the
+    /// stderr content otherwise.
+    async fn execute(&mut self, r_code: &str) -> Result<String> {
+        let submitted = format!(
+            "{}\ncat(\"\\n{marker}\\n\")\ncat(\"{marker}\\n\", file=stderr())\n",
+            r_code,
+            marker = SESSION_DONE_MARKER
+        );
+        self.stdin
+            .write_all(submitted.as_bytes())
+            .await
+            .map_err(|e| anyhow!("Failed to write to R session stdin: {e}"))?;
+        self.stdin
+            .flush()
+            .await
+            .map_err(|e| anyhow!("Failed to flush R session stdin: {e}"))?;
+
+        let stdout = Self::read_until_marker(&mut self.stdout).await?;
+        let stderr = Self::read_until_marker(&mut self.stderr).await?;
+
+        if stderr.trim().is_empty() {
+            Ok(stdout)
+        } else {
+            Err(anyhow!("R session reported an error: {}", stderr.trim()))
+        }
+    }
+
+    /// Reads lines from `reader` until one equal to [`SESSION_DONE_MARKER`]
+    /// is seen, returning everything before it joined back with newlines.
+    async fn read_until_marker<R: tokio::io::AsyncBufRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let bytes_read = reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| anyhow!("Failed to read from R session: {e}"))?;
+            if bytes_read == 0 {
+                return Err(anyhow!("R session closed its pipe before emitting {}", SESSION_DONE_MARKER));
+            }
+            if line.trim_end_matches(['\n', '\r']) == SESSION_DONE_MARKER {
+                return Ok(lines.join("\n"));
+            }
+            lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+    }
+
+    /// Whether the child process has exited (a broken session).
+    fn is_dead(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(Some(_)) | Err(_))
+    }
 }
 
 impl RSubprocess {
@@ -39,27 +325,91 @@ impl RSubprocess {
     /// let _ = RSubprocess::new(None);
     /// ```
     pub fn new(r_path: Option<PathBuf>) -> Option<Self> {
-        let path = match r_path {
-            Some(p) => {
-                if Self::is_valid_r_executable(&p) {
-                    Some(p)
-                } else {
-                    log::trace!("Provided R path is not valid: {:?}", p);
-                    None
-                }
-            }
-            None => Self::discover_r_path(),
-        };
-
-        path.map(|r_path| {
+        Self::resolve_r_path(r_path).map(|r_path| {
             log::trace!("Using R executable at: {:?}", r_path);
             Self {
                 r_path,
                 working_dir: None,
+                session: AsyncMutex::new(None),
+                next_session_generation: AtomicU64::new(1),
+                in_flight_generation: AtomicU64::new(0),
+                static_profile: None,
+                query_cache: QueryCache::default(),
             }
         })
     }
 
+    /// Resolves which R executable to use, checked in order:
+    ///
+    /// 1. The `RAVEN_R` or `R_BINARY` environment variable, if set - mirrors
+    ///    Cargo's `RUSTC` env var taking priority over its `build.rustc`
+    ///    config key. If set but invalid, this errors clearly rather than
+    ///    silently falling through to the next layer, so a typo'd override
+    ///    doesn't get masked by whatever happens to be on PATH.
+    /// 2. `project_config` (the project's configured R path, e.g. the LSP
+    ///    client's `packages.rPath` setting).
+    /// 3. The existing discovery chain: `R_HOME`, PATH, then common install
+    ///    locations for the current platform.
+    fn resolve_r_path(project_config: Option<PathBuf>) -> Option<PathBuf> {
+        for var in ["RAVEN_R", "R_BINARY"] {
+            let Ok(value) = std::env::var(var) else {
+                continue;
+            };
+            let candidate = PathBuf::from(&value);
+            if Self::is_valid_r_executable(&candidate) {
+                log::trace!("Using R executable from ${}: {:?}", var, candidate);
+                return Some(candidate);
+            }
+            log::error!(
+                "${} is set to '{}', but that isn't a working R executable; \
+                 not falling back to the project config or discovery chain \
+                 for this override",
+                var,
+                value
+            );
+            return None;
+        }
+
+        if let Some(p) = project_config {
+            return if Self::is_valid_r_executable(&p) {
+                Some(p)
+            } else {
+                log::trace!("Provided R path is not valid: {:?}", p);
+                None
+            };
+        }
+
+        let discovered = Self::discover_r_path();
+        if discovered.is_none() {
+            log::error!(
+                "No R executable found. Checked, in order: $RAVEN_R/$R_BINARY, \
+                 the configured R path, $R_HOME, PATH, and common install \
+                 locations for this platform."
+            );
+        }
+        discovered
+    }
+
+    /// Creates an RSubprocess backed by a fixed [`StaticRProfile`] instead of
+    /// a discovered/spawned R executable.
+    ///
+    /// `get_lib_paths` and `get_base_packages` answer directly from
+    /// `profile`; `get_package_exports` and `get_package_depends` read
+    /// `NAMESPACE`/`DESCRIPTION` files from `profile.lib_paths` instead of
+    /// invoking R. No R process is ever spawned by an `RSubprocess` built
+    /// this way.
+    pub fn from_static_profile(profile: StaticRProfile) -> Self {
+        Self {
+            r_path: PathBuf::new(),
+            working_dir: None,
+            session: AsyncMutex::new(None),
+            next_session_generation: AtomicU64::new(1),
+            in_flight_generation: AtomicU64::new(0),
+            static_profile: Some(profile),
+            query_cache: QueryCache::default(),
+        }
+    }
+
     /// Set the working directory for the R subprocess
     pub fn with_working_dir(mut self, path: PathBuf) -> Self {
         self.working_dir = Some(path);
@@ -73,14 +423,22 @@ impl RSubprocess {
 
     /// Locate an R executable on the system by searching common locations.
     ///
-    /// Attempts to find an R binary first via the system PATH and then by checking
-    /// a set of typical installation locations for the current platform.
+    /// Attempts to find an R binary first via `R_HOME`, then via the system
+    /// PATH, and finally by checking a set of typical installation locations
+    /// for the current platform.
     ///
     /// # Returns
     ///
     /// `Some(PathBuf)` containing the path to an R executable if found, `None` if no candidate was discovered.
     fn discover_r_path() -> Option<PathBuf> {
-        // First, try to find R in PATH using `which` on Unix or `where` on Windows
+        // R_HOME (or a `R RHOME` query against whatever's on PATH) wins over
+        // the hardcoded location list - it's how conda envs, rig-managed
+        // installs, and CI containers communicate R's location.
+        if let Some(path) = Self::find_r_via_home_env() {
+            return Some(path);
+        }
+
+        // Try to find R in PATH using `which` on Unix or `where` on Windows
         if let Some(path) = Self::find_r_in_path() {
             return Some(path);
         }
@@ -89,6 +447,51 @@ impl RSubprocess {
         Self::find_r_in_common_locations()
     }
 
+    /// Resolves an R executable from `R_HOME`, as libR-sys's build script
+    /// does: if the env var is set, `${R_HOME}/bin/R` (`bin/R.exe` on
+    /// Windows) is tried directly. If `R_HOME` isn't set, falls back to
+    /// asking whatever R binary is on PATH for its home via `R RHOME` and
+    /// re-resolving the executable under it.
+    fn find_r_via_home_env() -> Option<PathBuf> {
+        if let Ok(r_home) = std::env::var("R_HOME") {
+            if let Some(path) = Self::r_binary_under_home(&r_home) {
+                return Some(path);
+            }
+        }
+
+        let candidate = Self::find_r_in_path()?;
+        let home = Self::query_r_home(&candidate)?;
+        Self::r_binary_under_home(&home.to_string_lossy())
+    }
+
+    /// Joins `home` with the platform's `bin/R(.exe)` and validates it.
+    fn r_binary_under_home(home: &str) -> Option<PathBuf> {
+        let bin_name = if cfg!(windows) { "R.exe" } else { "R" };
+        let candidate = PathBuf::from(home).join("bin").join(bin_name);
+        if Self::is_valid_r_executable(&candidate) {
+            Some(candidate)
+        } else {
+            None
+        }
+    }
+
+    /// Invokes `<r_path> RHOME` and returns the home directory it reports.
+    fn query_r_home(r_path: &PathBuf) -> Option<PathBuf> {
+        let output = std::process::Command::new(r_path)
+            .arg("RHOME")
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let home = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if home.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(home))
+        }
+    }
+
     /// Locate an R executable by searching the system PATH.
     ///
     /// Returns `Some(PathBuf)` with the first valid R executable found in PATH, or `None` if no valid executable is discovered.
@@ -139,6 +542,13 @@ impl RSubprocess {
     fn get_common_r_paths() -> Vec<PathBuf> {
         let mut paths = Vec::new();
 
+        // An explicitly configured R_HOME always wins over a location we'd
+        // otherwise have to guess, so it goes at the very front of the list.
+        if let Ok(r_home) = std::env::var("R_HOME") {
+            let bin_name = if cfg!(windows) { "R.exe" } else { "R" };
+            paths.push(PathBuf::from(r_home).join("bin").join(bin_name));
+        }
+
         #[cfg(target_os = "macos")]
         {
             // Homebrew locations
@@ -234,6 +644,126 @@ impl RSubprocess {
         &self,
         r_code: &str,
         timeout: std::time::Duration,
+    ) -> Result<String> {
+        match tokio::time::timeout(timeout, self.execute_r_code_via_session(r_code)).await {
+            Ok(Ok(output)) => return Ok(output),
+            Ok(Err(e)) => {
+                log::trace!(
+                    "Persistent R session unavailable, falling back to one-shot subprocess: {}",
+                    e
+                );
+            }
+            Err(_) => {
+                // `execute_r_code_via_session` was cancelled mid-`.await`,
+                // possibly after it already wrote `r_code` to the session's
+                // stdin and asked R to emit its sentinel markers. The
+                // `MutexGuard` it held is dropped by the cancellation, but
+                // the session itself is left in place (still `Some`) with
+                // that write pending - the next call would read whatever
+                // this one eventually outputs (or a stale marker) as its
+                // own result. Kill and clear the session so the next call
+                // spawns a fresh one instead of silently reading corrupted
+                // output from this one.
+                self.reset_session_after_timeout().await;
+                return Err(anyhow!("R subprocess timed out after {timeout:?}"));
+            }
+        }
+
+        self.execute_r_code_oneshot(r_code, timeout).await
+    }
+
+    /// Kills and drops the persistent session, if any, after
+    /// [`Self::execute_r_code_with_timeout`] cancels an in-flight call to
+    /// [`Self::execute_r_code_via_session`]. See that method's timeout
+    /// branch for why leaving the session in place would desync later reads.
+    ///
+    /// Only kills the session that was actually in flight when the timeout
+    /// fired, identified by generation rather than by "whatever `session`
+    /// currently holds": a concurrent caller may have already raced in,
+    /// noticed the same pending generation via
+    /// [`Self::execute_r_code_via_session`]'s own check, and respawned a
+    /// fresh session by the time this runs - that replacement must be left
+    /// alone, not killed out from under it.
+    async fn reset_session_after_timeout(&self) {
+        let pending_generation = self.in_flight_generation.swap(0, Ordering::SeqCst);
+        if pending_generation == 0 {
+            return;
+        }
+        let mut guard = self.session.lock().await;
+        if guard.as_ref().map(|session| session.generation) == Some(pending_generation) {
+            if let Some(mut session) = guard.take() {
+                let _ = session.child.start_kill();
+            }
+        }
+    }
+
+    /// Runs `r_code` through the long-lived [`RSession`], spawning one if
+    /// none exists yet and respawning once if the existing session has died
+    /// (broken pipe, non-zero exit). Returns `Err` if the session can't be
+    /// established or used even after a respawn, so the caller can fall back
+    /// to [`Self::execute_r_code_oneshot`].
+    ///
+    /// `in_flight_generation` is set to the session's generation just before
+    /// writing to it and cleared just after its response is fully read, both
+    /// while holding `session`'s lock - so if this call is itself cancelled
+    /// mid-write/read (see [`Self::execute_r_code_with_timeout`]'s timeout
+    /// branch), the next caller to acquire the lock sees the generation
+    /// still pending and knows the session has a response outstanding that
+    /// nothing has consumed yet, rather than racing
+    /// [`Self::reset_session_after_timeout`] to decide whether it's safe to
+    /// reuse.
+    async fn execute_r_code_via_session(&self, r_code: &str) -> Result<String> {
+        let mut guard = self.session.lock().await;
+
+        if let Some(session) = guard.as_mut() {
+            let still_pending =
+                self.in_flight_generation.load(Ordering::SeqCst) == session.generation;
+            if session.is_dead() || still_pending {
+                *guard = None;
+            }
+        }
+
+        if guard.is_none() {
+            let generation = self.next_session_generation.fetch_add(1, Ordering::SeqCst);
+            *guard = Some(
+                RSession::spawn(&self.r_path, self.working_dir.as_deref(), generation).await?,
+            );
+        }
+
+        let generation = guard.as_ref().unwrap().generation;
+        self.in_flight_generation.store(generation, Ordering::SeqCst);
+        let result = guard.as_mut().unwrap().execute(r_code).await;
+        self.in_flight_generation.store(0, Ordering::SeqCst);
+
+        // Only a broken session (IO failure, closed pipe, dead child)
+        // warrants a respawn; an `Err` caused by `r_code` itself raising a
+        // real R error is a legitimate result the session is still healthy
+        // to serve, and should be returned as-is rather than retried.
+        if result.is_err() && guard.as_mut().unwrap().is_dead() {
+            log::trace!("Persistent R session died, respawning once");
+            *guard = None;
+            let generation = self.next_session_generation.fetch_add(1, Ordering::SeqCst);
+            if let Ok(mut session) =
+                RSession::spawn(&self.r_path, self.working_dir.as_deref(), generation).await
+            {
+                self.in_flight_generation.store(generation, Ordering::SeqCst);
+                let retried = session.execute(r_code).await;
+                self.in_flight_generation.store(0, Ordering::SeqCst);
+                *guard = Some(session);
+                return retried;
+            }
+        }
+        result
+    }
+
+    /// Spawns a fresh `R --slave` process for a single `r_code` call and
+    /// returns its stdout. This is the original per-call execution path,
+    /// kept as the fallback for when the persistent session in
+    /// [`Self::execute_r_code_via_session`] can't be used.
+    async fn execute_r_code_oneshot(
+        &self,
+        r_code: &str,
+        timeout: std::time::Duration,
     ) -> Result<String> {
         let start = std::time::Instant::now();
         crate::perf::increment_r_subprocess_calls();
@@ -303,19 +833,27 @@ impl RSubprocess {
     /// # }
     /// ```
     pub async fn get_lib_paths(&self) -> Result<Vec<PathBuf>> {
+        if let Some(profile) = &self.static_profile {
+            return Ok(profile.lib_paths.clone());
+        }
+
+        if let Some(cached) = self.query_cache.lib_paths.read().await.clone() {
+            return Ok(cached);
+        }
+
         // Use cat() with sep="\n" to output each path on its own line without R's vector formatting
         // Check for renv/activate.R and source it if it exists (handles renv projects)
         // Security: Validate that renv/activate.R is in the working directory to prevent path traversal
         let r_code = r#"renv_path <- normalizePath("renv/activate.R", mustWork=FALSE); if (file.exists(renv_path) && dirname(renv_path) == file.path(getwd(), "renv")) try(source(renv_path), silent=TRUE); cat(.libPaths(), sep="\n")"#;
 
-        match self.execute_r_code(r_code).await {
+        let result = match self.execute_r_code(r_code).await {
             Ok(output) => {
                 let paths = parse_lib_paths_output(&output);
                 if paths.is_empty() {
                     log::trace!("R returned empty .libPaths(), using fallback paths");
-                    Ok(get_fallback_lib_paths())
+                    get_fallback_lib_paths()
                 } else {
-                    Ok(paths)
+                    paths
                 }
             }
             Err(e) => {
@@ -323,9 +861,12 @@ impl RSubprocess {
                     "Failed to get .libPaths() from R: {}, using fallback paths",
                     e
                 );
-                Ok(get_fallback_lib_paths())
+                get_fallback_lib_paths()
             }
-        }
+        };
+
+        *self.query_cache.lib_paths.write().await = Some(result.clone());
+        Ok(result)
     }
 
     /// Retrieve the base (startup) packages provided by the R installation.
@@ -346,18 +887,26 @@ impl RSubprocess {
     /// # }
     /// ```
     pub async fn get_base_packages(&self) -> Result<Vec<String>> {
+        if let Some(profile) = &self.static_profile {
+            return Ok(profile.base_packages.clone());
+        }
+
+        if let Some(cached) = self.query_cache.base_packages.read().await.clone() {
+            return Ok(cached);
+        }
+
         // Use cat() with sep="\n" to output each package name on its own line
         // without R's vector formatting (e.g., [1] "base" "methods" ...)
         let r_code = r#"cat(.packages(), sep="\n")"#;
 
-        match self.execute_r_code(r_code).await {
+        let result = match self.execute_r_code(r_code).await {
             Ok(output) => {
                 let packages = parse_packages_output(&output);
                 if packages.is_empty() {
                     log::trace!("R returned empty .packages(), using fallback base packages");
-                    Ok(get_fallback_base_packages())
+                    get_fallback_base_packages()
                 } else {
-                    Ok(packages)
+                    packages
                 }
             }
             Err(e) => {
@@ -365,9 +914,12 @@ impl RSubprocess {
                     "Failed to get .packages() from R: {}, using fallback base packages",
                     e
                 );
-                Ok(get_fallback_base_packages())
+                get_fallback_base_packages()
             }
-        }
+        };
+
+        *self.query_cache.base_packages.write().await = Some(result.clone());
+        Ok(result)
     }
 
     /// Retrieve the exported symbol names of an installed R package.
@@ -407,6 +959,19 @@ impl RSubprocess {
             ));
         }
 
+        let current_mtime = self.package_dir_mtime(package).await;
+        if let Some(cached) = self.query_cache.cached_exports(package, current_mtime).await {
+            return Ok(cached);
+        }
+
+        if let Some(profile) = &self.static_profile {
+            let exports = get_package_exports_from_static_profile(profile, package)?;
+            self.query_cache
+                .store_exports(package, exports.clone(), current_mtime)
+                .await;
+            return Ok(exports);
+        }
+
         // Use cat() with sep="\n" to output each export name on its own line
         // without R's vector formatting (e.g., [1] "func1" "func2" ...)
         // We use tryCatch to handle the case where the package is not installed
@@ -420,10 +985,12 @@ impl RSubprocess {
         // Check if R returned an error
         if output.starts_with("__RLSP_ERROR__:") {
             let error_msg = output.trim_start_matches("__RLSP_ERROR__:").trim();
+            let suggestion = self.did_you_mean_suggestion(package).await;
             return Err(anyhow!(
-                "Failed to get exports for package '{}': {}",
+                "Failed to get exports for package '{}': {}{}",
                 package,
-                error_msg
+                error_msg,
+                suggestion
             ));
         }
 
@@ -463,6 +1030,9 @@ impl RSubprocess {
             }
         );
 
+        self.query_cache
+            .store_exports(package, exports.clone(), current_mtime)
+            .await;
         Ok(exports)
     }
 
@@ -499,6 +1069,19 @@ impl RSubprocess {
             ));
         }
 
+        let current_mtime = self.package_dir_mtime(package).await;
+        if let Some(cached) = self.query_cache.cached_depends(package, current_mtime).await {
+            return Ok(cached);
+        }
+
+        if let Some(profile) = &self.static_profile {
+            let depends = get_package_depends_from_static_profile(profile, package)?;
+            self.query_cache
+                .store_depends(package, depends.clone(), current_mtime)
+                .await;
+            return Ok(depends);
+        }
+
         // Use packageDescription to get the Depends field
         // First check if the package exists using find.package, then get the Depends field
         // We use tryCatch to handle the case where the package is not installed
@@ -521,10 +1104,12 @@ impl RSubprocess {
         // Check if R returned an error
         if output.starts_with("__RLSP_ERROR__:") {
             let error_msg = output.trim_start_matches("__RLSP_ERROR__:").trim();
+            let suggestion = self.did_you_mean_suggestion(package).await;
             return Err(anyhow!(
-                "Failed to get depends for package '{}': {}",
+                "Failed to get depends for package '{}': {}{}",
                 package,
-                error_msg
+                error_msg,
+                suggestion
             ));
         }
 
@@ -538,9 +1123,356 @@ impl RSubprocess {
             depends
         );
 
+        self.query_cache
+            .store_depends(package, depends.clone(), current_mtime)
+            .await;
         Ok(depends)
     }
 
+    /// The mtime of `package`'s installed directory, used to decide whether
+    /// a cached exports/depends entry for it is still fresh. Looks under
+    /// whatever `.libPaths()` are already cached (falling back to a live
+    /// `get_lib_paths` call only if none are cached yet) in live mode, or
+    /// `profile.lib_paths` directly in [`StaticRProfile`] mode. `None` if the
+    /// package isn't found anywhere searched, which never matches a cached
+    /// `Some(mtime)` - so a package that disappears invalidates its cache too.
+    async fn package_dir_mtime(&self, package: &str) -> Option<SystemTime> {
+        let lib_paths = if let Some(profile) = &self.static_profile {
+            profile.lib_paths.clone()
+        } else if let Some(cached) = self.query_cache.lib_paths.read().await.clone() {
+            cached
+        } else {
+            self.get_lib_paths().await.unwrap_or_default()
+        };
+
+        let dir = find_package_dir(&lib_paths, package)?;
+        std::fs::metadata(&dir).ok()?.modified().ok()
+    }
+
+    /// Clears every cached `get_lib_paths`/`get_base_packages`/
+    /// `get_package_exports`/`get_package_depends` result, for callers that
+    /// install or update packages mid-session and need the next query to
+    /// hit R (or re-read DESCRIPTION/NAMESPACE) again instead of returning
+    /// stale data.
+    pub async fn clear_cache(&self) {
+        self.query_cache.clear().await;
+    }
+
+    /// One package's edges in the dependency graph built by
+    /// [`RSubprocess::resolve_load_order`]: `hard` collects `Depends`,
+    /// `Imports`, and `LinkingTo` - a package can't load without these -
+    /// while `optional` collects `Suggests`, which is only followed when the
+    /// caller opts in, the same way Cargo only resolves a dependency behind
+    /// a feature flag when that feature is requested.
+    async fn package_edges(&self, package: &str) -> Result<PackageEdges> {
+        if let Some(profile) = &self.static_profile {
+            return package_edges_from_static_profile(profile, package);
+        }
+
+        let r_code = format!(
+            r#"tryCatch({{
+                find.package("{}")
+                desc <- packageDescription("{}", fields=c("Depends", "Imports", "LinkingTo", "Suggests"))
+                cat("__RLSP_DEPENDS__:", if (is.na(desc$Depends)) "" else desc$Depends, "\n", sep="")
+                cat("__RLSP_IMPORTS__:", if (is.na(desc$Imports)) "" else desc$Imports, "\n", sep="")
+                cat("__RLSP_LINKINGTO__:", if (is.na(desc$LinkingTo)) "" else desc$LinkingTo, "\n", sep="")
+                cat("__RLSP_SUGGESTS__:", if (is.na(desc$Suggests)) "" else desc$Suggests, "\n", sep="")
+            }}, error=function(e) cat("__RLSP_ERROR__:", conditionMessage(e), sep=""))"#,
+            package, package
+        );
+
+        let output = self.execute_r_code(&r_code).await?;
+
+        if output.starts_with("__RLSP_ERROR__:") {
+            let error_msg = output.trim_start_matches("__RLSP_ERROR__:").trim();
+            let suggestion = self.did_you_mean_suggestion(package).await;
+            return Err(anyhow!(
+                "Failed to get dependency fields for package '{}': {}{}",
+                package,
+                error_msg,
+                suggestion
+            ));
+        }
+
+        Ok(parse_package_edges_output(&output))
+    }
+
+    /// Resolves `roots` and their transitive hard dependencies (`Depends` +
+    /// `Imports` + `LinkingTo`) into a topologically sorted load order -
+    /// every package appears after everything it depends on. Passing
+    /// `include_suggests = true` additionally follows `Suggests` edges, the
+    /// way Cargo only resolves an optional dependency when the feature that
+    /// needs it is requested; with it `false`, `Suggests` never contributes
+    /// a node to the graph.
+    ///
+    /// Invalid package names (per [`is_valid_package_name`]) are dropped
+    /// before being inserted as graph nodes rather than failing the whole
+    /// resolve - a malformed entry in one package's DESCRIPTION shouldn't
+    /// break resolution for everything else.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a dependency cycle is detected (naming the
+    /// packages involved) or if a root or one of its dependencies can't be
+    /// queried.
+    pub async fn resolve_load_order(
+        &self,
+        roots: &[&str],
+        include_suggests: bool,
+    ) -> Result<Vec<String>> {
+        let mut graph: std::collections::HashMap<String, PackageEdges> =
+            std::collections::HashMap::new();
+        let mut worklist: Vec<String> = roots
+            .iter()
+            .filter(|name| is_valid_package_name(name))
+            .map(|name| name.to_string())
+            .collect();
+
+        while let Some(name) = worklist.pop() {
+            if graph.contains_key(&name) {
+                continue;
+            }
+            let edges = self.package_edges(&name).await?;
+
+            let mut to_visit: Vec<&String> = edges.hard.iter().collect();
+            if include_suggests {
+                to_visit.extend(edges.optional.iter());
+            }
+            for dep in to_visit {
+                if is_valid_package_name(dep) && !graph.contains_key(dep) {
+                    worklist.push(dep.clone());
+                }
+            }
+
+            graph.insert(name, edges);
+        }
+
+        topological_load_order(&graph, include_suggests)
+    }
+
+    /// Lists every package installed under R's library paths (base and
+    /// user-installed alike), via `installed.packages()`. Only used to build
+    /// "did you mean ...?" suggestions below, so it's fine that this is
+    /// relatively expensive - it's only called once a package is already
+    /// known not to exist.
+    async fn get_installed_package_names(&self) -> Result<Vec<String>> {
+        let r_code = r#"cat(rownames(installed.packages()), sep="\n")"#;
+        let output = self.execute_r_code(r_code).await?;
+        Ok(parse_packages_output(&output))
+    }
+
+    /// Builds a `"; did you mean \`...\`?"` suffix for an unknown package
+    /// name from the installed-package listing, or an empty string if
+    /// nothing was close enough to suggest or the listing itself failed.
+    /// Listing failures are swallowed here rather than surfaced - a missing
+    /// suggestion should never mask the original "package not found" error.
+    async fn did_you_mean_suggestion(&self, name: &str) -> String {
+        match self.get_installed_package_names().await {
+            Ok(candidates) => did_you_mean_suffix(name, &candidates),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// Retrieve exports for a batch of packages in a single R subprocess call,
+    /// preserving per-package success/failure instead of collapsing failures
+    /// to an empty list like [`Self::get_multiple_package_exports`] does.
+    ///
+    /// Each package's result is wrapped in a per-package `tryCatch`, so one
+    /// missing or broken package doesn't fail the whole batch - its entry in
+    /// the returned map is simply an `Err`. Every successfully resolved
+    /// package also populates [`Self::get_package_exports`]'s cache, so a
+    /// caller can batch-warm many packages up front and have later
+    /// single-package lookups come back without spawning R again.
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap` with one entry per requested package name, each holding
+    /// either its exports or the error R reported for it.
+    pub async fn get_package_exports_batch(
+        &self,
+        packages: &[&str],
+    ) -> Result<std::collections::HashMap<String, Result<Vec<String>>>> {
+        if packages.is_empty() {
+            return Ok(std::collections::HashMap::new());
+        }
+
+        for pkg in packages {
+            if !is_valid_package_name(pkg) {
+                return Err(anyhow!(
+                    "Invalid package name '{}': must contain only letters, numbers, dots, and underscores",
+                    pkg
+                ));
+            }
+        }
+
+        let packages_vector = packages
+            .iter()
+            .map(|p| format!("\"{}\"", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let r_code = format!(
+            r#"
+pkgs <- c({})
+for (pkg in pkgs) {{
+    cat(paste0("__RLSP_PKG__:", pkg, "\n"))
+    tryCatch({{
+        cat(getNamespaceExports(asNamespace(pkg)), sep="\n")
+        cat("\n")
+    }}, error = function(e) cat(paste0("__RLSP_ERR__:", pkg, ":", conditionMessage(e), "\n")))
+}}
+"#,
+            packages_vector
+        );
+
+        let output = self.execute_r_code(&r_code).await?;
+        let results = parse_batch_exports_output(&output, packages);
+
+        for (pkg, result) in &results {
+            if let Ok(exports) = result {
+                let mtime = self.package_dir_mtime(pkg).await;
+                self.query_cache.store_exports(pkg, exports.clone(), mtime).await;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Retrieve `Depends` for a batch of packages in a single R subprocess
+    /// call, mirroring [`Self::get_package_exports_batch`]'s per-package
+    /// `tryCatch`/result semantics.
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap` with one entry per requested package name, each holding
+    /// either its cleaned dependency names (see [`parse_depends_field`]) or
+    /// the error R reported for it.
+    pub async fn get_package_depends_batch(
+        &self,
+        packages: &[&str],
+    ) -> Result<std::collections::HashMap<String, Result<Vec<String>>>> {
+        if packages.is_empty() {
+            return Ok(std::collections::HashMap::new());
+        }
+
+        for pkg in packages {
+            if !is_valid_package_name(pkg) {
+                return Err(anyhow!(
+                    "Invalid package name '{}': must contain only letters, numbers, dots, and underscores",
+                    pkg
+                ));
+            }
+        }
+
+        let packages_vector = packages
+            .iter()
+            .map(|p| format!("\"{}\"", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let r_code = format!(
+            r#"
+pkgs <- c({})
+for (pkg in pkgs) {{
+    cat(paste0("__RLSP_PKG__:", pkg, "\n"))
+    tryCatch({{
+        find.package(pkg)
+        desc <- packageDescription(pkg, fields="Depends")
+        if (!is.na(desc)) cat(desc)
+        cat("\n")
+    }}, error = function(e) cat(paste0("__RLSP_ERR__:", pkg, ":", conditionMessage(e), "\n")))
+}}
+"#,
+            packages_vector
+        );
+
+        let output = self.execute_r_code(&r_code).await?;
+        Ok(parse_batch_depends_output(&output, packages))
+    }
+
+    /// The R version in effect: `profile.r_version` in [`StaticRProfile`]
+    /// mode, or a live `getRversion()` query otherwise, falling back to
+    /// `"unknown"` if that query fails - a version string should never block
+    /// [`Self::query_json`] from returning its other fields.
+    async fn r_version(&self) -> String {
+        if let Some(profile) = &self.static_profile {
+            return profile.r_version.clone();
+        }
+
+        let r_code = r#"cat(as.character(getRversion()))"#;
+        self.execute_r_code(r_code)
+            .await
+            .map(|output| output.trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    }
+
+    /// Runs `request` and returns its results as newline-delimited JSON, one
+    /// `kind`-tagged [`QueryReport`] per line - analogous to Cargo's
+    /// `--message-format=json`, giving editor/LSP integrations and CI
+    /// scripts a stable structured contract over the same data
+    /// `get_lib_paths`/`get_package_exports`/`get_package_depends` already
+    /// extract, instead of re-parsing human-readable text.
+    ///
+    /// A package-scoped request (`PackageExports`/`PackageDepends`) emits one
+    /// report per requested package that resolves successfully; a package
+    /// that fails to resolve is simply omitted rather than failing the whole
+    /// call, the same per-package tolerance `get_package_exports_batch` has.
+    pub async fn query_json(&self, request: QueryRequest) -> Result<String> {
+        let r_path = self.r_path.display().to_string();
+        let r_version = self.r_version().await;
+
+        let reports: Vec<QueryReport> = match request {
+            QueryRequest::LibPaths => {
+                let lib_paths = self.get_lib_paths().await?;
+                vec![QueryReport::LibPaths(LibPathsReport {
+                    r_path,
+                    r_version,
+                    lib_paths: lib_paths
+                        .into_iter()
+                        .map(|path| path.display().to_string())
+                        .collect(),
+                })]
+            }
+            QueryRequest::PackageExports(packages) => {
+                let mut reports = Vec::new();
+                for package in packages {
+                    if let Ok(exports) = self.get_package_exports(&package).await {
+                        reports.push(QueryReport::PackageExports(PackageExportsReport {
+                            r_path: r_path.clone(),
+                            r_version: r_version.clone(),
+                            package,
+                            exports,
+                        }));
+                    }
+                }
+                reports
+            }
+            QueryRequest::PackageDepends(packages) => {
+                let mut reports = Vec::new();
+                for package in packages {
+                    if let Ok(depends) = self.get_package_depends(&package).await {
+                        reports.push(QueryReport::PackageDepends(PackageDependsReport {
+                            r_path: r_path.clone(),
+                            r_version: r_version.clone(),
+                            package,
+                            depends,
+                        }));
+                    }
+                }
+                reports
+            }
+        };
+
+        reports
+            .iter()
+            .map(|report| {
+                serde_json::to_string(report)
+                    .map_err(|e| anyhow!("Failed to serialize query report: {}", e))
+            })
+            .collect::<Result<Vec<String>>>()
+            .map(|lines| lines.join("\n"))
+    }
+
     /// Retrieve exports for multiple packages in a single R subprocess call.
     ///
     /// This is significantly faster than calling `get_package_exports` multiple times,
@@ -930,6 +1862,81 @@ fn parse_multi_exports_output(
     Ok(result)
 }
 
+/// Splits `output` from [`RSubprocess::get_package_exports_batch`] or
+/// [`RSubprocess::get_package_depends_batch`]'s R code into one raw text
+/// block per package, keyed by package name.
+///
+/// Each block starts right after a `__RLSP_PKG__:<name>\n` marker and runs
+/// up to (but not including) the next marker or an `__RLSP_ERR__:<name>:`
+/// line, which instead records that package's error message directly.
+fn split_batch_sections(output: &str, packages: &[&str]) -> std::collections::HashMap<String, Result<String>> {
+    let mut result = std::collections::HashMap::new();
+    let mut current_package: Option<String> = None;
+    let mut current_lines: Vec<String> = Vec::new();
+
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("__RLSP_PKG__:") {
+            if let Some(pkg) = current_package.take() {
+                result.insert(pkg, Ok(std::mem::take(&mut current_lines).join("\n")));
+            }
+            current_package = Some(rest.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("__RLSP_ERR__:") {
+            if let Some((pkg, msg)) = rest.split_once(':') {
+                result.insert(pkg.to_string(), Err(anyhow!(msg.trim().to_string())));
+            }
+            current_package = None;
+            current_lines.clear();
+        } else if current_package.is_some() {
+            current_lines.push(line.to_string());
+        }
+    }
+
+    if let Some(pkg) = current_package {
+        result.insert(pkg, Ok(current_lines.join("\n")));
+    }
+
+    // A package missing from the output entirely (e.g. the R call was cut
+    // short) still gets an entry, so callers can rely on every requested
+    // name being present in the returned map.
+    for pkg in packages {
+        result
+            .entry(pkg.to_string())
+            .or_insert_with(|| Err(anyhow!("No result received for package '{}'", pkg)));
+    }
+
+    result
+}
+
+/// Parse the output of [`RSubprocess::get_package_exports_batch`] into a
+/// per-package result map.
+fn parse_batch_exports_output(
+    output: &str,
+    packages: &[&str],
+) -> std::collections::HashMap<String, Result<Vec<String>>> {
+    split_batch_sections(output, packages)
+        .into_iter()
+        .map(|(pkg, section)| {
+            let exports = section.map(|text| parse_packages_output(&text));
+            (pkg, exports)
+        })
+        .collect()
+}
+
+/// Parse the output of [`RSubprocess::get_package_depends_batch`] into a
+/// per-package result map.
+fn parse_batch_depends_output(
+    output: &str,
+    packages: &[&str],
+) -> std::collections::HashMap<String, Result<Vec<String>>> {
+    split_batch_sections(output, packages)
+        .into_iter()
+        .map(|(pkg, section)| {
+            let depends = section.map(|text| parse_depends_field(&text));
+            (pkg, depends)
+        })
+        .collect()
+}
+
 /// Parse an R DESCRIPTION `Depends` field into its package names.
 ///
 /// This returns a Vec of package names in the same order they appear in `depends_str`.
@@ -971,6 +1978,262 @@ fn parse_depends_field(depends_str: &str) -> Vec<String> {
         .collect()
 }
 
+/// Finds the installed package name closest to `name` by case-insensitive
+/// Levenshtein distance, for a "did you mean ...?" hint on an unknown
+/// package query. Only suggests a candidate within distance `<= 3` and
+/// strictly shorter than that distance would make the query itself, so an
+/// unrelated package never gets offered as a "fix".
+fn suggest_closest_package<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let lower_name = name.to_lowercase();
+    candidates
+        .iter()
+        .map(|candidate| {
+            let distance = levenshtein_distance(&lower_name, &candidate.to_lowercase());
+            (candidate, distance)
+        })
+        .filter(|(_, distance)| *distance <= 3 && *distance < name.len())
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate.as_str())
+}
+
+/// Formats a `suggest_closest_package` match as the `"; did you mean
+/// \`...\`?"` suffix appended to an unknown-package error, or an empty
+/// string when nothing was close enough to suggest.
+fn did_you_mean_suffix(name: &str, candidates: &[String]) -> String {
+    match suggest_closest_package(name, candidates) {
+        Some(suggestion) => format!("; did you mean `{}`?", suggestion),
+        None => String::new(),
+    }
+}
+
+/// Every package name installed under `lib_paths`, plus `base_packages` -
+/// the [`StaticRProfile`] counterpart to an R subprocess's `installed.packages()`,
+/// used only to build "did you mean ...?" suggestions for an unknown package.
+fn installed_package_names_from_profile(profile: &StaticRProfile) -> Vec<String> {
+    let mut names = profile.base_packages.clone();
+    for lib_path in &profile.lib_paths {
+        let Ok(entries) = std::fs::read_dir(lib_path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Finds `name`'s installed directory among `lib_paths`, the same way
+/// `PackageLibrary::find_package_directory` does for its own lib_paths list -
+/// used by the [`StaticRProfile`] query paths below, which only have a bare
+/// path list to search rather than a running R to ask.
+fn find_package_dir(lib_paths: &[PathBuf], name: &str) -> Option<PathBuf> {
+    lib_paths
+        .iter()
+        .map(|lib| lib.join(name))
+        .find(|dir| dir.is_dir())
+}
+
+/// `get_package_exports` for an [`RSubprocess`] built from a [`StaticRProfile`]:
+/// parses `NAMESPACE` directly instead of calling `getNamespaceExports`.
+fn get_package_exports_from_static_profile(
+    profile: &StaticRProfile,
+    package: &str,
+) -> Result<Vec<String>> {
+    let dir = find_package_dir(&profile.lib_paths, package).ok_or_else(|| {
+        let suggestion =
+            did_you_mean_suffix(package, &installed_package_names_from_profile(profile));
+        anyhow!(
+            "Package '{}' not found in configured library paths{}",
+            package,
+            suggestion
+        )
+    })?;
+
+    let exports = crate::namespace_parser::parse_namespace_exports(&dir.join("NAMESPACE"))?;
+    // Pattern exports can't be expanded without loading the package in R.
+    Ok(exports
+        .into_iter()
+        .filter(|e| !e.starts_with("__PATTERN__:"))
+        .collect())
+}
+
+/// `get_package_depends` for an [`RSubprocess`] built from a [`StaticRProfile`]:
+/// parses the `Depends:` field out of `DESCRIPTION` directly instead of
+/// calling `packageDescription`.
+fn get_package_depends_from_static_profile(
+    profile: &StaticRProfile,
+    package: &str,
+) -> Result<Vec<String>> {
+    let dir = find_package_dir(&profile.lib_paths, package).ok_or_else(|| {
+        let suggestion =
+            did_you_mean_suffix(package, &installed_package_names_from_profile(profile));
+        anyhow!(
+            "Package '{}' not found in configured library paths{}",
+            package,
+            suggestion
+        )
+    })?;
+
+    crate::namespace_parser::parse_description_depends(&dir.join("DESCRIPTION"))
+}
+
+/// One package's edges in the dependency graph built by
+/// [`RSubprocess::resolve_load_order`]. See that method's docs for what
+/// `hard` and `optional` mean.
+#[derive(Debug, Clone, Default)]
+struct PackageEdges {
+    hard: Vec<String>,
+    optional: Vec<String>,
+}
+
+/// `package_edges` for an [`RSubprocess`] built from a [`StaticRProfile`]:
+/// parses `Depends`, `Imports`, `LinkingTo`, and `Suggests` out of
+/// `DESCRIPTION` directly instead of calling `packageDescription`.
+fn package_edges_from_static_profile(
+    profile: &StaticRProfile,
+    package: &str,
+) -> Result<PackageEdges> {
+    let dir = find_package_dir(&profile.lib_paths, package).ok_or_else(|| {
+        let suggestion =
+            did_you_mean_suffix(package, &installed_package_names_from_profile(profile));
+        anyhow!(
+            "Package '{}' not found in configured library paths{}",
+            package,
+            suggestion
+        )
+    })?;
+
+    let description_path = dir.join("DESCRIPTION");
+    let mut hard =
+        crate::namespace_parser::parse_description_field_names(&description_path, "Depends")?;
+    hard.extend(crate::namespace_parser::parse_description_field_names(
+        &description_path,
+        "Imports",
+    )?);
+    hard.extend(crate::namespace_parser::parse_description_field_names(
+        &description_path,
+        "LinkingTo",
+    )?);
+    let optional =
+        crate::namespace_parser::parse_description_field_names(&description_path, "Suggests")?;
+
+    Ok(PackageEdges { hard, optional })
+}
+
+/// Parses the `__RLSP_DEPENDS__:`/`__RLSP_IMPORTS__:`/`__RLSP_LINKINGTO__:`/
+/// `__RLSP_SUGGESTS__:`-tagged lines emitted by [`RSubprocess::package_edges`]'s
+/// R code into a [`PackageEdges`], folding `Depends`, `Imports`, and
+/// `LinkingTo` together as hard edges.
+fn parse_package_edges_output(output: &str) -> PackageEdges {
+    let mut hard = Vec::new();
+    let mut optional = Vec::new();
+
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("__RLSP_DEPENDS__:") {
+            hard.extend(parse_depends_field(rest));
+        } else if let Some(rest) = line.strip_prefix("__RLSP_IMPORTS__:") {
+            hard.extend(parse_depends_field(rest));
+        } else if let Some(rest) = line.strip_prefix("__RLSP_LINKINGTO__:") {
+            hard.extend(parse_depends_field(rest));
+        } else if let Some(rest) = line.strip_prefix("__RLSP_SUGGESTS__:") {
+            optional.extend(parse_depends_field(rest));
+        }
+    }
+
+    PackageEdges { hard, optional }
+}
+
+/// Topologically sorts `graph` (built by [`RSubprocess::resolve_load_order`])
+/// via depth-first post-order traversal: each package is appended only after
+/// everything it depends on, so it never precedes one of its own
+/// dependencies in the result. `include_suggests` controls whether
+/// `Suggests` edges are walked, matching the flag `resolve_load_order` was
+/// called with.
+///
+/// Returns an error naming the packages in a cycle if one is found, instead
+/// of looping forever.
+fn topological_load_order(
+    graph: &std::collections::HashMap<String, PackageEdges>,
+    include_suggests: bool,
+) -> Result<Vec<String>> {
+    #[derive(PartialEq)]
+    enum Mark {
+        InProgress,
+        Done,
+    }
+
+    fn visit(
+        name: &str,
+        graph: &std::collections::HashMap<String, PackageEdges>,
+        include_suggests: bool,
+        marks: &mut std::collections::HashMap<String, Mark>,
+        path: &mut Vec<String>,
+        out: &mut Vec<String>,
+    ) -> Result<()> {
+        match marks.get(name) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::InProgress) => {
+                let cycle_start = path.iter().position(|n| n == name).unwrap_or(0);
+                let mut cycle = path[cycle_start..].to_vec();
+                cycle.push(name.to_string());
+                return Err(anyhow!(
+                    "Dependency cycle detected: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+            None => {}
+        }
+
+        let Some(edges) = graph.get(name) else {
+            return Ok(());
+        };
+
+        marks.insert(name.to_string(), Mark::InProgress);
+        path.push(name.to_string());
+
+        let mut deps: Vec<&String> = edges.hard.iter().collect();
+        if include_suggests {
+            deps.extend(edges.optional.iter());
+        }
+        for dep in deps {
+            visit(dep, graph, include_suggests, marks, path, out)?;
+        }
+
+        path.pop();
+        marks.insert(name.to_string(), Mark::Done);
+        out.push(name.to_string());
+        Ok(())
+    }
+
+    let mut marks = std::collections::HashMap::new();
+    let mut path = Vec::new();
+    let mut out = Vec::new();
+
+    let mut names: Vec<&String> = graph.keys().collect();
+    names.sort();
+    for name in names {
+        visit(name, graph, include_suggests, &mut marks, &mut path, &mut out)?;
+    }
+
+    Ok(out)
+}
+
+/// Splits an `R_LIBS`/`R_LIBS_USER`-style value (colon-separated on Unix,
+/// semicolon-separated on Windows) into its component paths, dropping empty
+/// entries.
+fn parse_lib_path_list(value: &str) -> Vec<PathBuf> {
+    let sep = if cfg!(windows) { ';' } else { ':' };
+    value
+        .split(sep)
+        .filter(|entry| !entry.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
 /// Parse newline-separated R library paths into a vector of existing `PathBuf`s.
 ///
 /// Trims each line, ignores empty lines, converts each remaining line into a `PathBuf`,
@@ -1006,7 +2269,7 @@ fn parse_packages_output(output: &str) -> Vec<String> {
 /// - Be at least 2 characters long (or 1 character if it's a letter)
 ///
 /// This validation prevents malicious input from being executed as R code.
-fn is_valid_package_name(name: &str) -> bool {
+pub(crate) fn is_valid_package_name(name: &str) -> bool {
     if name.is_empty() {
         return false;
     }
@@ -1174,6 +2437,16 @@ pub fn get_fallback_base_packages() -> Vec<String> {
 pub fn get_fallback_lib_paths() -> Vec<PathBuf> {
     let mut paths = Vec::new();
 
+    // R_LIBS / R_LIBS_USER are colon-separated (semicolon on Windows) lists
+    // of additional library directories a user or project may have set
+    // directly; honor them before falling back to the hardcoded guesses
+    // below, so an explicitly configured library always wins.
+    for var in ["R_LIBS", "R_LIBS_USER"] {
+        if let Ok(value) = std::env::var(var) {
+            paths.extend(parse_lib_path_list(&value));
+        }
+    }
+
     #[cfg(target_os = "macos")]
     {
         // R.app framework library
@@ -1261,6 +2534,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_resolve_r_path_invalid_env_override_does_not_fall_back() {
+        // An invalid `RAVEN_R` must error rather than silently falling back
+        // to the (here, otherwise-discoverable) project config or PATH.
+        let previous = std::env::var("RAVEN_R").ok();
+        std::env::set_var("RAVEN_R", "/nonexistent/raven-r-override");
+
+        let resolved = RSubprocess::resolve_r_path(None);
+
+        match previous {
+            Some(v) => std::env::set_var("RAVEN_R", v),
+            None => std::env::remove_var("RAVEN_R"),
+        }
+
+        assert!(resolved.is_none());
+    }
+
     #[test]
     fn test_new_with_invalid_path_returns_none() {
         let invalid_path = PathBuf::from("/nonexistent/path/to/R");
@@ -1324,6 +2614,52 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_execute_r_code_reuses_persistent_session() {
+        // Skip if R is not available
+        let subprocess = match RSubprocess::new(None) {
+            Some(s) => s,
+            None => return,
+        };
+
+        let pid1 = subprocess
+            .execute_r_code("cat(Sys.getpid())")
+            .await
+            .unwrap();
+        let pid2 = subprocess
+            .execute_r_code("cat(Sys.getpid())")
+            .await
+            .unwrap();
+
+        // Two calls through the same RSubprocess should be served by the
+        // same long-lived R process, not a fresh one each time.
+        assert_eq!(pid1.trim(), pid2.trim());
+    }
+
+    #[tokio::test]
+    async fn test_execute_r_code_session_survives_r_level_error() {
+        // Skip if R is not available
+        let subprocess = match RSubprocess::new(None) {
+            Some(s) => s,
+            None => return,
+        };
+
+        let pid_before = subprocess
+            .execute_r_code("cat(Sys.getpid())")
+            .await
+            .unwrap();
+        let err = subprocess.execute_r_code("stop('boom')").await;
+        assert!(err.is_err());
+        let pid_after = subprocess
+            .execute_r_code("cat(Sys.getpid())")
+            .await
+            .unwrap();
+
+        // An uncaught R-level error shouldn't be treated as a dead session -
+        // the same process should keep serving subsequent queries.
+        assert_eq!(pid_before.trim(), pid_after.trim());
+    }
+
     #[test]
     fn test_parse_lib_paths_output_simple() {
         // Test parsing output with simple paths (one per line)
@@ -1357,6 +2693,64 @@ mod tests {
         assert!(paths.is_empty());
     }
 
+    #[test]
+    fn test_parse_lib_path_list_unix_separator() {
+        if cfg!(windows) {
+            return;
+        }
+        let paths = parse_lib_path_list("/home/user/R/library:/opt/R/library");
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/home/user/R/library"),
+                PathBuf::from("/opt/R/library"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_lib_path_list_empty_entries_dropped() {
+        let paths = parse_lib_path_list("");
+        assert!(paths.is_empty());
+    }
+
+    #[test]
+    fn test_r_binary_under_home_nonexistent() {
+        assert!(RSubprocess::r_binary_under_home("/no/such/r/home").is_none());
+    }
+
+    #[test]
+    fn test_parse_batch_exports_output_mixed_success_and_error() {
+        let output = "__RLSP_PKG__:stats\nlm\nglm\n\n__RLSP_ERR__:nosuchpkg:there is no package called 'nosuchpkg'\n";
+        let result = parse_batch_exports_output(output, &["stats", "nosuchpkg"]);
+
+        assert_eq!(
+            result.get("stats").unwrap().as_ref().unwrap(),
+            &vec!["lm".to_string(), "glm".to_string()]
+        );
+        assert!(result.get("nosuchpkg").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_parse_batch_exports_output_missing_package_is_an_error() {
+        let output = "__RLSP_PKG__:stats\nlm\n";
+        let result = parse_batch_exports_output(output, &["stats", "neverran"]);
+
+        assert!(result.get("stats").unwrap().is_ok());
+        assert!(result.get("neverran").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_parse_batch_depends_output_strips_r_pseudo_dependency() {
+        let output = "__RLSP_PKG__:dplyr\nR (>= 3.5), methods\n";
+        let result = parse_batch_depends_output(output, &["dplyr"]);
+
+        assert_eq!(
+            result.get("dplyr").unwrap().as_ref().unwrap(),
+            &vec!["methods".to_string()]
+        );
+    }
+
     #[tokio::test]
     async fn test_get_lib_paths_returns_paths() {
         // Skip if R is not available
@@ -2524,6 +3918,446 @@ mod tests {
             "sum should have a ... parameter"
         );
     }
+
+    /// Writes a minimal installed-package layout (`NAMESPACE` + `DESCRIPTION`)
+    /// for `name` under `lib_dir`, for exercising the static-profile query
+    /// paths without a real R installation.
+    fn write_fake_installed_package(lib_dir: &std::path::Path, name: &str) {
+        let pkg_dir = lib_dir.join(name);
+        std::fs::create_dir_all(&pkg_dir).unwrap();
+        std::fs::write(
+            pkg_dir.join("NAMESPACE"),
+            "export(mutate)\nexport(filter)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            pkg_dir.join("DESCRIPTION"),
+            format!(
+                "Package: {}\nDepends: R (>= 3.5.0), tibble\nImports: rlang\n",
+                name
+            ),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_static_profile_get_lib_paths_and_base_packages() {
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![PathBuf::from("/opt/r-libs")],
+            base_packages: vec!["base".to_string(), "stats".to_string()],
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        assert_eq!(
+            subprocess.get_lib_paths().await.unwrap(),
+            vec![PathBuf::from("/opt/r-libs")]
+        );
+        assert_eq!(
+            subprocess.get_base_packages().await.unwrap(),
+            vec!["base".to_string(), "stats".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_static_profile_get_package_exports_reads_namespace() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let exports = subprocess.get_package_exports("dplyr").await.unwrap();
+
+        assert!(exports.contains(&"mutate".to_string()));
+        assert!(exports.contains(&"filter".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_static_profile_get_package_depends_reads_description() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let depends = subprocess.get_package_depends("dplyr").await.unwrap();
+
+        // "R" is the version pseudo-dependency and must be filtered out.
+        assert_eq!(depends, vec!["tibble".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_static_profile_get_package_exports_missing_package_errors() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        assert!(subprocess
+            .get_package_exports("nosuchpackage")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_static_profile_never_spawns_r_process() {
+        // An RSubprocess built from a static profile never touches `r_path`,
+        // so an empty (invalid) path must not prevent queries from working.
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: Vec::new(),
+            base_packages: vec!["base".to_string()],
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        assert_eq!(
+            subprocess.get_base_packages().await.unwrap(),
+            vec!["base".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_suggest_closest_package_finds_typo() {
+        let candidates = vec!["dplyr".to_string(), "ggplot2".to_string()];
+        assert_eq!(suggest_closest_package("dpylr", &candidates), Some("dplyr"));
+    }
+
+    #[test]
+    fn test_suggest_closest_package_rejects_distant_match() {
+        let candidates = vec!["dplyr".to_string()];
+        assert_eq!(suggest_closest_package("ggplot2", &candidates), None);
+    }
+
+    #[test]
+    fn test_did_you_mean_suffix_empty_when_no_match() {
+        let candidates = vec!["dplyr".to_string()];
+        assert_eq!(did_you_mean_suffix("ggplot2", &candidates), "");
+    }
+
+    #[test]
+    fn test_did_you_mean_suffix_formats_suggestion() {
+        let candidates = vec!["dplyr".to_string()];
+        assert_eq!(
+            did_you_mean_suffix("dpylr", &candidates),
+            "; did you mean `dplyr`?"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_static_profile_unknown_package_suggests_close_match() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let err = subprocess
+            .get_package_exports("dpylr")
+            .await
+            .unwrap_err()
+            .to_string();
+
+        assert!(
+            err.contains("did you mean `dplyr`?"),
+            "error should suggest the close match: {err}"
+        );
+    }
+
+    /// Writes a fake installed package with a caller-supplied DESCRIPTION body,
+    /// for [`RSubprocess::resolve_load_order`] tests that need specific
+    /// Depends/Imports/LinkingTo/Suggests graphs rather than
+    /// [`write_fake_installed_package`]'s fixed dplyr-like fixture.
+    fn write_fake_package_with_description(lib_dir: &std::path::Path, name: &str, description: &str) {
+        let pkg_dir = lib_dir.join(name);
+        std::fs::create_dir_all(&pkg_dir).unwrap();
+        std::fs::write(pkg_dir.join("NAMESPACE"), "").unwrap();
+        std::fs::write(pkg_dir.join("DESCRIPTION"), description).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_load_order_topologically_sorts_hard_dependencies() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_package_with_description(
+            tmp.path(),
+            "top",
+            "Package: top\nDepends: mid\n",
+        );
+        write_fake_package_with_description(
+            tmp.path(),
+            "mid",
+            "Package: mid\nImports: bottom\n",
+        );
+        write_fake_package_with_description(tmp.path(), "bottom", "Package: bottom\n");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let order = subprocess
+            .resolve_load_order(&["top"], false)
+            .await
+            .unwrap();
+
+        let pos = |name: &str| order.iter().position(|n| n == name).unwrap();
+        assert!(pos("bottom") < pos("mid"), "order was {order:?}");
+        assert!(pos("mid") < pos("top"), "order was {order:?}");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_load_order_ignores_suggests_by_default() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_package_with_description(
+            tmp.path(),
+            "core",
+            "Package: core\nSuggests: optional\n",
+        );
+        write_fake_package_with_description(tmp.path(), "optional", "Package: optional\n");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let order = subprocess
+            .resolve_load_order(&["core"], false)
+            .await
+            .unwrap();
+        assert_eq!(order, vec!["core".to_string()]);
+
+        let order_with_suggests = subprocess
+            .resolve_load_order(&["core"], true)
+            .await
+            .unwrap();
+        assert!(order_with_suggests.contains(&"optional".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_load_order_detects_cycle() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_package_with_description(tmp.path(), "a", "Package: a\nDepends: b\n");
+        write_fake_package_with_description(tmp.path(), "b", "Package: b\nDepends: a\n");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let err = subprocess
+            .resolve_load_order(&["a"], false)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Dependency cycle detected"),
+            "error should report the cycle: {err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_load_order_drops_invalid_root_names() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_package_with_description(tmp.path(), "valid", "Package: valid\n");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let order = subprocess
+            .resolve_load_order(&["valid", "../invalid"], false)
+            .await
+            .unwrap();
+        assert_eq!(order, vec!["valid".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_get_package_exports_caches_until_package_dir_changes() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let first = subprocess.get_package_exports("dplyr").await.unwrap();
+        assert!(first.contains(&"mutate".to_string()));
+
+        // Rewrite NAMESPACE with the package directory untouched: the cached
+        // entry (keyed off the directory's mtime) should still be served.
+        std::fs::write(
+            tmp.path().join("dplyr").join("NAMESPACE"),
+            "export(select)\n",
+        )
+        .unwrap();
+        let cached = subprocess.get_package_exports("dplyr").await.unwrap();
+        assert_eq!(cached, first, "unchanged directory mtime should serve the cached entry");
+
+        // Bump the directory's mtime (a reinstall would do this) to force a refresh.
+        let now_plus_one = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        filetime_set_mtime(&tmp.path().join("dplyr"), now_plus_one);
+        let refreshed = subprocess.get_package_exports("dplyr").await.unwrap();
+        assert_eq!(refreshed, vec!["select".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_clear_cache_forces_fresh_lookup() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        subprocess.get_package_exports("dplyr").await.unwrap();
+        std::fs::write(
+            tmp.path().join("dplyr").join("NAMESPACE"),
+            "export(select)\n",
+        )
+        .unwrap();
+
+        subprocess.clear_cache().await;
+        let refreshed = subprocess.get_package_exports("dplyr").await.unwrap();
+        assert_eq!(refreshed, vec!["select".to_string()]);
+    }
+
+    /// Sets a directory's modification time without pulling in a `filetime`
+    /// dependency - `std::fs::File::set_times` is stable and sufficient here.
+    fn filetime_set_mtime(path: &std::path::Path, mtime: std::time::SystemTime) {
+        let file = std::fs::File::open(path).unwrap();
+        let times = std::fs::FileTimes::new().set_modified(mtime);
+        file.set_times(times).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_query_json_lib_paths_reports_kind_and_version() {
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![PathBuf::from("/opt/r-libs")],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let json = subprocess
+            .query_json(QueryRequest::LibPaths)
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["kind"], "lib-paths");
+        assert_eq!(value["r_version"], "4.3.1");
+        assert_eq!(value["lib_paths"], serde_json::json!(["/opt/r-libs"]));
+    }
+
+    #[tokio::test]
+    async fn test_query_json_package_exports_one_line_per_package() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+        write_fake_installed_package(tmp.path(), "tidyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let json = subprocess
+            .query_json(QueryRequest::PackageExports(vec![
+                "dplyr".to_string(),
+                "tidyr".to_string(),
+            ]))
+            .await
+            .unwrap();
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        for line in &lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["kind"], "package-exports");
+            assert!(value["exports"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("mutate")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_json_package_exports_omits_unresolvable_package() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let json = subprocess
+            .query_json(QueryRequest::PackageExports(vec![
+                "dplyr".to_string(),
+                "nosuchpackage".to_string(),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(json.lines().count(), 1);
+        assert!(json.contains("\"package\":\"dplyr\""));
+    }
+
+    #[tokio::test]
+    async fn test_query_json_package_depends_reports_kind() {
+        let tmp = tempfile::TempDir::new().expect("create temp lib dir");
+        write_fake_installed_package(tmp.path(), "dplyr");
+
+        let profile = StaticRProfile {
+            r_version: "4.3.1".to_string(),
+            lib_paths: vec![tmp.path().to_path_buf()],
+            base_packages: Vec::new(),
+        };
+        let subprocess = RSubprocess::from_static_profile(profile);
+
+        let json = subprocess
+            .query_json(QueryRequest::PackageDepends(vec!["dplyr".to_string()]))
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["kind"], "package-depends");
+        assert_eq!(value["depends"], serde_json::json!(["tibble"]));
+    }
 }
 
 #[cfg(test)]
@@ -7,11 +7,13 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
+use dashmap::DashMap;
 use ropey::Rope;
-use tower_lsp::lsp_types::TextDocumentContentChangeEvent;
+use tower_lsp::lsp_types::{MarkupKind, TextDocumentContentChangeEvent};
 /// Symbol provider configuration
 ///
 /// Controls behavior of document symbol and workspace symbol providers.
@@ -95,6 +97,72 @@ impl SymbolConfig {
     }
 }
 
+/// Hover provider configuration
+///
+/// Controls how much work `hover` does and how it renders the result,
+/// mirroring the tunable surface of rust-analyzer's `HoverConfig`: clients
+/// that don't advertise Markdown support, or that want fast, offline
+/// hovers, can dial individual pieces back instead of getting an
+/// all-or-nothing response.
+#[derive(Debug, Clone, Copy)]
+pub struct HoverConfig {
+    /// Whether the client has advertised support for `command:` URIs
+    /// inside Markdown hover content. Defaults to `false` so clients that
+    /// don't understand the convention get plain, unclickable text.
+    ///
+    /// There is no standard LSP client capability for command links in
+    /// hover Markdown, so support is detected from the non-standard
+    /// `experimental.commandLinks` flag some clients advertise in
+    /// `InitializeParams.capabilities.experimental` (the same convention
+    /// rust-analyzer uses for its `experimental/hoverActions`).
+    pub supports_command_links: bool,
+    /// Whether an unresolved roxygen cross-reference (`\link{}`, `[fn()]`)
+    /// to a base/library function should fall back to a `command:` link
+    /// opening R help, rather than being left as plain backtick-quoted text.
+    /// Only takes effect when `supports_command_links` is also `true`.
+    /// Defaults to `true`: most unresolved references are real base/package
+    /// functions worth linking, not typos.
+    pub link_unresolved_refs_to_help: bool,
+    /// Whether to render the roxygen documentation block (description,
+    /// `@param`, `@return`, etc.) under the signature. Defaults to `true`;
+    /// set to `false` for a terser hover that's just the code block and
+    /// source location.
+    pub documentation: bool,
+    /// Whether to resolve symbols across `source()`d files. Defaults to
+    /// `true`; set to `false` to restrict hover to the current file's own
+    /// local scope (useful for fast, single-file-only hovers).
+    pub cross_file: bool,
+    /// Whether to fall back to the R subprocess / help cache for package
+    /// exports and builtins with no local definition. Defaults to `true`;
+    /// set to `false` to skip that lookup entirely (e.g. when R isn't
+    /// available, or to keep hover fast and offline) and fall back to a
+    /// signature/name-only response instead.
+    pub help_fallback: bool,
+    /// Whether to render only the signature/definition code block, skipping
+    /// documentation, defaults, source links, and command-link actions.
+    /// Defaults to `false`.
+    pub signature_only: bool,
+    /// The `MarkupKind` hover content is rendered as. Defaults to
+    /// `Markdown`; set to `PlainText` for clients that don't advertise
+    /// Markdown support in hover (the content itself is unchanged — it's
+    /// still fenced code blocks and Markdown links — only the declared
+    /// `kind` differs).
+    pub markup: MarkupKind,
+}
+
+impl Default for HoverConfig {
+    fn default() -> Self {
+        Self {
+            supports_command_links: false,
+            link_unresolved_refs_to_help: true,
+            documentation: true,
+            cross_file: true,
+            help_fallback: true,
+            signature_only: false,
+            markup: MarkupKind::Markdown,
+        }
+    }
+}
 
 use tower_lsp::lsp_types::Url;
 use tree_sitter::Parser;
@@ -105,9 +173,10 @@ use crate::cross_file::revalidation::CrossFileDiagnosticsGate;
 use crate::cross_file::{
     ArtifactsCache, CrossFileActivityState, CrossFileConfig, CrossFileFileCache,
     CrossFileRevalidationState, CrossFileWorkspaceIndex, DependencyGraph, MetadataCache,
-    ParentSelectionCache,
+    ParentSelectionCache, PendingFetchQueue,
 };
 use crate::document_store::DocumentStore;
+use crate::indentation::IndentationConfig;
 use crate::package_library::PackageLibrary;
 use crate::workspace_index::WorkspaceIndex;
 
@@ -118,6 +187,11 @@ pub struct Document {
     pub loaded_packages: Vec<String>,
     pub version: Option<i32>,
     pub revision: u64,
+    /// Cheap FNV-1a hash of the document's current text, recomputed on every
+    /// edit. Used as the `self_hash` component of a [`crate::cross_file::ScopeFingerprint`]
+    /// so scope-resolution results can be cached by content rather than
+    /// recomputed from the AST on every request.
+    pub content_hash: u64,
 }
 
 impl Document {
@@ -125,17 +199,19 @@ impl Document {
         let contents = Rope::from_str(text);
         let tree = parse_r(&contents);
         let loaded_packages = extract_loaded_packages(&tree, text);
+        let content_hash = fnv1a_hash(text.as_bytes());
         Self {
             contents,
             tree,
             loaded_packages,
             version,
             revision: 0,
+            content_hash,
         }
     }
 
     pub fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
-        if let Some(range) = change.range {
+        let edit = if let Some(range) = change.range {
             let start_line = range.start.line as usize;
             let start_utf16_char = range.start.character as usize;
             let end_line = range.end.line as usize;
@@ -150,22 +226,44 @@ impl Document {
             let start_idx = self.contents.line_to_char(start_line) + start_char;
             let end_idx = self.contents.line_to_char(end_line) + end_char;
 
+            let start_byte = self.contents.char_to_byte(start_idx);
+            let old_end_byte = self.contents.char_to_byte(end_idx);
+            let start_position = char_idx_to_point(&self.contents, start_idx);
+            let old_end_position = char_idx_to_point(&self.contents, end_idx);
+
             self.contents.remove(start_idx..end_idx);
             self.contents.insert(start_idx, &change.text);
+
+            let new_end_byte = start_byte + change.text.len();
+            let new_end_position =
+                end_position_after_insert(start_position, &change.text);
+
+            Some(tree_sitter::InputEdit {
+                start_byte,
+                old_end_byte,
+                new_end_byte,
+                start_position,
+                old_end_position,
+                new_end_position,
+            })
         } else {
-            // Full document sync
+            // Full document sync; there's no previous-tree subtree to reuse.
             self.contents = Rope::from_str(&change.text);
-        }
+            None
+        };
 
         self.revision += 1;
-        self.tree = parse_r(&self.contents);
+        let old_tree = match (&mut self.tree, edit) {
+            (Some(tree), Some(edit)) => {
+                tree.edit(&edit);
+                Some(tree.clone())
+            }
+            _ => None,
+        };
+        self.tree = parse_r_incremental(&self.contents, old_tree.as_ref());
         let text = self.contents.to_string();
         self.loaded_packages = extract_loaded_packages(&self.tree, &text);
-    }
-
-    #[allow(dead_code)]
-    pub fn contents_hash(&self) -> u64 {
-        self.revision
+        self.content_hash = fnv1a_hash(text.as_bytes());
     }
 
     pub fn text(&self) -> String {
@@ -173,6 +271,21 @@ impl Document {
     }
 }
 
+/// FNV-1a over raw bytes. Not cryptographic, just a cheap, stable fingerprint
+/// for cache keys (mirroring Deno's `FastInsecureHasher`) - a full document
+/// reparse is far more expensive than hashing its bytes once per edit.
+fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
 fn utf16_offset_to_char_offset(line_text: &str, utf16_offset: usize) -> usize {
     let mut utf16_count = 0;
     let mut char_count = 0;
@@ -188,10 +301,50 @@ fn utf16_offset_to_char_offset(line_text: &str, utf16_offset: usize) -> usize {
 }
 
 fn parse_r(contents: &Rope) -> Option<Tree> {
+    parse_r_incremental(contents, None)
+}
+
+/// Parse `contents`, reusing `old_tree`'s unchanged subtrees when given.
+///
+/// `old_tree` must already have every edit applied via [`Tree::edit`] (see
+/// [`Document::apply_change`]), or tree-sitter's incremental reuse will be
+/// wrong - it diffs the edited old tree's byte ranges against the new text,
+/// not the text itself.
+fn parse_r_incremental(contents: &Rope, old_tree: Option<&Tree>) -> Option<Tree> {
     let mut parser = Parser::new();
     parser.set_language(&tree_sitter_r::LANGUAGE.into()).ok()?;
     let text = contents.to_string();
-    parser.parse(&text, None)
+    parser.parse(&text, old_tree)
+}
+
+/// Converts a char index into the `Rope` into a tree-sitter [`tree_sitter::Point`],
+/// whose `column` is a *byte* offset within the line, not a char or UTF-16
+/// offset.
+fn char_idx_to_point(contents: &Rope, char_idx: usize) -> tree_sitter::Point {
+    let line = contents.char_to_line(char_idx);
+    let line_start_byte = contents.line_to_byte(line);
+    let byte = contents.char_to_byte(char_idx);
+    tree_sitter::Point {
+        row: line,
+        column: byte - line_start_byte,
+    }
+}
+
+/// Computes the end [`tree_sitter::Point`] of inserting `text` at `start`.
+fn end_position_after_insert(start: tree_sitter::Point, text: &str) -> tree_sitter::Point {
+    let newline_count = text.matches('\n').count();
+    if newline_count == 0 {
+        tree_sitter::Point {
+            row: start.row,
+            column: start.column + text.len(),
+        }
+    } else {
+        let last_line_len = text.rsplit('\n').next().unwrap_or("").len();
+        tree_sitter::Point {
+            row: start.row + newline_count,
+            column: last_line_len,
+        }
+    }
 }
 
 fn extract_loaded_packages(tree: &Option<Tree>, text: &str) -> Vec<String> {
@@ -475,6 +628,26 @@ impl Library {
     }
 }
 
+/// A document handed back by [`WorldState::get_document_or_workspace`]: either
+/// a live `DashMap` guard into `documents`, or a plain borrow into the legacy
+/// `workspace_index`. Callers that only need `&Document` can ignore the
+/// distinction and deref straight through.
+pub enum DocumentRef<'a> {
+    Open(dashmap::mapref::one::Ref<'a, Url, Document>),
+    Indexed(&'a Document),
+}
+
+impl Deref for DocumentRef<'_> {
+    type Target = Document;
+
+    fn deref(&self) -> &Document {
+        match self {
+            DocumentRef::Open(doc) => doc,
+            DocumentRef::Indexed(doc) => doc,
+        }
+    }
+}
+
 /// Global LSP state
 pub struct WorldState {
     // Document management (new architecture)
@@ -482,7 +655,17 @@ pub struct WorldState {
     pub workspace_index_new: WorkspaceIndex,
 
     // Legacy fields (kept for migration compatibility)
-    pub documents: HashMap<Url, Document>,
+    //
+    // `documents` backs every hot, read-mostly request path (goto-definition,
+    // hover, undefined-variable scanning) and is consulted far more often than
+    // it's mutated, so it's a `DashMap` rather than a plain `HashMap`: readers
+    // on different files don't serialize behind each other or behind an
+    // in-progress diagnostics pass the way they would sharing one lock over
+    // the whole map. `cross_file_graph` gets the same treatment via its own
+    // `take_dirty()` flag (see `cross_file::dependency::DependencyGraph`)
+    // instead of a second concurrent map, since it's small and consulted as a
+    // whole rather than keyed by URI.
+    pub documents: DashMap<Url, Document>,
     pub workspace_index: HashMap<Url, Document>,
     pub workspace_imports: Vec<String>, // Symbols imported via workspace NAMESPACE
 
@@ -500,12 +683,25 @@ pub struct WorldState {
     pub help_cache: crate::help::HelpCache,
     pub cross_file_file_cache: CrossFileFileCache,
     pub diagnostics_gate: CrossFileDiagnosticsGate,
+    /// URIs that a cache-only content lookup couldn't serve, queued for the
+    /// background indexer to read and compute. Shared (not per-request) so
+    /// it accumulates across calls to [`Self::content_provider`]; see
+    /// [`crate::cross_file::pending_fetch::PendingFetchQueue`].
+    pub pending_fetch_queue: Arc<PendingFetchQueue>,
+    /// Resolved parameter-name lists for inlay hints, keyed by `(symbol, source_uri)`
+    /// (source_uri is `package:<name>` for package exports). Avoids re-parsing a
+    /// callee's signature on every viewport change.
+    pub signature_param_cache: Arc<RwLock<HashMap<(String, String), Vec<String>>>>,
 
     // Cross-file state
     pub cross_file_config: CrossFileConfig,
     /// Symbol provider configuration
     /// Controls document symbol and workspace symbol behavior
     pub symbol_config: SymbolConfig,
+    /// Hover provider configuration (command-link support, etc.)
+    pub hover_config: HoverConfig,
+    /// On-type formatting configuration (indent style, tab size default).
+    pub indentation_config: IndentationConfig,
     pub cross_file_meta: MetadataCache,
     pub cross_file_graph: DependencyGraph,
     pub cross_file_cache: ArtifactsCache,
@@ -515,6 +711,11 @@ pub struct WorldState {
     #[allow(dead_code)]
     pub cross_file_parent_cache: ParentSelectionCache,
     pub package_library_ready: bool,
+    /// Whether the client advertised `textDocument.definition.linkSupport`.
+    /// When true, `goto_definition` may return `GotoDefinitionResponse::Link`
+    /// (LocationLink, with origin/target selection ranges) instead of
+    /// `Scalar`.
+    pub definition_link_support: bool,
 }
 
 impl WorldState {
@@ -577,7 +778,7 @@ impl WorldState {
             workspace_index_new: WorkspaceIndex::new(Default::default()),
 
             // Legacy fields (kept for migration compatibility)
-            documents: HashMap::new(),
+            documents: DashMap::new(),
             workspace_index: HashMap::new(),
             workspace_imports: Vec::new(),
 
@@ -592,12 +793,23 @@ impl WorldState {
 
             // Caches
             help_cache: crate::help::HelpCache::new(),
-            cross_file_file_cache: CrossFileFileCache::new(),
+            cross_file_file_cache: if config.fs_permission_checks_enabled {
+                CrossFileFileCache::with_permission_checker(
+                    crate::cross_file::real_vfs(),
+                    crate::cross_file::default_permission_checker(),
+                )
+            } else {
+                CrossFileFileCache::new()
+            },
             diagnostics_gate: CrossFileDiagnosticsGate::new(),
+            pending_fetch_queue: Arc::new(PendingFetchQueue::new()),
+            signature_param_cache: Arc::new(RwLock::new(HashMap::new())),
 
             // Cross-file state
             cross_file_config: config,
             symbol_config: SymbolConfig::default(),
+            hover_config: HoverConfig::default(),
+            indentation_config: IndentationConfig::default(),
             cross_file_meta: MetadataCache::new(),
             cross_file_graph: DependencyGraph::new(),
             cross_file_cache: ArtifactsCache::new(),
@@ -606,6 +818,7 @@ impl WorldState {
             cross_file_workspace_index: CrossFileWorkspaceIndex::new(),
             cross_file_parent_cache: ParentSelectionCache::new(),
             package_library_ready: false,
+            definition_link_support: false,
         }
     }
 
@@ -628,6 +841,7 @@ impl WorldState {
             &self.documents,
             &self.workspace_index,
             &self.cross_file_workspace_index,
+            Arc::clone(&self.pending_fetch_queue),
         )
     }
 
@@ -640,15 +854,36 @@ impl WorldState {
     }
 
     pub fn apply_change(&mut self, uri: &Url, change: TextDocumentContentChangeEvent) {
-        if let Some(doc) = self.documents.get_mut(uri) {
+        if let Some(mut doc) = self.documents.get_mut(uri) {
             doc.apply_change(change);
         }
     }
 
-    pub fn get_document(&self, uri: &Url) -> Option<&Document> {
+    /// Look up an open document by URI.
+    ///
+    /// Returns a `DashMap` read guard rather than a plain reference; it
+    /// derefs to `&Document` for field access like a borrow would, but
+    /// don't hold it across another `documents` lookup for the same URI
+    /// (including indirectly, e.g. via [`Self::get_enriched_metadata`]) or
+    /// it'll deadlock against itself. If you already need to call
+    /// [`Self::get_enriched_metadata`] for this same URI, use
+    /// [`Self::get_enriched_metadata_with_document`] instead and pass the
+    /// guard you're holding.
+    pub fn get_document(&self, uri: &Url) -> Option<dashmap::mapref::one::Ref<'_, Url, Document>> {
         self.documents.get(uri)
     }
 
+    /// Look up a document, falling back to the legacy `workspace_index` for
+    /// closed files. The two maps hand back differently-shaped references
+    /// (a `DashMap` guard vs. a plain borrow), so this returns a small enum
+    /// rather than `Option<&Document>`; it derefs to `&Document` either way.
+    pub fn get_document_or_workspace(&self, uri: &Url) -> Option<DocumentRef<'_>> {
+        if let Some(doc) = self.get_document(uri) {
+            return Some(DocumentRef::Open(doc));
+        }
+        self.workspace_index.get(uri).map(DocumentRef::Indexed)
+    }
+
     /// Get enriched metadata for a URI, preferring already-enriched sources.
     ///
     /// Priority order:
@@ -657,16 +892,39 @@ impl WorldState {
     /// 3. Legacy cross_file_workspace_index
     /// 4. Legacy documents HashMap (re-extract metadata)
     /// 5. File cache (re-extract metadata)
+    ///
+    /// If you're already holding `uri`'s `Document` (from [`Self::get_document`]
+    /// or [`Self::get_document_or_workspace`]), call
+    /// [`Self::get_enriched_metadata_with_document`] instead - this re-locks
+    /// `documents` for step 4 and will deadlock against a guard you already
+    /// hold for the same URI.
     pub fn get_enriched_metadata(&self, uri: &Url) -> Option<crate::cross_file::CrossFileMetadata> {
+        self.get_enriched_metadata_with_document(uri, None)
+    }
+
+    /// Same as [`Self::get_enriched_metadata`], but for callers that already
+    /// hold `uri`'s `Document` (e.g. the guard from [`Self::get_document`] or
+    /// [`Self::get_document_or_workspace`]). Pass it as `current_doc` so step
+    /// 4 reuses it instead of re-locking `documents` for the same key, which
+    /// would deadlock against the guard already held.
+    pub fn get_enriched_metadata_with_document(
+        &self,
+        uri: &Url,
+        current_doc: Option<&Document>,
+    ) -> Option<crate::cross_file::CrossFileMetadata> {
         self.document_store
             .get_without_touch(uri)
             .map(|doc| doc.metadata.clone())
             .or_else(|| self.workspace_index_new.get_metadata(uri))
             .or_else(|| self.cross_file_workspace_index.get_metadata(uri))
             .or_else(|| {
-                self.documents
-                    .get(uri)
+                current_doc
                     .map(|doc| crate::cross_file::extract_metadata(&doc.text()))
+                    .or_else(|| {
+                        self.documents
+                            .get(uri)
+                            .map(|doc| crate::cross_file::extract_metadata(&doc.text()))
+                    })
             })
             .or_else(|| {
                 self.cross_file_file_cache
@@ -700,10 +958,19 @@ impl WorldState {
         imports: Vec<String>,
         cross_file_entries: HashMap<Url, crate::cross_file::workspace_index::IndexEntry>,
         new_index_entries: HashMap<Url, crate::workspace_index::IndexEntry>,
+        untrusted: Vec<Url>,
     ) {
         self.workspace_index = index;
         self.workspace_imports = imports;
 
+        // Route scan-time permission rejections through the same
+        // untrusted-tracking mechanism `CrossFileFileCache::read_and_cache`
+        // uses, so `collect_untrusted_file_diagnostics` surfaces these too
+        // instead of silently dropping the file.
+        for uri in untrusted {
+            self.cross_file_file_cache.mark_untrusted(&uri);
+        }
+
         // Populate cross-file workspace index (legacy)
         for (uri, entry) in cross_file_entries {
             log::info!(
@@ -781,6 +1048,9 @@ impl WorldState {
 /// - `Vec<String>` - Workspace imports from NAMESPACE
 /// - `HashMap<Url, crate::cross_file::workspace_index::IndexEntry>` - Cross-file entries (legacy)
 /// - `HashMap<Url, crate::workspace_index::IndexEntry>` - New unified WorkspaceIndex entries
+/// - `Vec<Url>` - Files skipped because `permission_checks_enabled` rejected them, so the
+///   caller can register them with [`crate::cross_file::CrossFileFileCache::mark_untrusted`]
+///   the same way the on-demand `read_and_cache` path does
 ///
 /// **Validates: Requirements 11.1, 11.2, 11.3, 11.4, 11.5**
 pub type WorkspaceScanResult = (
@@ -788,17 +1058,33 @@ pub type WorkspaceScanResult = (
     Vec<String>,
     HashMap<Url, crate::cross_file::workspace_index::IndexEntry>,
     HashMap<Url, crate::workspace_index::IndexEntry>,
+    Vec<Url>,
 );
 
-pub fn scan_workspace(folders: &[Url], max_chain_depth: usize) -> WorkspaceScanResult {
+pub fn scan_workspace(
+    folders: &[Url],
+    max_chain_depth: usize,
+    permission_checks_enabled: bool,
+) -> WorkspaceScanResult {
     let mut index = HashMap::new();
     let mut imports = Vec::new();
     let mut cross_file_entries = HashMap::new();
     let mut new_index_entries = HashMap::new();
+    let mut untrusted = Vec::new();
 
     // Get workspace root for path resolution
     let workspace_root = folders.first().cloned();
 
+    // Persistent store of previously-computed content/metadata/artifacts,
+    // keyed by a hash of each file's URL (see
+    // `crate::cross_file::disk_cache::DiskCache`). Lets a repeat scan of an
+    // unchanged tree skip re-extracting cross-file metadata and recomputing
+    // scope artifacts for every file.
+    let disk_cache = workspace_root
+        .as_ref()
+        .and_then(|root| root.to_file_path().ok())
+        .map(|root| crate::cross_file::disk_cache::DiskCache::new(root.join(".raven-cache/cross-file")));
+
     for folder in folders {
         log::info!("Scanning folder: {}", folder);
         if let Ok(path) = folder.to_file_path() {
@@ -807,6 +1093,9 @@ pub fn scan_workspace(folders: &[Url], max_chain_depth: usize) -> WorkspaceScanR
                 &mut index,
                 &mut cross_file_entries,
                 &mut new_index_entries,
+                disk_cache.as_ref(),
+                permission_checks_enabled,
+                &mut untrusted,
             );
 
             // Check for NAMESPACE file
@@ -885,12 +1174,13 @@ pub fn scan_workspace(folders: &[Url], max_chain_depth: usize) -> WorkspaceScanR
     }
 
     log::info!(
-        "Scanned {} workspace files ({} with cross-file metadata, {} new index entries)",
+        "Scanned {} workspace files ({} with cross-file metadata, {} new index entries, {} untrusted)",
         index.len(),
         cross_file_entries.len(),
-        new_index_entries.len()
+        new_index_entries.len(),
+        untrusted.len()
     );
-    (index, imports, cross_file_entries, new_index_entries)
+    (index, imports, cross_file_entries, new_index_entries, untrusted)
 }
 
 /// Directories to skip during workspace scanning.
@@ -922,6 +1212,9 @@ fn scan_directory(
     index: &mut HashMap<Url, Document>,
     cross_file_entries: &mut HashMap<Url, crate::cross_file::workspace_index::IndexEntry>,
     new_index_entries: &mut HashMap<Url, crate::workspace_index::IndexEntry>,
+    disk_cache: Option<&crate::cross_file::disk_cache::DiskCache>,
+    permission_checks_enabled: bool,
+    untrusted: &mut Vec<Url>,
 ) {
     let Ok(entries) = fs::read_dir(dir) else {
         return;
@@ -938,34 +1231,67 @@ fn scan_directory(
                     continue;
                 }
             }
-            scan_directory(&path, index, cross_file_entries, new_index_entries);
+            scan_directory(
+                &path,
+                index,
+                cross_file_entries,
+                new_index_entries,
+                disk_cache,
+                permission_checks_enabled,
+                untrusted,
+            );
         } else if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
             // Match both .R and .r extensions (case-insensitive)
             if ext.eq_ignore_ascii_case("r") {
                 if let Ok(text) = fs::read_to_string(&path) {
                     if let Ok(uri) = Url::from_file_path(&path) {
+                        if permission_checks_enabled
+                            && !crate::cross_file::default_permission_checker().is_trusted(&path)
+                        {
+                            log::warn!(
+                                "Skipping untrusted file during workspace scan: {}",
+                                uri
+                            );
+                            untrusted.push(uri);
+                            continue;
+                        }
+
                         log::trace!("Scanning file: {}", uri);
                         let doc = Document::new(&text, None);
 
                         // Also compute cross-file metadata and artifacts
                         if let Ok(metadata_result) = fs::metadata(&path) {
-                            let cross_file_meta = crate::cross_file::extract_metadata(&text);
-
-                            // Compute artifacts if we have a tree
-                            // Use compute_artifacts_with_metadata to include declared symbols from directives
-                            // **Validates: Requirements 5.1, 5.2, 5.3, 5.4** (Diagnostic suppression for declared symbols)
-                            let artifacts = if let Some(tree) = doc.tree.as_ref() {
-                                crate::cross_file::scope::compute_artifacts_with_metadata(&uri, tree, &text, Some(&cross_file_meta))
-                            } else {
-                                crate::cross_file::scope::ScopeArtifacts::default()
-                            };
-
                             let snapshot =
                                 crate::cross_file::file_cache::FileSnapshot::with_content_hash(
                                     &metadata_result,
                                     &text,
                                 );
 
+                            // Reuse a previous scan's metadata/artifacts when the disk
+                            // cache has a still-valid entry for this file, instead of
+                            // re-extracting and recomputing them from scratch.
+                            let cached = disk_cache.and_then(|cache| cache.get(&uri, &snapshot));
+                            let (cross_file_meta, artifacts) = if let Some((_, _, meta, artifacts)) = cached {
+                                (meta, artifacts)
+                            } else {
+                                let cross_file_meta = crate::cross_file::extract_metadata(&text);
+
+                                // Compute artifacts if we have a tree
+                                // Use compute_artifacts_with_metadata to include declared symbols from directives
+                                // **Validates: Requirements 5.1, 5.2, 5.3, 5.4** (Diagnostic suppression for declared symbols)
+                                let artifacts = if let Some(tree) = doc.tree.as_ref() {
+                                    crate::cross_file::scope::compute_artifacts_with_metadata(&uri, tree, &text, Some(&cross_file_meta))
+                                } else {
+                                    crate::cross_file::scope::ScopeArtifacts::default()
+                                };
+
+                                if let Some(cache) = disk_cache {
+                                    cache.put(&uri, &snapshot, &text, &cross_file_meta, &artifacts);
+                                }
+
+                                (cross_file_meta, artifacts)
+                            };
+
                             // Create legacy cross-file entry
                             cross_file_entries.insert(
                                 uri.clone(),
@@ -974,6 +1300,7 @@ fn scan_directory(
                                     metadata: cross_file_meta.clone(),
                                     artifacts: artifacts.clone(),
                                     indexed_at_version: 0, // Initial version; not modified by insert()
+                                    content: Some(text.clone()),
                                 },
                             );
 
@@ -1153,6 +1480,78 @@ mod tests {
         assert_eq!(doc.text(), "line1\nðŸŽ‰test");
     }
 
+    #[test]
+    fn test_document_apply_change_reparses_incrementally() {
+        let mut doc = Document::new("x <- 1\ny <- 2\n", None);
+        let full = parse_r(&doc.contents).unwrap().root_node().to_sexp();
+
+        doc.apply_change(TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position {
+                    line: 1,
+                    character: 5,
+                },
+                end: Position {
+                    line: 1,
+                    character: 6,
+                },
+            }),
+            range_length: None,
+            text: "3".to_string(),
+        });
+
+        // The incrementally-reparsed tree should describe the same source as
+        // a full reparse would, even though it was produced via `tree.edit`
+        // + `parser.parse(text, Some(&old_tree))`.
+        let incremental = doc.tree.as_ref().unwrap().root_node().to_sexp();
+        let full_after = parse_r(&doc.contents).unwrap().root_node().to_sexp();
+        assert_eq!(incremental, full_after);
+        assert_eq!(doc.text(), "x <- 1\ny <- 3\n");
+        assert_ne!(full, full_after);
+    }
+
+    #[test]
+    fn test_document_apply_change_full_sync_drops_old_tree() {
+        let mut doc = Document::new("x <- 1", None);
+        assert!(doc.tree.is_some());
+
+        // A full-document sync (no range) has no edit to apply to the old
+        // tree, so this must fall back to a from-scratch parse rather than
+        // handing tree-sitter a stale, unedited tree.
+        doc.apply_change(TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "y <- 2".to_string(),
+        });
+
+        assert_eq!(doc.text(), "y <- 2");
+        assert!(doc.tree.is_some());
+    }
+
+    #[test]
+    fn test_char_idx_to_point_multiline() {
+        let rope = Rope::from_str("abc\nde");
+        let point = char_idx_to_point(&rope, 5);
+        assert_eq!(point.row, 1);
+        assert_eq!(point.column, 1);
+    }
+
+    #[test]
+    fn test_end_position_after_insert_single_line() {
+        let start = tree_sitter::Point { row: 0, column: 2 };
+        let end = end_position_after_insert(start, "abc");
+        assert_eq!(end.row, 0);
+        assert_eq!(end.column, 5);
+    }
+
+    #[test]
+    fn test_end_position_after_insert_multiline() {
+        let start = tree_sitter::Point { row: 0, column: 2 };
+        let end = end_position_after_insert(start, "a\nbc");
+        assert_eq!(end.row, 1);
+        assert_eq!(end.column, 2);
+    }
+
     #[test]
     fn test_utf16_offset_to_char_offset_ascii() {
         let line = "hello";